@@ -16,6 +16,7 @@ BuildRequires:  systemd
 %{?systemd_requires}
 Requires:       xfsprogs
 Requires:       device-mapper-persistent-data
+Requires:       cryptsetup
 
 Conflicts: stratisd
 
@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate byteorder;
+extern crate libstratis;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use libstratis::engine::fuzz::fuzz_mda_header;
+
+// The first eight bytes are the size of the region, the rest the header.
+fuzz_target!(|data: &[u8]| {
+    if data.len() >= 8 {
+        fuzz_mda_header(LittleEndian::read_u64(&data[..8]), &data[8..]);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate libstratis;
+
+use libstratis::engine::fuzz::fuzz_pool_metadata;
+
+fuzz_target!(|data: &[u8]| {
+    fuzz_pool_metadata(data);
+});
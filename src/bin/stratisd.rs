@@ -24,11 +24,12 @@ use std::env;
 use std::fs::{File, OpenOptions};
 use std::io::{ErrorKind, Read, Write};
 use std::os::unix::io::AsRawFd;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::rc::Rc;
+use std::time::Instant;
 
-use chrono::Duration;
+use chrono::{Duration, Utc};
 use clap::{App, Arg, ArgMatches};
 use env_logger::Builder;
 use libc::pid_t;
@@ -44,12 +45,18 @@ use dbus::{Connection, WatchEvent};
 
 use devicemapper::Device;
 #[cfg(feature = "dbus_enabled")]
-use libstratis::dbus_api::{consts, prop_changed_dispatch};
+use libstratis::dbus_api::{
+    clock_skew_signal, consts, lifecycle_signal, option_to_tuple, pool_state_signal,
+    prop_changed_dispatch, remove_progress_signal, setup_progress_signal, state_changed_dispatch,
+    thin_meta_extended_signal, usage_threshold_signal, watermark_crossed_signal,
+};
 #[cfg(feature = "dbus_enabled")]
+use libstratis::engine::{BlockDevState, EngineEvent, EngineListener, MaybeDbusPath};
 use libstratis::engine::{
-    get_engine_listener_list_mut, EngineEvent, EngineListener, MaybeDbusPath,
+    get_engine_listener_list_mut, persist_statistics, probe_device, Engine, Hooks, SimEngine,
+    StratEngine, DEFAULT_HOOK_TIMEOUT_SECS, STATS_PATH,
 };
-use libstratis::engine::{Engine, SimEngine, StratEngine};
+use libstratis::jsonrpc::{JsonRpcServer, DEFAULT_SOCKET_PATH};
 use libstratis::stratis::buff_log;
 use libstratis::stratis::{StratisError, StratisResult, VERSION};
 
@@ -58,6 +65,11 @@ const STRATISD_PID_PATH: &str = "/var/run/stratisd.pid";
 /// Interval at which to have stratisd dump its state
 const DEFAULT_STATE_DUMP_MINUTES: i64 = 10;
 
+/// Interval at which to run the snapshot schedules and the housekeeping of
+/// the pools. A schedule takes its snapshot in the first run after its hour
+/// or day begins.
+const SNAPSHOT_SCHEDULE_MINUTES: i64 = 1;
+
 /// Number of minutes to buffer log entries.
 const DEFAULT_LOG_HOLD_MINUTES: i64 = 30;
 
@@ -124,6 +136,15 @@ fn handle_udev_event(event: &libudev::Event) -> Option<(Device, PathBuf)> {
     None
 }
 
+/// Given a udev event check to see if it's a remove and if it is return the
+/// devicemapper::Device that was removed.
+fn handle_udev_remove_event(event: &libudev::Event) -> Option<Device> {
+    if event.event_type() == libudev::EventType::Remove {
+        return event.device().devnum().map(Device::from);
+    }
+    None
+}
+
 /// To ensure only one instance of stratisd runs at a time, acquire an
 /// exclusive lock. Return an error if lock attempt fails.
 fn trylock_pid_file() -> StratisResult<File> {
@@ -183,20 +204,36 @@ impl EventHandler {
 impl EngineListener for EventHandler {
     fn notify(&self, event: &EngineEvent) {
         match *event {
-            EngineEvent::BlockdevStateChanged { dbus_path, state } => {
+            EngineEvent::BlockdevStateChanged {
+                dbus_path,
+                uuid,
+                state,
+                ..
+            } => {
                 if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
-                    prop_changed_dispatch(
+                    state_changed_dispatch(
                         &self.dbus_conn.borrow(),
                         consts::BLOCKDEV_STATE_PROP,
+                        consts::BLOCKDEV_STATE_NAME_PROP,
                         state.to_dbus_value(),
+                        state,
                         &dbus_path,
                     ).unwrap_or_else(|()| {
                         error!(
                             "BlockdevStateChanged: {} state: {} failed to send dbus update.",
-                            dbus_path,
-                            state.to_dbus_value(),
+                            dbus_path, state,
                         );
                     });
+                    if state == BlockDevState::Bad || state == BlockDevState::Missing {
+                        let signal =
+                            lifecycle_signal(consts::DEVICE_FAILED_SIGNAL, dbus_path, uuid);
+                        if self.dbus_conn.borrow().send(signal).is_err() {
+                            error!(
+                                "BlockdevStateChanged: {} failed to send DeviceFailed signal.",
+                                dbus_path
+                            );
+                        }
+                    }
                 }
             }
             EngineEvent::FilesystemRenamed {
@@ -218,22 +255,53 @@ impl EngineListener for EventHandler {
                     });
                 }
             }
-            EngineEvent::PoolExtendStateChanged { dbus_path, state } => {
+            EngineEvent::PoolDaysUntilFullChanged {
+                dbus_path,
+                days_until_full,
+            } => {
                 if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
                     prop_changed_dispatch(
+                        &self.dbus_conn.borrow(),
+                        consts::POOL_DAYS_UNTIL_FULL_PROP,
+                        option_to_tuple(days_until_full, 0),
+                        &dbus_path,
+                    ).unwrap_or_else(|()| {
+                        error!(
+                            "PoolDaysUntilFullChanged: {} days: {:?} failed to send dbus update.",
+                            dbus_path, days_until_full,
+                        );
+                    });
+                }
+            }
+            EngineEvent::PoolExtendStateChanged { dbus_path, state, .. } => {
+                if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
+                    state_changed_dispatch(
                         &self.dbus_conn.borrow(),
                         consts::POOL_EXTEND_STATE_PROP,
+                        consts::POOL_EXTEND_STATE_NAME_PROP,
                         state.to_dbus_value(),
+                        state,
                         &dbus_path,
                     ).unwrap_or_else(|()| {
                         error!(
                             "PoolExtendStateChanged: {} state: {} failed to send dbus update.",
-                            dbus_path,
-                            state.to_dbus_value(),
+                            dbus_path, state,
                         );
                     });
                 }
             }
+            EngineEvent::PoolRemoveProgress {
+                dbus_path,
+                moved,
+                total,
+            } => {
+                if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
+                    let signal = remove_progress_signal(dbus_path, moved, total);
+                    if self.dbus_conn.borrow().send(signal).is_err() {
+                        error!("PoolRemoveProgress: {} failed to send signal.", dbus_path);
+                    }
+                }
+            }
             EngineEvent::PoolRenamed {
                 dbus_path,
                 from,
@@ -253,36 +321,121 @@ impl EngineListener for EventHandler {
                     });
                 }
             }
-            EngineEvent::PoolSpaceStateChanged { dbus_path, state } => {
+            EngineEvent::PoolSpaceStateChanged { dbus_path, state, .. } => {
                 if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
-                    prop_changed_dispatch(
+                    state_changed_dispatch(
                         &self.dbus_conn.borrow(),
                         consts::POOL_SPACE_STATE_PROP,
+                        consts::POOL_SPACE_STATE_NAME_PROP,
                         state.to_dbus_value(),
+                        state,
                         &dbus_path,
                     ).unwrap_or_else(|()| {
                         error!(
                             "PoolSpaceStateChanged: {} state: {} failed to send dbus update.",
-                            dbus_path,
-                            state.to_dbus_value(),
+                            dbus_path, state,
                         );
                     });
                 }
             }
-            EngineEvent::PoolStateChanged { dbus_path, state } => {
+            EngineEvent::PoolStateChanged { dbus_path, state, .. } => {
                 if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
-                    prop_changed_dispatch(
+                    state_changed_dispatch(
                         &self.dbus_conn.borrow(),
                         consts::POOL_STATE_PROP,
+                        consts::POOL_STATE_NAME_PROP,
                         state.to_dbus_value(),
+                        state,
                         &dbus_path,
                     ).unwrap_or_else(|()| {
                         error!(
                             "PoolStateChanged: {} state: {} failed to send dbus update.",
-                            dbus_path,
-                            state.to_dbus_value(),
+                            dbus_path, state,
                         );
                     });
+                    let signal = pool_state_signal(dbus_path, state.to_dbus_value(), state);
+                    if self.dbus_conn.borrow().send(signal).is_err() {
+                        error!(
+                            "PoolStateChanged: {} failed to send PoolStateChanged signal.",
+                            dbus_path
+                        );
+                    }
+                }
+            }
+            EngineEvent::PoolMetadataClockSkew {
+                dbus_path,
+                proposed,
+                newest,
+            } => {
+                if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
+                    let signal = clock_skew_signal(dbus_path, proposed, newest);
+                    if self.dbus_conn.borrow().send(signal).is_err() {
+                        error!("PoolMetadataClockSkew: {} failed to send signal.", dbus_path);
+                    }
+                }
+            }
+            EngineEvent::PoolThinMetaExtended {
+                dbus_path,
+                succeeded,
+                total,
+                reason,
+            } => {
+                if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
+                    let signal = thin_meta_extended_signal(dbus_path, succeeded, total, reason);
+                    if self.dbus_conn.borrow().send(signal).is_err() {
+                        error!("PoolThinMetaExtended: {} failed to send signal.", dbus_path);
+                    }
+                }
+            }
+            EngineEvent::PoolUsageThresholdCrossed {
+                dbus_path,
+                threshold,
+                rising,
+                used,
+                total,
+            } => {
+                if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
+                    let signal = usage_threshold_signal(dbus_path, threshold, rising, used, total);
+                    if self.dbus_conn.borrow().send(signal).is_err() {
+                        error!(
+                            "PoolUsageThresholdCrossed: {} threshold: {}% failed to send signal.",
+                            dbus_path, threshold,
+                        );
+                    }
+                }
+            }
+            EngineEvent::PoolWatermarkCrossed {
+                dbus_path,
+                watermark,
+                percent,
+                rising,
+                used,
+                total,
+            } => {
+                if let MaybeDbusPath(Some(ref dbus_path)) = *dbus_path {
+                    let signal = watermark_crossed_signal(
+                        dbus_path, watermark, percent, rising, used, total,
+                    );
+                    if self.dbus_conn.borrow().send(signal).is_err() {
+                        error!(
+                            "PoolWatermarkCrossed: {} {} watermark: {}% failed to send signal.",
+                            dbus_path, watermark, percent,
+                        );
+                    }
+                }
+            }
+            EngineEvent::SetupProgress {
+                name,
+                step,
+                total,
+                stage,
+            } => {
+                let signal = setup_progress_signal(name, step, total, stage);
+                if self.dbus_conn.borrow().send(signal).is_err() {
+                    error!(
+                        "SetupProgress: {} step: {}/{} failed to send signal.",
+                        name, step, total,
+                    );
                 }
             }
         }
@@ -297,6 +450,8 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
     // Ensure that the debug log is output when we leave this function.
     let _guard = buff_log.to_guard();
 
+    let started = Instant::now();
+
     // Even if dbus is enabled at compile time, it may not be available at all times depending
     // on the environment we are running in.
     #[cfg(feature = "dbus_enabled")]
@@ -312,15 +467,47 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
     monitor.match_subsystem_devtype("block", "disk")?;
     let mut udev = monitor.listen()?;
 
+    // Register the hooks before initializing the engine, so that they are
+    // run on events that occur while the engine sets up pools.
+    if let Some(dir) = matches.value_of("hooks_dir") {
+        let timeout = matches
+            .value_of("hook_timeout")
+            .map_or(DEFAULT_HOOK_TIMEOUT_SECS, |seconds| {
+                seconds.parse().expect("validated when the arguments were parsed")
+            });
+        let hooks = Hooks::new(PathBuf::from(dir), std::time::Duration::from_secs(timeout))?;
+        get_engine_listener_list_mut().register_listener(Box::new(hooks));
+    }
+
     let engine: Rc<RefCell<Engine>> = {
         if matches.is_present("sim") {
             info!("Using SimEngine");
             Rc::new(RefCell::new(SimEngine::default()))
         } else {
             info!("Using StratEngine");
-            Rc::new(RefCell::new(StratEngine::initialize()?))
+            // Statistics are counted whether or not they can be persisted.
+            if let Err(err) = persist_statistics(Path::new(STATS_PATH)) {
+                warn!("unable to persist statistics to {}, reason: {}", STATS_PATH, err);
+            }
+            let mut engine = StratEngine::initialize()?;
+            engine.set_tail_sigblocks(matches.is_present("tail_sigblock"));
+            engine.set_spread_metadata(matches.is_present("spread_metadata"));
+            engine.set_metadata_time_fallback(matches.is_present("metadata_time_fallback"));
+            if let Some(seconds) = matches.value_of("metadata_write_interval") {
+                engine.set_metadata_write_interval(
+                    seconds.parse().expect("validated when the arguments were parsed"),
+                );
+            }
+            if let Some(seconds) = matches.value_of("undo_destroy_window") {
+                engine.set_undo_destroy_window(
+                    seconds.parse().expect("validated when the arguments were parsed"),
+                );
+            }
+            Rc::new(RefCell::new(engine))
         }
     };
+    let startup = started.elapsed();
+    info!("Engine set up in {:?}", startup);
 
     /*
     The file descriptor array indexes are:
@@ -328,15 +515,19 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
     0   == Always udev fd index
     1   == SIGNAL FD index
     2   == TIMER FD for periodic dump index
-    2   == engine index if eventable
-    3/4 == Start of dbus client file descriptor(s)
-            * 3 if engine is not eventable
-            * else 4
+    3   == TIMER FD for snapshot schedules and housekeeping index
+    4   == JSON-RPC socket index, -1 (never ready) if not listening
+    5   == engine index if eventable
+    5/6 == Start of dbus client file descriptor(s)
+            * 5 if engine is not eventable
+            * else 6
     */
     const FD_INDEX_UDEV: usize = 0;
     const FD_INDEX_SIGNALFD: usize = 1;
     const FD_INDEX_DUMP_TIMERFD: usize = 2;
-    const FD_INDEX_ENGINE: usize = 3;
+    const FD_INDEX_SCHEDULE_TIMERFD: usize = 3;
+    const FD_INDEX_JSONRPC: usize = 4;
+    const FD_INDEX_ENGINE: usize = 5;
 
     /*
     fds is a Vec of libc::pollfd structs. Ideally, it would be possible
@@ -390,6 +581,51 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
         events: libc::POLLIN,
     });
 
+    let mut schedule_tfd = TimerFd::new()?;
+    let schedule_interval = Duration::minutes(SNAPSHOT_SCHEDULE_MINUTES)
+        .to_std()
+        .expect("std::Duration can represent positive values");
+    schedule_tfd.set_state(
+        TimerState::Periodic {
+            current: schedule_interval,
+            interval: schedule_interval,
+        },
+        SetTimeFlags::Default,
+    );
+
+    fds.push(libc::pollfd {
+        fd: schedule_tfd.as_raw_fd(),
+        revents: 0,
+        events: libc::POLLIN,
+    });
+
+    let jsonrpc_server = if matches.is_present("jsonrpc") {
+        let path = Path::new(
+            matches
+                .value_of("jsonrpc_socket")
+                .unwrap_or(DEFAULT_SOCKET_PATH),
+        );
+        let server = JsonRpcServer::bind(path)?;
+        info!("JSON-RPC API is listening on {}", path.display());
+        Some(server)
+    } else {
+        None
+    };
+
+    // poll() ignores a negative fd, so the index is kept when not listening.
+    fds.push(libc::pollfd {
+        fd: jsonrpc_server.as_ref().map_or(-1, |server| server.as_raw_fd()),
+        revents: 0,
+        events: libc::POLLIN,
+    });
+
+    #[cfg(feature = "dbus_enabled")]
+    let dbus_budget = matches.value_of("dbus_budget").map(|seconds| {
+        std::time::Duration::from_secs(
+            seconds.parse().expect("validated when the arguments were parsed"),
+        )
+    });
+
     let eventable = engine.borrow().get_eventable();
 
     if let Some(ref evt) = eventable {
@@ -413,6 +649,18 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
         // Process any udev block events
         if fds[FD_INDEX_UDEV].revents != 0 {
             while let Some(event) = udev.receive_event() {
+                // Mark a removed blockdev missing now, rather than when I/O
+                // to it or a metadata save fails. The state changes are
+                // signalled on the D-Bus through the engine's listeners.
+                if let Some(device) = handle_udev_remove_event(&event) {
+                    if let Some((pool_uuid, dev_uuid)) = engine.borrow_mut().block_removed(device)
+                    {
+                        error!(
+                            "blockdev {} of pool {} was removed from the system",
+                            dev_uuid, pool_uuid
+                        );
+                    }
+                }
                 if let Some((device, devnode)) = handle_udev_event(&event) {
                     // If block evaluate returns an error we are going to ignore it as
                     // there is nothing we can do for a device we are getting errors with.
@@ -487,6 +735,64 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
             log_engine_state(&*engine.borrow());
         }
 
+        // Run the snapshot schedules. If the D-Bus is not yet available, the
+        // snapshots taken get their objects when their pool is registered.
+        // Then do the pools' housekeeping.
+        if fds[FD_INDEX_SCHEDULE_TIMERFD].revents != 0 {
+            schedule_tfd.read(); // clear the event
+            let now = Utc::now();
+
+            #[cfg(not(feature = "dbus_enabled"))]
+            engine.borrow_mut().run_snapshot_schedules(now);
+
+            #[cfg(feature = "dbus_enabled")]
+            {
+                if let Some(ref mut handle) = dbus_handle {
+                    if let Err(err) = libstratis::dbus_api::run_snapshot_schedules(
+                        &handle.connection.borrow(),
+                        &mut handle.tree,
+                        &handle.context,
+                        now,
+                    ) {
+                        log_engine_state(&*engine.borrow());
+                        print_err(&From::from(err));
+                    }
+                } else {
+                    engine.borrow_mut().run_snapshot_schedules(now);
+                }
+            }
+
+            engine.borrow_mut().run_housekeeping();
+        }
+
+        // Answer the JSON-RPC clients. The pools and filesystems that they
+        // created get their D-Bus objects now, if the D-Bus is available,
+        // or else when their pool is registered.
+        if fds[FD_INDEX_JSONRPC].revents != 0 {
+            if let Some(ref server) = jsonrpc_server {
+                #[cfg(not(feature = "dbus_enabled"))]
+                server.handle_clients(&mut *engine.borrow_mut());
+
+                #[cfg(feature = "dbus_enabled")]
+                {
+                    let handled = server.handle_clients(&mut *engine.borrow_mut());
+                    if let Some(ref mut handle) = dbus_handle {
+                        if handled {
+                            if let Err(err) = libstratis::dbus_api::register_new_objects(
+                                &handle.connection.borrow(),
+                                &mut handle.tree,
+                                &handle.context,
+                                &handle.path,
+                            ) {
+                                log_engine_state(&*engine.borrow());
+                                print_err(&From::from(err));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
         // Handle engine events, if the engine is eventable
         if let Some(ref evt) = eventable {
             if fds[FD_INDEX_ENGINE].revents != 0 {
@@ -511,7 +817,7 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
                     {
                         if let Err(r) = libstratis::dbus_api::handle(
                             &handle.connection.borrow(),
-                            &item,
+                            item,
                             &mut handle.tree,
                             &handle.context,
                         ) {
@@ -532,7 +838,12 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
                         .iter()
                         .map(|w| w.to_pollfd()),
                 );
-            } else if let Ok(mut handle) = libstratis::dbus_api::connect(Rc::clone(&engine)) {
+            } else if let Ok(mut handle) = libstratis::dbus_api::connect(
+                Rc::clone(&engine),
+                dbus_budget,
+                started,
+                startup,
+            ) {
                 info!("DBUS API is now available");
                 let event_handler = Box::new(EventHandler::new(Rc::clone(&handle.connection)));
                 get_engine_listener_list_mut().register_listener(event_handler);
@@ -563,8 +874,16 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
 
         // If dbus support is compiled in and dbus isn't available we will set timeout to
         // 1 second so that we periodically check to see if we can bring it up.
+        // If calls put off as jobs wait to be handled, do not wait at all, so
+        // that a job is handled only once there is nothing else to do.
         #[cfg(feature = "dbus_enabled")]
-        let poll_timeout = dbus_handle.as_ref().map_or(1000, |_| -1);
+        let poll_timeout = dbus_handle.as_ref().map_or(1000, |handle| {
+            if libstratis::dbus_api::jobs_queued(&handle.context) {
+                0
+            } else {
+                -1
+            }
+        });
         // Default timeout is infinite
         #[cfg(not(feature = "dbus_enabled"))]
         let poll_timeout = -1;
@@ -581,6 +900,59 @@ fn run(matches: &ArgMatches, buff_log: &buff_log::Handle<env_logger::Logger>) ->
                 poll_timeout
             )));
         }
+
+        // Nothing else to do, so handle a job, if any waits.
+        #[cfg(feature = "dbus_enabled")]
+        {
+            if r == 0 {
+                if let Some(ref mut handle) = dbus_handle {
+                    if let Err(err) = libstratis::dbus_api::run_job(
+                        &handle.connection.borrow(),
+                        &mut handle.tree,
+                        &handle.context,
+                    ) {
+                        log_engine_state(&*engine.borrow());
+                        print_err(&From::from(err));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reconstruct a pool from a metadata backup file onto fresh devices, and
+/// exit without serving any requests. The reconstructed pool is left set up.
+fn reconstruct(
+    matches: &ArgMatches,
+    buff_log: &buff_log::Handle<env_logger::Logger>,
+) -> StratisResult<()> {
+    let _guard = buff_log.to_guard();
+
+    let backup_path = matches
+        .value_of("reconstruct")
+        .map(Path::new)
+        .expect("reconstruct mode was selected");
+    let devices = matches
+        .values_of("device")
+        .map_or_else(Vec::new, |values| values.map(Path::new).collect());
+
+    let mut engine = StratEngine::initialize()?;
+    let pool_uuid = engine.reconstruct_pool_from_file(backup_path, &devices)?;
+    println!("{}", pool_uuid);
+    Ok(())
+}
+
+/// Print the properties of the device at devnode, one KEY=VALUE pair per
+/// line, if it is a Stratis device. Return whether it is.
+fn probe(devnode: &Path) -> StratisResult<bool> {
+    match probe_device(devnode)? {
+        Some(properties) => {
+            for (key, value) in properties {
+                println!("{}={}", key, value);
+            }
+            Ok(true)
+        }
+        None => Ok(false),
     }
 }
 
@@ -598,8 +970,141 @@ fn main() {
                 .long("sim")
                 .help("Use simulator engine"),
         )
+        .arg(
+            Arg::with_name("tail_sigblock")
+                .long("tail-sigblock")
+                .help("Keep a copy of the signature block at the end of new pools' devices"),
+        )
+        .arg(
+            Arg::with_name("spread_metadata")
+                .long("spread-metadata")
+                .help(
+                    "Place the backup copies of new pools' metadata far enough from the \
+                     primary copies that a flash erase block does not hold both",
+                ),
+        )
+        .arg(
+            Arg::with_name("metadata_time_fallback")
+                .long("metadata-time-fallback")
+                .help(
+                    "Save pool metadata even if the clock is behind the time of metadata \
+                     already written, ordering saves by their sequence",
+                ),
+        )
+        .arg(
+            Arg::with_name("metadata_write_interval")
+                .long("metadata-write-interval")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .conflicts_with("sim")
+                .validator(|v| v.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .help(
+                    "Write metadata in the background to any one blockdev no more than \
+                     once every SECONDS seconds, holding back writes that come too soon",
+                ),
+        )
+        .arg(
+            Arg::with_name("undo_destroy_window")
+                .long("undo-destroy-window")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .conflicts_with("sim")
+                .validator(|v| v.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .help(
+                    "Allow the destruction of a pool whose signatures were kept to be \
+                     undone for SECONDS seconds; the default is one day",
+                ),
+        )
+        .arg(
+            Arg::with_name("dbus_budget")
+                .long("dbus-budget")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .validator(|v| v.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .help(
+                    "Put off as a job any long-running D-Bus method whose last call took \
+                     longer than SECONDS seconds, answering its caller at once",
+                ),
+        )
+        .arg(
+            Arg::with_name("jsonrpc")
+                .long("jsonrpc")
+                .help("Serve the JSON-RPC control API on a Unix socket alongside the D-Bus"),
+        )
+        .arg(
+            Arg::with_name("jsonrpc_socket")
+                .long("jsonrpc-socket")
+                .value_name("PATH")
+                .takes_value(true)
+                .requires("jsonrpc")
+                .help("Listen for JSON-RPC clients on a socket at PATH, not at the default"),
+        )
+        .arg(
+            Arg::with_name("hooks_dir")
+                .long("hooks-dir")
+                .value_name("DIR")
+                .takes_value(true)
+                .help(
+                    "Run every executable in DIR when a pool runs short of space, a \
+                     blockdev fails, or a pool is degraded",
+                ),
+        )
+        .arg(
+            Arg::with_name("hook_timeout")
+                .long("hook-timeout")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .requires("hooks_dir")
+                .validator(|v| v.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("Kill any hook that has not exited within SECONDS seconds"),
+        )
+        .arg(
+            Arg::with_name("reconstruct")
+                .long("reconstruct")
+                .value_name("BACKUP")
+                .takes_value(true)
+                .conflicts_with("sim")
+                .requires("device")
+                .help(
+                    "Reconstruct the pool backed up in BACKUP onto fresh devices, \
+                     then exit",
+                ),
+        )
+        .arg(
+            Arg::with_name("device")
+                .long("device")
+                .value_name("DEVICE")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("reconstruct")
+                .help("A fresh device to reconstruct onto, in the order recorded in the backup"),
+        )
+        .arg(
+            Arg::with_name("probe")
+                .long("probe")
+                .value_name("DEVICE")
+                .takes_value(true)
+                .conflicts_with_all(&["sim", "reconstruct"])
+                .help(
+                    "Print udev properties of DEVICE if it is a Stratis device, exiting \
+                     with status 2 if it is not, then exit",
+                ),
+        )
         .get_matches();
 
+    // Probing only reads the device, so it may be done while a daemon runs.
+    if let Some(devnode) = matches.value_of("probe") {
+        match probe(Path::new(devnode)) {
+            Ok(true) => exit(0),
+            Ok(false) => exit(2),
+            Err(err) => {
+                print_err(&err);
+                exit(1);
+            }
+        }
+    }
+
     // Using a let-expression here so that the scope of the lock file
     // is the rest of the block.
     let lock_file = trylock_pid_file();
@@ -609,7 +1114,11 @@ fn main() {
             Err(err) => Err(err),
             Ok(_) => {
                 let log_handle = initialize_log(matches.is_present("debug"));
-                run(&matches, &log_handle)
+                if matches.is_present("reconstruct") {
+                    reconstruct(&matches, &log_handle)
+                } else {
+                    run(&matches, &log_handle)
+                }
             }
         }
     };
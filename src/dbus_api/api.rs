@@ -3,55 +3,1345 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::cell::RefCell;
-use std::path::Path;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 use std::vec::Vec;
 
+use chrono::{DateTime, SecondsFormat, Utc};
+use devicemapper::{Bytes, Sectors};
 use dbus;
 use dbus::arg::{Array, IterAppend};
 use dbus::tree::{
     Access, EmitsChangedSignal, Factory, MTFn, MethodErr, MethodInfo, MethodResult, PropInfo, Tree,
 };
-use dbus::{BusType, Connection, ConnectionItem, Message, NameFlag};
+use dbus::{BusType, Connection, ConnectionItem, Message, MessageType, NameFlag};
 use uuid::Uuid;
 
-use super::super::engine::{Engine, Pool, PoolUuid};
-use super::super::stratis::VERSION;
+use super::super::engine::{
+    changes_since, commands, count, statistics, ChangeKind, ChangeToken, Counter, Engine,
+    Filesystem, FilesystemFilter, FilesystemUuid, ObjectKind, Pool, PoolUuid,
+    MAX_FORMAT_VERSIONS,
+};
+use super::super::stratis::{build_features, VERSION};
+
+use super::audit::AuditRecord;
+use super::auth::{check_authorized, query_interface_name};
+use super::blockdev::create_dbus_blockdev;
+use super::consts;
+use super::filesystem::create_dbus_filesystem;
+use super::jobs::JobState;
+use super::pool::create_dbus_pool;
+use super::types::{ActionQueue, DbusContext, DbusErrorEnum, DeferredAction, TData};
+use super::util::{
+    caller_identity, dbus_err_tuple, dbus_to_sectors, engine_to_dbus_err_tuple,
+    format_versions_to_tuple, get_next_arg, lifecycle_signal, make_object_path, msg_code_ok,
+    msg_details_ok, msg_string_ok, option_to_tuple, sectors_to_dbus, tuple_to_option,
+    STRATIS_BASE_PATH, STRATIS_BASE_SERVICE,
+};
+
+/// The methods that record the identity of their caller.
+const CALLER_RECORDING_METHODS: &[&str] = &[
+    "CreateEncryptedPool",
+    "CreateFilesystems",
+    "CreatePool",
+    "CreatePoolWithFilesystems",
+    "CreatePrefixedFilesystems",
+];
+
+fn create_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let name: &str = get_next_arg(&mut iter, 0)?;
+    let redundancy: (bool, u16) = get_next_arg(&mut iter, 1)?;
+    let size_policy: (bool, u16) = get_next_arg(&mut iter, 2)?;
+    let signature_policy: (bool, u16) = get_next_arg(&mut iter, 3)?;
+    let devs: Array<&str, _> = get_next_arg(&mut iter, 4)?;
+
+    let blockdevs = devs.map(|x| Path::new(x)).collect::<Vec<&Path>>();
+
+    let object_path = m.path.get_name();
+    let dbus_context = m.tree.get_data();
+    let mut engine = dbus_context.engine.borrow_mut();
+    let result = commands::create_pool(
+        &mut *engine,
+        name,
+        &blockdevs,
+        tuple_to_option(redundancy),
+        tuple_to_option(size_policy),
+        tuple_to_option(signature_policy),
+        dbus_context.caller.borrow().clone(),
+    );
+
+    let return_message = message.method_return();
+
+    let default_return: (dbus::Path, Vec<dbus::Path>) = (dbus::Path::default(), Vec::new());
+
+    let msgs = match result {
+        Ok(pool_uuid) => {
+            let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+            let pool_object_path: dbus::Path =
+                create_dbus_pool(dbus_context, object_path.clone(), pool_uuid, pool);
+
+            let bd_object_paths = pool.blockdevs_mut()
+                .into_iter()
+                .map(|(uuid, bd)| {
+                    create_dbus_blockdev(dbus_context, pool_object_path.clone(), uuid, bd)
+                })
+                .collect::<Vec<_>>();
+
+            let signal =
+                lifecycle_signal(consts::POOL_CREATED_SIGNAL, &pool_object_path, pool_uuid);
+
+            vec![
+                return_message.append3(
+                    (pool_object_path, bd_object_paths),
+                    msg_code_ok(),
+                    msg_string_ok(),
+                ).append1(msg_details_ok()),
+                signal,
+            ]
+        }
+        Err(x) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&x);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+    Ok(msgs)
+}
+
+fn create_encrypted_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let name: &str = get_next_arg(&mut iter, 0)?;
+    let redundancy: (bool, u16) = get_next_arg(&mut iter, 1)?;
+    let size_policy: (bool, u16) = get_next_arg(&mut iter, 2)?;
+    let signature_policy: (bool, u16) = get_next_arg(&mut iter, 3)?;
+    let devs: Array<&str, _> = get_next_arg(&mut iter, 4)?;
+    let passphrase: &str = get_next_arg(&mut iter, 5)?;
+
+    let blockdevs = devs.map(|x| Path::new(x)).collect::<Vec<&Path>>();
+
+    let object_path = m.path.get_name();
+    let dbus_context = m.tree.get_data();
+    let mut engine = dbus_context.engine.borrow_mut();
+    let result = engine.create_encrypted_pool(
+        name,
+        &blockdevs,
+        tuple_to_option(redundancy),
+        tuple_to_option(size_policy),
+        tuple_to_option(signature_policy),
+        passphrase,
+        dbus_context.caller.borrow().clone(),
+    );
+
+    let return_message = message.method_return();
+
+    let default_return: (dbus::Path, Vec<dbus::Path>) = (dbus::Path::default(), Vec::new());
+
+    let msgs = match result {
+        Ok(pool_uuid) => {
+            let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+            let pool_object_path: dbus::Path =
+                create_dbus_pool(dbus_context, object_path.clone(), pool_uuid, pool);
+
+            let bd_object_paths = pool.blockdevs_mut()
+                .into_iter()
+                .map(|(uuid, bd)| {
+                    create_dbus_blockdev(dbus_context, pool_object_path.clone(), uuid, bd)
+                })
+                .collect::<Vec<_>>();
+
+            let signal =
+                lifecycle_signal(consts::POOL_CREATED_SIGNAL, &pool_object_path, pool_uuid);
+
+            vec![
+                return_message.append3(
+                    (pool_object_path, bd_object_paths),
+                    msg_code_ok(),
+                    msg_string_ok(),
+                ).append1(msg_details_ok()),
+                signal,
+            ]
+        }
+        Err(x) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&x);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+    Ok(msgs)
+}
+
+fn create_pool_with_filesystems(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let name: &str = get_next_arg(&mut iter, 0)?;
+    let redundancy: (bool, u16) = get_next_arg(&mut iter, 1)?;
+    let size_policy: (bool, u16) = get_next_arg(&mut iter, 2)?;
+    let signature_policy: (bool, u16) = get_next_arg(&mut iter, 3)?;
+    let devs: Array<&str, _> = get_next_arg(&mut iter, 4)?;
+    let filesystems: Array<(&str, (bool, u64), bool), _> = get_next_arg(&mut iter, 5)?;
+
+    let blockdevs = devs.map(|x| Path::new(x)).collect::<Vec<&Path>>();
+    let specs = filesystems
+        .map(|(name, size, guaranteed)| {
+            (
+                name,
+                tuple_to_option(size).map(dbus_to_sectors),
+                guaranteed,
+            )
+        })
+        .collect::<Vec<(&str, Option<Sectors>, bool)>>();
+
+    let object_path = m.path.get_name();
+    let dbus_context = m.tree.get_data();
+    let mut engine = dbus_context.engine.borrow_mut();
+    let result = engine.create_pool_with_filesystems(
+        name,
+        &blockdevs,
+        tuple_to_option(redundancy),
+        tuple_to_option(size_policy),
+        tuple_to_option(signature_policy),
+        &specs,
+        dbus_context.caller.borrow().clone(),
+    );
+
+    let return_message = message.method_return();
+
+    let default_return: (dbus::Path, Vec<dbus::Path>, Vec<dbus::Path>) =
+        (dbus::Path::default(), Vec::new(), Vec::new());
+
+    let msgs = match result {
+        Ok((pool_uuid, fs_uuids)) => {
+            let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+            let pool_object_path: dbus::Path =
+                create_dbus_pool(dbus_context, object_path.clone(), pool_uuid, pool);
+
+            let bd_object_paths = pool.blockdevs_mut()
+                .into_iter()
+                .map(|(uuid, bd)| {
+                    create_dbus_blockdev(dbus_context, pool_object_path.clone(), uuid, bd)
+                })
+                .collect::<Vec<_>>();
+
+            let fs_object_paths = fs_uuids
+                .iter()
+                .map(|&uuid| {
+                    create_dbus_filesystem(
+                        dbus_context,
+                        pool_object_path.clone(),
+                        uuid,
+                        pool.get_mut_filesystem(uuid)
+                            .expect("just created by create_pool_with_filesystems")
+                            .1,
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let mut signals = vec![lifecycle_signal(
+                consts::POOL_CREATED_SIGNAL,
+                &pool_object_path,
+                pool_uuid,
+            )];
+            signals.extend(
+                fs_object_paths
+                    .iter()
+                    .zip(fs_uuids.iter())
+                    .map(|(fs_path, &uuid)| {
+                        lifecycle_signal(consts::FILESYSTEM_CREATED_SIGNAL, fs_path, uuid)
+                    }),
+            );
+
+            let mut msgs = vec![
+                return_message
+                    .append3(
+                        (pool_object_path, bd_object_paths, fs_object_paths),
+                        msg_code_ok(),
+                        msg_string_ok(),
+                    )
+                    .append1(msg_details_ok()),
+            ];
+            msgs.extend(signals);
+            msgs
+        }
+        Err(x) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&x);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+    Ok(msgs)
+}
+
+fn destroy_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let object_path: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let keep_signatures: bool = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+
+    let default_return = false;
+    let return_message = message.method_return();
+
+    let pool_uuid = match m.tree.get(&object_path) {
+        Some(pool_path) => get_data!(pool_path; default_return; return_message).pool_uuid(),
+        None => {
+            return Ok(vec![return_message.append3(
+                default_return,
+                msg_code_ok(),
+                msg_string_ok(),
+            ).append1(msg_details_ok())]);
+        }
+    };
+
+    let result = dbus_context
+        .engine
+        .borrow_mut()
+        .destroy_pool(pool_uuid, keep_signatures);
+    let msgs = match result {
+        Ok(action) => {
+            dbus_context
+                .actions
+                .borrow_mut()
+                .push_remove(&object_path, m.tree);
+            let mut msgs = vec![
+                return_message
+                    .append3(action, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            if action {
+                msgs.push(lifecycle_signal(
+                    consts::POOL_DESTROYED_SIGNAL,
+                    &object_path,
+                    pool_uuid,
+                ));
+            }
+            msgs
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+    Ok(msgs)
+}
+
+fn wipe_reclaimable_device(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let devnode: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+
+    let default_return = false;
+    let return_message = message.method_return();
+
+    let result = dbus_context
+        .engine
+        .borrow_mut()
+        .wipe_reclaimable_device(Path::new(devnode));
+    let msg = match result {
+        Ok(action) => return_message
+            .append3(action, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn list_reclaimable_devices(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: Vec<(String, String, String, String)> = Vec::new();
+
+    let msg = match dbus_context.engine.borrow().reclaimable_devices() {
+        Ok(devices) => {
+            let devices = devices
+                .into_iter()
+                .map(|dev| {
+                    (
+                        dev.devnode.display().to_string(),
+                        dev.pool_uuid.simple().to_string(),
+                        dev.dev_uuid.simple().to_string(),
+                        dev.destroyed.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    )
+                })
+                .collect::<Vec<_>>();
+            return_message
+                .append3(devices, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn list_cloned_devices(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: Vec<(String, String, String, String)> = Vec::new();
+
+    let msg = match dbus_context.engine.borrow().cloned_devices() {
+        Ok(devices) => {
+            let devices = devices
+                .into_iter()
+                .map(|dev| {
+                    (
+                        dev.devnode.display().to_string(),
+                        dev.pool_uuid.simple().to_string(),
+                        dev.dev_uuid.simple().to_string(),
+                        dev.kept.display().to_string(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            return_message
+                .append3(devices, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn list_metadata_backups(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: Vec<(u64, String)> = Vec::new();
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let msg = match dbus_context.engine.borrow().metadata_backups(pool_uuid) {
+        Ok(backups) => {
+            let backups = backups
+                .into_iter()
+                .map(|(generation, time)| (generation, time.to_rfc3339()))
+                .collect::<Vec<_>>();
+            return_message
+                .append3(backups, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn plan_destroy_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: (Vec<(String, String, Vec<String>)>, Vec<String>, Vec<String>) =
+        (Vec::new(), Vec::new(), Vec::new());
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let msg = match dbus_context.engine.borrow().plan_destroy_pool(pool_uuid) {
+        Ok(Some(plan)) => {
+            let filesystems = plan.filesystems
+                .into_iter()
+                .map(|fs| {
+                    (
+                        fs.uuid.simple().to_string(),
+                        fs.name,
+                        fs.mount_points
+                            .iter()
+                            .map(|mount_point| mount_point.display().to_string())
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let paths = |paths: Vec<PathBuf>| {
+                paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+            };
+            return_message
+                .append3(
+                    (filesystems, paths(plan.blockdevs), paths(plan.devlinks)),
+                    msg_code_ok(),
+                    msg_string_ok(),
+                )
+                .append1(msg_details_ok())
+        }
+        Ok(None) => {
+            let error_message = format!("no pool for uuid {}", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn list_filesystem_space(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: Vec<(dbus::Path<'static>, String, String)> = Vec::new();
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let engine = dbus_context.engine.borrow();
+    let (_, pool) = match engine.get_pool(pool_uuid) {
+        Some(pool) => pool,
+        None => {
+            let error_message = format!("no pool for uuid {}", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let msg = match pool.filesystem_space() {
+        Ok(space) => {
+            let space = space
+                .into_iter()
+                .filter_map(|(uuid, exclusive, shared)| {
+                    pool.get_filesystem(uuid).map(|(_, fs)| {
+                        (
+                            fs.get_dbus_path().0.clone().unwrap_or_default(),
+                            sectors_to_dbus(exclusive),
+                            sectors_to_dbus(shared),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            return_message
+                .append3(space, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+/// A filesystem as listed by ListFilesystems: the UUID and name of its pool,
+/// its UUID and name, its creation time, and its object path.
+type FilesystemRecord = (String, String, String, String, String, dbus::Path<'static>);
+
+/// The most filesystems listed in a page, whatever limit is asked for, so
+/// that a reply stays well within the limit on the size of a message.
+const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Make a filter from the criteria passed to ListFilesystems. Return the
+/// error code and a message if a criterion is invalid.
+fn filesystem_filter(
+    pool_uuid: (bool, &str),
+    name: (bool, &str),
+    created_since: (bool, &str),
+) -> Result<FilesystemFilter, (DbusErrorEnum, String)> {
+    let pool_uuid = match tuple_to_option(pool_uuid) {
+        Some(uuid) => Some(PoolUuid::parse_str(uuid).map_err(|_| {
+            (
+                DbusErrorEnum::NOTFOUND,
+                format!("{} is not a valid pool UUID", uuid),
+            )
+        })?),
+        None => None,
+    };
+    let created_since = match tuple_to_option(created_since) {
+        Some(time) => Some(
+            DateTime::parse_from_rfc3339(time)
+                .map_err(|_| {
+                    (
+                        DbusErrorEnum::ERROR,
+                        format!("{} is not a valid RFC 3339 time", time),
+                    )
+                })?
+                .with_timezone(&Utc),
+        ),
+        None => None,
+    };
+    Ok(FilesystemFilter {
+        pool_uuid,
+        name_glob: tuple_to_option(name).map(|name| name.to_owned()),
+        created_since,
+    })
+}
+
+fn filesystem_record(
+    pool_name: &str,
+    pool_uuid: PoolUuid,
+    name: &str,
+    uuid: FilesystemUuid,
+    fs: &Filesystem,
+) -> FilesystemRecord {
+    (
+        pool_uuid.simple().to_string(),
+        pool_name.to_owned(),
+        uuid.simple().to_string(),
+        name.to_owned(),
+        fs.created().to_rfc3339_opts(SecondsFormat::Secs, true),
+        fs.get_dbus_path().0.clone().unwrap_or_default(),
+    )
+}
+
+/// The cursor from which ListFilesystemsPage continues after the filesystem
+/// with uuid in the pool with pool_uuid.
+fn page_cursor(pool_uuid: PoolUuid, uuid: FilesystemUuid) -> String {
+    format!("{}/{}", pool_uuid.simple(), uuid.simple())
+}
+
+/// The pool and filesystem UUIDs in a cursor made by page_cursor().
+fn parse_page_cursor(cursor: &str) -> Option<(PoolUuid, FilesystemUuid)> {
+    let mut uuids = cursor.splitn(2, '/');
+    match (
+        uuids.next().map(PoolUuid::parse_str),
+        uuids.next().map(FilesystemUuid::parse_str),
+    ) {
+        (Some(Ok(pool_uuid)), Some(Ok(uuid))) => Some((pool_uuid, uuid)),
+        _ => None,
+    }
+}
+
+fn list_filesystems(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid_tuple: (bool, &str) = get_next_arg(&mut iter, 0)?;
+    let name_tuple: (bool, &str) = get_next_arg(&mut iter, 1)?;
+    let created_since_tuple: (bool, &str) = get_next_arg(&mut iter, 2)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: Vec<FilesystemRecord> = Vec::new();
+
+    let filter = match filesystem_filter(pool_uuid_tuple, name_tuple, created_since_tuple) {
+        Ok(filter) => filter,
+        Err((code, error_message)) => {
+            let (rc, rs, details) = dbus_err_tuple(code, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let engine = dbus_context.engine.borrow();
+    let filesystems = engine
+        .list_filesystems(&filter)
+        .into_iter()
+        .map(|(pool_name, pool_uuid, name, uuid, fs)| {
+            filesystem_record(&pool_name, pool_uuid, &name, uuid, fs)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(vec![
+        return_message
+            .append3(filesystems, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+    ])
+}
+
+fn list_filesystems_page(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid_tuple: (bool, &str) = get_next_arg(&mut iter, 0)?;
+    let name_tuple: (bool, &str) = get_next_arg(&mut iter, 1)?;
+    let created_since_tuple: (bool, &str) = get_next_arg(&mut iter, 2)?;
+    let cursor_tuple: (bool, &str) = get_next_arg(&mut iter, 3)?;
+    let limit: u32 = get_next_arg(&mut iter, 4)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: (Vec<FilesystemRecord>, (bool, String)) =
+        (Vec::new(), (false, String::new()));
+
+    let filter = match filesystem_filter(pool_uuid_tuple, name_tuple, created_since_tuple) {
+        Ok(filter) => filter,
+        Err((code, error_message)) => {
+            let (rc, rs, details) = dbus_err_tuple(code, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let after = match tuple_to_option(cursor_tuple) {
+        Some(cursor) => match parse_page_cursor(cursor) {
+            Some(after) => Some(after),
+            None => {
+                let error_message = format!("{} is not a valid cursor", cursor);
+                let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::ERROR, &error_message);
+                return Ok(vec![
+                    return_message.append3(default_return, rc, rs).append1(details),
+                ]);
+            }
+        },
+        None => None,
+    };
+
+    let engine = dbus_context.engine.borrow();
+    let (filesystems, more) =
+        engine.list_filesystems_page(&filter, after, min(limit, MAX_PAGE_SIZE) as usize);
+    let next_cursor = if more {
+        filesystems
+            .last()
+            .map_or((false, String::new()), |&(_, pool_uuid, _, uuid, _)| {
+                (true, page_cursor(pool_uuid, uuid))
+            })
+    } else {
+        (false, String::new())
+    };
+    let filesystems = filesystems
+        .into_iter()
+        .map(|(pool_name, pool_uuid, name, uuid, fs)| {
+            filesystem_record(&pool_name, pool_uuid, &name, uuid, fs)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(vec![
+        return_message
+            .append3((filesystems, next_cursor), msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+    ])
+}
+
+/// The token that GetChanges returns to name the latest change.
+fn change_token(token: ChangeToken) -> String {
+    format!("{}.{}", token.epoch.simple(), token.sequence)
+}
+
+/// The change named by a token made by change_token().
+fn parse_change_token(token: &str) -> Option<ChangeToken> {
+    let mut parts = token.splitn(2, '.');
+    match (
+        parts.next().map(Uuid::parse_str),
+        parts.next().map(|s| s.parse::<u64>()),
+    ) {
+        (Some(Ok(epoch)), Some(Ok(sequence))) => Some(ChangeToken { epoch, sequence }),
+        _ => None,
+    }
+}
+
+fn get_changes(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let since_tuple: (bool, &str) = get_next_arg(&mut iter, 0)?;
+
+    let return_message = message.method_return();
+
+    // A token that can not be parsed is treated like one from another run
+    // of the daemon: the client is told to enumerate all objects again.
+    let since = tuple_to_option(since_tuple).and_then(parse_change_token);
+    let (token, changes) = changes_since(since);
+    let complete = changes.is_some();
+    let changes = changes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|change| {
+            let kind = match change.object {
+                ObjectKind::Pool => "pool",
+                ObjectKind::Filesystem => "filesystem",
+                ObjectKind::BlockDev => "blockdev",
+            };
+            let action = match change.kind {
+                ChangeKind::Created => "created",
+                ChangeKind::Modified => "modified",
+                ChangeKind::Deleted => "deleted",
+            };
+            (
+                kind.to_owned(),
+                change.uuid.simple().to_string(),
+                dbus::Path::from(make_object_path(kind, change.uuid)),
+                action.to_owned(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Ok(vec![
+        return_message
+            .append3(
+                (change_token(token), complete, changes),
+                msg_code_ok(),
+                msg_string_ok(),
+            )
+            .append1(msg_details_ok()),
+    ])
+}
+
+fn restore_metadata_backup(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+    let generation: u64 = get_next_arg(&mut iter, 1)?;
+
+    let object_path = m.path.get_name();
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: (bool, dbus::Path) = (false, dbus::Path::default());
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let msg = match engine.restore_metadata_backup(pool_uuid, generation) {
+        Ok(true) => {
+            let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+            let pool_object_path = register_pool_dbus(dbus_context, pool_uuid, pool, object_path);
+            return_message
+                .append3((true, pool_object_path), msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Ok(false) => return_message
+            .append3(default_return, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn undo_destroy_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+
+    let object_path = m.path.get_name();
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: (bool, dbus::Path) = (false, dbus::Path::default());
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let msg = match engine.undo_destroy_pool(pool_uuid) {
+        Ok(true) => {
+            let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+            let pool_object_path = register_pool_dbus(dbus_context, pool_uuid, pool, object_path);
+            return_message
+                .append3((true, pool_object_path), msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Ok(false) => return_message
+            .append3(default_return, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn unlock_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+    let passphrase: &str = get_next_arg(&mut iter, 1)?;
+
+    let object_path = m.path.get_name();
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: (bool, dbus::Path) = (false, dbus::Path::default());
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let msg = match engine.unlock_pool(pool_uuid, passphrase) {
+        Ok(true) => {
+            let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+            let pool_object_path = register_pool_dbus(dbus_context, pool_uuid, pool, object_path);
+            return_message
+                .append3((true, pool_object_path), msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Ok(false) => return_message
+            .append3(default_return, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn list_locked_pools(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: Vec<String> = Vec::new();
+
+    let msg = match dbus_context.engine.borrow().locked_pools() {
+        Ok(pool_uuids) => {
+            let pool_uuids = pool_uuids
+                .into_iter()
+                .map(|pool_uuid| pool_uuid.simple().to_string())
+                .collect::<Vec<_>>();
+            return_message
+                .append3(pool_uuids, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_key(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+    let passphrase: &str = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let msg = match engine.set_key(pool_uuid, passphrase) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn unset_key(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let msg = match dbus_context.engine.borrow_mut().unset_key(pool_uuid) {
+        Ok(unset) => return_message
+            .append3(unset, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn bind_tang(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+    let passphrase: &str = get_next_arg(&mut iter, 1)?;
+    let url: &str = get_next_arg(&mut iter, 2)?;
+    let thumbprint: &str = get_next_arg(&mut iter, 3)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let msg = match engine.bind_tang(pool_uuid, passphrase, url, thumbprint) {
+        Ok(bound) => return_message
+            .append3(bound, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn unbind_tang(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let msg = match dbus_context.engine.borrow_mut().unbind_tang(pool_uuid) {
+        Ok(unbound) => return_message
+            .append3(unbound, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn list_keys(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: Vec<String> = Vec::new();
+
+    let msg = match dbus_context.engine.borrow().keys() {
+        Ok(pool_uuids) => {
+            let pool_uuids = pool_uuids
+                .into_iter()
+                .map(|pool_uuid| pool_uuid.simple().to_string())
+                .collect::<Vec<_>>();
+            return_message
+                .append3(pool_uuids, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn list_bindings(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: (bool, (bool, String), Vec<String>, (u32, u32)) =
+        (false, (false, String::new()), Vec::new(), (0, 0));
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
 
-use super::blockdev::create_dbus_blockdev;
-use super::filesystem::create_dbus_filesystem;
-use super::pool::create_dbus_pool;
-use super::types::{ActionQueue, DbusContext, DbusErrorEnum, DeferredAction, TData};
-use super::util::{
-    engine_to_dbus_err_tuple, get_next_arg, msg_code_ok, msg_string_ok, tuple_to_option,
-    STRATIS_BASE_PATH, STRATIS_BASE_SERVICE,
-};
+    let msg = match dbus_context.engine.borrow().bindings(pool_uuid) {
+        Ok(Some(bindings)) => {
+            let result = (
+                bindings.unlocked,
+                option_to_tuple(bindings.key_description, String::new()),
+                bindings.clevis_pins,
+                (bindings.keyslots_used, bindings.keyslots_total),
+            );
+            return_message
+                .append3(result, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
+        }
+        Ok(None) => {
+            let error_message = format!("no devices of an encrypted pool {} found", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
 
-fn create_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+/// The number of whole milliseconds in duration.
+fn to_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_nanos() / 1_000_000)
+}
+
+/// Everything about the running daemon that a support bundle or a health
+/// check wants, in one call. The uptime and the time the daemon took to set
+/// up its engine are in milliseconds.
+fn daemon_info(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let engine = dbus_context.engine.borrow();
+    let pools = engine.pools();
+    let filesystems: usize = pools
+        .iter()
+        .map(|&(_, _, pool)| pool.filesystems().len())
+        .sum();
+    let blockdevs: usize = pools
+        .iter()
+        .map(|&(_, _, pool)| pool.blockdevs().len())
+        .sum();
+
+    let info = (
+        VERSION,
+        build_features(),
+        if engine.is_simulator() { "sim" } else { "real" },
+        to_millis(dbus_context.started.elapsed()),
+        to_millis(dbus_context.startup),
+        pools.len() as u64,
+        filesystems as u64,
+        blockdevs as u64,
+    );
+
+    Ok(vec![
+        message
+            .method_return()
+            .append3(info, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+    ])
+}
+
+fn repair_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let message: &Message = m.msg;
     let mut iter = message.iter_init();
 
-    let name: &str = get_next_arg(&mut iter, 0)?;
-    let redundancy: (bool, u16) = get_next_arg(&mut iter, 1)?;
-    let devs: Array<&str, _> = get_next_arg(&mut iter, 3)?;
+    let object_path: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_uuid = match m.tree.get(&object_path) {
+        Some(pool_path) => get_data!(pool_path; default_return; return_message).pool_uuid(),
+        None => {
+            let message = format!("no data for object path {}", object_path);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &message);
+            return Ok(vec![return_message.append3(default_return, rc, rs).append1(details)]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.repair(pool_uuid, &pool_name) {
+        Ok(repaired) => return_message
+            .append3(repaired, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn reconstruct_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_uuid: &str = get_next_arg(&mut iter, 0)?;
+    let generation: u64 = get_next_arg(&mut iter, 1)?;
+    let devs: Array<&str, _> = get_next_arg(&mut iter, 2)?;
 
     let blockdevs = devs.map(|x| Path::new(x)).collect::<Vec<&Path>>();
 
     let object_path = m.path.get_name();
     let dbus_context = m.tree.get_data();
-    let mut engine = dbus_context.engine.borrow_mut();
-    let result = engine.create_pool(name, &blockdevs, tuple_to_option(redundancy));
-
     let return_message = message.method_return();
-
     let default_return: (dbus::Path, Vec<dbus::Path>) = (dbus::Path::default(), Vec::new());
 
-    let msg = match result {
-        Ok(pool_uuid) => {
-            let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid) {
+        Ok(pool_uuid) => pool_uuid,
+        Err(_) => {
+            let error_message = format!("{} is not a valid pool UUID", pool_uuid);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let msgs = match engine.reconstruct_pool(pool_uuid, generation, &blockdevs) {
+        Ok(new_uuid) => {
+            let (_, pool) = get_mut_pool!(engine; new_uuid; default_return; return_message);
 
             let pool_object_path: dbus::Path =
-                create_dbus_pool(dbus_context, object_path.clone(), pool_uuid, pool);
+                create_dbus_pool(dbus_context, object_path.clone(), new_uuid, pool);
 
             let bd_object_paths = pool.blockdevs_mut()
                 .into_iter()
@@ -60,53 +1350,48 @@ fn create_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
                 })
                 .collect::<Vec<_>>();
 
-            return_message.append3(
-                (pool_object_path, bd_object_paths),
-                msg_code_ok(),
-                msg_string_ok(),
-            )
+            let signal = lifecycle_signal(consts::POOL_CREATED_SIGNAL, &pool_object_path, new_uuid);
+
+            vec![
+                return_message.append3(
+                    (pool_object_path, bd_object_paths),
+                    msg_code_ok(),
+                    msg_string_ok(),
+                ).append1(msg_details_ok()),
+                signal,
+            ]
         }
-        Err(x) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&x);
-            return_message.append3(default_return, rc, rs)
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
         }
     };
-    Ok(vec![msg])
+    Ok(msgs)
 }
 
-fn destroy_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+fn get_job(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let message: &Message = m.msg;
     let mut iter = message.iter_init();
 
-    let object_path: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let job: u64 = get_next_arg(&mut iter, 0)?;
 
     let dbus_context = m.tree.get_data();
-
-    let default_return = false;
     let return_message = message.method_return();
+    let default_return: (bool, u16, String) = (false, 0, String::new());
 
-    let pool_uuid = match m.tree.get(&object_path) {
-        Some(pool_path) => get_data!(pool_path; default_return; return_message).uuid,
+    let msg = match dbus_context.jobs.borrow_mut().query(job) {
+        Some(JobState::Running) => return_message
+            .append3(default_return, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Some(JobState::Finished(rc, rs)) => return_message
+            .append3((true, rc, rs), msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
         None => {
-            return Ok(vec![return_message.append3(
-                default_return,
-                msg_code_ok(),
-                msg_string_ok(),
-            )]);
-        }
-    };
-
-    let msg = match dbus_context.engine.borrow_mut().destroy_pool(pool_uuid) {
-        Ok(action) => {
-            dbus_context
-                .actions
-                .borrow_mut()
-                .push_remove(&object_path, m.tree);
-            return_message.append3(action, msg_code_ok(), msg_string_ok())
-        }
-        Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append3(default_return, rc, rs)
+            let error_message = format!("no job {}, or its outcome was already queried", job);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return_message
+                .append3(default_return, rc, rs)
+                .append1(details)
         }
     };
     Ok(vec![msg])
@@ -117,6 +1402,38 @@ fn get_version(i: &mut IterAppend, _p: &PropInfo<MTFn<TData>, TData>) -> Result<
     Ok(())
 }
 
+fn get_max_format_versions(
+    i: &mut IterAppend,
+    _p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    i.append(format_versions_to_tuple(MAX_FORMAT_VERSIONS));
+    Ok(())
+}
+
+fn get_engine_capabilities(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    let capabilities = p.tree
+        .get_data()
+        .engine
+        .borrow()
+        .capabilities()
+        .iter()
+        .map(|capability| capability.to_string())
+        .collect::<Vec<_>>();
+    i.append(capabilities);
+    Ok(())
+}
+
+fn get_statistics(
+    i: &mut IterAppend,
+    _p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    i.append(statistics().into_iter().collect::<HashMap<_, _>>());
+    Ok(())
+}
+
 fn configure_simulator(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let message = m.msg;
     let mut iter = message.iter_init();
@@ -132,10 +1449,66 @@ fn configure_simulator(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
 
     let msg = match result {
-        Ok(_) => return_message.append2(msg_code_ok(), msg_string_ok()),
+        Ok(_) => return_message.append2(msg_code_ok(), msg_string_ok()).append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append2(rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn configure_simulator_capacities(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message = m.msg;
+    let mut iter = message.iter_init();
+
+    let device_size: (bool, u64) = get_next_arg(&mut iter, 0)?;
+    let filesystem_size: (bool, u64) = get_next_arg(&mut iter, 1)?;
+    let initial_used: (bool, u64) = get_next_arg(&mut iter, 2)?;
+    let growth: (bool, u64) = get_next_arg(&mut iter, 3)?;
+
+    let dbus_context = m.tree.get_data();
+    let result = dbus_context
+        .engine
+        .borrow_mut()
+        .configure_simulator_capacities(
+            tuple_to_option(device_size).map(dbus_to_sectors),
+            tuple_to_option(filesystem_size).map(dbus_to_sectors),
+            tuple_to_option(initial_used).map(Bytes),
+            tuple_to_option(growth).map(Bytes),
+        );
+
+    let return_message = message.method_return();
+
+    let msg = match result {
+        Ok(_) => return_message.append2(msg_code_ok(), msg_string_ok()).append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append2(rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn advance_simulator_clock(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message = m.msg;
+    let mut iter = message.iter_init();
+
+    let seconds: u64 = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let result = dbus_context
+        .engine
+        .borrow_mut()
+        .advance_simulator_clock(seconds);
+
+    let return_message = message.method_return();
+
+    let msg = match result {
+        Ok(_) => return_message.append2(msg_code_ok(), msg_string_ok()).append1(msg_details_ok()),
         Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append2(rc, rs)
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append2(rc, rs).append1(details)
         }
     };
     Ok(vec![msg])
@@ -149,21 +1522,295 @@ fn get_base_tree<'a>(dbus_context: DbusContext) -> (Tree<MTFn<TData>, TData>, db
     let create_pool_method = f.method("CreatePool", (), create_pool)
         .in_arg(("name", "s"))
         .in_arg(("redundancy", "(bq)"))
+        .in_arg(("size_policy", "(bq)"))
+        .in_arg(("signature_policy", "(bq)"))
+        .in_arg(("devices", "as"))
+        .out_arg(("result", "(oao)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let create_encrypted_pool_method = f.method("CreateEncryptedPool", (), create_encrypted_pool)
+        .in_arg(("name", "s"))
+        .in_arg(("redundancy", "(bq)"))
+        .in_arg(("size_policy", "(bq)"))
+        .in_arg(("signature_policy", "(bq)"))
         .in_arg(("devices", "as"))
+        .in_arg(("passphrase", "s"))
         .out_arg(("result", "(oao)"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let create_pool_with_filesystems_method =
+        f.method("CreatePoolWithFilesystems", (), create_pool_with_filesystems)
+            .in_arg(("name", "s"))
+            .in_arg(("redundancy", "(bq)"))
+            .in_arg(("size_policy", "(bq)"))
+            .in_arg(("signature_policy", "(bq)"))
+            .in_arg(("devices", "as"))
+            .in_arg(("filesystems", "a(s(bt)b)"))
+            .out_arg(("result", "(oaoao)"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
 
     let destroy_pool_method = f.method("DestroyPool", (), destroy_pool)
         .in_arg(("pool", "o"))
+        .in_arg(("keep_signatures", "b"))
         .out_arg(("action", "b"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let wipe_reclaimable_device_method =
+        f.method("WipeReclaimableDevice", (), wipe_reclaimable_device)
+            .in_arg(("devnode", "s"))
+            .out_arg(("action", "b"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let list_reclaimable_devices_method =
+        f.method("ListReclaimableDevices", (), list_reclaimable_devices)
+            .out_arg(("devices", "a(ssss)"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let list_cloned_devices_method = f.method("ListClonedDevices", (), list_cloned_devices)
+        .out_arg(("devices", "a(ssss)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
 
     let configure_simulator_method = f.method("ConfigureSimulator", (), configure_simulator)
         .in_arg(("denominator", "u"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let configure_simulator_capacities_method =
+        f.method("ConfigureSimulatorCapacities", (), configure_simulator_capacities)
+            .in_arg(("device_size", "(bt)"))
+            .in_arg(("filesystem_size", "(bt)"))
+            .in_arg(("initial_used", "(bt)"))
+            .in_arg(("growth", "(bt)"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let advance_simulator_clock_method =
+        f.method("AdvanceSimulatorClock", (), advance_simulator_clock)
+            .in_arg(("seconds", "t"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let list_metadata_backups_method =
+        f.method("ListMetadataBackups", (), list_metadata_backups)
+            .in_arg(("pool_uuid", "s"))
+            .out_arg(("backups", "a(ts)"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let plan_destroy_pool_method = f.method("PlanDestroyPool", (), plan_destroy_pool)
+        .in_arg(("pool_uuid", "s"))
+        .out_arg(("plan", "(a(ssas)asas)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let list_filesystem_space_method =
+        f.method("ListFilesystemSpace", (), list_filesystem_space)
+            .in_arg(("pool_uuid", "s"))
+            .out_arg(("space", "a(oss)"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let list_filesystems_method = f.method("ListFilesystems", (), list_filesystems)
+        .in_arg(("pool_uuid", "(bs)"))
+        .in_arg(("name", "(bs)"))
+        .in_arg(("created_since", "(bs)"))
+        .out_arg(("filesystems", "a(ssssso)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let list_filesystems_page_method =
+        f.method("ListFilesystemsPage", (), list_filesystems_page)
+            .in_arg(("pool_uuid", "(bs)"))
+            .in_arg(("name", "(bs)"))
+            .in_arg(("created_since", "(bs)"))
+            .in_arg(("cursor", "(bs)"))
+            .in_arg(("limit", "u"))
+            .out_arg(("result", "(a(ssssso)(bs))"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let get_changes_method = f.method("GetChanges", (), get_changes)
+        .in_arg(("since", "(bs)"))
+        .out_arg(("result", "(sba(ssos))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let reconstruct_pool_method = f.method("ReconstructPool", (), reconstruct_pool)
+        .in_arg(("pool_uuid", "s"))
+        .in_arg(("generation", "t"))
+        .in_arg(("devices", "as"))
+        .out_arg(("result", "(oao)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let repair_pool_method = f.method("RepairPool", (), repair_pool)
+        .in_arg(("pool", "o"))
+        .out_arg(("repaired", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let restore_metadata_backup_method =
+        f.method("RestoreMetadataBackup", (), restore_metadata_backup)
+            .in_arg(("pool_uuid", "s"))
+            .in_arg(("generation", "t"))
+            .out_arg(("result", "(bo)"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let undo_destroy_pool_method = f.method("UndoDestroyPool", (), undo_destroy_pool)
+        .in_arg(("pool_uuid", "s"))
+        .out_arg(("result", "(bo)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let unlock_pool_method = f.method("UnlockPool", (), unlock_pool)
+        .in_arg(("pool_uuid", "s"))
+        .in_arg(("passphrase", "s"))
+        .out_arg(("result", "(bo)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_key_method = f.method("SetKey", (), set_key)
+        .in_arg(("pool_uuid", "s"))
+        .in_arg(("passphrase", "s"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let unset_key_method = f.method("UnsetKey", (), unset_key)
+        .in_arg(("pool_uuid", "s"))
+        .out_arg(("unset", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let bind_tang_method = f.method("BindTang", (), bind_tang)
+        .in_arg(("pool_uuid", "s"))
+        .in_arg(("passphrase", "s"))
+        .in_arg(("url", "s"))
+        .in_arg(("thumbprint", "s"))
+        .out_arg(("bound", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let unbind_tang_method = f.method("UnbindTang", (), unbind_tang)
+        .in_arg(("pool_uuid", "s"))
+        .out_arg(("unbound", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let list_keys_method = f.method("ListKeys", (), list_keys)
+        .out_arg(("pool_uuids", "as"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let list_bindings_method = f.method("ListBindings", (), list_bindings)
+        .in_arg(("pool_uuid", "s"))
+        .out_arg(("bindings", "(b(bs)as(uu))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let list_locked_pools_method = f.method("ListLockedPools", (), list_locked_pools)
+        .out_arg(("pool_uuids", "as"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let daemon_info_method = f.method("DaemonInfo", (), daemon_info)
+        .out_arg(("info", "(sassttttt)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let get_job_method = f.method("GetJob", (), get_job)
+        .in_arg(("job", "t"))
+        .out_arg(("result", "(bqs)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let max_format_versions_property =
+        f.property::<(u16, u16, u16), _>("MaxFormatVersions", ())
+            .access(Access::Read)
+            .emits_changed(EmitsChangedSignal::Const)
+            .on_get(get_max_format_versions);
+
+    let engine_capabilities_property = f.property::<Vec<String>, _>("EngineCapabilities", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_engine_capabilities);
+
+    let statistics_property = f.property::<HashMap<&str, u64>, _>("Statistics", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_statistics);
+
+    let pool_created_signal = f.signal(consts::POOL_CREATED_SIGNAL, ())
+        .sarg::<dbus::Path, _>("pool")
+        .sarg::<&str, _>("uuid");
+
+    let pool_destroyed_signal = f.signal(consts::POOL_DESTROYED_SIGNAL, ())
+        .sarg::<dbus::Path, _>("pool")
+        .sarg::<&str, _>("uuid");
+
+    let filesystem_created_signal = f.signal(consts::FILESYSTEM_CREATED_SIGNAL, ())
+        .sarg::<dbus::Path, _>("filesystem")
+        .sarg::<&str, _>("uuid");
+
+    let filesystem_destroyed_signal = f.signal(consts::FILESYSTEM_DESTROYED_SIGNAL, ())
+        .sarg::<dbus::Path, _>("filesystem")
+        .sarg::<&str, _>("uuid");
+
+    let device_added_signal = f.signal(consts::DEVICE_ADDED_SIGNAL, ())
+        .sarg::<dbus::Path, _>("blockdev")
+        .sarg::<&str, _>("uuid");
+
+    let device_failed_signal = f.signal(consts::DEVICE_FAILED_SIGNAL, ())
+        .sarg::<dbus::Path, _>("blockdev")
+        .sarg::<&str, _>("uuid");
+
+    let device_removed_signal = f.signal(consts::DEVICE_REMOVED_SIGNAL, ())
+        .sarg::<dbus::Path, _>("blockdev")
+        .sarg::<&str, _>("uuid");
+
+    let setup_progress_signal = f.signal(consts::SETUP_PROGRESS_SIGNAL, ())
+        .sarg::<&str, _>("name")
+        .sarg::<u32, _>("step")
+        .sarg::<u32, _>("total")
+        .sarg::<&str, _>("stage");
 
     let version_property = f.property::<&str, _>("Version", ())
         .access(Access::Read)
@@ -178,9 +1825,50 @@ fn get_base_tree<'a>(dbus_context: DbusContext) -> (Tree<MTFn<TData>, TData>, db
         .add(
             f.interface(interface_name, ())
                 .add_m(create_pool_method)
+                .add_m(create_encrypted_pool_method)
+                .add_m(create_pool_with_filesystems_method)
                 .add_m(destroy_pool_method)
                 .add_m(configure_simulator_method)
-                .add_p(version_property),
+                .add_m(configure_simulator_capacities_method)
+                .add_m(advance_simulator_clock_method)
+                .add_m(bind_tang_method)
+                .add_m(get_job_method)
+                .add_m(reconstruct_pool_method)
+                .add_m(repair_pool_method)
+                .add_m(restore_metadata_backup_method)
+                .add_m(set_key_method)
+                .add_m(undo_destroy_pool_method)
+                .add_m(unbind_tang_method)
+                .add_m(unlock_pool_method)
+                .add_m(unset_key_method)
+                .add_m(wipe_reclaimable_device_method)
+                .add_p(version_property)
+                .add_p(max_format_versions_property)
+                .add_p(engine_capabilities_property)
+                .add_p(statistics_property)
+                .add_s(pool_created_signal)
+                .add_s(pool_destroyed_signal)
+                .add_s(filesystem_created_signal)
+                .add_s(filesystem_destroyed_signal)
+                .add_s(device_added_signal)
+                .add_s(device_failed_signal)
+                .add_s(device_removed_signal)
+                .add_s(setup_progress_signal),
+        )
+        .add(
+            f.interface(query_interface_name(), ())
+                .add_m(daemon_info_method)
+                .add_m(get_changes_method)
+                .add_m(list_bindings_method)
+                .add_m(list_cloned_devices_method)
+                .add_m(list_filesystem_space_method)
+                .add_m(list_filesystems_method)
+                .add_m(list_filesystems_page_method)
+                .add_m(list_keys_method)
+                .add_m(list_metadata_backups_method)
+                .add_m(list_locked_pools_method)
+                .add_m(list_reclaimable_devices_method)
+                .add_m(plan_destroy_pool_method),
         );
 
     let path = obj_path.get_name().to_owned();
@@ -188,12 +1876,13 @@ fn get_base_tree<'a>(dbus_context: DbusContext) -> (Tree<MTFn<TData>, TData>, db
 }
 
 /// Given an Pool, create all the needed dbus objects to represent it.
+/// Returns the object path of the pool.
 fn register_pool_dbus(
     dbus_context: &DbusContext,
     pool_uuid: PoolUuid,
     pool: &mut Pool,
     object_path: &dbus::Path<'static>,
-) {
+) -> dbus::Path<'static> {
     let pool_path = create_dbus_pool(dbus_context, object_path.clone(), pool_uuid, pool);
     for (_, fs_uuid, fs) in pool.filesystems_mut() {
         create_dbus_filesystem(dbus_context, pool_path.clone(), fs_uuid, fs);
@@ -201,6 +1890,7 @@ fn register_pool_dbus(
     for (uuid, bd) in pool.blockdevs_mut() {
         create_dbus_blockdev(dbus_context, pool_path.clone(), uuid, bd);
     }
+    pool_path
 }
 
 /// Returned data from when you connect a stratis engine to dbus.
@@ -212,9 +1902,16 @@ pub struct DbusConnectionData<'a> {
 }
 
 /// Connect a stratis engine to dbus.
-pub fn connect<'a>(engine: Rc<RefCell<Engine>>) -> Result<DbusConnectionData<'a>, dbus::Error> {
+/// Calls of long-running methods are put off as jobs once they have taken
+/// longer than budget, if a budget is given.
+pub fn connect<'a>(
+    engine: Rc<RefCell<Engine>>,
+    budget: Option<Duration>,
+    started: Instant,
+    startup: Duration,
+) -> Result<DbusConnectionData<'a>, dbus::Error> {
     let c = Connection::get_private(BusType::System)?;
-    let (tree, object_path) = get_base_tree(DbusContext::new(engine));
+    let (tree, object_path) = get_base_tree(DbusContext::new(engine, budget, started, startup));
     let dbus_context = tree.get_data().clone();
     tree.set_registered(&c, true)?;
     c.register_name(STRATIS_BASE_SERVICE, NameFlag::ReplaceExisting as u32)?;
@@ -231,7 +1928,7 @@ pub fn register_pool(
     c: &Connection,
     dbus_context: &DbusContext,
     tree: &mut Tree<MTFn<TData>, TData>,
-    pool_uuid: Uuid,
+    pool_uuid: PoolUuid,
     pool: &mut Pool,
     object_path: &dbus::Path<'static>,
 ) -> Result<(), dbus::Error> {
@@ -260,14 +1957,49 @@ fn process_deferred_actions(
     Ok(())
 }
 
+/// Handle a message. A method call which its caller is not authorized to
+/// make is refused. A method call which is put off as a job is answered at
+/// once with an error naming the job; run_job() handles it later.
 pub fn handle(
     c: &Connection,
-    item: &ConnectionItem,
+    item: ConnectionItem,
     tree: &mut Tree<MTFn<TData>, TData>,
     dbus_context: &DbusContext,
 ) -> Result<(), dbus::Error> {
-    if let ConnectionItem::MethodCall(ref msg) = *item {
-        if let Some(v) = tree.handle(msg) {
+    if let ConnectionItem::MethodCall(msg) = item {
+        if let Some(reply) = check_authorized(c, &msg) {
+            let _ = c.send(reply);
+            return Ok(());
+        }
+
+        let audit_record = AuditRecord::begin(c, &msg);
+        let method = msg.member().map_or_else(String::new, |m| (*m).to_owned());
+        let caller = if CALLER_RECORDING_METHODS.contains(&method.as_str()) {
+            caller_identity(c, &msg)
+        } else {
+            None
+        };
+
+        if dbus_context.jobs.borrow().should_defer(&method) {
+            let reply = dbus_context
+                .jobs
+                .borrow_mut()
+                .defer(msg, caller, audit_record);
+            let _ = c.send(reply);
+            return Ok(());
+        }
+
+        *dbus_context.caller.borrow_mut() = caller;
+        let start = Instant::now();
+        if let Some(v) = tree.handle(&msg) {
+            dbus_context
+                .jobs
+                .borrow_mut()
+                .record_duration(&method, start.elapsed());
+            if let Some(record) = audit_record {
+                record.finish(&v);
+            }
+
             // Probably the wisest is to ignore any send errors here -
             // maybe the remote has disconnected during our processing.
             for m in v {
@@ -280,3 +2012,155 @@ pub fn handle(
 
     Ok(())
 }
+
+/// Whether any method call put off as a job waits to be handled.
+pub fn jobs_queued(dbus_context: &DbusContext) -> bool {
+    dbus_context.jobs.borrow().has_queued()
+}
+
+/// Handle the method call put off longest ago as a job, if any, and record
+/// its outcome. Its caller was answered when it was put off, so its reply
+/// is not sent, but any signals are. Return true if a job was handled.
+pub fn run_job(
+    c: &Connection,
+    tree: &mut Tree<MTFn<TData>, TData>,
+    dbus_context: &DbusContext,
+) -> Result<bool, dbus::Error> {
+    let queued = match dbus_context.jobs.borrow_mut().next_queued() {
+        Some(queued) => queued,
+        None => return Ok(false),
+    };
+
+    *dbus_context.caller.borrow_mut() = queued.caller;
+    let replies = tree.handle(&queued.msg).unwrap_or_else(Vec::new);
+    if let Some(record) = queued.audit_record {
+        record.finish(&replies);
+    }
+
+    let (replies, signals): (Vec<Message>, Vec<Message>) =
+        replies.into_iter().partition(|m| match m.msg_type() {
+            MessageType::MethodReturn | MessageType::Error => true,
+            _ => false,
+        });
+    dbus_context
+        .jobs
+        .borrow_mut()
+        .finish(queued.id, replies.first());
+    info!("job {} finished", queued.id);
+    count(Counter::JobsCompleted);
+    for m in signals {
+        let _ = c.send(m);
+    }
+
+    process_deferred_actions(c, tree, &mut dbus_context.actions.borrow_mut())?;
+    Ok(true)
+}
+
+/// Run the snapshot schedules of every pool at now. An object is added for
+/// each snapshot taken, and the object of each snapshot destroyed is
+/// removed; the creation and destruction of each are signalled.
+pub fn run_snapshot_schedules(
+    c: &Connection,
+    tree: &mut Tree<MTFn<TData>, TData>,
+    dbus_context: &DbusContext,
+    now: DateTime<Utc>,
+) -> Result<(), dbus::Error> {
+    let mut signals = Vec::new();
+    {
+        let mut engine = dbus_context.engine.borrow_mut();
+
+        // The object paths of the filesystems must be found before they
+        // are destroyed.
+        let mut filesystem_paths = HashMap::new();
+        for (_, _, pool) in engine.pools() {
+            for (_, uuid, fs) in pool.filesystems() {
+                if let Some(ref path) = fs.get_dbus_path().0 {
+                    filesystem_paths.insert(uuid, path.clone());
+                }
+            }
+        }
+
+        for (pool_uuid, taken, destroyed) in engine.run_snapshot_schedules(now) {
+            let pool = engine
+                .get_mut_pool(pool_uuid)
+                .expect("the schedules of the pool were just run")
+                .1;
+            let pool_path = match pool.get_dbus_path().0 {
+                Some(ref path) => path.clone(),
+                None => continue,
+            };
+            for uuid in taken {
+                if let Some((_, fs)) = pool.get_mut_filesystem(uuid) {
+                    let fs_path: dbus::Path =
+                        create_dbus_filesystem(dbus_context, pool_path.clone(), uuid, fs);
+                    signals.push(lifecycle_signal(
+                        consts::FILESYSTEM_CREATED_SIGNAL,
+                        &fs_path,
+                        uuid,
+                    ));
+                }
+            }
+            for uuid in destroyed {
+                if let Some(path) = filesystem_paths.get(&uuid) {
+                    dbus_context.actions.borrow_mut().push_remove(path, tree);
+                    signals.push(lifecycle_signal(
+                        consts::FILESYSTEM_DESTROYED_SIGNAL,
+                        path,
+                        uuid,
+                    ));
+                }
+            }
+        }
+    }
+
+    process_deferred_actions(c, tree, &mut dbus_context.actions.borrow_mut())?;
+    for m in signals {
+        let _ = c.send(m);
+    }
+    Ok(())
+}
+
+/// Add an object for each pool and filesystem that was created other than
+/// through the D-Bus, such as through the JSON-RPC server, and so has none;
+/// the creation of each is signalled.
+pub fn register_new_objects(
+    c: &Connection,
+    tree: &mut Tree<MTFn<TData>, TData>,
+    dbus_context: &DbusContext,
+    object_path: &dbus::Path<'static>,
+) -> Result<(), dbus::Error> {
+    let mut signals = Vec::new();
+    {
+        let mut engine = dbus_context.engine.borrow_mut();
+        for (_, pool_uuid, pool) in engine.pools_mut() {
+            let pool_path = pool.get_dbus_path().0.clone();
+            match pool_path {
+                Some(pool_path) => for (_, uuid, fs) in pool.filesystems_mut() {
+                    if fs.get_dbus_path().0.is_none() {
+                        let fs_path: dbus::Path =
+                            create_dbus_filesystem(dbus_context, pool_path.clone(), uuid, fs);
+                        signals.push(lifecycle_signal(
+                            consts::FILESYSTEM_CREATED_SIGNAL,
+                            &fs_path,
+                            uuid,
+                        ));
+                    }
+                },
+                None => {
+                    let pool_path = register_pool_dbus(dbus_context, pool_uuid, pool, object_path);
+                    signals.push(lifecycle_signal(
+                        consts::POOL_CREATED_SIGNAL,
+                        &pool_path,
+                        pool_uuid,
+                    ));
+                }
+            }
+        }
+    }
+
+    process_deferred_actions(c, tree, &mut dbus_context.actions.borrow_mut())?;
+    for m in signals {
+        let _ = c.send(m);
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Audit records of security-sensitive operations requested over the D-Bus.
+// A record is a line of key=value fields logged to the audit target, which
+// is never buffered, so that it can be picked out of the journal.
+
+use dbus;
+use dbus::arg::Array;
+use dbus::{Connection, Message, MessageType};
+
+use super::super::stratis::buff_log::AUDIT_TARGET;
+
+use super::util::{caller_uid, msg_code_ok};
+
+/// The methods whose invocations are audited.
+const AUDITED_METHODS: &[&str] = &[
+    "BindTang",
+    "DestroyFilesystems",
+    "DestroyPool",
+    "ReconstructPool",
+    "RemoveBlockDevs",
+    "ReplaceBlockDev",
+    "RepairPool",
+    "RestoreMetadataBackup",
+    "SetKey",
+    "ShrinkReservedSpace",
+    "UnbindTang",
+    "UndoDestroyPool",
+    "UnlockPool",
+    "UnsetKey",
+    "WipeReclaimableDevice",
+];
+
+/// An audit record of a method call, begun when the call is received and
+/// finished when its reply is ready.
+#[derive(Debug)]
+pub struct AuditRecord {
+    method: String,
+    object: String,
+    target: String,
+    sender: String,
+    uid: Option<u32>,
+}
+
+impl AuditRecord {
+    /// Begin an audit record of the method call msg, if it is audited.
+    /// The UID of the caller is looked up before the call is handled, while
+    /// the caller is certain to be connected.
+    pub fn begin(c: &Connection, msg: &Message) -> Option<AuditRecord> {
+        let method = msg.member()?;
+        if !AUDITED_METHODS.contains(&&*method) {
+            return None;
+        }
+
+        Some(AuditRecord {
+            method: (*method).to_owned(),
+            object: msg.path().map_or_else(String::new, |p| (*p).to_owned()),
+            target: target(msg),
+            sender: msg.sender().map_or_else(String::new, |s| (*s).to_owned()),
+            uid: caller_uid(c, msg),
+        })
+    }
+
+    /// Finish the audit record with the outcome of the call, as found in
+    /// replies, and log it.
+    pub fn finish(self, replies: &[Message]) {
+        let return_code = replies
+            .iter()
+            .find(|m| m.msg_type() == MessageType::MethodReturn)
+            .and_then(|reply| {
+                let mut iter = reply.iter_init();
+                if iter.next() {
+                    iter.get::<u16>()
+                } else {
+                    None
+                }
+            });
+        let result = match return_code {
+            Some(rc) if rc == msg_code_ok() => "success".to_owned(),
+            Some(rc) => format!("failure({})", rc),
+            None => "error".to_owned(),
+        };
+
+        info!(
+            target: AUDIT_TARGET,
+            "method={} object={} target={} sender={} uid={} result={}",
+            self.method,
+            self.object,
+            self.target,
+            self.sender,
+            self.uid.map_or_else(|| "unknown".to_owned(), |uid| uid.to_string()),
+            result
+        );
+    }
+}
+
+/// The object that the audited method call msg operates on, as named by
+/// its first argument: an object path, a UUID, or an array of object paths.
+fn target(msg: &Message) -> String {
+    if let Some(path) = msg.get1::<dbus::Path>() {
+        return (*path).to_owned();
+    }
+    if let Some(uuid) = msg.get1::<&str>() {
+        return uuid.to_owned();
+    }
+    if let Some(paths) = msg.get1::<Array<dbus::Path, _>>() {
+        return paths.map(|p| (*p).to_owned()).collect::<Vec<_>>().join(",");
+    }
+    String::new()
+}
@@ -0,0 +1,103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Authorization of method calls. Calls that only read the state of the
+// daemon, which are those of the Query interface and of the standard
+// interfaces through which objects and their properties are found, may be
+// made by any user whom the bus policy lets through. Any other call may
+// change the state of the daemon, and is refused unless made by root.
+
+use dbus::{Connection, Message};
+
+use super::util::{caller_uid, STRATIS_BASE_SERVICE};
+
+/// The name of the error with which a call that is not authorized is
+/// answered.
+const ACCESS_DENIED_ERROR: &str = "org.freedesktop.DBus.Error.AccessDenied";
+
+/// Standard interfaces all of whose methods only read.
+const READ_ONLY_STANDARD_INTERFACES: &[&str] = &[
+    "org.freedesktop.DBus.Introspectable",
+    "org.freedesktop.DBus.ObjectManager",
+    "org.freedesktop.DBus.Peer",
+];
+
+/// The name of the interface whose methods only read.
+pub fn query_interface_name() -> String {
+    format!("{}.{}", STRATIS_BASE_SERVICE, "Query")
+}
+
+/// Whether the method call msg only reads the state of the daemon.
+fn is_read_only(msg: &Message) -> bool {
+    let interface = match msg.interface() {
+        Some(interface) => (*interface).to_owned(),
+        None => return false,
+    };
+    let member = msg.member().map_or_else(String::new, |m| (*m).to_owned());
+
+    interface == query_interface_name()
+        || READ_ONLY_STANDARD_INTERFACES.contains(&interface.as_str())
+        || (interface == "org.freedesktop.DBus.Properties"
+            && (member == "Get" || member == "GetAll"))
+}
+
+/// If the caller of the method call msg is not authorized to make it,
+/// return the answer to it. A call that does not only read is authorized
+/// only if its caller is root. If the caller's UID can not be found, the
+/// call is not authorized.
+pub fn check_authorized(c: &Connection, msg: &Message) -> Option<Message> {
+    if is_read_only(msg) || caller_uid(c, msg) == Some(0) {
+        return None;
+    }
+
+    let method = msg.member().map_or_else(String::new, |m| (*m).to_owned());
+    warn!(
+        "refused {} from unprivileged caller {}",
+        method,
+        msg.sender().map_or_else(String::new, |s| (*s).to_owned())
+    );
+    let text = format!("{} may only be called by root", method);
+    Some(
+        Message::new_error(msg, ACCESS_DENIED_ERROR, &text)
+            .expect("error name and message are valid"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::util::STRATIS_BASE_PATH;
+    use super::*;
+
+    fn method_call(interface: &str, member: &str) -> Message {
+        Message::new_method_call(STRATIS_BASE_SERVICE, STRATIS_BASE_PATH, interface, member)
+            .unwrap()
+    }
+
+    #[test]
+    /// Verify that calls of the Query interface and reads of properties are
+    /// read-only, but that calls of other Stratis interfaces and writes of
+    /// properties are not.
+    fn test_is_read_only() {
+        assert!(is_read_only(&method_call(
+            &query_interface_name(),
+            "ListFilesystems"
+        )));
+        assert!(is_read_only(&method_call(
+            "org.freedesktop.DBus.ObjectManager",
+            "GetManagedObjects"
+        )));
+        assert!(is_read_only(&method_call(
+            "org.freedesktop.DBus.Properties",
+            "GetAll"
+        )));
+        assert!(!is_read_only(&method_call(
+            "org.freedesktop.DBus.Properties",
+            "Set"
+        )));
+        assert!(!is_read_only(&method_call(
+            &format!("{}.{}", STRATIS_BASE_SERVICE, "Manager"),
+            "DestroyPool"
+        )));
+    }
+}
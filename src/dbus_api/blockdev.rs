@@ -2,6 +2,8 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::collections::HashMap;
+
 use dbus;
 use dbus::arg::IterAppend;
 use dbus::tree::{
@@ -9,22 +11,21 @@ use dbus::tree::{
 };
 use dbus::Message;
 
-use uuid::Uuid;
-
-use super::super::engine::{BlockDev, BlockDevTier, MaybeDbusPath};
+use super::super::engine::{BlockDev, BlockDevTier, DevUuid, MaybeDbusPath};
 
 use super::consts;
 use super::types::{DbusContext, DbusErrorEnum, OPContext, TData};
 
 use super::util::{
-    engine_to_dbus_err_tuple, get_next_arg, get_parent, get_uuid, msg_code_ok, msg_string_ok,
-    STRATIS_BASE_PATH, STRATIS_BASE_SERVICE,
+    dbus_err_tuple, engine_to_dbus_err_tuple, format_versions_to_tuple, get_next_arg, get_parent,
+    get_uuid, make_object_path, metadata_stats_to_dict, msg_code_ok, msg_details_ok,
+    msg_string_ok, sectors_to_dbus, STRATIS_BASE_SERVICE,
 };
 
 pub fn create_dbus_blockdev<'a>(
     dbus_context: &DbusContext,
     parent: dbus::Path<'static>,
-    uuid: Uuid,
+    uuid: DevUuid,
     blockdev: &mut BlockDev,
 ) -> dbus::Path<'a> {
     let f = Factory::new_fn();
@@ -33,13 +34,19 @@ pub fn create_dbus_blockdev<'a>(
         .in_arg(("id", "s"))
         .out_arg(("changed", "b"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
 
     let devnode_property = f.property::<&str, _>("Devnode", ())
         .access(Access::Read)
         .emits_changed(EmitsChangedSignal::Const)
         .on_get(get_blockdev_devnode);
 
+    let format_versions_property = f.property::<(u16, u16, u16), _>("FormatVersions", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_blockdev_format_versions);
+
     let hardware_info_property = f.property::<&str, _>("HardwareInfo", ())
         .access(Access::Read)
         .emits_changed(EmitsChangedSignal::Const)
@@ -50,6 +57,26 @@ pub fn create_dbus_blockdev<'a>(
         .emits_changed(EmitsChangedSignal::False)
         .on_get(get_blockdev_user_info);
 
+    let metadata_corruptions_property = f.property::<u64, _>("MetadataCorruptions", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_blockdev_metadata_corruptions);
+
+    let metadata_degraded_property = f.property::<bool, _>("MetadataDegraded", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_blockdev_metadata_degraded);
+
+    let metadata_statistics_property = f.property::<HashMap<&str, u64>, _>("MetadataStatistics", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_blockdev_metadata_statistics);
+
+    let metadata_write_throttled_property = f.property::<bool, _>("MetadataWriteThrottled", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_blockdev_metadata_write_throttled);
+
     let initialization_time_property = f.property::<u64, _>("InitializationTime", ())
         .access(Access::Read)
         .emits_changed(EmitsChangedSignal::Const)
@@ -60,11 +87,21 @@ pub fn create_dbus_blockdev<'a>(
         .emits_changed(EmitsChangedSignal::False)
         .on_get(get_blockdev_physical_size);
 
+    let usable_size_property = f.property::<&str, _>("UsableSize", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_blockdev_usable_size);
+
     let state_property = f.property::<u16, _>(consts::BLOCKDEV_STATE_PROP, ())
         .access(Access::Read)
         .emits_changed(EmitsChangedSignal::True)
         .on_get(get_blockdev_state);
 
+    let state_name_property = f.property::<&str, _>(consts::BLOCKDEV_STATE_NAME_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_blockdev_state_name);
+
     let pool_property = f.property::<&dbus::Path, _>("Pool", ())
         .access(Access::Read)
         .emits_changed(EmitsChangedSignal::Const)
@@ -80,11 +117,12 @@ pub fn create_dbus_blockdev<'a>(
         .emits_changed(EmitsChangedSignal::False)
         .on_get(get_blockdev_tier);
 
-    let object_name = format!(
-        "{}/{}",
-        STRATIS_BASE_PATH,
-        dbus_context.get_next_id().to_string()
-    );
+    let tier_name_property = f.property::<&str, _>("TierName", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_blockdev_tier_name);
+
+    let object_name = make_object_path("blockdev", uuid);
 
     let interface_name = format!("{}.{}", STRATIS_BASE_SERVICE, "blockdev");
 
@@ -94,12 +132,20 @@ pub fn create_dbus_blockdev<'a>(
             f.interface(interface_name, ())
                 .add_m(set_userid_method)
                 .add_p(devnode_property)
+                .add_p(format_versions_property)
                 .add_p(hardware_info_property)
                 .add_p(initialization_time_property)
+                .add_p(metadata_corruptions_property)
+                .add_p(metadata_degraded_property)
+                .add_p(metadata_statistics_property)
+                .add_p(metadata_write_throttled_property)
                 .add_p(total_physical_size_property)
                 .add_p(pool_property)
                 .add_p(state_property)
+                .add_p(state_name_property)
                 .add_p(tier_property)
+                .add_p(tier_name_property)
+                .add_p(usable_size_property)
                 .add_p(user_info_property)
                 .add_p(uuid_property),
         );
@@ -130,18 +176,20 @@ fn set_user_info(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let blockdev_data = get_data!(blockdev_path; default_return; return_message);
 
     let pool_path = get_parent!(m; blockdev_data; default_return; return_message);
-    let pool_uuid = get_data!(pool_path; default_return; return_message).uuid;
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
 
     let mut engine = dbus_context.engine.borrow_mut();
     let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
 
-    let result = pool.set_blockdev_user_info(&pool_name, blockdev_data.uuid, new_id);
+    let result = pool.set_blockdev_user_info(&pool_name, blockdev_data.dev_uuid(), new_id);
 
     let msg = match result {
-        Ok(id_changed) => return_message.append3(id_changed, msg_code_ok(), msg_string_ok()),
+        Ok(id_changed) => return_message
+            .append3(id_changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
         Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
         }
     };
 
@@ -183,14 +231,14 @@ where
         .get_data()
         .as_ref()
         .ok_or_else(|| MethodErr::failed(&format!("no data for object path {}", object_path)))?
-        .uuid;
+        .pool_uuid();
 
     let engine = dbus_context.engine.borrow();
     let (_, pool) = engine.get_pool(pool_uuid).ok_or_else(|| {
         MethodErr::failed(&format!("no pool corresponding to uuid {}", &pool_uuid))
     })?;
-    let (tier, blockdev) = pool.get_blockdev(blockdev_data.uuid).ok_or_else(|| {
-        MethodErr::failed(&format!("no blockdev with uuid {}", blockdev_data.uuid))
+    let (tier, blockdev) = pool.get_blockdev(blockdev_data.dev_uuid()).ok_or_else(|| {
+        MethodErr::failed(&format!("no blockdev with uuid {}", blockdev_data.uuid()))
     })?;
     i.append(getter(tier, blockdev)?);
     Ok(())
@@ -204,6 +252,13 @@ fn get_blockdev_devnode(
     get_blockdev_property(i, p, |_, p| Ok(format!("{}", p.devnode().display())))
 }
 
+fn get_blockdev_format_versions(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, p| Ok(format_versions_to_tuple(p.format_versions())))
+}
+
 fn get_blockdev_hardware_info(
     i: &mut IterAppend,
     p: &PropInfo<MTFn<TData>, TData>,
@@ -225,11 +280,46 @@ fn get_blockdev_initialization_time(
     get_blockdev_property(i, p, |_, p| Ok(p.initialization_time().timestamp() as u64))
 }
 
+fn get_blockdev_metadata_corruptions(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, p| Ok(p.metadata_corruptions()))
+}
+
+fn get_blockdev_metadata_degraded(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, p| Ok(p.metadata_degraded()))
+}
+
+fn get_blockdev_metadata_statistics(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, p| Ok(metadata_stats_to_dict(p.metadata_stats())))
+}
+
+fn get_blockdev_metadata_write_throttled(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, p| Ok(p.metadata_write_throttled()))
+}
+
 fn get_blockdev_physical_size(
     i: &mut IterAppend,
     p: &PropInfo<MTFn<TData>, TData>,
 ) -> Result<(), MethodErr> {
-    get_blockdev_property(i, p, |_, p| Ok(format!("{}", *p.size())))
+    get_blockdev_property(i, p, |_, p| Ok(sectors_to_dbus(p.size())))
+}
+
+fn get_blockdev_usable_size(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, p| Ok(sectors_to_dbus(p.usable_size())))
 }
 
 fn get_blockdev_state(
@@ -243,17 +333,27 @@ fn get_blockdev_state(
     get_blockdev_property(i, p, get_state)
 }
 
+fn get_blockdev_state_name(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, blockdev| Ok(blockdev.state().to_string()))
+}
+
 fn get_blockdev_tier(
     i: &mut IterAppend,
     p: &PropInfo<MTFn<TData>, TData>,
 ) -> Result<(), MethodErr> {
     fn get(tier: BlockDevTier, _: &BlockDev) -> Result<u16, MethodErr> {
-        let tier: u16 = match tier {
-            BlockDevTier::Data => 0,
-            BlockDevTier::Cache => 1,
-        };
-        Ok(tier)
+        Ok(tier.to_dbus_value())
     }
 
     get_blockdev_property(i, p, get)
 }
+
+fn get_blockdev_tier_name(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |tier, _| Ok(tier.to_string()))
+}
@@ -5,12 +5,36 @@
 // Pool Properties
 pub const POOL_NAME_PROP: &str = "Name";
 pub const POOL_STATE_PROP: &str = "State";
+pub const POOL_STATE_NAME_PROP: &str = "StateName";
 pub const POOL_EXTEND_STATE_PROP: &str = "ExtendState";
+pub const POOL_EXTEND_STATE_NAME_PROP: &str = "ExtendStateName";
 pub const POOL_SPACE_STATE_PROP: &str = "SpaceState";
+pub const POOL_SPACE_STATE_NAME_PROP: &str = "SpaceStateName";
+pub const POOL_DAYS_UNTIL_FULL_PROP: &str = "DaysUntilFull";
 
 // Filesystem Properties
 pub const FILESYSTEM_NAME_PROP: &str = "Name";
 pub const FILESYSTEM_USED_PROP: &str = "Used";
+pub const FILESYSTEM_READ_AHEAD_PROP: &str = "ReadAhead";
+pub const FILESYSTEM_PROTECTED_PROP: &str = "Protected";
+pub const FILESYSTEM_SIZE_LIMIT_PROP: &str = "SizeLimit";
 
 // Blockdev Properties
 pub const BLOCKDEV_STATE_PROP: &str = "State";
+pub const BLOCKDEV_STATE_NAME_PROP: &str = "StateName";
+
+// Manager Signals
+pub const POOL_CREATED_SIGNAL: &str = "PoolCreated";
+pub const POOL_DESTROYED_SIGNAL: &str = "PoolDestroyed";
+pub const FILESYSTEM_CREATED_SIGNAL: &str = "FilesystemCreated";
+pub const FILESYSTEM_DESTROYED_SIGNAL: &str = "FilesystemDestroyed";
+pub const DEVICE_ADDED_SIGNAL: &str = "DeviceAdded";
+pub const DEVICE_FAILED_SIGNAL: &str = "DeviceFailed";
+pub const DEVICE_REMOVED_SIGNAL: &str = "DeviceRemoved";
+pub const SETUP_PROGRESS_SIGNAL: &str = "SetupProgress";
+pub const USAGE_THRESHOLD_CROSSED_SIGNAL: &str = "UsageThresholdCrossed";
+pub const WATERMARK_CROSSED_SIGNAL: &str = "WatermarkCrossed";
+pub const METADATA_CLOCK_SKEW_SIGNAL: &str = "MetadataClockSkew";
+pub const THIN_META_EXTENDED_SIGNAL: &str = "ThinMetaExtended";
+pub const REMOVE_PROGRESS_SIGNAL: &str = "RemoveBlockDevsProgress";
+pub const POOL_STATE_CHANGED_SIGNAL: &str = "PoolStateChanged";
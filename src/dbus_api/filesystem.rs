@@ -10,22 +10,24 @@ use dbus::tree::{
 };
 use dbus::Message;
 
-use uuid::Uuid;
-
-use super::super::engine::{filesystem_mount_path, Filesystem, MaybeDbusPath, Name, RenameAction};
+use super::super::engine::{
+    filesystem_mount_path, Filesystem, FilesystemUuid, MaybeDbusPath, Name, RenameAction,
+};
 
 use super::consts;
 use super::types::{DbusContext, DbusErrorEnum, OPContext, TData};
 
 use super::util::{
-    engine_to_dbus_err_tuple, get_next_arg, get_parent, get_uuid, msg_code_ok, msg_string_ok,
-    STRATIS_BASE_PATH, STRATIS_BASE_SERVICE,
+    bytes_to_dbus, creator_to_tuple, dbus_err_tuple, dbus_to_sectors, engine_to_dbus_err_tuple,
+    get_next_arg, get_parent, get_uuid, make_object_path, msg_code_ok, msg_details_ok,
+    msg_string_ok, option_to_tuple, prop_changed_signal, sectors_to_dbus, tuple_to_option,
+    STRATIS_BASE_SERVICE,
 };
 
 pub fn create_dbus_filesystem<'a>(
     dbus_context: &DbusContext,
     parent: dbus::Path<'static>,
-    uuid: Uuid,
+    uuid: FilesystemUuid,
     filesystem: &mut Filesystem,
 ) -> dbus::Path<'a> {
     let f = Factory::new_fn();
@@ -34,7 +36,22 @@ pub fn create_dbus_filesystem<'a>(
         .in_arg(("name", "s"))
         .out_arg(("action", "b"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_read_ahead_method = f.method("SetReadAhead", (), set_read_ahead)
+        .in_arg(("read_ahead", "(bt)"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_protected_method = f.method("SetProtected", (), set_protected)
+        .in_arg(("protected", "b"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
 
     let devnode_property = f.property::<&str, _>("Devnode", ())
         .access(Access::Read)
@@ -66,11 +83,37 @@ pub fn create_dbus_filesystem<'a>(
         .emits_changed(EmitsChangedSignal::False)
         .on_get(get_filesystem_used);
 
-    let object_name = format!(
-        "{}/{}",
-        STRATIS_BASE_PATH,
-        dbus_context.get_next_id().to_string()
-    );
+    let guaranteed_property = f.property::<bool, _>("Guaranteed", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_filesystem_guaranteed);
+
+    let created_by_property = f.property::<(bool, (u32, &str)), _>("CreatedBy", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_filesystem_created_by);
+
+    let read_ahead_property = f.property::<(bool, &str), _>(consts::FILESYSTEM_READ_AHEAD_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_filesystem_read_ahead);
+
+    let size_limit_property = f.property::<(bool, &str), _>(consts::FILESYSTEM_SIZE_LIMIT_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_filesystem_size_limit);
+
+    let protected_property = f.property::<bool, _>(consts::FILESYSTEM_PROTECTED_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_filesystem_protected);
+
+    let dm_device_property = f.property::<(bool, (&str, &str, &str)), _>("DmDevice", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_filesystem_dm_device);
+
+    let object_name = make_object_path("filesystem", uuid);
 
     let interface_name = format!("{}.{}", STRATIS_BASE_SERVICE, "filesystem");
 
@@ -79,12 +122,20 @@ pub fn create_dbus_filesystem<'a>(
         .add(
             f.interface(interface_name, ())
                 .add_m(rename_method)
+                .add_m(set_read_ahead_method)
+                .add_m(set_protected_method)
                 .add_p(devnode_property)
                 .add_p(name_property)
                 .add_p(pool_property)
                 .add_p(uuid_property)
                 .add_p(created_property)
-                .add_p(used_property),
+                .add_p(used_property)
+                .add_p(guaranteed_property)
+                .add_p(created_by_property)
+                .add_p(read_ahead_property)
+                .add_p(size_limit_property)
+                .add_p(protected_property)
+                .add_p(dm_device_property),
         );
 
     let path = object_path.get_name().to_owned();
@@ -110,33 +161,131 @@ fn rename_filesystem(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let filesystem_data = get_data!(filesystem_path; default_return; return_message);
 
     let pool_path = get_parent!(m; filesystem_data; default_return; return_message);
-    let pool_uuid = get_data!(pool_path; default_return; return_message).uuid;
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+    let filesystem_uuid = filesystem_data.filesystem_uuid();
 
     let mut engine = dbus_context.engine.borrow_mut();
     let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
 
-    let msg = match pool.rename_filesystem(&pool_name, filesystem_data.uuid, new_name) {
+    let msg = match pool.rename_filesystem(&pool_name, filesystem_uuid, new_name) {
         Ok(RenameAction::NoSource) => {
             let error_message = format!(
                 "pool {} doesn't know about filesystem {}",
-                pool_uuid, filesystem_data.uuid
+                pool_uuid, filesystem_uuid
             );
-            let (rc, rs) = (u16::from(DbusErrorEnum::INTERNAL_ERROR), error_message);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::INTERNAL_ERROR, &error_message);
+            return_message.append3(default_return, rc, rs).append1(details)
         }
         Ok(RenameAction::Identity) => {
-            return_message.append3(default_return, msg_code_ok(), msg_string_ok())
+            return_message
+                .append3(default_return, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
         }
-        Ok(RenameAction::Renamed) => return_message.append3(true, msg_code_ok(), msg_string_ok()),
+        Ok(RenameAction::Renamed) => return_message
+            .append3(true, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
         Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
         }
     };
 
     Ok(vec![msg])
 }
 
+fn set_read_ahead(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let read_ahead: (bool, u64) = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let filesystem_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let filesystem_data = get_data!(filesystem_path; default_return; return_message);
+
+    let pool_path = get_parent!(m; filesystem_data; default_return; return_message);
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+    let filesystem_uuid = filesystem_data.filesystem_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let read_ahead = tuple_to_option(read_ahead).map(dbus_to_sectors);
+    match pool.set_filesystem_read_ahead(&pool_name, filesystem_uuid, read_ahead) {
+        Ok(changed) => {
+            let mut msgs = vec![
+                return_message
+                    .append3(changed, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            if changed {
+                msgs.push(prop_changed_signal(
+                    consts::FILESYSTEM_READ_AHEAD_PROP,
+                    option_to_tuple(read_ahead.map(sectors_to_dbus), String::new()),
+                    object_path,
+                ));
+            }
+            Ok(msgs)
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            Ok(vec![return_message.append3(default_return, rc, rs).append1(details)])
+        }
+    }
+}
+
+fn set_protected(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let protected: bool = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let filesystem_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let filesystem_data = get_data!(filesystem_path; default_return; return_message);
+
+    let pool_path = get_parent!(m; filesystem_data; default_return; return_message);
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+    let filesystem_uuid = filesystem_data.filesystem_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    match pool.set_filesystem_protected(&pool_name, filesystem_uuid, protected) {
+        Ok(changed) => {
+            let mut msgs = vec![
+                return_message
+                    .append3(changed, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            if changed {
+                msgs.push(prop_changed_signal(
+                    consts::FILESYSTEM_PROTECTED_PROP,
+                    protected,
+                    object_path,
+                ));
+            }
+            Ok(msgs)
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            Ok(vec![return_message.append3(default_return, rc, rs).append1(details)])
+        }
+    }
+}
+
 /// Get a filesystem property and place it on the D-Bus. The property is
 /// found by means of the getter method which takes a reference to a
 /// Filesystem and obtains the property from the filesystem.
@@ -172,13 +321,13 @@ where
         .get_data()
         .as_ref()
         .ok_or_else(|| MethodErr::failed(&format!("no data for object path {}", object_path)))?
-        .uuid;
+        .pool_uuid();
 
     let engine = dbus_context.engine.borrow();
     let (pool_name, pool) = engine.get_pool(pool_uuid).ok_or_else(|| {
         MethodErr::failed(&format!("no pool corresponding to uuid {}", &pool_uuid))
     })?;
-    let filesystem_uuid = filesystem_data.uuid;
+    let filesystem_uuid = filesystem_data.filesystem_uuid();
     let (fs_name, fs) = pool.get_filesystem(filesystem_uuid).ok_or_else(|| {
         MethodErr::failed(&format!(
             "no name for filesystem with uuid {}",
@@ -219,6 +368,71 @@ fn get_filesystem_created(
     })
 }
 
+fn get_filesystem_guaranteed(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(fs.guaranteed()))
+}
+
+fn get_filesystem_created_by(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(creator_to_tuple(fs.created_by())))
+}
+
+fn get_filesystem_read_ahead(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, fs)| {
+        Ok(option_to_tuple(
+            fs.read_ahead().map(sectors_to_dbus),
+            String::new(),
+        ))
+    })
+}
+
+fn get_filesystem_size_limit(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, fs)| {
+        Ok(option_to_tuple(
+            fs.size_limit().map(sectors_to_dbus),
+            String::new(),
+        ))
+    })
+}
+
+fn get_filesystem_protected(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(fs.protected()))
+}
+
+/// Get the name, uuid, which is empty if it has none, and devnode of the
+/// filesystem's DM device, if it has one.
+fn get_filesystem_dm_device(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, fs)| {
+        Ok(option_to_tuple(
+            fs.dm_device().map(|device| {
+                (
+                    device.name,
+                    device.uuid.unwrap_or_default(),
+                    format!("{}", device.devnode.display()),
+                )
+            }),
+            (String::new(), String::new(), String::new()),
+        ))
+    })
+}
+
 /// Get the number of bytes used for any purpose on the filesystem
 fn get_filesystem_used(
     i: &mut IterAppend,
@@ -226,7 +440,7 @@ fn get_filesystem_used(
 ) -> Result<(), MethodErr> {
     get_filesystem_property(i, p, |(_, _, fs)| {
         fs.used()
-            .map(|v| (*v).to_string())
+            .map(bytes_to_dbus)
             .map_err(|_| MethodErr::failed(&"fs used() engine call failed".to_owned()))
     })
 }
@@ -0,0 +1,243 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Jobs, through which a long-running method call can be put off until its
+// caller has been answered, so that the caller is not left to hit the bus's
+// timeout with no way to learn what came of the call. A method that may
+// take long is put off if its last call took longer than the processing
+// budget configured for the daemon. Its caller is answered at once with an
+// error that names the job, and may query the job for the outcome of the
+// call once the call has been handled.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use dbus::{Message, MessageType};
+
+use super::super::engine::Creator;
+
+use super::audit::AuditRecord;
+use super::types::DbusErrorEnum;
+use super::util::STRATIS_BASE_SERVICE;
+
+/// The methods which may take long enough that they are put off as jobs.
+const LONG_RUNNING_METHODS: &[&str] = &[
+    "AddCacheDevs",
+    "AddDataDevs",
+    "ApplyAddDataDevsPlan",
+    "CheckFilesystem",
+    "CreateFilesystems",
+    "CreatePool",
+    "CreatePoolWithFilesystems",
+    "CreatePrefixedFilesystems",
+    "EmergencyReclaim",
+    "ReconstructPool",
+    "RemoveBlockDevs",
+    "ReplaceBlockDev",
+    "RepairPool",
+    "SnapshotFilesystem",
+];
+
+/// A method call put off as a job, with what was learned about it when it
+/// was received.
+#[derive(Debug)]
+pub struct QueuedCall {
+    pub id: u64,
+    pub msg: Message,
+    pub caller: Option<Creator>,
+    pub audit_record: Option<AuditRecord>,
+}
+
+/// The state of a job.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum JobState {
+    /// The call has not yet been handled.
+    Running,
+    /// The call has been handled, with this return code and string.
+    Finished(u16, String),
+}
+
+/// The jobs, both those waiting to be handled and those finished, whose
+/// outcome has not yet been queried.
+#[derive(Debug, Default)]
+pub struct JobTable {
+    budget: Option<Duration>,
+    next_id: u64,
+    queue: VecDeque<QueuedCall>,
+    states: HashMap<u64, JobState>,
+    last_durations: HashMap<String, Duration>,
+}
+
+impl JobTable {
+    /// A table of jobs for the given processing budget. If there is no
+    /// budget, no call is put off.
+    pub fn new(budget: Option<Duration>) -> JobTable {
+        JobTable {
+            budget,
+            ..JobTable::default()
+        }
+    }
+
+    /// Whether a call of method ought to be put off, because it is
+    /// long-running and took longer than the budget when it was last
+    /// called.
+    pub fn should_defer(&self, method: &str) -> bool {
+        match (self.budget, self.last_durations.get(method)) {
+            (Some(budget), Some(last)) => LONG_RUNNING_METHODS.contains(&method) && *last > budget,
+            _ => false,
+        }
+    }
+
+    /// Record how long a call of method took to handle. Warn if it took
+    /// longer than the budget, as the caller may have given up waiting.
+    pub fn record_duration(&mut self, method: &str, duration: Duration) {
+        if let Some(budget) = self.budget {
+            if duration > budget && LONG_RUNNING_METHODS.contains(&method) {
+                warn!(
+                    "{} took {}s, longer than the budget of {}s; later calls will be run as jobs",
+                    method,
+                    duration.as_secs(),
+                    budget.as_secs()
+                );
+            }
+        }
+        self.last_durations.insert(method.to_owned(), duration);
+    }
+
+    /// Put off the method call msg as a job, and return the answer to it.
+    pub fn defer(
+        &mut self,
+        msg: Message,
+        caller: Option<Creator>,
+        audit_record: Option<AuditRecord>,
+    ) -> Message {
+        let id = self.next_id;
+        self.next_id += 1;
+        let reply = continuing_reply(&msg, id);
+        info!(
+            "{} put off as job {}",
+            msg.member().map_or_else(String::new, |m| (*m).to_owned()),
+            id
+        );
+        self.states.insert(id, JobState::Running);
+        self.queue.push_back(QueuedCall {
+            id,
+            msg,
+            caller,
+            audit_record,
+        });
+        reply
+    }
+
+    /// Whether any job waits to be handled.
+    pub fn has_queued(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// Take the job which has waited longest to be handled.
+    pub fn next_queued(&mut self) -> Option<QueuedCall> {
+        self.queue.pop_front()
+    }
+
+    /// Record the outcome of the job, as found in the reply to its call.
+    pub fn finish(&mut self, id: u64, reply: Option<&Message>) {
+        self.states.insert(id, outcome(reply));
+    }
+
+    /// The state of the job. A finished job is forgotten once its outcome
+    /// has been queried.
+    pub fn query(&mut self, id: u64) -> Option<JobState> {
+        let state = self.states.get(&id).cloned();
+        if let Some(JobState::Finished(_, _)) = state {
+            self.states.remove(&id);
+        }
+        state
+    }
+}
+
+/// The name of the error with which a call that is put off is answered.
+fn continuing_error_name() -> String {
+    format!("{}.Error.OperationContinuing", STRATIS_BASE_SERVICE)
+}
+
+/// The answer to the method call msg, which has been put off as job id. Its
+/// arguments are a message, and the number of the job.
+fn continuing_reply(msg: &Message, id: u64) -> Message {
+    let text = format!("operation continuing in background, query job {}", id);
+    Message::new_error(msg, &continuing_error_name(), &text)
+        .expect("error name and message are valid")
+        .append1(id)
+}
+
+/// The return code and string of a reply to a method call. An error reply,
+/// or no reply at all, is an internal error.
+fn outcome(reply: Option<&Message>) -> JobState {
+    let (rc, rs) = match reply {
+        Some(reply) if reply.msg_type() == MessageType::MethodReturn => {
+            let mut iter = reply.iter_init();
+            let rc = if iter.next() { iter.get::<u16>() } else { None };
+            let rs = if iter.next() { iter.get::<&str>() } else { None };
+            match (rc, rs) {
+                (Some(rc), Some(rs)) => (rc, rs.to_owned()),
+                _ => (
+                    DbusErrorEnum::INTERNAL_ERROR.into(),
+                    "reply had no return code and string".to_owned(),
+                ),
+            }
+        }
+        Some(reply) => (
+            DbusErrorEnum::INTERNAL_ERROR.into(),
+            reply.get1::<&str>().unwrap_or("method failed").to_owned(),
+        ),
+        None => (
+            DbusErrorEnum::INTERNAL_ERROR.into(),
+            "method gave no reply".to_owned(),
+        ),
+    };
+    JobState::Finished(rc, rs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that a long-running method is put off only once a call of it
+    /// has taken longer than the budget, that other methods are never put
+    /// off, and that nothing is put off if there is no budget.
+    fn test_should_defer() {
+        let mut jobs = JobTable::new(Some(Duration::from_secs(10)));
+        assert!(!jobs.should_defer("CreatePool"));
+        jobs.record_duration("CreatePool", Duration::from_secs(5));
+        assert!(!jobs.should_defer("CreatePool"));
+        jobs.record_duration("CreatePool", Duration::from_secs(15));
+        assert!(jobs.should_defer("CreatePool"));
+        jobs.record_duration("CreatePool", Duration::from_secs(5));
+        assert!(!jobs.should_defer("CreatePool"));
+
+        jobs.record_duration("ListFilesystems", Duration::from_secs(15));
+        assert!(!jobs.should_defer("ListFilesystems"));
+
+        let mut jobs = JobTable::new(None);
+        jobs.record_duration("CreatePool", Duration::from_secs(15));
+        assert!(!jobs.should_defer("CreatePool"));
+    }
+
+    #[test]
+    /// Verify that a finished job is forgotten once it has been queried,
+    /// but a running job is not.
+    fn test_query() {
+        let mut jobs = JobTable::new(None);
+        jobs.states.insert(0, JobState::Running);
+        assert_eq!(jobs.query(0), Some(JobState::Running));
+        assert_eq!(jobs.query(0), Some(JobState::Running));
+
+        jobs.finish(0, None);
+        match jobs.query(0) {
+            Some(JobState::Finished(_, _)) => (),
+            state => panic!("job should be finished, but is {:?}", state),
+        }
+        assert_eq!(jobs.query(0), None);
+    }
+}
@@ -10,8 +10,8 @@ macro_rules! get_data {
             data
         } else {
             let message = format!("no data for object path {}", $path.get_name());
-            let (rc, rs) = (u16::from(DbusErrorEnum::INTERNAL_ERROR), message);
-            return Ok(vec![$message.append3($default, rc, rs)]);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::INTERNAL_ERROR, &message);
+            return Ok(vec![$message.append3($default, rc, rs).append1(details)]);
         }
     };
 }
@@ -24,8 +24,8 @@ macro_rules! get_parent {
             parent
         } else {
             let message = format!("no path for object path {}", $data.parent);
-            let (rc, rs) = (u16::from(DbusErrorEnum::INTERNAL_ERROR), message);
-            return Ok(vec![$message.append3($default, rc, rs)]);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::INTERNAL_ERROR, &message);
+            return Ok(vec![$message.append3($default, rc, rs).append1(details)]);
         }
     };
 }
@@ -37,8 +37,8 @@ macro_rules! get_mut_pool {
             pool
         } else {
             let message = format!("engine does not know about pool with uuid {}", $uuid);
-            let (rc, rs) = (u16::from(DbusErrorEnum::INTERNAL_ERROR), message);
-            return Ok(vec![$message.append3($default, rc, rs)]);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::INTERNAL_ERROR, &message);
+            return Ok(vec![$message.append3($default, rc, rs).append1(details)]);
         }
     };
 }
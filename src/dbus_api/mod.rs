@@ -6,12 +6,22 @@
 mod macros;
 
 mod api;
+mod audit;
+mod auth;
 mod blockdev;
 pub mod consts;
 mod filesystem;
+mod jobs;
 mod pool;
 mod types;
 mod util;
 
-pub use self::api::{connect, handle, register_pool, DbusConnectionData};
-pub use self::util::prop_changed_dispatch;
+pub use self::api::{
+    connect, handle, jobs_queued, register_new_objects, register_pool, run_job,
+    run_snapshot_schedules, DbusConnectionData,
+};
+pub use self::util::{
+    clock_skew_signal, lifecycle_signal, option_to_tuple, pool_state_signal, prop_changed_dispatch,
+    remove_progress_signal, setup_progress_signal, state_changed_dispatch,
+    thin_meta_extended_signal, usage_threshold_signal, watermark_crossed_signal,
+};
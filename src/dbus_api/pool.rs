@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::vec::Vec;
 
+use chrono::SecondsFormat;
 use dbus;
 use dbus::arg::{Array, IterAppend};
 use dbus::tree::{
@@ -17,7 +18,10 @@ use uuid::Uuid;
 
 use devicemapper::Sectors;
 
-use super::super::engine::{BlockDevTier, MaybeDbusPath, Name, Pool, RenameAction};
+use super::super::engine::{
+    commands, BlockDevTier, DevUuid, FilesystemUuid, MaybeDbusPath, Name, Pool, PoolUuid,
+    RenameAction, Watermarks,
+};
 
 use super::blockdev::create_dbus_blockdev;
 use super::consts;
@@ -25,8 +29,10 @@ use super::filesystem::create_dbus_filesystem;
 use super::types::{DbusContext, DbusErrorEnum, OPContext, TData};
 
 use super::util::{
-    engine_to_dbus_err_tuple, get_next_arg, get_uuid, msg_code_ok, msg_string_ok,
-    STRATIS_BASE_PATH, STRATIS_BASE_SERVICE,
+    creator_to_tuple, dbus_err_tuple, dbus_to_sectors, engine_to_dbus_err_tuple,
+    format_versions_to_tuple, get_next_arg, get_uuid, lifecycle_signal, make_object_path,
+    msg_code_ok, msg_details_ok, msg_string_ok, option_to_tuple, prop_changed_signal,
+    sectors_to_dbus, tuple_to_option, STRATIS_BASE_SERVICE,
 };
 
 fn create_filesystems(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
@@ -34,6 +40,9 @@ fn create_filesystems(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let mut iter = message.iter_init();
 
     let filesystems: Array<&str, _> = get_next_arg(&mut iter, 0)?;
+    let size: (bool, u64) = get_next_arg(&mut iter, 1)?;
+    let guaranteed: bool = get_next_arg(&mut iter, 2)?;
+    let size = tuple_to_option(size).map(dbus_to_sectors);
     let dbus_context = m.tree.get_data();
 
     let object_path = m.path.get_name();
@@ -42,56 +51,144 @@ fn create_filesystems(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
 
     if filesystems.count() > 1 {
         let error_message = "only 1 filesystem per request allowed";
-        let (rc, rs) = (u16::from(DbusErrorEnum::ERROR), error_message);
-        return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::ERROR, &error_message);
+        return Ok(vec![return_message.append3(default_return, rc, rs).append1(details)]);
     }
 
     let pool_path = m.tree
         .get(object_path)
         .expect("implicit argument must be in tree");
-    let pool_uuid = get_data!(pool_path; default_return; return_message).uuid;
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let result = commands::create_filesystems(
+        &mut *engine,
+        pool_uuid,
+        &filesystems
+            .map(|x| (x, size, guaranteed))
+            .collect::<Vec<(&str, Option<Sectors>, bool)>>(),
+        dbus_context.caller.borrow().clone(),
+    );
+
+    let msgs = match result {
+        Ok(ref infos) => {
+            let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+            let return_value = create_dbus_filesystems(dbus_context, object_path, pool, infos);
+            let signals = filesystem_created_signals(&return_value, infos);
+            let mut msgs = vec![
+                return_message
+                    .append3(return_value, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            msgs.extend(signals);
+            msgs
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+    Ok(msgs)
+}
+
+fn create_prefixed_filesystems(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let prefix: &str = get_next_arg(&mut iter, 0)?;
+    let count: u16 = get_next_arg(&mut iter, 1)?;
+    let size: (bool, u64) = get_next_arg(&mut iter, 2)?;
+    let guaranteed: bool = get_next_arg(&mut iter, 3)?;
+    let size = tuple_to_option(size).map(dbus_to_sectors);
+    let dbus_context = m.tree.get_data();
+
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: Vec<(dbus::Path, &str)> = Vec::new();
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
 
     let mut engine = dbus_context.engine.borrow_mut();
     let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
 
-    let result = pool.create_filesystems(
+    let result = pool.create_prefixed_filesystems(
         pool_uuid,
         &pool_name,
-        &filesystems
-            .map(|x| (x, None))
-            .collect::<Vec<(&str, Option<Sectors>)>>(),
+        prefix,
+        count as usize,
+        size,
+        guaranteed,
+        dbus_context.caller.borrow().clone(),
     );
 
-    let msg = match result {
-        Ok(ref infos) => {
-            let return_value = infos
+    let msgs = match result {
+        Ok(created) => {
+            let infos = created
                 .iter()
-                .map(|&(name, uuid)| {
-                    (
-                        // FIXME: To avoid this expect, modify create_filesystem
-                        // so that it returns a mutable reference to the
-                        // filesystem created.
-                        create_dbus_filesystem(
-                            dbus_context,
-                            object_path.clone(),
-                            uuid,
-                            pool.get_mut_filesystem(uuid)
-                                .expect("just inserted by create_filesystems")
-                                .1,
-                        ),
-                        name,
-                    )
-                })
+                .map(|&(ref name, uuid)| (&**name, uuid))
                 .collect::<Vec<_>>();
-
-            return_message.append3(return_value, msg_code_ok(), msg_string_ok())
+            let return_value = create_dbus_filesystems(dbus_context, object_path, pool, &infos);
+            let signals = filesystem_created_signals(&return_value, &infos);
+            let mut msgs = vec![
+                return_message
+                    .append3(return_value, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            msgs.extend(signals);
+            msgs
         }
         Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
         }
     };
-    Ok(vec![msg])
+    Ok(msgs)
+}
+
+/// Create D-Bus objects for the filesystems just created in pool, with
+/// the given names and uuids.
+fn create_dbus_filesystems<'a, 'b>(
+    dbus_context: &DbusContext,
+    pool_path: &dbus::Path<'static>,
+    pool: &mut Pool,
+    infos: &[(&'b str, FilesystemUuid)],
+) -> Vec<(dbus::Path<'a>, &'b str)> {
+    infos
+        .iter()
+        .map(|&(name, uuid)| {
+            (
+                // FIXME: To avoid this expect, modify create_filesystem
+                // so that it returns a mutable reference to the
+                // filesystem created.
+                create_dbus_filesystem(
+                    dbus_context,
+                    pool_path.clone(),
+                    uuid,
+                    pool.get_mut_filesystem(uuid)
+                        .expect("just inserted by create_filesystems")
+                        .1,
+                ),
+                name,
+            )
+        })
+        .collect()
+}
+
+/// Make a FilesystemCreated signal for each filesystem just created.
+fn filesystem_created_signals(
+    fs_paths: &[(dbus::Path, &str)],
+    infos: &[(&str, FilesystemUuid)],
+) -> Vec<Message> {
+    fs_paths
+        .iter()
+        .zip(infos.iter())
+        .map(|(&(ref fs_path, _), &(_, uuid))| {
+            lifecycle_signal(consts::FILESYSTEM_CREATED_SIGNAL, fs_path, uuid)
+        })
+        .collect()
 }
 
 fn destroy_filesystems(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
@@ -103,47 +200,123 @@ fn destroy_filesystems(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let dbus_context = m.tree.get_data();
     let object_path = m.path.get_name();
     let return_message = message.method_return();
-    let default_return: Vec<&str> = Vec::new();
+    let default_return: Vec<(String, bool, String)> = Vec::new();
 
     let pool_path = m.tree
         .get(object_path)
         .expect("implicit argument must be in tree");
-    let pool_uuid = get_data!(pool_path; default_return; return_message).uuid;
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
 
     let mut engine = dbus_context.engine.borrow_mut();
     let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
 
-    let mut filesystem_map: HashMap<Uuid, dbus::Path<'static>> = HashMap::new();
+    let mut filesystem_map: HashMap<FilesystemUuid, dbus::Path<'static>> = HashMap::new();
     for op in filesystems {
         if let Some(filesystem_path) = m.tree.get(&op) {
-            let filesystem_uuid = get_data!(filesystem_path; default_return; return_message).uuid;
+            let filesystem_uuid =
+                get_data!(filesystem_path; default_return; return_message).filesystem_uuid();
             filesystem_map.insert(filesystem_uuid, op);
         }
     }
 
     let result = pool.destroy_filesystems(
         &pool_name,
-        &filesystem_map.keys().cloned().collect::<Vec<Uuid>>(),
+        &filesystem_map.keys().cloned().collect::<Vec<FilesystemUuid>>(),
     );
-    let msg = match result {
-        Ok(ref uuids) => {
-            for uuid in uuids {
+    let msgs = match result {
+        Ok(ref results) => {
+            let mut signals = Vec::new();
+            let mut return_value = Vec::new();
+            for &(uuid, ref result) in results {
                 let op = filesystem_map
-                    .get(uuid)
-                    .expect("'uuids' is a subset of filesystem_map.keys()");
-                dbus_context.actions.borrow_mut().push_remove(op, m.tree);
+                    .get(&uuid)
+                    .expect("the uuids of 'results' are a subset of filesystem_map.keys()");
+                let error = match *result {
+                    Ok(_) => {
+                        dbus_context.actions.borrow_mut().push_remove(op, m.tree);
+                        signals.push(lifecycle_signal(
+                            consts::FILESYSTEM_DESTROYED_SIGNAL,
+                            op,
+                            uuid,
+                        ));
+                        String::new()
+                    }
+                    Err(ref err) => engine_to_dbus_err_tuple(err).1,
+                };
+                return_value.push((format!("{}", uuid.simple()), result.is_ok(), error));
             }
 
-            let return_value: Vec<String> =
-                uuids.iter().map(|n| format!("{}", n.simple())).collect();
-            return_message.append3(return_value, msg_code_ok(), msg_string_ok())
+            let mut msgs = vec![
+                return_message
+                    .append3(return_value, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            msgs.extend(signals);
+            msgs
         }
         Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
         }
     };
-    Ok(vec![msg])
+    Ok(msgs)
+}
+
+fn emergency_reclaim(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let target: u8 = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: Vec<dbus::Path> = Vec::new();
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    // The object paths of the filesystems must be found before they are
+    // destroyed.
+    let filesystem_paths = pool.filesystems()
+        .into_iter()
+        .filter_map(|(_, uuid, fs)| fs.get_dbus_path().0.clone().map(|path| (uuid, path)))
+        .collect::<HashMap<_, _>>();
+
+    let msgs = match pool.emergency_reclaim(&pool_name, target) {
+        Ok(destroyed) => {
+            let mut signals = Vec::new();
+            let mut return_value = Vec::new();
+            for (_, uuid) in destroyed {
+                if let Some(op) = filesystem_paths.get(&uuid) {
+                    dbus_context.actions.borrow_mut().push_remove(op, m.tree);
+                    signals.push(lifecycle_signal(
+                        consts::FILESYSTEM_DESTROYED_SIGNAL,
+                        op,
+                        uuid,
+                    ));
+                    return_value.push(op.clone());
+                }
+            }
+            let mut msgs = vec![
+                return_message
+                    .append3(return_value, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            msgs.extend(signals);
+            msgs
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+    Ok(msgs)
 }
 
 fn snapshot_filesystem(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
@@ -151,7 +324,8 @@ fn snapshot_filesystem(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let mut iter = message.iter_init();
 
     let filesystem: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
-    let snapshot_name: &str = get_next_arg(&mut iter, 0)?;
+    let snapshot_name: &str = get_next_arg(&mut iter, 1)?;
+    let freeze: bool = get_next_arg(&mut iter, 2)?;
 
     let dbus_context = m.tree.get_data();
     let object_path = m.path.get_name();
@@ -161,29 +335,152 @@ fn snapshot_filesystem(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let pool_path = m.tree
         .get(object_path)
         .expect("implicit argument must be in tree");
-    let pool_uuid = get_data!(pool_path; default_return; return_message).uuid;
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
 
     let fs_uuid = match m.tree.get(&filesystem) {
-        Some(op) => get_data!(op; default_return; return_message).uuid,
+        Some(op) => get_data!(op; default_return; return_message).filesystem_uuid(),
         None => {
             let message = format!("no data for object path {}", filesystem);
-            let (rc, rs) = (u16::from(DbusErrorEnum::NOTFOUND), message);
-            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &message);
+            return Ok(vec![return_message.append3(default_return, rc, rs).append1(details)]);
         }
     };
 
     let mut engine = dbus_context.engine.borrow_mut();
     let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
 
-    let msg = match pool.snapshot_filesystem(pool_uuid, &pool_name, fs_uuid, snapshot_name) {
+    let result = pool.snapshot_filesystem(pool_uuid, &pool_name, fs_uuid, snapshot_name, freeze);
+    let msgs = match result {
         Ok((uuid, fs)) => {
             let fs_object_path: dbus::Path =
                 create_dbus_filesystem(dbus_context, object_path.clone(), uuid, fs);
-            return_message.append3(fs_object_path, msg_code_ok(), msg_string_ok())
+            let signal =
+                lifecycle_signal(consts::FILESYSTEM_CREATED_SIGNAL, &fs_object_path, uuid);
+            vec![
+                return_message
+                    .append3(fs_object_path, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+                signal,
+            ]
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+
+    Ok(msgs)
+}
+
+fn check_filesystem(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let filesystem: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let repair: bool = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = (false, String::new());
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let fs_uuid = match m.tree.get(&filesystem) {
+        Some(op) => get_data!(op; default_return; return_message).filesystem_uuid(),
+        None => {
+            let message = format!("no data for object path {}", filesystem);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &message);
+            return Ok(vec![return_message.append3(default_return, rc, rs).append1(details)]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.check_filesystem(fs_uuid, repair) {
+        Ok(result) => return_message
+            .append3(result, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+
+    Ok(vec![msg])
+}
+
+/// Estimate the space that destroying each filesystem of the pool would
+/// return to it, which is the space the filesystem alone maps. The
+/// filesystems are listed with those that would return the most first.
+fn get_reclaim_estimates(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: Vec<(dbus::Path<'static>, String)> = Vec::new();
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.filesystem_space() {
+        Ok(mut space) => {
+            space.sort_by(|&(_, a, _), &(_, b, _)| b.cmp(&a));
+            let estimates = space
+                .into_iter()
+                .filter_map(|(uuid, exclusive, _)| {
+                    pool.get_filesystem(uuid).map(|(_, fs)| {
+                        (
+                            fs.get_dbus_path().0.clone().unwrap_or_default(),
+                            sectors_to_dbus(exclusive),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            return_message
+                .append3(estimates, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
         }
         Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+
+    Ok(vec![msg])
+}
+
+fn replay_metadata(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: Vec<String> = Vec::new();
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.replay_metadata(pool_uuid) {
+        Ok(anomalies) => return_message
+            .append3(anomalies, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
         }
     };
 
@@ -195,6 +492,12 @@ fn add_blockdevs(m: &MethodInfo<MTFn<TData>, TData>, tier: BlockDevTier) -> Meth
     let mut iter = message.iter_init();
 
     let devs: Array<&str, _> = get_next_arg(&mut iter, 1)?;
+    let signature_policy: (bool, u16) = get_next_arg(&mut iter, 2)?;
+    let cache_args: Option<((bool, u16), bool)> = if tier == BlockDevTier::Cache {
+        Some((get_next_arg(&mut iter, 3)?, get_next_arg(&mut iter, 4)?))
+    } else {
+        None
+    };
 
     let dbus_context = m.tree.get_data();
     let object_path = m.path.get_name();
@@ -204,44 +507,348 @@ fn add_blockdevs(m: &MethodInfo<MTFn<TData>, TData>, tier: BlockDevTier) -> Meth
     let pool_path = m.tree
         .get(object_path)
         .expect("implicit argument must be in tree");
-    let pool_uuid = get_data!(pool_path; default_return; return_message).uuid;
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
 
     let mut engine = dbus_context.engine.borrow_mut();
     let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
 
     let blockdevs = devs.map(|x| Path::new(x)).collect::<Vec<&Path>>();
 
-    let result = pool.add_blockdevs(pool_uuid, &*pool_name, &blockdevs, tier);
-    let msg = match result {
+    let result = match cache_args {
+        Some((cache_type, volatile)) => pool.add_cachedevs(
+            pool_uuid,
+            &*pool_name,
+            &blockdevs,
+            tuple_to_option(signature_policy),
+            tuple_to_option(cache_type),
+            volatile,
+        ),
+        None => pool.add_blockdevs(
+            pool_uuid,
+            &*pool_name,
+            &blockdevs,
+            tier,
+            tuple_to_option(signature_policy),
+        ),
+    };
+    let msgs = match result {
         Ok(uuids) => {
-            let return_value = uuids
+            let return_value = create_dbus_blockdevs(dbus_context, object_path, pool, &uuids);
+            let signals = device_added_signals(&return_value, &uuids);
+            let mut msgs = vec![
+                return_message
+                    .append3(return_value, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            msgs.extend(signals);
+            msgs
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+
+    Ok(msgs)
+}
+
+fn remove_blockdevs(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let blockdevs: Array<dbus::Path<'static>, _> = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: Vec<dbus::Path> = Vec::new();
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut blockdev_map: HashMap<DevUuid, dbus::Path<'static>> = HashMap::new();
+    for op in blockdevs {
+        let blockdev_uuid = match m.tree.get(&op) {
+            Some(blockdev_path) => {
+                let data = get_data!(blockdev_path; default_return; return_message);
+                if data.parent == *object_path {
+                    Some(data.dev_uuid())
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+        match blockdev_uuid {
+            Some(uuid) => {
+                blockdev_map.insert(uuid, op);
+            }
+            None => {
+                let message = format!("no blockdev of the pool at {}", op);
+                let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &message);
+                return Ok(vec![return_message.append3(default_return, rc, rs).append1(details)]);
+            }
+        }
+    }
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let uuids = blockdev_map.keys().cloned().collect::<Vec<_>>();
+    let msgs = match pool.remove_blockdevs(pool_uuid, &pool_name, &uuids) {
+        Ok(_) => {
+            let mut signals = Vec::new();
+            let mut return_value = Vec::new();
+            for (uuid, op) in blockdev_map {
+                dbus_context.actions.borrow_mut().push_remove(&op, m.tree);
+                signals.push(lifecycle_signal(consts::DEVICE_REMOVED_SIGNAL, &op, uuid));
+                return_value.push(op);
+            }
+
+            let mut msgs = vec![
+                return_message
+                    .append3(return_value, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            msgs.extend(signals);
+            msgs
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+    Ok(msgs)
+}
+
+fn replace_blockdev(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let old_path: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let new: &str = get_next_arg(&mut iter, 1)?;
+    let signature_policy: (bool, u16) = get_next_arg(&mut iter, 2)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = dbus::Path::default();
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let old_uuid = match m.tree.get(&old_path) {
+        Some(blockdev_path) => {
+            let data = get_data!(blockdev_path; default_return; return_message);
+            if data.parent == *object_path {
+                Some(data.dev_uuid())
+            } else {
+                None
+            }
+        }
+        None => None,
+    };
+    let old_uuid = match old_uuid {
+        Some(uuid) => uuid,
+        None => {
+            let message = format!("no blockdev of the pool at {}", old_path);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &message);
+            return Ok(vec![return_message.append3(default_return, rc, rs).append1(details)]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let result = pool.replace_blockdev(
+        pool_uuid,
+        &pool_name,
+        old_uuid,
+        Path::new(new),
+        tuple_to_option(signature_policy),
+    );
+
+    // A replacement that is cut short leaves the new device in the pool, so
+    // it is given an object whether or not the replacement is done.
+    let unregistered = pool.blockdevs()
+        .into_iter()
+        .filter(|&(_, bd)| bd.get_dbus_path().0.is_none())
+        .map(|(uuid, _)| uuid)
+        .collect::<Vec<_>>();
+    let added = create_dbus_blockdevs(dbus_context, object_path, pool, &unregistered);
+    let mut signals = device_added_signals(&added, &unregistered);
+
+    let msgs = match result {
+        Ok(new_uuid) => {
+            let new_path = pool.get_blockdev(new_uuid)
+                .and_then(|(_, bd)| bd.get_dbus_path().0.clone())
+                .expect("every blockdev of the pool was just given an object");
+            dbus_context.actions.borrow_mut().push_remove(&old_path, m.tree);
+            signals.push(lifecycle_signal(consts::DEVICE_REMOVED_SIGNAL, &old_path, old_uuid));
+
+            vec![
+                return_message
+                    .append3(new_path, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ]
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+    Ok(msgs.into_iter().chain(signals).collect())
+}
+
+/// Create D-Bus objects for the blockdevs with the given uuids, which
+/// have just been added to pool.
+fn create_dbus_blockdevs<'a>(
+    dbus_context: &DbusContext,
+    pool_path: &dbus::Path<'static>,
+    pool: &mut Pool,
+    uuids: &[DevUuid],
+) -> Vec<dbus::Path<'a>> {
+    uuids
+        .iter()
+        .map(|uuid| {
+            // FIXME: To avoid this expect, modify add_blockdevs
+            // so that it returns a mutable reference to each
+            // blockdev created.
+            create_dbus_blockdev(
+                dbus_context,
+                pool_path.clone(),
+                *uuid,
+                pool.get_mut_blockdev(*uuid)
+                    .expect("just inserted by add_blockdevs")
+                    .1,
+            )
+        })
+        .collect()
+}
+
+/// Make a DeviceAdded signal for each blockdev just added to a pool.
+fn device_added_signals(paths: &[dbus::Path], uuids: &[DevUuid]) -> Vec<Message> {
+    paths
+        .iter()
+        .zip(uuids.iter())
+        .map(|(path, uuid)| lifecycle_signal(consts::DEVICE_ADDED_SIGNAL, path, *uuid))
+        .collect()
+}
+
+fn plan_add_datadevs(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let devs: Array<&str, _> = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = (
+        String::new(),
+        String::new(),
+        Vec::<(String, String, String, (bool, bool), bool)>::new(),
+        Vec::<String>::new(),
+    );
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let blockdevs = devs.map(|x| Path::new(x)).collect::<Vec<&Path>>();
+
+    let msg = match pool.plan_add_datadevs(pool_uuid, &blockdevs) {
+        Ok((token, plan)) => {
+            let layout = plan.layout
                 .iter()
-                .map(|uuid| {
-                    // FIXME: To avoid this expect, modify add_blockdevs
-                    // so that it returns a mutable reference to each
-                    // blockdev created.
-                    create_dbus_blockdev(
-                        dbus_context,
-                        object_path.clone(),
-                        *uuid,
-                        pool.get_mut_blockdev(*uuid)
-                            .expect("just inserted by add_blockdevs")
-                            .1,
+                .map(|dev| {
+                    (
+                        dev.devnode.display().to_string(),
+                        sectors_to_dbus(dev.size),
+                        sectors_to_dbus(dev.usable),
+                        (dev.rotational.is_some(), dev.rotational.unwrap_or(false)),
+                        dev.new,
                     )
                 })
                 .collect::<Vec<_>>();
-
-            return_message.append3(return_value, msg_code_ok(), msg_string_ok())
+            let return_value = (
+                token.simple().to_string(),
+                sectors_to_dbus(plan.usable_capacity),
+                layout,
+                plan.warnings,
+            );
+            return_message
+                .append3(return_value, msg_code_ok(), msg_string_ok())
+                .append1(msg_details_ok())
         }
         Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
         }
     };
 
     Ok(vec![msg])
 }
 
+fn apply_add_datadevs_plan(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let token: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: Vec<dbus::Path> = Vec::new();
+
+    let token = match Uuid::parse_str(token) {
+        Ok(token) => token,
+        Err(_) => {
+            let error_message = format!("{} is not a valid plan token", token);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &error_message);
+            return Ok(vec![
+                return_message.append3(default_return, rc, rs).append1(details),
+            ]);
+        }
+    };
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msgs = match pool.apply_add_datadevs_plan(pool_uuid, &*pool_name, token) {
+        Ok(uuids) => {
+            let return_value = create_dbus_blockdevs(dbus_context, object_path, pool, &uuids);
+            let signals = device_added_signals(&return_value, &uuids);
+            let mut msgs = vec![
+                return_message
+                    .append3(return_value, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            msgs.extend(signals);
+            msgs
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            vec![return_message.append3(default_return, rc, rs).append1(details)]
+        }
+    };
+
+    Ok(msgs)
+}
+
 fn add_datadevs(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     add_blockdevs(m, BlockDevTier::Data)
 }
@@ -264,7 +871,7 @@ fn rename_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     let pool_path = m.tree
         .get(object_path)
         .expect("implicit argument must be in tree");
-    let pool_uuid = get_data!(pool_path; default_return; return_message).uuid;
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
 
     let msg = match dbus_context
         .engine
@@ -273,146 +880,1063 @@ fn rename_pool(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
     {
         Ok(RenameAction::NoSource) => {
             let error_message = format!("engine doesn't know about pool {}", &pool_uuid);
-            let (rc, rs) = (u16::from(DbusErrorEnum::INTERNAL_ERROR), error_message);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::INTERNAL_ERROR, &error_message);
+            return_message.append3(default_return, rc, rs).append1(details)
         }
-        Ok(RenameAction::Identity) => return_message.append3(false, msg_code_ok(), msg_string_ok()),
-        Ok(RenameAction::Renamed) => return_message.append3(true, msg_code_ok(), msg_string_ok()),
+        Ok(RenameAction::Identity) => return_message
+            .append3(false, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Ok(RenameAction::Renamed) => return_message
+            .append3(true, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
         Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return_message.append3(default_return, rc, rs)
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
         }
     };
     Ok(vec![msg])
 }
 
-/// Get a pool property and place it on the D-Bus. The property is
-/// found by means of the getter method which takes a reference to a
-/// Pool and obtains the property from the pool.
+fn set_verify_writes(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let verify: bool = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.set_verify_writes(pool_uuid, &pool_name, verify) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_allocation_strategy(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let strategy: (bool, u16) = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.set_allocation_strategy(pool_uuid, &pool_name, tuple_to_option(strategy)) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_worker_priority(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let niceness: i16 = get_next_arg(&mut iter, 0)?;
+    let io_class: (bool, u16) = get_next_arg(&mut iter, 1)?;
+    let io_level: u8 = get_next_arg(&mut iter, 2)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.set_worker_priority(
+        pool_uuid,
+        &pool_name,
+        niceness,
+        tuple_to_option(io_class),
+        io_level,
+    ) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn shrink_reserved_space(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = sectors_to_dbus(Sectors(0));
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (_, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.shrink_reserved_space() {
+        Ok(reclaimed) => return_message
+            .append3(sectors_to_dbus(reclaimed), msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_default_filesystem_size(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let size: (bool, u64) = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let size = tuple_to_option(size).map(dbus_to_sectors);
+    let msg = match pool.set_default_filesystem_size(pool_uuid, &pool_name, size) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_overcommit_cap(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let cap: (bool, u64) = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.set_overcommit_cap(pool_uuid, &pool_name, tuple_to_option(cap)) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_fs_limit(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let limit: u64 = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.set_fs_limit(pool_uuid, &pool_name, limit) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_filesystem_limit(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let filesystem: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let limit: (bool, u64) = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let fs_uuid = match m.tree.get(&filesystem) {
+        Some(op) => get_data!(op; default_return; return_message).filesystem_uuid(),
+        None => {
+            let message = format!("no data for object path {}", filesystem);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &message);
+            return Ok(vec![return_message.append3(default_return, rc, rs).append1(details)]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let limit = tuple_to_option(limit).map(dbus_to_sectors);
+    match pool.set_filesystem_size_limit(&pool_name, fs_uuid, limit) {
+        Ok(changed) => {
+            let mut msgs = vec![
+                return_message
+                    .append3(changed, msg_code_ok(), msg_string_ok())
+                    .append1(msg_details_ok()),
+            ];
+            if changed {
+                msgs.push(prop_changed_signal(
+                    consts::FILESYSTEM_SIZE_LIMIT_PROP,
+                    option_to_tuple(limit.map(sectors_to_dbus), String::new()),
+                    &filesystem,
+                ));
+            }
+            Ok(msgs)
+        }
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            Ok(vec![return_message.append3(default_return, rc, rs).append1(details)])
+        }
+    }
+}
+
+fn set_snapshot_schedule(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let filesystem: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let schedule: (bool, (u16, u32)) = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let fs_uuid = match m.tree.get(&filesystem) {
+        Some(op) => get_data!(op; default_return; return_message).filesystem_uuid(),
+        None => {
+            let message = format!("no data for object path {}", filesystem);
+            let (rc, rs, details) = dbus_err_tuple(DbusErrorEnum::NOTFOUND, &message);
+            return Ok(vec![return_message.append3(default_return, rc, rs).append1(details)]);
+        }
+    };
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.set_snapshot_schedule(&pool_name, fs_uuid, tuple_to_option(schedule)) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_usage_thresholds(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let thresholds: Array<u8, _> = get_next_arg(&mut iter, 0)?;
+    let thresholds: Vec<u8> = thresholds.collect();
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.set_usage_thresholds(pool_uuid, &pool_name, &thresholds) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+fn set_watermarks(m: &MethodInfo<MTFn<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let warn: (bool, u8) = get_next_arg(&mut iter, 0)?;
+    let extend: (bool, u8) = get_next_arg(&mut iter, 1)?;
+    let watermarks = Watermarks {
+        warn: tuple_to_option(warn),
+        extend: tuple_to_option(extend),
+    };
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = get_data!(pool_path; default_return; return_message).pool_uuid();
+
+    let mut engine = dbus_context.engine.borrow_mut();
+    let (pool_name, pool) = get_mut_pool!(engine; pool_uuid; default_return; return_message);
+
+    let msg = match pool.set_watermarks(pool_uuid, &pool_name, watermarks) {
+        Ok(changed) => return_message
+            .append3(changed, msg_code_ok(), msg_string_ok())
+            .append1(msg_details_ok()),
+        Err(err) => {
+            let (rc, rs, details) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs).append1(details)
+        }
+    };
+    Ok(vec![msg])
+}
+
+/// Get a pool property and place it on the D-Bus. The property is
+/// found by means of the getter method which takes a reference to a
+/// Pool and obtains the property from the pool.
 fn get_pool_property<F, R>(
     i: &mut IterAppend,
     p: &PropInfo<MTFn<TData>, TData>,
-    getter: F,
-) -> Result<(), MethodErr>
-where
-    F: Fn((Name, Uuid, &Pool)) -> Result<R, MethodErr>,
-    R: dbus::arg::Append,
-{
+    getter: F,
+) -> Result<(), MethodErr>
+where
+    F: Fn((Name, PoolUuid, &Pool)) -> Result<R, MethodErr>,
+    R: dbus::arg::Append,
+{
+    let dbus_context = p.tree.get_data();
+    let object_path = p.path.get_name();
+    let pool_path = p.tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+
+    let pool_uuid = pool_path
+        .get_data()
+        .as_ref()
+        .ok_or_else(|| MethodErr::failed(&format!("no data for object path {}", object_path)))?
+        .pool_uuid();
+
+    let engine = dbus_context.engine.borrow();
+    let (pool_name, pool) = engine.get_pool(pool_uuid).ok_or_else(|| {
+        MethodErr::failed(&format!("no pool corresponding to uuid {}", &pool_uuid))
+    })?;
+
+    i.append(getter((pool_name, pool_uuid, pool))?);
+    Ok(())
+}
+
+fn get_pool_name(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(name, _, _)| Ok(name.to_owned()))
+}
+
+fn get_pool_total_physical_used(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    fn get_used((_, uuid, pool): (Name, PoolUuid, &Pool)) -> Result<String, MethodErr> {
+        let err_func = |_| {
+            MethodErr::failed(&format!(
+                "no total physical size computed for pool with uuid {}",
+                uuid
+            ))
+        };
+
+        pool.total_physical_used()
+            .map(|u| Ok(sectors_to_dbus(u)))
+            .map_err(err_func)?
+    }
+
+    get_pool_property(i, p, get_used)
+}
+
+fn get_pool_total_physical_size(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, p)| {
+        Ok(sectors_to_dbus(p.total_physical_size()))
+    })
+}
+
+fn get_pool_worker_priority(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        let priority = pool.worker_priority();
+        Ok((
+            i16::from(priority.niceness),
+            u16::from(priority.io_class),
+            priority.io_level,
+        ))
+    })
+}
+
+fn get_pool_allocation_strategy(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(u16::from(pool.allocation_strategy()))
+    })
+}
+
+fn get_pool_cache_type(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(pool.cache_type().map(u16::from), 0))
+    })
+}
+
+fn get_pool_cache_volatile(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.cache_volatile()))
+}
+
+fn get_pool_cache_usage(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    fn get_usage(
+        (_, uuid, pool): (Name, PoolUuid, &Pool),
+    ) -> Result<(bool, (String, String, String)), MethodErr> {
+        let usage = pool.cache_usage().map_err(|_| {
+            MethodErr::failed(&format!(
+                "no cache usage computed for pool with uuid {}",
+                uuid
+            ))
+        })?;
+        Ok(option_to_tuple(
+            usage.map(|usage| {
+                (
+                    sectors_to_dbus(usage.used),
+                    sectors_to_dbus(usage.dirty),
+                    sectors_to_dbus(usage.total),
+                )
+            }),
+            (String::new(), String::new(), String::new()),
+        ))
+    }
+
+    get_pool_property(i, p, get_usage)
+}
+
+fn get_pool_state(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.state().to_dbus_value()))
+}
+
+fn get_pool_state_name(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.state().to_string()))
+}
+
+fn get_pool_extend_state(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.extend_state().to_dbus_value()))
+}
+
+fn get_pool_extend_state_name(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.extend_state().to_string()))
+}
+
+fn get_space_state(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(pool.free_space_state().to_dbus_value())
+    })
+}
+
+fn get_space_state_name(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.free_space_state().to_string()))
+}
+
+fn get_days_until_full(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(pool.days_until_full(), 0))
+    })
+}
+
+fn get_overcommit_percent(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(pool.overcommit_percent(), 0))
+    })
+}
+
+fn get_overcommit_cap(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(pool.overcommit_cap(), 0))
+    })
+}
+
+fn get_usage_thresholds(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.usage_thresholds()))
+}
+
+fn get_warn_watermark(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(pool.watermarks().warn, 0))
+    })
+}
+
+fn get_extend_watermark(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(pool.watermarks().extend, 0))
+    })
+}
+
+fn get_default_filesystem_size(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(sectors_to_dbus(pool.default_filesystem_size()))
+    })
+}
+
+fn get_fs_limit(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.fs_limit()))
+}
+
+fn get_filesystem_count(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.filesystems().len() as u64))
+}
+
+fn get_thin_meta_total(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(
+            pool.thin_meta_usage().map(|(total, _)| sectors_to_dbus(total)),
+            String::new(),
+        ))
+    })
+}
+
+fn get_thin_meta_used(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(
+            pool.thin_meta_usage().map(|(_, used)| sectors_to_dbus(used)),
+            String::new(),
+        ))
+    })
+}
+
+fn get_pool_created_by(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(creator_to_tuple(pool.created_by())))
+}
+
+fn get_pool_encrypted(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.encrypted()))
+}
+
+/// The description of the key kept in the kernel keyring for the pool,
+/// which the engine, rather than the pool, knows of.
+fn get_pool_key_description(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
     let dbus_context = p.tree.get_data();
     let object_path = p.path.get_name();
-    let pool_path = p.tree
+    let pool_uuid = p.tree
         .get(object_path)
-        .expect("implicit argument must be in tree");
-
-    let pool_uuid = pool_path
+        .expect("implicit argument must be in tree")
         .get_data()
         .as_ref()
         .ok_or_else(|| MethodErr::failed(&format!("no data for object path {}", object_path)))?
-        .uuid;
-
-    let engine = dbus_context.engine.borrow();
-    let (pool_name, pool) = engine.get_pool(pool_uuid).ok_or_else(|| {
-        MethodErr::failed(&format!("no pool corresponding to uuid {}", &pool_uuid))
-    })?;
+        .pool_uuid();
 
-    i.append(getter((pool_name, pool_uuid, pool))?);
+    let description = dbus_context
+        .engine
+        .borrow()
+        .key_description(pool_uuid)
+        .map_err(|err| {
+            MethodErr::failed(&format!(
+                "no key description found for pool with uuid {}: {}",
+                pool_uuid, err
+            ))
+        })?;
+    i.append(option_to_tuple(description, String::new()));
     Ok(())
 }
 
-fn get_pool_name(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<(), MethodErr> {
-    get_pool_property(i, p, |(name, _, _)| Ok(name.to_owned()))
+fn get_pool_creation_mda_size(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(
+            pool.creation_params()
+                .map(|params| sectors_to_dbus(params.mda_size)),
+            String::new(),
+        ))
+    })
 }
 
-fn get_pool_total_physical_used(
+fn get_pool_creation_chunk_size(
     i: &mut IterAppend,
     p: &PropInfo<MTFn<TData>, TData>,
 ) -> Result<(), MethodErr> {
-    fn get_used((_, uuid, pool): (Name, Uuid, &Pool)) -> Result<String, MethodErr> {
-        let err_func = |_| {
-            MethodErr::failed(&format!(
-                "no total physical size computed for pool with uuid {}",
-                uuid
-            ))
-        };
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(
+            pool.creation_params()
+                .map(|params| sectors_to_dbus(params.chunk_size)),
+            String::new(),
+        ))
+    })
+}
 
-        pool.total_physical_used()
-            .map(|u| Ok(format!("{}", *u)))
-            .map_err(err_func)?
-    }
+fn get_pool_creation_redundancy(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(
+            pool.creation_params()
+                .map(|params| u16::from(params.redundancy)),
+            0,
+        ))
+    })
+}
 
-    get_pool_property(i, p, get_used)
+fn get_pool_creation_encrypted(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(
+            pool.creation_params().map(|params| params.encrypted),
+            false,
+        ))
+    })
 }
 
-fn get_pool_total_physical_size(
+fn get_pool_creation_version(
     i: &mut IterAppend,
     p: &PropInfo<MTFn<TData>, TData>,
 ) -> Result<(), MethodErr> {
-    get_pool_property(i, p, |(_, _, p)| {
-        Ok(format!("{}", *p.total_physical_size()))
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(option_to_tuple(
+            pool.creation_params()
+                .map(|params| params.daemon_version.clone()),
+            String::new(),
+        ))
     })
 }
 
-fn get_pool_state(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<(), MethodErr> {
-    get_pool_property(i, p, |(_, _, pool)| Ok(pool.state().to_dbus_value()))
+fn get_pool_verify_writes(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(pool.verify_writes()))
 }
 
-fn get_pool_extend_state(
+fn get_pool_operation_stuck(
     i: &mut IterAppend,
     p: &PropInfo<MTFn<TData>, TData>,
 ) -> Result<(), MethodErr> {
-    get_pool_property(i, p, |(_, _, pool)| Ok(pool.extend_state().to_dbus_value()))
+    get_pool_property(i, p, |(_, uuid, pool)| Ok(pool.operation_stuck(uuid)))
 }
 
-fn get_space_state(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<(), MethodErr> {
+fn get_pool_last_metadata_update(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
     get_pool_property(i, p, |(_, _, pool)| {
-        Ok(pool.free_space_state().to_dbus_value())
+        Ok(option_to_tuple(
+            pool.last_metadata_update()
+                .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            String::new(),
+        ))
+    })
+}
+
+fn get_pool_metadata_behind_devs(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(pool.metadata_behind_blockdevs()
+            .into_iter()
+            .filter_map(|uuid| {
+                pool.get_blockdev(uuid)
+                    .and_then(|(_, bd)| bd.get_dbus_path().0.clone())
+            })
+            .collect::<Vec<dbus::Path<'static>>>())
+    })
+}
+
+fn get_pool_format_versions(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(format_versions_to_tuple(pool.format_versions())))
+}
+
+/// Get the DM devices of the pool, each as its role, name, uuid, which is
+/// empty if it has none, and devnode.
+fn get_pool_dm_devices(
+    i: &mut IterAppend,
+    p: &PropInfo<MTFn<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(pool.dm_devices()
+            .into_iter()
+            .map(|(role, device)| {
+                (
+                    role,
+                    device.name,
+                    device.uuid.unwrap_or_default(),
+                    format!("{}", device.devnode.display()),
+                )
+            })
+            .collect::<Vec<_>>())
     })
 }
 
 pub fn create_dbus_pool<'a>(
     dbus_context: &DbusContext,
     parent: dbus::Path<'static>,
-    uuid: Uuid,
+    uuid: PoolUuid,
     pool: &mut Pool,
 ) -> dbus::Path<'a> {
     let f = Factory::new_fn();
 
     let create_filesystems_method = f.method("CreateFilesystems", (), create_filesystems)
         .in_arg(("specs", "as"))
+        .in_arg(("size", "(bt)"))
+        .in_arg(("guaranteed", "b"))
         .out_arg(("filesystems", "a(os)"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let create_prefixed_filesystems_method =
+        f.method("CreatePrefixedFilesystems", (), create_prefixed_filesystems)
+            .in_arg(("prefix", "s"))
+            .in_arg(("count", "q"))
+            .in_arg(("size", "(bt)"))
+            .in_arg(("guaranteed", "b"))
+            .out_arg(("filesystems", "a(os)"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
 
     let destroy_filesystems_method = f.method("DestroyFilesystems", (), destroy_filesystems)
         .in_arg(("filesystems", "ao"))
-        .out_arg(("results", "as"))
+        .out_arg(("results", "a(sbs)"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
 
     let add_blockdevs_method = f.method("AddDataDevs", (), add_datadevs)
         .in_arg(("devices", "as"))
+        .in_arg(("signature_policy", "(bq)"))
         .out_arg(("results", "ao"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let plan_add_datadevs_method = f.method("PlanAddDataDevs", (), plan_add_datadevs)
+        .in_arg(("devices", "as"))
+        .out_arg(("plan", "(ssa(sss(bb)b)as)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let apply_add_datadevs_plan_method =
+        f.method("ApplyAddDataDevsPlan", (), apply_add_datadevs_plan)
+            .in_arg(("plan", "s"))
+            .out_arg(("results", "ao"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
 
     let add_cachedevs_method = f.method("AddCacheDevs", (), add_cachedevs)
         .in_arg(("devices", "as"))
+        .in_arg(("signature_policy", "(bq)"))
+        .in_arg(("cache_type", "(bq)"))
+        .in_arg(("volatile", "b"))
         .out_arg(("results", "ao"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
 
     let rename_method = f.method("SetName", (), rename_pool)
         .in_arg(("name", "s"))
         .out_arg(("action", "b"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
 
     let snapshot_method = f.method("SnapshotFilesystem", (), snapshot_filesystem)
         .in_arg(("origin", "o"))
         .in_arg(("snapshot_name", "s"))
+        .in_arg(("freeze", "b"))
         .out_arg(("result", "o"))
         .out_arg(("return_code", "q"))
-        .out_arg(("return_string", "s"));
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let check_filesystem_method = f.method("CheckFilesystem", (), check_filesystem)
+        .in_arg(("filesystem", "o"))
+        .in_arg(("repair", "b"))
+        .out_arg(("result", "(bs)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let emergency_reclaim_method = f.method("EmergencyReclaim", (), emergency_reclaim)
+        .in_arg(("target", "y"))
+        .out_arg(("destroyed", "ao"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let get_reclaim_estimates_method =
+        f.method("GetReclaimEstimates", (), get_reclaim_estimates)
+            .out_arg(("estimates", "a(os)"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let remove_blockdevs_method = f.method("RemoveBlockDevs", (), remove_blockdevs)
+        .in_arg(("blockdevs", "ao"))
+        .out_arg(("removed", "ao"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let replace_blockdev_method = f.method("ReplaceBlockDev", (), replace_blockdev)
+        .in_arg(("blockdev", "o"))
+        .in_arg(("device", "s"))
+        .in_arg(("signature_policy", "(bq)"))
+        .out_arg(("replacement", "o"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let replay_metadata_method = f.method("ReplayMetadata", (), replay_metadata)
+        .out_arg(("anomalies", "as"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_allocation_strategy_method =
+        f.method("SetAllocationStrategy", (), set_allocation_strategy)
+            .in_arg(("strategy", "(bq)"))
+            .out_arg(("changed", "b"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let shrink_reserved_space_method = f.method("ShrinkReservedSpace", (), shrink_reserved_space)
+        .out_arg(("reclaimed", "s"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_worker_priority_method = f.method("SetWorkerPriority", (), set_worker_priority)
+        .in_arg(("niceness", "n"))
+        .in_arg(("io_class", "(bq)"))
+        .in_arg(("io_level", "y"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_verify_writes_method = f.method("SetVerifyWrites", (), set_verify_writes)
+        .in_arg(("verify", "b"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_overcommit_cap_method = f.method("SetOvercommitCap", (), set_overcommit_cap)
+        .in_arg(("cap", "(bt)"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_usage_thresholds_method = f.method("SetUsageThresholds", (), set_usage_thresholds)
+        .in_arg(("thresholds", "ay"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_watermarks_method = f.method("SetWatermarks", (), set_watermarks)
+        .in_arg(("warn", "(by)"))
+        .in_arg(("extend", "(by)"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_default_filesystem_size_method =
+        f.method("SetDefaultFilesystemSize", (), set_default_filesystem_size)
+            .in_arg(("size", "(bt)"))
+            .out_arg(("changed", "b"))
+            .out_arg(("return_code", "q"))
+            .out_arg(("return_string", "s"))
+            .out_arg(("return_details", "(sa{ss})"));
+
+    let set_fs_limit_method = f.method("SetFsLimit", (), set_fs_limit)
+        .in_arg(("limit", "t"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_filesystem_limit_method = f.method("SetFilesystemLimit", (), set_filesystem_limit)
+        .in_arg(("filesystem", "o"))
+        .in_arg(("limit", "(bt)"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
+
+    let set_snapshot_schedule_method = f.method("SetSnapshotSchedule", (), set_snapshot_schedule)
+        .in_arg(("filesystem", "o"))
+        .in_arg(("schedule", "(b(qu))"))
+        .out_arg(("changed", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+        .out_arg(("return_details", "(sa{ss})"));
 
     let name_property = f.property::<&str, _>(consts::POOL_NAME_PROP, ())
         .access(Access::Read)
@@ -439,21 +1963,215 @@ pub fn create_dbus_pool<'a>(
         .emits_changed(EmitsChangedSignal::True)
         .on_get(get_pool_state);
 
+    let state_name_property = f.property::<&str, _>(consts::POOL_STATE_NAME_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_pool_state_name);
+
     let extend_state_property = f.property::<u16, _>(consts::POOL_EXTEND_STATE_PROP, ())
         .access(Access::Read)
         .emits_changed(EmitsChangedSignal::True)
         .on_get(get_pool_extend_state);
 
+    let extend_state_name_property =
+        f.property::<&str, _>(consts::POOL_EXTEND_STATE_NAME_PROP, ())
+            .access(Access::Read)
+            .emits_changed(EmitsChangedSignal::True)
+            .on_get(get_pool_extend_state_name);
+
     let space_state_property = f.property::<u16, _>(consts::POOL_SPACE_STATE_PROP, ())
         .access(Access::Read)
         .emits_changed(EmitsChangedSignal::True)
         .on_get(get_space_state);
 
-    let object_name = format!(
-        "{}/{}",
-        STRATIS_BASE_PATH,
-        dbus_context.get_next_id().to_string()
-    );
+    let space_state_name_property = f.property::<&str, _>(consts::POOL_SPACE_STATE_NAME_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_space_state_name);
+
+    let days_until_full_property =
+        f.property::<(bool, u64), _>(consts::POOL_DAYS_UNTIL_FULL_PROP, ())
+            .access(Access::Read)
+            .emits_changed(EmitsChangedSignal::True)
+            .on_get(get_days_until_full);
+
+    let overcommit_percent_property = f.property::<(bool, u64), _>("OvercommitPercent", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_overcommit_percent);
+
+    let overcommit_cap_property = f.property::<(bool, u64), _>("OvercommitCap", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_overcommit_cap);
+
+    let usage_thresholds_property = f.property::<Vec<u8>, _>("UsageThresholds", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_usage_thresholds);
+
+    let usage_threshold_crossed_signal = f.signal(consts::USAGE_THRESHOLD_CROSSED_SIGNAL, ())
+        .sarg::<u8, _>("threshold")
+        .sarg::<bool, _>("rising")
+        .sarg::<&str, _>("used")
+        .sarg::<&str, _>("total");
+
+    let warn_watermark_property = f.property::<(bool, u8), _>("WarnWatermark", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_warn_watermark);
+
+    let extend_watermark_property = f.property::<(bool, u8), _>("ExtendWatermark", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_extend_watermark);
+
+    let watermark_crossed_signal = f.signal(consts::WATERMARK_CROSSED_SIGNAL, ())
+        .sarg::<&str, _>("watermark")
+        .sarg::<u8, _>("percent")
+        .sarg::<bool, _>("rising")
+        .sarg::<&str, _>("used")
+        .sarg::<&str, _>("total");
+
+    let metadata_clock_skew_signal = f.signal(consts::METADATA_CLOCK_SKEW_SIGNAL, ())
+        .sarg::<&str, _>("proposed")
+        .sarg::<&str, _>("newest");
+
+    let default_filesystem_size_property = f.property::<&str, _>("DefaultFilesystemSize", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_default_filesystem_size);
+
+    let fs_limit_property = f.property::<u64, _>("FsLimit", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_fs_limit);
+
+    let filesystem_count_property = f.property::<u64, _>("FilesystemCount", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_filesystem_count);
+
+    let thin_meta_total_property = f.property::<(bool, &str), _>("ThinMetaTotal", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_thin_meta_total);
+
+    let thin_meta_used_property = f.property::<(bool, &str), _>("ThinMetaUsed", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_thin_meta_used);
+
+    let thin_meta_extended_signal = f.signal(consts::THIN_META_EXTENDED_SIGNAL, ())
+        .sarg::<bool, _>("succeeded")
+        .sarg::<&str, _>("total")
+        .sarg::<&str, _>("reason");
+
+    let remove_progress_signal = f.signal(consts::REMOVE_PROGRESS_SIGNAL, ())
+        .sarg::<&str, _>("moved")
+        .sarg::<&str, _>("total");
+
+    let pool_state_changed_signal = f.signal(consts::POOL_STATE_CHANGED_SIGNAL, ())
+        .sarg::<u16, _>("state")
+        .sarg::<&str, _>("state_name");
+
+    let created_by_property = f.property::<(bool, (u32, &str)), _>("CreatedBy", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_created_by);
+
+    let encrypted_property = f.property::<bool, _>("Encrypted", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_encrypted);
+
+    let key_description_property = f.property::<(bool, &str), _>("KeyDescription", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_key_description);
+
+    let creation_mda_size_property = f.property::<(bool, &str), _>("CreationMdaSize", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_creation_mda_size);
+
+    let creation_chunk_size_property = f.property::<(bool, &str), _>("CreationChunkSize", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_creation_chunk_size);
+
+    let creation_redundancy_property = f.property::<(bool, u16), _>("CreationRedundancy", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_creation_redundancy);
+
+    let creation_encrypted_property = f.property::<(bool, bool), _>("CreationEncrypted", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_creation_encrypted);
+
+    let creation_version_property = f.property::<(bool, &str), _>("CreationVersion", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_creation_version);
+
+    let verify_writes_property = f.property::<bool, _>("VerifyWrites", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_verify_writes);
+
+    let operation_stuck_property = f.property::<bool, _>("OperationStuck", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_operation_stuck);
+
+    let last_metadata_update_property = f.property::<(bool, &str), _>("LastMetadataUpdate", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_last_metadata_update);
+
+    let metadata_behind_devs_property =
+        f.property::<Vec<dbus::Path<'static>>, _>("MetadataBehindDevs", ())
+            .access(Access::Read)
+            .emits_changed(EmitsChangedSignal::False)
+            .on_get(get_pool_metadata_behind_devs);
+
+    let allocation_strategy_property = f.property::<u16, _>("AllocationStrategy", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_allocation_strategy);
+
+    let worker_priority_property = f.property::<(i16, u16, u8), _>("WorkerPriority", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_worker_priority);
+
+    let cache_type_property = f.property::<(bool, u16), _>("CacheType", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_cache_type);
+
+    let cache_volatile_property = f.property::<bool, _>("CacheVolatile", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_cache_volatile);
+
+    let cache_usage_property = f.property::<(bool, (&str, &str, &str)), _>("CacheUsage", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_cache_usage);
+
+    let format_versions_property = f.property::<(u16, u16, u16), _>("FormatVersions", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_format_versions);
+
+    let dm_devices_property = f.property::<Vec<(&str, &str, &str, &str)>, _>("DmDevices", ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_dm_devices);
+
+    let object_name = make_object_path("pool", uuid);
 
     let interface_name = format!("{}.{}", STRATIS_BASE_SERVICE, "pool");
 
@@ -462,18 +2180,77 @@ pub fn create_dbus_pool<'a>(
         .add(
             f.interface(interface_name, ())
                 .add_m(create_filesystems_method)
+                .add_m(create_prefixed_filesystems_method)
                 .add_m(destroy_filesystems_method)
                 .add_m(snapshot_method)
+                .add_m(check_filesystem_method)
+                .add_m(get_reclaim_estimates_method)
+                .add_m(replay_metadata_method)
+                .add_m(emergency_reclaim_method)
                 .add_m(add_blockdevs_method)
+                .add_m(plan_add_datadevs_method)
+                .add_m(apply_add_datadevs_plan_method)
+                .add_m(remove_blockdevs_method)
+                .add_m(replace_blockdev_method)
                 .add_m(add_cachedevs_method)
                 .add_m(rename_method)
+                .add_m(set_verify_writes_method)
+                .add_m(set_allocation_strategy_method)
+                .add_m(set_worker_priority_method)
+                .add_m(shrink_reserved_space_method)
+                .add_m(set_overcommit_cap_method)
+                .add_m(set_usage_thresholds_method)
+                .add_m(set_watermarks_method)
+                .add_m(set_default_filesystem_size_method)
+                .add_m(set_fs_limit_method)
+                .add_m(set_filesystem_limit_method)
+                .add_m(set_snapshot_schedule_method)
                 .add_p(name_property)
                 .add_p(total_physical_size_property)
                 .add_p(total_physical_used_property)
                 .add_p(uuid_property)
                 .add_p(state_property)
+                .add_p(state_name_property)
                 .add_p(space_state_property)
-                .add_p(extend_state_property),
+                .add_p(space_state_name_property)
+                .add_p(extend_state_property)
+                .add_p(extend_state_name_property)
+                .add_p(days_until_full_property)
+                .add_p(overcommit_percent_property)
+                .add_p(overcommit_cap_property)
+                .add_p(usage_thresholds_property)
+                .add_p(warn_watermark_property)
+                .add_p(extend_watermark_property)
+                .add_p(default_filesystem_size_property)
+                .add_p(fs_limit_property)
+                .add_p(filesystem_count_property)
+                .add_p(thin_meta_total_property)
+                .add_p(thin_meta_used_property)
+                .add_p(created_by_property)
+                .add_p(encrypted_property)
+                .add_p(key_description_property)
+                .add_p(creation_mda_size_property)
+                .add_p(creation_chunk_size_property)
+                .add_p(creation_redundancy_property)
+                .add_p(creation_encrypted_property)
+                .add_p(creation_version_property)
+                .add_p(verify_writes_property)
+                .add_p(operation_stuck_property)
+                .add_p(last_metadata_update_property)
+                .add_p(metadata_behind_devs_property)
+                .add_p(format_versions_property)
+                .add_p(allocation_strategy_property)
+                .add_p(cache_type_property)
+                .add_p(cache_volatile_property)
+                .add_p(cache_usage_property)
+                .add_p(dm_devices_property)
+                .add_p(worker_priority_property)
+                .add_s(usage_threshold_crossed_signal)
+                .add_s(watermark_crossed_signal)
+                .add_s(metadata_clock_skew_signal)
+                .add_s(thin_meta_extended_signal)
+                .add_s(remove_progress_signal)
+                .add_s(pool_state_changed_signal),
         );
 
     let path = object_path.get_name().to_owned();
@@ -2,17 +2,20 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::cell::{Cell, RefCell};
+use std::cell::RefCell;
 use std::collections::vec_deque::{Drain, VecDeque};
 use std::convert::From;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use dbus::tree::{DataType, MTFn, ObjectPath, Tree};
 use dbus::Path;
 
 use uuid::Uuid;
 
-use super::super::engine::Engine;
+use super::super::engine::{Creator, DevUuid, Engine, FilesystemUuid, PoolUuid};
+
+use super::jobs::JobTable;
 
 macro_attr! {
     #[derive(Clone, Copy, Debug)]
@@ -25,6 +28,7 @@ macro_attr! {
         BUSY,
         INTERNAL_ERROR,
         NOTFOUND,
+        NO_SPACE,
     }
 }
 
@@ -44,6 +48,21 @@ impl DbusErrorEnum {
             DbusErrorEnum::BUSY => "Operation can not be performed at this time",
             DbusErrorEnum::INTERNAL_ERROR => "Internal error",
             DbusErrorEnum::NOTFOUND => "Not found",
+            DbusErrorEnum::NO_SPACE => "Not enough space",
+        }
+    }
+
+    /// A stable identifier for the error, for use in structured error
+    /// details.
+    pub fn get_error_identifier(self) -> &'static str {
+        match self {
+            DbusErrorEnum::OK => "Ok",
+            DbusErrorEnum::ERROR => "Error",
+            DbusErrorEnum::ALREADY_EXISTS => "AlreadyExists",
+            DbusErrorEnum::BUSY => "Busy",
+            DbusErrorEnum::INTERNAL_ERROR => "InternalError",
+            DbusErrorEnum::NOTFOUND => "NotFound",
+            DbusErrorEnum::NO_SPACE => "NoSpace",
         }
     }
 }
@@ -54,45 +73,121 @@ pub enum DeferredAction {
     Remove(Path<'static>),
 }
 
+/// The UUID of the object for which an object path stands, of the type of
+/// the UUIDs of its kind.
+#[derive(Clone, Copy, Debug)]
+pub enum ObjectUuid {
+    Pool(PoolUuid),
+    Filesystem(FilesystemUuid),
+    Blockdev(DevUuid),
+}
+
+impl From<PoolUuid> for ObjectUuid {
+    fn from(uuid: PoolUuid) -> ObjectUuid {
+        ObjectUuid::Pool(uuid)
+    }
+}
+
+impl From<FilesystemUuid> for ObjectUuid {
+    fn from(uuid: FilesystemUuid) -> ObjectUuid {
+        ObjectUuid::Filesystem(uuid)
+    }
+}
+
+impl From<DevUuid> for ObjectUuid {
+    fn from(uuid: DevUuid) -> ObjectUuid {
+        ObjectUuid::Blockdev(uuid)
+    }
+}
+
 /// Context for an object path.
 /// Contains the object path of the parent as a Path and the UUID of the
-/// object itself.
+/// object itself. The context of every kind of object is the same type, so
+/// the UUID is kept with its kind; the handlers for each kind of object ask
+/// for the UUID of that kind.
 #[derive(Debug)]
 pub struct OPContext {
     pub(super) parent: Path<'static>,
-    pub(super) uuid: Uuid,
+    uuid: ObjectUuid,
 }
 
 impl OPContext {
-    pub fn new(parent: Path<'static>, uuid: Uuid) -> OPContext {
-        OPContext { parent, uuid }
+    pub fn new<U: Into<ObjectUuid>>(parent: Path<'static>, uuid: U) -> OPContext {
+        OPContext {
+            parent,
+            uuid: uuid.into(),
+        }
+    }
+
+    /// The UUID of the object, whatever its kind.
+    pub fn uuid(&self) -> Uuid {
+        match self.uuid {
+            ObjectUuid::Pool(uuid) => *uuid,
+            ObjectUuid::Filesystem(uuid) => *uuid,
+            ObjectUuid::Blockdev(uuid) => *uuid,
+        }
+    }
+
+    /// The UUID of the pool for which the object path stands.
+    /// Panics if it stands for some other kind of object, as only the
+    /// handlers of a pool's interface ask for it.
+    pub fn pool_uuid(&self) -> PoolUuid {
+        match self.uuid {
+            ObjectUuid::Pool(uuid) => uuid,
+            uuid => panic!("object path stands for {:?}, not a pool", uuid),
+        }
+    }
+
+    /// The UUID of the filesystem for which the object path stands.
+    /// Panics if it stands for some other kind of object.
+    pub fn filesystem_uuid(&self) -> FilesystemUuid {
+        match self.uuid {
+            ObjectUuid::Filesystem(uuid) => uuid,
+            uuid => panic!("object path stands for {:?}, not a filesystem", uuid),
+        }
+    }
+
+    /// The UUID of the blockdev for which the object path stands.
+    /// Panics if it stands for some other kind of object.
+    pub fn dev_uuid(&self) -> DevUuid {
+        match self.uuid {
+            ObjectUuid::Blockdev(uuid) => uuid,
+            uuid => panic!("object path stands for {:?}, not a blockdev", uuid),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct DbusContext {
-    pub(super) next_index: Rc<Cell<u64>>,
     pub(super) engine: Rc<RefCell<Engine>>,
     pub(super) actions: Rc<RefCell<ActionQueue>>,
+    /// The identity of the caller of the method being handled, if it is
+    /// one that records its caller.
+    pub(super) caller: Rc<RefCell<Option<Creator>>>,
+    /// The method calls put off as jobs.
+    pub(super) jobs: Rc<RefCell<JobTable>>,
+    /// When the daemon started.
+    pub(super) started: Instant,
+    /// How long the daemon took to set up its engine.
+    pub(super) startup: Duration,
 }
 
 impl DbusContext {
-    pub fn new(engine: Rc<RefCell<Engine>>) -> DbusContext {
+    pub fn new(
+        engine: Rc<RefCell<Engine>>,
+        budget: Option<Duration>,
+        started: Instant,
+        startup: Duration,
+    ) -> DbusContext {
         DbusContext {
             actions: Rc::new(RefCell::new(ActionQueue::default())),
+            caller: Rc::new(RefCell::new(None)),
             engine,
-            next_index: Rc::new(Cell::new(0)),
+            jobs: Rc::new(RefCell::new(JobTable::new(budget))),
+            started,
+            startup,
         }
     }
-
-    /// Generates a new id for object paths.
-    /// It is assumed that, while Stratisd is running, it will never generate
-    /// more than 2^64 object paths. If it turns out that this is a bad
-    /// assumption, the solution is to use unbounded integers.
-    pub fn get_next_id(&self) -> u64 {
-        self.next_index.set(self.next_index.get() + 1);
-        self.next_index.get()
-    }
 }
 
 #[derive(Default, Debug)]
@@ -2,24 +2,34 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
 
+use chrono::{DateTime, SecondsFormat, Utc};
 use dbus;
 use dbus::arg::{ArgType, Iter, IterAppend, RefArg, Variant};
 use dbus::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
 use dbus::tree::{MTFn, MethodErr, PropInfo};
-use dbus::Connection;
 use dbus::SignalArgs;
+use dbus::{Connection, Message};
 
-use devicemapper::DmError;
+use devicemapper::{Bytes, DmError, Sectors, SECTOR_SIZE};
+use uuid::Uuid;
 
+use super::super::engine::{count, Counter, Creator, FormatVersions, MetadataStats};
 use super::super::stratis::{ErrorEnum, StratisError};
 
+use super::consts;
 use super::types::{DbusErrorEnum, TData};
 
 pub const STRATIS_BASE_PATH: &str = "/org/storage/stratis1";
 pub const STRATIS_BASE_SERVICE: &str = "org.storage.stratis1";
 
+/// The time in milliseconds to wait for the bus to report the credentials
+/// of a caller.
+const CREDENTIALS_LOOKUP_TIMEOUT_MS: i32 = 1000;
+
 /// Convert a tuple as option to an Option type
 pub fn tuple_to_option<T>(value: (bool, T)) -> Option<T> {
     if value.0 {
@@ -29,6 +39,99 @@ pub fn tuple_to_option<T>(value: (bool, T)) -> Option<T> {
     }
 }
 
+/// Convert an Option type to a tuple as option, using default as the
+/// second element if the option is None
+pub fn option_to_tuple<T>(value: Option<T>, default: T) -> (bool, T) {
+    match value {
+        Some(value) => (true, value),
+        None => (false, default),
+    }
+}
+
+/// Convert a size in bytes to the representation of every size on the
+/// D-Bus interface: the number of bytes as a decimal string, which a client
+/// can hold without overflow whatever its integer types.
+pub fn bytes_to_dbus(bytes: Bytes) -> String {
+    (*bytes).to_string()
+}
+
+/// Convert a size in sectors to its representation on the D-Bus interface.
+pub fn sectors_to_dbus(sectors: Sectors) -> String {
+    bytes_to_dbus(sectors.bytes())
+}
+
+/// Convert a size in bytes given on the D-Bus interface to sectors,
+/// rounding up to a whole sector.
+pub fn dbus_to_sectors(bytes: u64) -> Sectors {
+    let sector_size = SECTOR_SIZE as u64;
+    Sectors(bytes / sector_size + if bytes % sector_size == 0 { 0 } else { 1 })
+}
+
+/// Convert format versions to a tuple of the sigblock, MDA header, and
+/// metadata versions, in that order.
+pub fn format_versions_to_tuple(versions: FormatVersions) -> (u16, u16, u16) {
+    (
+        u16::from(versions.sigblock),
+        u16::from(versions.mda_header),
+        u16::from(versions.metadata),
+    )
+}
+
+/// Convert counts of the outcomes of reads and writes of metadata to a
+/// dict from the name of each count to its value.
+pub fn metadata_stats_to_dict(stats: MetadataStats) -> HashMap<&'static str, u64> {
+    let mut dict = HashMap::new();
+    dict.insert("saves", stats.saves);
+    dict.insert("retried_saves", stats.retried_saves);
+    dict.insert("crc_repairs", stats.crc_repairs);
+    dict.insert("fallback_loads", stats.fallback_loads);
+    dict
+}
+
+/// Convert the creator of a pool or a filesystem to a tuple as option of
+/// its UID and process name. The name is empty if it is not known.
+pub fn creator_to_tuple(creator: Option<&Creator>) -> (bool, (u32, String)) {
+    option_to_tuple(
+        creator.map(|c| (c.uid, c.process.clone().unwrap_or_default())),
+        (0, String::new()),
+    )
+}
+
+/// Ask the bus for a credential of the process that sent msg, by calling
+/// the bus method of that name.
+fn caller_credential(c: &Connection, msg: &Message, method: &str) -> Option<u32> {
+    let sender = msg.sender()?;
+    let query = Message::new_method_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        method,
+    ).ok()?;
+    let query = query.append1(&*sender);
+    match c.send_with_reply_and_block(query, CREDENTIALS_LOOKUP_TIMEOUT_MS) {
+        Ok(reply) => reply.get1(),
+        Err(err) => {
+            warn!("{} failed for D-Bus caller {}: {}", method, &*sender, err);
+            None
+        }
+    }
+}
+
+/// Ask the bus for the UID of the process that sent msg.
+pub fn caller_uid(c: &Connection, msg: &Message) -> Option<u32> {
+    caller_credential(c, msg, "GetConnectionUnixUser")
+}
+
+/// The identity of the process that sent msg: its UID, and its name as
+/// found in procfs. None if the UID can not be found.
+pub fn caller_identity(c: &Connection, msg: &Message) -> Option<Creator> {
+    let uid = caller_uid(c, msg)?;
+    Some(match caller_credential(c, msg, "GetConnectionUnixProcessID") {
+        Some(pid) => Creator::of_process(uid, pid),
+        None => Creator { uid, process: None },
+    })
+}
+
 /// Get the next argument off the bus
 pub fn get_next_arg<'a, T>(iter: &mut Iter<'a>, loc: u16) -> Result<T, MethodErr>
 where
@@ -41,32 +144,48 @@ where
     Ok(value)
 }
 
-/// Translates an engine error to the (errorcode, string) tuple that Stratis
-/// D-Bus methods return.
-pub fn engine_to_dbus_err_tuple(err: &StratisError) -> (u16, String) {
-    let error = match *err {
-        StratisError::Error(_) => DbusErrorEnum::ERROR,
-        StratisError::Engine(ref e, _) => match *e {
-            ErrorEnum::Error => DbusErrorEnum::ERROR,
-            ErrorEnum::AlreadyExists => DbusErrorEnum::ALREADY_EXISTS,
-            ErrorEnum::Busy => DbusErrorEnum::BUSY,
-            ErrorEnum::Invalid => DbusErrorEnum::ERROR,
-            ErrorEnum::NotFound => DbusErrorEnum::NOTFOUND,
-        },
-        StratisError::Io(_) => DbusErrorEnum::ERROR,
-        StratisError::Nix(_) => DbusErrorEnum::ERROR,
-        StratisError::Uuid(_)
-        | StratisError::Utf8(_)
-        | StratisError::Serde(_)
-        | StratisError::DM(_)
-        | StratisError::Dbus(_)
-        | StratisError::Udev(_) => DbusErrorEnum::ERROR,
+/// The structured description of an error that Stratis D-Bus methods
+/// return alongside the return code and string: a stable identifier and
+/// named parameters.
+pub type ErrorDetails = (String, HashMap<String, String>);
+
+/// Translates an engine error to the (errorcode, string, details) tuple that
+/// Stratis D-Bus methods return.
+pub fn engine_to_dbus_err_tuple(err: &StratisError) -> (u16, String, ErrorDetails) {
+    let error = match err.code() {
+        ErrorEnum::Error => DbusErrorEnum::ERROR,
+        ErrorEnum::AlreadyExists => DbusErrorEnum::ALREADY_EXISTS,
+        ErrorEnum::Busy => DbusErrorEnum::BUSY,
+        ErrorEnum::Invalid => DbusErrorEnum::ERROR,
+        ErrorEnum::NoSpace => DbusErrorEnum::NO_SPACE,
+        ErrorEnum::NotFound => DbusErrorEnum::NOTFOUND,
     };
-    let description = match *err {
+    let description = match *err.root() {
         StratisError::DM(DmError::Core(ref err)) => err.to_string(),
+        StratisError::Structured(_, ref payload) => payload.render(),
         ref err => err.description().to_owned(),
     };
-    (error.into(), description)
+    let description = match err.context() {
+        Some(context) => format!("{}: {}", context, description),
+        None => description,
+    };
+    let payload = err.payload();
+    let details = (payload.id.to_owned(), payload.params.into_iter().collect());
+    count(Counter::Errors);
+    (error.into(), description, details)
+}
+
+/// The (errorcode, string, details) tuple for an error that arises in the
+/// D-Bus layer itself, rather than in the engine.
+pub fn dbus_err_tuple(error: DbusErrorEnum, message: &str) -> (u16, String, ErrorDetails) {
+    let mut params = HashMap::new();
+    params.insert("message".to_owned(), message.to_owned());
+    count(Counter::Errors);
+    (
+        error.into(),
+        message.to_owned(),
+        (error.get_error_identifier().to_owned(), params),
+    )
 }
 
 /// Convenience function to get the error value for "OK"
@@ -79,6 +198,14 @@ pub fn msg_string_ok() -> String {
     DbusErrorEnum::OK.get_error_string().to_owned()
 }
 
+/// Convenience function to get the error details for "OK"
+pub fn msg_details_ok() -> ErrorDetails {
+    (
+        DbusErrorEnum::OK.get_error_identifier().to_owned(),
+        HashMap::new(),
+    )
+}
+
 /// Get the UUID for an object path.
 pub fn get_uuid(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<(), MethodErr> {
     let object_path = p.path.get_name();
@@ -90,7 +217,7 @@ pub fn get_uuid(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Result<
         .as_ref()
         .ok_or_else(|| MethodErr::failed(&format!("no data for object path {}", object_path)))?;
 
-    i.append(format!("{}", data.uuid.simple()));
+    i.append(format!("{}", data.uuid().simple()));
     Ok(())
 }
 
@@ -109,6 +236,151 @@ pub fn get_parent(i: &mut IterAppend, p: &PropInfo<MTFn<TData>, TData>) -> Resul
     Ok(())
 }
 
+/// The object path of the object of the given kind with the given UUID.
+/// Paths are derived from UUIDs, rather than assigned in the order that
+/// objects are registered, so that an object keeps its path when the daemon
+/// is restarted.
+pub fn make_object_path<U: Into<Uuid>>(kind: &str, uuid: U) -> String {
+    format!("{}/{}/{}", STRATIS_BASE_PATH, kind, uuid.into().simple())
+}
+
+/// Make a signal of the Manager interface announcing that the object with
+/// the given path and UUID was created, destroyed, or has failed.
+pub fn lifecycle_signal<U: Into<Uuid>>(name: &str, object_path: &dbus::Path, uuid: U) -> Message {
+    Message::new_signal(
+        STRATIS_BASE_PATH,
+        format!("{}.{}", STRATIS_BASE_SERVICE, "Manager"),
+        name,
+    ).expect("the path, interface, and signal names are valid")
+        .append2(object_path.clone(), format!("{}", uuid.into().simple()))
+}
+
+/// Make a signal of the Manager interface announcing that the setup of the
+/// pool with the given name has reached step of total, at the given stage.
+pub fn setup_progress_signal(name: &str, step: u32, total: u32, stage: &str) -> Message {
+    Message::new_signal(
+        STRATIS_BASE_PATH,
+        format!("{}.{}", STRATIS_BASE_SERVICE, "Manager"),
+        consts::SETUP_PROGRESS_SIGNAL,
+    ).expect("the path, interface, and signal names are valid")
+        .append2(name, step)
+        .append2(total, stage)
+}
+
+/// Make a signal of the pool interface of the pool with the given path
+/// announcing that its state has changed to state, whose value on the
+/// D-Bus is value.
+pub fn pool_state_signal<S: fmt::Display>(pool_path: &dbus::Path, value: u16, state: S) -> Message {
+    Message::new_signal(
+        &**pool_path,
+        format!("{}.{}", STRATIS_BASE_SERVICE, "pool"),
+        consts::POOL_STATE_CHANGED_SIGNAL,
+    ).expect("the path, interface, and signal names are valid")
+        .append2(value, state.to_string())
+}
+
+/// Make a signal of the pool interface of the pool with the given path
+/// announcing that its data usage has crossed threshold, upward if rising.
+/// The used and total data space are given as sizes.
+pub fn usage_threshold_signal(
+    pool_path: &dbus::Path,
+    threshold: u8,
+    rising: bool,
+    used: Sectors,
+    total: Sectors,
+) -> Message {
+    Message::new_signal(
+        &**pool_path,
+        format!("{}.{}", STRATIS_BASE_SERVICE, "pool"),
+        consts::USAGE_THRESHOLD_CROSSED_SIGNAL,
+    ).expect("the path, interface, and signal names are valid")
+        .append2(threshold, rising)
+        .append2(sectors_to_dbus(used), sectors_to_dbus(total))
+}
+
+/// Make a signal of the pool interface of the pool with the given path
+/// announcing that its usage has crossed its watermark, of the given name
+/// and percentage, upward if rising. The used and total space are given as
+/// sizes.
+pub fn watermark_crossed_signal(
+    pool_path: &dbus::Path,
+    watermark: &str,
+    percent: u8,
+    rising: bool,
+    used: Sectors,
+    total: Sectors,
+) -> Message {
+    Message::new_signal(
+        &**pool_path,
+        format!("{}.{}", STRATIS_BASE_SERVICE, "pool"),
+        consts::WATERMARK_CROSSED_SIGNAL,
+    ).expect("the path, interface, and signal names are valid")
+        .append3(watermark, percent, rising)
+        .append2(sectors_to_dbus(used), sectors_to_dbus(total))
+}
+
+/// Make a signal of the pool interface of the pool with the given path
+/// announcing that the clock reads proposed, earlier than the time newest
+/// of the pool's most recently written metadata.
+pub fn clock_skew_signal(
+    pool_path: &dbus::Path,
+    proposed: &DateTime<Utc>,
+    newest: &DateTime<Utc>,
+) -> Message {
+    Message::new_signal(
+        &**pool_path,
+        format!("{}.{}", STRATIS_BASE_SERVICE, "pool"),
+        consts::METADATA_CLOCK_SKEW_SIGNAL,
+    ).expect("the path, interface, and signal names are valid")
+        .append2(
+            proposed.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            newest.to_rfc3339_opts(SecondsFormat::Nanos, true),
+        )
+}
+
+/// Make a signal of the pool interface of the pool with the given path
+/// announcing that the metadata device of its thin pool was extended
+/// automatically to total, or, if not succeeded, that it could not be, for
+/// reason.
+pub fn thin_meta_extended_signal(
+    pool_path: &dbus::Path,
+    succeeded: bool,
+    total: Sectors,
+    reason: &str,
+) -> Message {
+    Message::new_signal(
+        &**pool_path,
+        format!("{}.{}", STRATIS_BASE_SERVICE, "pool"),
+        consts::THIN_META_EXTENDED_SIGNAL,
+    ).expect("the path, interface, and signal names are valid")
+        .append3(succeeded, sectors_to_dbus(total), reason)
+}
+
+/// Make a signal of the pool interface of the pool with the given path
+/// announcing that moved of the total data on the blockdevs being removed
+/// from it has been moved to its other blockdevs.
+pub fn remove_progress_signal(pool_path: &dbus::Path, moved: Sectors, total: Sectors) -> Message {
+    Message::new_signal(
+        &**pool_path,
+        format!("{}.{}", STRATIS_BASE_SERVICE, "pool"),
+        consts::REMOVE_PROGRESS_SIGNAL,
+    ).expect("the path, interface, and signal names are valid")
+        .append2(sectors_to_dbus(moved), sectors_to_dbus(total))
+}
+
+/// Make a signal that a property of the object with the given path has
+/// changed to new_value, for a method to return with its reply.
+pub fn prop_changed_signal<T: 'static>(prop_name: &str, new_value: T, path: &dbus::Path) -> Message
+where
+    T: RefArg,
+{
+    let mut prop_changed: PropertiesPropertiesChanged = Default::default();
+    prop_changed
+        .changed_properties
+        .insert(prop_name.into(), Variant(Box::new(new_value)));
+    prop_changed.to_emit_message(path)
+}
+
 /// Place a property changed signal on the D-Bus.
 pub fn prop_changed_dispatch<T: 'static>(
     conn: &Connection,
@@ -119,10 +391,29 @@ pub fn prop_changed_dispatch<T: 'static>(
 where
     T: RefArg,
 {
+    conn.send(prop_changed_signal(prop_name, new_value, path))?;
+
+    Ok(())
+}
+
+/// Send a signal that a state property, and the property that names its
+/// value, have changed. Both are sent in the one signal, so that a client
+/// never sees the one without the other.
+pub fn state_changed_dispatch<S: fmt::Display>(
+    conn: &Connection,
+    prop_name: &str,
+    name_prop_name: &str,
+    value: u16,
+    state: S,
+    path: &dbus::Path,
+) -> Result<(), ()> {
     let mut prop_changed: PropertiesPropertiesChanged = Default::default();
     prop_changed
         .changed_properties
-        .insert(prop_name.into(), Variant(Box::new(new_value)));
+        .insert(prop_name.into(), Variant(Box::new(value)));
+    prop_changed
+        .changed_properties
+        .insert(name_prop_name.into(), Variant(Box::new(state.to_string())));
 
     conn.send(prop_changed.to_emit_message(path))?;
 
@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// A log of the objects created, modified, and deleted by the engine, so
+// that a client that may have missed the signals announcing the changes
+// can find out cheaply what changed since it last looked. Each change is
+// numbered in sequence. A client is given a token naming the latest change
+// it has seen, and presents it to get the changes after it.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use uuid::Uuid;
+
+use super::engine::Pool;
+use super::types::PoolUuid;
+
+/// The number of changes retained. A client whose token names a change
+/// older than those retained must enumerate all objects again.
+const MAX_CHANGES: usize = 4096;
+
+/// The kind of object that changed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ObjectKind {
+    Pool,
+    Filesystem,
+    BlockDev,
+}
+
+/// What happened to the object.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// A change to an object.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Change {
+    pub object: ObjectKind,
+    pub uuid: Uuid,
+    pub kind: ChangeKind,
+}
+
+/// Names the latest change that a client has seen. The epoch distinguishes
+/// the tokens of one run of the daemon from those of another, as the
+/// sequence begins anew on each run.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ChangeToken {
+    pub epoch: Uuid,
+    pub sequence: u64,
+}
+
+#[derive(Debug)]
+struct ChangeLog {
+    epoch: Uuid,
+    /// The sequence number of the latest change.
+    sequence: u64,
+    /// The retained changes, oldest first, with their sequence numbers.
+    changes: VecDeque<(u64, Change)>,
+}
+
+impl ChangeLog {
+    fn new() -> ChangeLog {
+        ChangeLog {
+            epoch: Uuid::new_v4(),
+            sequence: 0,
+            changes: VecDeque::new(),
+        }
+    }
+
+    fn token(&self) -> ChangeToken {
+        ChangeToken {
+            epoch: self.epoch,
+            sequence: self.sequence,
+        }
+    }
+
+    fn record(&mut self, change: Change) {
+        self.sequence += 1;
+        if self.changes.len() == MAX_CHANGES {
+            self.changes.pop_front();
+        }
+        self.changes.push_back((self.sequence, change));
+    }
+
+    /// The changes after the one named by token, or None if they are not
+    /// all known.
+    fn since(&self, token: ChangeToken) -> Option<Vec<Change>> {
+        let oldest = self.changes
+            .front()
+            .map_or(self.sequence + 1, |&(sequence, _)| sequence);
+        if token.epoch != self.epoch || token.sequence > self.sequence
+            || token.sequence + 1 < oldest
+        {
+            return None;
+        }
+        Some(
+            self.changes
+                .iter()
+                .filter(|&&(sequence, _)| sequence > token.sequence)
+                .map(|&(_, change)| change)
+                .collect(),
+        )
+    }
+}
+
+lazy_static! {
+    static ref CHANGE_LOG: Mutex<ChangeLog> = Mutex::new(ChangeLog::new());
+}
+
+/// Record a change to an object, whose UUID is that of a pool, filesystem,
+/// or blockdev, as object says.
+pub fn record_change<U: Into<Uuid>>(object: ObjectKind, uuid: U, kind: ChangeKind) {
+    CHANGE_LOG
+        .lock()
+        .expect("the change log is never left inconsistent")
+        .record(Change {
+            object,
+            uuid: uuid.into(),
+            kind,
+        });
+}
+
+/// Record the creation or deletion of a pool, and with it of all of its
+/// filesystems and blockdevs.
+pub fn record_pool_change(pool_uuid: PoolUuid, pool: &Pool, kind: ChangeKind) {
+    record_change(ObjectKind::Pool, pool_uuid, kind);
+    for (_, uuid, _) in pool.filesystems() {
+        record_change(ObjectKind::Filesystem, uuid, kind);
+    }
+    for (uuid, _) in pool.blockdevs() {
+        record_change(ObjectKind::BlockDev, uuid, kind);
+    }
+}
+
+/// The token naming the latest change, and the changes after the one named
+/// by since. If since is None, or the changes after it are not all known,
+/// because it belongs to another run of the daemon or the changes after it
+/// are no longer retained, the changes are None, and the client must
+/// enumerate all objects again.
+pub fn changes_since(since: Option<ChangeToken>) -> (ChangeToken, Option<Vec<Change>>) {
+    let log = CHANGE_LOG
+        .lock()
+        .expect("the change log is never left inconsistent");
+    (log.token(), since.and_then(|token| log.since(token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(kind: ChangeKind) -> Change {
+        Change {
+            object: ObjectKind::Pool,
+            uuid: Uuid::new_v4(),
+            kind,
+        }
+    }
+
+    #[test]
+    /// Verify that exactly the changes after a token are returned, and
+    /// that a token from another run, or one naming a change no longer
+    /// retained, yields no changes.
+    fn test_since() {
+        let mut log = ChangeLog::new();
+        let start = log.token();
+        assert_eq!(log.since(start), Some(vec![]));
+
+        let first = change(ChangeKind::Created);
+        let second = change(ChangeKind::Deleted);
+        log.record(first);
+        let middle = log.token();
+        log.record(second);
+        assert_eq!(log.since(start), Some(vec![first, second]));
+        assert_eq!(log.since(middle), Some(vec![second]));
+        assert_eq!(log.since(log.token()), Some(vec![]));
+
+        assert_eq!(log.since(ChangeLog::new().token()), None);
+        assert_eq!(
+            log.since(ChangeToken {
+                epoch: start.epoch,
+                sequence: 3,
+            }),
+            None
+        );
+
+        for _ in 0..MAX_CHANGES - 1 {
+            log.record(change(ChangeKind::Modified));
+        }
+        assert_eq!(log.since(start), None);
+        assert_eq!(log.since(middle).map(|changes| changes.len()), Some(MAX_CHANGES));
+    }
+}
@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// The operations on the engine that stratisd's control interfaces, the D-Bus
+// and the JSON-RPC server, both offer. Each interface decodes its requests
+// and encodes its replies as it must, but leaves what is done with them to
+// the commands here, so that the interfaces behave alike.
+
+use std::path::Path;
+
+use devicemapper::Sectors;
+
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
+
+use super::engine::Engine;
+use super::types::{Creator, DevUuid, FilesystemUuid, PoolUuid};
+
+/// Create a pool named name from the devices at blockdev_paths.
+pub fn create_pool(
+    engine: &mut Engine,
+    name: &str,
+    blockdev_paths: &[&Path],
+    redundancy: Option<u16>,
+    size_policy: Option<u16>,
+    signature_policy: Option<u16>,
+    created_by: Option<Creator>,
+) -> StratisResult<PoolUuid> {
+    engine.create_pool(
+        name,
+        blockdev_paths,
+        redundancy,
+        size_policy,
+        signature_policy,
+        created_by,
+    )
+}
+
+/// Create the filesystems given by specs, each a name, a size, and whether
+/// its space is guaranteed, in the pool.
+pub fn create_filesystems<'a>(
+    engine: &mut Engine,
+    pool_uuid: PoolUuid,
+    specs: &[(&'a str, Option<Sectors>, bool)],
+    created_by: Option<Creator>,
+) -> StratisResult<Vec<(&'a str, FilesystemUuid)>> {
+    let (pool_name, pool) = engine.get_mut_pool(pool_uuid).ok_or_else(|| {
+        StratisError::Structured(
+            ErrorEnum::NotFound,
+            ErrorPayload::new("PoolNotFound").param("uuid", pool_uuid.simple()),
+        )
+    })?;
+    pool.create_filesystems(pool_uuid, &pool_name, specs, created_by)
+}
+
+/// The state of a blockdev, as reported by report().
+#[derive(Debug, Serialize)]
+pub struct BlockDevReport {
+    pub uuid: DevUuid,
+    pub devnode: String,
+    pub tier: String,
+    pub state: String,
+    /// The total size of the device, in bytes.
+    pub size: u64,
+}
+
+/// The state of a filesystem, as reported by report().
+#[derive(Debug, Serialize)]
+pub struct FilesystemReport {
+    pub uuid: FilesystemUuid,
+    pub name: String,
+    pub devnode: String,
+    /// The amount of data stored on the filesystem, in bytes, if it could
+    /// be found.
+    pub used: Option<u64>,
+    pub guaranteed: bool,
+}
+
+/// The state of a pool, as reported by report().
+#[derive(Debug, Serialize)]
+pub struct PoolReport {
+    pub uuid: PoolUuid,
+    pub name: String,
+    pub state: String,
+    pub encrypted: bool,
+    /// The size of the pool's devices, in bytes.
+    pub total_physical_size: u64,
+    /// The space used on the pool's devices, in bytes, if it could be
+    /// found.
+    pub total_physical_used: Option<u64>,
+    pub blockdevs: Vec<BlockDevReport>,
+    pub filesystems: Vec<FilesystemReport>,
+}
+
+/// The state of every pool, and of its blockdevs and filesystems.
+pub fn report(engine: &Engine) -> Vec<PoolReport> {
+    engine
+        .pools()
+        .into_iter()
+        .map(|(pool_name, pool_uuid, pool)| PoolReport {
+            uuid: pool_uuid,
+            name: pool_name.to_string(),
+            state: pool.state().to_string(),
+            encrypted: pool.encrypted(),
+            total_physical_size: *pool.total_physical_size().bytes(),
+            total_physical_used: pool.total_physical_used().ok().map(|used| *used.bytes()),
+            blockdevs: pool.blockdevs()
+                .into_iter()
+                .map(|(dev_uuid, bd)| BlockDevReport {
+                    uuid: dev_uuid,
+                    devnode: bd.devnode().display().to_string(),
+                    tier: pool.get_blockdev(dev_uuid)
+                        .map(|(tier, _)| tier.to_string())
+                        .unwrap_or_default(),
+                    state: bd.state().to_string(),
+                    size: *bd.size().bytes(),
+                })
+                .collect(),
+            filesystems: pool.filesystems()
+                .into_iter()
+                .map(|(fs_name, fs_uuid, fs)| FilesystemReport {
+                    uuid: fs_uuid,
+                    name: fs_name.to_string(),
+                    devnode: fs.devnode().display().to_string(),
+                    used: fs.used().ok().map(|used| *used),
+                    guaranteed: fs.guaranteed(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::sim_engine::SimEngine;
+
+    use super::*;
+
+    #[test]
+    /// Verify that the pools and filesystems created by the commands are
+    /// reported, and that filesystems can not be created in a pool that
+    /// does not exist.
+    fn test_create_and_report() {
+        let mut engine = SimEngine::default();
+        let pool_uuid = create_pool(
+            &mut engine,
+            "pool",
+            &[Path::new("/dev/one"), Path::new("/dev/two")],
+            None,
+            None,
+            None,
+            None,
+        ).unwrap();
+        let created = create_filesystems(&mut engine, pool_uuid, &[("fs", None, false)], None)
+            .unwrap();
+
+        let reports = report(&engine);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].uuid, pool_uuid);
+        assert_eq!(reports[0].name, "pool");
+        assert_eq!(reports[0].blockdevs.len(), 2);
+        assert_eq!(reports[0].filesystems.len(), 1);
+        assert_eq!(reports[0].filesystems[0].uuid, created[0].1);
+        assert_eq!(reports[0].filesystems[0].name, "fs");
+
+        let err = create_filesystems(&mut engine, PoolUuid::new_v4(), &[("fs", None, false)], None)
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorEnum::NotFound);
+        assert_eq!(err.payload().id, "PoolNotFound");
+    }
+}
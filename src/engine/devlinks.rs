@@ -27,12 +27,24 @@ pub fn setup_dev_path() -> StratisResult<()> {
     Ok(())
 }
 
+/// A change made under /stratis to bring it back in line with the pools.
+#[derive(Debug)]
+pub enum DevlinkRepair {
+    /// A filesystem's symlink was missing.
+    Created(PathBuf),
+    /// A filesystem's symlink pointed to some other device.
+    Replaced(PathBuf),
+    /// A symlink or directory belonged to no filesystem or pool.
+    Removed(PathBuf),
+}
+
 /// Setup the pool directory and the symlinks in /stratis for the specified pool and filesystems
-/// it contains.
+/// it contains. Return the changes that were needed.
 // Don't just remove and recreate everything in case there are processes
 // (e.g. user shells) with the current working directory within the tree.
-pub fn setup_pool_devlinks(pool_name: &str, pool: &Pool) -> () {
-    if let Err(err) = || -> StratisResult<()> {
+pub fn setup_pool_devlinks(pool_name: &str, pool: &Pool) -> Vec<DevlinkRepair> {
+    match || -> StratisResult<Vec<DevlinkRepair>> {
+        let mut repairs = Vec::new();
         let pool_path = pool_directory(pool_name);
 
         if !pool_path.exists() {
@@ -46,29 +58,52 @@ pub fn setup_pool_devlinks(pool_name: &str, pool: &Pool) -> () {
             .collect::<Result<HashSet<_>, _>>()?;
 
         for (fs_name, _, fs) in pool.filesystems() {
-            filesystem_added(pool_name, &fs_name, &fs.devnode());
+            let link = filesystem_mount_path(pool_name, &*fs_name);
+            let devnode = fs.devnode();
+            match fs::read_link(&link) {
+                Ok(ref target) if *target == devnode => {}
+                Ok(_) => {
+                    filesystem_added(pool_name, &fs_name, &devnode);
+                    repairs.push(DevlinkRepair::Replaced(link));
+                }
+                Err(_) => {
+                    filesystem_added(pool_name, &fs_name, &devnode);
+                    repairs.push(DevlinkRepair::Created(link));
+                }
+            }
             existing_files.remove(&fs_name.to_owned());
         }
 
         for leftover in existing_files {
             filesystem_removed(pool_name, &leftover);
+            repairs.push(DevlinkRepair::Removed(filesystem_mount_path(
+                pool_name,
+                &*leftover,
+            )));
         }
 
-        Ok(())
+        Ok(repairs)
     }() {
-        warn!(
-            "setup_pool_devlinks failed for /stratis/{}, reason {:?}",
-            pool_name, err
-        );
-    };
+        Ok(repairs) => repairs,
+        Err(err) => {
+            warn!(
+                "setup_pool_devlinks failed for /stratis/{}, reason {:?}",
+                pool_name, err
+            );
+            Vec::new()
+        }
+    }
 }
 
 /// Clean up directories and symlinks under /stratis based on current
 /// config. Clear out any directory or file that doesn't correspond to a pool.
+/// Return the directories that were removed.
 // Don't just remove everything in case there are processes
 // (e.g. user shells) with the current working directory within the tree.
-pub fn cleanup_devlinks<'a, I: Iterator<Item = &'a (Name, PoolUuid, &'a Pool)>>(pools: I) -> () {
-    if let Err(err) = || -> StratisResult<()> {
+pub fn cleanup_devlinks<'a, I: Iterator<Item = &'a (Name, PoolUuid, &'a Pool)>>(
+    pools: I,
+) -> Vec<DevlinkRepair> {
+    match || -> StratisResult<Vec<DevlinkRepair>> {
         let mut existing_dirs = fs::read_dir(DEV_PATH)?
             .map(|dir_e| {
                 dir_e.and_then(|d| Ok(d.file_name().into_string().expect("Unix is utf-8")))
@@ -79,13 +114,19 @@ pub fn cleanup_devlinks<'a, I: Iterator<Item = &'a (Name, PoolUuid, &'a Pool)>>(
             existing_dirs.remove(&pool_name.to_owned());
         }
 
+        let mut repairs = Vec::new();
         for leftover in existing_dirs {
-            pool_removed(&Name::new(leftover));
+            pool_removed(&Name::new(leftover.clone()));
+            repairs.push(DevlinkRepair::Removed(pool_directory(leftover)));
         }
 
-        Ok(())
+        Ok(repairs)
     }() {
-        warn!("cleanup_devlinks failed, reason {:?}", err);
+        Ok(repairs) => repairs,
+        Err(err) => {
+            warn!("cleanup_devlinks failed, reason {:?}", err);
+            Vec::new()
+        }
     }
 }
 
@@ -157,7 +198,7 @@ pub fn filesystem_renamed(pool_name: &str, old_name: &str, new_name: &str) -> ()
 
 /// Given a pool name, synthesize a pool directory name for storing filesystem
 /// mount paths.
-fn pool_directory<T: AsRef<str>>(pool_name: T) -> PathBuf {
+pub fn pool_directory<T: AsRef<str>>(pool_name: T) -> PathBuf {
     vec![DEV_PATH, pool_name.as_ref()].iter().collect()
 }
 
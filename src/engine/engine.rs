@@ -2,6 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
@@ -11,11 +12,17 @@ use uuid::Uuid;
 
 use devicemapper::{Bytes, Device, Sectors};
 
+use super::devlinks::{filesystem_mount_path, pool_directory};
+use super::event::{get_engine_listener_list, EngineEvent};
 use super::types::{
-    BlockDevState, BlockDevTier, DevUuid, FilesystemUuid, FreeSpaceState, MaybeDbusPath, Name,
-    PoolExtendState, PoolState, PoolUuid, RenameAction,
+    prefixed_names, AddDataDevsPlan, AllocationStrategy, BlockDevState, BlockDevTier, CacheType,
+    CacheUsage, Capability, ClonedDevice, CreationParams, Creator, DestroyPoolPlan, DevUuid,
+    DmDeviceInfo, FilesystemFilter, FilesystemUuid, FormatVersions, FreeSpaceState,
+    MaybeDbusPath, MetadataStats, Name, PlannedFilesystem, PoolBindings, PoolExtendState,
+    PoolState, PoolUuid, ReclaimableDevice, RenameAction, SnapshotSchedule, Watermarks,
+    WorkerPriority,
 };
-use stratis::StratisResult;
+use stratis::{StratisError, StratisResult};
 
 pub const DEV_PATH: &str = "/stratis";
 
@@ -29,6 +36,37 @@ pub trait Filesystem: Debug {
     /// The amount of data stored on the filesystem, including overhead.
     fn used(&self) -> StratisResult<Bytes>;
 
+    /// Whether the whole of the filesystem's device was allocated from the
+    /// pool when the filesystem was created, so that writes to it can not
+    /// fail for lack of space in the pool.
+    fn guaranteed(&self) -> bool;
+
+    /// The identity of the process that requested the creation of the
+    /// filesystem, if it was recorded.
+    fn created_by(&self) -> Option<&Creator>;
+
+    /// The read-ahead set for the filesystem's device whenever it is
+    /// activated, if one was set.
+    fn read_ahead(&self) -> Option<Sectors>;
+
+    /// The size beyond which the filesystem is never extended, if it has
+    /// one.
+    fn size_limit(&self) -> Option<Sectors>;
+
+    /// The UUID of the filesystem of which this filesystem was created as
+    /// a snapshot, if it is a snapshot. The origin may since have been
+    /// destroyed.
+    fn origin(&self) -> Option<FilesystemUuid>;
+
+    /// Whether the filesystem is protected from being destroyed.
+    fn protected(&self) -> bool;
+
+    /// The devicemapper device of the filesystem, if it has one.
+    fn dm_device(&self) -> Option<DmDeviceInfo>;
+
+    /// The places where the filesystem is mounted.
+    fn mount_points(&self) -> StratisResult<Vec<PathBuf>>;
+
     /// Set dbus path associated with the Pool.
     fn set_dbus_path(&mut self, path: MaybeDbusPath) -> ();
 
@@ -53,9 +91,32 @@ pub trait BlockDev: Debug {
     /// The total size of the device, including space not usable for data.
     fn size(&self) -> Sectors;
 
+    /// The space on the device that is available for data.
+    fn usable_size(&self) -> Sectors;
+
     /// The current state of the blockdev.
     fn state(&self) -> BlockDevState;
 
+    /// The versions of the on-disk format found on the blockdev.
+    fn format_versions(&self) -> FormatVersions;
+
+    /// The number of times a copy of the metadata on the blockdev has been
+    /// found to be corrupt since the blockdev was set up.
+    fn metadata_corruptions(&self) -> u64;
+
+    /// Whether writes to the backup copies of the metadata on the blockdev
+    /// have failed, so that the metadata may only be held by the primary
+    /// copies.
+    fn metadata_degraded(&self) -> bool;
+
+    /// Counts of the outcomes of reads and writes of the metadata on the
+    /// blockdev since the blockdev was set up.
+    fn metadata_stats(&self) -> MetadataStats;
+
+    /// Whether background writes of metadata to the blockdev are currently
+    /// held back, because metadata was written to it too recently.
+    fn metadata_write_throttled(&self) -> bool;
+
     /// Set dbus path associated with the BlockDev.
     fn set_dbus_path(&mut self, path: MaybeDbusPath) -> ();
 
@@ -68,40 +129,200 @@ pub trait Pool: Debug {
     /// Returns a list of the names of filesystems actually created.
     /// Returns an error if any of the specified names are already in use
     /// for filesystems in this pool. If the same name is passed multiple
-    /// times, the size associated with the last item is used.
+    /// times, the size associated with the last item is used. A filesystem
+    /// whose size is None gets the pool's default filesystem size.
+    /// If the guaranteed flag of a spec is set, all the space of the
+    /// filesystem is allocated from the pool at creation; returns an error
+    /// if the pool can not supply the space for all guaranteed filesystems.
+    /// The creator, if given, is recorded for each filesystem created.
     fn create_filesystems<'a, 'b>(
         &'a mut self,
         pool_uuid: PoolUuid,
         pool_name: &str,
-        specs: &[(&'b str, Option<Sectors>)],
+        specs: &[(&'b str, Option<Sectors>, bool)],
+        created_by: Option<Creator>,
     ) -> StratisResult<Vec<(&'b str, FilesystemUuid)>>;
 
+    /// Creates count filesystems, all of the given size and guarantee,
+    /// named from prefix and a number of at least three digits,
+    /// "<prefix>-001" and so on, skipping each name already in use in this
+    /// pool. All the filesystems are created, as create_filesystems creates
+    /// them, or none are.
+    /// Returns the names of the filesystems created, in order of their
+    /// numbers, with their UUIDs.
+    fn create_prefixed_filesystems(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        prefix: &str,
+        count: usize,
+        size: Option<Sectors>,
+        guaranteed: bool,
+        created_by: Option<Creator>,
+    ) -> StratisResult<Vec<(Name, FilesystemUuid)>> {
+        let taken = self.filesystems()
+            .into_iter()
+            .map(|(name, _, _)| name.to_string())
+            .collect::<HashSet<_>>();
+        let names = prefixed_names(prefix, count, &taken)?;
+        let specs = names
+            .iter()
+            .map(|name| (name.as_str(), size, guaranteed))
+            .collect::<Vec<_>>();
+        let created: HashMap<_, _> = self.create_filesystems(
+            pool_uuid,
+            pool_name,
+            &specs,
+            created_by,
+        )?
+            .into_iter()
+            .collect();
+        Ok(names
+            .iter()
+            .map(|name| (Name::new(name.clone()), created[name.as_str()]))
+            .collect())
+    }
+
     /// Adds blockdevs specified by paths to pool.
     /// Returns a list of uuids corresponding to devices actually added.
     /// Returns an error if a blockdev can not be added because it is owned
     /// or there was an error while reading or writing a blockdev.
+    /// The signature policy code determines whether devices with foreign
+    /// signatures are refused (0), added regardless (1), or wiped of the
+    /// signatures and then added (2); if unspecified, they are refused.
     fn add_blockdevs(
         &mut self,
         pool_uuid: PoolUuid,
         pool_name: &str,
         paths: &[&Path],
         tier: BlockDevTier,
+        signature_policy: Option<u16>,
+    ) -> StratisResult<Vec<DevUuid>>;
+
+    /// Remove the blockdevs of the data tier specified by uuids from the
+    /// pool while it remains in use, moving the data on them to its other
+    /// blockdevs first, a part at a time. The progress of the move is
+    /// announced by a PoolRemoveProgress event after each part. The
+    /// metadata on the blockdevs is erased, so that they may be reused.
+    /// Returns an error if a blockdev is not in the data tier or is
+    /// missing, if no blockdev would remain, or if the other blockdevs have
+    /// no room for the data. If the move fails part way, the data already
+    /// moved stays where it was moved to, and no blockdev is removed.
+    fn remove_blockdevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        uuids: &[DevUuid],
+    ) -> StratisResult<()>;
+
+    /// Replace the blockdev old, of the data tier, by the device at new,
+    /// while the pool remains in use. The new device is initialized and
+    /// added to the pool; the data on old is then moved onto it a part at a
+    /// time, announced by PoolRemoveProgress events, or, if the pool has
+    /// redundancy, rebuilt onto it from the other members of the array.
+    /// Last, old is removed from the pool and its metadata erased.
+    /// If the replacement is cut short, the new device is left in the pool,
+    /// and calling this again with the same arguments resumes it.
+    /// Returns the UUID of the blockdev made from new.
+    fn replace_blockdev(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        old: DevUuid,
+        new: &Path,
+        signature_policy: Option<u16>,
+    ) -> StratisResult<DevUuid>;
+
+    /// Adds blockdevs specified by paths to the cache tier of the pool, as
+    /// add_blockdevs does. If the pool has no cache, the cache is made with
+    /// the DM target given by the cache type code: dm-cache (0) or
+    /// dm-writecache (1); if unspecified, dm-cache. If volatile is true,
+    /// the cache is made on volatile devices, such as RAM disks, in
+    /// writethrough mode, and the pool does without it if its devices are
+    /// missing when the pool is set up. Returns an error if the pool has a
+    /// cache made with a different target or a different volatility, if its
+    /// cache is a writecache, which can not be extended, or if a volatile
+    /// cache is requested with a target other than dm-cache.
+    fn add_cachedevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        paths: &[&Path],
+        signature_policy: Option<u16>,
+        cache_type: Option<u16>,
+        volatile: bool,
+    ) -> StratisResult<Vec<DevUuid>>;
+
+    /// The DM target with which the pool's cache is made, if it has a cache.
+    fn cache_type(&self) -> Option<CacheType>;
+
+    /// Whether the pool's cache is on volatile devices. False if the pool
+    /// has no cache.
+    fn cache_volatile(&self) -> bool;
+
+    /// The usage of the pool's cache, if it has a cache.
+    fn cache_usage(&self) -> StratisResult<Option<CacheUsage>>;
+
+    /// Determine the effect of adding the blockdevs specified by paths to
+    /// the data tier of the pool, without adding them.
+    /// Returns a token by which the plan can be applied, and the plan.
+    /// Returns an error if the blockdevs could not be added.
+    /// Only the most recently made plan for a pool can be applied.
+    fn plan_add_datadevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        paths: &[&Path],
+    ) -> StratisResult<(Uuid, AddDataDevsPlan)>;
+
+    /// Add the blockdevs of the plan identified by token to the data tier.
+    /// Returns a list of uuids corresponding to devices actually added.
+    /// Returns an error if there is no such plan, or if the result of
+    /// adding the blockdevs is no longer the one that was planned.
+    fn apply_add_datadevs_plan(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        token: Uuid,
     ) -> StratisResult<Vec<DevUuid>>;
 
     /// Destroy the pool.
+    /// If keep_signatures is true, the Stratis signatures of the blockdevs
+    /// are kept, marked as those of a destroyed pool, but the pool's
+    /// metadata is erased from them.
     /// Precondition: All filesystems belonging to this pool must be
     /// unmounted.
-    fn destroy(&mut self) -> StratisResult<()>;
+    fn destroy(&mut self, keep_signatures: bool) -> StratisResult<()>;
 
     /// Ensures that all designated filesystems are gone from pool.
-    /// Returns a list of the filesystems found, and actually destroyed.
-    /// This list will be a subset of the uuids passed in fs_uuids.
+    /// The filesystems are destroyed in the order given by destroy_order(),
+    /// so that a snapshot is always destroyed before its origin, and a
+    /// failure to destroy one filesystem does not stop the others from
+    /// being destroyed.
+    /// Returns, in the order tried, each filesystem found together with the
+    /// result of destroying it. The filesystems found will be a subset of
+    /// the uuids passed in fs_uuids.
     /// Precondition: All filesystems given must be unmounted.
+    /// Returns an error, destroying nothing, if any of the filesystems is
+    /// protected.
     fn destroy_filesystems<'a>(
         &'a mut self,
         pool_name: &str,
         fs_uuids: &[FilesystemUuid],
-    ) -> StratisResult<Vec<FilesystemUuid>>;
+    ) -> StratisResult<Vec<(FilesystemUuid, StratisResult<()>)>>;
+
+    /// Destroy snapshots in this pool, the oldest first, until less than
+    /// target percent of the pool's data space is in use. This is for an
+    /// operator to invoke when the pool is critically short of space.
+    /// Filesystems that are not snapshots, and protected snapshots, are
+    /// never destroyed. A snapshot
+    /// that can not be destroyed, for example because it is mounted, is
+    /// skipped. Every snapshot destroyed is logged.
+    /// Returns the names and UUIDs of the snapshots destroyed.
+    fn emergency_reclaim(
+        &mut self,
+        pool_name: &str,
+        target: u8,
+    ) -> StratisResult<Vec<(Name, FilesystemUuid)>>;
 
     /// Rename filesystem
     /// Rename pool with uuid to new_name.
@@ -115,16 +336,76 @@ pub trait Pool: Debug {
         new_name: &str,
     ) -> StratisResult<RenameAction>;
 
+    /// Set the read-ahead of the filesystem with the given UUID, applying
+    /// it to the filesystem's device at once, and whenever the device is
+    /// activated. If read_ahead is None, no read-ahead is set when the
+    /// device is activated, but the device keeps its current read-ahead
+    /// until then. The read-ahead is rounded down to a whole KiB.
+    /// Returns true if the read-ahead was changed, otherwise false.
+    fn set_filesystem_read_ahead(
+        &mut self,
+        pool_name: &str,
+        uuid: FilesystemUuid,
+        read_ahead: Option<Sectors>,
+    ) -> StratisResult<bool>;
+
+    /// Protect the filesystem with the given UUID from being destroyed, or
+    /// unprotect it. A protected filesystem can not be destroyed, whether
+    /// by a user or by emergency reclamation, until it is unprotected.
+    /// Returns true if the protection was changed, otherwise false.
+    fn set_filesystem_protected(
+        &mut self,
+        pool_name: &str,
+        uuid: FilesystemUuid,
+        protected: bool,
+    ) -> StratisResult<bool>;
+
+    /// Set the size limit of the filesystem with the given UUID. The
+    /// filesystem's device is extended to the limit at once, and the
+    /// filesystem grown to fill it, so that it is never extended beyond it.
+    /// The limit may not be below the size of the filesystem's device. If
+    /// limit is None, the filesystem is extended as it fills, without limit.
+    /// Returns true if the limit was changed, otherwise false.
+    fn set_filesystem_size_limit(
+        &mut self,
+        pool_name: &str,
+        uuid: FilesystemUuid,
+        limit: Option<Sectors>,
+    ) -> StratisResult<bool>;
+
     /// Snapshot filesystem
     /// Create a CoW snapshot of the origin
+    /// If freeze is true and the origin is mounted, the origin is frozen
+    /// for the instant the snapshot is taken and thawed immediately after,
+    /// so that the snapshot is clean, not just crash-consistent.
     fn snapshot_filesystem(
         &mut self,
         pool_uuid: PoolUuid,
         pool_name: &str,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        freeze: bool,
     ) -> StratisResult<(FilesystemUuid, &mut Filesystem)>;
 
+    /// Check the consistency of the filesystem with the given UUID, which
+    /// must not be mounted, by running xfs_repair on its device. If repair
+    /// is true, any inconsistency found is repaired, otherwise the
+    /// filesystem is not modified.
+    /// Returns whether the filesystem was found consistent, or was repaired,
+    /// and the output of xfs_repair.
+    /// Returns an error if there is no such filesystem, if it is mounted,
+    /// or if xfs_repair could not complete.
+    fn check_filesystem(&self, uuid: FilesystemUuid, repair: bool) -> StratisResult<(bool, String)>;
+
+    /// Repair the Pool if the kernel has flagged its thin pool metadata as
+    /// needing a check, which it does on finding a problem with it. While
+    /// the flag is set, operations that would change the metadata fail.
+    /// The filesystems, which must not be mounted, and the thin pool are
+    /// deactivated, the metadata is checked, and repaired if inconsistent,
+    /// which clears the flag, and they are activated again.
+    /// Returns true if the Pool was repaired, false if it needed no repair.
+    fn repair(&mut self, pool_uuid: PoolUuid, pool_name: &str) -> StratisResult<bool>;
+
     /// The total number of Sectors belonging to this pool.
     /// There are no exclusions, so this number includes overhead sectors
     /// of all sorts, sectors allocated for every sort of metadata by
@@ -150,9 +431,16 @@ pub trait Pool: Debug {
     /// Get the mutable filesystem in this pool with this UUID.
     fn get_mut_filesystem(&mut self, uuid: FilesystemUuid) -> Option<(Name, &mut Filesystem)>;
 
+    /// The space taken by each filesystem in this pool, as a pair of the
+    /// space that the filesystem alone uses, and that which it shares with
+    /// others, as a snapshot shares space with its origin and with its own
+    /// snapshots. The exclusive space is what destroying the filesystem
+    /// would free.
+    fn filesystem_space(&self) -> StratisResult<Vec<(FilesystemUuid, Sectors, Sectors)>>;
+
     /// Get _all_ the blockdevs that belong to this pool.
     /// All really means all. For example, it does not exclude cache blockdevs.
-    fn blockdevs(&self) -> Vec<(Uuid, &BlockDev)>;
+    fn blockdevs(&self) -> Vec<(DevUuid, &BlockDev)>;
 
     /// Get all the blockdevs belonging to this pool as mutable references.
     fn blockdevs_mut(&mut self) -> Vec<(DevUuid, &mut BlockDev)>;
@@ -172,15 +460,236 @@ pub trait Pool: Debug {
         user_info: Option<&str>,
     ) -> StratisResult<bool>;
 
+    /// The identity of the process that requested the creation of the pool,
+    /// if it was recorded.
+    fn created_by(&self) -> Option<&Creator>;
+
+    /// Whether the pool's data devices are encrypted with LUKS2.
+    fn encrypted(&self) -> bool;
+
+    /// The parameters with which the pool was created, if they were
+    /// recorded. Pools created by earlier versions of stratisd do not
+    /// record them.
+    fn creation_params(&self) -> Option<&CreationParams>;
+
+    /// Whether writes of pool metadata are read back and verified before
+    /// being considered durable.
+    fn verify_writes(&self) -> bool;
+
+    /// Set whether writes of pool metadata are read back and verified.
+    /// Returns true if the setting was changed, otherwise false.
+    fn set_verify_writes(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        verify: bool,
+    ) -> StratisResult<bool>;
+
+    /// The strategy by which space is allocated from the pool's data tier.
+    fn allocation_strategy(&self) -> AllocationStrategy;
+
+    /// Allocate space from the pool's data tier by the strategy given by
+    /// the code: fill-first (0), round-robin (1), or ssd-first (2); if
+    /// unspecified, fill-first. Space already allocated is not moved.
+    /// Returns true if the strategy was changed, otherwise false.
+    fn set_allocation_strategy(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        strategy: Option<u16>,
+    ) -> StratisResult<bool>;
+
+    /// The priority of the threads that do the pool's background work.
+    fn worker_priority(&self) -> WorkerPriority;
+
+    /// Run the threads that do the pool's background work at the given
+    /// niceness, from -20 to 19, and in the I/O class given by the code:
+    /// best-effort (0), or idle (1); if unspecified, best-effort. The level
+    /// of I/O priority, from 0 to 7, applies only to the best-effort class.
+    /// Returns true if the priority was changed, otherwise false.
+    fn set_worker_priority(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        niceness: i16,
+        io_class: Option<u16>,
+        io_level: u8,
+    ) -> StratisResult<bool>;
+
+    /// Shrink the reserved area of the metadata on each of the Pool's data
+    /// blockdevs to the smallest that the on-disk format allows, so that
+    /// the sectors it gives up may be allocated. A blockdev whose reserved
+    /// area has been shrunk can not be set up by a version of stratisd that
+    /// predates the shrunk reserved area.
+    /// Returns the number of sectors reclaimed.
+    fn shrink_reserved_space(&mut self) -> StratisResult<Sectors>;
+
+    /// Mark the blockdev with the given device number missing, as it has
+    /// been removed from the system, and fail the Pool.
+    /// Returns the tier and UUID of the blockdev if it was newly marked
+    /// missing, otherwise None.
+    fn blockdev_removed(&mut self, device: Device) -> Option<(BlockDevTier, DevUuid)>;
+
     /// The current state of the Pool.
     fn state(&self) -> PoolState;
 
+    /// The lowest versions of the on-disk format found on any of the
+    /// Pool's blockdevs.
+    fn format_versions(&self) -> FormatVersions;
+
+    /// The devicemapper devices of the Pool's thin pool and of the devices
+    /// that it is made from, each with the part it plays.
+    fn dm_devices(&self) -> Vec<(String, DmDeviceInfo)>;
+
     /// The current extend state of the Pool.
     fn extend_state(&self) -> PoolExtendState;
 
     /// The current space state of the Pool.
     fn free_space_state(&self) -> FreeSpaceState;
 
+    /// The forecast number of days until the pool is full, computed from
+    /// the growth of its used space since the daemon started. None if the
+    /// used space is not growing or if there is not yet enough history.
+    fn days_until_full(&self) -> Option<u64>;
+
+    /// The sum of the sizes of the Pool's filesystems as a percentage of
+    /// the size of its data tier. None if the data tier is empty.
+    fn overcommit_percent(&self) -> Option<u64>;
+
+    /// The size of the metadata device of the Pool's thin pool and the
+    /// space used on it, if known.
+    fn thin_meta_usage(&self) -> Option<(Sectors, Sectors)>;
+
+    /// Replay the generations of the Pool's metadata held on its devices,
+    /// older first, checking each as setting up the Pool would and against
+    /// the one before it, without setting anything up.
+    /// Returns a description of each anomaly found, which a crash while
+    /// the metadata was being saved may have left.
+    fn replay_metadata(&self, pool_uuid: PoolUuid) -> StratisResult<Vec<String>>;
+
+    /// The overcommit percentage beyond which no filesystem or snapshot
+    /// may be created, if any.
+    fn overcommit_cap(&self) -> Option<u64>;
+
+    /// Set or unset the overcommit cap. The cap may be set below the
+    /// current overcommit percentage, in which case it only prevents
+    /// further growth.
+    /// Returns true if the cap was changed, otherwise false.
+    fn set_overcommit_cap(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        cap: Option<u64>,
+    ) -> StratisResult<bool>;
+
+    /// The percentages of the Pool's data space in use at which a crossing
+    /// is signalled, in ascending order. A threshold crossed upward is only
+    /// crossed downward once usage has fallen some margin below it.
+    fn usage_thresholds(&self) -> Vec<u8>;
+
+    /// Replace the usage thresholds. Returns an error if any threshold is
+    /// not a percentage from 1 to 100.
+    /// Returns true if the thresholds were changed, otherwise false.
+    fn set_usage_thresholds(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        thresholds: &[u8],
+    ) -> StratisResult<bool>;
+
+    /// The watermarks of the Pool. The crossing of either watermark is
+    /// signalled, as the crossing of a usage threshold is.
+    fn watermarks(&self) -> Watermarks;
+
+    /// Replace the watermarks of the Pool. The thin pool's data device is
+    /// extended at the new extend watermark from now on. Returns an error
+    /// if either watermark is not a percentage from 1 to 99.
+    /// Returns true if the watermarks were changed, otherwise false.
+    fn set_watermarks(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        watermarks: Watermarks,
+    ) -> StratisResult<bool>;
+
+    /// The size of a filesystem created without an explicit size.
+    fn default_filesystem_size(&self) -> Sectors;
+
+    /// Set the size of a filesystem created without an explicit size, or
+    /// restore the engine's built-in default if size is None.
+    /// Returns true if the default was changed, otherwise false.
+    fn set_default_filesystem_size(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        size: Option<Sectors>,
+    ) -> StratisResult<bool>;
+
+    /// The most filesystems and snapshots that the Pool may hold.
+    fn fs_limit(&self) -> u64;
+
+    /// Set the most filesystems and snapshots that the Pool may hold.
+    /// Returns an error if the limit is below the number of filesystems
+    /// that the Pool already holds.
+    /// Returns true if the limit was changed, otherwise false.
+    fn set_fs_limit(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        limit: u64,
+    ) -> StratisResult<bool>;
+
+    /// The snapshot schedule of the filesystem, if it has one.
+    fn snapshot_schedule(&self, uuid: FilesystemUuid) -> Option<SnapshotSchedule>;
+
+    /// Take a snapshot of the filesystem once in each period given by the
+    /// code: hourly (0) or daily (1), keeping the given number of the
+    /// snapshots so taken; or, if no schedule is given, take no more
+    /// snapshots of it. The snapshots taken by a schedule that is removed
+    /// are kept.
+    /// Returns true if the schedule was changed, otherwise false.
+    fn set_snapshot_schedule(
+        &mut self,
+        pool_name: &str,
+        uuid: FilesystemUuid,
+        schedule: Option<(u16, u32)>,
+    ) -> StratisResult<bool>;
+
+    /// Run the snapshot schedules of the Pool's filesystems at now: take
+    /// each snapshot that is due, then destroy the oldest of the snapshots
+    /// taken by each schedule beyond those that it keeps. A snapshot that
+    /// can not be taken or destroyed is logged; one that could not be
+    /// destroyed is tried again the next time that the schedules run.
+    /// Returns the UUIDs of the snapshots taken and of those destroyed.
+    fn run_snapshot_schedules(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        now: DateTime<Utc>,
+    ) -> StratisResult<(Vec<FilesystemUuid>, Vec<FilesystemUuid>)>;
+
+    /// Do the work that the Pool has put off, or that nothing else would
+    /// prompt: check its devices' usage, extending them as needed, and write
+    /// a background metadata save that was held back by the metadata write
+    /// interval, once the interval has elapsed.
+    fn run_housekeeping(&mut self, pool_uuid: PoolUuid, pool_name: &str) -> StratisResult<()>;
+
+    /// The time at which the Pool's metadata was last written, if ever.
+    fn last_metadata_update(&self) -> Option<DateTime<Utc>>;
+
+    /// The blockdevs that do not hold a copy of the Pool's most recently
+    /// written metadata. Each write goes to no more than ten blockdevs, so
+    /// in a large pool some blockdevs are always behind; in a small one, a
+    /// blockdev that is behind is one to which a write has failed.
+    fn metadata_behind_blockdevs(&self) -> Vec<DevUuid>;
+
+    /// Whether an operation on one of the Pool's devices has taken longer
+    /// than expected, and no operation on the Pool has completed promptly
+    /// since. A suspend or resume that does not complete in time is left
+    /// running and its caller given an error, so the Pool is reported
+    /// stuck while the operation is still in progress.
+    fn operation_stuck(&self, pool_uuid: PoolUuid) -> bool;
+
     /// Set dbus path associated with the Pool.
     fn set_dbus_path(&mut self, path: MaybeDbusPath) -> ();
 
@@ -193,13 +702,125 @@ pub trait Engine: Debug {
     /// Returns the UUID of the newly created pool.
     /// Returns an error if the redundancy code does not correspond to a
     /// supported redundancy.
+    /// The size policy code determines whether devices of very different
+    /// sizes are accepted (0), warned about (1), or rejected (2); if
+    /// unspecified, they are warned about.
+    /// The signature policy code determines whether devices with foreign
+    /// signatures are refused (0), initialized regardless (1), or wiped of
+    /// the signatures and then initialized (2); if unspecified, they are
+    /// refused.
+    /// The creator, if given, is recorded in the pool's metadata.
     fn create_pool(
         &mut self,
         name: &str,
         blockdev_paths: &[&Path],
         redundancy: Option<u16>,
+        size_policy: Option<u16>,
+        signature_policy: Option<u16>,
+        created_by: Option<Creator>,
+    ) -> StratisResult<PoolUuid>;
+
+    /// Create a Stratis pool, as create_pool() does, whose devices are
+    /// encrypted with LUKS2. Each device is formatted as a LUKS2 device
+    /// unlocked by passphrase, and the pool is made on the dm-crypt devices
+    /// opened on them. Returns an error if the passphrase is empty.
+    fn create_encrypted_pool(
+        &mut self,
+        name: &str,
+        blockdev_paths: &[&Path],
+        redundancy: Option<u16>,
+        size_policy: Option<u16>,
+        signature_policy: Option<u16>,
+        passphrase: &str,
+        created_by: Option<Creator>,
     ) -> StratisResult<PoolUuid>;
 
+    /// Create a Stratis pool, as create_pool() does, and then create the
+    /// filesystems specified in it, as Pool::create_filesystems() does, one
+    /// at a time, for the use of an installer. The progress of the setup is
+    /// announced by a SetupProgress event after each step. If any step
+    /// fails, the filesystems and the pool already created are destroyed,
+    /// so that the setup may be tried again.
+    /// Returns the UUID of the pool, and the UUIDs of the filesystems, in
+    /// the order in which they were specified.
+    fn create_pool_with_filesystems(
+        &mut self,
+        name: &str,
+        blockdev_paths: &[&Path],
+        redundancy: Option<u16>,
+        size_policy: Option<u16>,
+        signature_policy: Option<u16>,
+        filesystems: &[(&str, Option<Sectors>, bool)],
+        created_by: Option<Creator>,
+    ) -> StratisResult<(PoolUuid, Vec<FilesystemUuid>)> {
+        let total = filesystems.len() as u32 + 1;
+        let progress = |step: u32, stage: &str| {
+            get_engine_listener_list().notify(&EngineEvent::SetupProgress {
+                name,
+                step,
+                total,
+                stage,
+            })
+        };
+
+        progress(0, "creating pool");
+        let pool_uuid = self.create_pool(
+            name,
+            blockdev_paths,
+            redundancy,
+            size_policy,
+            signature_policy,
+            created_by.clone(),
+        )?;
+
+        let mut fs_uuids = Vec::new();
+        let mut failure = None;
+        for (step, spec) in filesystems.iter().enumerate() {
+            progress(step as u32 + 1, &format!("creating filesystem {}", spec.0));
+            let result = {
+                let (pool_name, pool) = self.get_mut_pool(pool_uuid)
+                    .expect("the pool was created above");
+                pool.create_filesystems(pool_uuid, &pool_name, &[*spec], created_by.clone())
+            };
+            match result {
+                Ok(created) => fs_uuids.extend(created.into_iter().map(|(_, uuid)| uuid)),
+                Err(err) => {
+                    failure = Some(err);
+                    break;
+                }
+            }
+        }
+
+        let err = match failure {
+            None => {
+                progress(total, "done");
+                return Ok((pool_uuid, fs_uuids));
+            }
+            Some(err) => err,
+        };
+
+        progress(total, "cleaning up");
+        let fs_cleanup = {
+            let (pool_name, pool) = self.get_mut_pool(pool_uuid)
+                .expect("the pool was created above");
+            pool.destroy_filesystems(&pool_name, &fs_uuids)
+        };
+        let cleanup = match fs_cleanup {
+            Ok(results) => match results.into_iter().find(|&(_, ref result)| result.is_err()) {
+                Some((_, Err(err))) => Err(err),
+                _ => self.destroy_pool(pool_uuid, false),
+            },
+            Err(err) => Err(err),
+        };
+        match cleanup {
+            Ok(_) => Err(err),
+            Err(cleanup_err) => Err(StratisError::Error(format!(
+                "setup failed: {}; the partial setup could not be undone: {}",
+                err, cleanup_err
+            ))),
+        }
+    }
+
     /// Evaluate a device node & devicemapper::Device to see if it's a valid
     /// stratis device.  If all the devices are present in the pool and the pool isn't already
     /// up and running, it will get setup and the pool uuid will be returned.
@@ -211,8 +832,124 @@ pub trait Engine: Debug {
 
     /// Destroy a pool.
     /// Ensures that the pool of the given UUID is absent on completion.
+    /// If keep_signatures is true, the blockdevs of the pool keep their
+    /// Stratis signatures, so that they are listed as reclaimable devices
+    /// until they are wiped.
     /// Returns true if some action was necessary, otherwise false.
-    fn destroy_pool(&mut self, uuid: PoolUuid) -> StratisResult<bool>;
+    fn destroy_pool(&mut self, uuid: PoolUuid, keep_signatures: bool) -> StratisResult<bool>;
+
+    /// The devices that kept their Stratis signatures when their pool was
+    /// destroyed.
+    fn reclaimable_devices(&self) -> StratisResult<Vec<ReclaimableDevice>>;
+
+    /// Wipe the Stratis signature from the reclaimable device at devnode.
+    /// Returns true if the device was wiped, false if it is not a
+    /// reclaimable device.
+    fn wipe_reclaimable_device(&mut self, devnode: &Path) -> StratisResult<bool>;
+
+    /// The devices that claim the same device UUID in the same pool as
+    /// another device, and so are left out of the pool as clones.
+    fn cloned_devices(&self) -> StratisResult<Vec<ClonedDevice>>;
+
+    /// Undo the destruction of the pool designated by pool_uuid, which
+    /// must have been destroyed with its signatures kept, by setting it up
+    /// again from the latest backup of its metadata. Every blockdev of the
+    /// pool must still be a reclaimable device, and the pool must have been
+    /// destroyed no longer ago than the engine's grace window.
+    /// Returns true if the pool was set up, otherwise false.
+    fn undo_destroy_pool(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool>;
+
+    /// Unlock the encrypted pool designated by pool_uuid, whose devices
+    /// are found but not opened, by opening each with passphrase, and set
+    /// up the pool on the dm-crypt devices opened.
+    /// Returns an error if the passphrase does not unlock every device.
+    /// Returns true if the pool was set up, false if it was already set up
+    /// or could not yet be set up.
+    fn unlock_pool(&mut self, pool_uuid: PoolUuid, passphrase: &str) -> StratisResult<bool>;
+
+    /// Bind the encrypted pool designated by pool_uuid to the Tang server
+    /// at url, whose signing key has the given thumbprint, so that the
+    /// engine unlocks the pool with the server's help when it sets it up
+    /// and no key kept for it unlocks it. The pool is bound with a keyslot
+    /// of its own on each device, added with passphrase.
+    /// Returns an error if the passphrase does not open every device.
+    /// Returns true if the pool was bound, false if it was already bound to
+    /// a Tang server.
+    fn bind_tang(
+        &mut self,
+        pool_uuid: PoolUuid,
+        passphrase: &str,
+        url: &str,
+        thumbprint: &str,
+    ) -> StratisResult<bool>;
+
+    /// Remove the binding of the encrypted pool designated by pool_uuid to
+    /// a Tang server, and the keyslots that it opened.
+    /// Returns true if the pool was bound.
+    fn unbind_tang(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool>;
+
+    /// The UUIDs of the encrypted pools that have devices which are found
+    /// but not opened, and which are not set up.
+    fn locked_pools(&self) -> StratisResult<Vec<PoolUuid>>;
+
+    /// Keep passphrase as the key that unlocks the encrypted pool
+    /// designated by pool_uuid, so that the pool is unlocked without a
+    /// passphrase being given when the engine sets it up. The pool need not
+    /// yet be found.
+    /// Returns true if the key kept was changed.
+    fn set_key(&mut self, pool_uuid: PoolUuid, passphrase: &str) -> StratisResult<bool>;
+
+    /// Forget the key that unlocks the pool designated by pool_uuid.
+    /// Returns true if a key was kept.
+    fn unset_key(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool>;
+
+    /// The UUIDs of the pools for which a key is kept.
+    fn keys(&self) -> StratisResult<Vec<PoolUuid>>;
+
+    /// The description of the key in the kernel keyring that unlocks the
+    /// pool designated by pool_uuid, if one is kept.
+    fn key_description(&self, pool_uuid: PoolUuid) -> StratisResult<Option<String>>;
+
+    /// The means by which the encrypted pool designated by pool_uuid may be
+    /// unlocked, whether or not it is locked.
+    /// Returns None if no device of such a pool is found.
+    fn bindings(&self, pool_uuid: PoolUuid) -> StratisResult<Option<PoolBindings>>;
+
+    /// Determine everything that destroying the pool designated by uuid
+    /// would affect, without destroying it.
+    /// Returns None if there is no such pool.
+    fn plan_destroy_pool(&self, uuid: PoolUuid) -> StratisResult<Option<DestroyPoolPlan>> {
+        let (pool_name, pool) = match self.get_pool(uuid) {
+            Some(pool) => pool,
+            None => return Ok(None),
+        };
+
+        let mut filesystems = Vec::new();
+        let mut devlinks = Vec::new();
+        for (fs_name, fs_uuid, fs) in pool.filesystems() {
+            filesystems.push(PlannedFilesystem {
+                uuid: fs_uuid,
+                name: fs_name.to_string(),
+                mount_points: fs.mount_points()?,
+            });
+            devlinks.push(filesystem_mount_path(&*pool_name, &*fs_name));
+        }
+        filesystems.sort_by(|a, b| a.name.cmp(&b.name));
+        devlinks.sort();
+        devlinks.push(pool_directory(&*pool_name));
+
+        let mut blockdevs = pool.blockdevs()
+            .into_iter()
+            .map(|(_, bd)| bd.devnode())
+            .collect::<Vec<_>>();
+        blockdevs.sort();
+
+        Ok(Some(DestroyPoolPlan {
+            filesystems,
+            blockdevs,
+            devlinks,
+        }))
+    }
 
     /// Rename pool with uuid to new_name.
     /// Raises an error if the mapping can't be applied because
@@ -230,12 +967,160 @@ pub trait Engine: Debug {
     /// denominator: the probably of failure is 1/denominator.
     fn configure_simulator(&mut self, denominator: u32) -> StratisResult<()>;
 
+    /// Configure the capacities that the simulator models, for the real
+    /// engine, this is a null op.
+    /// device_size: the size of each device added from now on
+    /// filesystem_size: the size of a filesystem created without a size in
+    /// a pool that has no default filesystem size
+    /// initial_used: the space that a newly created filesystem uses
+    /// growth: the bytes by which the space each filesystem uses grows in
+    /// each second of simulated time
+    /// Each capacity that is None is left unchanged.
+    fn configure_simulator_capacities(
+        &mut self,
+        device_size: Option<Sectors>,
+        filesystem_size: Option<Sectors>,
+        initial_used: Option<Bytes>,
+        growth: Option<Bytes>,
+    ) -> StratisResult<()>;
+
+    /// Advance the simulator's clock by the given number of seconds, for
+    /// the real engine, this is a null op.
+    fn advance_simulator_clock(&mut self, seconds: u64) -> StratisResult<()>;
+
     /// Get all pools belonging to this engine.
     fn pools(&self) -> Vec<(Name, PoolUuid, &Pool)>;
 
     /// Get mutable references to all pools belonging to this engine.
     fn pools_mut(&mut self) -> Vec<(Name, PoolUuid, &mut Pool)>;
 
+    /// Run the snapshot schedules of every pool at now. Return, for each
+    /// pool in which any snapshot was taken or destroyed, its UUID and the
+    /// UUIDs of the snapshots taken and of those destroyed. A pool whose
+    /// schedules could not be run is logged and passed over.
+    fn run_snapshot_schedules(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Vec<(PoolUuid, Vec<FilesystemUuid>, Vec<FilesystemUuid>)> {
+        let mut changes = Vec::new();
+        for (pool_name, pool_uuid, pool) in self.pools_mut() {
+            match pool.run_snapshot_schedules(pool_uuid, &pool_name, now) {
+                Ok((taken, destroyed)) => {
+                    if !taken.is_empty() || !destroyed.is_empty() {
+                        changes.push((pool_uuid, taken, destroyed));
+                    }
+                }
+                Err(err) => warn!(
+                    "Could not run the snapshot schedules of pool {}: {}",
+                    pool_name, err
+                ),
+            }
+        }
+        changes
+    }
+
+    /// Run the housekeeping of every pool. A pool whose housekeeping could
+    /// not be done is logged and passed over.
+    fn run_housekeeping(&mut self) {
+        for (pool_name, pool_uuid, pool) in self.pools_mut() {
+            if let Err(err) = pool.run_housekeeping(pool_uuid, &pool_name) {
+                warn!("Could not do the housekeeping of pool {}: {}", pool_name, err);
+            }
+        }
+    }
+
+    /// Handle udev's report that the block device with the given device
+    /// number has been removed from the system. If it is a blockdev of a
+    /// pool, it is marked missing and the pool is failed at once.
+    /// Returns the UUIDs of the pool and of the blockdev if a blockdev was
+    /// newly marked missing.
+    fn block_removed(&mut self, device: Device) -> Option<(PoolUuid, DevUuid)> {
+        self.pools_mut()
+            .into_iter()
+            .filter_map(|(_, pool_uuid, pool)| {
+                pool.blockdev_removed(device).map(|(_, dev_uuid)| (pool_uuid, dev_uuid))
+            })
+            .next()
+    }
+
+    /// The filesystems of all pools that are selected by filter, each with
+    /// the name and UUID of its pool.
+    fn list_filesystems(
+        &self,
+        filter: &FilesystemFilter,
+    ) -> Vec<(Name, PoolUuid, Name, FilesystemUuid, &Filesystem)> {
+        self.pools()
+            .into_iter()
+            .filter(|&(_, pool_uuid, _)| filter.pool_uuid.map_or(true, |uuid| uuid == pool_uuid))
+            .flat_map(|(pool_name, pool_uuid, pool)| {
+                pool.filesystems()
+                    .into_iter()
+                    .filter(|&(ref name, _, fs)| filter.matches(pool_uuid, name, fs.created()))
+                    .map(|(name, uuid, fs)| (pool_name.clone(), pool_uuid, name, uuid, fs))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// A page of at most limit of the filesystems that list_filesystems()
+    /// selects by filter. The filesystems are ordered by the UUID of their
+    /// pool, and then by their own UUID, and the page begins after the
+    /// filesystem identified by after, if specified. Returns the page and
+    /// whether any selected filesystems follow it.
+    fn list_filesystems_page(
+        &self,
+        filter: &FilesystemFilter,
+        after: Option<(PoolUuid, FilesystemUuid)>,
+        limit: usize,
+    ) -> (Vec<(Name, PoolUuid, Name, FilesystemUuid, &Filesystem)>, bool) {
+        let mut filesystems = self.list_filesystems(filter)
+            .into_iter()
+            .filter(|&(_, pool_uuid, _, uuid, _)| {
+                after.map_or(true, |after| (pool_uuid, uuid) > after)
+            })
+            .collect::<Vec<_>>();
+        filesystems.sort_by_key(|&(_, pool_uuid, _, uuid, _)| (pool_uuid, uuid));
+        let more = filesystems.len() > limit;
+        filesystems.truncate(limit);
+        (filesystems, more)
+    }
+
+    /// The generations of the backups kept of the metadata of the pool,
+    /// oldest first, each with the time its metadata was saved.
+    fn metadata_backups(&self, pool_uuid: PoolUuid) -> StratisResult<Vec<(u64, DateTime<Utc>)>>;
+
+    /// Write the backup of the metadata of the pool with the given
+    /// generation to the MDAs of the pool's devices, and try again to set
+    /// up the pool. Only a pool that has not been set up may be restored.
+    /// Returns true if the pool was set up, otherwise false.
+    fn restore_metadata_backup(
+        &mut self,
+        pool_uuid: PoolUuid,
+        generation: u64,
+    ) -> StratisResult<bool>;
+
+    /// Reconstruct the pool from the backup of its metadata with the given
+    /// generation, on the fresh devices specified by blockdev_paths, one for
+    /// each data tier blockdev recorded in the metadata, in the order
+    /// recorded. The reconstructed pool has the same layout as the original,
+    /// but a new UUID, and no filesystems. A pool that is set up may not be
+    /// reconstructed.
+    /// Returns the UUID of the reconstructed pool.
+    fn reconstruct_pool(
+        &mut self,
+        pool_uuid: PoolUuid,
+        generation: u64,
+        blockdev_paths: &[&Path],
+    ) -> StratisResult<PoolUuid>;
+
+    /// The optional capabilities available, as detected when the engine
+    /// was set up.
+    fn capabilities(&self) -> Vec<Capability>;
+
+    /// Whether this engine is the simulator, rather than the engine that
+    /// manages real devices.
+    fn is_simulator(&self) -> bool;
+
     /// If the engine would like to include an event in the message loop, it
     /// may return an Eventable from this method.
     fn get_eventable(&self) -> Option<&'static Eventable>;
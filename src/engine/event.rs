@@ -5,7 +5,13 @@
 use std::fmt::Debug;
 use std::sync::{Once, ONCE_INIT};
 
-use super::types::{BlockDevState, FreeSpaceState, MaybeDbusPath, PoolExtendState, PoolState};
+use chrono::{DateTime, Utc};
+
+use devicemapper::Sectors;
+
+use super::types::{
+    BlockDevState, DevUuid, FreeSpaceState, MaybeDbusPath, PoolExtendState, PoolState, PoolUuid,
+};
 
 static INIT: Once = ONCE_INIT;
 static mut ENGINE_LISTENER_LIST: Option<EngineListenerList> = None;
@@ -14,6 +20,8 @@ static mut ENGINE_LISTENER_LIST: Option<EngineListenerList> = None;
 pub enum EngineEvent<'a> {
     BlockdevStateChanged {
         dbus_path: &'a MaybeDbusPath,
+        pool_uuid: PoolUuid,
+        uuid: DevUuid,
         state: BlockDevState,
     },
     FilesystemRenamed {
@@ -21,10 +29,22 @@ pub enum EngineEvent<'a> {
         from: &'a str,
         to: &'a str,
     },
+    PoolDaysUntilFullChanged {
+        dbus_path: &'a MaybeDbusPath,
+        days_until_full: Option<u64>,
+    },
     PoolExtendStateChanged {
         dbus_path: &'a MaybeDbusPath,
+        pool_uuid: PoolUuid,
         state: PoolExtendState,
     },
+    /// moved of the total sectors of data on the blockdevs that are being
+    /// removed from the pool have been moved to its other blockdevs.
+    PoolRemoveProgress {
+        dbus_path: &'a MaybeDbusPath,
+        moved: Sectors,
+        total: Sectors,
+    },
     PoolRenamed {
         dbus_path: &'a MaybeDbusPath,
         from: &'a str,
@@ -32,12 +52,54 @@ pub enum EngineEvent<'a> {
     },
     PoolSpaceStateChanged {
         dbus_path: &'a MaybeDbusPath,
+        pool_uuid: PoolUuid,
         state: FreeSpaceState,
     },
     PoolStateChanged {
         dbus_path: &'a MaybeDbusPath,
+        pool_uuid: PoolUuid,
         state: PoolState,
     },
+    PoolMetadataClockSkew {
+        dbus_path: &'a MaybeDbusPath,
+        proposed: &'a DateTime<Utc>,
+        newest: &'a DateTime<Utc>,
+    },
+    /// The metadata device of the pool's thin pool was extended
+    /// automatically to total, or could not be, for reason.
+    PoolThinMetaExtended {
+        dbus_path: &'a MaybeDbusPath,
+        succeeded: bool,
+        total: Sectors,
+        reason: &'a str,
+    },
+    PoolUsageThresholdCrossed {
+        dbus_path: &'a MaybeDbusPath,
+        threshold: u8,
+        rising: bool,
+        used: Sectors,
+        total: Sectors,
+    },
+    /// The usage of the pool has crossed its watermark, of the given name
+    /// and percentage, upward if rising. The used and total space are
+    /// those of the pool's data space for the warn watermark, and of its
+    /// thin pool's data device for the extend watermark.
+    PoolWatermarkCrossed {
+        dbus_path: &'a MaybeDbusPath,
+        watermark: &'static str,
+        percent: u8,
+        rising: bool,
+        used: Sectors,
+        total: Sectors,
+    },
+    /// A step of the setup of the pool name, of which there are total, has
+    /// been reached.
+    SetupProgress {
+        name: &'a str,
+        step: u32,
+        total: u32,
+        stage: &'a str,
+    },
 }
 
 pub trait EngineListener: Debug {
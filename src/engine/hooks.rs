@@ -0,0 +1,396 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Hooks are executables that the daemon runs on events that a site may want
+// to act on, such as a pool running short of space, so that it can page an
+// operator or start some automation without writing a D-Bus client. Every
+// executable file in the hooks directory is run for every such event, in the
+// order of the files' names, with the event described by its environment:
+//
+// STRATIS_EVENT: the kind of the event, see HookEventKind::name().
+// STRATIS_POOL_UUID: the UUID of the pool.
+// STRATIS_DEV_UUID: the UUID of the blockdev, for an event on a blockdev.
+// STRATIS_DETAILS: the new state of the pool or blockdev, as named by the
+// StateName, ExtendStateName, or SpaceStateName D-Bus property.
+//
+// So that a slow hook never holds up the engine, the hooks run on a thread
+// of their own, one at a time, in the order in which the events occurred.
+// A hook that has not exited within the timeout is killed.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::event::{EngineEvent, EngineListener};
+use super::types::{BlockDevState, DevUuid, FreeSpaceState, PoolExtendState, PoolState, PoolUuid};
+
+use stratis::{StratisError, StratisResult};
+
+/// The number of seconds a hook may run before it is killed, unless
+/// another timeout is given.
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 30;
+
+/// How often to check whether a running hook has exited.
+const HOOK_POLL_INTERVAL_MS: u64 = 50;
+
+/// A kind of event for which the hooks are run.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum HookEventKind {
+    /// The pool's free space has fallen to the warning or critical level.
+    LowSpace,
+    /// A blockdev of the pool has gone bad or missing.
+    DeviceFailure,
+    /// The pool can no longer be written, or can no longer be extended.
+    PoolDegraded,
+}
+
+impl HookEventKind {
+    /// The name of the kind, as given to the hooks in STRATIS_EVENT.
+    fn name(self) -> &'static str {
+        match self {
+            HookEventKind::LowSpace => "low-space",
+            HookEventKind::DeviceFailure => "device-failure",
+            HookEventKind::PoolDegraded => "pool-degraded",
+        }
+    }
+}
+
+/// An event for which the hooks are run.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct HookEvent {
+    kind: HookEventKind,
+    pool_uuid: PoolUuid,
+    dev_uuid: Option<DevUuid>,
+    details: String,
+}
+
+impl HookEvent {
+    /// The event for which the hooks are run on the engine event, if any.
+    fn from_engine_event(event: &EngineEvent) -> Option<HookEvent> {
+        let (kind, pool_uuid, dev_uuid, details) = match *event {
+            EngineEvent::BlockdevStateChanged {
+                pool_uuid,
+                uuid,
+                state,
+                ..
+            } => match state {
+                BlockDevState::Bad | BlockDevState::Missing => (
+                    HookEventKind::DeviceFailure,
+                    pool_uuid,
+                    Some(uuid),
+                    state.to_string(),
+                ),
+                _ => return None,
+            },
+            EngineEvent::PoolExtendStateChanged {
+                pool_uuid, state, ..
+            } => match state {
+                PoolExtendState::DataFailed
+                | PoolExtendState::MetaFailed
+                | PoolExtendState::MetaAndDataFailed => {
+                    (HookEventKind::PoolDegraded, pool_uuid, None, state.to_string())
+                }
+                _ => return None,
+            },
+            EngineEvent::PoolSpaceStateChanged {
+                pool_uuid, state, ..
+            } => match state {
+                FreeSpaceState::Warn | FreeSpaceState::Crit => {
+                    (HookEventKind::LowSpace, pool_uuid, None, state.to_string())
+                }
+                FreeSpaceState::Good => return None,
+            },
+            EngineEvent::PoolStateChanged {
+                pool_uuid, state, ..
+            } => match state {
+                PoolState::ReadOnly
+                | PoolState::OutOfDataSpace
+                | PoolState::Failed
+                | PoolState::NeedsCheck => {
+                    (HookEventKind::PoolDegraded, pool_uuid, None, state.to_string())
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(HookEvent {
+            kind,
+            pool_uuid,
+            dev_uuid,
+            details,
+        })
+    }
+}
+
+/// The executable files in dir, in the order of their names.
+fn find_hooks(dir: &Path) -> StratisResult<Vec<PathBuf>> {
+    let mut hooks = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = fs::metadata(&path)?;
+        if metadata.is_file() && metadata.permissions().mode() & 0o111 != 0 {
+            hooks.push(path);
+        }
+    }
+    hooks.sort();
+    Ok(hooks)
+}
+
+/// Run the hook with the environment that describes event, killing it if
+/// it has not exited within timeout.
+fn run_hook(hook: &Path, event: &HookEvent, timeout: Duration) -> StratisResult<ExitStatus> {
+    let mut command = Command::new(hook);
+    command
+        .env("STRATIS_EVENT", event.kind.name())
+        .env("STRATIS_POOL_UUID", event.pool_uuid.simple().to_string())
+        .env("STRATIS_DETAILS", &event.details)
+        .stdin(Stdio::null());
+    if let Some(dev_uuid) = event.dev_uuid {
+        command.env("STRATIS_DEV_UUID", dev_uuid.simple().to_string());
+    }
+
+    let mut child = command.spawn()?;
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if start.elapsed() >= timeout {
+            child.kill()?;
+            child.wait()?;
+            return Err(StratisError::Error(format!(
+                "hook {} did not exit within {} seconds and was killed",
+                hook.display(),
+                timeout.as_secs()
+            )));
+        }
+        thread::sleep(Duration::from_millis(HOOK_POLL_INTERVAL_MS));
+    }
+}
+
+/// Run every hook in dir for event, one after the other. The outcome of
+/// each hook is logged.
+fn run_hooks(dir: &Path, event: &HookEvent, timeout: Duration) {
+    let hooks = match find_hooks(dir) {
+        Ok(hooks) => hooks,
+        Err(err) => {
+            error!("Could not list the hooks in {}: {}", dir.display(), err);
+            return;
+        }
+    };
+
+    for hook in hooks {
+        match run_hook(&hook, event, timeout) {
+            Ok(ref status) if status.success() => info!(
+                "Ran hook {} for {} event on pool {}",
+                hook.display(),
+                event.kind.name(),
+                event.pool_uuid
+            ),
+            Ok(status) => warn!(
+                "Hook {} for {} event on pool {} failed: {}",
+                hook.display(),
+                event.kind.name(),
+                event.pool_uuid,
+                status
+            ),
+            Err(err) => error!(
+                "Could not run hook {} for {} event on pool {}: {}",
+                hook.display(),
+                event.kind.name(),
+                event.pool_uuid,
+                err
+            ),
+        }
+    }
+}
+
+/// A listener that runs the hooks in a directory on the events for which
+/// hooks are run.
+#[derive(Debug)]
+pub struct Hooks {
+    events: Sender<HookEvent>,
+}
+
+impl Hooks {
+    /// Start the thread that runs the hooks in dir, killing any hook that
+    /// has not exited within timeout. The directory is read anew for each
+    /// event, so hooks may be added or removed while the daemon runs.
+    pub fn new(dir: PathBuf, timeout: Duration) -> StratisResult<Hooks> {
+        let (events, received) = channel::<HookEvent>();
+        thread::Builder::new()
+            .name("stratisd-hooks".into())
+            .spawn(move || {
+                for event in received {
+                    run_hooks(&dir, &event, timeout);
+                }
+            })?;
+        Ok(Hooks { events })
+    }
+}
+
+impl EngineListener for Hooks {
+    fn notify(&self, event: &EngineEvent) {
+        if let Some(event) = HookEvent::from_engine_event(event) {
+            if self.events.send(event).is_err() {
+                error!("The thread that runs the hooks has exited");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{File, Permissions};
+    use std::io::{Read, Write};
+
+    use tempfile;
+
+    use super::super::types::MaybeDbusPath;
+
+    use super::*;
+
+    /// Write a hook named name into dir, running script with sh. Make it
+    /// executable if executable is true.
+    fn write_hook(dir: &Path, name: &str, script: &str, executable: bool) {
+        let path = dir.join(name);
+        {
+            let mut f = File::create(&path).unwrap();
+            f.write_all(format!("#!/bin/sh\n{}\n", script).as_bytes())
+                .unwrap();
+        }
+        let mode = if executable { 0o755 } else { 0o644 };
+        fs::set_permissions(&path, Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    /// Verify that hooks are run only on the events that a site would act
+    /// on, and that each such event is described as documented.
+    fn test_hook_events() {
+        let dbus_path = MaybeDbusPath(None);
+        let pool_uuid = PoolUuid::new_v4();
+        let dev_uuid = DevUuid::new_v4();
+
+        assert_eq!(
+            HookEvent::from_engine_event(&EngineEvent::PoolSpaceStateChanged {
+                dbus_path: &dbus_path,
+                pool_uuid,
+                state: FreeSpaceState::Crit,
+            }),
+            Some(HookEvent {
+                kind: HookEventKind::LowSpace,
+                pool_uuid,
+                dev_uuid: None,
+                details: "crit".into(),
+            })
+        );
+        assert_eq!(
+            HookEvent::from_engine_event(&EngineEvent::BlockdevStateChanged {
+                dbus_path: &dbus_path,
+                pool_uuid,
+                uuid: dev_uuid,
+                state: BlockDevState::Missing,
+            }),
+            Some(HookEvent {
+                kind: HookEventKind::DeviceFailure,
+                pool_uuid,
+                dev_uuid: Some(dev_uuid),
+                details: "missing".into(),
+            })
+        );
+        assert_eq!(
+            HookEvent::from_engine_event(&EngineEvent::PoolStateChanged {
+                dbus_path: &dbus_path,
+                pool_uuid,
+                state: PoolState::ReadOnly,
+            }).map(|event| event.kind),
+            Some(HookEventKind::PoolDegraded)
+        );
+        assert_eq!(
+            HookEvent::from_engine_event(&EngineEvent::PoolExtendStateChanged {
+                dbus_path: &dbus_path,
+                pool_uuid,
+                state: PoolExtendState::MetaFailed,
+            }).map(|event| event.kind),
+            Some(HookEventKind::PoolDegraded)
+        );
+
+        assert_eq!(
+            HookEvent::from_engine_event(&EngineEvent::PoolSpaceStateChanged {
+                dbus_path: &dbus_path,
+                pool_uuid,
+                state: FreeSpaceState::Good,
+            }),
+            None
+        );
+        assert_eq!(
+            HookEvent::from_engine_event(&EngineEvent::BlockdevStateChanged {
+                dbus_path: &dbus_path,
+                pool_uuid,
+                uuid: dev_uuid,
+                state: BlockDevState::InUse,
+            }),
+            None
+        );
+        assert_eq!(
+            HookEvent::from_engine_event(&EngineEvent::PoolStateChanged {
+                dbus_path: &dbus_path,
+                pool_uuid,
+                state: PoolState::Running,
+            }),
+            None
+        );
+    }
+
+    #[test]
+    /// Verify that every executable hook is run, in the order of the names
+    /// of the hooks, with the documented environment, that a file that is
+    /// not executable is not run, and that a hook that runs too long is
+    /// killed without holding up the hooks after it.
+    fn test_run_hooks() {
+        let dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let out = dir.path().join("out");
+        let record = format!(
+            "echo \"$(basename $0) $STRATIS_EVENT $STRATIS_POOL_UUID $STRATIS_DEV_UUID \
+             $STRATIS_DETAILS\" >> {}",
+            out.display()
+        );
+        write_hook(dir.path(), "10-first", &record, true);
+        write_hook(dir.path(), "20-slow", "sleep 10", true);
+        write_hook(dir.path(), "30-second", &record, true);
+        write_hook(dir.path(), "40-disabled", &record, false);
+
+        let event = HookEvent {
+            kind: HookEventKind::DeviceFailure,
+            pool_uuid: PoolUuid::new_v4(),
+            dev_uuid: Some(DevUuid::new_v4()),
+            details: "bad".into(),
+        };
+        let start = Instant::now();
+        run_hooks(dir.path(), &event, Duration::from_secs(1));
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        let mut contents = String::new();
+        File::open(&out)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        let line = format!(
+            "device-failure {} {} bad",
+            event.pool_uuid.simple(),
+            event.dev_uuid.unwrap().simple()
+        );
+        assert_eq!(
+            contents,
+            format!("10-first {}\n30-second {}\n", line, line)
+        );
+    }
+}
@@ -6,9 +6,106 @@ macro_rules! calculate_redundancy {
     ($redundancy:ident) => {
         match $redundancy {
             None | Some(0) => Redundancy::NONE,
+            Some(1) => Redundancy::RAID1,
+            Some(2) => Redundancy::RAID5,
             Some(n) => {
-                let message = format!("code {} does not correspond to any redundancy", n);
-                return Err(StratisError::Engine(ErrorEnum::Error, message));
+                return Err(StratisError::Structured(
+                    ErrorEnum::Error,
+                    ErrorPayload::new("RedundancyUnsupported").param("code", n),
+                ));
+            }
+        }
+    };
+}
+
+macro_rules! calculate_size_policy {
+    ($size_policy:ident) => {
+        match $size_policy {
+            Some(0) => MixedSizePolicy::Accept,
+            None | Some(1) => MixedSizePolicy::Warn,
+            Some(2) => MixedSizePolicy::Reject,
+            Some(n) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Error,
+                    ErrorPayload::new("SizePolicyUnsupported").param("code", n),
+                ));
+            }
+        }
+    };
+}
+
+macro_rules! calculate_signature_policy {
+    ($signature_policy:ident) => {
+        match $signature_policy {
+            None | Some(0) => SignaturePolicy::Refuse,
+            Some(1) => SignaturePolicy::Ignore,
+            Some(2) => SignaturePolicy::Wipe,
+            Some(n) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Error,
+                    ErrorPayload::new("SignaturePolicyUnsupported").param("code", n),
+                ));
+            }
+        }
+    };
+}
+
+macro_rules! calculate_cache_type {
+    ($cache_type:ident) => {
+        match $cache_type {
+            None | Some(0) => CacheType::Cache,
+            Some(1) => CacheType::WriteCache,
+            Some(n) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Error,
+                    ErrorPayload::new("CacheTypeUnsupported").param("code", n),
+                ));
+            }
+        }
+    };
+}
+
+macro_rules! calculate_allocation_strategy {
+    ($strategy:ident) => {
+        match $strategy {
+            None | Some(0) => AllocationStrategy::FillFirst,
+            Some(1) => AllocationStrategy::RoundRobin,
+            Some(2) => AllocationStrategy::SsdFirst,
+            Some(n) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Error,
+                    ErrorPayload::new("AllocationStrategyUnsupported").param("code", n),
+                ));
+            }
+        }
+    };
+}
+
+macro_rules! calculate_io_class {
+    ($io_class:ident) => {
+        match $io_class {
+            None | Some(0) => IoClass::BestEffort,
+            Some(1) => IoClass::Idle,
+            Some(n) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Error,
+                    ErrorPayload::new("IoClassUnsupported").param("code", n),
+                ));
+            }
+        }
+    };
+}
+
+macro_rules! calculate_snapshot_interval {
+    ($interval:ident) => {
+        match $interval {
+            0 => SnapshotInterval::Hourly,
+            1 => SnapshotInterval::Daily,
+            n => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Error,
+                    ErrorPayload::new("SnapshotIntervalUnsupported").param("code", n),
+                ));
             }
         }
     };
@@ -42,9 +139,9 @@ macro_rules! rename_filesystem_pre {
         }
 
         if $s.filesystems.contains_name($new_name) {
-            return Err(StratisError::Engine(
+            return Err(StratisError::Structured(
                 ErrorEnum::AlreadyExists,
-                $new_name.into(),
+                ErrorPayload::new("FilesystemNameExists").param("name", $new_name),
             ));
         }
         old_name
@@ -63,9 +160,9 @@ macro_rules! rename_pool_pre {
         }
 
         if $s.pools.contains_name($new_name) {
-            return Err(StratisError::Engine(
+            return Err(StratisError::Structured(
                 ErrorEnum::AlreadyExists,
-                $new_name.into(),
+                ErrorPayload::new("PoolNameExists").param("name", $new_name),
             ));
         }
         old_name
@@ -4,6 +4,8 @@
 
 pub use devicemapper::{IEC, SECTOR_SIZE};
 
+pub use self::changes::{changes_since, Change, ChangeKind, ChangeToken, ObjectKind};
+
 pub use self::devlinks::filesystem_mount_path;
 
 pub use self::engine::BlockDev;
@@ -13,27 +15,64 @@ pub use self::engine::Pool;
 
 pub use self::event::{get_engine_listener_list_mut, EngineEvent, EngineListener};
 
+pub use self::hooks::{Hooks, DEFAULT_HOOK_TIMEOUT_SECS};
+
 pub use self::sim_engine::SimEngine;
-pub use self::strat_engine::StratEngine;
 
+pub use self::stats::{count, count_by, persist_statistics, statistics, Counter, STATS_PATH};
+
+#[cfg(feature = "fuzzing")]
+pub use self::strat_engine::fuzz;
+pub use self::strat_engine::{probe_device, StratEngine};
+
+pub use self::types::AddDataDevsPlan;
+pub use self::types::AllocationStrategy;
 pub use self::types::BlockDevState;
 pub use self::types::BlockDevTier;
+pub use self::types::CacheType;
+pub use self::types::Capability;
+pub use self::types::CacheUsage;
+pub use self::types::ClonedDevice;
+pub use self::types::CreationParams;
+pub use self::types::Creator;
+pub use self::types::DestroyPoolPlan;
 pub use self::types::DevUuid;
+pub use self::types::DmDeviceInfo;
+pub use self::types::FilesystemFilter;
 pub use self::types::FilesystemUuid;
+pub use self::types::FormatVersions;
+pub use self::types::IoClass;
+pub use self::types::MAX_FORMAT_VERSIONS;
 pub use self::types::MaybeDbusPath;
+pub use self::types::MetadataStats;
+pub use self::types::MixedSizePolicy;
 pub use self::types::Name;
+pub use self::types::PlannedBlockDev;
+pub use self::types::PlannedFilesystem;
+pub use self::types::PoolBindings;
 pub use self::types::PoolUuid;
+pub use self::types::ReclaimableDevice;
 pub use self::types::Redundancy;
 pub use self::types::RenameAction;
+pub use self::types::SignaturePolicy;
+pub use self::types::SnapshotInterval;
+pub use self::types::SnapshotSchedule;
+pub use self::types::Watermarks;
+pub use self::types::WorkerPriority;
 
 #[macro_use]
 mod macros;
 
+mod changes;
+pub mod commands;
 mod devlinks;
 #[allow(module_inception)]
 mod engine;
 mod event;
+mod hooks;
+mod schedule;
 mod sim_engine;
+mod stats;
 mod strat_engine;
 mod structures;
 mod types;
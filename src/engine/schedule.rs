@@ -0,0 +1,254 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to keep the snapshot schedules of the filesystems of a pool. The
+// schedules are run whenever stratisd's timer for them expires: a schedule
+// takes a snapshot of its filesystem unless it has already run in the
+// current hour or day, and the oldest of the snapshots that it has taken are
+// destroyed once there are more than it keeps. A snapshot that a schedule
+// did not take is never destroyed by it.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use super::types::{FilesystemUuid, SnapshotInterval, SnapshotSchedule};
+
+/// The snapshot schedule of a filesystem, and what it has done.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScheduleState {
+    pub schedule: SnapshotSchedule,
+    /// When the schedule last ran, whether or not its snapshot could be
+    /// taken.
+    pub last_run: Option<DateTime<Utc>>,
+    /// The snapshots taken by the schedule that have not been destroyed,
+    /// the oldest first.
+    pub snapshots: Vec<FilesystemUuid>,
+}
+
+impl ScheduleState {
+    pub fn new(schedule: SnapshotSchedule) -> ScheduleState {
+        ScheduleState {
+            schedule,
+            last_run: None,
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Whether the schedule is due to take a snapshot at now.
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        let period_start = self.schedule.interval.period_start(now);
+        self.last_run.map_or(true, |last_run| last_run < period_start)
+    }
+}
+
+/// The snapshot schedules of the filesystems of a pool.
+#[derive(Debug, Default)]
+pub struct SnapshotSchedules {
+    schedules: HashMap<FilesystemUuid, ScheduleState>,
+}
+
+impl SnapshotSchedules {
+    pub fn new(states: Vec<(FilesystemUuid, ScheduleState)>) -> SnapshotSchedules {
+        SnapshotSchedules {
+            schedules: states.into_iter().collect(),
+        }
+    }
+
+    /// The state of the schedule of each filesystem that has one.
+    pub fn states(&self) -> &HashMap<FilesystemUuid, ScheduleState> {
+        &self.schedules
+    }
+
+    /// The schedule of the filesystem, if it has one.
+    pub fn get(&self, uuid: FilesystemUuid) -> Option<SnapshotSchedule> {
+        self.schedules.get(&uuid).map(|state| state.schedule)
+    }
+
+    /// Set the schedule of the filesystem, or remove it if schedule is
+    /// None. A schedule that is changed keeps the snapshots it has taken;
+    /// a schedule that is removed forgets them, so that they are kept.
+    /// Returns true if the schedule was changed.
+    pub fn set(&mut self, uuid: FilesystemUuid, schedule: Option<SnapshotSchedule>) -> bool {
+        match schedule {
+            Some(schedule) => match self.schedules.entry(uuid) {
+                Entry::Occupied(mut entry) => {
+                    if entry.get().schedule == schedule {
+                        return false;
+                    }
+                    entry.get_mut().schedule = schedule;
+                    true
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(ScheduleState::new(schedule));
+                    true
+                }
+            },
+            None => self.schedules.remove(&uuid).is_some(),
+        }
+    }
+
+    /// Forget the schedule of each filesystem, and each snapshot taken by a
+    /// schedule, that no longer exists.
+    /// Returns true if anything was forgotten.
+    pub fn forget_missing<F>(&mut self, exists: F) -> bool
+    where
+        F: Fn(FilesystemUuid) -> bool,
+    {
+        let count = |schedules: &HashMap<FilesystemUuid, ScheduleState>| {
+            schedules
+                .values()
+                .fold(schedules.len(), |acc, state| acc + state.snapshots.len())
+        };
+        let before = count(&self.schedules);
+        self.schedules.retain(|&uuid, _| exists(uuid));
+        for state in self.schedules.values_mut() {
+            state.snapshots.retain(|&uuid| exists(uuid));
+        }
+        count(&self.schedules) != before
+    }
+
+    /// The filesystems whose schedules are due to take a snapshot at now,
+    /// each with the interval of its schedule.
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<(FilesystemUuid, SnapshotInterval)> {
+        self.schedules
+            .iter()
+            .filter(|&(_, state)| state.is_due(now))
+            .map(|(&uuid, state)| (uuid, state.schedule.interval))
+            .collect()
+    }
+
+    /// Record that the schedule of origin ran at now, and took snapshot, if
+    /// the snapshot could be taken. Return the snapshots taken by the
+    /// schedule beyond those that it keeps, the oldest first. These remain
+    /// recorded until they are found to have been destroyed.
+    pub fn record_run(
+        &mut self,
+        origin: FilesystemUuid,
+        now: DateTime<Utc>,
+        snapshot: Option<FilesystemUuid>,
+    ) -> Vec<FilesystemUuid> {
+        match self.schedules.get_mut(&origin) {
+            Some(state) => {
+                state.last_run = Some(now);
+                state.snapshots.extend(snapshot);
+                let excess = state
+                    .snapshots
+                    .len()
+                    .saturating_sub(state.schedule.retain as usize);
+                state.snapshots[..excess].to_vec()
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+/// The name of the snapshot of the filesystem named origin that a schedule
+/// with the given interval takes at now, such as "home-hourly-20190214-1300".
+pub fn scheduled_snapshot_name(
+    origin: &str,
+    interval: SnapshotInterval,
+    now: DateTime<Utc>,
+) -> String {
+    format!(
+        "{}-{}-{}",
+        origin,
+        interval,
+        interval.period_start(now).format("%Y%m%d-%H%M")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    /// Verify that a schedule is due once in each period, that it keeps as
+    /// many snapshots as its retention, and that the snapshots beyond those
+    /// are given oldest first until they are forgotten.
+    fn test_schedule_runs() {
+        let origin = FilesystemUuid::new_v4();
+        let mut schedules = SnapshotSchedules::default();
+        assert!(schedules.set(
+            origin,
+            Some(SnapshotSchedule {
+                interval: SnapshotInterval::Hourly,
+                retain: 2,
+            })
+        ));
+
+        let start = Utc.ymd(2019, 2, 14).and_hms(13, 27, 5);
+        let mut snapshots = Vec::new();
+        for hour in 0..3 {
+            let now = start + Duration::hours(hour);
+            assert_eq!(
+                schedules.due(now),
+                vec![(origin, SnapshotInterval::Hourly)]
+            );
+            let snapshot = FilesystemUuid::new_v4();
+            snapshots.push(snapshot);
+            let excess = schedules.record_run(origin, now, Some(snapshot));
+            assert!(schedules.due(now + Duration::minutes(30)).is_empty());
+            if hour < 2 {
+                assert!(excess.is_empty());
+            } else {
+                assert_eq!(excess, vec![snapshots[0]]);
+            }
+        }
+
+        assert!(schedules.forget_missing(|uuid| uuid != snapshots[0]));
+        assert!(!schedules.forget_missing(|uuid| uuid != snapshots[0]));
+        assert_eq!(schedules.states()[&origin].snapshots, &snapshots[1..]);
+
+        assert!(schedules.forget_missing(|uuid| uuid != origin));
+        assert_eq!(schedules.get(origin), None);
+    }
+
+    #[test]
+    /// Verify that a schedule is changed only if it differs, and that a
+    /// schedule which is removed forgets the snapshots that it took.
+    fn test_set_schedule() {
+        let origin = FilesystemUuid::new_v4();
+        let hourly = SnapshotSchedule {
+            interval: SnapshotInterval::Hourly,
+            retain: 24,
+        };
+        let daily = SnapshotSchedule {
+            interval: SnapshotInterval::Daily,
+            retain: 7,
+        };
+        let mut schedules = SnapshotSchedules::default();
+        assert!(!schedules.set(origin, None));
+        assert!(schedules.set(origin, Some(hourly)));
+        assert!(!schedules.set(origin, Some(hourly)));
+
+        let now = Utc.ymd(2019, 2, 14).and_hms(13, 27, 5);
+        schedules.record_run(origin, now, Some(FilesystemUuid::new_v4()));
+        assert!(schedules.set(origin, Some(daily)));
+        assert_eq!(schedules.get(origin), Some(daily));
+        assert_eq!(schedules.states()[&origin].snapshots.len(), 1);
+        assert!(schedules.due(now).is_empty());
+
+        assert!(schedules.set(origin, None));
+        assert!(schedules.states().is_empty());
+    }
+
+    #[test]
+    /// Verify that the name of a scheduled snapshot is that of the period
+    /// in which it was taken.
+    fn test_scheduled_snapshot_name() {
+        let now = Utc.ymd(2019, 2, 14).and_hms(13, 27, 5);
+        assert_eq!(
+            scheduled_snapshot_name("home", SnapshotInterval::Hourly, now),
+            "home-hourly-20190214-1300"
+        );
+        assert_eq!(
+            scheduled_snapshot_name("home", SnapshotInterval::Daily, now),
+            "home-daily-20190214-0000"
+        );
+    }
+}
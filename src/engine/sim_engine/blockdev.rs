@@ -7,12 +7,13 @@ use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use chrono::{DateTime, TimeZone, Utc};
-use uuid::Uuid;
 
-use devicemapper::{Bytes, Sectors, IEC};
+use devicemapper::Sectors;
 
 use super::super::engine::BlockDev;
-use super::super::types::{BlockDevState, MaybeDbusPath};
+use super::super::types::{
+    BlockDevState, DevUuid, FormatVersions, MaybeDbusPath, MetadataStats, MAX_FORMAT_VERSIONS,
+};
 
 use super::randomization::Randomizer;
 
@@ -21,6 +22,7 @@ use super::randomization::Randomizer;
 pub struct SimDev {
     devnode: PathBuf,
     rdm: Rc<RefCell<Randomizer>>,
+    size: Sectors,
     user_info: Option<String>,
     hardware_info: Option<String>,
     initialization_time: u64,
@@ -45,7 +47,11 @@ impl BlockDev for SimDev {
     }
 
     fn size(&self) -> Sectors {
-        Bytes(IEC::Gi).sectors()
+        self.size
+    }
+
+    fn usable_size(&self) -> Sectors {
+        self.size()
     }
 
     fn state(&self) -> BlockDevState {
@@ -59,16 +65,37 @@ impl BlockDev for SimDev {
     fn get_dbus_path(&self) -> &MaybeDbusPath {
         &self.dbus_path
     }
+
+    fn format_versions(&self) -> FormatVersions {
+        MAX_FORMAT_VERSIONS
+    }
+
+    fn metadata_corruptions(&self) -> u64 {
+        0
+    }
+
+    fn metadata_degraded(&self) -> bool {
+        false
+    }
+
+    fn metadata_stats(&self) -> MetadataStats {
+        MetadataStats::default()
+    }
+
+    fn metadata_write_throttled(&self) -> bool {
+        false
+    }
 }
 
 impl SimDev {
-    /// Generates a new device from any devnode.
-    pub fn new(rdm: Rc<RefCell<Randomizer>>, devnode: &Path) -> (Uuid, SimDev) {
+    /// Generates a new device of the given size from any devnode.
+    pub fn new(rdm: Rc<RefCell<Randomizer>>, devnode: &Path, size: Sectors) -> (DevUuid, SimDev) {
         (
-            Uuid::new_v4(),
+            DevUuid::new_v4(),
             SimDev {
                 devnode: devnode.to_owned(),
                 rdm,
+                size,
                 user_info: None,
                 hardware_info: None,
                 initialization_time: Utc::now().timestamp() as u64,
@@ -0,0 +1,140 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::cmp::min;
+
+use devicemapper::{Bytes, Sectors, IEC};
+
+use stratis::{ErrorEnum, StratisError, StratisResult};
+
+/// The size of a filesystem created without a size in a pool that has no
+/// default filesystem size, the same as in the real engine.
+const DEFAULT_FS_SIZE: Sectors = Sectors(2 * IEC::Gi);
+
+/// The space that a simulated filesystem uses when it is created.
+const DEFAULT_INITIAL_USED: Bytes = Bytes(12_345_678);
+
+/// The sizes of simulated devices and filesystems, and the space that the
+/// filesystems use, which grows with each second of simulated time. The
+/// simulated clock only moves when it is advanced, so that clients may
+/// watch space run out at whatever pace they choose.
+#[derive(Debug)]
+pub struct Capacities {
+    device_size: Sectors,
+    filesystem_size: Sectors,
+    initial_used: Bytes,
+    growth: Bytes,
+    clock: u64,
+}
+
+impl Default for Capacities {
+    fn default() -> Capacities {
+        Capacities {
+            device_size: Bytes(IEC::Gi).sectors(),
+            filesystem_size: DEFAULT_FS_SIZE,
+            initial_used: DEFAULT_INITIAL_USED,
+            growth: Bytes(0),
+            clock: 0,
+        }
+    }
+}
+
+impl Capacities {
+    /// The size of a device added from now on.
+    pub fn device_size(&self) -> Sectors {
+        self.device_size
+    }
+
+    /// The size of a filesystem created from now on without a size, in a
+    /// pool that has no default filesystem size.
+    pub fn filesystem_size(&self) -> Sectors {
+        self.filesystem_size
+    }
+
+    /// The simulated time, in seconds since the simulator started.
+    pub fn now(&self) -> u64 {
+        self.clock
+    }
+
+    /// The bytes by which the space that a filesystem uses grows in each
+    /// second of simulated time.
+    pub fn growth(&self) -> Bytes {
+        self.growth
+    }
+
+    /// The space used by a filesystem of the given size that was created at
+    /// the given simulated time. A filesystem never uses more than its size.
+    pub fn used(&self, size: Sectors, created: u64) -> Bytes {
+        let grown = (*self.growth)
+            .saturating_mul(self.clock.saturating_sub(created))
+            .saturating_add(*self.initial_used);
+        min(Bytes(grown), size.bytes())
+    }
+
+    /// Set each of the capacities that is specified, leaving the others
+    /// unchanged. Devices and filesystems that already exist keep their
+    /// sizes.
+    pub fn configure(
+        &mut self,
+        device_size: Option<Sectors>,
+        filesystem_size: Option<Sectors>,
+        initial_used: Option<Bytes>,
+        growth: Option<Bytes>,
+    ) -> StratisResult<()> {
+        if device_size == Some(Sectors(0)) || filesystem_size == Some(Sectors(0)) {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                "simulated devices and filesystems must have a size".into(),
+            ));
+        }
+        self.device_size = device_size.unwrap_or(self.device_size);
+        self.filesystem_size = filesystem_size.unwrap_or(self.filesystem_size);
+        self.initial_used = initial_used.unwrap_or(self.initial_used);
+        self.growth = growth.unwrap_or(self.growth);
+        Ok(())
+    }
+
+    /// Advance the simulated clock by the given number of seconds.
+    pub fn advance(&mut self, seconds: u64) {
+        self.clock = self.clock.saturating_add(seconds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the space a filesystem uses grows with simulated time
+    /// from the moment it was created, and stops growing at its size.
+    fn used_grows() {
+        let mut caps = Capacities::default();
+        let size = Bytes(IEC::Mi).sectors();
+        caps.configure(None, None, Some(Bytes(0)), Some(Bytes(IEC::Ki))).unwrap();
+        caps.advance(10);
+        let created = caps.now();
+        assert_eq!(caps.used(size, created), Bytes(0));
+
+        caps.advance(10);
+        assert_eq!(caps.used(size, created), Bytes(10 * IEC::Ki));
+        assert_eq!(caps.used(size, 0), Bytes(20 * IEC::Ki));
+
+        caps.advance(IEC::Ki);
+        assert_eq!(caps.used(size, created), size.bytes());
+    }
+
+    #[test]
+    /// Verify that only the specified capacities are changed, and that a
+    /// size of 0 is refused.
+    fn configure_some() {
+        let mut caps = Capacities::default();
+        caps.configure(Some(Sectors(1024)), None, None, None).unwrap();
+        assert_eq!(caps.device_size(), Sectors(1024));
+        assert_eq!(caps.filesystem_size(), DEFAULT_FS_SIZE);
+        assert_eq!(caps.growth(), Bytes(0));
+
+        assert!(caps.configure(None, Some(Sectors(0)), None, None).is_err());
+        assert_eq!(caps.filesystem_size(), DEFAULT_FS_SIZE);
+    }
+}
@@ -0,0 +1,169 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use stratis::{ErrorEnum, StratisError, StratisResult};
+
+use super::super::strat_engine::{first_free, CryptOps, LUKS2_KEYSLOTS, LUKS2_TOKENS};
+
+/// The protected header of a JWE sealed with the tang pin,
+/// {"clevis":{"pin":"tang"}}.
+const TANG_PROTECTED: &str = "eyJjbGV2aXMiOnsicGluIjoidGFuZyJ9fQ";
+
+/// A LUKS2 device held in memory.
+#[derive(Debug)]
+struct SimLuks {
+    keyslots: BTreeMap<u32, String>,
+    tokens: BTreeMap<u32, String>,
+}
+
+/// The operations on LUKS2 devices, done on devices held in memory, so
+/// that the dm-crypt layer can be simulated, and tested without root
+/// privileges.
+#[derive(Debug, Default)]
+pub struct SimCrypt {
+    devices: RefCell<HashMap<PathBuf, SimLuks>>,
+    active: RefCell<HashMap<String, PathBuf>>,
+}
+
+impl SimCrypt {
+    /// Apply f to the LUKS2 device at physical.
+    fn with_device<F, T>(&self, physical: &Path, f: F) -> StratisResult<T>
+    where
+        F: FnOnce(&mut SimLuks) -> T,
+    {
+        match self.devices.borrow_mut().get_mut(physical) {
+            Some(device) => Ok(f(device)),
+            None => Err(StratisError::Engine(
+                ErrorEnum::NotFound,
+                format!("{} is not a LUKS2 device", physical.display()),
+            )),
+        }
+    }
+
+    /// Deactivate the dm-crypt device opened on the LUKS2 device at
+    /// physical, if there is one, and wipe its header.
+    pub fn wipe(&self, physical: &Path) {
+        self.active.borrow_mut().retain(|_, opened| opened != physical);
+        self.devices.borrow_mut().remove(physical);
+    }
+}
+
+impl CryptOps for SimCrypt {
+    fn format(
+        &self,
+        physical: &Path,
+        _luks_uuid: Uuid,
+        _label: &str,
+        passphrase: &str,
+    ) -> StratisResult<()> {
+        self.devices.borrow_mut().insert(
+            physical.to_owned(),
+            SimLuks {
+                keyslots: [(0, passphrase.to_owned())].iter().cloned().collect(),
+                tokens: BTreeMap::new(),
+            },
+        );
+        Ok(())
+    }
+
+    fn activate(&self, physical: &Path, name: &str, passphrase: &str) -> StratisResult<bool> {
+        let opened = self.with_device(physical, |device| {
+            device.keyslots.values().any(|key| key == passphrase)
+        })?;
+        if opened {
+            self.active
+                .borrow_mut()
+                .insert(name.to_owned(), physical.to_owned());
+        }
+        Ok(opened)
+    }
+
+    fn deactivate(&self, name: &str) -> StratisResult<()> {
+        match self.active.borrow_mut().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(StratisError::Engine(
+                ErrorEnum::NotFound,
+                format!("no dm-crypt device {} is active", name),
+            )),
+        }
+    }
+
+    fn add_keyslot(
+        &self,
+        physical: &Path,
+        passphrase: &str,
+        new_passphrase: &str,
+    ) -> StratisResult<Option<u32>> {
+        let used = self.keyslots(physical)?;
+        let keyslot = first_free(&used, LUKS2_KEYSLOTS, "keyslot", physical)?;
+        self.with_device(physical, |device| {
+            if device.keyslots.values().any(|key| key == passphrase) {
+                device.keyslots.insert(keyslot, new_passphrase.to_owned());
+                Some(keyslot)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn remove_keyslot(&self, physical: &Path, keyslot: u32) -> StratisResult<()> {
+        self.with_device(physical, |device| {
+            device.keyslots.remove(&keyslot);
+        })
+    }
+
+    fn keyslots(&self, physical: &Path) -> StratisResult<Vec<u32>> {
+        self.with_device(physical, |device| device.keyslots.keys().cloned().collect())
+    }
+
+    fn tokens(&self, physical: &Path) -> StratisResult<Vec<(u32, String)>> {
+        self.with_device(physical, |device| {
+            device
+                .tokens
+                .iter()
+                .map(|(&token_id, json)| (token_id, json.clone()))
+                .collect()
+        })
+    }
+
+    fn add_token(&self, physical: &Path, json: &str) -> StratisResult<u32> {
+        let used = self.with_device(physical, |device| {
+            device.tokens.keys().cloned().collect::<Vec<_>>()
+        })?;
+        let token_id = first_free(&used, LUKS2_TOKENS, "token", physical)?;
+        self.with_device(physical, |device| {
+            device.tokens.insert(token_id, json.to_owned());
+            token_id
+        })
+    }
+
+    fn remove_token(&self, physical: &Path, token_id: u32) -> StratisResult<()> {
+        self.with_device(physical, |device| {
+            device.tokens.remove(&token_id);
+        })
+    }
+
+    /// The secret is kept in the clear, as the ciphertext of the JWE.
+    /// A url with no host stands for a Tang server that can not be
+    /// reached.
+    fn seal_tang(&self, url: &str, _thumbprint: &str, secret: &str) -> StratisResult<String> {
+        if url == "http://" {
+            return Err(StratisError::Error("no Tang server is reached".into()));
+        }
+        Ok(format!("{}...{}.", TANG_PROTECTED, secret))
+    }
+
+    fn unseal(&self, jwe: &str) -> StratisResult<String> {
+        jwe.split('.')
+            .nth(3)
+            .map(|secret| secret.to_owned())
+            .ok_or_else(|| StratisError::Error("not a JWE in compact form".into()))
+    }
+}
@@ -6,29 +6,65 @@ extern crate libc;
 
 use std::cell::RefCell;
 use std::collections::hash_map::RandomState;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use devicemapper::Device;
+use chrono::{DateTime, Duration, Utc};
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use devicemapper::{Bytes, Device, Sectors};
 
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
+
+use super::super::changes::{record_change, record_pool_change, ChangeKind, ObjectKind};
 use super::super::engine::{Engine, Eventable, Pool};
+use super::super::strat_engine::{
+    bind_devices_tang, header_bindings, initialize_crypt, unbind_devices_tang, LUKS2_KEYSLOTS,
+};
 use super::super::structures::Table;
-use super::super::types::{Name, PoolUuid, Redundancy, RenameAction};
-
+use super::super::types::{
+    Capability, ClonedDevice, Creator, MixedSizePolicy, Name, PoolBindings, PoolUuid,
+    ReclaimableDevice, Redundancy, RenameAction, SignaturePolicy,
+    DEFAULT_UNDO_DESTROY_WINDOW_SECS, validate_tang,
+};
+
+use super::capacity::Capacities;
+use super::crypt::SimCrypt;
 use super::pool::SimPool;
 use super::randomization::Randomizer;
 
 #[derive(Debug, Default)]
 pub struct SimEngine {
-    pools: Table<SimPool>,
+    pools: Table<PoolUuid, SimPool>,
     rdm: Rc<RefCell<Randomizer>>,
+    caps: Rc<RefCell<Capacities>>,
+    reclaimable: Vec<ReclaimableDevice>,
+    // Pools destroyed with their signatures kept, which may be set up again
+    destroyed: HashMap<PoolUuid, (Name, SimPool)>,
+    // The keys kept to unlock encrypted pools
+    keys: HashMap<PoolUuid, String>,
+    // The LUKS2 devices of the encrypted pools
+    crypt: SimCrypt,
 }
 
-impl SimEngine {}
+impl SimEngine {
+    /// The devnodes of the LUKS2 devices of the simulated encrypted pool
+    /// designated by pool_uuid. Return an error if there are none.
+    fn crypt_devices(&self, pool_uuid: PoolUuid) -> StratisResult<Vec<PathBuf>> {
+        match self.pools.get_by_uuid(pool_uuid) {
+            Some((_, pool)) if pool.encrypted() && !pool.blockdevs().is_empty() => Ok(pool
+                .blockdevs()
+                .iter()
+                .map(|&(_, bd)| bd.devnode())
+                .collect()),
+            _ => Err(StratisError::Engine(
+                ErrorEnum::NotFound,
+                format!("no devices of an encrypted pool {} found", pool_uuid),
+            )),
+        }
+    }
+}
 
 impl Engine for SimEngine {
     fn create_pool(
@@ -36,28 +72,82 @@ impl Engine for SimEngine {
         name: &str,
         blockdev_paths: &[&Path],
         redundancy: Option<u16>,
+        size_policy: Option<u16>,
+        signature_policy: Option<u16>,
+        created_by: Option<Creator>,
     ) -> StratisResult<PoolUuid> {
         let redundancy = calculate_redundancy!(redundancy);
+        // Simulated devices are all of the same size, and have no
+        // signatures, so these policies are only checked for validity.
+        let _ = calculate_size_policy!(size_policy);
+        let _ = calculate_signature_policy!(signature_policy);
 
         if self.pools.contains_name(name) {
-            return Err(StratisError::Engine(ErrorEnum::AlreadyExists, name.into()));
+            return Err(StratisError::Structured(
+                ErrorEnum::AlreadyExists,
+                ErrorPayload::new("PoolNameExists").param("name", name),
+            ));
         }
 
         let device_set: HashSet<_, RandomState> = HashSet::from_iter(blockdev_paths);
         let devices = device_set.into_iter().map(|x| *x).collect::<Vec<&Path>>();
-
-        let (pool_uuid, pool) = SimPool::new(&Rc::clone(&self.rdm), &devices, redundancy);
+        redundancy.check_devices(devices.len())?;
+
+        let (pool_uuid, pool) = SimPool::new(
+            &Rc::clone(&self.rdm),
+            &self.caps,
+            &devices,
+            redundancy,
+            created_by,
+        );
 
         if self.rdm.borrow_mut().throw_die() {
             return Err(StratisError::Engine(ErrorEnum::Error, "X".into()));
         }
 
+        record_pool_change(pool_uuid, &pool, ChangeKind::Created);
         self.pools
             .insert(Name::new(name.to_owned()), pool_uuid, pool);
 
         Ok(pool_uuid)
     }
 
+    fn create_encrypted_pool(
+        &mut self,
+        name: &str,
+        blockdev_paths: &[&Path],
+        redundancy: Option<u16>,
+        size_policy: Option<u16>,
+        signature_policy: Option<u16>,
+        passphrase: &str,
+        created_by: Option<Creator>,
+    ) -> StratisResult<PoolUuid> {
+        if passphrase.is_empty() {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                "the passphrase must not be empty".into(),
+            ));
+        }
+
+        let pool_uuid = self.create_pool(
+            name,
+            blockdev_paths,
+            redundancy,
+            size_policy,
+            signature_policy,
+            created_by,
+        )?;
+        let pool = self.pools
+            .get_mut_by_uuid(pool_uuid)
+            .expect("the pool was created above")
+            .1;
+        pool.set_encrypted();
+        for (_, bd) in pool.blockdevs() {
+            initialize_crypt(&self.crypt, pool_uuid, &bd.devnode(), passphrase)?;
+        }
+        Ok(pool_uuid)
+    }
+
     fn block_evaluate(
         &mut self,
         device: Device,
@@ -68,25 +158,215 @@ impl Engine for SimEngine {
         Ok(None)
     }
 
-    fn destroy_pool(&mut self, uuid: PoolUuid) -> StratisResult<bool> {
+    fn destroy_pool(&mut self, uuid: PoolUuid, keep_signatures: bool) -> StratisResult<bool> {
         if let Some((_, pool)) = self.pools.get_by_uuid(uuid) {
             if pool.has_filesystems() {
-                return Err(StratisError::Engine(
+                return Err(StratisError::Structured(
                     ErrorEnum::Busy,
-                    "filesystems remaining on pool".into(),
+                    ErrorPayload::new("PoolHasFilesystems"),
                 ));
             };
         } else {
             return Ok(false);
         }
-        self.pools
+        let (pool_name, mut pool) = self.pools
             .remove_by_uuid(uuid)
-            .expect("Must succeed since self.pool.get_by_uuid() returned a value")
-            .1
-            .destroy()?;
+            .expect("Must succeed since self.pool.get_by_uuid() returned a value");
+        pool.destroy(keep_signatures)?;
+        if keep_signatures {
+            let destroyed = Utc::now();
+            self.reclaimable
+                .extend(pool.blockdevs().into_iter().map(|(dev_uuid, bd)| {
+                    ReclaimableDevice {
+                        devnode: bd.devnode(),
+                        pool_uuid: uuid,
+                        dev_uuid,
+                        destroyed,
+                    }
+                }));
+        } else {
+            for (_, bd) in pool.blockdevs() {
+                self.crypt.wipe(&bd.devnode());
+            }
+        }
+        record_pool_change(uuid, &pool, ChangeKind::Deleted);
+        if keep_signatures {
+            self.destroyed.insert(uuid, (pool_name, pool));
+        }
         Ok(true)
     }
 
+    fn reclaimable_devices(&self) -> StratisResult<Vec<ReclaimableDevice>> {
+        Ok(self.reclaimable.clone())
+    }
+
+    fn wipe_reclaimable_device(&mut self, devnode: &Path) -> StratisResult<bool> {
+        let count = self.reclaimable.len();
+        self.reclaimable.retain(|dev| dev.devnode != devnode);
+        let wiped = self.reclaimable.len() != count;
+        if wiped {
+            self.crypt.wipe(devnode);
+        }
+        Ok(wiped)
+    }
+
+    fn cloned_devices(&self) -> StratisResult<Vec<ClonedDevice>> {
+        Ok(vec![])
+    }
+
+    fn undo_destroy_pool(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool> {
+        if self.pools.contains_uuid(pool_uuid) {
+            let err_msg = format!("pool {} is set up and was not destroyed", pool_uuid);
+            return Err(StratisError::Engine(ErrorEnum::Busy, err_msg));
+        }
+
+        let destroyed = match self.reclaimable
+            .iter()
+            .filter(|dev| dev.pool_uuid == pool_uuid)
+            .map(|dev| dev.destroyed)
+            .min()
+        {
+            Some(destroyed) => destroyed,
+            None => {
+                let err_msg = format!("no devices found belonging to destroyed pool {}", pool_uuid);
+                return Err(StratisError::Engine(ErrorEnum::NotFound, err_msg));
+            }
+        };
+        if Utc::now() - destroyed > Duration::seconds(DEFAULT_UNDO_DESTROY_WINDOW_SECS as i64) {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("UndoWindowElapsed")
+                    .param("pool", pool_uuid)
+                    .param("destroyed", destroyed.to_rfc3339()),
+            ));
+        }
+
+        let (pool_name, pool) = self.destroyed
+            .remove(&pool_uuid)
+            .expect("a pool's devices are reclaimable only while it is kept");
+        let missing = pool.blockdevs()
+            .into_iter()
+            .map(|(dev_uuid, _)| dev_uuid)
+            .find(|&dev_uuid| {
+                !self.reclaimable
+                    .iter()
+                    .any(|dev| dev.pool_uuid == pool_uuid && dev.dev_uuid == dev_uuid)
+            });
+        if let Some(dev_uuid) = missing {
+            self.destroyed.insert(pool_uuid, (pool_name, pool));
+            return Err(StratisError::Structured(
+                ErrorEnum::NotFound,
+                ErrorPayload::new("DestroyedDeviceMissing")
+                    .param("dev", dev_uuid)
+                    .param("pool", pool_uuid),
+            ));
+        }
+        if self.pools.contains_name(&pool_name) {
+            let err = StratisError::Structured(
+                ErrorEnum::AlreadyExists,
+                ErrorPayload::new("PoolNameExists").param("name", &pool_name),
+            );
+            self.destroyed.insert(pool_uuid, (pool_name, pool));
+            return Err(err);
+        }
+
+        self.reclaimable.retain(|dev| dev.pool_uuid != pool_uuid);
+        record_pool_change(pool_uuid, &pool, ChangeKind::Created);
+        self.pools.insert(pool_name, pool_uuid, pool);
+        Ok(true)
+    }
+
+    fn unlock_pool(&mut self, pool_uuid: PoolUuid, _passphrase: &str) -> StratisResult<bool> {
+        // Simulated pools are never locked, so there is nothing to unlock
+        // unless the pool is already set up.
+        if self.pools.contains_uuid(pool_uuid) {
+            return Ok(false);
+        }
+        let err_msg = format!("no locked devices found belonging to pool {}", pool_uuid);
+        Err(StratisError::Engine(ErrorEnum::NotFound, err_msg))
+    }
+
+    fn bind_tang(
+        &mut self,
+        pool_uuid: PoolUuid,
+        passphrase: &str,
+        url: &str,
+        thumbprint: &str,
+    ) -> StratisResult<bool> {
+        validate_tang(url, thumbprint)?;
+        let devices = self.crypt_devices(pool_uuid)?;
+        let changed = bind_devices_tang(
+            &self.crypt,
+            pool_uuid,
+            &devices,
+            passphrase,
+            url,
+            thumbprint,
+        )?;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn unbind_tang(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool> {
+        let changed = unbind_devices_tang(&self.crypt, &self.crypt_devices(pool_uuid)?)?;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn locked_pools(&self) -> StratisResult<Vec<PoolUuid>> {
+        Ok(Vec::new())
+    }
+
+    fn set_key(&mut self, pool_uuid: PoolUuid, passphrase: &str) -> StratisResult<bool> {
+        if passphrase.is_empty() {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                "the passphrase must not be empty".into(),
+            ));
+        }
+        let old = self.keys.insert(pool_uuid, passphrase.to_owned());
+        Ok(old.as_ref().map(|p| p.as_str()) != Some(passphrase))
+    }
+
+    fn unset_key(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool> {
+        Ok(self.keys.remove(&pool_uuid).is_some())
+    }
+
+    fn keys(&self) -> StratisResult<Vec<PoolUuid>> {
+        Ok(self.keys.keys().cloned().collect())
+    }
+
+    fn key_description(&self, pool_uuid: PoolUuid) -> StratisResult<Option<String>> {
+        // Described as the key is described in the kernel keyring.
+        Ok(self.keys
+            .get(&pool_uuid)
+            .map(|_| format!("stratis-1-key-{}", pool_uuid.simple())))
+    }
+
+    fn bindings(&self, pool_uuid: PoolUuid) -> StratisResult<Option<PoolBindings>> {
+        // A simulated encrypted pool is never locked. Every device of a
+        // pool is bound alike, so the header of its first device is read.
+        let first = match self.crypt_devices(pool_uuid) {
+            Ok(devices) => devices
+                .into_iter()
+                .min()
+                .expect("crypt_devices returns some devices"),
+            Err(_) => return Ok(None),
+        };
+        let (clevis_pins, keyslots_used) = header_bindings(&self.crypt, &first)?;
+        Ok(Some(PoolBindings {
+            unlocked: true,
+            key_description: self.key_description(pool_uuid)?,
+            clevis_pins,
+            keyslots_used,
+            keyslots_total: LUKS2_KEYSLOTS,
+        }))
+    }
+
     fn rename_pool(&mut self, uuid: PoolUuid, new_name: &str) -> StratisResult<RenameAction> {
         rename_pool_pre!(self; uuid; new_name);
 
@@ -96,6 +376,7 @@ impl Engine for SimEngine {
 
         self.pools
             .insert(Name::new(new_name.to_owned()), uuid, pool);
+        record_change(ObjectKind::Pool, uuid, ChangeKind::Modified);
         Ok(RenameAction::Renamed)
     }
 
@@ -113,6 +394,21 @@ impl Engine for SimEngine {
         Ok(())
     }
 
+    fn configure_simulator_capacities(
+        &mut self,
+        device_size: Option<Sectors>,
+        filesystem_size: Option<Sectors>,
+        initial_used: Option<Bytes>,
+        growth: Option<Bytes>,
+    ) -> StratisResult<()> {
+        self.caps.borrow_mut().configure(device_size, filesystem_size, initial_used, growth)
+    }
+
+    fn advance_simulator_clock(&mut self, seconds: u64) -> StratisResult<()> {
+        self.caps.borrow_mut().advance(seconds);
+        Ok(())
+    }
+
     fn pools(&self) -> Vec<(Name, PoolUuid, &Pool)> {
         self.pools
             .iter()
@@ -127,6 +423,58 @@ impl Engine for SimEngine {
             .collect()
     }
 
+    fn metadata_backups(&self, _pool_uuid: PoolUuid) -> StratisResult<Vec<(u64, DateTime<Utc>)>> {
+        Ok(vec![])
+    }
+
+    fn restore_metadata_backup(
+        &mut self,
+        pool_uuid: PoolUuid,
+        generation: u64,
+    ) -> StratisResult<bool> {
+        // The simulator keeps no metadata, so it has no backups of it.
+        if self.pools.contains_uuid(pool_uuid) {
+            let err_msg = format!("pool {} is set up and can not be restored", pool_uuid);
+            return Err(StratisError::Engine(ErrorEnum::Busy, err_msg));
+        }
+        Err(StratisError::Structured(
+            ErrorEnum::NotFound,
+            ErrorPayload::new("BackupNotFound")
+                .param("pool", pool_uuid)
+                .param("generation", generation),
+        ))
+    }
+
+    fn reconstruct_pool(
+        &mut self,
+        pool_uuid: PoolUuid,
+        generation: u64,
+        _blockdev_paths: &[&Path],
+    ) -> StratisResult<PoolUuid> {
+        if self.pools.contains_uuid(pool_uuid) {
+            let err_msg = format!("pool {} is set up and can not be reconstructed", pool_uuid);
+            return Err(StratisError::Engine(ErrorEnum::Busy, err_msg));
+        }
+        Err(StratisError::Structured(
+            ErrorEnum::NotFound,
+            ErrorPayload::new("BackupNotFound")
+                .param("pool", pool_uuid)
+                .param("generation", generation),
+        ))
+    }
+
+    fn capabilities(&self) -> Vec<Capability> {
+        Capability::ALL
+            .iter()
+            .cloned()
+            .filter(|capability| capability.implemented())
+            .collect()
+    }
+
+    fn is_simulator(&self) -> bool {
+        true
+    }
+
     fn get_eventable(&self) -> Option<&'static Eventable> {
         None
     }
@@ -140,16 +488,18 @@ impl Engine for SimEngine {
 mod tests {
 
     use std;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
     use proptest::prelude::any;
-    use uuid::Uuid;
 
     use stratis::{ErrorEnum, StratisError};
 
     use super::SimEngine;
 
+    use engine::BlockDevTier;
     use engine::Engine;
+    use engine::FilesystemFilter;
+    use engine::PoolUuid;
     use engine::RenameAction;
 
     proptest! {
@@ -166,21 +516,21 @@ mod tests {
     #[test]
     /// When an engine has no pools, any name lookup should fail
     fn get_pool_err() {
-        assert!(SimEngine::default().get_pool(Uuid::new_v4()).is_none());
+        assert!(SimEngine::default().get_pool(PoolUuid::new_v4()).is_none());
     }
 
     #[test]
     /// When an engine has no pools, destroying any pool must succeed
     fn destroy_pool_empty() {
-        assert!(SimEngine::default().destroy_pool(Uuid::new_v4()).is_ok());
+        assert!(SimEngine::default().destroy_pool(PoolUuid::new_v4(), false).is_ok());
     }
 
     #[test]
     /// Destroying an empty pool should succeed.
     fn destroy_empty_pool() {
         let mut engine = SimEngine::default();
-        let uuid = engine.create_pool("name", &[], None).unwrap();
-        assert!(engine.destroy_pool(uuid).is_ok());
+        let uuid = engine.create_pool("name", &[], None, None, None, None).unwrap();
+        assert!(engine.destroy_pool(uuid, false).is_ok());
     }
 
     #[test]
@@ -188,9 +538,9 @@ mod tests {
     fn destroy_pool_w_devices() {
         let mut engine = SimEngine::default();
         let uuid = engine
-            .create_pool("name", &[Path::new("/s/d")], None)
+            .create_pool("name", &[Path::new("/s/d")], None, None, None, None)
             .unwrap();
-        assert!(engine.destroy_pool(uuid).is_ok());
+        assert!(engine.destroy_pool(uuid, false).is_ok());
     }
 
     #[test]
@@ -199,14 +549,95 @@ mod tests {
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
         let uuid = engine
-            .create_pool(pool_name, &[Path::new("/s/d")], None)
+            .create_pool(pool_name, &[Path::new("/s/d")], None, None, None, None)
             .unwrap();
         {
             let pool = engine.get_mut_pool(uuid).unwrap().1;
-            pool.create_filesystems(uuid, pool_name, &[("test", None)])
+            pool.create_filesystems(uuid, pool_name, &[("test", None, false)], None)
                 .unwrap();
         }
-        assert!(engine.destroy_pool(uuid).is_err());
+        assert!(engine.destroy_pool(uuid, false).is_err());
+    }
+
+    #[test]
+    /// Destroying a pool keeping its signatures should leave its devices
+    /// reclaimable until they are wiped.
+    fn destroy_pool_keep_signatures() {
+        let mut engine = SimEngine::default();
+        let uuid = engine
+            .create_pool("name", &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        assert!(engine.destroy_pool(uuid, true).unwrap());
+
+        let reclaimable = engine.reclaimable_devices().unwrap();
+        assert_eq!(
+            reclaimable
+                .iter()
+                .map(|dev| (dev.devnode.as_path(), dev.pool_uuid))
+                .collect::<Vec<_>>(),
+            vec![(Path::new("/s/d"), uuid)]
+        );
+
+        assert!(!engine.wipe_reclaimable_device(Path::new("/s/e")).unwrap());
+        assert!(engine.wipe_reclaimable_device(Path::new("/s/d")).unwrap());
+        assert!(engine.reclaimable_devices().unwrap().is_empty());
+    }
+
+    #[test]
+    /// Undoing the destruction of a pool should set it up again, unless
+    /// one of its devices was wiped in the meantime.
+    fn undo_destroy_pool() {
+        let mut engine = SimEngine::default();
+        let uuid = engine
+            .create_pool("name", &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        assert!(engine.undo_destroy_pool(uuid).is_err());
+
+        engine.destroy_pool(uuid, true).unwrap();
+        assert!(engine.undo_destroy_pool(uuid).unwrap());
+        assert!(engine.get_pool(uuid).is_some());
+        assert!(engine.reclaimable_devices().unwrap().is_empty());
+
+        engine.destroy_pool(uuid, true).unwrap();
+        engine.wipe_reclaimable_device(Path::new("/s/d")).unwrap();
+        assert!(engine.undo_destroy_pool(uuid).is_err());
+        assert!(engine.get_pool(uuid).is_none());
+    }
+
+    #[test]
+    /// Planning the destruction of a pool should find its filesystems,
+    /// blockdevs, and devlinks, and should not destroy anything.
+    fn plan_destroy_pool() {
+        let mut engine = SimEngine::default();
+        assert_eq!(engine.plan_destroy_pool(PoolUuid::new_v4()).unwrap(), None);
+
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(pool_name, &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        {
+            let pool = engine.get_mut_pool(uuid).unwrap().1;
+            pool.create_filesystems(uuid, pool_name, &[("test", None, false)], None)
+                .unwrap();
+        }
+
+        let plan = engine.plan_destroy_pool(uuid).unwrap().unwrap();
+        assert_eq!(
+            plan.filesystems
+                .iter()
+                .map(|fs| (fs.name.as_str(), fs.mount_points.len()))
+                .collect::<Vec<_>>(),
+            vec![("test", 0)]
+        );
+        assert_eq!(plan.blockdevs, vec![PathBuf::from("/s/d")]);
+        assert_eq!(
+            plan.devlinks,
+            vec![
+                PathBuf::from("/stratis/pool_name/test"),
+                PathBuf::from("/stratis/pool_name"),
+            ]
+        );
+        assert!(engine.get_pool(uuid).is_some());
     }
 
     #[test]
@@ -215,8 +646,8 @@ mod tests {
     fn create_new_pool_twice() {
         let name = "name";
         let mut engine = SimEngine::default();
-        engine.create_pool(name, &[], None).unwrap();
-        assert!(match engine.create_pool(name, &[], None) {
+        engine.create_pool(name, &[], None, None, None, None).unwrap();
+        assert!(match engine.create_pool(name, &[], None, None, None, None) {
             Ok(uuid) => engine.get_pool(uuid).unwrap().1.blockdevs().is_empty(),
             Err(_) => false,
         });
@@ -228,10 +659,10 @@ mod tests {
         let name = "name";
         let mut engine = SimEngine::default();
         engine
-            .create_pool(name, &[Path::new("/s/d")], None)
+            .create_pool(name, &[Path::new("/s/d")], None, None, None, None)
             .unwrap();
-        assert!(match engine.create_pool(name, &[], None) {
-            Err(StratisError::Engine(ErrorEnum::AlreadyExists, _)) => true,
+        assert!(match engine.create_pool(name, &[], None, None, None, None) {
+            Err(ref err) => err.code() == ErrorEnum::AlreadyExists,
             _ => false,
         });
     }
@@ -242,28 +673,189 @@ mod tests {
         let path = "/s/d";
         let mut engine = SimEngine::default();
         let devices = vec![Path::new(path), Path::new(path)];
-        assert!(match engine.create_pool("name", &devices, None) {
+        assert!(match engine.create_pool("name", &devices, None, None, None, None) {
             Ok(uuid) => engine.get_pool(uuid).unwrap().1.blockdevs().len() == 1,
             _ => false,
         });
     }
 
+    #[test]
+    /// Creating a pool with filesystems should create them all, in order,
+    /// and if any can not be created, should leave no pool behind, so that
+    /// the setup can be tried again.
+    fn create_pool_with_filesystems() {
+        let name = "name";
+        let devices = [Path::new("/s/d")];
+        let mut engine = SimEngine::default();
+
+        let specs = [("root", None, false), ("root", None, false)];
+        assert!(match engine.create_pool_with_filesystems(
+            name, &devices, None, None, None, &specs, None
+        ) {
+            Err(ref err) => err.code() == ErrorEnum::AlreadyExists,
+            _ => false,
+        });
+        assert!(engine.pools().is_empty());
+
+        let specs = [("root", None, false), ("home", None, true)];
+        let (uuid, fs_uuids) = engine
+            .create_pool_with_filesystems(name, &devices, None, None, None, &specs, None)
+            .unwrap();
+        let (pool_name, pool) = engine.get_pool(uuid).unwrap();
+        assert_eq!(&*pool_name, name);
+        assert_eq!(
+            fs_uuids
+                .iter()
+                .map(|&fs_uuid| pool.get_filesystem(fs_uuid).unwrap().0.to_string())
+                .collect::<Vec<_>>(),
+            vec!["root", "home"]
+        );
+    }
+
     #[test]
     /// Creating a pool with an impossible raid level should fail
     fn create_pool_max_u16_raid() {
         let mut engine = SimEngine::default();
         assert!(
             engine
-                .create_pool("name", &[], Some(std::u16::MAX))
+                .create_pool("name", &[], Some(std::u16::MAX), None, None, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    /// Creating a pool with an unknown size policy should fail
+    fn create_pool_max_u16_size_policy() {
+        let mut engine = SimEngine::default();
+        assert!(
+            engine
+                .create_pool("name", &[], None, Some(std::u16::MAX), None, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    /// Creating a pool with an unknown signature policy should fail
+    fn create_pool_max_u16_signature_policy() {
+        let mut engine = SimEngine::default();
+        assert!(
+            engine
+                .create_pool("name", &[], None, None, Some(std::u16::MAX), None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    /// Creating an encrypted pool should make a pool to which no devices
+    /// can be added, and an empty passphrase should be refused.
+    fn create_encrypted_pool() {
+        let mut engine = SimEngine::default();
+        let devices = [Path::new("/s/d")];
+        assert!(
+            engine
+                .create_encrypted_pool("name", &devices, None, None, None, "", None)
+                .is_err()
+        );
+
+        let uuid = engine
+            .create_encrypted_pool("name", &devices, None, None, None, "secret", None)
+            .unwrap();
+        let (pool_name, pool) = engine.get_mut_pool(uuid).unwrap();
+        assert!(pool.encrypted());
+        assert!(
+            pool.add_blockdevs(uuid, &pool_name, &[Path::new("/s/e")], BlockDevTier::Data, None)
                 .is_err()
         );
+
+        assert!(!engine.unlock_pool(uuid, "secret").unwrap());
+        assert!(engine.locked_pools().unwrap().is_empty());
+    }
+
+    #[test]
+    /// A key is changed only by a different passphrase, and is listed until
+    /// it is unset.
+    fn set_and_unset_key() {
+        let mut engine = SimEngine::default();
+        let uuid = PoolUuid::new_v4();
+        assert!(engine.set_key(uuid, "").is_err());
+        assert!(engine.set_key(uuid, "secret").unwrap());
+        assert!(!engine.set_key(uuid, "secret").unwrap());
+        assert!(engine.set_key(uuid, "other").unwrap());
+        assert_eq!(engine.keys().unwrap(), vec![uuid]);
+
+        assert!(engine.unset_key(uuid).unwrap());
+        assert!(!engine.unset_key(uuid).unwrap());
+        assert!(engine.keys().unwrap().is_empty());
+    }
+
+    #[test]
+    /// Only an encrypted pool has bindings, which show whether a key is
+    /// kept for it.
+    fn bindings() {
+        let mut engine = SimEngine::default();
+        let plain = engine
+            .create_pool("plain", &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        assert_eq!(engine.bindings(plain).unwrap(), None);
+        assert_eq!(engine.bindings(PoolUuid::new_v4()).unwrap(), None);
+
+        let uuid = engine
+            .create_encrypted_pool("name", &[Path::new("/s/e")], None, None, None, "secret", None)
+            .unwrap();
+        let bindings = engine.bindings(uuid).unwrap().unwrap();
+        assert!(bindings.unlocked);
+        assert_eq!(bindings.key_description, None);
+        assert!(bindings.clevis_pins.is_empty());
+        assert_eq!((bindings.keyslots_used, bindings.keyslots_total), (1, 32));
+
+        engine.set_key(uuid, "secret").unwrap();
+        assert_eq!(
+            engine.bindings(uuid).unwrap().unwrap().key_description,
+            engine.key_description(uuid).unwrap()
+        );
+        assert!(engine.key_description(uuid).unwrap().is_some());
+    }
+
+    #[test]
+    /// Only an encrypted pool may be bound to a Tang server, given its URL,
+    /// its thumbprint, and a passphrase that opens the pool, and it is
+    /// bound just once.
+    fn bind_tang() {
+        let mut engine = SimEngine::default();
+        let plain = engine
+            .create_pool("plain", &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        assert!(engine.bind_tang(plain, "secret", "http://tang", "thp").is_err());
+        assert!(engine.unbind_tang(plain).is_err());
+
+        let devices = [Path::new("/s/e"), Path::new("/s/f")];
+        let uuid = engine
+            .create_encrypted_pool("name", &devices, None, None, None, "secret", None)
+            .unwrap();
+        assert!(engine.bind_tang(uuid, "secret", "", "thp").is_err());
+        assert!(engine.bind_tang(uuid, "secret", "http://tang", "").is_err());
+        assert!(engine.bind_tang(uuid, "", "http://tang", "thp").is_err());
+        assert!(engine.bind_tang(uuid, "wrong", "http://tang", "thp").is_err());
+        assert!(engine.bindings(uuid).unwrap().unwrap().clevis_pins.is_empty());
+        assert!(engine.bind_tang(uuid, "secret", "http://tang", "thp").unwrap());
+        assert!(!engine.bind_tang(uuid, "secret", "http://tang", "thp").unwrap());
+
+        let bindings = engine.bindings(uuid).unwrap().unwrap();
+        assert_eq!(bindings.clevis_pins, vec!["tang".to_owned()]);
+        assert_eq!(bindings.keyslots_used, 2);
+
+        assert!(engine.unbind_tang(uuid).unwrap());
+        assert!(!engine.unbind_tang(uuid).unwrap());
+        let bindings = engine.bindings(uuid).unwrap().unwrap();
+        assert!(bindings.clevis_pins.is_empty());
+        assert_eq!(bindings.keyslots_used, 1);
     }
 
     #[test]
     /// Renaming a pool on an empty engine always works
     fn rename_empty() {
         let mut engine = SimEngine::default();
-        assert!(match engine.rename_pool(Uuid::new_v4(), "new_name") {
+        assert!(match engine.rename_pool(PoolUuid::new_v4(), "new_name") {
             Ok(RenameAction::NoSource) => true,
             _ => false,
         });
@@ -274,7 +866,7 @@ mod tests {
     fn rename_identity() {
         let name = "name";
         let mut engine = SimEngine::default();
-        let uuid = engine.create_pool(name, &[], None).unwrap();
+        let uuid = engine.create_pool(name, &[], None, None, None, None).unwrap();
         assert!(match engine.rename_pool(uuid, name) {
             Ok(RenameAction::Identity) => true,
             _ => false,
@@ -285,7 +877,7 @@ mod tests {
     /// Renaming a pool to another pool should work if new name not taken
     fn rename_happens() {
         let mut engine = SimEngine::default();
-        let uuid = engine.create_pool("old_name", &[], None).unwrap();
+        let uuid = engine.create_pool("old_name", &[], None, None, None, None).unwrap();
         assert!(match engine.rename_pool(uuid, "new_name") {
             Ok(RenameAction::Renamed) => true,
             _ => false,
@@ -297,10 +889,10 @@ mod tests {
     fn rename_fails() {
         let new_name = "new_name";
         let mut engine = SimEngine::default();
-        let uuid = engine.create_pool("old_name", &[], None).unwrap();
-        engine.create_pool(new_name, &[], None).unwrap();
+        let uuid = engine.create_pool("old_name", &[], None, None, None, None).unwrap();
+        engine.create_pool(new_name, &[], None, None, None, None).unwrap();
         assert!(match engine.rename_pool(uuid, new_name) {
-            Err(StratisError::Engine(ErrorEnum::AlreadyExists, _)) => true,
+            Err(ref err) => err.code() == ErrorEnum::AlreadyExists,
             _ => false,
         });
     }
@@ -310,11 +902,53 @@ mod tests {
     fn rename_no_op() {
         let new_name = "new_name";
         let mut engine = SimEngine::default();
-        engine.create_pool(new_name, &[], None).unwrap();
-        assert!(match engine.rename_pool(Uuid::new_v4(), new_name) {
+        engine.create_pool(new_name, &[], None, None, None, None).unwrap();
+        assert!(match engine.rename_pool(PoolUuid::new_v4(), new_name) {
             Ok(RenameAction::NoSource) => true,
             _ => false,
         });
     }
 
+    #[test]
+    /// Paging through the filesystems of all pools should return each
+    /// filesystem exactly once, in order of pool UUID and filesystem UUID.
+    fn list_filesystems_paged() {
+        let mut engine = SimEngine::default();
+        for pool_name in &["first", "second"] {
+            let devnode = format!("/s/{}", pool_name);
+            let uuid = engine
+                .create_pool(pool_name, &[Path::new(&devnode)], None, None, None, None)
+                .unwrap();
+            let pool = engine.get_mut_pool(uuid).unwrap().1;
+            let specs = (0..5).map(|i| format!("fs{}", i)).collect::<Vec<_>>();
+            let specs = specs
+                .iter()
+                .map(|name| (name.as_str(), None, false))
+                .collect::<Vec<_>>();
+            pool.create_filesystems(uuid, pool_name, &specs, None)
+                .unwrap();
+        }
+
+        let filter = FilesystemFilter::default();
+        let mut listed = Vec::new();
+        let mut after = None;
+        loop {
+            let (page, more) = engine.list_filesystems_page(&filter, after, 3);
+            assert!(page.len() <= 3);
+            listed.extend(page.iter().map(|&(_, pool_uuid, _, uuid, _)| (pool_uuid, uuid)));
+            if !more {
+                break;
+            }
+            after = listed.last().cloned();
+        }
+
+        let mut all = engine
+            .list_filesystems(&filter)
+            .iter()
+            .map(|&(_, pool_uuid, _, uuid, _)| (pool_uuid, uuid))
+            .collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all.len(), 10);
+        assert_eq!(listed, all);
+    }
 }
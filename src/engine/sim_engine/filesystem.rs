@@ -6,30 +6,97 @@ use chrono::{DateTime, Utc};
 
 use rand;
 
+use std::cell::RefCell;
 use std::path::PathBuf;
+use std::rc::Rc;
 
-use devicemapper::Bytes;
+use devicemapper::{Bytes, Sectors};
 
 use super::super::engine::Filesystem;
-use super::super::types::MaybeDbusPath;
+use super::super::types::{
+    validate_fs_size_limit, Creator, DmDeviceInfo, FilesystemUuid, MaybeDbusPath,
+};
 
 use stratis::StratisResult;
 
+use super::capacity::Capacities;
+
 #[derive(Debug)]
 pub struct SimFilesystem {
     rand: u32,
+    caps: Rc<RefCell<Capacities>>,
     created: DateTime<Utc>,
+    // The simulated time at which the space the filesystem uses began to
+    // grow
+    born: u64,
+    size: Sectors,
+    guaranteed: bool,
+    created_by: Option<Creator>,
+    read_ahead: Option<Sectors>,
+    size_limit: Option<Sectors>,
+    origin: Option<FilesystemUuid>,
+    protected: bool,
     dbus_path: MaybeDbusPath,
 }
 
 impl SimFilesystem {
-    pub fn new() -> SimFilesystem {
+    pub fn new(
+        caps: &Rc<RefCell<Capacities>>,
+        size: Sectors,
+        guaranteed: bool,
+        created_by: Option<Creator>,
+    ) -> SimFilesystem {
         SimFilesystem {
             rand: rand::random::<u32>(),
+            caps: Rc::clone(caps),
             created: Utc::now(),
+            born: caps.borrow().now(),
+            size,
+            guaranteed,
+            created_by,
+            read_ahead: None,
+            size_limit: None,
+            origin: None,
+            protected: false,
             dbus_path: MaybeDbusPath(None),
         }
     }
+
+    /// The virtual size of the filesystem.
+    pub fn size(&self) -> Sectors {
+        self.size
+    }
+
+    /// Set the read-ahead of the filesystem.
+    pub fn set_read_ahead(&mut self, read_ahead: Option<Sectors>) {
+        self.read_ahead = read_ahead;
+    }
+
+    /// Protect the filesystem from being destroyed, or unprotect it.
+    pub fn set_protected(&mut self, protected: bool) {
+        self.protected = protected;
+    }
+
+    /// Set the size limit of the filesystem, extending it to the limit.
+    pub fn set_size_limit(&mut self, limit: Option<Sectors>) -> StratisResult<()> {
+        if let Some(limit) = limit {
+            validate_fs_size_limit(self.size, limit)?;
+            self.size = limit;
+        }
+        self.size_limit = limit;
+        Ok(())
+    }
+
+    /// A snapshot of this filesystem, whose UUID is origin_uuid. The
+    /// snapshot starts out using the same space as its origin.
+    pub fn new_snapshot(&self, origin_uuid: FilesystemUuid) -> SimFilesystem {
+        SimFilesystem {
+            born: self.born,
+            size_limit: self.size_limit,
+            origin: Some(origin_uuid),
+            ..SimFilesystem::new(&self.caps, self.size, false, None)
+        }
+    }
 }
 
 impl Filesystem for SimFilesystem {
@@ -44,7 +111,40 @@ impl Filesystem for SimFilesystem {
     }
 
     fn used(&self) -> StratisResult<Bytes> {
-        Ok(Bytes(12_345_678))
+        Ok(self.caps.borrow().used(self.size, self.born))
+    }
+
+    fn guaranteed(&self) -> bool {
+        self.guaranteed
+    }
+
+    fn created_by(&self) -> Option<&Creator> {
+        self.created_by.as_ref()
+    }
+
+    fn read_ahead(&self) -> Option<Sectors> {
+        self.read_ahead
+    }
+
+    fn size_limit(&self) -> Option<Sectors> {
+        self.size_limit
+    }
+
+    fn origin(&self) -> Option<FilesystemUuid> {
+        self.origin
+    }
+
+    fn protected(&self) -> bool {
+        self.protected
+    }
+
+    fn dm_device(&self) -> Option<DmDeviceInfo> {
+        None
+    }
+
+    fn mount_points(&self) -> StratisResult<Vec<PathBuf>> {
+        // Simulated filesystems are never mounted.
+        Ok(Vec::new())
     }
 
     fn set_dbus_path(&mut self, path: MaybeDbusPath) -> () {
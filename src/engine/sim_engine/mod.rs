@@ -1,6 +1,10 @@
+#[cfg(test)]
+pub use self::crypt::SimCrypt;
 pub use self::engine::SimEngine;
 
 mod blockdev;
+mod capacity;
+mod crypt;
 mod engine;
 mod filesystem;
 mod pool;
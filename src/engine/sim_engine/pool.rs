@@ -3,6 +3,7 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::cell::RefCell;
+use std::cmp::min;
 use std::collections::hash_map::RandomState;
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
@@ -10,60 +11,146 @@ use std::path::Path;
 use std::rc::Rc;
 use std::vec::Vec;
 
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-use devicemapper::{Sectors, IEC};
+use devicemapper::{Device, Sectors, IEC};
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
 
+use super::super::changes::{record_change, ChangeKind, ObjectKind};
 use super::super::engine::{BlockDev, Filesystem, Pool};
+use super::super::schedule::{scheduled_snapshot_name, SnapshotSchedules};
 use super::super::structures::Table;
 use super::super::types::{
-    BlockDevTier, DevUuid, FilesystemUuid, FreeSpaceState, MaybeDbusPath, Name, PoolExtendState,
-    PoolState, PoolUuid, Redundancy, RenameAction,
+    AddDataDevsPlan, AllocationStrategy, BlockDevTier, CacheType, CacheUsage, CreationParams,
+    Creator, DevUuid, DmDeviceInfo, FilesystemUuid, FormatVersions, FreeSpaceState, IoClass,
+    MaybeDbusPath, Name, PlannedBlockDev, PoolExtendState, PoolState, PoolUuid, Redundancy,
+    RenameAction, SignaturePolicy, SnapshotInterval, SnapshotSchedule, Watermarks,
+    WorkerPriority, MAX_FORMAT_VERSIONS,
+};
+use super::super::types::{
+    check_fs_limit, check_overcommit, destroy_order, overcommit_percent, reclaim_order,
+    refuse_if_protected, validate_fs_limit, validate_reclaim_target, validate_snapshot_retention,
+    validate_usage_thresholds, validate_watermarks, validate_worker_priority, DEFAULT_FS_LIMIT,
+    DEFAULT_USAGE_THRESHOLDS,
 };
 
 use super::blockdev::SimDev;
+use super::capacity::Capacities;
 use super::filesystem::SimFilesystem;
 use super::randomization::Randomizer;
 
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The MDA size and the chunk size recorded among the creation parameters
+/// of every simulated pool, those with which the real engine creates a pool.
+const SIM_MDA_SIZE: Sectors = Sectors(2032);
+const SIM_CHUNK_SIZE: Sectors = Sectors(2 * IEC::Ki);
+
 #[derive(Debug)]
 pub struct SimPool {
     block_devs: HashMap<DevUuid, SimDev>,
     cache_devs: HashMap<DevUuid, SimDev>,
-    filesystems: Table<SimFilesystem>,
+    cache_type: Option<CacheType>,
+    cache_volatile: bool,
+    filesystems: Table<FilesystemUuid, SimFilesystem>,
     redundancy: Redundancy,
     rdm: Rc<RefCell<Randomizer>>,
+    caps: Rc<RefCell<Capacities>>,
     pool_state: PoolState,
     pool_extend_state: PoolExtendState,
     free_space_state: FreeSpaceState,
+    verify_writes: bool,
+    allocation_strategy: AllocationStrategy,
+    worker_priority: WorkerPriority,
+    created_by: Option<Creator>,
+    encrypted: bool,
+    creation_params: CreationParams,
+    overcommit_cap: Option<u64>,
+    usage_thresholds: Vec<u8>,
+    watermarks: Watermarks,
+    default_fs_size: Option<Sectors>,
+    fs_limit: u64,
+    snapshot_schedules: SnapshotSchedules,
+    add_datadevs_plan: Option<(Uuid, AddDataDevsPlan)>,
     dbus_path: MaybeDbusPath,
 }
 
 impl SimPool {
     pub fn new(
         rdm: &Rc<RefCell<Randomizer>>,
+        caps: &Rc<RefCell<Capacities>>,
         paths: &[&Path],
         redundancy: Redundancy,
+        created_by: Option<Creator>,
     ) -> (PoolUuid, SimPool) {
         let devices: HashSet<_, RandomState> = HashSet::from_iter(paths);
-        let device_pairs = devices.iter().map(|p| SimDev::new(Rc::clone(rdm), p));
+        let size = caps.borrow().device_size();
+        let device_pairs = devices.iter().map(|p| SimDev::new(Rc::clone(rdm), p, size));
         (
-            Uuid::new_v4(),
+            PoolUuid::new_v4(),
             SimPool {
                 block_devs: HashMap::from_iter(device_pairs),
                 cache_devs: HashMap::new(),
+                cache_type: None,
+                cache_volatile: false,
                 filesystems: Table::default(),
                 redundancy,
                 rdm: Rc::clone(rdm),
+                caps: Rc::clone(caps),
                 pool_state: PoolState::Initializing,
                 pool_extend_state: PoolExtendState::Good,
                 free_space_state: FreeSpaceState::Good,
+                verify_writes: false,
+                allocation_strategy: AllocationStrategy::FillFirst,
+                worker_priority: WorkerPriority::default(),
+                created_by,
+                encrypted: false,
+                creation_params: CreationParams::new(
+                    SIM_MDA_SIZE,
+                    SIM_CHUNK_SIZE,
+                    redundancy,
+                    false,
+                ),
+                overcommit_cap: None,
+                usage_thresholds: DEFAULT_USAGE_THRESHOLDS.to_vec(),
+                watermarks: Watermarks::default(),
+                default_fs_size: None,
+                fs_limit: DEFAULT_FS_LIMIT,
+                snapshot_schedules: SnapshotSchedules::default(),
+                add_datadevs_plan: None,
                 dbus_path: MaybeDbusPath(None),
             },
         )
     }
 
+    /// Make the pool one whose devices are encrypted, as it would be if it
+    /// had been created so.
+    pub fn set_encrypted(&mut self) {
+        self.encrypted = true;
+        self.creation_params.encrypted = true;
+    }
+
+    /// Forget the snapshot schedules of filesystems that have been
+    /// destroyed, and the destroyed snapshots that schedules took.
+    fn forget_destroyed_schedules(&mut self) {
+        let filesystems = &self.filesystems;
+        self.snapshot_schedules
+            .forget_missing(|uuid| filesystems.get_by_uuid(uuid).is_some());
+    }
+
+    /// Return an error if the pool is encrypted, as the real engine does.
+    fn refuse_if_encrypted(&self, pool_name: &str) -> StratisResult<()> {
+        if self.encrypted {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("PoolEncrypted").param("name", pool_name),
+            ));
+        }
+        Ok(())
+    }
+
     pub fn has_filesystems(&self) -> bool {
         !self.filesystems.is_empty()
     }
@@ -79,6 +166,21 @@ impl SimPool {
                     .and_then(|bd| Some((BlockDevTier::Cache, bd)))
             })
     }
+
+    /// The sum of the sizes of all the filesystems.
+    fn virtual_size(&self) -> Sectors {
+        self.filesystems.iter().map(|(_, _, fs)| fs.size()).sum()
+    }
+
+    /// The percentage of the pool's physical space that is in use, 0 if
+    /// the pool has no space.
+    fn used_percent(&self) -> StratisResult<u8> {
+        let total = self.total_physical_size();
+        if total == Sectors(0) {
+            return Ok(0);
+        }
+        Ok((*self.total_physical_used()? * 100 / *total) as u8)
+    }
 }
 
 impl Pool for SimPool {
@@ -86,24 +188,45 @@ impl Pool for SimPool {
         &'a mut self,
         _pool_uuid: PoolUuid,
         _pool_name: &str,
-        specs: &[(&'b str, Option<Sectors>)],
+        specs: &[(&'b str, Option<Sectors>, bool)],
+        created_by: Option<Creator>,
     ) -> StratisResult<Vec<(&'b str, FilesystemUuid)>> {
-        let names: HashMap<_, _> = HashMap::from_iter(specs.iter().map(|&tup| (tup.0, tup.1)));
+        let names: HashMap<_, _> =
+            HashMap::from_iter(specs.iter().map(|&tup| (tup.0, (tup.1, tup.2))));
         for name in names.keys() {
             if self.filesystems.contains_name(name) {
-                return Err(StratisError::Engine(
+                return Err(StratisError::Structured(
                     ErrorEnum::AlreadyExists,
-                    name.to_string(),
+                    ErrorPayload::new("FilesystemNameExists").param("name", name),
                 ));
             }
         }
+        check_fs_limit(self.filesystems.len(), names.len(), self.fs_limit)?;
+
+        let default_size = self.default_filesystem_size();
+        let additional = names
+            .values()
+            .map(|&(size, _)| size.unwrap_or(default_size))
+            .sum();
+        check_overcommit(
+            self.virtual_size(),
+            additional,
+            self.total_physical_size(),
+            self.overcommit_cap,
+        )?;
 
         let mut result = Vec::new();
-        for name in names.keys() {
-            let uuid = Uuid::new_v4();
-            let new_filesystem = SimFilesystem::new();
+        for (name, &(size, guaranteed)) in &names {
+            let uuid = FilesystemUuid::new_v4();
+            let new_filesystem = SimFilesystem::new(
+                &self.caps,
+                size.unwrap_or(default_size),
+                guaranteed,
+                created_by.clone(),
+            );
             self.filesystems
                 .insert(Name::new((&**name).to_owned()), uuid, new_filesystem);
+            record_change(ObjectKind::Filesystem, uuid, ChangeKind::Created);
             result.push((*name, uuid));
         }
 
@@ -112,28 +235,285 @@ impl Pool for SimPool {
 
     fn add_blockdevs(
         &mut self,
-        _pool_uuid: PoolUuid,
-        _pool_name: &str,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
         paths: &[&Path],
         tier: BlockDevTier,
+        signature_policy: Option<u16>,
     ) -> StratisResult<Vec<DevUuid>> {
+        if tier == BlockDevTier::Cache {
+            let cache_type = self.cache_type.map(u16::from);
+            let volatile = self.cache_volatile;
+            return self.add_cachedevs(
+                pool_uuid,
+                pool_name,
+                paths,
+                signature_policy,
+                cache_type,
+                volatile,
+            );
+        }
+
+        self.refuse_if_encrypted(pool_name)?;
+        // Simulated devices have no signatures, so the signature policy is
+        // only checked for validity.
+        let _ = calculate_signature_policy!(signature_policy);
+
         let devices: HashSet<_, RandomState> = HashSet::from_iter(paths);
+        let size = self.caps.borrow().device_size();
         let device_pairs: Vec<_> = devices
             .iter()
-            .map(|p| SimDev::new(Rc::clone(&self.rdm), p))
+            .map(|p| SimDev::new(Rc::clone(&self.rdm), p, size))
             .collect();
-        let ret_uuids = device_pairs.iter().map(|&(uuid, _)| uuid).collect();
+        let ret_uuids = device_pairs
+            .iter()
+            .map(|&(uuid, _)| uuid)
+            .collect::<Vec<_>>();
+        for &uuid in &ret_uuids {
+            record_change(ObjectKind::BlockDev, uuid, ChangeKind::Created);
+        }
+
+        self.block_devs.extend(device_pairs);
+        Ok(ret_uuids)
+    }
+
+    fn remove_blockdevs(
+        &mut self,
+        _pool_uuid: PoolUuid,
+        _pool_name: &str,
+        uuids: &[DevUuid],
+    ) -> StratisResult<()> {
+        if self.redundancy != Redundancy::NONE {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("RaidDataDevsUnsupported").param("redundancy", self.redundancy),
+            ));
+        }
+        for &uuid in uuids {
+            if self.cache_devs.contains_key(&uuid) {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("BlockDevNotData").param("uuid", uuid.simple()),
+                ));
+            }
+            if !self.block_devs.contains_key(&uuid) {
+                return Err(StratisError::Engine(
+                    ErrorEnum::NotFound,
+                    format!("no blockdev with UUID {} found", uuid.simple()),
+                ));
+            }
+        }
+        if self.block_devs.keys().all(|uuid| uuids.contains(uuid)) {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("RemoveAllDataDevs"),
+            ));
+        }
+
+        // Simulated devices hold no data, so there is nothing to move.
+        for uuid in uuids {
+            if self.block_devs.remove(uuid).is_some() {
+                record_change(ObjectKind::BlockDev, *uuid, ChangeKind::Deleted);
+            }
+        }
+        Ok(())
+    }
+
+    fn replace_blockdev(
+        &mut self,
+        _pool_uuid: PoolUuid,
+        pool_name: &str,
+        old: DevUuid,
+        new: &Path,
+        signature_policy: Option<u16>,
+    ) -> StratisResult<DevUuid> {
+        self.refuse_if_encrypted(pool_name)?;
+        let _ = calculate_signature_policy!(signature_policy);
+        if self.cache_devs.contains_key(&old) {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("BlockDevNotData").param("uuid", old.simple()),
+            ));
+        }
+        if !self.block_devs.contains_key(&old) {
+            return Err(StratisError::Engine(
+                ErrorEnum::NotFound,
+                format!("no blockdev with UUID {} found", old.simple()),
+            ));
+        }
 
-        let the_vec = match tier {
-            BlockDevTier::Cache => &mut self.cache_devs,
-            BlockDevTier::Data => &mut self.block_devs,
+        let existing = self.block_devs
+            .iter()
+            .find(|&(_, bd)| bd.devnode() == new)
+            .map(|(&uuid, _)| uuid);
+        let new_uuid = match existing {
+            Some(uuid) if uuid == old => {
+                return Err(StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!("blockdev {} can not be replaced by itself", old.simple()),
+                ))
+            }
+            Some(uuid) => uuid,
+            None => {
+                let size = self.caps.borrow().device_size();
+                let (uuid, bd) = SimDev::new(Rc::clone(&self.rdm), new, size);
+                self.block_devs.insert(uuid, bd);
+                record_change(ObjectKind::BlockDev, uuid, ChangeKind::Created);
+                uuid
+            }
         };
 
-        the_vec.extend(device_pairs);
+        // Simulated devices hold no data, so there is nothing to move.
+        self.block_devs.remove(&old);
+        record_change(ObjectKind::BlockDev, old, ChangeKind::Deleted);
+        Ok(new_uuid)
+    }
+
+    fn add_cachedevs(
+        &mut self,
+        _pool_uuid: PoolUuid,
+        pool_name: &str,
+        paths: &[&Path],
+        signature_policy: Option<u16>,
+        cache_type: Option<u16>,
+        volatile: bool,
+    ) -> StratisResult<Vec<DevUuid>> {
+        self.refuse_if_encrypted(pool_name)?;
+        let _ = calculate_signature_policy!(signature_policy);
+        let cache_type = calculate_cache_type!(cache_type);
+
+        if volatile && cache_type != CacheType::Cache {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                "A volatile cache can only be made with the dm-cache target".into(),
+            ));
+        }
+
+        match self.cache_type {
+            Some(existing) if existing != cache_type => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("CacheTypeMismatch")
+                        .param("name", pool_name)
+                        .param("existing", existing)
+                        .param("requested", cache_type),
+                ));
+            }
+            Some(_) if self.cache_volatile != volatile => {
+                return Err(StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!(
+                        "The cache of pool {} is {}volatile",
+                        pool_name,
+                        if volatile { "not " } else { "" }
+                    ),
+                ));
+            }
+            Some(CacheType::WriteCache) => {
+                return Err(StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    "The cache device of a writecache can not be extended".into(),
+                ));
+            }
+            _ => {}
+        }
+
+        let devices: HashSet<_, RandomState> = HashSet::from_iter(paths);
+        let size = self.caps.borrow().device_size();
+        let device_pairs: Vec<_> = devices
+            .iter()
+            .map(|p| SimDev::new(Rc::clone(&self.rdm), p, size))
+            .collect();
+        let ret_uuids = device_pairs
+            .iter()
+            .map(|&(uuid, _)| uuid)
+            .collect::<Vec<_>>();
+        for &uuid in &ret_uuids {
+            record_change(ObjectKind::BlockDev, uuid, ChangeKind::Created);
+        }
+
+        if !ret_uuids.is_empty() {
+            self.cache_type = Some(cache_type);
+            self.cache_volatile = volatile;
+        }
+        self.cache_devs.extend(device_pairs);
         Ok(ret_uuids)
     }
 
-    fn destroy(&mut self) -> StratisResult<()> {
+    fn cache_type(&self) -> Option<CacheType> {
+        self.cache_type
+    }
+
+    fn cache_volatile(&self) -> bool {
+        self.cache_volatile
+    }
+
+    fn cache_usage(&self) -> StratisResult<Option<CacheUsage>> {
+        // A simulated cache holds no data.
+        Ok(self.cache_type.map(|_| CacheUsage {
+            used: Sectors(0),
+            dirty: Sectors(0),
+            total: self.cache_devs.values().map(|bd| bd.size()).sum(),
+        }))
+    }
+
+    fn plan_add_datadevs(
+        &mut self,
+        _pool_uuid: PoolUuid,
+        paths: &[&Path],
+    ) -> StratisResult<(Uuid, AddDataDevsPlan)> {
+        let current = self.block_devs
+            .values()
+            .map(|bd| (bd.devnode(), bd.size()))
+            .collect::<HashMap<_, _>>();
+        let added = paths
+            .iter()
+            .map(|p| p.to_path_buf())
+            .filter(|p| !current.contains_key(p))
+            .collect::<HashSet<_>>();
+        let added_size = self.caps.borrow().device_size();
+        let layout = current
+            .into_iter()
+            .map(|(devnode, size)| (devnode, size, false))
+            .chain(added.into_iter().map(|devnode| (devnode, added_size, true)))
+            .map(|(devnode, size, new)| PlannedBlockDev {
+                devnode,
+                size,
+                usable: size,
+                rotational: None,
+                new,
+            })
+            .collect();
+
+        let plan = AddDataDevsPlan::new(layout);
+        let token = Uuid::new_v4();
+        self.add_datadevs_plan = Some((token, plan.clone()));
+        Ok((token, plan))
+    }
+
+    fn apply_add_datadevs_plan(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        token: Uuid,
+    ) -> StratisResult<Vec<DevUuid>> {
+        let devnodes = match self.add_datadevs_plan {
+            Some((plan_token, ref plan)) if plan_token == token => plan.new_devnodes(),
+            _ => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NotFound,
+                    ErrorPayload::new("PlanNotFound").param("token", token),
+                ))
+            }
+        };
+
+        let paths = devnodes.iter().map(|p| p.as_path()).collect::<Vec<_>>();
+        let uuids = self.add_blockdevs(pool_uuid, pool_name, &paths, BlockDevTier::Data, None)?;
+        self.add_datadevs_plan = None;
+        Ok(uuids)
+    }
+
+    fn destroy(&mut self, _keep_signatures: bool) -> StratisResult<()> {
         // Nothing to do here.
         Ok(())
     }
@@ -142,14 +522,56 @@ impl Pool for SimPool {
         &'a mut self,
         _pool_name: &str,
         fs_uuids: &[FilesystemUuid],
-    ) -> StratisResult<Vec<FilesystemUuid>> {
-        let mut removed = Vec::new();
-        for &uuid in fs_uuids {
-            if self.filesystems.remove_by_uuid(uuid).is_some() {
-                removed.push(uuid);
+    ) -> StratisResult<Vec<(FilesystemUuid, StratisResult<()>)>> {
+        refuse_if_protected(fs_uuids.iter().filter_map(|&uuid| {
+            self.filesystems
+                .get_by_uuid(uuid)
+                .map(|(_, fs)| (uuid, fs.protected()))
+        }))?;
+
+        let order = destroy_order(fs_uuids.iter().filter_map(|&uuid| {
+            self.filesystems
+                .get_by_uuid(uuid)
+                .map(|(_, fs)| (uuid, fs.origin()))
+        }));
+
+        let mut results = Vec::new();
+        for uuid in order {
+            self.filesystems.remove_by_uuid(uuid);
+            record_change(ObjectKind::Filesystem, uuid, ChangeKind::Deleted);
+            results.push((uuid, Ok(())));
+        }
+        Ok(results)
+    }
+
+    fn emergency_reclaim(
+        &mut self,
+        pool_name: &str,
+        target: u8,
+    ) -> StratisResult<Vec<(Name, FilesystemUuid)>> {
+        validate_reclaim_target(target)?;
+
+        let order = reclaim_order(
+            self.filesystems
+                .iter()
+                .map(|(_, &uuid, fs)| (uuid, fs.origin(), fs.protected(), fs.created())),
+        );
+
+        let mut destroyed = Vec::new();
+        for uuid in order {
+            if self.used_percent()? < target {
+                break;
+            }
+            if let Some((name, _)) = self.filesystems.remove_by_uuid(uuid) {
+                warn!(
+                    "Emergency reclamation destroyed snapshot {} with UUID {} of pool {}",
+                    name, uuid, pool_name
+                );
+                record_change(ObjectKind::Filesystem, uuid, ChangeKind::Deleted);
+                destroyed.push((name, uuid));
             }
         }
-        Ok(removed)
+        Ok(destroyed)
     }
 
     fn rename_filesystem(
@@ -166,36 +588,119 @@ impl Pool for SimPool {
 
         self.filesystems
             .insert(Name::new(new_name.to_owned()), uuid, filesystem);
+        record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
 
         Ok(RenameAction::Renamed)
     }
 
+    fn set_filesystem_read_ahead(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        read_ahead: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        let filesystem = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some((_, filesystem)) => filesystem,
+            None => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NotFound,
+                    ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+                ));
+            }
+        };
+        if filesystem.read_ahead() == read_ahead {
+            return Ok(false);
+        }
+        filesystem.set_read_ahead(read_ahead);
+        record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn set_filesystem_protected(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        protected: bool,
+    ) -> StratisResult<bool> {
+        let filesystem = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some((_, filesystem)) => filesystem,
+            None => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NotFound,
+                    ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+                ));
+            }
+        };
+        if filesystem.protected() == protected {
+            return Ok(false);
+        }
+        filesystem.set_protected(protected);
+        record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn set_filesystem_size_limit(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        limit: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        let filesystem = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some((_, filesystem)) => filesystem,
+            None => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NotFound,
+                    ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+                ));
+            }
+        };
+        if filesystem.size_limit() == limit {
+            return Ok(false);
+        }
+        filesystem.set_size_limit(limit)?;
+        record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
     fn snapshot_filesystem(
         &mut self,
         _pool_uuid: PoolUuid,
         _pool_name: &str,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        _freeze: bool,
     ) -> StratisResult<(FilesystemUuid, &mut Filesystem)> {
         if self.filesystems.contains_name(snapshot_name) {
-            return Err(StratisError::Engine(
+            return Err(StratisError::Structured(
                 ErrorEnum::AlreadyExists,
-                snapshot_name.to_string(),
+                ErrorPayload::new("FilesystemNameExists").param("name", snapshot_name),
             ));
         }
+        check_fs_limit(self.filesystems.len(), 1, self.fs_limit)?;
 
-        let uuid = Uuid::new_v4();
-        let snapshot = match self.get_filesystem(origin_uuid) {
-            Some(_filesystem) => SimFilesystem::new(),
+        let uuid = FilesystemUuid::new_v4();
+        let snapshot = match self.filesystems.get_by_uuid(origin_uuid) {
+            // Snapshots share their blocks with their origin, so the
+            // guarantee does not carry over to them.
+            Some((_, filesystem)) => {
+                check_overcommit(
+                    self.virtual_size(),
+                    filesystem.size(),
+                    self.total_physical_size(),
+                    self.overcommit_cap,
+                )?;
+                filesystem.new_snapshot(origin_uuid)
+            }
             None => {
-                return Err(StratisError::Engine(
+                return Err(StratisError::Structured(
                     ErrorEnum::NotFound,
-                    origin_uuid.to_string(),
+                    ErrorPayload::new("FilesystemNotFound").param("uuid", origin_uuid),
                 ));
             }
         };
         self.filesystems
             .insert(Name::new(snapshot_name.to_owned()), uuid, snapshot);
+        record_change(ObjectKind::Filesystem, uuid, ChangeKind::Created);
         Ok((
             uuid,
             self.filesystems
@@ -205,14 +710,36 @@ impl Pool for SimPool {
         ))
     }
 
+    fn check_filesystem(
+        &self,
+        uuid: FilesystemUuid,
+        _repair: bool,
+    ) -> StratisResult<(bool, String)> {
+        if self.filesystems.get_by_uuid(uuid).is_none() {
+            return Err(StratisError::Structured(
+                ErrorEnum::NotFound,
+                ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+            ));
+        }
+        Ok((true, String::new()))
+    }
+
+    fn repair(&mut self, _pool_uuid: PoolUuid, _pool_name: &str) -> StratisResult<bool> {
+        Ok(false)
+    }
+
     fn total_physical_size(&self) -> Sectors {
-        // We choose to make our pools very big, and we can change that
-        // if it is inconvenient.
-        Sectors(IEC::Ei)
+        self.block_devs.values().map(|bd| bd.size()).sum()
     }
 
     fn total_physical_used(&self) -> StratisResult<Sectors> {
-        Ok(Sectors(0))
+        // A snapshot is counted as if it shared no blocks with its origin,
+        // which overstates the space used rather than understating it.
+        let used = self.filesystems
+            .iter()
+            .map(|(_, _, fs)| fs.used().map(|used| used.sectors()))
+            .sum::<StratisResult<Sectors>>()?;
+        Ok(min(used, self.total_physical_size()))
     }
 
     fn filesystems(&self) -> Vec<(Name, FilesystemUuid, &Filesystem)> {
@@ -241,6 +768,13 @@ impl Pool for SimPool {
             .map(|(name, p)| (name, p as &mut Filesystem))
     }
 
+    fn filesystem_space(&self) -> StratisResult<Vec<(FilesystemUuid, Sectors, Sectors)>> {
+        self.filesystems
+            .iter()
+            .map(|(_, &uuid, fs)| Ok((uuid, fs.used()?.sectors(), Sectors(0))))
+            .collect()
+    }
+
     fn blockdevs(&self) -> Vec<(DevUuid, &BlockDev)> {
         self.block_devs
             .iter()
@@ -286,53 +820,401 @@ impl Pool for SimPool {
                     format!("No blockdev for uuid {} found", uuid),
                 ))
             },
-            |(_, b)| Ok(b.set_user_info(user_info)),
+            |(_, b)| {
+                let changed = b.set_user_info(user_info);
+                if changed {
+                    record_change(ObjectKind::BlockDev, uuid, ChangeKind::Modified);
+                }
+                Ok(changed)
+            },
         )
     }
 
+    fn created_by(&self) -> Option<&Creator> {
+        self.created_by.as_ref()
+    }
+
+    fn encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    fn creation_params(&self) -> Option<&CreationParams> {
+        Some(&self.creation_params)
+    }
+
+    fn verify_writes(&self) -> bool {
+        self.verify_writes
+    }
+
+    fn set_verify_writes(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        verify: bool,
+    ) -> StratisResult<bool> {
+        let changed = self.verify_writes != verify;
+        self.verify_writes = verify;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn allocation_strategy(&self) -> AllocationStrategy {
+        self.allocation_strategy
+    }
+
+    fn set_allocation_strategy(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        strategy: Option<u16>,
+    ) -> StratisResult<bool> {
+        let strategy = calculate_allocation_strategy!(strategy);
+        let changed = self.allocation_strategy != strategy;
+        self.allocation_strategy = strategy;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn worker_priority(&self) -> WorkerPriority {
+        self.worker_priority
+    }
+
+    fn set_worker_priority(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        niceness: i16,
+        io_class: Option<u16>,
+        io_level: u8,
+    ) -> StratisResult<bool> {
+        validate_worker_priority(niceness, io_level)?;
+        let io_class = calculate_io_class!(io_class);
+        let priority = WorkerPriority {
+            niceness: niceness as i8,
+            io_class,
+            io_level: if io_class == IoClass::Idle { 0 } else { io_level },
+        };
+        let changed = self.worker_priority != priority;
+        self.worker_priority = priority;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn shrink_reserved_space(&mut self) -> StratisResult<Sectors> {
+        // A simulated blockdev has no metadata, and so no reserved area.
+        Ok(Sectors(0))
+    }
+
+    fn blockdev_removed(&mut self, _device: Device) -> Option<(BlockDevTier, DevUuid)> {
+        // A simulated blockdev has no device number, so udev never reports
+        // its removal.
+        None
+    }
+
     fn state(&self) -> PoolState {
         self.pool_state
     }
 
+    fn format_versions(&self) -> FormatVersions {
+        FormatVersions::lowest(self.blockdevs().iter().map(|&(_, bd)| bd.format_versions()))
+            .unwrap_or(MAX_FORMAT_VERSIONS)
+    }
+
+    fn dm_devices(&self) -> Vec<(String, DmDeviceInfo)> {
+        // The simulator makes no devicemapper devices.
+        Vec::new()
+    }
+
     fn extend_state(&self) -> PoolExtendState {
         self.pool_extend_state
     }
 
+    fn last_metadata_update(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    fn metadata_behind_blockdevs(&self) -> Vec<DevUuid> {
+        vec![]
+    }
+
+    fn operation_stuck(&self, _pool_uuid: PoolUuid) -> bool {
+        false
+    }
+
     fn free_space_state(&self) -> FreeSpaceState {
         self.free_space_state
     }
 
-    fn set_dbus_path(&mut self, path: MaybeDbusPath) -> () {
-        self.dbus_path = path
+    fn days_until_full(&self) -> Option<u64> {
+        // Every filesystem that is not yet full grows at the same rate.
+        let growing = self.filesystems
+            .iter()
+            .filter(|&(_, _, fs)| fs.used().map_or(false, |used| used < fs.size().bytes()))
+            .count() as u64;
+        let rate = (*self.caps.borrow().growth())
+            .saturating_mul(growing)
+            .saturating_mul(SECONDS_PER_DAY);
+        if rate == 0 {
+            return None;
+        }
+        match self.total_physical_used() {
+            Ok(used) => Some(*(self.total_physical_size() - used).bytes() / rate),
+            Err(_) => None,
+        }
     }
 
-    fn get_dbus_path(&self) -> &MaybeDbusPath {
-        &self.dbus_path
+    fn overcommit_percent(&self) -> Option<u64> {
+        overcommit_percent(self.virtual_size(), self.total_physical_size())
     }
-}
-
-#[cfg(test)]
-mod tests {
 
-    use std::path::Path;
+    fn thin_meta_usage(&self) -> Option<(Sectors, Sectors)> {
+        // The simulator has no thin pool.
+        None
+    }
 
-    use uuid::Uuid;
+    fn replay_metadata(&self, _pool_uuid: PoolUuid) -> StratisResult<Vec<String>> {
+        // The simulator saves no metadata, so there is nothing to replay.
+        Ok(vec![])
+    }
 
-    use engine::Engine;
+    fn overcommit_cap(&self) -> Option<u64> {
+        self.overcommit_cap
+    }
 
-    use super::super::SimEngine;
+    fn set_overcommit_cap(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        cap: Option<u64>,
+    ) -> StratisResult<bool> {
+        let changed = self.overcommit_cap != cap;
+        self.overcommit_cap = cap;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
 
-    use super::*;
+    fn usage_thresholds(&self) -> Vec<u8> {
+        self.usage_thresholds.clone()
+    }
 
-    #[test]
-    /// Renaming a filesystem on an empty pool always works
+    fn set_usage_thresholds(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        thresholds: &[u8],
+    ) -> StratisResult<bool> {
+        validate_usage_thresholds(thresholds)?;
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort();
+        thresholds.dedup();
+        let changed = self.usage_thresholds != thresholds;
+        self.usage_thresholds = thresholds;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn watermarks(&self) -> Watermarks {
+        self.watermarks
+    }
+
+    fn set_watermarks(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        watermarks: Watermarks,
+    ) -> StratisResult<bool> {
+        validate_watermarks(watermarks)?;
+        let changed = self.watermarks != watermarks;
+        self.watermarks = watermarks;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn default_filesystem_size(&self) -> Sectors {
+        self.default_fs_size.unwrap_or_else(|| self.caps.borrow().filesystem_size())
+    }
+
+    fn set_default_filesystem_size(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        size: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        if let Some(size) = size {
+            check_overcommit(
+                Sectors(0),
+                size,
+                self.total_physical_size(),
+                self.overcommit_cap,
+            )?;
+        }
+        let changed = self.default_fs_size != size;
+        self.default_fs_size = size;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn fs_limit(&self) -> u64 {
+        self.fs_limit
+    }
+
+    fn set_fs_limit(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        limit: u64,
+    ) -> StratisResult<bool> {
+        validate_fs_limit(self.filesystems.len(), limit)?;
+        let changed = self.fs_limit != limit;
+        self.fs_limit = limit;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn snapshot_schedule(&self, uuid: FilesystemUuid) -> Option<SnapshotSchedule> {
+        self.snapshot_schedules.get(uuid)
+    }
+
+    fn set_snapshot_schedule(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        schedule: Option<(u16, u32)>,
+    ) -> StratisResult<bool> {
+        let schedule = match schedule {
+            Some((interval, retain)) => {
+                validate_snapshot_retention(retain)?;
+                Some(SnapshotSchedule {
+                    interval: calculate_snapshot_interval!(interval),
+                    retain,
+                })
+            }
+            None => None,
+        };
+        if self.filesystems.get_by_uuid(uuid).is_none() {
+            return Err(StratisError::Structured(
+                ErrorEnum::NotFound,
+                ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+            ));
+        }
+        let changed = self.snapshot_schedules.set(uuid, schedule);
+        if changed {
+            record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn run_snapshot_schedules(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        now: DateTime<Utc>,
+    ) -> StratisResult<(Vec<FilesystemUuid>, Vec<FilesystemUuid>)> {
+        let mut taken = Vec::new();
+        let mut destroyed = Vec::new();
+        self.forget_destroyed_schedules();
+        for (origin, interval) in self.snapshot_schedules.due(now) {
+            let snapshot_name = match self.filesystems.get_by_uuid(origin) {
+                Some((name, _)) => scheduled_snapshot_name(&name, interval, now),
+                None => continue,
+            };
+            let snapshot =
+                match self.snapshot_filesystem(pool_uuid, pool_name, origin, &snapshot_name, true) {
+                    Ok((uuid, _)) => Some(uuid),
+                    Err(err) => {
+                        warn!(
+                            "Could not take scheduled snapshot {} of pool {}: {}",
+                            snapshot_name, pool_name, err
+                        );
+                        None
+                    }
+                };
+            taken.extend(snapshot);
+
+            let excess = self.snapshot_schedules.record_run(origin, now, snapshot);
+            for uuid in excess {
+                match self.destroy_filesystems(pool_name, &[uuid]) {
+                    Ok(results) => destroyed.extend(
+                        results
+                            .into_iter()
+                            .filter(|&(_, ref result)| result.is_ok())
+                            .map(|(uuid, _)| uuid),
+                    ),
+                    Err(err) => warn!(
+                        "Could not destroy scheduled snapshot {} of pool {}: {}",
+                        uuid, pool_name, err
+                    ),
+                }
+            }
+        }
+        self.forget_destroyed_schedules();
+        Ok((taken, destroyed))
+    }
+
+    fn run_housekeeping(&mut self, _pool_uuid: PoolUuid, _pool_name: &str) -> StratisResult<()> {
+        Ok(())
+    }
+
+    fn set_dbus_path(&mut self, path: MaybeDbusPath) -> () {
+        self.dbus_path = path
+    }
+
+    fn get_dbus_path(&self) -> &MaybeDbusPath {
+        &self.dbus_path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::Path;
+
+    use chrono::Duration;
+    use devicemapper::{Bytes, IEC};
+
+    use engine::{changes_since, Change, Engine};
+
+    use super::super::SimEngine;
+
+    use super::*;
+
+    /// The filesystems destroyed, in the order destroyed. Panics if any
+    /// filesystem found could not be destroyed.
+    fn destroyed(results: Vec<(FilesystemUuid, StratisResult<()>)>) -> Vec<FilesystemUuid> {
+        results
+            .into_iter()
+            .map(|(uuid, result)| {
+                result.unwrap();
+                uuid
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Renaming a filesystem on an empty pool always works
     fn rename_empty() {
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
         assert!(
-            match pool.rename_filesystem(pool_name, Uuid::new_v4(), "new_name") {
+            match pool.rename_filesystem(pool_name, FilesystemUuid::new_v4(), "new_name") {
                 Ok(RenameAction::NoSource) => true,
                 _ => false,
             }
@@ -344,9 +1226,9 @@ mod tests {
     fn rename_happens() {
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
-        let infos = pool.create_filesystems(uuid, pool_name, &[("old_name", None)])
+        let infos = pool.create_filesystems(uuid, pool_name, &[("old_name", None, false)], None)
             .unwrap();
         assert!(
             match pool.rename_filesystem(pool_name, infos[0].1, "new_name") {
@@ -363,15 +1245,18 @@ mod tests {
         let new_name = "new_name";
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
-        let results =
-            pool.create_filesystems(uuid, pool_name, &[(old_name, None), (new_name, None)])
-                .unwrap();
+        let results = pool.create_filesystems(
+            uuid,
+            pool_name,
+            &[(old_name, None, false), (new_name, None, false)],
+            None,
+        ).unwrap();
         let old_uuid = results.iter().find(|x| x.0 == old_name).unwrap().1;
         assert!(
             match pool.rename_filesystem(pool_name, old_uuid, new_name) {
-                Err(StratisError::Engine(ErrorEnum::AlreadyExists, _)) => true,
+                Err(ref err) => err.code() == ErrorEnum::AlreadyExists,
                 _ => false,
             }
         );
@@ -383,22 +1268,174 @@ mod tests {
         let new_name = "new_name";
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
         assert!(
-            match pool.rename_filesystem(pool_name, Uuid::new_v4(), new_name) {
+            match pool.rename_filesystem(pool_name, FilesystemUuid::new_v4(), new_name) {
                 Ok(RenameAction::NoSource) => true,
                 _ => false,
             }
         );
     }
 
+    #[test]
+    /// Setting the read-ahead of a filesystem should record it, report a
+    /// change only if there was one, and fail for an unknown filesystem.
+    fn set_read_ahead() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool.create_filesystems(uuid, pool_name, &[("fs", None, false)], None)
+            .unwrap()[0]
+            .1;
+        let read_ahead = Some(Sectors(8192));
+
+        assert!(pool.set_filesystem_read_ahead(pool_name, fs_uuid, read_ahead).unwrap());
+        assert!(!pool.set_filesystem_read_ahead(pool_name, fs_uuid, read_ahead).unwrap());
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.read_ahead(), read_ahead);
+        assert!(pool.set_filesystem_read_ahead(pool_name, fs_uuid, None).unwrap());
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.read_ahead(), None);
+
+        assert!(
+            pool.set_filesystem_read_ahead(pool_name, FilesystemUuid::new_v4(), read_ahead)
+                .is_err()
+        );
+    }
+
+    #[test]
+    /// Setting the size limit of a filesystem should record it, report a
+    /// change only if there was one, and fail for a limit below the size
+    /// of the filesystem.
+    fn set_size_limit() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool.create_filesystems(uuid, pool_name, &[("fs", None, false)], None)
+            .unwrap()[0]
+            .1;
+        let limit = Some(Sectors(1 << 40));
+
+        assert!(pool.set_filesystem_size_limit(pool_name, fs_uuid, limit).unwrap());
+        assert!(!pool.set_filesystem_size_limit(pool_name, fs_uuid, limit).unwrap());
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.size_limit(), limit);
+        assert!(
+            pool.set_filesystem_size_limit(pool_name, fs_uuid, Some(Sectors(1)))
+                .is_err()
+        );
+        assert!(pool.set_filesystem_size_limit(pool_name, fs_uuid, None).unwrap());
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.size_limit(), None);
+    }
+
+    #[test]
+    /// A snapshot of a snapshot can be made, and the space of every
+    /// filesystem, snapshots included, is listed.
+    fn snapshot_of_snapshot_space() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool.create_filesystems(uuid, pool_name, &[("fs", None, false)], None)
+            .unwrap()[0]
+            .1;
+        let snap_uuid = pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snap", false)
+            .unwrap()
+            .0;
+        let snap_snap_uuid = pool
+            .snapshot_filesystem(uuid, pool_name, snap_uuid, "snap_snap", false)
+            .unwrap()
+            .0;
+
+        let space = pool.filesystem_space().unwrap();
+        assert_eq!(space.len(), 3);
+        for fs in &[fs_uuid, snap_uuid, snap_snap_uuid] {
+            assert!(space.iter().any(|&(uuid, _, _)| uuid == *fs));
+        }
+    }
+
+    #[test]
+    /// Emergency reclamation destroys snapshots, snapshots of snapshots
+    /// included, but never a filesystem that is not a snapshot, and
+    /// destroys nothing once the target usage is reached.
+    fn emergency_reclaim() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool.create_filesystems(uuid, pool_name, &[("fs", None, false)], None)
+            .unwrap()[0]
+            .1;
+        let snap_uuid = pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snap", false)
+            .unwrap()
+            .0;
+        let snap_snap_uuid = pool
+            .snapshot_filesystem(uuid, pool_name, snap_uuid, "snap_snap", false)
+            .unwrap()
+            .0;
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.origin(), None);
+        assert_eq!(
+            pool.get_filesystem(snap_snap_uuid).unwrap().1.origin(),
+            Some(snap_uuid)
+        );
+
+        assert!(pool.emergency_reclaim(pool_name, 101).is_err());
+        assert!(pool.emergency_reclaim(pool_name, 50).unwrap().is_empty());
+
+        let destroyed = pool.emergency_reclaim(pool_name, 0)
+            .unwrap()
+            .into_iter()
+            .map(|(_, uuid)| uuid)
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            destroyed,
+            [snap_uuid, snap_snap_uuid].iter().cloned().collect()
+        );
+        assert_eq!(pool.filesystems().len(), 1);
+        assert!(pool.get_filesystem(fs_uuid).is_some());
+    }
+
+    #[test]
+    /// A protected filesystem can not be destroyed, whether directly or by
+    /// emergency reclamation, until it is unprotected.
+    fn protected_filesystem() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool.create_filesystems(uuid, pool_name, &[("fs", None, false)], None)
+            .unwrap()[0]
+            .1;
+        let snap_uuid = pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snap", false)
+            .unwrap()
+            .0;
+
+        assert!(pool.set_filesystem_protected(pool_name, snap_uuid, true).unwrap());
+        assert!(!pool.set_filesystem_protected(pool_name, snap_uuid, true).unwrap());
+        assert!(pool.get_filesystem(snap_uuid).unwrap().1.protected());
+
+        assert!(pool.destroy_filesystems(pool_name, &[fs_uuid, snap_uuid]).is_err());
+        assert_eq!(pool.filesystems().len(), 2);
+        assert!(pool.emergency_reclaim(pool_name, 0).unwrap().is_empty());
+
+        assert!(pool.set_filesystem_protected(pool_name, snap_uuid, false).unwrap());
+        assert_eq!(
+            destroyed(pool.destroy_filesystems(pool_name, &[snap_uuid]).unwrap()),
+            vec![snap_uuid]
+        );
+
+        assert!(
+            pool.set_filesystem_protected(pool_name, FilesystemUuid::new_v4(), true)
+                .is_err()
+        );
+    }
+
     #[test]
     /// Removing an empty list of filesystems should always succeed
     fn destroy_fs_empty() {
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
         assert!(match pool.destroy_filesystems(pool_name, &[]) {
             Ok(names) => names.is_empty(),
@@ -411,10 +1448,10 @@ mod tests {
     fn destroy_fs_some() {
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
         assert!(
-            pool.destroy_filesystems(pool_name, &[Uuid::new_v4()])
+            pool.destroy_filesystems(pool_name, &[FilesystemUuid::new_v4()])
                 .is_ok()
         );
     }
@@ -424,27 +1461,383 @@ mod tests {
     fn destroy_fs_any() {
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
-        let fs_results = pool.create_filesystems(uuid, pool_name, &[("fs_name", None)])
+        let fs_results = pool.create_filesystems(uuid, pool_name, &[("fs_name", None, false)], None)
             .unwrap();
         let fs_uuid = fs_results[0].1;
         assert!(
-            match pool.destroy_filesystems(pool_name, &[fs_uuid, Uuid::new_v4()]) {
-                Ok(filesystems) => filesystems == vec![fs_uuid],
+            match pool.destroy_filesystems(pool_name, &[fs_uuid, FilesystemUuid::new_v4()]) {
+                Ok(results) => destroyed(results) == vec![fs_uuid],
                 _ => false,
             }
         );
     }
 
+    #[test]
+    /// A snapshot is destroyed before its origin, whatever the order in which
+    /// they are given.
+    fn destroy_fs_snapshot_first() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool.create_filesystems(uuid, pool_name, &[("fs", None, false)], None)
+            .unwrap()[0]
+            .1;
+        let snap_uuid = pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snap", false)
+            .unwrap()
+            .0;
+        assert_eq!(
+            destroyed(pool.destroy_filesystems(pool_name, &[fs_uuid, snap_uuid]).unwrap()),
+            vec![snap_uuid, fs_uuid]
+        );
+        assert!(pool.filesystems().is_empty());
+    }
+
+    #[test]
+    /// A filesystem created as guaranteed is reported as such, but a
+    /// snapshot of it is not
+    fn create_fs_guaranteed() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool.create_filesystems(uuid, pool_name, &[("fs_name", None, true)], None)
+            .unwrap()[0]
+            .1;
+        assert!(pool.get_filesystem(fs_uuid).unwrap().1.guaranteed());
+        let (_, snapshot) = pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snapshot", false)
+            .unwrap();
+        assert!(!snapshot.guaranteed());
+    }
+
+    #[test]
+    /// The creator of a pool and of a filesystem is reported as given, but
+    /// no creator is reported for a snapshot.
+    fn create_with_creator() {
+        let creator = Creator {
+            uid: 1000,
+            process: Some("stratis".into()),
+        };
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(pool_name, &[], None, None, None, Some(creator.clone()))
+            .unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        assert_eq!(pool.created_by(), Some(&creator));
+        let fs_uuid = pool
+            .create_filesystems(uuid, pool_name, &[("fs_name", None, false)], Some(creator.clone()))
+            .unwrap()[0]
+            .1;
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.created_by(), Some(&creator));
+        let (_, snapshot) = pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snapshot", false)
+            .unwrap();
+        assert_eq!(snapshot.created_by(), None);
+    }
+
+    #[test]
+    /// The parameters with which a pool was created are reported, whether
+    /// or not it is encrypted.
+    fn creation_params() {
+        let mut engine = SimEngine::default();
+        let uuid = engine
+            .create_pool("pool_name", &[], None, None, None, None)
+            .unwrap();
+        let params = engine.get_pool(uuid).unwrap().1.creation_params().cloned();
+        assert_eq!(
+            params,
+            Some(CreationParams::new(
+                SIM_MDA_SIZE,
+                SIM_CHUNK_SIZE,
+                Redundancy::NONE,
+                false,
+            ))
+        );
+
+        let uuid = engine
+            .create_encrypted_pool("encrypted", &[], None, None, None, "passphrase", None)
+            .unwrap();
+        let params = engine.get_pool(uuid).unwrap().1.creation_params().cloned();
+        assert!(params.map_or(false, |p| p.encrypted));
+    }
+
+    #[test]
+    /// Checking a filesystem succeeds, but checking a nonexistent one
+    /// fails.
+    fn check_filesystem() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool.create_filesystems(uuid, pool_name, &[("fs_name", None, false)], None)
+            .unwrap()[0]
+            .1;
+        assert!(pool.check_filesystem(fs_uuid, false).unwrap().0);
+        assert!(match pool.check_filesystem(FilesystemUuid::new_v4(), true) {
+            Err(ref err) => err.code() == ErrorEnum::NotFound,
+            _ => false,
+        });
+    }
+
+    #[test]
+    /// Verify that a pool setter that changes the pool records the pool as
+    /// modified, and that one that changes nothing records nothing.
+    fn set_records_change() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let modified = Change {
+            object: ObjectKind::Pool,
+            uuid: *uuid,
+            kind: ChangeKind::Modified,
+        };
+
+        let (token, _) = changes_since(None);
+        assert!(pool.set_fs_limit(uuid, pool_name, 7).unwrap());
+        assert!(changes_since(Some(token)).1.unwrap().contains(&modified));
+
+        let (token, _) = changes_since(None);
+        assert!(!pool.set_fs_limit(uuid, pool_name, 7).unwrap());
+        assert!(!changes_since(Some(token)).1.unwrap().contains(&modified));
+    }
+
+    #[test]
+    /// Verify that usage thresholds are kept in ascending order, and that a
+    /// threshold that is not a percentage is refused.
+    fn set_usage_thresholds() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        assert_eq!(pool.usage_thresholds(), DEFAULT_USAGE_THRESHOLDS);
+        assert!(pool.set_usage_thresholds(uuid, pool_name, &[90, 50]).unwrap());
+        assert_eq!(pool.usage_thresholds(), vec![50, 90]);
+        assert!(!pool.set_usage_thresholds(uuid, pool_name, &[50, 90, 90]).unwrap());
+        assert!(match pool.set_usage_thresholds(uuid, pool_name, &[101]) {
+            Err(ref err) => err.code() == ErrorEnum::Invalid,
+            _ => false,
+        });
+        assert_eq!(pool.usage_thresholds(), vec![50, 90]);
+    }
+
+    #[test]
+    /// Verify that watermarks are set, and that a watermark that is not a
+    /// percentage from 1 to 99 is refused.
+    fn set_watermarks() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        assert_eq!(pool.watermarks(), Watermarks::default());
+        let watermarks = Watermarks {
+            warn: Some(80),
+            extend: Some(90),
+        };
+        assert!(pool.set_watermarks(uuid, pool_name, watermarks).unwrap());
+        assert!(!pool.set_watermarks(uuid, pool_name, watermarks).unwrap());
+        let bad_watermarks = Watermarks {
+            warn: None,
+            extend: Some(100),
+        };
+        assert!(match pool.set_watermarks(uuid, pool_name, bad_watermarks) {
+            Err(ref err) => err.code() == ErrorEnum::Invalid,
+            _ => false,
+        });
+        assert_eq!(pool.watermarks(), watermarks);
+    }
+
+    #[test]
+    /// Verify that the overcommit cap blocks a snapshot that would take the
+    /// pool beyond it, and that removing the cap unblocks it.
+    fn snapshot_beyond_overcommit_cap() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(pool_name, &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        assert_eq!(pool.overcommit_percent(), Some(0));
+        assert!(pool.set_overcommit_cap(uuid, pool_name, Some(100)).unwrap());
+        let size = pool.total_physical_size();
+        let fs_uuid = pool
+            .create_filesystems(uuid, pool_name, &[("fs_name", Some(size), false)], None)
+            .unwrap()[0]
+            .1;
+        assert_eq!(pool.overcommit_percent(), Some(100));
+        assert!(
+            pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snapshot", false)
+                .is_err()
+        );
+        assert!(pool.set_overcommit_cap(uuid, pool_name, None).unwrap());
+        assert!(
+            pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snapshot", false)
+                .is_ok()
+        );
+        assert_eq!(pool.overcommit_percent(), Some(200));
+    }
+
+    #[test]
+    /// Verify that no filesystem or snapshot is created beyond the limit,
+    /// and that the limit can not be set below the number of filesystems.
+    fn create_fs_beyond_limit() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(pool_name, &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        assert!(pool.set_fs_limit(uuid, pool_name, 1).unwrap());
+        let specs = [("a", None, false), ("b", None, false)];
+        assert!(
+            pool.create_filesystems(uuid, pool_name, &specs, None)
+                .is_err()
+        );
+        let fs_uuid = pool
+            .create_filesystems(uuid, pool_name, &[("a", None, false)], None)
+            .unwrap()[0]
+            .1;
+        assert!(
+            pool.snapshot_filesystem(uuid, pool_name, fs_uuid, "snapshot", false)
+                .is_err()
+        );
+        assert!(pool.set_fs_limit(uuid, pool_name, 0).is_err());
+        assert_eq!(pool.fs_limit(), 1);
+    }
+
+    #[test]
+    /// Verify that a snapshot schedule takes one snapshot in each period,
+    /// destroys the oldest beyond those it keeps, and is forgotten when its
+    /// filesystem is destroyed.
+    fn run_snapshot_schedules() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(pool_name, &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let fs_uuid = pool
+            .create_filesystems(uuid, pool_name, &[("home", None, false)], None)
+            .unwrap()[0]
+            .1;
+
+        assert!(
+            pool.set_snapshot_schedule(pool_name, FilesystemUuid::new_v4(), Some((0, 1)))
+                .is_err()
+        );
+        assert!(
+            pool.set_snapshot_schedule(pool_name, fs_uuid, Some((2, 1)))
+                .is_err()
+        );
+        assert!(
+            pool.set_snapshot_schedule(pool_name, fs_uuid, Some((0, 0)))
+                .is_err()
+        );
+        assert!(
+            pool.set_snapshot_schedule(pool_name, fs_uuid, Some((0, 1)))
+                .unwrap()
+        );
+        assert_eq!(
+            pool.snapshot_schedule(fs_uuid),
+            Some(SnapshotSchedule {
+                interval: SnapshotInterval::Hourly,
+                retain: 1,
+            })
+        );
+
+        let now = Utc::now();
+        let (taken, destroyed) = pool.run_snapshot_schedules(uuid, pool_name, now).unwrap();
+        assert_eq!(taken.len(), 1);
+        assert!(destroyed.is_empty());
+        assert_eq!(
+            pool.run_snapshot_schedules(uuid, pool_name, now).unwrap(),
+            (vec![], vec![])
+        );
+
+        let later = now + Duration::hours(1);
+        let (later_taken, destroyed) = pool.run_snapshot_schedules(uuid, pool_name, later)
+            .unwrap();
+        assert_eq!(later_taken.len(), 1);
+        assert_eq!(destroyed, taken);
+        assert_eq!(pool.filesystems().len(), 2);
+
+        pool.destroy_filesystems(pool_name, &[later_taken[0], fs_uuid])
+            .unwrap();
+        assert_eq!(
+            pool.run_snapshot_schedules(uuid, pool_name, later + Duration::hours(1))
+                .unwrap(),
+            (vec![], vec![])
+        );
+        assert_eq!(pool.snapshot_schedule(fs_uuid), None);
+    }
+
+    #[test]
+    /// Verify that filesystems made from a prefix skip the names in use,
+    /// and that none are made if not all of them can be.
+    fn create_prefixed_fs() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(pool_name, &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        pool.create_filesystems(uuid, pool_name, &[("fs-002", None, false)], None)
+            .unwrap();
+        let created = pool.create_prefixed_filesystems(uuid, pool_name, "fs", 2, None, false, None)
+            .unwrap();
+        assert_eq!(
+            created.iter().map(|&(ref name, _)| &**name).collect::<Vec<_>>(),
+            vec!["fs-001", "fs-003"]
+        );
+
+        assert!(pool.set_fs_limit(uuid, pool_name, 4).unwrap());
+        assert!(
+            pool.create_prefixed_filesystems(uuid, pool_name, "fs", 2, None, false, None)
+                .is_err()
+        );
+        assert_eq!(pool.filesystems().len(), 3);
+    }
+
+    #[test]
+    /// Verify that a filesystem created without a size gets the pool's
+    /// default size, and that a default beyond the overcommit cap is
+    /// refused.
+    fn create_fs_default_size() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(pool_name, &[Path::new("/s/d")], None, None, None, None)
+            .unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let size = Sectors(IEC::Gi);
+        assert!(pool.set_default_filesystem_size(uuid, pool_name, Some(size)).unwrap());
+        assert_eq!(pool.default_filesystem_size(), size);
+        pool.create_filesystems(uuid, pool_name, &[("fs_name", None, false)], None)
+            .unwrap();
+        assert_eq!(
+            pool.overcommit_percent(),
+            overcommit_percent(size, pool.total_physical_size())
+        );
+
+        assert!(pool.set_overcommit_cap(uuid, pool_name, Some(100)).unwrap());
+        let too_big = 2u64 * pool.total_physical_size();
+        assert!(
+            pool.set_default_filesystem_size(uuid, pool_name, Some(too_big))
+                .is_err()
+        );
+        assert_eq!(pool.default_filesystem_size(), size);
+    }
+
     #[test]
     /// Creating an empty list of filesystems should succeed, always
     fn create_fs_none() {
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
-        assert!(match pool.create_filesystems(uuid, pool_name, &[]) {
+        assert!(match pool.create_filesystems(uuid, pool_name, &[], None) {
             Ok(names) => names.is_empty(),
             _ => false,
         });
@@ -455,10 +1848,10 @@ mod tests {
     fn create_fs_some() {
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
         assert!(
-            match pool.create_filesystems(uuid, pool_name, &[("name", None)]) {
+            match pool.create_filesystems(uuid, pool_name, &[("name", None, false)], None) {
                 Ok(names) => (names.len() == 1) & (names[0].0 == "name"),
                 _ => false,
             }
@@ -471,13 +1864,13 @@ mod tests {
         let fs_name = "fs_name";
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
-        pool.create_filesystems(uuid, pool_name, &[(fs_name, None)])
+        pool.create_filesystems(uuid, pool_name, &[(fs_name, None, false)], None)
             .unwrap();
         assert!(
-            match pool.create_filesystems(uuid, pool_name, &[(fs_name, None)]) {
-                Err(StratisError::Engine(ErrorEnum::AlreadyExists, _)) => true,
+            match pool.create_filesystems(uuid, pool_name, &[(fs_name, None, false)], None) {
+                Err(ref err) => err.code() == ErrorEnum::AlreadyExists,
                 _ => false,
             }
         );
@@ -489,10 +1882,15 @@ mod tests {
         let fs_name = "fs_name";
         let mut engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = engine.create_pool(pool_name, &[], None).unwrap();
+        let uuid = engine.create_pool(pool_name, &[], None, None, None, None).unwrap();
         let pool = engine.get_mut_pool(uuid).unwrap().1;
         assert!(
-            match pool.create_filesystems(uuid, pool_name, &[(fs_name, None), (fs_name, None)]) {
+            match pool.create_filesystems(
+                uuid,
+                pool_name,
+                &[(fs_name, None, false), (fs_name, None, false)],
+                None,
+            ) {
                 Ok(names) => (names.len() == 1) & (names[0].0 == fs_name),
                 _ => false,
             }
@@ -503,14 +1901,236 @@ mod tests {
     /// Adding a list of devices to an empty pool should yield list.
     fn add_device_empty() {
         let mut engine = SimEngine::default();
-        let uuid = engine.create_pool("pool_name", &[], None).unwrap();
+        let uuid = engine.create_pool("pool_name", &[], None, None, None, None).unwrap();
         let (pool_name, pool) = engine.get_mut_pool(uuid).unwrap();
         let devices = [Path::new("/s/a"), Path::new("/s/b")];
         assert!(
-            match pool.add_blockdevs(uuid, &*pool_name, &devices, BlockDevTier::Data) {
+            match pool.add_blockdevs(uuid, &*pool_name, &devices, BlockDevTier::Data, None) {
                 Ok(devs) => devs.len() == devices.len(),
                 _ => false,
             }
         );
     }
+
+    #[test]
+    /// A cache is made with the cache type of the first request to add
+    /// cache devices; a request for another type fails, as does extending
+    /// a writecache.
+    fn add_cachedevs_cache_type() {
+        let mut engine = SimEngine::default();
+        let uuid = engine.create_pool("pool_name", &[], None, None, None, None).unwrap();
+        let (pool_name, pool) = engine.get_mut_pool(uuid).unwrap();
+        assert_eq!(pool.cache_type(), None);
+        assert_eq!(pool.cache_usage().unwrap(), None);
+
+        pool.add_cachedevs(uuid, &*pool_name, &[Path::new("/s/a")], None, Some(1), false)
+            .unwrap();
+        assert_eq!(pool.cache_type(), Some(CacheType::WriteCache));
+        assert!(
+            pool.add_cachedevs(uuid, &*pool_name, &[Path::new("/s/b")], None, Some(0), false)
+                .is_err()
+        );
+        assert!(
+            pool.add_cachedevs(uuid, &*pool_name, &[Path::new("/s/b")], None, Some(1), false)
+                .is_err()
+        );
+        assert!(
+            pool.add_blockdevs(uuid, &*pool_name, &[Path::new("/s/b")], BlockDevTier::Cache, None)
+                .is_err()
+        );
+        assert!(
+            pool.add_cachedevs(uuid, &*pool_name, &[Path::new("/s/b")], None, Some(2), false)
+                .is_err()
+        );
+        assert_eq!(pool.cache_usage().unwrap().unwrap().used, Sectors(0));
+    }
+
+    #[test]
+    /// A volatile cache must be made with dm-cache, and may be extended
+    /// only with volatile devices.
+    fn add_cachedevs_volatile() {
+        let mut engine = SimEngine::default();
+        let uuid = engine.create_pool("pool_name", &[], None, None, None, None).unwrap();
+        let (pool_name, pool) = engine.get_mut_pool(uuid).unwrap();
+        assert!(!pool.cache_volatile());
+        assert!(
+            pool.add_cachedevs(uuid, &*pool_name, &[Path::new("/s/a")], None, Some(1), true)
+                .is_err()
+        );
+
+        pool.add_cachedevs(uuid, &*pool_name, &[Path::new("/s/a")], None, None, true)
+            .unwrap();
+        assert!(pool.cache_volatile());
+        assert!(
+            pool.add_cachedevs(uuid, &*pool_name, &[Path::new("/s/b")], None, None, false)
+                .is_err()
+        );
+        pool.add_blockdevs(uuid, &*pool_name, &[Path::new("/s/b")], BlockDevTier::Cache, None)
+            .unwrap();
+        assert!(pool.cache_volatile());
+    }
+
+    #[test]
+    /// Setting the allocation strategy changes it only if it differs, and
+    /// an unknown strategy code is an error.
+    fn set_allocation_strategy() {
+        let mut engine = SimEngine::default();
+        let uuid = engine.create_pool("pool_name", &[], None, None, None, None).unwrap();
+        let (pool_name, pool) = engine.get_mut_pool(uuid).unwrap();
+        assert_eq!(pool.allocation_strategy(), AllocationStrategy::FillFirst);
+        assert!(pool.set_allocation_strategy(uuid, &*pool_name, Some(2)).unwrap());
+        assert!(!pool.set_allocation_strategy(uuid, &*pool_name, Some(2)).unwrap());
+        assert_eq!(pool.allocation_strategy(), AllocationStrategy::SsdFirst);
+        assert!(pool.set_allocation_strategy(uuid, &*pool_name, Some(3)).is_err());
+        assert!(pool.set_allocation_strategy(uuid, &*pool_name, None).unwrap());
+        assert_eq!(pool.allocation_strategy(), AllocationStrategy::FillFirst);
+    }
+
+    #[test]
+    /// Setting the worker priority changes it only if it differs, the idle
+    /// class has no level, and a priority out of range is an error.
+    fn set_worker_priority() {
+        let mut engine = SimEngine::default();
+        let uuid = engine.create_pool("pool_name", &[], None, None, None, None).unwrap();
+        let (pool_name, pool) = engine.get_mut_pool(uuid).unwrap();
+        assert_eq!(pool.worker_priority(), WorkerPriority::default());
+        assert!(pool.set_worker_priority(uuid, &*pool_name, 10, Some(1), 3).unwrap());
+        assert!(!pool.set_worker_priority(uuid, &*pool_name, 10, Some(1), 5).unwrap());
+        assert_eq!(
+            pool.worker_priority(),
+            WorkerPriority {
+                niceness: 10,
+                io_class: IoClass::Idle,
+                io_level: 0,
+            }
+        );
+        assert!(pool.set_worker_priority(uuid, &*pool_name, 10, Some(2), 3).is_err());
+        assert!(pool.set_worker_priority(uuid, &*pool_name, 20, None, 3).is_err());
+        assert!(pool.set_worker_priority(uuid, &*pool_name, 0, None, 4).unwrap());
+        assert_eq!(pool.worker_priority(), WorkerPriority::default());
+    }
+
+    #[test]
+    /// Applying a plan to add devices adds exactly the planned devices,
+    /// and a plan can be applied only once.
+    fn plan_add_datadevs() {
+        let mut engine = SimEngine::default();
+        let uuid = engine
+            .create_pool("pool_name", &[Path::new("/s/a")], None, None, None, None)
+            .unwrap();
+        let (pool_name, pool) = engine.get_mut_pool(uuid).unwrap();
+        let devices = [Path::new("/s/a"), Path::new("/s/b"), Path::new("/s/c")];
+        let (token, plan) = pool.plan_add_datadevs(uuid, &devices).unwrap();
+        assert_eq!(plan.layout.len(), 3);
+        assert_eq!(plan.new_devnodes().len(), 2);
+        assert_eq!(
+            plan.usable_capacity,
+            plan.layout.iter().map(|dev| dev.usable).sum::<Sectors>()
+        );
+
+        assert_eq!(
+            pool.apply_add_datadevs_plan(uuid, &*pool_name, token)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(
+            match pool.apply_add_datadevs_plan(uuid, &*pool_name, token) {
+                Err(ref err) => err.code() == ErrorEnum::NotFound,
+                _ => false,
+            }
+        );
+    }
+
+    #[test]
+    /// The pool's size is the sum of the sizes of its devices, and the
+    /// space in use grows as the simulated clock is advanced, until the
+    /// filesystems are full.
+    fn space_accounting() {
+        let mut engine = SimEngine::default();
+        let device_size = Bytes(4 * IEC::Gi).sectors();
+        let fs_size = Bytes(IEC::Gi).sectors();
+        engine
+            .configure_simulator_capacities(
+                Some(device_size),
+                Some(fs_size),
+                Some(Bytes(0)),
+                Some(Bytes(IEC::Mi)),
+            )
+            .unwrap();
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(
+                pool_name,
+                &[Path::new("/s/a"), Path::new("/s/b")],
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        {
+            let pool = engine.get_mut_pool(uuid).unwrap().1;
+            assert_eq!(pool.total_physical_size(), 2u64 * device_size);
+            pool.create_filesystems(uuid, pool_name, &[("fs_name", None, false)], None)
+                .unwrap();
+            assert_eq!(pool.total_physical_used().unwrap(), Sectors(0));
+            assert_eq!(pool.days_until_full(), Some(0));
+        }
+
+        engine.advance_simulator_clock(IEC::Ki / 2).unwrap();
+        {
+            let pool = engine.get_mut_pool(uuid).unwrap().1;
+            assert_eq!(
+                pool.total_physical_used().unwrap(),
+                Bytes(IEC::Gi / 2).sectors()
+            );
+        }
+
+        engine.advance_simulator_clock(IEC::Ki).unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        assert_eq!(pool.total_physical_used().unwrap(), fs_size);
+        assert_eq!(pool.days_until_full(), None);
+    }
+
+    #[test]
+    /// Replacing a blockdev leaves the new device in its place, and a
+    /// blockdev can be replaced neither by itself nor if it is not in the
+    /// pool.
+    fn replace_blockdev() {
+        let mut engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = engine
+            .create_pool(
+                pool_name,
+                &[Path::new("/s/a"), Path::new("/s/b")],
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let pool = engine.get_mut_pool(uuid).unwrap().1;
+        let old = pool.blockdevs()
+            .into_iter()
+            .find(|&(_, bd)| bd.devnode() == Path::new("/s/a"))
+            .unwrap()
+            .0;
+
+        assert!(pool.replace_blockdev(uuid, pool_name, old, Path::new("/s/a"), None)
+            .is_err());
+        assert!(
+            pool.replace_blockdev(uuid, pool_name, DevUuid::new_v4(), Path::new("/s/c"), None)
+                .is_err()
+        );
+
+        let new = pool.replace_blockdev(uuid, pool_name, old, Path::new("/s/c"), None)
+            .unwrap();
+        assert!(pool.get_blockdev(old).is_none());
+        assert_eq!(
+            pool.get_blockdev(new).unwrap().1.devnode(),
+            Path::new("/s/c")
+        );
+        assert_eq!(pool.blockdevs().len(), 2);
+    }
 }
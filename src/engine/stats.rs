@@ -0,0 +1,208 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Cumulative counts of things the daemon has done, for monitoring. So that
+// rates computed from the counts are not reset by a restart, the daemon may
+// have the counts persisted to a file, from which they are loaded when it
+// starts again. Until then, they are kept only in memory.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_json;
+
+use stratis::StratisResult;
+
+/// The file to which the daemon persists the counts.
+pub const STATS_PATH: &str = "/var/lib/stratisd/stats.json";
+
+/// A thing that is counted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Counter {
+    /// Metadata saved to a pool's MDAs.
+    MetadataSaves,
+    /// Repairs made, of a pool or of DM devices.
+    Repairs,
+    /// Errors returned to clients.
+    Errors,
+    /// Jobs finished.
+    JobsCompleted,
+}
+
+impl Counter {
+    pub const ALL: &'static [Counter] = &[
+        Counter::MetadataSaves,
+        Counter::Repairs,
+        Counter::Errors,
+        Counter::JobsCompleted,
+    ];
+
+    /// The name of the counter, as persisted and reported.
+    pub fn name(self) -> &'static str {
+        match self {
+            Counter::MetadataSaves => "metadata_saves",
+            Counter::Repairs => "repairs",
+            Counter::Errors => "errors",
+            Counter::JobsCompleted => "jobs_completed",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Statistics {
+    counts: BTreeMap<String, u64>,
+    /// The file to which the counts are persisted, if any.
+    path: Option<PathBuf>,
+}
+
+impl Statistics {
+    fn add(&mut self, counter: Counter, n: u64) {
+        *self.counts.entry(counter.name().to_owned()).or_insert(0) += n;
+    }
+
+    /// Every counter, with its count.
+    fn counts(&self) -> Vec<(&'static str, u64)> {
+        Counter::ALL
+            .iter()
+            .map(|counter| {
+                (
+                    counter.name(),
+                    self.counts.get(counter.name()).cloned().unwrap_or(0),
+                )
+            })
+            .collect()
+    }
+
+    /// Persist the counts to the file at path from now on, adding the counts
+    /// already persisted there to those counted so far.
+    fn persist(&mut self, path: &Path) -> StratisResult<()> {
+        for (name, count) in load(path)? {
+            *self.counts.entry(name).or_insert(0) += count;
+        }
+        self.path = Some(path.to_owned());
+        self.save()
+    }
+
+    /// Write the counts to the file, if there is one. The counts are written
+    /// to a temporary file first, so that they are never found half-written.
+    fn save(&self) -> StratisResult<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut f = File::create(&tmp_path)?;
+            f.write_all(serde_json::to_string(&self.counts)?.as_bytes())?;
+        }
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Load the counts persisted to path, if the file exists. Counts for
+/// counters not known to this version are kept, so that they are not lost
+/// if an earlier version is run.
+fn load(path: &Path) -> StratisResult<BTreeMap<String, u64>> {
+    let mut contents = String::new();
+    match File::open(path) {
+        Ok(mut f) => f.read_to_string(&mut contents)?,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(serde_json::from_str(&contents)?)
+}
+
+lazy_static! {
+    static ref STATISTICS: Mutex<Statistics> = Mutex::new(Statistics::default());
+}
+
+/// From now on, persist the counts to the file at path, adding the counts
+/// already persisted there to those counted so far.
+pub fn persist_statistics(path: &Path) -> StratisResult<()> {
+    STATISTICS
+        .lock()
+        .expect("the statistics are never left inconsistent")
+        .persist(path)
+}
+
+/// Add n to the count of counter. A failure to persist the counts is
+/// logged, but otherwise ignored.
+pub fn count_by(counter: Counter, n: u64) {
+    if n == 0 {
+        return;
+    }
+    let mut stats = STATISTICS
+        .lock()
+        .expect("the statistics are never left inconsistent");
+    stats.add(counter, n);
+    if let Err(err) = stats.save() {
+        warn!("unable to persist statistics, reason: {}", err);
+    }
+}
+
+/// Add one to the count of counter.
+pub fn count(counter: Counter) {
+    count_by(counter, 1)
+}
+
+/// Every counter, with its count.
+pub fn statistics() -> Vec<(&'static str, u64)> {
+    STATISTICS
+        .lock()
+        .expect("the statistics are never left inconsistent")
+        .counts()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    /// Verify that counts persisted to a file are loaded and added to, that
+    /// counts not yet persisted are kept, and that counts of counters not
+    /// known are preserved.
+    fn test_persist() {
+        let dir = env::temp_dir().join(format!("stratis_test_stats_{}", Uuid::new_v4().simple()));
+        let path = dir.join("stats.json");
+        fs::create_dir_all(&dir).unwrap();
+        File::create(&path)
+            .unwrap()
+            .write_all(b"{\"repairs\": 2, \"from_the_future\": 7}")
+            .unwrap();
+
+        let mut stats = Statistics::default();
+        stats.add(Counter::Repairs, 1);
+        stats.add(Counter::Errors, 3);
+        stats.persist(&path).unwrap();
+        stats.add(Counter::MetadataSaves, 1);
+        stats.save().unwrap();
+
+        assert_eq!(
+            stats.counts(),
+            vec![
+                ("metadata_saves", 1),
+                ("repairs", 3),
+                ("errors", 3),
+                ("jobs_completed", 0),
+            ]
+        );
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.get("repairs"), Some(&3));
+        assert_eq!(loaded.get("from_the_future"), Some(&7));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(load(&path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Allocators, which decide from which blockdevs the space for a request is
+// taken. Each implements one of the allocation strategies that may be
+// chosen for a pool. The BlockDevMgr takes the space as its allocator
+// directs, so a new strategy needs no change to the backstore.
+
+use std::cmp;
+use std::fmt::Debug;
+
+use devicemapper::Sectors;
+
+use super::super::super::types::AllocationStrategy;
+
+/// The space available on a blockdev, and what is known of the blockdev
+/// that may bear on where space is to be taken.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DevSpace {
+    pub available: Sectors,
+    pub rotational: Option<bool>,
+}
+
+/// Decides from which blockdevs the space for a request is taken.
+pub trait Allocator: Debug {
+    /// Divide a request for needed sectors among the blockdevs described
+    /// by devs. Return the amount to take from each blockdev, identified by
+    /// its index in devs, in the order in which the amounts are to be
+    /// taken. No blockdev appears more than once, and no amount exceeds
+    /// the space available on its blockdev.
+    /// Precondition: needed <= the sum of the space available on devs.
+    /// Postcondition: the sum of the amounts == needed.
+    fn allocate(&mut self, devs: &[DevSpace], needed: Sectors) -> Vec<(usize, Sectors)>;
+}
+
+/// Take as much as is needed from each blockdev in order, skipping those
+/// with no space available.
+fn take_in_order(devs: &[DevSpace], order: &[usize], needed: Sectors) -> Vec<(usize, Sectors)> {
+    let mut remaining = needed;
+    let mut amounts = Vec::new();
+    for &index in order {
+        if remaining == Sectors(0) {
+            break;
+        }
+        let amount = cmp::min(devs[index].available, remaining);
+        if amount != Sectors(0) {
+            amounts.push((index, amount));
+            remaining = remaining - amount;
+        }
+    }
+    assert_eq!(remaining, Sectors(0));
+    amounts
+}
+
+/// Fill each blockdev in turn.
+#[derive(Debug)]
+struct FillFirst;
+
+impl Allocator for FillFirst {
+    fn allocate(&mut self, devs: &[DevSpace], needed: Sectors) -> Vec<(usize, Sectors)> {
+        let order = (0..devs.len()).collect::<Vec<_>>();
+        take_in_order(devs, &order, needed)
+    }
+}
+
+/// Begin each request on the blockdev after the one on which the previous
+/// request began, wrapping around to the first.
+#[derive(Debug)]
+struct RoundRobin {
+    next: usize,
+}
+
+impl Allocator for RoundRobin {
+    fn allocate(&mut self, devs: &[DevSpace], needed: Sectors) -> Vec<(usize, Sectors)> {
+        if devs.is_empty() {
+            return take_in_order(devs, &[], needed);
+        }
+        let start = self.next % devs.len();
+        self.next = start + 1;
+        let order = (start..devs.len()).chain(0..start).collect::<Vec<_>>();
+        take_in_order(devs, &order, needed)
+    }
+}
+
+/// Fill the blockdevs that are not rotational first, then those that may
+/// or may not be, then those that are, each in turn.
+#[derive(Debug)]
+struct SsdFirst;
+
+impl Allocator for SsdFirst {
+    fn allocate(&mut self, devs: &[DevSpace], needed: Sectors) -> Vec<(usize, Sectors)> {
+        let mut order = (0..devs.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| match devs[index].rotational {
+            Some(false) => 0,
+            None => 1,
+            Some(true) => 2,
+        });
+        take_in_order(devs, &order, needed)
+    }
+}
+
+/// The allocator that implements the given strategy.
+pub fn allocator(strategy: AllocationStrategy) -> Box<Allocator> {
+    match strategy {
+        AllocationStrategy::FillFirst => Box::new(FillFirst),
+        AllocationStrategy::RoundRobin => Box::new(RoundRobin { next: 0 }),
+        AllocationStrategy::SsdFirst => Box::new(SsdFirst),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn devs(spec: &[(u64, Option<bool>)]) -> Vec<DevSpace> {
+        spec.iter()
+            .map(|&(available, rotational)| DevSpace {
+                available: Sectors(available),
+                rotational,
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Verify that fill-first takes space from each blockdev in turn, and
+    /// skips those that are full.
+    fn test_fill_first() {
+        let devs = devs(&[(0, None), (10, None), (10, None)]);
+        let mut allocator = allocator(AllocationStrategy::FillFirst);
+        assert_eq!(
+            allocator.allocate(&devs, Sectors(15)),
+            vec![(1, Sectors(10)), (2, Sectors(5))]
+        );
+        assert_eq!(allocator.allocate(&devs, Sectors(5)), vec![(1, Sectors(5))]);
+        assert_eq!(allocator.allocate(&devs, Sectors(0)), vec![]);
+    }
+
+    #[test]
+    /// Verify that round-robin begins each request on the next blockdev,
+    /// wrapping around, and moves on if a blockdev has too little space.
+    fn test_round_robin() {
+        let devs = devs(&[(10, None), (2, None), (10, None)]);
+        let mut allocator = allocator(AllocationStrategy::RoundRobin);
+        assert_eq!(allocator.allocate(&devs, Sectors(4)), vec![(0, Sectors(4))]);
+        assert_eq!(
+            allocator.allocate(&devs, Sectors(4)),
+            vec![(1, Sectors(2)), (2, Sectors(2))]
+        );
+        assert_eq!(allocator.allocate(&devs, Sectors(4)), vec![(2, Sectors(4))]);
+        assert_eq!(
+            allocator.allocate(&devs, Sectors(14)),
+            vec![(0, Sectors(10)), (1, Sectors(2)), (2, Sectors(2))]
+        );
+        assert_eq!(allocator.allocate(&[], Sectors(0)), vec![]);
+    }
+
+    #[test]
+    /// Verify that ssd-first fills blockdevs that are not rotational first,
+    /// and those known to be rotational last, otherwise in order.
+    fn test_ssd_first() {
+        let devs = devs(&[(10, Some(true)), (10, None), (10, Some(false)), (10, Some(false))]);
+        let mut allocator = allocator(AllocationStrategy::SsdFirst);
+        assert_eq!(
+            allocator.allocate(&devs, Sectors(25)),
+            vec![(2, Sectors(10)), (3, Sectors(10)), (1, Sectors(5))]
+        );
+        assert_eq!(
+            allocator.allocate(&devs, Sectors(40)),
+            vec![
+                (2, Sectors(10)),
+                (3, Sectors(10)),
+                (1, Sectors(10)),
+                (0, Sectors(10)),
+            ]
+        );
+    }
+}
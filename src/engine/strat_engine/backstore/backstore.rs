@@ -6,67 +6,230 @@
 
 use std::cmp;
 use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
+use byteorder::{ByteOrder, LittleEndian};
 use chrono::{DateTime, Utc};
 
-use devicemapper::{CacheDev, Device, DmDevice, LinearDev, Sectors};
+use devicemapper::{
+    device_exists, devnode_to_devno, CacheDev, CacheDevStatus, DevId, Device, DmDevice, DmFlags,
+    DmName, DmOptions, LinearDev, LinearDevTargetParams, LinearTargetParams, Sectors, TargetLine,
+    IEC, SECTOR_SIZE,
+};
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
 
-use super::super::super::types::{BlockDevTier, DevUuid, PoolUuid};
+use super::super::super::types::{
+    AddDataDevsPlan, AllocationStrategy, BlockDevTier, CacheType, CacheUsage, DevUuid, PoolUuid,
+    Redundancy, SignaturePolicy, WorkerPriority,
+};
 
-use super::super::device::wipe_sectors;
+#[cfg(test)]
+use super::super::crypt::close_crypt;
+use super::super::crypt::{destroy_crypt, initialize_crypt, Cryptsetup};
+use super::super::device::{copy_sectors, wipe_sectors};
 use super::super::dm::get_dm;
-use super::super::names::{format_backstore_ids, CacheRole};
+use super::super::names::{format_backstore_ids, format_flex_ids, CacheRole, FlexRole};
 use super::super::serde_structs::{BackstoreSave, CapSave, Recordable};
+use super::super::targets::{cache_target, require_target};
+use super::super::verify::verify_mapping;
+use super::super::watchdog::{resume_dm, suspend_dm, watch_dm};
 
 use super::blockdev::StratBlockDev;
-use super::blockdevmgr::{map_to_dm, BlockDevMgr};
+use super::blockdevmgr::{map_to_dm, prepare_devices, BlkDevSegment, BlockDevMgr};
 use super::cache_tier::CacheTier;
+use super::cleanup::wipe_stray_device;
 use super::data_tier::DataTier;
-use super::metadata::MIN_MDA_SECTORS;
+use super::metadata::{MetadataLayout, MIN_MDA_SECTORS};
+use super::raid::RaidLayer;
 use super::setup::get_blockdevs;
+use super::writecache::{WriteCacheDev, WRITECACHE_BLOCK_SIZE};
 
 /// Use a cache block size that the kernel docs indicate is the largest
 /// typical size.
 const CACHE_BLOCK_SIZE: Sectors = Sectors(2048); // 1024 KiB
 
+/// The magic number of a dm-cache metadata superblock.
+const CACHE_SUPERBLOCK_MAGIC: u64 = 0o6_142_003;
+
+/// The feature arguments of a dm-cache table that select its mode.
+const CACHE_MODES: &[&str] = &["writeback", "writethrough", "passthrough"];
+
+/// The most data moved at once off the blockdevs that are being removed
+/// from the data tier. I/O to the pool waits while each part is moved.
+const MOVE_CHUNK_SIZE: Sectors = Sectors(2 * IEC::Mi); // 1 GiB
+
+/// Check the dm-cache superblock at the start of the meta sub-device
+/// against the cache that is about to be set up on it, with a cache
+/// sub-device of cache_size. Return a description of the first discrepancy
+/// found, if any. A superblock that is zeroed has yet to be written by the
+/// kernel, and is accepted.
+fn check_cache_superblock<F: Read>(
+    f: &mut F,
+    cache_size: Sectors,
+) -> StratisResult<Option<String>> {
+    let mut buf = [0u8; SECTOR_SIZE];
+    f.read_exact(&mut buf)?;
+
+    if buf.iter().all(|b| *b == 0) {
+        return Ok(None);
+    }
+
+    let magic = LittleEndian::read_u64(&buf[32..40]);
+    if magic != CACHE_SUPERBLOCK_MAGIC {
+        return Ok(Some(format!("superblock has bad magic number {}", magic)));
+    }
+
+    let block_size = Sectors(u64::from(LittleEndian::read_u32(&buf[244..248])));
+    if block_size != CACHE_BLOCK_SIZE {
+        return Ok(Some(format!(
+            "superblock records cache block size {}, expected {}",
+            block_size, CACHE_BLOCK_SIZE
+        )));
+    }
+
+    let cache_blocks = u64::from(LittleEndian::read_u32(&buf[252..256]));
+    if cache_blocks > *cache_size / *CACHE_BLOCK_SIZE {
+        return Ok(Some(format!(
+            "superblock records {} cache blocks, more than the cache sub-device holds",
+            cache_blocks
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Reload the table of the cache device so that it is in the given mode,
+/// one of CACHE_MODES. In passthrough mode every read and write goes to the
+/// origin, and the cache is neither consulted nor written. In writethrough
+/// mode every write goes to the origin as well as to the cache, so the
+/// origin is never behind the cache.
+fn set_cache_mode(cache: &CacheDev, mode: &str) -> StratisResult<()> {
+    let name = cache.name().to_owned();
+    let id = DevId::Name(&name);
+    let (_, table) = get_dm().table_status(
+        &id,
+        &DmOptions::new().set_flags(DmFlags::DM_STATUS_TABLE),
+    )?;
+
+    let mut reloaded = Vec::new();
+    for (start, length, target_type, params) in table {
+        // <meta> <cache> <origin> <block size> <#features> <feature>* <policy> ...
+        let words = params.split_whitespace().collect::<Vec<_>>();
+        let num_features = match words.get(4).and_then(|w| w.parse::<usize>().ok()) {
+            Some(num) if words.len() > 5 + num => num,
+            _ => {
+                let err_msg = format!("unexpected table for cache device {}: {}", &*name, params);
+                return Err(StratisError::Engine(ErrorEnum::Error, err_msg));
+            }
+        };
+        let features = words[5..5 + num_features]
+            .iter()
+            .cloned()
+            .filter(|word| !CACHE_MODES.iter().any(|mode| mode == word))
+            .chain(Some(mode))
+            .collect::<Vec<_>>();
+        let count = features.len().to_string();
+        let params = words[..4]
+            .iter()
+            .cloned()
+            .chain(Some(count.as_str()))
+            .chain(features)
+            .chain(words[5 + num_features..].iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        reloaded.push((start, length, target_type, params));
+    }
+
+    watch_dm(&name, "reload", || {
+        get_dm().table_load(&id, &reloaded)
+    })?;
+    resume_dm(&name)?;
+    Ok(())
+}
+
+/// The device, start, and length of each of the segments.
+fn segment_records(segments: &[BlkDevSegment]) -> Vec<(Device, Sectors, Sectors)> {
+    segments
+        .iter()
+        .map(|bseg| {
+            let segment = bseg.to_segment();
+            (segment.device, segment.start, segment.length)
+        })
+        .collect()
+}
+
 /// Make a DM cache device. If the cache device is being made new,
-/// take extra steps to make it clean.
+/// take extra steps to make it clean. Otherwise, check the dm-cache
+/// superblock first; if it does not match the cache tier, the cache device
+/// is set up in passthrough mode, so that the cache is not used. A volatile
+/// cache is set up in writethrough mode. The meta and cache sub-devices are
+/// each a linear concatenation of their segments, which may lie on any
+/// number of the cache tier's blockdevs. If the cache device can not be
+/// made, the sub-devices are removed again; the origin is left in place.
 fn make_cache(
     pool_uuid: PoolUuid,
     cache_tier: &CacheTier,
     origin: LinearDev,
     new: bool,
 ) -> StratisResult<CacheDev> {
-    let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::MetaSub);
-    let meta = LinearDev::setup(
+    let (meta_name, meta_uuid) = format_backstore_ids(pool_uuid, CacheRole::MetaSub);
+    let mut meta = LinearDev::setup(
         get_dm(),
-        &dm_name,
-        Some(&dm_uuid),
+        &meta_name,
+        Some(&meta_uuid),
         map_to_dm(&cache_tier.meta_segments),
     )?;
 
-    if new {
+    let mismatch = if new {
         // See comment in ThinPool::new() method
-        wipe_sectors(
+        if let Err(err) = wipe_sectors(
             &meta.devnode(),
             Sectors(0),
             cmp::min(Sectors(8), meta.size()),
-        )?;
-    }
+        ) {
+            meta.teardown(get_dm())?;
+            return Err(err);
+        }
+        None
+    } else {
+        let cache_size = cache_tier
+            .cache_segments
+            .iter()
+            .map(|x| x.segment.length)
+            .sum();
+        match OpenOptions::new()
+            .read(true)
+            .open(meta.devnode())
+            .map_err(StratisError::from)
+            .and_then(|mut f| check_cache_superblock(&mut f, cache_size))
+        {
+            Ok(mismatch) => mismatch,
+            Err(err) => {
+                meta.teardown(get_dm())?;
+                return Err(err);
+            }
+        }
+    };
 
-    let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::CacheSub);
-    let cache = LinearDev::setup(
+    let (cache_name, cache_uuid) = format_backstore_ids(pool_uuid, CacheRole::CacheSub);
+    let cache = match LinearDev::setup(
         get_dm(),
-        &dm_name,
-        Some(&dm_uuid),
+        &cache_name,
+        Some(&cache_uuid),
         map_to_dm(&cache_tier.cache_segments),
-    )?;
+    ) {
+        Ok(cache) => cache,
+        Err(err) => {
+            meta.teardown(get_dm())?;
+            return Err(err.into());
+        }
+    };
 
     let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::Cache);
-    Ok(CacheDev::setup(
+    let cache = CacheDev::setup(
         get_dm(),
         &dm_name,
         Some(&dm_uuid),
@@ -74,25 +237,307 @@ fn make_cache(
         cache,
         origin,
         CACHE_BLOCK_SIZE,
+    ).or_else(|err| {
+        for name in &[&cache_name, &meta_name] {
+            if device_exists(get_dm(), name)? {
+                get_dm().device_remove(&DevId::Name(name), &DmOptions::new())?;
+            }
+        }
+        Err(StratisError::from(err))
+    })?;
+
+    if let Some(reason) = mismatch {
+        warn!(
+            "dm-cache metadata of pool {} does not match its cache: {}; using passthrough mode",
+            pool_uuid.simple(),
+            reason
+        );
+        set_cache_mode(&cache, "passthrough")?;
+    } else if cache_tier.volatile {
+        set_cache_mode(&cache, "writethrough")?;
+    }
+
+    Ok(cache)
+}
+
+/// Make a DM writecache device. If the writecache device is being made
+/// new, the first block of the cache sub-device is zeroed, so that
+/// dm-writecache formats the cache afresh. If the writecache device can not
+/// be made, the cache sub-device is removed again; the origin is left in
+/// place.
+fn make_writecache(
+    pool_uuid: PoolUuid,
+    cache_tier: &CacheTier,
+    origin: LinearDev,
+    new: bool,
+) -> StratisResult<WriteCacheDev> {
+    let (cache_name, cache_uuid) = format_backstore_ids(pool_uuid, CacheRole::CacheSub);
+    let mut cache = LinearDev::setup(
+        get_dm(),
+        &cache_name,
+        Some(&cache_uuid),
+        map_to_dm(&cache_tier.cache_segments),
+    )?;
+
+    if new {
+        if let Err(err) = wipe_sectors(
+            &cache.devnode(),
+            Sectors(0),
+            cmp::min(WRITECACHE_BLOCK_SIZE, cache.size()),
+        ) {
+            cache.teardown(get_dm())?;
+            return Err(err);
+        }
+    }
+
+    let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::Cache);
+    WriteCacheDev::setup(&dm_name, &dm_uuid, cache, origin).or_else(|err| {
+        if device_exists(get_dm(), &cache_name)? {
+            get_dm().device_remove(&DevId::Name(&cache_name), &DmOptions::new())?;
+        }
+        Err(err)
+    })
+}
+
+/// The DM device of a cache tier, made with the tier's DM target.
+#[derive(Debug)]
+enum CacheDevice {
+    Cache(CacheDev),
+    WriteCache(WriteCacheDev),
+}
+
+impl CacheDevice {
+    /// Make the DM device of the cache tier, on top of origin.
+    fn setup(
+        pool_uuid: PoolUuid,
+        cache_tier: &CacheTier,
+        origin: LinearDev,
+        new: bool,
+    ) -> StratisResult<CacheDevice> {
+        require_target(cache_target(cache_tier.cache_type))?;
+        match cache_tier.cache_type {
+            CacheType::Cache => {
+                make_cache(pool_uuid, cache_tier, origin, new).map(CacheDevice::Cache)
+            }
+            CacheType::WriteCache => {
+                make_writecache(pool_uuid, cache_tier, origin, new).map(CacheDevice::WriteCache)
+            }
+        }
+    }
+
+    fn name(&self) -> &DmName {
+        match *self {
+            CacheDevice::Cache(ref cache) => cache.name(),
+            CacheDevice::WriteCache(ref cache) => cache.name(),
+        }
+    }
+
+    fn device(&self) -> Device {
+        match *self {
+            CacheDevice::Cache(ref cache) => cache.device(),
+            CacheDevice::WriteCache(ref cache) => cache.device(),
+        }
+    }
+
+    fn size(&self) -> Sectors {
+        match *self {
+            CacheDevice::Cache(ref cache) => cache.size(),
+            CacheDevice::WriteCache(ref cache) => cache.size(),
+        }
+    }
+
+    /// Load the origin sub-device with the given table, and resume the
+    /// device so that it takes on the origin's new size.
+    fn set_origin_table(
+        &mut self,
+        table: Vec<TargetLine<LinearDevTargetParams>>,
+    ) -> StratisResult<()> {
+        let name = self.name().to_owned();
+        match *self {
+            CacheDevice::Cache(ref mut cache) => {
+                watch_dm(&name, "reload", || cache.set_origin_table(get_dm(), table))?;
+            }
+            CacheDevice::WriteCache(ref mut cache) => {
+                watch_dm(&name, "reload", || cache.set_origin_table(table))?;
+            }
+        }
+        resume_dm(&name)
+    }
+
+    /// Suspend the device, once the I/O in flight to it has completed, so
+    /// that it neither takes I/O from above nor moves blocks to or from
+    /// its origin.
+    fn suspend(&mut self) -> StratisResult<()> {
+        let name = self.name().to_owned();
+        suspend_dm(&name, true)
+    }
+
+    /// The usage of the cache, from the status of the device.
+    fn usage(&self) -> StratisResult<CacheUsage> {
+        match *self {
+            CacheDevice::Cache(ref cache) => match cache.status(get_dm())? {
+                CacheDevStatus::Working(status) => {
+                    let block_size = status.usage.cache_block_size;
+                    Ok(CacheUsage {
+                        used: block_size * *status.usage.used_cache,
+                        dirty: block_size * status.performance.dirty,
+                        total: block_size * *status.usage.total_cache,
+                    })
+                }
+                CacheDevStatus::Fail => Err(StratisError::Engine(
+                    ErrorEnum::Error,
+                    format!("cache device {} has failed", cache.name()),
+                )),
+            },
+            CacheDevice::WriteCache(ref cache) => Ok(cache.status()?.usage()),
+        }
+    }
+
+    /// Remove the device and its sub-devices, all but the origin of which
+    /// belong to the cache tier.
+    fn teardown(&mut self) -> StratisResult<()> {
+        match *self {
+            CacheDevice::Cache(ref mut cache) => Ok(cache.teardown(get_dm())?),
+            CacheDevice::WriteCache(ref mut cache) => cache.teardown(),
+        }
+    }
+}
+
+/// The table of the origin sub-device, which maps all of the raid device if
+/// the pool has redundancy, otherwise the segments allocated in the data
+/// tier.
+fn origin_table(
+    raid: Option<&RaidLayer>,
+    data_tier: &DataTier,
+) -> Vec<TargetLine<LinearDevTargetParams>> {
+    match raid {
+        Some(raid) => cap_table(raid.device(), raid.size()),
+        None => map_to_dm(&data_tier.segments),
+    }
+}
+
+/// The table of the cap device, which maps all of the device at the top of
+/// the tiers.
+fn cap_table(top: Device, size: Sectors) -> Vec<TargetLine<LinearDevTargetParams>> {
+    vec![TargetLine::new(
+        Sectors(0),
+        size,
+        LinearDevTargetParams::Linear(LinearTargetParams::new(top, Sectors(0))),
+    )]
+}
+
+/// Make the cap device, mapping all of the device at the top of the tiers.
+fn make_cap(pool_uuid: PoolUuid, top: Device, size: Sectors) -> StratisResult<LinearDev> {
+    let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::Cap);
+    Ok(LinearDev::setup(
+        get_dm(),
+        &dm_name,
+        Some(&dm_uuid),
+        cap_table(top, size),
     )?)
 }
 
+/// Versions of stratisd that predate the cap device built the flex devices
+/// of a pool directly on the device at the top of the tiers. If such a pool
+/// is still set up when the cap device is first made, point every linear
+/// target of its flex devices that refers to the old top device at the cap
+/// device instead, so that the tables agree with those the flex devices
+/// would now be set up with.
+fn migrate_flex_devs(pool_uuid: PoolUuid, old: Device, cap: Device) -> StratisResult<()> {
+    let old = old.to_string();
+    for role in &[
+        FlexRole::MetadataVolume,
+        FlexRole::ThinData,
+        FlexRole::ThinMeta,
+    ] {
+        let (dm_name, _) = format_flex_ids(pool_uuid, *role);
+        if !device_exists(get_dm(), &dm_name)? {
+            continue;
+        }
+
+        let id = DevId::Name(&dm_name);
+        let (_, table) = get_dm().table_status(
+            &id,
+            &DmOptions::new().set_flags(DmFlags::DM_STATUS_TABLE),
+        )?;
+        if !table
+            .iter()
+            .any(|&(_, _, _, ref params)| params.split_whitespace().next() == Some(old.as_str()))
+        {
+            continue;
+        }
+
+        let table = table
+            .into_iter()
+            .map(|(start, length, target_type, params)| {
+                let params = {
+                    let mut words = params.split_whitespace();
+                    match words.next() {
+                        Some(device) if device == old => Some(cap.to_string())
+                            .into_iter()
+                            .chain(words.map(|word| word.to_owned()))
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                        _ => params.clone(),
+                    }
+                };
+                (start, length, target_type, params)
+            })
+            .collect::<Vec<_>>();
+
+        info!(
+            "Moving DM device {} onto the cap device of pool {}",
+            &*dm_name,
+            pool_uuid.simple()
+        );
+        watch_dm(&dm_name, "reload", || {
+            get_dm().table_load(&id, &table)
+        })?;
+        resume_dm(&dm_name)?;
+    }
+    Ok(())
+}
+
+/// A part of the data of the data tier that has been copied to newly
+/// allocated space, while the device that maps the data tier is suspended,
+/// but that is still mapped where it was.
+#[derive(Debug)]
+pub struct DataMove {
+    /// The segments of the data tier as they were before the move.
+    previous: Vec<BlkDevSegment>,
+    /// The number of sectors moved.
+    pub length: Sectors,
+}
+
 /// This structure can allocate additional space to the upper layer, but it
 /// cannot accept returned space. When it is extended to be able to accept
 /// returned space the allocation algorithm will have to be revised.
 #[derive(Debug)]
 pub struct Backstore {
-    /// A cache DM Device.
-    cache: Option<CacheDev>,
+    /// A cache DM Device, made with either the cache or the writecache
+    /// target.
+    cache: Option<CacheDevice>,
+    /// The cap device, a linear DM device that maps all of the device at the
+    /// top of the tiers: the cache device if there is one, otherwise the
+    /// linear device. Its name never changes as tiers are added, so the
+    /// layers above the backstore never need their tables rewritten.
+    cap: Option<LinearDev>,
     /// Coordinate handling of blockdevs that back the cache. Optional, since
     /// this structure can operate without a cache.
     cache_tier: Option<CacheTier>,
     /// Coordinates handling of the blockdevs that form the base.
     data_tier: DataTier,
+    /// The raid device made from the blockdevs of the data tier, if the pool
+    /// has redundancy. All of it is mapped by the origin sub-device as soon
+    /// as any space is allocated from the backstore.
+    raid: Option<RaidLayer>,
     /// A linear DM device.
     linear: Option<LinearDev>,
     /// Index for managing allocation of cap device
     next: Sectors,
+    /// Whether the blockdevs of the data tier are dm-crypt devices, each
+    /// opened on a LUKS2 device.
+    encrypted: bool,
 }
 
 impl Backstore {
@@ -108,6 +553,7 @@ impl Backstore {
     /// Postcondition:
     /// self.linear.is_some() XOR self.cache.is_some()
     /// self.cache.is_some() <=> self.cache_tier.is_some()
+    /// self.cap.is_some()
     pub fn setup(
         pool_uuid: PoolUuid,
         backstore_save: &BackstoreSave,
@@ -117,12 +563,20 @@ impl Backstore {
         let (datadevs, cachedevs) = get_blockdevs(pool_uuid, backstore_save, devnodes)?;
         let block_mgr = BlockDevMgr::new(datadevs, last_update_time);
         let data_tier = DataTier::setup(block_mgr, &backstore_save.data_tier)?;
+        let raid = match backstore_save.raid {
+            Some(ref raid_save) => Some(RaidLayer::setup(
+                pool_uuid,
+                &*data_tier.block_mgr.uuid_to_devno(),
+                raid_save,
+            )?),
+            None => None,
+        };
         let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
         let origin = LinearDev::setup(
             get_dm(),
             &dm_name,
             Some(&dm_uuid),
-            map_to_dm(&data_tier.segments),
+            origin_table(raid.as_ref(), &data_tier),
         )?;
 
         let (cache_tier, cache, origin) = if !cachedevs.is_empty() {
@@ -131,7 +585,7 @@ impl Backstore {
                 Some(ref cache_tier_save) => {
                     let cache_tier = CacheTier::setup(block_mgr, &cache_tier_save)?;
 
-                    let cache_device = make_cache(pool_uuid, &cache_tier, origin, false)?;
+                    let cache_device = CacheDevice::setup(pool_uuid, &cache_tier, origin, false)?;
                     (Some(cache_tier), Some(cache_device), None)
                 }
                 None => {
@@ -143,13 +597,30 @@ impl Backstore {
             (None, None, Some(origin))
         };
 
-        Ok(Backstore {
+        let (top, size) = cache
+            .as_ref()
+            .map(|d| (d.device(), d.size()))
+            .or_else(|| origin.as_ref().map(|d| (d.device(), d.size())))
+            .expect("origin.is_some() XOR cache.is_some()");
+        let (cap_name, _) = format_backstore_ids(pool_uuid, CacheRole::Cap);
+        let cap_existed = device_exists(get_dm(), &cap_name)?;
+        let cap = make_cap(pool_uuid, top, size)?;
+        if !cap_existed {
+            migrate_flex_devs(pool_uuid, top, cap.device())?;
+        }
+
+        let backstore = Backstore {
             data_tier,
+            raid,
             cache_tier,
             linear: origin,
             cache,
+            cap: Some(cap),
             next: backstore_save.cap.allocs[0].1,
-        })
+            encrypted: false,
+        };
+        backstore.verify_mappings(pool_uuid)?;
+        Ok(backstore)
     }
 
     /// Initialize a Backstore object, by initializing the specified devs.
@@ -157,26 +628,149 @@ impl Backstore {
     /// Immediately after initialization a backstore has no cap device, since
     /// no segments are allocated in the data tier.
     ///
+    /// All devices in the backstore are initialized with the given metadata
+    /// layout.
+    ///
+    /// The signature policy determines whether devices with foreign
+    /// signatures are initialized.
+    ///
     /// WARNING: metadata changing event
     pub fn initialize(
         pool_uuid: PoolUuid,
         paths: &[&Path],
         mda_size: Sectors,
+        layout: MetadataLayout,
+        signature_policy: SignaturePolicy,
     ) -> StratisResult<Backstore> {
-        let data_tier = DataTier::new(BlockDevMgr::initialize(pool_uuid, paths, mda_size)?);
+        let data_tier = DataTier::new(BlockDevMgr::initialize(
+            pool_uuid,
+            paths,
+            mda_size,
+            layout,
+            signature_policy,
+        )?);
 
         Ok(Backstore {
             data_tier,
+            raid: None,
             cache_tier: None,
             linear: None,
             cache: None,
+            cap: None,
             next: Sectors(0),
+            encrypted: false,
         })
     }
 
+    /// Initialize a Backstore object, as initialize() does, on dm-crypt
+    /// devices. Each device specified by paths is checked for admission to
+    /// the pool, formatted as a LUKS2 device opened by passphrase, and
+    /// opened, and a blockdev is initialized on the dm-crypt device.
+    /// If any step fails, the dm-crypt devices already opened are closed,
+    /// and the headers of their LUKS2 devices wiped.
+    pub fn initialize_encrypted(
+        pool_uuid: PoolUuid,
+        paths: &[&Path],
+        mda_size: Sectors,
+        layout: MetadataLayout,
+        signature_policy: SignaturePolicy,
+        passphrase: &str,
+    ) -> StratisResult<Backstore> {
+        let physical = prepare_devices(pool_uuid, paths, signature_policy)?;
+
+        let mut crypt_devnodes = Vec::new();
+        let mut result = Ok(());
+        for devnode in &physical {
+            match initialize_crypt(&Cryptsetup, pool_uuid, devnode, passphrase) {
+                Ok(crypt_devnode) => crypt_devnodes.push(crypt_devnode),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+
+        // The contents of a newly opened dm-crypt device are noise, in which
+        // any signature found is spurious.
+        let backstore = result.and_then(|_| {
+            let paths = crypt_devnodes.iter().map(|p| p.as_path()).collect::<Vec<_>>();
+            Backstore::initialize(pool_uuid, &paths, mda_size, layout, SignaturePolicy::Ignore)
+        });
+
+        match backstore {
+            Ok(mut backstore) => {
+                backstore.encrypted = true;
+                Ok(backstore)
+            }
+            Err(err) => {
+                for devnode in &crypt_devnodes {
+                    let _ = destroy_crypt(&Cryptsetup, devnode);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Make the RAID layer of a pool with the given redundancy, with every
+    /// blockdev of the data tier as a member. A pool without redundancy has
+    /// no RAID layer, and nothing is done.
+    ///
+    /// Precondition: no space has been allocated from the backstore.
+    ///
+    /// WARNING: metadata changing event
+    pub fn initialize_raid(
+        &mut self,
+        pool_uuid: PoolUuid,
+        redundancy: Redundancy,
+    ) -> StratisResult<()> {
+        assert!(self.cap.is_none() && self.raid.is_none());
+        if redundancy != Redundancy::NONE {
+            self.raid = Some(RaidLayer::initialize(
+                pool_uuid,
+                redundancy,
+                &mut self.data_tier.block_mgr,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// The redundancy of the pool, given by its RAID layer, if any.
+    pub fn redundancy(&self) -> Redundancy {
+        self.raid
+            .as_ref()
+            .map_or(Redundancy::NONE, |raid| raid.redundancy())
+    }
+
+    /// The error for a change to the data tier of a pool with a RAID layer,
+    /// whose members are fixed when the pool is made.
+    fn raid_unsupported(&self) -> Option<StratisError> {
+        self.raid.as_ref().map(|raid| {
+            StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("RaidDataDevsUnsupported")
+                    .param("redundancy", raid.redundancy()),
+            )
+        })
+    }
+
+    /// Whether the blockdevs of the data tier are dm-crypt devices.
+    pub fn encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Record whether the blockdevs of the data tier are dm-crypt devices,
+    /// as the pool's metadata says they are.
+    pub fn set_encrypted(&mut self, encrypted: bool) {
+        self.encrypted = encrypted
+    }
+
     /// Add cachedevs to the backstore.
     ///
-    /// If the cache tier does not already exist, create it.
+    /// If the cache tier does not already exist, create it, with a cache
+    /// made with the DM target given by cache_type, on volatile devices if
+    /// volatile is true. If it does exist, cache_type and volatile are
+    /// ignored; the cache of a writecache tier can not be extended, so it
+    /// is an error to add to it.
     /// If the addition of the cache devs would result in a cache with a
     /// cache sub-device size greater than 32 TiB return an error.
     /// FIXME: This restriction on the size of the cache sub-device is
@@ -184,49 +778,86 @@ impl Backstore {
     ///
     /// Precondition: Must be invoked only after some space has been allocated
     /// from the backstore. This ensures that there is certainly a cap device.
+    ///
+    /// If the cache tier is created, the cap device is made to map the new
+    /// cache device, so the device of the backstore does not change.
     // Precondition: self.linear.is_some() XOR self.cache.is_some()
     // Postcondition: self.cache.is_some() && self.linear.is_none()
     pub fn add_cachedevs(
         &mut self,
         pool_uuid: PoolUuid,
         paths: &[&Path],
+        signature_policy: SignaturePolicy,
+        cache_type: CacheType,
+        volatile: bool,
     ) -> StratisResult<Vec<DevUuid>> {
-        match self.cache_tier {
+        let uuids = match self.cache_tier {
             Some(ref mut cache_tier) => {
-                let mut cache_device = self.cache
-                    .as_mut()
-                    .expect("cache_tier.is_some() <=> self.cache.is_some()");
-                let (uuids, (cache_change, meta_change)) = cache_tier.add(pool_uuid, paths)?;
+                let (uuids, (cache_change, meta_change)) =
+                    cache_tier.add(pool_uuid, paths, signature_policy)?;
+                let cache_device = match self.cache {
+                    Some(CacheDevice::Cache(ref mut cache)) => cache,
+                    _ => panic!(
+                        "cache_tier.is_some() <=> self.cache.is_some(); only dm-cache grows"
+                    ),
+                };
+                let name = cache_device.name().to_owned();
 
                 if cache_change {
                     let table = map_to_dm(&cache_tier.cache_segments);
-                    cache_device.set_cache_table(get_dm(), table)?;
-                    cache_device.resume(get_dm())?;
+                    watch_dm(&name, "reload", || cache_device.set_cache_table(get_dm(), table))?;
+                    resume_dm(&name)?;
                 }
 
-                // NOTE: currently CacheTier::add() does not ever update the
-                // meta segments. That means that this code is dead. But,
-                // when CacheTier::add() is fixed, this code will become live.
+                // NOTE: CacheTier::add() never updates the meta segments,
+                // as the meta sub-device is made large enough for the
+                // largest cache sub-device permitted. This code is kept
+                // for when the size of the meta sub-device is managed.
                 if meta_change {
                     let table = map_to_dm(&cache_tier.meta_segments);
-                    cache_device.set_meta_table(get_dm(), table)?;
-                    cache_device.resume(get_dm())?;
+                    watch_dm(&name, "reload", || cache_device.set_meta_table(get_dm(), table))?;
+                    resume_dm(&name)?;
                 }
 
-                Ok(uuids)
+                uuids
             }
             None => {
-                let bdm = BlockDevMgr::initialize(pool_uuid, paths, MIN_MDA_SECTORS)?;
+                require_target(cache_target(cache_type))?;
 
-                let cache_tier = CacheTier::new(bdm)?;
+                let bdm = BlockDevMgr::initialize(
+                    pool_uuid,
+                    paths,
+                    MIN_MDA_SECTORS,
+                    self.data_tier.block_mgr.metadata_layout(),
+                    signature_policy,
+                )?;
+
+                let mut cache_tier = CacheTier::new(bdm, cache_type, volatile)?;
 
                 let linear = self.linear
                     .take()
                     .expect("some space has already been allocated from the backstore => (cache_tier.is_none() <=> self.linear.is_some())");
 
-                let cache = make_cache(pool_uuid, &cache_tier, linear, true)?;
+                let cache = match CacheDevice::setup(pool_uuid, &cache_tier, linear, true) {
+                    Ok(cache) => cache,
+                    Err(err) => {
+                        // The origin is still set up and unchanged; take
+                        // hold of it again, and give up the cache devices.
+                        let (dm_name, dm_uuid) =
+                            format_backstore_ids(pool_uuid, CacheRole::OriginSub);
+                        self.linear = Some(LinearDev::setup(
+                            get_dm(),
+                            &dm_name,
+                            Some(&dm_uuid),
+                            origin_table(self.raid.as_ref(), &self.data_tier),
+                        )?);
+                        cache_tier.destroy(false)?;
+                        return Err(err);
+                    }
+                };
 
                 self.cache = Some(cache);
+                self.reload_cap()?;
 
                 let uuids = cache_tier
                     .block_mgr
@@ -237,9 +868,12 @@ impl Backstore {
 
                 self.cache_tier = Some(cache_tier);
 
-                Ok(uuids)
+                uuids
             }
-        }
+        };
+
+        self.verify_mappings(pool_uuid)?;
+        Ok(uuids)
     }
 
     /// Add datadevs to the backstore. The data tier always exists if the
@@ -248,38 +882,417 @@ impl Backstore {
         &mut self,
         pool_uuid: PoolUuid,
         paths: &[&Path],
+        signature_policy: SignaturePolicy,
     ) -> StratisResult<Vec<DevUuid>> {
-        self.data_tier.add(pool_uuid, paths)
+        if let Some(err) = self.raid_unsupported() {
+            return Err(err);
+        }
+        self.data_tier.add(pool_uuid, paths, signature_policy)
+    }
+
+    /// Determine the effect of adding datadevs to the backstore, without
+    /// adding them.
+    pub fn plan_add_datadevs(
+        &self,
+        pool_uuid: PoolUuid,
+        paths: &[&Path],
+    ) -> StratisResult<AddDataDevsPlan> {
+        if let Some(err) = self.raid_unsupported() {
+            return Err(err);
+        }
+        self.data_tier.plan_add(pool_uuid, paths)
+    }
+
+    /// Check that the blockdevs specified by uuids may be removed from the
+    /// data tier: that each is a blockdev of the data tier that is present,
+    /// that some blockdev would remain, and that the other blockdevs have
+    /// room for the data on them. Return the number of sectors of data that
+    /// must be moved off them first.
+    pub fn check_remove_datadevs(&self, uuids: &[DevUuid]) -> StratisResult<Sectors> {
+        if let Some(err) = self.raid_unsupported() {
+            return Err(err);
+        }
+
+        let mut available = self.data_tier.block_mgr.avail_space();
+        for (index, &uuid) in uuids.iter().enumerate() {
+            let bd = self.present_datadev(uuid)?;
+            if !uuids[..index].contains(&uuid) {
+                available -= bd.available();
+            }
+        }
+
+        if self.data_tier
+            .blockdevs()
+            .iter()
+            .all(|&(uuid, _)| uuids.contains(&uuid))
+        {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("RemoveAllDataDevs"),
+            ));
+        }
+
+        let required = self.data_tier.allocated_on(uuids);
+        if required > available {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("SpaceUnavailable")
+                    .param("requested", *required)
+                    .param("available", *available),
+            ));
+        }
+        Ok(required)
+    }
+
+    /// The blockdev of the data tier with the given UUID. Return an error
+    /// if there is no such blockdev, or if it is missing.
+    fn present_datadev(&self, uuid: DevUuid) -> StratisResult<&StratBlockDev> {
+        match self.get_blockdev_by_uuid(uuid) {
+            Some((BlockDevTier::Data, bd)) => {
+                if bd.is_missing() {
+                    return Err(StratisError::Structured(
+                        ErrorEnum::Invalid,
+                        ErrorPayload::new("BlockDevMissing").param("uuid", uuid.simple()),
+                    ));
+                }
+                Ok(bd)
+            }
+            Some((BlockDevTier::Cache, _)) => Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("BlockDevNotData").param("uuid", uuid.simple()),
+            )),
+            None => Err(StratisError::Engine(
+                ErrorEnum::NotFound,
+                format!("no blockdev with UUID {} found", uuid.simple()),
+            )),
+        }
     }
 
-    /// Extend the cap device whether it is a cache or not. Create the DM
-    /// device if it does not already exist. Return an error if DM
-    /// operations fail. Use all segments currently allocated in the data tier.
+    /// Wipe the sigblock from the device at path, if it claims to belong to
+    /// the pool but is not one of its blockdevs, as a device may whose
+    /// initialization was cut short. A device that is no longer there is
+    /// left alone. Return true if the device was wiped.
+    pub fn wipe_stray_device(&self, pool_uuid: PoolUuid, path: &Path) -> StratisResult<bool> {
+        if !path.exists() {
+            return Ok(false);
+        }
+        let uuids = self.blockdevs()
+            .iter()
+            .map(|&(uuid, _)| uuid)
+            .collect::<Vec<_>>();
+        wipe_stray_device(path, pool_uuid, &uuids)
+    }
+
+    /// The UUID of the blockdev of the data tier on the device at path, if
+    /// the device is one.
+    pub fn datadev_at(&self, path: &Path) -> StratisResult<Option<DevUuid>> {
+        let device = match devnode_to_devno(path)? {
+            Some(devno) => Device::from(devno),
+            None => return Ok(None),
+        };
+        Ok(self.data_tier
+            .blockdevs()
+            .iter()
+            .find(|&&(_, bd)| *bd.device() == device)
+            .map(|&(uuid, _)| uuid))
+    }
+
+    /// Add the device at path to the data tier, to replace one of its
+    /// blockdevs. Unlike add_datadevs(), this is allowed whatever the
+    /// redundancy of the pool, since the number of members of the array
+    /// does not change.
+    /// WARNING: metadata changing event
+    pub fn add_replacement_datadev(
+        &mut self,
+        pool_uuid: PoolUuid,
+        path: &Path,
+        signature_policy: SignaturePolicy,
+    ) -> StratisResult<DevUuid> {
+        self.data_tier
+            .add(pool_uuid, &[path], signature_policy)?
+            .pop()
+            .ok_or_else(|| {
+                StratisError::Engine(
+                    ErrorEnum::AlreadyExists,
+                    format!("device {} already belongs to the pool", path.display()),
+                )
+            })
+    }
+
+    /// Check that the blockdev old of the data tier may be replaced by the
+    /// blockdev new: that both are blockdevs of the data tier that are
+    /// present, and, if the pool has no redundancy, that new has room for
+    /// the data on old. If new is None, only old is checked. Return the
+    /// number of sectors of data that must be moved off old first.
+    pub fn check_replace_datadev(
+        &self,
+        old: DevUuid,
+        new: Option<DevUuid>,
+    ) -> StratisResult<Sectors> {
+        self.present_datadev(old)?;
+        let required = self.data_tier.allocated_on(&[old]);
+        if let Some(new) = new {
+            if new == old {
+                return Err(StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!("blockdev {} can not be replaced by itself", old.simple()),
+                ));
+            }
+            let available = self.present_datadev(new)?.available();
+            if self.raid.is_none() && required > available {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("SpaceUnavailable")
+                        .param("requested", *required)
+                        .param("available", *available),
+                ));
+            }
+        }
+        Ok(required)
+    }
+
+    /// Move the next part of the data on the blockdevs specified by uuids
+    /// to the other blockdevs of the data tier, or only to the blockdev
+    /// target if one is given: suspend the device that maps the data tier,
+    /// and copy the part to newly allocated space. The device is left
+    /// suspended, so that the data can not change until the metadata
+    /// records where it has been moved to; it is resumed by
+    /// commit_data_move(), or by abort_data_move() if the metadata could
+    /// not be saved. Return None if no data is left on the blockdevs.
+    /// WARNING: metadata changing event
+    pub fn begin_data_move(
+        &mut self,
+        uuids: &[DevUuid],
+        target: Option<DevUuid>,
+    ) -> StratisResult<Option<DataMove>> {
+        let (previous, moved, new) =
+            match self.data_tier.relocate(uuids, MOVE_CHUNK_SIZE, target)? {
+                Some(relocation) => relocation,
+                None => return Ok(None),
+            };
+        let data_move = DataMove {
+            previous,
+            length: moved.segment.length,
+        };
+
+        let copied = self.suspend_origin()
+            .and_then(|_| self.copy_segment(&moved, &new));
+        if let Err(err) = copied {
+            self.abort_data_move(data_move)?;
+            return Err(err);
+        }
+        Ok(Some(data_move))
+    }
+
+    /// Whether the blockdev is a member of the array of a pool with
+    /// redundancy.
+    pub fn is_raid_member(&self, uuid: DevUuid) -> bool {
+        self.raid
+            .as_ref()
+            .map_or(false, |raid| raid.member_index(uuid).is_some())
+    }
+
+    /// Replace the member of the array on the blockdev old of the data tier
+    /// with a member on the blockdev new, which the kernel rebuilds from the
+    /// other members in the background. Nothing is read from old.
+    ///
+    /// Precondition: the pool has redundancy.
+    /// WARNING: metadata changing event
+    pub fn rebuild_raid_member(
+        &mut self,
+        pool_uuid: PoolUuid,
+        old: DevUuid,
+        new: DevUuid,
+    ) -> StratisResult<()> {
+        self.raid
+            .as_mut()
+            .expect("the pool has redundancy")
+            .replace_member(old, new, &mut self.data_tier.block_mgr)?;
+        self.verify_mappings(pool_uuid)
+    }
+
+    /// Map the data moved by begin_data_move() where it has been moved to,
+    /// now that the metadata records it there, and resume the device that
+    /// maps the data tier.
+    pub fn commit_data_move(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _data_move: DataMove,
+    ) -> StratisResult<()> {
+        self.resume_origin()?;
+        self.verify_mappings(pool_uuid)
+    }
+
+    /// Undo the move begun by begin_data_move(), and resume the device that
+    /// maps the data tier. The space allocated for the move is not given
+    /// back until the pool is next set up.
+    pub fn abort_data_move(&mut self, data_move: DataMove) -> StratisResult<()> {
+        self.data_tier.segments = data_move.previous;
+        self.resume_origin()
+    }
+
+    /// Copy the data of the segment from to the segments to, in order.
+    fn copy_segment(&self, from: &BlkDevSegment, to: &[BlkDevSegment]) -> StratisResult<()> {
+        let devnode = |seg: &BlkDevSegment| {
+            self.data_tier
+                .get_blockdev_by_uuid(seg.uuid)
+                .map(|(_, bd)| bd.devnode.clone())
+                .expect("segments of the data tier are on blockdevs of the data tier")
+        };
+        let from_devnode = devnode(from);
+        let mut offset = from.segment.start;
+        for seg in to {
+            copy_sectors(
+                &from_devnode,
+                offset,
+                devnode(seg),
+                seg.segment.start,
+                seg.segment.length,
+            )?;
+            offset += seg.segment.length;
+        }
+        Ok(())
+    }
+
+    /// Suspend the device that maps the data tier: the cache device if
+    /// there is one, otherwise the linear device.
+    ///
+    /// Precondition: some space has been allocated from the data tier.
+    fn suspend_origin(&mut self) -> StratisResult<()> {
+        match (self.cache.as_mut(), self.linear.as_mut()) {
+            (Some(cache), None) => cache.suspend(),
+            (None, Some(linear)) => {
+                let name = linear.name().to_owned();
+                suspend_dm(&name, true)
+            }
+            _ => panic!("exactly one of self.cache and self.linear is some"),
+        }
+    }
+
+    /// Load the origin with the table of the data tier as it is now, and
+    /// resume the device suspended by suspend_origin().
+    fn resume_origin(&mut self) -> StratisResult<()> {
+        let table = origin_table(self.raid.as_ref(), &self.data_tier);
+        match (self.cache.as_mut(), self.linear.as_mut()) {
+            (Some(cache), None) => cache.set_origin_table(table),
+            (None, Some(linear)) => {
+                let name = linear.name().to_owned();
+                watch_dm(&name, "reload", || linear.set_table(get_dm(), table))?;
+                resume_dm(&name)
+            }
+            _ => panic!("exactly one of self.cache and self.linear is some"),
+        }
+    }
+
+    /// Remove the blockdevs specified by uuids from the data tier, once all
+    /// the data on them has been moved off, and erase their metadata. If
+    /// the pool is encrypted, their dm-crypt devices are closed and the
+    /// headers of the LUKS2 devices beneath are wiped.
+    /// WARNING: metadata changing event
+    pub fn remove_datadevs(&mut self, uuids: &[DevUuid]) -> StratisResult<()> {
+        let crypt_devnodes: Vec<PathBuf> = if self.encrypted {
+            self.data_tier
+                .blockdevs()
+                .iter()
+                .filter(|&&(uuid, _)| uuids.contains(&uuid))
+                .map(|&(_, bd)| bd.devnode.clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.data_tier.remove(uuids)?;
+        for devnode in crypt_devnodes {
+            destroy_crypt(&Cryptsetup, &devnode)?;
+        }
+        Ok(())
+    }
+
+    /// Load the cap device with a table that maps all of the device at the
+    /// top of the tiers, as it is now.
+    ///
+    /// Precondition: self.cap.is_some()
+    fn reload_cap(&mut self) -> StratisResult<()> {
+        let (top, size) = self.top()
+            .expect("the cap device exists, so some device is at the top of the tiers");
+        let cap = self.cap
+            .as_mut()
+            .expect("space has been allocated from the backstore");
+        let name = cap.name().to_owned();
+        watch_dm(&name, "reload", || cap.set_table(get_dm(), cap_table(top, size)))?;
+        watch_dm(&name, "resume", || cap.resume(get_dm()))?;
+        Ok(())
+    }
+
+    /// The device at the top of the tiers, and its size. This is the cache
+    /// device if there is a cache, otherwise the linear device. There is no
+    /// such device if no data has yet been allocated from the backstore.
+    fn top(&self) -> Option<(Device, Sectors)> {
+        self.cache
+            .as_ref()
+            .map(|d| (d.device(), d.size()))
+            .or_else(|| self.linear.as_ref().map(|d| (d.device(), d.size())))
+    }
+
+    /// Extend the cap device and the device at the top of the tiers, whether
+    /// it is a cache or not. Create the DM devices if they do not already
+    /// exist. Return an error if DM operations fail. Use all segments
+    /// currently allocated in the data tier.
     fn extend_cap_device(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
+        let table = origin_table(self.raid.as_ref(), &self.data_tier);
         let create = match (self.cache.as_mut(), self.linear.as_mut()) {
-            (None, None) => true,
+            (None, None) => Some(table),
             (Some(cache), None) => {
-                let table = map_to_dm(&self.data_tier.segments);
-                cache.set_origin_table(get_dm(), table)?;
-                cache.resume(get_dm())?;
-                false
+                cache.set_origin_table(table)?;
+                None
             }
             (None, Some(linear)) => {
-                let table = map_to_dm(&self.data_tier.segments);
-                linear.set_table(get_dm(), table)?;
-                linear.resume(get_dm())?;
-                false
+                let name = linear.name().to_owned();
+                watch_dm(&name, "reload", || linear.set_table(get_dm(), table))?;
+                watch_dm(&name, "resume", || linear.resume(get_dm()))?;
+                None
             }
             _ => panic!("NOT (self.cache().is_some() AND self.linear.is_some())"),
         };
 
-        if create {
-            let table = map_to_dm(&self.data_tier.segments);
+        if let Some(table) = create {
             let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
             let origin = LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), table)?;
+            let cap = make_cap(pool_uuid, origin.device(), origin.size())?;
             self.linear = Some(origin);
+            self.cap = Some(cap);
+        } else {
+            self.reload_cap()?;
         }
 
+        self.verify_mappings(pool_uuid)
+    }
+
+    /// If verification of mappings is turned on, check the tables of the
+    /// origin sub-device, of the sub-devices of the members of the raid
+    /// device, and of the sub-devices of the cache, if there is one, against
+    /// the segments recorded as allocated to each.
+    fn verify_mappings(&self, pool_uuid: PoolUuid) -> StratisResult<()> {
+        if self.linear.is_some() || self.cache.is_some() {
+            let (dm_name, _) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
+            let records = match self.raid {
+                Some(ref raid) => vec![(raid.device(), Sectors(0), raid.size())],
+                None => segment_records(&self.data_tier.segments),
+            };
+            verify_mapping(&dm_name, &records)?;
+        }
+        if let Some(ref raid) = self.raid {
+            for (dm_name, segments) in raid.member_segments() {
+                verify_mapping(&dm_name, &segment_records(segments))?;
+            }
+        }
+        if let Some(ref cache_tier) = self.cache_tier {
+            let (dm_name, _) = format_backstore_ids(pool_uuid, CacheRole::CacheSub);
+            verify_mapping(&dm_name, &segment_records(&cache_tier.cache_segments))?;
+            if cache_tier.cache_type == CacheType::Cache {
+                let (dm_name, _) = format_backstore_ids(pool_uuid, CacheRole::MetaSub);
+                verify_mapping(&dm_name, &segment_records(&cache_tier.meta_segments))?;
+            }
+        }
         Ok(())
     }
 
@@ -305,7 +1318,7 @@ impl Backstore {
         let total_required = sizes.iter().cloned().sum();
         let available = self.available_in_cap();
         if available < total_required {
-            if self.data_tier.alloc(total_required - available) {
+            if self.alloc_data(total_required - available) {
                 self.extend_cap_device(pool_uuid)?;
             } else {
                 return Ok(None);
@@ -331,6 +1344,17 @@ impl Backstore {
         Ok(Some(chunks))
     }
 
+    /// Allocate at least request sectors to the origin sub-device. If the
+    /// pool has redundancy, the origin maps all of the raid device from the
+    /// first allocation on, and no more can ever be allocated.
+    /// Return true if the request can be satisfied.
+    fn alloc_data(&mut self, request: Sectors) -> bool {
+        match self.raid {
+            Some(ref raid) => self.cap.is_none() && raid.size() >= request,
+            None => self.data_tier.alloc(request),
+        }
+    }
+
     /// Allocate a single segment from the backstore.
     /// If it is impossible to allocate the requested amount, try
     /// something smaller. If it is impossible to allocate any amount
@@ -363,7 +1387,7 @@ impl Backstore {
         if available < internal_request {
             let mut allocated = false;
             while !allocated && internal_request != Sectors(0) {
-                allocated = self.data_tier.alloc(internal_request - available);
+                allocated = self.alloc_data(internal_request - available);
                 let temp = internal_request / 2usize;
                 internal_request = (temp / modulus) * modulus;
             }
@@ -421,18 +1445,17 @@ impl Backstore {
     /// the size of each DM device is calculated; the result is computed and
     /// no ioctl is required.
     fn size(&self) -> Sectors {
-        self.linear
-            .as_ref()
-            .map(|d| d.size())
-            .or_else(|| self.cache.as_ref().map(|d| d.size()))
-            .unwrap_or(Sectors(0))
+        self.cap.as_ref().map_or(Sectors(0), |d| d.size())
     }
 
     /// The total number of unallocated usable sectors in the
     /// backstore. Includes both in the cap but unallocated as well as not yet
     /// added to cap.
     pub fn available_in_backstore(&self) -> Sectors {
-        self.data_tier.usable_size() - self.next
+        match self.raid {
+            Some(ref raid) => raid.size() - self.next,
+            None => self.data_tier.usable_size() - self.next,
+        }
     }
 
     /// The available number of Sectors.
@@ -451,47 +1474,160 @@ impl Backstore {
         size - self.next
     }
 
-    /// Destroy the entire store.
-    pub fn destroy(&mut self) -> StratisResult<()> {
+    /// Destroy the entire store. If keep_signatures is true, the sigblocks
+    /// of the blockdevs are kept, marked as those of a destroyed pool.
+    pub fn destroy(&mut self, keep_signatures: bool) -> StratisResult<()> {
+        if let Some(ref mut cap) = self.cap {
+            let name = cap.name().to_owned();
+            watch_dm(&name, "teardown", || cap.teardown(get_dm()))?;
+        }
         match self.cache {
             Some(ref mut cache) => {
-                cache.teardown(get_dm())?;
+                let name = cache.name().to_owned();
+                watch_dm(&name, "teardown", || cache.teardown())?;
                 self.cache_tier
                     .as_mut()
                     .expect("if dm_device is cache, cache tier exists")
-                    .destroy()?;
+                    .destroy(keep_signatures)?;
             }
             None => {
                 if let Some(ref mut linear) = self.linear {
-                    linear.teardown(get_dm())?;
+                    let name = linear.name().to_owned();
+                    watch_dm(&name, "teardown", || linear.teardown(get_dm()))?;
                 }
             }
         };
-        self.data_tier.destroy()
+        if let Some(ref mut raid) = self.raid {
+            raid.teardown()?;
+        }
+        // The dm-crypt devices of the data tier are left open if the
+        // blockdevs keep their signatures, so that the destruction of the
+        // pool can be undone.
+        let crypt_devnodes: Vec<PathBuf> = if self.encrypted && !keep_signatures {
+            self.data_tier
+                .blockdevs()
+                .iter()
+                .map(|&(_, bd)| bd.devnode.clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.data_tier.destroy(keep_signatures)?;
+        for devnode in crypt_devnodes {
+            destroy_crypt(&Cryptsetup, &devnode)?;
+        }
+        Ok(())
     }
 
     /// Teardown the DM devices in the backstore.
     #[cfg(test)]
     pub fn teardown(&mut self) -> StratisResult<()> {
+        if let Some(ref mut cap) = self.cap {
+            cap.teardown(get_dm())?;
+        }
         match self.cache {
-            Some(ref mut cache) => cache.teardown(get_dm()),
+            Some(ref mut cache) => cache.teardown()?,
             None => if let Some(ref mut linear) = self.linear {
-                linear.teardown(get_dm())
-            } else {
-                Ok(())
+                linear.teardown(get_dm())?;
             },
-        }.map_err(|e| e.into())
+        }
+        if let Some(ref mut raid) = self.raid {
+            raid.teardown()?;
+        }
+        if self.encrypted {
+            for (_, bd) in self.data_tier.blockdevs() {
+                close_crypt(&Cryptsetup, &bd.devnode)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The strategy by which space is allocated from the data tier.
+    pub fn allocation_strategy(&self) -> AllocationStrategy {
+        self.data_tier.block_mgr.allocation_strategy()
+    }
+
+    /// Allocate space from the data tier by the given strategy from now on.
+    pub fn set_allocation_strategy(&mut self, strategy: AllocationStrategy) {
+        self.data_tier.block_mgr.set_allocation_strategy(strategy)
+    }
+
+    /// The priority of the threads that do the backstore's background work.
+    pub fn worker_priority(&self) -> WorkerPriority {
+        self.data_tier.block_mgr.worker_priority()
+    }
+
+    /// Run the threads that do the backstore's background work at the
+    /// given priority from now on.
+    pub fn set_worker_priority(&mut self, priority: WorkerPriority) {
+        self.data_tier.block_mgr.set_worker_priority(priority)
+    }
+
+    /// Shrink the reserved area of the metadata on each of the data tier's
+    /// blockdevs, returning the sectors it gives up to the space from which
+    /// the data tier allocates. Returns the number of sectors reclaimed.
+    pub fn shrink_reserved(&mut self) -> StratisResult<Sectors> {
+        self.data_tier.block_mgr.shrink_reserved()
+    }
+
+    /// Record the name of the pool in the sigblock of each blockdev in
+    /// either tier, wherever it is not already recorded.
+    pub fn set_pool_name(&mut self, name: &str) {
+        self.data_tier.block_mgr.set_pool_name(name);
+        if let Some(ref mut cache_tier) = self.cache_tier {
+            cache_tier.block_mgr.set_pool_name(name);
+        }
+    }
+
+    /// Mark the blockdev, in either tier, with the given device number as
+    /// missing. Returns the tier and UUID of the blockdev if it was newly
+    /// marked missing.
+    pub fn mark_missing(&mut self, device: Device) -> Option<(BlockDevTier, DevUuid)> {
+        let cache_tier = &mut self.cache_tier;
+        self.data_tier
+            .block_mgr
+            .mark_missing(device)
+            .map(|uuid| (BlockDevTier::Data, uuid))
+            .or_else(move || {
+                cache_tier
+                    .as_mut()
+                    .and_then(|c| c.block_mgr.mark_missing(device))
+                    .map(|uuid| (BlockDevTier::Cache, uuid))
+            })
+    }
+
+    /// Whether any blockdev, in either tier, has been removed from the
+    /// system.
+    pub fn has_missing(&self) -> bool {
+        self.data_tier.block_mgr.has_missing()
+            || self.cache_tier
+                .as_ref()
+                .map_or(false, |c| c.block_mgr.has_missing())
+    }
+
+    /// The DM target with which the cache is made, if there is a cache.
+    pub fn cache_type(&self) -> Option<CacheType> {
+        self.cache_tier.as_ref().map(|c| c.cache_type)
+    }
+
+    /// Whether the cache is on volatile devices. False if there is no cache.
+    pub fn cache_volatile(&self) -> bool {
+        self.cache_tier.as_ref().map_or(false, |c| c.volatile)
+    }
+
+    /// The usage of the cache, if there is a cache.
+    pub fn cache_usage(&self) -> StratisResult<Option<CacheUsage>> {
+        match self.cache {
+            Some(ref cache) => Ok(Some(cache.usage()?)),
+            None => Ok(None),
+        }
     }
 
-    /// Return the device that this tier is currently using.
-    /// This changes, depending on whether the backstore is supporting a cache
-    /// or not. There may be no device if no data has yet been allocated from
-    /// the backstore.
+    /// Return the cap device, on which the layers above the backstore are
+    /// built. It does not change when a cache is added. There may be no
+    /// device if no data has yet been allocated from the backstore.
     pub fn device(&self) -> Option<Device> {
-        self.cache
-            .as_ref()
-            .map(|d| d.device())
-            .or_else(|| self.linear.as_ref().map(|d| d.device()))
+        self.cap.as_ref().map(|d| d.device())
     }
 
     /// Lookup an immutable blockdev by its Stratis UUID.
@@ -524,9 +1660,46 @@ impl Backstore {
         self.data_tier.metadata_size()
     }
 
-    /// Write the given data to the data tier's devices.
-    pub fn save_state(&mut self, metadata: &[u8]) -> StratisResult<()> {
-        self.data_tier.save_state(metadata)
+    /// Write the given data to the data tier's devices. If throttle is
+    /// true, the save is a background one, subject to the metadata write
+    /// interval.
+    pub fn save_state(
+        &mut self,
+        metadata: &[u8],
+        verify: bool,
+        throttle: bool,
+    ) -> StratisResult<()> {
+        self.data_tier.save_state(metadata, verify, throttle)
+    }
+
+    /// Write the background save held back by the metadata write interval,
+    /// if there is one. Return true if a save is still held back.
+    pub fn save_deferred(&mut self) -> StratisResult<bool> {
+        self.data_tier.save_deferred()
+    }
+
+    /// The time at which metadata was last saved, if ever.
+    pub fn last_update_time(&self) -> Option<&DateTime<Utc>> {
+        self.data_tier.last_update_time()
+    }
+
+    /// If the clock reads earlier than the time of the newest metadata
+    /// written, the current time and that time.
+    pub fn clock_skew(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        self.data_tier.clock_skew()
+    }
+
+    /// The UUIDs of the blockdevs in the data tier which do not hold a copy
+    /// of the most recently saved metadata. The blockdevs in the cache tier
+    /// hold no metadata, and are never behind.
+    pub fn metadata_behind(&self) -> Vec<DevUuid> {
+        let latest = self.data_tier.last_update_time();
+        self.data_tier
+            .blockdevs()
+            .into_iter()
+            .filter(|&(_, bd)| bd.last_update_time() != latest)
+            .map(|(uuid, _)| uuid)
+            .collect()
     }
 
     /// Set user info field on the specified blockdev.
@@ -556,13 +1729,14 @@ impl Recordable<BackstoreSave> for Backstore {
                 allocs: vec![(Sectors(0), self.next)],
             },
             data_tier: self.data_tier.record(),
+            raid: self.raid.as_ref().map(|r| r.record()),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use uuid::Uuid;
+    use std::io::Cursor;
 
     use devicemapper::{CacheDevStatus, DataBlocks, IEC};
 
@@ -578,6 +1752,7 @@ mod tests {
     /// Assert some invariants of the backstore
     /// * backstore.cache_tier.is_some() <=> backstore.cache.is_some() &&
     ///   backstore.cache_tier.is_some() => backstore.linear.is_none()
+    /// * backstore.cap.is_some() <=> some device is at the top of the tiers
     /// * backstore's data tier allocated is equal to the size of the cap device
     /// * backstore's next index is always less than the size of the cap
     ///   device
@@ -597,9 +1772,37 @@ mod tests {
                 _ => panic!("impossible; see first assertion"),
             }
         );
+        assert_eq!(backstore.cap.is_some(), backstore.top().is_some());
+        assert_eq!(backstore.size(), backstore.top().map_or(Sectors(0), |(_, s)| s));
         assert!(backstore.next <= backstore.size())
     }
 
+    #[test]
+    /// Verify that a zeroed dm-cache superblock is accepted, as is one that
+    /// agrees with the cache, but that one with a bad magic number, a
+    /// different block size, or more cache blocks than the cache sub-device
+    /// holds is not.
+    fn test_check_cache_superblock() {
+        let cache_size = CACHE_BLOCK_SIZE * 16u64;
+        let superblock = |magic, block_size: Sectors, cache_blocks| {
+            let mut buf = vec![0u8; SECTOR_SIZE];
+            LittleEndian::write_u64(&mut buf[32..40], magic);
+            LittleEndian::write_u32(&mut buf[244..248], *block_size as u32);
+            LittleEndian::write_u32(&mut buf[252..256], cache_blocks);
+            check_cache_superblock(&mut Cursor::new(buf), cache_size).unwrap()
+        };
+
+        let zeroed = vec![0u8; SECTOR_SIZE];
+        assert_eq!(
+            check_cache_superblock(&mut Cursor::new(zeroed), cache_size).unwrap(),
+            None
+        );
+        assert_eq!(superblock(CACHE_SUPERBLOCK_MAGIC, CACHE_BLOCK_SIZE, 16), None);
+        assert!(superblock(CACHE_SUPERBLOCK_MAGIC + 1, CACHE_BLOCK_SIZE, 16).is_some());
+        assert!(superblock(CACHE_SUPERBLOCK_MAGIC, CACHE_BLOCK_SIZE * 2u64, 16).is_some());
+        assert!(superblock(CACHE_SUPERBLOCK_MAGIC, CACHE_BLOCK_SIZE, 17).is_some());
+    }
+
     /// Test adding cachedevs to the backstore.
     /// When cachedevs are added, cache tier, etc. must exist.
     /// Nonetheless, because nothing is written or read, cache usage ought
@@ -614,9 +1817,14 @@ mod tests {
         let (cachedevpaths, paths) = paths.split_at(1);
         let (datadevpaths, initdatapaths) = paths.split_at(1);
 
-        let pool_uuid = Uuid::new_v4();
-        let mut backstore =
-            Backstore::initialize(pool_uuid, initdatapaths, MIN_MDA_SECTORS).unwrap();
+        let pool_uuid = PoolUuid::new_v4();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            initdatapaths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
 
         invariant(&backstore);
 
@@ -625,18 +1833,23 @@ mod tests {
             .alloc(pool_uuid, &[INITIAL_BACKSTORE_ALLOCATION])
             .unwrap();
 
-        let cache_uuids = backstore.add_cachedevs(pool_uuid, initcachepaths).unwrap();
+        let cache_uuids = backstore.add_cachedevs(
+            pool_uuid,
+            initcachepaths,
+            SignaturePolicy::Refuse,
+            CacheType::Cache,
+            false,
+        ).unwrap();
 
         invariant(&backstore);
 
         assert_eq!(cache_uuids.len(), initcachepaths.len());
         assert!(backstore.linear.is_none());
 
-        let cache_status = backstore
-            .cache
-            .as_ref()
-            .map(|c| c.status(get_dm()).unwrap())
-            .unwrap();
+        let cache_status = match backstore.cache {
+            Some(CacheDevice::Cache(ref cache)) => cache.status(get_dm()).unwrap(),
+            _ => panic!("the cache is a dm-cache"),
+        };
 
         match cache_status {
             CacheDevStatus::Working(status) => {
@@ -648,19 +1861,28 @@ mod tests {
             CacheDevStatus::Fail => panic!("cache status should succeed"),
         }
 
-        let data_uuids = backstore.add_datadevs(pool_uuid, datadevpaths).unwrap();
+        let data_uuids = backstore.add_datadevs(
+            pool_uuid,
+            datadevpaths,
+            SignaturePolicy::Refuse,
+        ).unwrap();
         invariant(&backstore);
         assert_eq!(data_uuids.len(), datadevpaths.len());
 
-        let cache_uuids = backstore.add_cachedevs(pool_uuid, cachedevpaths).unwrap();
+        let cache_uuids = backstore.add_cachedevs(
+            pool_uuid,
+            cachedevpaths,
+            SignaturePolicy::Refuse,
+            CacheType::Cache,
+            false,
+        ).unwrap();
         invariant(&backstore);
         assert_eq!(cache_uuids.len(), cachedevpaths.len());
 
-        let cache_status = backstore
-            .cache
-            .as_ref()
-            .map(|c| c.status(get_dm()).unwrap())
-            .unwrap();
+        let cache_status = match backstore.cache {
+            Some(CacheDevice::Cache(ref cache)) => cache.status(get_dm()).unwrap(),
+            _ => panic!("the cache is a dm-cache"),
+        };
 
         match cache_status {
             CacheDevStatus::Working(status) => {
@@ -672,7 +1894,171 @@ mod tests {
             CacheDevStatus::Fail => panic!("cache status should succeed"),
         }
 
-        backstore.destroy().unwrap();
+        backstore.destroy(false).unwrap();
+    }
+
+    /// Test making the cache with the writecache target. The cache holds
+    /// no data, as nothing has been written. Extending the origin must
+    /// succeed, but extending the cache must fail, and leave the backstore
+    /// as it was.
+    fn test_add_writecache(paths: &[&Path]) -> () {
+        assert!(paths.len() > 2);
+
+        let (cachepaths, paths) = paths.split_at(1);
+        let (morecachepaths, datapaths) = paths.split_at(1);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            datapaths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+
+        backstore
+            .alloc(pool_uuid, &[INITIAL_BACKSTORE_ALLOCATION])
+            .unwrap();
+        let old_device = backstore.device();
+
+        backstore
+            .add_cachedevs(
+                pool_uuid,
+                cachepaths,
+                SignaturePolicy::Refuse,
+                CacheType::WriteCache,
+                false,
+            )
+            .unwrap();
+        invariant(&backstore);
+        assert_eq!(backstore.device(), old_device);
+        assert_eq!(backstore.cache_type(), Some(CacheType::WriteCache));
+        assert!(
+            backstore
+                .cache_tier
+                .as_ref()
+                .map_or(false, |c| c.meta_segments.is_empty())
+        );
+
+        let usage = backstore.cache_usage().unwrap().unwrap();
+        assert_eq!(usage.used, Sectors(0));
+        assert!(usage.total > Sectors(0));
+
+        let size = backstore.size();
+        backstore.alloc(pool_uuid, &[size]).unwrap().unwrap();
+        invariant(&backstore);
+        assert!(backstore.size() > size);
+
+        assert!(
+            backstore
+                .add_cachedevs(
+                    pool_uuid,
+                    morecachepaths,
+                    SignaturePolicy::Refuse,
+                    CacheType::WriteCache,
+                    false,
+                )
+                .is_err()
+        );
+        invariant(&backstore);
+        assert_eq!(backstore.cache_tier.as_ref().map(|c| c.blockdevs().len()), Some(1));
+
+        backstore.destroy(false).unwrap();
+    }
+
+    #[test]
+    pub fn loop_test_add_writecache() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(3, 4, None),
+            test_add_writecache,
+        );
+    }
+
+    #[test]
+    pub fn real_test_add_writecache() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(3, None, None),
+            test_add_writecache,
+        );
+    }
+
+    /// Test making a volatile cache. It must be in writethrough mode. If
+    /// its devices lose their contents, as RAM disks do on a reboot, the
+    /// backstore must be set up again without a cache.
+    fn test_volatile_cache(paths: &[&Path]) -> () {
+        assert!(paths.len() > 1);
+
+        let (cachepaths, datapaths) = paths.split_at(1);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            datapaths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+
+        backstore
+            .alloc(pool_uuid, &[INITIAL_BACKSTORE_ALLOCATION])
+            .unwrap();
+
+        backstore
+            .add_cachedevs(pool_uuid, cachepaths, SignaturePolicy::Refuse, CacheType::Cache, true)
+            .unwrap();
+        invariant(&backstore);
+        assert!(backstore.cache_volatile());
+
+        let (cache_name, _) = format_backstore_ids(pool_uuid, CacheRole::Cache);
+        let (_, table) = get_dm()
+            .table_status(
+                &DevId::Name(&cache_name),
+                &DmOptions::new().set_flags(DmFlags::DM_STATUS_TABLE),
+            )
+            .unwrap();
+        assert!(
+            table
+                .iter()
+                .all(|&(_, _, _, ref params)| params.contains("writethrough"))
+        );
+
+        let backstore_save = backstore.record();
+        assert_eq!(
+            backstore_save.cache_tier.as_ref().and_then(|c| c.volatile),
+            Some(true)
+        );
+
+        backstore.teardown().unwrap();
+        for (_, blockdev) in backstore.cache_tier.as_ref().unwrap().blockdevs() {
+            blockdev.wipe_metadata().unwrap();
+        }
+
+        cmd::udev_settle().unwrap();
+        let map = find_all().unwrap();
+        let map = map.get(&pool_uuid).unwrap();
+        let mut backstore = Backstore::setup(pool_uuid, &backstore_save, &map, None).unwrap();
+        invariant(&backstore);
+        assert_eq!(backstore.cache_type(), None);
+        assert!(!backstore.cache_volatile());
+        assert_eq!(backstore.record().cache_tier, None);
+
+        backstore.destroy(false).unwrap();
+    }
+
+    #[test]
+    pub fn loop_test_volatile_cache() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(2, 3, None),
+            test_volatile_cache,
+        );
+    }
+
+    #[test]
+    pub fn real_test_volatile_cache() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(2, None, None),
+            test_volatile_cache,
+        );
     }
 
     #[test]
@@ -707,8 +2093,14 @@ mod tests {
     fn test_request(paths: &[&Path]) -> () {
         assert!(paths.len() > 0);
 
-        let pool_uuid = Uuid::new_v4();
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let pool_uuid = PoolUuid::new_v4();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
 
         assert!(
             backstore
@@ -739,7 +2131,7 @@ mod tests {
         // length * 2. In that case, length * 2 would have been allocated.
         assert!(new_request.is_none() || new_request.expect("!is_none()").1 < length);
         cmd::udev_settle().unwrap();
-        backstore.destroy().unwrap();
+        backstore.destroy(false).unwrap();
         cmd::udev_settle().unwrap();
     }
 
@@ -770,9 +2162,15 @@ mod tests {
 
         let (paths1, paths2) = paths.split_at(paths.len() / 2);
 
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
 
-        let mut backstore = Backstore::initialize(pool_uuid, paths1, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths1,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         invariant(&backstore);
 
         // Allocate space from the backstore so that the cap device is made.
@@ -782,10 +2180,12 @@ mod tests {
 
         let old_device = backstore.device();
 
-        backstore.add_cachedevs(pool_uuid, paths2).unwrap();
+        backstore
+            .add_cachedevs(pool_uuid, paths2, SignaturePolicy::Refuse, CacheType::Cache, false)
+            .unwrap();
         invariant(&backstore);
 
-        assert_ne!(backstore.device(), old_device);
+        assert_eq!(backstore.device(), old_device);
 
         let backstore_save = backstore.record();
 
@@ -811,7 +2211,7 @@ mod tests {
         assert_eq!(backstore_save.cache_tier, backstore_save2.cache_tier);
         assert_eq!(backstore_save.data_tier, backstore_save2.data_tier);
 
-        backstore.destroy().unwrap();
+        backstore.destroy(false).unwrap();
     }
 
     #[test]
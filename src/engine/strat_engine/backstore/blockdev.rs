@@ -6,20 +6,24 @@
 
 use std::fs::OpenOptions;
 use std::path::PathBuf;
+use std::time::Instant;
 
 use chrono::{DateTime, TimeZone, Utc};
 
 use devicemapper::{Device, Sectors};
 
-use stratis::StratisResult;
+use stratis::{ErrorEnum, StratisError, StratisResult};
 
 use super::super::super::engine::BlockDev;
 use super::super::super::event::{get_engine_listener_list, EngineEvent};
-use super::super::super::types::{BlockDevState, DevUuid, MaybeDbusPath, PoolUuid};
+use super::super::super::types::{
+    BlockDevState, DevUuid, FormatVersions, MaybeDbusPath, MetadataStats, PoolUuid,
+};
 
 use super::super::serde_structs::{BaseBlockDevSave, Recordable};
 
-use super::metadata::BDA;
+use super::blockdevmgr::metadata_write_interval;
+use super::metadata::{truncate_pool_name, MetadataLayout, BDA};
 use super::range_alloc::RangeAllocator;
 
 #[derive(Debug)]
@@ -31,6 +35,8 @@ pub struct StratBlockDev {
     user_info: Option<String>,
     hardware_info: Option<String>,
     dbus_path: MaybeDbusPath,
+    last_write: Option<Instant>,
+    missing: bool,
 }
 
 impl StratBlockDev {
@@ -58,6 +64,7 @@ impl StratBlockDev {
         hardware_info: Option<String>,
     ) -> StratisResult<StratBlockDev> {
         let mut segments = vec![(Sectors(0), bda.size())];
+        segments.extend(bda.tail_region());
         segments.extend(upper_segments);
         let allocator = RangeAllocator::new(bda.dev_size(), &segments)?;
 
@@ -69,6 +76,8 @@ impl StratBlockDev {
             user_info,
             hardware_info,
             dbus_path: MaybeDbusPath(None),
+            last_write: None,
+            missing: false,
         })
     }
 
@@ -77,14 +86,117 @@ impl StratBlockDev {
         &self.dev
     }
 
+    /// Whether udev has reported that the device was removed from the
+    /// system.
+    pub fn is_missing(&self) -> bool {
+        self.missing
+    }
+
+    /// Record that the device has been removed from the system. No more
+    /// metadata is written to it and no more space is allocated from it.
+    /// Returns true if the device was not already known to be missing.
+    pub fn set_missing(&mut self) -> bool {
+        if self.missing {
+            return false;
+        }
+        self.missing = true;
+        get_engine_listener_list().notify(&EngineEvent::BlockdevStateChanged {
+            dbus_path: self.get_dbus_path(),
+            pool_uuid: self.pool_uuid(),
+            uuid: self.uuid(),
+            state: BlockDevState::Missing,
+        });
+        true
+    }
+
     pub fn wipe_metadata(&self) -> StratisResult<()> {
-        let mut f = OpenOptions::new().write(true).open(&self.devnode)?;
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.devnode)?;
         BDA::wipe(&mut f)
     }
 
-    pub fn save_state(&mut self, time: &DateTime<Utc>, metadata: &[u8]) -> StratisResult<()> {
-        let mut f = OpenOptions::new().write(true).open(&self.devnode)?;
-        self.bda.save_state(time, metadata, &mut f)
+    /// Mark the device's sigblock as that of a device whose pool was
+    /// destroyed at destroyed_time, and erase its pool metadata.
+    pub fn mark_destroyed(&mut self, destroyed_time: u64) -> StratisResult<()> {
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.devnode)?;
+        self.bda.mark_destroyed(destroyed_time, &mut f)
+    }
+
+    /// Shrink the reserved area of the device's BDA, and mark the sectors
+    /// it gives up as unused, so that they may be allocated. The sectors
+    /// are first verified to be in use by nothing but the BDA.
+    /// Returns the number of sectors reclaimed.
+    pub fn shrink_reserved(&mut self) -> StratisResult<Sectors> {
+        let (offset, length) = match self.bda.excess_reserved() {
+            Some(excess) => excess,
+            None => return Ok(Sectors(0)),
+        };
+        if !self.used.in_use(offset, length) {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                format!(
+                    "sectors ({}, {}) of the reserved area of device {} are not in use",
+                    offset,
+                    length,
+                    self.devnode.display()
+                ),
+            ));
+        }
+
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.devnode)?;
+        if self.bda.shrink_reserved(&mut f)?.is_some() {
+            self.used.release(offset, length)?;
+        }
+        Ok(length)
+    }
+
+    /// Record the name of the device's pool in its sigblock, if it is not
+    /// already recorded there. Returns true if the recorded name changed.
+    pub fn set_pool_name(&mut self, name: &str) -> StratisResult<bool> {
+        if self.bda.pool_name() == Some(truncate_pool_name(name)) {
+            return Ok(false);
+        }
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.devnode)?;
+        self.bda.set_pool_name(name, &mut f)
+    }
+
+    /// Save metadata to the device's BDA. If verify is true, the metadata
+    /// is read back after it is written and checked.
+    pub fn save_state(
+        &mut self,
+        time: &DateTime<Utc>,
+        metadata: &[u8],
+        verify: bool,
+    ) -> StratisResult<()> {
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.devnode)?;
+        self.last_write = Some(Instant::now());
+        self.bda.save_state(time, metadata, verify, &mut f)
+    }
+
+    /// When this daemon last wrote metadata to the device, if ever.
+    pub fn last_write(&self) -> Option<Instant> {
+        self.last_write
+    }
+
+    /// Whether background writes of metadata to the device are held back,
+    /// because metadata was written to it less than interval seconds ago.
+    pub fn write_throttled(&self, interval: u64) -> bool {
+        self.last_write
+            .map_or(false, |time| time.elapsed().as_secs() < interval)
     }
 
     /// The device's UUID.
@@ -93,7 +205,6 @@ impl StratBlockDev {
     }
 
     /// The device's pool's UUID.
-    #[allow(dead_code)]
     pub fn pool_uuid(&self) -> PoolUuid {
         self.bda.pool_uuid()
     }
@@ -113,6 +224,8 @@ impl StratBlockDev {
         if result.0 > Sectors(0) && prev_state != BlockDevState::InUse {
             get_engine_listener_list().notify(&EngineEvent::BlockdevStateChanged {
                 dbus_path: self.get_dbus_path(),
+                pool_uuid: self.pool_uuid(),
+                uuid: self.uuid(),
                 state: BlockDevState::InUse,
             });
         }
@@ -122,7 +235,12 @@ impl StratBlockDev {
     // ALL SIZE METHODS (except size(), which is in BlockDev impl.)
     /// The number of Sectors on this device used by Stratis for metadata
     pub fn metadata_size(&self) -> Sectors {
-        self.bda.size()
+        self.bda.size() + self.bda.tail_region().map_or(Sectors(0), |(_, len)| len)
+    }
+
+    /// The layout of the metadata on this device.
+    pub fn metadata_layout(&self) -> MetadataLayout {
+        self.bda.layout()
     }
 
     /// The number of Sectors on this device not allocated for any purpose.
@@ -170,9 +288,15 @@ impl BlockDev for StratBlockDev {
         size
     }
 
+    fn usable_size(&self) -> Sectors {
+        self.size() - self.metadata_size()
+    }
+
     fn state(&self) -> BlockDevState {
         // TODO: Implement support for other BlockDevStates
-        if self.used.used() > self.bda.size() {
+        if self.missing {
+            BlockDevState::Missing
+        } else if self.used.used() > self.metadata_size() {
             BlockDevState::InUse
         } else {
             BlockDevState::NotInUse
@@ -186,6 +310,26 @@ impl BlockDev for StratBlockDev {
     fn get_dbus_path(&self) -> &MaybeDbusPath {
         &self.dbus_path
     }
+
+    fn format_versions(&self) -> FormatVersions {
+        self.bda.format_versions()
+    }
+
+    fn metadata_corruptions(&self) -> u64 {
+        self.bda.metadata_corruptions()
+    }
+
+    fn metadata_degraded(&self) -> bool {
+        self.bda.metadata_degraded()
+    }
+
+    fn metadata_stats(&self) -> MetadataStats {
+        self.bda.metadata_stats()
+    }
+
+    fn metadata_write_throttled(&self) -> bool {
+        self.write_throttled(metadata_write_interval())
+    }
 }
 
 impl Recordable<BaseBlockDevSave> for StratBlockDev {
@@ -4,35 +4,86 @@
 
 // Code to handle a collection of block devices.
 
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs::{File, OpenOptions};
-use std::path::Path;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
 use chrono::{DateTime, Duration, Utc};
 use rand::{seq, thread_rng};
-use uuid::Uuid;
 
 use devicemapper::{
     Bytes, Device, LinearDevTargetParams, LinearTargetParams, Sectors, TargetLine, IEC,
 };
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
 
 use super::super::super::engine::BlockDev;
-use super::super::super::types::{DevUuid, PoolUuid};
+use super::super::super::stats::{count, Counter};
+use super::super::super::types::{
+    mixed_sizes, AddDataDevsPlan, AllocationStrategy, DevUuid, MixedSizePolicy, PlannedBlockDev,
+    PoolUuid, SignaturePolicy, WorkerPriority,
+};
 
+use super::super::backup::{save_backup, BACKUP_DIR};
+use super::super::priority::set_thread_priority;
 use super::super::serde_structs::{BaseBlockDevSave, BaseDevSave, Recordable};
 
+use super::allocation::{allocator, Allocator, DevSpace};
 use super::blockdev::StratBlockDev;
-use super::cleanup::wipe_blockdevs;
-use super::device::{blkdev_size, identify, resolve_devices, DevOwnership};
-use super::metadata::{validate_mda_size, BDA, MIN_MDA_SECTORS};
+use super::cleanup::{mark_blockdevs_destroyed, wipe_blockdevs};
+use super::device::{
+    blkdev_size, identify, is_rotational, resolve_devices, DevOwnership, MULTIPATH_SIGNATURE,
+};
+use super::metadata::{
+    metadata_footprint, validate_mda_size, MetadataLayout, BDA, MIN_MDA_SECTORS,
+};
+use super::signatures::{probe_signatures, wipe_signatures, ForeignSignature};
 use super::util::hw_lookup;
 
 const MIN_DEV_SIZE: Bytes = Bytes(IEC::Gi);
 const MAX_NUM_TO_WRITE: usize = 10;
 
+/// The greatest number of blockdevs to which metadata is written at once.
+const MAX_WRITE_THREADS: usize = 4;
+
+/// Whether metadata saved while the clock reads no later than the time of
+/// newer metadata written in an earlier run is stamped just after that
+/// time, so that saves are ordered by their sequence rather than by the
+/// clock. If not, such a save is refused until the clock catches up.
+static METADATA_TIME_FALLBACK: AtomicBool = ATOMIC_BOOL_INIT;
+
+/// Set whether metadata saves fall back on ordering by sequence when the
+/// clock is behind the time of metadata already written.
+pub fn set_metadata_time_fallback(enabled: bool) {
+    METADATA_TIME_FALLBACK.store(enabled, Ordering::SeqCst);
+}
+
+/// The least number of seconds between background writes of metadata to
+/// any one blockdev. If zero, background writes are not throttled.
+static METADATA_WRITE_INTERVAL: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Set the least number of seconds between background writes of metadata
+/// to any one blockdev, so that stratisd's own housekeeping does not
+/// compete with other I/O to a busy device. Metadata saved at a client's
+/// request is never throttled. A background save that would be written to
+/// a blockdev too soon is held back as a whole, and written later to as
+/// many blockdevs as any other save, or superseded by a newer save.
+pub fn set_metadata_write_interval(seconds: u64) {
+    METADATA_WRITE_INTERVAL.store(seconds as usize, Ordering::SeqCst);
+}
+
+/// The least number of seconds between background writes of metadata to
+/// any one blockdev.
+pub fn metadata_write_interval() -> u64 {
+    METADATA_WRITE_INTERVAL.load(Ordering::SeqCst) as u64
+}
+
 /// struct to represent a continuous set of sectors on a disk
 #[derive(Debug, Clone)]
 pub struct Segment {
@@ -159,14 +210,92 @@ pub fn map_to_dm(bsegs: &[BlkDevSegment]) -> Vec<TargetLine<LinearDevTargetParam
     table
 }
 
+/// Write metadata stamped with time to each of the blockdevs, each paired
+/// with its index, from no more than MAX_WRITE_THREADS threads at once.
+/// If a priority is given, the threads run at that priority.
+/// Return each blockdev with its index and whether the write succeeded.
+/// A failed write is logged, but is otherwise no more significant than in a
+/// sequential write, since it suffices that the metadata be written to any
+/// one of the blockdevs.
+fn save_state_parallel(
+    block_devs: Vec<(usize, StratBlockDev)>,
+    time: DateTime<Utc>,
+    metadata: &[u8],
+    verify: bool,
+    priority: Option<WorkerPriority>,
+) -> Vec<(usize, StratBlockDev, bool)> {
+    let num_threads = cmp::min(block_devs.len(), MAX_WRITE_THREADS);
+    let queue = Arc::new(Mutex::new(block_devs));
+    let metadata = Arc::new(metadata.to_vec());
+    let (sender, receiver) = mpsc::channel();
+
+    let threads = (0..num_threads)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let metadata = Arc::clone(&metadata);
+            let sender = sender.clone();
+            thread::spawn(move || {
+                if let Some(ref priority) = priority {
+                    if let Err(err) = set_thread_priority(priority) {
+                        warn!("unable to set priority {:?} of worker, reason: {}", priority, err);
+                    }
+                }
+                loop {
+                    let next = queue
+                        .lock()
+                        .expect("no code panics while holding the queue lock")
+                        .pop();
+                    let (index, mut bd) = match next {
+                        Some(next) => next,
+                        None => return,
+                    };
+                    let ok = match bd.save_state(&time, &metadata, verify) {
+                        Ok(()) => true,
+                        Err(err) => {
+                            warn!(
+                                "failed to save metadata to {}, reason: {}",
+                                bd.devnode.display(),
+                                err
+                            );
+                            false
+                        }
+                    };
+                    sender
+                        .send((index, bd, ok))
+                        .expect("the receiver outlives every thread");
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(sender);
+
+    let written = receiver.iter().collect::<Vec<_>>();
+    for handle in threads {
+        if let Err(err) = handle.join() {
+            panic::resume_unwind(err);
+        }
+    }
+    written
+}
+
 #[derive(Debug)]
 pub struct BlockDevMgr {
     block_devs: Vec<StratBlockDev>,
     last_update_time: Option<DateTime<Utc>>,
+    /// The strategy by which space is allocated, and the allocator that
+    /// implements it.
+    strategy: AllocationStrategy,
+    allocator: Box<Allocator>,
+    /// The priority of the threads that write metadata in the background.
+    worker_priority: WorkerPriority,
+    /// A background save held back by the metadata write interval, and
+    /// whether its writes are to be verified.
+    deferred_save: Option<(Vec<u8>, bool)>,
 }
 
 impl BlockDevMgr {
-    /// Make a struct that represents an existing BlockDevMgr.
+    /// Make a struct that represents an existing BlockDevMgr. Space is
+    /// allocated by the fill-first strategy until another is set.
     pub fn new(
         block_devs: Vec<StratBlockDev>,
         last_update_time: Option<DateTime<Utc>>,
@@ -174,22 +303,77 @@ impl BlockDevMgr {
         BlockDevMgr {
             block_devs,
             last_update_time,
+            strategy: AllocationStrategy::FillFirst,
+            allocator: allocator(AllocationStrategy::FillFirst),
+            worker_priority: WorkerPriority::default(),
+            deferred_save: None,
         }
     }
 
+    /// The strategy by which space is allocated.
+    pub fn allocation_strategy(&self) -> AllocationStrategy {
+        self.strategy
+    }
+
+    /// Allocate space by the given strategy from now on. Space already
+    /// allocated is not moved.
+    pub fn set_allocation_strategy(&mut self, strategy: AllocationStrategy) {
+        if strategy != self.strategy {
+            self.strategy = strategy;
+            self.allocator = allocator(strategy);
+        }
+    }
+
+    /// The priority of the threads that write metadata in the background.
+    pub fn worker_priority(&self) -> WorkerPriority {
+        self.worker_priority
+    }
+
+    /// Run the threads that write metadata in the background at the given
+    /// priority from now on.
+    pub fn set_worker_priority(&mut self, priority: WorkerPriority) {
+        self.worker_priority = priority;
+    }
+
     /// Initialize a new StratBlockDevMgr with specified pool and devices.
+    /// Every device is initialized with the given metadata layout. Devices
+    /// added later follow the same layout.
+    /// The signature policy determines whether devices with foreign
+    /// signatures are initialized.
     pub fn initialize(
         pool_uuid: PoolUuid,
         paths: &[&Path],
         mda_size: Sectors,
+        layout: MetadataLayout,
+        signature_policy: SignaturePolicy,
     ) -> StratisResult<BlockDevMgr> {
         let devices = resolve_devices(paths)?;
         Ok(BlockDevMgr::new(
-            initialize(pool_uuid, devices, mda_size, &HashSet::new())?,
+            initialize(
+                pool_uuid,
+                devices,
+                mda_size,
+                &HashSet::new(),
+                layout,
+                signature_policy,
+            )?,
             None,
         ))
     }
 
+    /// The layout of the metadata on the devices managed by this manager.
+    /// A copy of the metadata is placed as it is on any device.
+    pub fn metadata_layout(&self) -> MetadataLayout {
+        let layouts = self.block_devs
+            .iter()
+            .map(|bd| bd.metadata_layout())
+            .collect::<Vec<_>>();
+        MetadataLayout {
+            tail_copy: layouts.iter().any(|layout| layout.tail_copy),
+            spread: layouts.iter().any(|layout| layout.spread),
+        }
+    }
+
     /// Get a function that maps UUIDs to Devices.
     pub fn uuid_to_devno(&self) -> Box<Fn(DevUuid) -> Option<Device>> {
         let uuid_map: HashMap<DevUuid, Device> = self.block_devs
@@ -203,19 +387,103 @@ impl BlockDevMgr {
     /// Add paths to self.
     /// Return the uuids of all blockdevs corresponding to paths that were
     /// added.
-    pub fn add(&mut self, pool_uuid: PoolUuid, paths: &[&Path]) -> StratisResult<Vec<DevUuid>> {
+    pub fn add(
+        &mut self,
+        pool_uuid: PoolUuid,
+        paths: &[&Path],
+        signature_policy: SignaturePolicy,
+    ) -> StratisResult<Vec<DevUuid>> {
         let devices = resolve_devices(paths)?;
         let current_uuids = self.block_devs.iter().map(|bd| bd.uuid()).collect();
-        let bds = initialize(pool_uuid, devices, MIN_MDA_SECTORS, &current_uuids)?;
+        let bds = initialize(
+            pool_uuid,
+            devices,
+            MIN_MDA_SECTORS,
+            &current_uuids,
+            self.metadata_layout(),
+            signature_policy,
+        )?;
         let bdev_uuids = bds.iter().map(|bd| bd.uuid()).collect();
         self.block_devs.extend(bds);
         Ok(bdev_uuids)
     }
 
+    /// Determine the layout that would result from adding paths to self,
+    /// without writing to any device. Return an error if any of the
+    /// devices could not be added.
+    pub fn plan_add(&self, pool_uuid: PoolUuid, paths: &[&Path]) -> StratisResult<AddDataDevsPlan> {
+        let devices = resolve_devices(paths)?;
+        let current_uuids = self.block_devs.iter().map(|bd| bd.uuid()).collect();
+        let dev_infos = devices.into_iter().map(|(d, p)| (d, dev_info(p)));
+        let add_devs = filter_devs(
+            dev_infos,
+            pool_uuid,
+            &current_uuids,
+            SignaturePolicy::Refuse,
+        )?;
+
+        let metadata_size = metadata_footprint(MIN_MDA_SECTORS, self.metadata_layout());
+        let layout = self.block_devs
+            .iter()
+            .map(|bd| PlannedBlockDev {
+                devnode: bd.devnode(),
+                size: bd.size(),
+                usable: bd.size() - bd.metadata_size(),
+                rotational: is_rotational(*bd.device()),
+                new: false,
+            })
+            .chain(
+                add_devs
+                    .into_iter()
+                    .map(|(dev, (devnode, dev_size, _, _))| PlannedBlockDev {
+                        devnode: devnode.to_owned(),
+                        size: dev_size.sectors(),
+                        usable: dev_size.sectors() - metadata_size,
+                        rotational: is_rotational(dev),
+                        new: true,
+                    }),
+            )
+            .collect();
+
+        Ok(AddDataDevsPlan::new(layout))
+    }
+
     pub fn destroy_all(&mut self) -> StratisResult<()> {
         wipe_blockdevs(&self.block_devs)
     }
 
+    /// Mark all the blockdevs as those of a destroyed pool, keeping their
+    /// sigblocks, but erasing the pool metadata on them.
+    pub fn mark_all_destroyed(&mut self) -> StratisResult<()> {
+        mark_blockdevs_destroyed(&mut self.block_devs, Utc::now().timestamp() as u64)
+    }
+
+    /// Shrink the reserved area of the metadata on each blockdev. Returns
+    /// the number of sectors reclaimed.
+    pub fn shrink_reserved(&mut self) -> StratisResult<Sectors> {
+        let mut reclaimed = Sectors(0);
+        for bd in &mut self.block_devs {
+            reclaimed += bd.shrink_reserved()?;
+        }
+        Ok(reclaimed)
+    }
+
+    /// Record the name of the pool in the sigblock of each blockdev that is
+    /// not missing. The name is only informational, so a failure to record
+    /// it is logged, but otherwise ignored.
+    pub fn set_pool_name(&mut self, name: &str) {
+        for bd in self.block_devs.iter_mut().filter(|bd| !bd.is_missing()) {
+            if let Err(err) = bd.set_pool_name(name) {
+                warn!(
+                    "failed to record pool name {} on blockdev {}: {}",
+                    name,
+                    bd.devnode().display(),
+                    err
+                );
+            }
+        }
+    }
+
     /// Remove the specified block devs and erase their metadata.
     ///
     /// Precondition: It is the responsibility of the caller to ensure that
@@ -232,17 +500,10 @@ impl BlockDevMgr {
     pub(super) fn remove_blockdevs(&mut self, uuids: &[DevUuid]) -> StratisResult<()> {
         let mut removed = Vec::new();
         for uuid in uuids {
-            let mut found = false;
-            let blockdevs_last_index = self.block_devs.len() - 1;
-            for i in 0..blockdevs_last_index {
-                let index = blockdevs_last_index - i;
-                if self.block_devs[index].uuid() == *uuid {
-                    removed.push(self.block_devs.swap_remove(index));
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
+            let index = self.block_devs.iter().rposition(|bd| bd.uuid() == *uuid);
+            if let Some(index) = index {
+                removed.push(self.block_devs.swap_remove(index));
+            } else {
                 return Err(StratisError::Engine(
                     ErrorEnum::Error,
                     format!(
@@ -262,28 +523,54 @@ impl BlockDevMgr {
     /// This method is atomic, it either allocates all requested or allocates
     /// nothing.
     pub fn alloc_space(&mut self, sizes: &[Sectors]) -> Option<Vec<Vec<BlkDevSegment>>> {
+        self.alloc_space_except(sizes, &[])
+    }
+
+    /// Allocate space as alloc_space() does, but only from blockdevs other
+    /// than those specified.
+    pub fn alloc_space_except(
+        &mut self,
+        sizes: &[Sectors],
+        except: &[DevUuid],
+    ) -> Option<Vec<Vec<BlkDevSegment>>> {
+        let usable = |bd: &StratBlockDev| !bd.is_missing() && !except.contains(&bd.uuid());
+
         let total_needed: Sectors = sizes.iter().cloned().sum();
-        if self.avail_space() < total_needed {
+        let total_available: Sectors = self.block_devs
+            .iter()
+            .filter(|bd| usable(bd))
+            .map(|bd| bd.available())
+            .sum();
+        if total_available < total_needed {
             return None;
         }
 
+        let rotational = self.block_devs
+            .iter()
+            .map(|bd| is_rotational(*bd.device()))
+            .collect::<Vec<_>>();
+
         let mut lists = Vec::new();
         for &needed in sizes {
+            let devs = self.block_devs
+                .iter()
+                .zip(rotational.iter())
+                .map(|(bd, &rotational)| DevSpace {
+                    available: if usable(bd) {
+                        bd.available()
+                    } else {
+                        Sectors(0)
+                    },
+                    rotational,
+                })
+                .collect::<Vec<_>>();
+
             let mut alloc = Sectors(0);
             let mut segs = Vec::new();
-            // TODO: Consider greater efficiency for allocation generally.
-            // Over time, the blockdevs at the start will be exhausted. It
-            // might be a good idea to keep an auxiliary structure, so that
-            // only blockdevs with some space left to allocate are accessed.
-            // In the context of this major inefficiency that ensues over time
-            // the obvious but more minor inefficiency of this inner loop is
-            // not worth worrying about.
-            for bd in &mut self.block_devs {
-                if alloc == needed {
-                    break;
-                }
-
-                let (gotten, r_segs) = bd.request_space(needed - alloc);
+            for (index, amount) in self.allocator.allocate(&devs, needed) {
+                let bd = &mut self.block_devs[index];
+                let (gotten, r_segs) = bd.request_space(amount);
+                assert_eq!(gotten, amount);
                 let blkdev_segs = r_segs.into_iter().map(|(start, length)| {
                     BlkDevSegment::new(bd.uuid(), Segment::new(*bd.device(), start, length))
                 });
@@ -297,40 +584,151 @@ impl BlockDevMgr {
         Some(lists)
     }
 
+    /// Allocate space of each of the given sizes from every blockdev, in
+    /// order, so that each blockdev holds a part of every allocation.
+    /// Return, for each blockdev, the segments allocated for each size, or
+    /// None if some blockdev could not satisfy the request.
+    /// This method is atomic, it either allocates all requested or allocates
+    /// nothing.
+    pub fn alloc_from_each(&mut self, sizes: &[Sectors]) -> Option<Vec<Vec<Vec<BlkDevSegment>>>> {
+        let total_needed: Sectors = sizes.iter().cloned().sum();
+        if self.block_devs
+            .iter()
+            .any(|bd| bd.is_missing() || bd.available() < total_needed)
+        {
+            return None;
+        }
+
+        let mut lists = Vec::new();
+        for bd in &mut self.block_devs {
+            let mut dev_lists = Vec::new();
+            for &needed in sizes {
+                let (gotten, r_segs) = bd.request_space(needed);
+                assert_eq!(gotten, needed);
+                dev_lists.push(
+                    r_segs
+                        .into_iter()
+                        .map(|(start, length)| {
+                            BlkDevSegment::new(bd.uuid(), Segment::new(*bd.device(), start, length))
+                        })
+                        .collect(),
+                );
+            }
+            lists.push(dev_lists);
+        }
+
+        Some(lists)
+    }
+
     /// Write the given data to all blockdevs marking with current time.
     /// Return an error if data was not written to any blockdev.
     /// Omit blockdevs which do not have sufficient space in BDA to accommodate
     /// metadata. If current time is not more recent than previously written
     /// time, use a time that is one nanosecond greater than that previously
-    /// written. Randomly select no more than MAX_NUM_TO_WRITE blockdevs to
-    /// write to.
-    pub fn save_state(&mut self, metadata: &[u8]) -> StratisResult<()> {
-        let current_time = Utc::now();
-        let stamp_time = if Some(current_time) <= self.last_update_time {
-            self.last_update_time
-                .expect("self.last_update_time >= Some(current_time")
-                .checked_add_signed(Duration::nanoseconds(1))
-                .expect("self.last_update_time << maximum representable DateTime")
-        } else {
-            current_time
-        };
-
+    /// written. If the newer time was written in an earlier run, do so only
+    /// if the metadata time fallback is enabled, and otherwise return an
+    /// error. Randomly select no more than MAX_NUM_TO_WRITE blockdevs to
+    /// write to, and write to them in parallel. If verify is true, each
+    /// write is read back and checked, and a blockdev on which verification
+    /// fails does not count as written. If throttle is true, the write is a
+    /// background one, made from threads run at the worker priority, to the
+    /// blockdevs written least recently. If any of those was written within
+    /// the metadata write interval, nothing is written now; the save is held
+    /// back, to be written by save_deferred(), unless a later save
+    /// supersedes it.
+    pub fn save_state(
+        &mut self,
+        metadata: &[u8],
+        verify: bool,
+        throttle: bool,
+    ) -> StratisResult<()> {
         let data_size = Bytes(metadata.len() as u64).sectors();
-        let candidates = self.block_devs
-            .iter_mut()
-            .filter(|b| b.max_metadata_size() >= data_size);
+        let fitting = (0..self.block_devs.len())
+            .filter(|&index| {
+                !self.block_devs[index].is_missing()
+                    && self.block_devs[index].max_metadata_size() >= data_size
+            })
+            .collect::<Vec<_>>();
 
         // TODO: consider making selection not entirely random, i.e, ensuring
         // distribution of metadata over different paths.
-        let saved = seq::sample_iter(&mut thread_rng(), candidates, MAX_NUM_TO_WRITE)
-            .unwrap_or_else(|e| e)
-            .iter_mut()
-            .fold(false, |acc, b| {
-                acc | b.save_state(&stamp_time, metadata).is_ok()
-            });
+        let selected: HashSet<usize> = if throttle {
+            let interval = metadata_write_interval();
+            let mut fitting = fitting;
+            fitting.sort_by_key(|&index| self.block_devs[index].last_write());
+            fitting.truncate(MAX_NUM_TO_WRITE);
+            if fitting
+                .iter()
+                .any(|&index| self.block_devs[index].write_throttled(interval))
+            {
+                debug!("background metadata save held back by the write interval");
+                self.deferred_save = Some((metadata.to_vec(), verify));
+                return Ok(());
+            }
+            fitting.into_iter().collect()
+        } else {
+            seq::sample_iter(&mut thread_rng(), fitting, MAX_NUM_TO_WRITE)
+                .unwrap_or_else(|e| e)
+                .into_iter()
+                .collect()
+        };
+
+        let current_time = Utc::now();
+        let newest_time = cmp::max(self.last_update_time, self.newest_on_disk());
+        let stamp_time = match newest_time {
+            Some(newest_time) if current_time <= newest_time => {
+                if Some(newest_time) != self.last_update_time
+                    && !METADATA_TIME_FALLBACK.load(Ordering::SeqCst)
+                {
+                    return Err(StratisError::Structured(
+                        ErrorEnum::Invalid,
+                        ErrorPayload::new("MetadataClockSkew")
+                            .param("proposed", current_time.to_rfc3339())
+                            .param("newest", newest_time.to_rfc3339()),
+                    ));
+                }
+                newest_time
+                    .checked_add_signed(Duration::nanoseconds(1))
+                    .expect("newest_time << maximum representable DateTime")
+            }
+            _ => current_time,
+        };
+
+        let (to_write, rest): (Vec<_>, Vec<_>) = self.block_devs
+            .drain(..)
+            .enumerate()
+            .partition(|&(index, _)| selected.contains(&index));
+        let priority = if throttle {
+            Some(self.worker_priority)
+        } else {
+            None
+        };
+        let written = save_state_parallel(to_write, stamp_time, metadata, verify, priority);
+        let saved = written.iter().any(|&(_, _, ok)| ok);
+
+        let mut block_devs = written
+            .into_iter()
+            .map(|(index, bd, _)| (index, bd))
+            .chain(rest)
+            .collect::<Vec<_>>();
+        block_devs.sort_by_key(|&(index, _)| index);
+        self.block_devs = block_devs.into_iter().map(|(_, bd)| bd).collect();
 
         if saved {
             self.last_update_time = Some(stamp_time);
+            self.deferred_save = None;
+            count(Counter::MetadataSaves);
+            // A backup is a convenience; failing to make one does not
+            // make the save a failure.
+            if let Some(pool_uuid) = self.block_devs.first().map(|bd| bd.pool_uuid()) {
+                let backup_dir = Path::new(BACKUP_DIR);
+                if let Err(err) = save_backup(backup_dir, pool_uuid, &stamp_time, metadata) {
+                    warn!(
+                        "unable to back up metadata of pool {}, reason: {}",
+                        pool_uuid, err
+                    );
+                }
+            }
             Ok(())
         } else {
             let err_msg = "Failed to save metadata to even one device in pool";
@@ -338,6 +736,45 @@ impl BlockDevMgr {
         }
     }
 
+    /// Write the background save held back by the metadata write interval,
+    /// if there is one, as save_state() would, so that it is held back again
+    /// if the interval has not yet elapsed. Return true if a save is still
+    /// held back.
+    pub fn save_deferred(&mut self) -> StratisResult<bool> {
+        if let Some((metadata, verify)) = self.deferred_save.take() {
+            self.save_state(&metadata, verify, true)?;
+        }
+        Ok(self.deferred_save.is_some())
+    }
+
+    /// The time at which metadata was last saved, if ever.
+    pub fn last_update_time(&self) -> Option<&DateTime<Utc>> {
+        self.last_update_time.as_ref()
+    }
+
+    /// The time of the newest metadata on any blockdev, if any.
+    fn newest_on_disk(&self) -> Option<DateTime<Utc>> {
+        self.block_devs
+            .iter()
+            .filter_map(|bd| bd.last_update_time())
+            .max()
+            .cloned()
+    }
+
+    /// If the clock reads earlier than the time of the newest metadata
+    /// written, the current time and that time.
+    pub fn clock_skew(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let current_time = Utc::now();
+        cmp::max(self.last_update_time, self.newest_on_disk())
+            .and_then(|newest_time| {
+                if current_time < newest_time {
+                    Some((current_time, newest_time))
+                } else {
+                    None
+                }
+            })
+    }
+
     /// Get references to managed blockdevs.
     pub fn blockdevs(&self) -> Vec<(DevUuid, &StratBlockDev)> {
         self.block_devs.iter().map(|bd| (bd.uuid(), bd)).collect()
@@ -358,11 +795,37 @@ impl BlockDevMgr {
         self.block_devs.iter_mut().find(|bd| bd.uuid() == uuid)
     }
 
+    /// Mark the blockdev with the given device number as missing, as udev
+    /// has reported that it was removed from the system.
+    /// Returns the UUID of the blockdev if it was newly marked missing.
+    pub fn mark_missing(&mut self, device: Device) -> Option<DevUuid> {
+        self.block_devs
+            .iter_mut()
+            .find(|bd| *bd.device() == device)
+            .and_then(|bd| {
+                if bd.set_missing() {
+                    Some(bd.uuid())
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Whether any blockdev has been removed from the system.
+    pub fn has_missing(&self) -> bool {
+        self.block_devs.iter().any(|bd| bd.is_missing())
+    }
+
     // SIZE methods
 
-    /// The number of sectors not allocated for any purpose.
+    /// The number of sectors not allocated for any purpose, on devices
+    /// that have not been removed from the system.
     pub fn avail_space(&self) -> Sectors {
-        self.block_devs.iter().map(|bd| bd.available()).sum()
+        self.block_devs
+            .iter()
+            .filter(|bd| !bd.is_missing())
+            .map(|bd| bd.available())
+            .sum()
     }
 
     /// The current size of all the blockdevs.
@@ -385,99 +848,232 @@ impl Recordable<Vec<BaseBlockDevSave>> for BlockDevMgr {
     }
 }
 
-/// Initialize multiple blockdevs at once. This allows all of them
-/// to be checked for usability before writing to any of them.
-fn initialize(
+/// Get device information, returns an error if problem with obtaining
+/// that information.
+/// Returns a tuple with the device's path, its size in bytes,
+/// its signature as determined by calling device::identify(),
+/// and an open File handle, all of which are needed later.
+fn dev_info(devnode: &Path) -> StratisResult<(&Path, Bytes, DevOwnership, File)> {
+    let f = OpenOptions::new().read(true).write(true).open(&devnode)?;
+    let dev_size = blkdev_size(&f)?;
+    let ownership = identify(devnode)?;
+
+    Ok((devnode, dev_size, ownership, f))
+}
+
+/// Filter devices for admission to pool based on dev_infos.
+/// If there is an error finding out the info, return that error.
+/// Also, return an error if a device is not appropriate for this pool.
+/// A device with a foreign signature, whether recorded in the udev db or
+/// found by probing the device, is admitted only if the signature policy
+/// allows it. A path of a multipath device is never admitted.
+/// Each admitted device is returned with the foreign signatures to be wiped
+/// from it before it is initialized.
+#[allow(type_complexity)]
+fn filter_devs<'a, I>(
+    dev_infos: I,
     pool_uuid: PoolUuid,
-    devices: HashMap<Device, &Path>,
-    mda_size: Sectors,
     owned_devs: &HashSet<DevUuid>,
-) -> StratisResult<Vec<StratBlockDev>> {
-    /// Get device information, returns an error if problem with obtaining
-    /// that information.
-    /// Returns a tuple with the device's path, its size in bytes,
-    /// its signature as determined by calling device::identify(),
-    /// and an open File handle, all of which are needed later.
-    fn dev_info(devnode: &Path) -> StratisResult<(&Path, Bytes, DevOwnership, File)> {
-        let f = OpenOptions::new().read(true).write(true).open(&devnode)?;
-        let dev_size = blkdev_size(&f)?;
-        let ownership = identify(devnode)?;
-
-        Ok((devnode, dev_size, ownership, f))
-    }
-
-    /// Filter devices for admission to pool based on dev_infos.
-    /// If there is an error finding out the info, return that error.
-    /// Also, return an error if a device is not appropriate for this pool.
-    #[allow(type_complexity)]
-    fn filter_devs<'a, I>(
-        dev_infos: I,
-        pool_uuid: PoolUuid,
-        owned_devs: &HashSet<DevUuid>,
-    ) -> StratisResult<Vec<(Device, (&'a Path, Bytes, File))>>
-    where
-        I: Iterator<Item = (Device, StratisResult<(&'a Path, Bytes, DevOwnership, File)>)>,
-    {
-        let mut add_devs = Vec::new();
-        for (dev, dev_result) in dev_infos {
-            let (devnode, dev_size, ownership, mut f) = dev_result?;
-            if dev_size < MIN_DEV_SIZE {
-                let error_message = format!(
-                    "{} too small, minimum {} bytes",
-                    devnode.display(),
-                    MIN_DEV_SIZE
-                );
-                return Err(StratisError::Engine(ErrorEnum::Invalid, error_message));
-            };
-            match ownership {
-                DevOwnership::Unowned => add_devs.push((dev, (devnode, dev_size, f))),
-                DevOwnership::Theirs(signature) => {
-                    let err_str = format!(
-                        "Device {} has an existing signature {}",
+    signature_policy: SignaturePolicy,
+) -> StratisResult<Vec<(Device, (&'a Path, Bytes, File, Vec<ForeignSignature>))>>
+where
+    I: Iterator<Item = (Device, StratisResult<(&'a Path, Bytes, DevOwnership, File)>)>,
+{
+    let mut add_devs = Vec::new();
+    for (dev, dev_result) in dev_infos {
+        let (devnode, dev_size, ownership, mut f) = dev_result?;
+        if dev_size < MIN_DEV_SIZE {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("DeviceTooSmall")
+                    .param("devnode", devnode.display())
+                    .param("minimum", *MIN_DEV_SIZE),
+            ));
+        };
+        match ownership {
+            DevOwnership::Unowned => {
+                let signatures = probe_signatures(&mut f, dev_size)?;
+                let to_wipe = if signatures.is_empty() {
+                    Vec::new()
+                } else {
+                    let names = signatures
+                        .iter()
+                        .map(|sig| sig.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    match signature_policy {
+                        SignaturePolicy::Refuse => {
+                            return Err(StratisError::Structured(
+                                ErrorEnum::Invalid,
+                                ErrorPayload::new("DeviceHasForeignSignatures")
+                                    .param("devnode", devnode.display())
+                                    .param("signatures", names),
+                            ));
+                        }
+                        SignaturePolicy::Ignore => {
+                            warn!(
+                                "initializing device {} despite foreign signatures: {}",
+                                devnode.display(),
+                                names
+                            );
+                            Vec::new()
+                        }
+                        SignaturePolicy::Wipe => {
+                            warn!(
+                                "wiping foreign signatures from device {}: {}",
+                                devnode.display(),
+                                names
+                            );
+                            signatures
+                        }
+                    }
+                };
+                add_devs.push((dev, (devnode, dev_size, f, to_wipe)))
+            }
+            DevOwnership::Theirs(ref signature)
+                if signature_policy != SignaturePolicy::Refuse
+                    && signature != MULTIPATH_SIGNATURE =>
+            {
+                let to_wipe = if signature_policy == SignaturePolicy::Wipe {
+                    probe_signatures(&mut f, dev_size)?
+                } else {
+                    Vec::new()
+                };
+                if to_wipe.is_empty() {
+                    warn!(
+                        "initializing device {} despite existing signature {}",
+                        devnode.display(),
+                        signature
+                    );
+                } else {
+                    warn!(
+                        "wiping foreign signatures from device {} with existing signature {}",
                         devnode.display(),
                         signature
                     );
-                    return Err(StratisError::Engine(ErrorEnum::Invalid, err_str));
                 }
-                DevOwnership::Ours(uuid, dev_uuid) => {
-                    if pool_uuid == uuid {
-                        if !owned_devs.contains(&dev_uuid) {
-                            let error_str = format!(
-                                "Device {} with pool UUID is unknown to pool",
-                                devnode.display()
-                            );
-                            return Err(StratisError::Engine(ErrorEnum::Invalid, error_str));
-                        }
-                    } else {
-                        let error_str = format!(
-                            "Device {} already belongs to Stratis pool {}",
-                            devnode.display(),
-                            uuid
-                        );
-                        return Err(StratisError::Engine(ErrorEnum::Invalid, error_str));
+                add_devs.push((dev, (devnode, dev_size, f, to_wipe)))
+            }
+            DevOwnership::Theirs(signature) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("DeviceHasSignature")
+                        .param("devnode", devnode.display())
+                        .param("signature", signature),
+                ));
+            }
+            DevOwnership::Ours(uuid, dev_uuid) => {
+                if pool_uuid == uuid {
+                    if !owned_devs.contains(&dev_uuid) {
+                        return Err(StratisError::Structured(
+                            ErrorEnum::Invalid,
+                            ErrorPayload::new("DeviceUnknownToPool")
+                                .param("devnode", devnode.display()),
+                        ));
                     }
+                } else {
+                    return Err(StratisError::Structured(
+                        ErrorEnum::Invalid,
+                        ErrorPayload::new("DeviceInOtherPool")
+                            .param("devnode", devnode.display())
+                            .param("owner", uuid),
+                    ));
                 }
             }
+            DevOwnership::Destroyed(uuid, _) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("DeviceOfDestroyedPool")
+                        .param("devnode", devnode.display())
+                        .param("owner", uuid),
+                ));
+            }
+        }
+    }
+    Ok(add_devs)
+}
+
+/// Apply the policy for devices of very different sizes to the devices
+/// specified by paths. Return an error if the policy rejects the devices.
+pub fn check_device_sizes(paths: &[&Path], policy: MixedSizePolicy) -> StratisResult<()> {
+    if policy == MixedSizePolicy::Accept {
+        return Ok(());
+    }
+
+    let mut sizes = Vec::new();
+    for path in resolve_devices(paths)?.values() {
+        sizes.push(blkdev_size(&File::open(path)?)?.sectors());
+    }
+
+    if let Some((smallest, largest)) = mixed_sizes(sizes) {
+        if policy == MixedSizePolicy::Reject {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("DeviceSizesMixed")
+                    .param("smallest", *smallest)
+                    .param("largest", *largest),
+            ));
         }
-        Ok(add_devs)
+        warn!(
+            "Devices are of mixed sizes, from {} to {} sectors",
+            *smallest, *largest
+        );
     }
+    Ok(())
+}
+
+/// Check the devices specified by paths for admission to the pool, as they
+/// would be checked before being initialized, and wipe each of any foreign
+/// signatures that the signature policy says are to be wiped. This readies
+/// devices that are to be formatted as LUKS2 devices, and on which the
+/// blockdevs are not initialized directly.
+/// Return the devnodes of the devices, each device given once.
+pub fn prepare_devices(
+    pool_uuid: PoolUuid,
+    paths: &[&Path],
+    signature_policy: SignaturePolicy,
+) -> StratisResult<Vec<PathBuf>> {
+    let devices = resolve_devices(paths)?;
+    let dev_infos = devices.into_iter().map(|(d, p)| (d, dev_info(p)));
+    let add_devs = filter_devs(dev_infos, pool_uuid, &HashSet::new(), signature_policy)?;
 
+    let mut devnodes = Vec::new();
+    for (_, (devnode, _, mut f, to_wipe)) in add_devs {
+        wipe_signatures(&mut f, &to_wipe)?;
+        devnodes.push(devnode.to_owned());
+    }
+    Ok(devnodes)
+}
+
+/// Initialize multiple blockdevs at once. This allows all of them
+/// to be checked for usability before writing to any of them.
+fn initialize(
+    pool_uuid: PoolUuid,
+    devices: HashMap<Device, &Path>,
+    mda_size: Sectors,
+    owned_devs: &HashSet<DevUuid>,
+    layout: MetadataLayout,
+    signature_policy: SignaturePolicy,
+) -> StratisResult<Vec<StratBlockDev>> {
     validate_mda_size(mda_size)?;
 
     let dev_infos = devices.into_iter().map(|(d, p)| (d, dev_info(p)));
 
-    let add_devs = filter_devs(dev_infos, pool_uuid, owned_devs)?;
+    let add_devs = filter_devs(dev_infos, pool_uuid, owned_devs, signature_policy)?;
 
     let mut bds: Vec<StratBlockDev> = Vec::new();
-    for (dev, (devnode, dev_size, mut f)) in add_devs {
-        let bda = BDA::initialize(
-            &mut f,
-            pool_uuid,
-            Uuid::new_v4(),
-            mda_size,
-            dev_size.sectors(),
-            Utc::now().timestamp() as u64,
-        );
+    for (dev, (devnode, dev_size, mut f, to_wipe)) in add_devs {
+        let bda = wipe_signatures(&mut f, &to_wipe).and_then(|_| {
+            BDA::initialize(
+                &mut f,
+                pool_uuid,
+                DevUuid::new_v4(),
+                mda_size,
+                dev_size.sectors(),
+                Utc::now().timestamp() as u64,
+                layout,
+            )
+        });
         if let Ok(bda) = bda {
             let hw_id = match hw_lookup(devnode) {
                 Ok(id) => id,
@@ -506,13 +1102,13 @@ mod tests {
     use std::fs::OpenOptions;
 
     use rand;
-    use uuid::Uuid;
 
     use super::super::super::tests::{loopbacked, real};
 
     use super::super::metadata::{StaticHeader, MIN_MDA_SECTORS};
     use super::super::setup::{find_all, get_metadata};
 
+    use super::super::super::super::types::BlockDevState;
     use super::super::super::cmd;
     use super::super::super::device::wipe_sectors;
 
@@ -540,7 +1136,13 @@ mod tests {
     /// After 2 Sectors have been allocated, that amount must also be included
     /// in balance.
     fn test_blockdevmgr_used(paths: &[&Path]) -> () {
-        let mut mgr = BlockDevMgr::initialize(Uuid::new_v4(), paths, MIN_MDA_SECTORS).unwrap();
+        let mut mgr = BlockDevMgr::initialize(
+            PoolUuid::new_v4(),
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         assert_eq!(mgr.avail_space() + mgr.metadata_size(), mgr.size());
 
         let allocated = Sectors(2);
@@ -583,8 +1185,16 @@ mod tests {
         cmd::create_ext3_fs(paths[index]).unwrap();
         cmd::udev_settle().unwrap();
 
-        let pool_uuid = Uuid::new_v4();
-        assert!(BlockDevMgr::initialize(pool_uuid, paths, MIN_MDA_SECTORS).is_err());
+        let pool_uuid = PoolUuid::new_v4();
+        assert!(
+            BlockDevMgr::initialize(
+                pool_uuid,
+                paths,
+                MIN_MDA_SECTORS,
+                MetadataLayout::default(),
+                SignaturePolicy::Refuse,
+            ).is_err()
+        );
         assert!(paths.iter().enumerate().all(|(i, path)| {
             let tmp = if i == index {
                 DevOwnership::Theirs(String::from(""))
@@ -599,7 +1209,15 @@ mod tests {
         wipe_sectors(paths[index], Sectors(0), MIN_MDA_SECTORS).unwrap();
         cmd::udev_settle().unwrap();
 
-        assert!(BlockDevMgr::initialize(pool_uuid, paths, MIN_MDA_SECTORS).is_ok());
+        assert!(
+            BlockDevMgr::initialize(
+                pool_uuid,
+                paths,
+                MIN_MDA_SECTORS,
+                MetadataLayout::default(),
+                SignaturePolicy::Refuse,
+            ).is_ok()
+        );
         cmd::udev_settle().unwrap();
 
         assert!(paths.iter().all(|path| {
@@ -646,22 +1264,42 @@ mod tests {
         assert!(paths.len() > 1);
         let (paths1, paths2) = paths.split_at(paths.len() / 2);
 
-        let uuid = Uuid::new_v4();
-        let uuid2 = Uuid::new_v4();
+        let uuid = PoolUuid::new_v4();
+        let uuid2 = PoolUuid::new_v4();
 
-        let mut bd_mgr = BlockDevMgr::initialize(uuid, paths1, MIN_MDA_SECTORS).unwrap();
+        let mut bd_mgr = BlockDevMgr::initialize(
+            uuid,
+            paths1,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         cmd::udev_settle().unwrap();
 
-        assert!(BlockDevMgr::initialize(uuid2, paths1, MIN_MDA_SECTORS).is_err());
+        assert!(
+            BlockDevMgr::initialize(
+                uuid2,
+                paths1,
+                MIN_MDA_SECTORS,
+                MetadataLayout::default(),
+                SignaturePolicy::Refuse,
+            ).is_err()
+        );
 
         let original_length = bd_mgr.block_devs.len();
-        assert!(bd_mgr.add(uuid, paths1).is_ok());
+        assert!(bd_mgr.add(uuid, paths1, SignaturePolicy::Refuse).is_ok());
         assert_eq!(bd_mgr.block_devs.len(), original_length);
 
-        BlockDevMgr::initialize(uuid, paths2, MIN_MDA_SECTORS).unwrap();
+        BlockDevMgr::initialize(
+            uuid,
+            paths2,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         cmd::udev_settle().unwrap();
 
-        assert!(bd_mgr.add(uuid, paths2).is_err());
+        assert!(bd_mgr.add(uuid, paths2, SignaturePolicy::Refuse).is_err());
     }
 
     #[test]
@@ -703,8 +1341,14 @@ mod tests {
 
         let (paths1, paths2) = paths.split_at(paths.len() / 2);
 
-        let uuid1 = Uuid::new_v4();
-        BlockDevMgr::initialize(uuid1, paths1, MIN_MDA_SECTORS).unwrap();
+        let uuid1 = PoolUuid::new_v4();
+        BlockDevMgr::initialize(
+            uuid1,
+            paths1,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
 
         cmd::udev_settle().unwrap();
         let pools = find_all().unwrap();
@@ -713,8 +1357,14 @@ mod tests {
         let devices = pools.get(&uuid1).expect("pools.contains_key() was true");
         assert_eq!(devices.len(), paths1.len());
 
-        let uuid2 = Uuid::new_v4();
-        BlockDevMgr::initialize(uuid2, paths2, MIN_MDA_SECTORS).unwrap();
+        let uuid2 = PoolUuid::new_v4();
+        BlockDevMgr::initialize(
+            uuid2,
+            paths2,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
 
         cmd::udev_settle().unwrap();
         let pools = find_all().unwrap();
@@ -754,8 +1404,14 @@ mod tests {
     /// Test that initialing devices claims all and that destroying
     /// them releases all.
     fn test_ownership(paths: &[&Path]) -> () {
-        let pool_uuid = Uuid::new_v4();
-        let mut bd_mgr = BlockDevMgr::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let pool_uuid = PoolUuid::new_v4();
+        let mut bd_mgr = BlockDevMgr::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
 
         cmd::udev_settle().unwrap();
 
@@ -794,4 +1450,211 @@ mod tests {
     pub fn travis_test_ownership() {
         loopbacked::test_with_spec(loopbacked::DeviceLimits::Range(1, 3, None), test_ownership);
     }
+
+    /// Verify that any blockdev may be removed, including the first, and
+    /// that removing one that is not there is an error.
+    fn test_remove_blockdevs(paths: &[&Path]) -> () {
+        let mut bd_mgr = BlockDevMgr::initialize(
+            PoolUuid::new_v4(),
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+
+        let uuids = bd_mgr
+            .blockdevs()
+            .iter()
+            .map(|&(uuid, _)| uuid)
+            .collect::<Vec<_>>();
+
+        bd_mgr.remove_blockdevs(&uuids[..1]).unwrap();
+        assert_eq!(bd_mgr.blockdevs().len(), paths.len() - 1);
+        assert!(bd_mgr.get_blockdev_by_uuid(uuids[0]).is_none());
+        assert!(bd_mgr.remove_blockdevs(&uuids[..1]).is_err());
+
+        bd_mgr.destroy_all().unwrap();
+    }
+
+    #[test]
+    pub fn loop_test_remove_blockdevs() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_remove_blockdevs,
+        );
+    }
+
+    #[test]
+    pub fn real_test_remove_blockdevs() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(1, None, None),
+            test_remove_blockdevs,
+        );
+    }
+
+    #[test]
+    pub fn travis_test_remove_blockdevs() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_remove_blockdevs,
+        );
+    }
+
+    /// Verify that a blockdev marked missing is marked only once, gives no
+    /// space to the pool, and is not written to when metadata is saved.
+    fn test_mark_missing(paths: &[&Path]) -> () {
+        let mut bd_mgr = BlockDevMgr::initialize(
+            PoolUuid::new_v4(),
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+        assert!(!bd_mgr.has_missing());
+
+        let (uuid, device, available) = {
+            let bd = &bd_mgr.block_devs[0];
+            (bd.uuid(), *bd.device(), bd.available())
+        };
+        let avail_space = bd_mgr.avail_space();
+
+        assert_eq!(bd_mgr.mark_missing(device), Some(uuid));
+        assert_eq!(bd_mgr.mark_missing(device), None);
+        assert!(bd_mgr.has_missing());
+        assert_eq!(
+            bd_mgr.get_blockdev_by_uuid(uuid).unwrap().state(),
+            BlockDevState::Missing
+        );
+        assert_eq!(bd_mgr.avail_space(), avail_space - available);
+
+        bd_mgr.save_state(b"{}", false, false).unwrap();
+        assert!(bd_mgr.last_update_time().is_some());
+        assert!(
+            bd_mgr
+                .get_blockdev_by_uuid(uuid)
+                .unwrap()
+                .last_update_time()
+                .is_none()
+        );
+
+        bd_mgr.destroy_all().unwrap();
+    }
+
+    #[test]
+    pub fn loop_test_mark_missing() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(2, 3, None),
+            test_mark_missing,
+        );
+    }
+
+    #[test]
+    pub fn real_test_mark_missing() {
+        real::test_with_spec(real::DeviceLimits::AtLeast(2, None, None), test_mark_missing);
+    }
+
+    /// Verify that saving metadata while the clock is behind the time of
+    /// metadata written in an earlier run is refused unless the metadata
+    /// time fallback is enabled, and that if it is, the metadata is stamped
+    /// just after the newer time.
+    fn test_clock_skew(paths: &[&Path]) -> () {
+        let mgr = BlockDevMgr::initialize(
+            PoolUuid::new_v4(),
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+        let future = Utc::now() + Duration::days(1);
+        let mut block_devs = mgr.block_devs;
+        for bd in &mut block_devs {
+            bd.save_state(&future, b"{}", false).unwrap();
+        }
+
+        let mut mgr = BlockDevMgr::new(block_devs, None);
+        assert_eq!(mgr.clock_skew().map(|(_, newest)| newest), Some(future));
+
+        set_metadata_time_fallback(false);
+        assert!(mgr.save_state(b"{}", false, false).is_err());
+        assert_eq!(mgr.last_update_time(), None);
+
+        set_metadata_time_fallback(true);
+        mgr.save_state(b"{}", false, false).unwrap();
+        assert_eq!(
+            mgr.last_update_time(),
+            Some(&(future + Duration::nanoseconds(1)))
+        );
+        set_metadata_time_fallback(false);
+    }
+
+    #[test]
+    pub fn loop_test_clock_skew() {
+        loopbacked::test_with_spec(loopbacked::DeviceLimits::Range(1, 3, None), test_clock_skew);
+    }
+
+    #[test]
+    pub fn real_test_clock_skew() {
+        real::test_with_spec(real::DeviceLimits::AtLeast(1, None, None), test_clock_skew);
+    }
+
+    /// Verify that while the metadata write interval has not elapsed, a
+    /// background save is held back as a whole, and written to as many
+    /// devices as a save at a client's request once it has elapsed, and
+    /// that a save at a client's request is never held back, and supersedes
+    /// a background save that was.
+    fn test_write_throttle(paths: &[&Path]) -> () {
+        let mut mgr = BlockDevMgr::initialize(
+            PoolUuid::new_v4(),
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+        let num_devs = cmp::min(paths.len(), MAX_NUM_TO_WRITE);
+        let written = |mgr: &BlockDevMgr| {
+            mgr.block_devs
+                .iter()
+                .filter(|bd| bd.last_update_time() == mgr.last_update_time())
+                .count()
+        };
+
+        mgr.save_state(b"{}", false, false).unwrap();
+        assert_eq!(written(&mgr), num_devs);
+        let first_time = mgr.last_update_time().cloned();
+
+        set_metadata_write_interval(3600);
+        assert!(mgr.block_devs.iter().all(|bd| bd.write_throttled(3600)));
+        mgr.save_state(b"{}", false, true).unwrap();
+        assert_eq!(mgr.last_update_time().cloned(), first_time);
+        assert!(mgr.save_deferred().unwrap());
+        assert_eq!(mgr.last_update_time().cloned(), first_time);
+
+        set_metadata_write_interval(0);
+        assert!(!mgr.save_deferred().unwrap());
+        assert!(mgr.last_update_time().cloned() > first_time);
+        assert_eq!(written(&mgr), num_devs);
+        let second_time = mgr.last_update_time().cloned();
+
+        set_metadata_write_interval(3600);
+        mgr.save_state(b"{}", false, true).unwrap();
+        assert_eq!(mgr.last_update_time().cloned(), second_time);
+        mgr.save_state(b"{}", false, false).unwrap();
+        assert!(mgr.last_update_time().cloned() > second_time);
+        assert_eq!(written(&mgr), num_devs);
+        assert!(!mgr.save_deferred().unwrap());
+        set_metadata_write_interval(0);
+    }
+
+    #[test]
+    pub fn loop_test_write_throttle() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(2, 3, None),
+            test_write_throttle,
+        );
+    }
+
+    #[test]
+    pub fn real_test_write_throttle() {
+        real::test_with_spec(real::DeviceLimits::AtLeast(2, None, None), test_write_throttle);
+    }
 }
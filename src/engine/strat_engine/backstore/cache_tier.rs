@@ -6,11 +6,14 @@
 
 use std::path::Path;
 
+use byteorder::{ByteOrder, LittleEndian};
+use crc::crc32;
+
 use devicemapper::{Sectors, IEC, SECTOR_SIZE};
 
 use stratis::{ErrorEnum, StratisError, StratisResult};
 
-use super::super::super::types::{BlockDevTier, DevUuid, PoolUuid};
+use super::super::super::types::{BlockDevTier, CacheType, DevUuid, PoolUuid, SignaturePolicy};
 
 use super::super::serde_structs::{BaseDevSave, BlockDevSave, CacheTierSave, Recordable};
 
@@ -25,6 +28,26 @@ use super::blockdevmgr::{coalesce_blkdevsegs, BlkDevSegment, BlockDevMgr, Segmen
 /// 1 Mi-sectors.
 const MAX_CACHE_SIZE: Sectors = Sectors(32 * IEC::Ti / SECTOR_SIZE as u64);
 
+/// A checksum of the layout of the cache and meta sub-devices as recorded
+/// in the metadata, so that a layout that does not agree with the one that
+/// was recorded is detected before a cache is set up on it.
+fn layout_checksum(allocs: &[Vec<BaseDevSave>]) -> u32 {
+    let mut buf = Vec::new();
+    let mut word = [0u8; 8];
+    for list in allocs {
+        LittleEndian::write_u64(&mut word, list.len() as u64);
+        buf.extend_from_slice(&word);
+        for seg in list {
+            buf.extend_from_slice(seg.parent.as_bytes());
+            LittleEndian::write_u64(&mut word, *seg.start);
+            buf.extend_from_slice(&word);
+            LittleEndian::write_u64(&mut word, *seg.length);
+            buf.extend_from_slice(&word);
+        }
+    }
+    crc32::checksum_castagnoli(&buf)
+}
+
 /// Handles the cache devices.
 #[derive(Debug)]
 pub struct CacheTier {
@@ -34,8 +57,15 @@ pub struct CacheTier {
     /// device.
     pub cache_segments: Vec<BlkDevSegment>,
     /// The list of segments granted by block_mgr and used by the metadata
-    /// device.
+    /// device. A writecache keeps its metadata on the cache device, so
+    /// this is empty if the cache is a writecache.
     pub meta_segments: Vec<BlkDevSegment>,
+    /// The DM target with which the cache is made.
+    pub cache_type: CacheType,
+    /// Whether the cache is on volatile devices, such as RAM disks, whose
+    /// contents do not survive a reboot. A volatile cache is always a
+    /// dm-cache in writethrough mode, so the origin is never behind it.
+    pub volatile: bool,
 }
 
 impl CacheTier {
@@ -68,12 +98,52 @@ impl CacheTier {
             ))
         };
 
-        let meta_segments = cache_tier_save.blockdev.allocs[1]
+        let allocs = &cache_tier_save.blockdev.allocs;
+        if allocs.len() != 2 {
+            let err_msg = format!(
+                "{} cache sub-devices recorded, expected 2; probable metadata corruption",
+                allocs.len()
+            );
+            return Err(StratisError::Engine(ErrorEnum::Error, err_msg));
+        }
+
+        // Metadata written by earlier versions records neither the target
+        // nor a checksum; those versions made only dm-cache caches.
+        let cache_type = match cache_tier_save.target.as_ref().map(|t| t.as_str()) {
+            None | Some("cache") => CacheType::Cache,
+            Some("writecache") => CacheType::WriteCache,
+            Some(target) => {
+                let err_msg = format!(
+                    "unknown cache target {}; probable metadata corruption",
+                    target
+                );
+                return Err(StratisError::Engine(ErrorEnum::Error, err_msg));
+            }
+        };
+
+
+        if let Some(checksum) = cache_tier_save.checksum {
+            if checksum != layout_checksum(allocs) {
+                let err_msg = "cache layout does not match checksum; probable metadata corruption";
+                return Err(StratisError::Engine(ErrorEnum::Error, err_msg.into()));
+            }
+        }
+
+        let volatile = cache_tier_save.volatile.unwrap_or(false);
+        if volatile && cache_type != CacheType::Cache {
+            let err_msg = format!(
+                "volatile cache recorded with target {}; probable metadata corruption",
+                cache_type
+            );
+            return Err(StratisError::Engine(ErrorEnum::Error, err_msg));
+        }
+
+        let meta_segments = allocs[1]
             .iter()
             .map(&mapper)
             .collect::<StratisResult<Vec<_>>>()?;
 
-        let cache_segments = cache_tier_save.blockdev.allocs[0]
+        let cache_segments = allocs[0]
             .iter()
             .map(&mapper)
             .collect::<StratisResult<Vec<_>>>()?;
@@ -82,6 +152,8 @@ impl CacheTier {
             block_mgr,
             meta_segments,
             cache_segments,
+            cache_type,
+            volatile,
         })
     }
 
@@ -89,22 +161,34 @@ impl CacheTier {
     /// corresponding to the specified paths and a pair of Boolean values.
     /// The first is true if the cache sub-device's segments were changed,
     /// the second is true if the meta sub-device's segments were changed.
-    /// Adds all additional space to cache sub-device.
+    /// Adds all additional space to cache sub-device, which is extended by
+    /// concatenating the new segments to it.
     /// WARNING: metadata changing event
     ///
     /// Return an error if the addition of the cachedevs would result in a
-    /// cache with a cache sub-device size greater than 32 TiB.
+    /// cache with a cache sub-device size greater than 32 TiB. The new
+    /// blockdevs are then wiped and removed from the tier again.
     ///
-    // FIXME: That all segments on the newly added device are added to the
-    // cache sub-device and none to the meta sub-device could lead to failure.
-    // Presumably, the size required for the meta sub-device varies directly
-    // with the size of cache sub-device.
+    /// Return an error if the cache is a writecache, as dm-writecache can
+    /// not extend its cache device.
+    ///
+    // The meta sub-device is never extended, as dm-cache can not grow its
+    // metadata device while it is in use. It is made large enough for a
+    // cache sub-device of MAX_CACHE_SIZE when the tier is made.
     pub fn add(
         &mut self,
         pool_uuid: PoolUuid,
         paths: &[&Path],
+        signature_policy: SignaturePolicy,
     ) -> StratisResult<(Vec<DevUuid>, (bool, bool))> {
-        let uuids = self.block_mgr.add(pool_uuid, paths)?;
+        if self.cache_type == CacheType::WriteCache {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                "The cache device of a writecache can not be extended".into(),
+            ));
+        }
+
+        let uuids = self.block_mgr.add(pool_uuid, paths, signature_policy)?;
 
         let avail_space = self.block_mgr.avail_space();
 
@@ -138,17 +222,34 @@ impl CacheTier {
         Ok((uuids, (true, false)))
     }
 
-    /// Setup a new CacheTier struct from the block_mgr.
+    /// Setup a new CacheTier struct from the block_mgr, for a cache made
+    /// with the given DM target, on volatile devices if volatile is true.
     ///
     /// Returns an error if the block devices passed would make the cache
-    /// sub-device too big.
+    /// sub-device of a dm-cache too big, or if a volatile cache is to be
+    /// made with a target other than dm-cache.
     ///
     /// WARNING: metadata changing event
-    pub fn new(mut block_mgr: BlockDevMgr) -> StratisResult<CacheTier> {
+    pub fn new(
+        mut block_mgr: BlockDevMgr,
+        cache_type: CacheType,
+        volatile: bool,
+    ) -> StratisResult<CacheTier> {
+        if volatile && cache_type != CacheType::Cache {
+            block_mgr.destroy_all()?;
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                "A volatile cache can only be made with the dm-cache target".into(),
+            ));
+        }
+
         let avail_space = block_mgr.avail_space();
 
         // FIXME: Come up with a better way to choose metadata device size
-        let meta_space = Sectors(IEC::Mi);
+        let meta_space = match cache_type {
+            CacheType::Cache => Sectors(IEC::Mi),
+            CacheType::WriteCache => Sectors(0),
+        };
 
         assert!(
             meta_space < avail_space,
@@ -157,7 +258,7 @@ impl CacheTier {
 
         // FIXME: This check will become unnecessary when cache metadata device
         // can be increased dynamically.
-        if avail_space - meta_space > MAX_CACHE_SIZE {
+        if cache_type == CacheType::Cache && avail_space - meta_space > MAX_CACHE_SIZE {
             block_mgr.destroy_all()?;
             return Err(StratisError::Engine(
                 ErrorEnum::Invalid,
@@ -179,12 +280,19 @@ impl CacheTier {
             block_mgr,
             meta_segments,
             cache_segments,
+            cache_type,
+            volatile,
         })
     }
 
-    /// Destroy the tier. Wipe its blockdevs.
-    pub fn destroy(&mut self) -> StratisResult<()> {
-        self.block_mgr.destroy_all()
+    /// Destroy the tier. Wipe its blockdevs, or, if keep_signatures is
+    /// true, keep their sigblocks, marked as those of a destroyed pool.
+    pub fn destroy(&mut self, keep_signatures: bool) -> StratisResult<()> {
+        if keep_signatures {
+            self.block_mgr.mark_all_destroyed()
+        } else {
+            self.block_mgr.destroy_all()
+        }
     }
 
     /// Get all the blockdevs belonging to this tier.
@@ -216,11 +324,16 @@ impl CacheTier {
 
 impl Recordable<CacheTierSave> for CacheTier {
     fn record(&self) -> CacheTierSave {
+        let allocs = vec![self.cache_segments.record(), self.meta_segments.record()];
+        let checksum = layout_checksum(&allocs);
         CacheTierSave {
             blockdev: BlockDevSave {
-                allocs: vec![self.cache_segments.record(), self.meta_segments.record()],
+                allocs,
                 devs: self.block_mgr.record(),
             },
+            target: Some(self.cache_type.to_string()),
+            checksum: Some(checksum),
+            volatile: if self.volatile { Some(true) } else { None },
         }
     }
 }
@@ -228,14 +341,33 @@ impl Recordable<CacheTierSave> for CacheTier {
 #[cfg(test)]
 mod tests {
 
-    use uuid::Uuid;
+    use std::fs::OpenOptions;
 
     use super::super::super::tests::{loopbacked, real};
 
-    use super::super::metadata::MIN_MDA_SECTORS;
+    use super::super::metadata::{MetadataLayout, StaticHeader, MIN_MDA_SECTORS};
 
     use super::*;
 
+    #[test]
+    /// Verify that the layout checksum changes if any segment is moved,
+    /// resized, or assigned to the other sub-device.
+    fn test_layout_checksum() {
+        let seg = |start, length| BaseDevSave {
+            parent: DevUuid::nil(),
+            start: Sectors(start),
+            length: Sectors(length),
+        };
+        let checksum = layout_checksum(&[vec![seg(0, 8), seg(16, 8)], vec![seg(8, 8)]]);
+        assert_eq!(
+            checksum,
+            layout_checksum(&[vec![seg(0, 8), seg(16, 8)], vec![seg(8, 8)]])
+        );
+        assert!(checksum != layout_checksum(&[vec![seg(0, 8), seg(16, 16)], vec![seg(8, 8)]]));
+        assert!(checksum != layout_checksum(&[vec![seg(0, 8), seg(24, 8)], vec![seg(8, 8)]]));
+        assert!(checksum != layout_checksum(&[vec![seg(0, 8)], vec![seg(16, 8), seg(8, 8)]]));
+    }
+
     /// Do basic testing of the cache. Make a new cache and test some
     /// expected properties, then add some additional blockdevs and test
     /// some more properties.
@@ -244,11 +376,17 @@ mod tests {
 
         let (paths1, paths2) = paths.split_at(paths.len() / 2);
 
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
 
-        let mgr = BlockDevMgr::initialize(pool_uuid, paths1, MIN_MDA_SECTORS).unwrap();
+        let mgr = BlockDevMgr::initialize(
+            pool_uuid,
+            paths1,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
 
-        let mut cache_tier = CacheTier::new(mgr).unwrap();
+        let mut cache_tier = CacheTier::new(mgr, CacheType::Cache, false).unwrap();
 
         // A cache tier w/ some devices and everything promptly allocated to
         // the tier.
@@ -269,7 +407,11 @@ mod tests {
         assert_eq!(cache_tier.block_mgr.avail_space(), Sectors(0));
         assert_eq!(size - metadata_size, allocated + cache_metadata_size);
 
-        let (_, (cache, meta)) = cache_tier.add(pool_uuid, paths2).unwrap();
+        let (_, (cache, meta)) = cache_tier.add(
+            pool_uuid,
+            paths2,
+            SignaturePolicy::Refuse,
+        ).unwrap();
         // TODO: Ultimately, it should be the case that meta can be true.
         assert!(cache);
         assert!(!meta);
@@ -287,7 +429,87 @@ mod tests {
             .sum::<Sectors>();
         assert_eq!(size - metadata_size, allocated + cache_metadata_size);
 
-        cache_tier.destroy().unwrap();
+        cache_tier.destroy(false).unwrap();
+    }
+
+    /// Make a cache tier from several blockdevs at once. Verify that its
+    /// cache sub-device is concatenated from segments on every one of them,
+    /// and that destroying the tier wipes them all.
+    fn cache_test_multiple(paths: &[&Path]) -> () {
+        assert!(paths.len() > 1);
+
+        let pool_uuid = PoolUuid::new_v4();
+
+        let mgr = BlockDevMgr::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+
+        let mut cache_tier = CacheTier::new(mgr, CacheType::Cache, false).unwrap();
+
+        let uuids = cache_tier
+            .blockdevs()
+            .iter()
+            .map(|&(uuid, _)| uuid)
+            .collect::<Vec<_>>();
+        assert_eq!(uuids.len(), paths.len());
+        assert!(uuids.iter().all(|uuid| {
+            cache_tier
+                .cache_segments
+                .iter()
+                .any(|seg| seg.uuid == *uuid)
+        }));
+        assert_eq!(
+            cache_tier
+                .meta_segments
+                .iter()
+                .map(|x| x.segment.length)
+                .sum::<Sectors>(),
+            Sectors(IEC::Mi)
+        );
+
+        let cache_tier_save = cache_tier.record();
+        assert_eq!(
+            cache_tier_save.blockdev.allocs[0].len(),
+            cache_tier.cache_segments.len()
+        );
+
+        cache_tier.destroy(false).unwrap();
+        assert!(paths.iter().all(|path| {
+            StaticHeader::device_identifiers(&mut OpenOptions::new()
+                .read(true)
+                .open(path)
+                .unwrap())
+                .unwrap()
+                .is_none()
+        }));
+    }
+
+    #[test]
+    pub fn loop_cache_test_multiple() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(2, 3, None),
+            cache_test_multiple,
+        );
+    }
+
+    #[test]
+    pub fn real_cache_test_multiple() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(2, None, None),
+            cache_test_multiple,
+        );
+    }
+
+    #[test]
+    pub fn travis_cache_test_multiple() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(2, 3, None),
+            cache_test_multiple,
+        );
     }
 
     #[test]
@@ -4,9 +4,15 @@
 
 // Code to handle cleanup after a failed operation.
 
+use std::fs::OpenOptions;
+use std::path::Path;
+
 use stratis::{ErrorEnum, StratisError, StratisResult};
 
+use super::super::super::types::{DevUuid, PoolUuid};
+
 use super::blockdev::StratBlockDev;
+use super::metadata::{StaticHeader, BDA};
 
 /// Wipe some blockdevs of their identifying headers.
 /// Return an error if any of the blockdevs could not be wiped.
@@ -30,3 +36,63 @@ pub fn wipe_blockdevs(blockdevs: &[StratBlockDev]) -> StratisResult<()> {
         Err(StratisError::Engine(ErrorEnum::Error, err_msg))
     }
 }
+
+/// Mark some blockdevs as those of a pool destroyed at destroyed_time,
+/// keeping their sigblocks.
+/// Return an error if any of the blockdevs could not be marked.
+/// If an error occurs while marking a blockdev, attempt to mark all remaining.
+pub fn mark_blockdevs_destroyed(
+    blockdevs: &mut [StratBlockDev],
+    destroyed_time: u64,
+) -> StratisResult<()> {
+    let mut unmarked_devnodes = Vec::new();
+
+    for bd in blockdevs {
+        if bd.mark_destroyed(destroyed_time).is_err() {
+            unmarked_devnodes.push(bd.devnode.to_owned());
+        }
+    }
+
+    if unmarked_devnodes.is_empty() {
+        Ok(())
+    } else {
+        let err_msg = format!(
+            "Failed to mark devnodes of destroyed pool: {:?}",
+            unmarked_devnodes
+        );
+        Err(StratisError::Engine(ErrorEnum::Error, err_msg))
+    }
+}
+
+/// Wipe the sigblock from the device at devnode, if it was kept when the
+/// device's pool was destroyed. Return true if the device was wiped, false
+/// if it was not such a device, in which case it is left untouched.
+pub fn wipe_destroyed_device(devnode: &Path) -> StratisResult<bool> {
+    let mut f = OpenOptions::new().read(true).write(true).open(devnode)?;
+    if StaticHeader::destroyed_identifiers(&mut f)?.is_none() {
+        return Ok(false);
+    }
+    BDA::wipe(&mut f)?;
+    Ok(true)
+}
+
+/// Wipe the sigblock from the device at devnode, if it claims to belong to
+/// the pool, but is not among its blockdevs, as a device may whose addition
+/// to the pool was cut short. Return true if the device was wiped, false if
+/// it was not such a device, in which case it is left untouched.
+pub fn wipe_stray_device(
+    devnode: &Path,
+    pool_uuid: PoolUuid,
+    blockdevs: &[DevUuid],
+) -> StratisResult<bool> {
+    let mut f = OpenOptions::new().read(true).write(true).open(devnode)?;
+    match StaticHeader::device_identifiers(&mut f)? {
+        Some((dev_pool_uuid, dev_uuid))
+            if dev_pool_uuid == pool_uuid && !blockdevs.contains(&dev_uuid) =>
+        {
+            BDA::wipe(&mut f)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
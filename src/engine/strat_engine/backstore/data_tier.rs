@@ -4,13 +4,19 @@
 
 // Code to handle the backing store of a pool.
 
+use std::cmp;
+use std::mem;
 use std::path::Path;
 
+use chrono::{DateTime, Utc};
+
 use devicemapper::Sectors;
 
 use stratis::{ErrorEnum, StratisError, StratisResult};
 
-use super::super::super::types::{BlockDevTier, DevUuid, PoolUuid};
+use super::super::super::types::{
+    AddDataDevsPlan, AllocationStrategy, BlockDevTier, DevUuid, PoolUuid, SignaturePolicy,
+};
 
 use super::super::serde_structs::{BaseDevSave, BlockDevSave, DataTierSave, Recordable};
 
@@ -28,8 +34,24 @@ pub struct DataTier {
 
 impl DataTier {
     /// Setup a previously existing data layer from the block_mgr and
-    /// previously allocated segments.
-    pub fn setup(block_mgr: BlockDevMgr, data_tier_save: &DataTierSave) -> StratisResult<DataTier> {
+    /// previously allocated segments. Space is allocated by the recorded
+    /// strategy; if the strategy is not known, as it may not be to an
+    /// earlier version, by the fill-first strategy.
+    pub fn setup(
+        mut block_mgr: BlockDevMgr,
+        data_tier_save: &DataTierSave,
+    ) -> StratisResult<DataTier> {
+        if let Some(ref name) = data_tier_save.allocation_strategy {
+            match AllocationStrategy::from_name(name) {
+                Some(strategy) => block_mgr.set_allocation_strategy(strategy),
+                None => warn!(
+                    "unknown allocation strategy {}; allocating by {}",
+                    name,
+                    AllocationStrategy::FillFirst
+                ),
+            }
+        }
+
         let uuid_to_devno = block_mgr.uuid_to_devno();
         let mapper = |ld: &BaseDevSave| -> StratisResult<BlkDevSegment> {
             let parent = ld.parent;
@@ -70,8 +92,19 @@ impl DataTier {
     /// Add the given paths to self. Return UUIDs of the new blockdevs
     /// corresponding to the specified paths.
     /// WARNING: metadata changing event
-    pub fn add(&mut self, pool_uuid: PoolUuid, paths: &[&Path]) -> StratisResult<Vec<DevUuid>> {
-        self.block_mgr.add(pool_uuid, paths)
+    pub fn add(
+        &mut self,
+        pool_uuid: PoolUuid,
+        paths: &[&Path],
+        signature_policy: SignaturePolicy,
+    ) -> StratisResult<Vec<DevUuid>> {
+        self.block_mgr.add(pool_uuid, paths, signature_policy)
+    }
+
+    /// Determine the layout of the data tier if the blockdevs specified
+    /// by paths were added, without adding them.
+    pub fn plan_add(&self, pool_uuid: PoolUuid, paths: &[&Path]) -> StratisResult<AddDataDevsPlan> {
+        self.block_mgr.plan_add(pool_uuid, paths)
     }
 
     /// Allocate at least request sectors from unallocated segments in
@@ -94,6 +127,97 @@ impl DataTier {
         }
     }
 
+    /// Move the first part, no longer than max_length, of the first segment
+    /// allocated on any of the blockdevs specified by uuids to space
+    /// allocated from the other blockdevs, or only from the blockdev target
+    /// if one is given. The data is not copied.
+    /// Return the segments as they were before, the part moved, and the
+    /// segments that it was moved to, or None if nothing is allocated on
+    /// the blockdevs. Return an error if there is not enough space on the
+    /// blockdevs that may be allocated from.
+    /// WARNING: metadata changing event
+    pub fn relocate(
+        &mut self,
+        uuids: &[DevUuid],
+        max_length: Sectors,
+        target: Option<DevUuid>,
+    ) -> StratisResult<Option<(Vec<BlkDevSegment>, BlkDevSegment, Vec<BlkDevSegment>)>> {
+        let index = match self.segments
+            .iter()
+            .position(|seg| uuids.contains(&seg.uuid))
+        {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let old = self.segments[index].clone();
+        let length = cmp::min(old.segment.length, max_length);
+        let except: Vec<DevUuid> = match target {
+            Some(target) => self.blockdevs()
+                .iter()
+                .map(|&(uuid, _)| uuid)
+                .filter(|&uuid| uuid != target)
+                .collect(),
+            None => uuids.to_vec(),
+        };
+        let new = self.block_mgr
+            .alloc_space_except(&[length], &except)
+            .ok_or_else(|| {
+                StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!(
+                        "no space for {} sectors on blockdevs other than those moved from",
+                        length
+                    ),
+                )
+            })?
+            .pop()
+            .expect("one list of segments for one size");
+
+        let moved = BlkDevSegment::new(
+            old.uuid,
+            Segment::new(old.segment.device, old.segment.start, length),
+        );
+        let mut rest = Vec::new();
+        if length < old.segment.length {
+            rest.push(BlkDevSegment::new(
+                old.uuid,
+                Segment::new(
+                    old.segment.device,
+                    old.segment.start + length,
+                    old.segment.length - length,
+                ),
+            ));
+        }
+        rest.extend_from_slice(&self.segments[index + 1..]);
+        let segments = coalesce_blkdevsegs(
+            &coalesce_blkdevsegs(&self.segments[..index], &new),
+            &rest,
+        );
+
+        let previous = mem::replace(&mut self.segments, segments);
+        Ok(Some((previous, moved, new)))
+    }
+
+    /// The sum of the lengths of the segments allocated on the blockdevs
+    /// specified by uuids.
+    pub fn allocated_on(&self, uuids: &[DevUuid]) -> Sectors {
+        self.segments
+            .iter()
+            .filter(|seg| uuids.contains(&seg.uuid))
+            .map(|seg| seg.segment.length)
+            .sum()
+    }
+
+    /// Remove the blockdevs specified by uuids and erase their metadata.
+    ///
+    /// Precondition: nothing is allocated on the blockdevs.
+    /// WARNING: metadata changing event
+    pub fn remove(&mut self, uuids: &[DevUuid]) -> StratisResult<()> {
+        assert_eq!(self.allocated_on(uuids), Sectors(0));
+        self.block_mgr.remove_blockdevs(uuids)
+    }
+
     /// The sum of the lengths of all the sectors that have been mapped to an
     /// upper device.
     #[cfg(test)]
@@ -119,15 +243,43 @@ impl DataTier {
         self.size() - self.metadata_size()
     }
 
-    /// Destroy the store. Wipe its blockdevs.
-    pub fn destroy(&mut self) -> StratisResult<()> {
-        self.block_mgr.destroy_all()
+    /// Destroy the store. Wipe its blockdevs, or, if keep_signatures is
+    /// true, keep their sigblocks, marked as those of a destroyed pool.
+    pub fn destroy(&mut self, keep_signatures: bool) -> StratisResult<()> {
+        if keep_signatures {
+            self.block_mgr.mark_all_destroyed()
+        } else {
+            self.block_mgr.destroy_all()
+        }
     }
 
     /// Save the given state to the devices. This action bypasses the DM
-    /// device entirely.
-    pub fn save_state(&mut self, metadata: &[u8]) -> StratisResult<()> {
-        self.block_mgr.save_state(metadata)
+    /// device entirely. If throttle is true, the save is a background one,
+    /// subject to the metadata write interval.
+    pub fn save_state(
+        &mut self,
+        metadata: &[u8],
+        verify: bool,
+        throttle: bool,
+    ) -> StratisResult<()> {
+        self.block_mgr.save_state(metadata, verify, throttle)
+    }
+
+    /// Write the background save held back by the metadata write interval,
+    /// if there is one. Return true if a save is still held back.
+    pub fn save_deferred(&mut self) -> StratisResult<bool> {
+        self.block_mgr.save_deferred()
+    }
+
+    /// The time at which metadata was last saved, if ever.
+    pub fn last_update_time(&self) -> Option<&DateTime<Utc>> {
+        self.block_mgr.last_update_time()
+    }
+
+    /// If the clock reads earlier than the time of the newest metadata
+    /// written, the current time and that time.
+    pub fn clock_skew(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        self.block_mgr.clock_skew()
     }
 
     /// Lookup an immutable blockdev by its Stratis UUID.
@@ -164,6 +316,10 @@ impl Recordable<DataTierSave> for DataTier {
                 allocs: vec![self.segments.record()],
                 devs: self.block_mgr.record(),
             },
+            allocation_strategy: match self.block_mgr.allocation_strategy() {
+                AllocationStrategy::FillFirst => None,
+                strategy => Some(strategy.to_string()),
+            },
         }
     }
 }
@@ -171,11 +327,9 @@ impl Recordable<DataTierSave> for DataTier {
 #[cfg(test)]
 mod tests {
 
-    use uuid::Uuid;
-
     use super::super::super::tests::{loopbacked, real};
 
-    use super::super::metadata::MIN_MDA_SECTORS;
+    use super::super::metadata::{MetadataLayout, MIN_MDA_SECTORS};
 
     use super::*;
 
@@ -186,9 +340,15 @@ mod tests {
         assert!(paths.len() > 1);
         let (paths1, paths2) = paths.split_at(paths.len() / 2);
 
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
 
-        let mgr = BlockDevMgr::initialize(pool_uuid, paths1, MIN_MDA_SECTORS).unwrap();
+        let mgr = BlockDevMgr::initialize(
+            pool_uuid,
+            paths1,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
 
         let mut data_tier = DataTier::new(mgr);
 
@@ -211,7 +371,7 @@ mod tests {
         assert_eq!(data_tier.size(), size);
         allocated = data_tier.allocated();
 
-        data_tier.add(pool_uuid, paths2).unwrap();
+        data_tier.add(pool_uuid, paths2, SignaturePolicy::Refuse).unwrap();
 
         // A data tier w/ additional blockdevs added
         assert!(data_tier.size() > size);
@@ -225,7 +385,83 @@ mod tests {
         assert!(data_tier.allocated() >= request_amount + last_request_amount);
         assert_eq!(data_tier.size(), size);
 
-        data_tier.destroy().unwrap();
+        data_tier.destroy(false).unwrap();
+    }
+
+    /// Allocate some space, then move all that is on the blockdev first
+    /// allocated from to the others, a part at a time, and remove it. If
+    /// there are more than two blockdevs, the data is all moved to the last.
+    fn test_relocate(paths: &[&Path]) -> () {
+        assert!(paths.len() > 1);
+
+        let mgr = BlockDevMgr::initialize(
+            PoolUuid::new_v4(),
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+
+        let mut data_tier = DataTier::new(mgr);
+        let request_amount = data_tier.blockdevs()[0].1.available() / 2usize;
+        assert!(data_tier.alloc(request_amount));
+        let allocated = data_tier.allocated();
+        let first = data_tier.segments[0].uuid;
+        assert!(data_tier.allocated_on(&[first]) != Sectors(0));
+
+        let target = if paths.len() > 2 {
+            data_tier
+                .blockdevs()
+                .iter()
+                .map(|&(uuid, _)| uuid)
+                .filter(|&uuid| uuid != first)
+                .last()
+        } else {
+            None
+        };
+
+        let max_length = request_amount / 3usize;
+        while let Some((previous, moved, new)) = data_tier
+            .relocate(&[first], max_length, target)
+            .unwrap()
+        {
+            assert!(moved.segment.length <= max_length);
+            assert_eq!(
+                new.iter().map(|seg| seg.segment.length).sum::<Sectors>(),
+                moved.segment.length
+            );
+            assert!(new.iter().all(|seg| seg.uuid != first));
+            if let Some(target) = target {
+                assert!(new.iter().all(|seg| seg.uuid == target));
+            }
+            assert_eq!(
+                previous.iter().map(|seg| seg.segment.length).sum::<Sectors>(),
+                allocated
+            );
+            assert_eq!(data_tier.allocated(), allocated);
+        }
+        assert_eq!(data_tier.allocated_on(&[first]), Sectors(0));
+
+        data_tier.remove(&[first]).unwrap();
+        assert_eq!(data_tier.blockdevs().len(), paths.len() - 1);
+        assert_eq!(data_tier.allocated(), allocated);
+
+        data_tier.destroy(false).unwrap();
+    }
+
+    #[test]
+    pub fn loop_test_relocate() {
+        loopbacked::test_with_spec(loopbacked::DeviceLimits::Range(2, 3, None), test_relocate);
+    }
+
+    #[test]
+    pub fn real_test_relocate() {
+        real::test_with_spec(real::DeviceLimits::AtLeast(2, None, None), test_relocate);
+    }
+
+    #[test]
+    pub fn travis_test_relocate() {
+        loopbacked::test_with_spec(loopbacked::DeviceLimits::Range(2, 3, None), test_relocate);
     }
 
     #[test]
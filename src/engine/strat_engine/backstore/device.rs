@@ -5,9 +5,9 @@
 // Functions for dealing with devices.
 
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
+use std::fs::{self, File, OpenOptions};
 use std::os::unix::prelude::AsRawFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use devicemapper::{devnode_to_devno, Bytes, Device};
 use stratis::{ErrorEnum, StratisError, StratisResult};
@@ -47,9 +47,15 @@ pub fn resolve_devices<'a>(paths: &'a [&Path]) -> StratisResult<HashMap<Device,
     Ok(map)
 }
 
+/// The signature reported for a device that is a path of a multipath device.
+pub const MULTIPATH_SIGNATURE: &str = "multipath path";
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum DevOwnership {
     Ours(PoolUuid, DevUuid),
+    // The sigblock was kept when the pool to which the device belonged was
+    // destroyed. The device belongs to no pool until it is wiped.
+    Destroyed(PoolUuid, DevUuid),
     Unowned,
     Theirs(String), // String is something useful to give back to end user about what's on device
 }
@@ -79,6 +85,17 @@ fn signature(device: &HashMap<String, String>) -> String {
     }
 }
 
+/// Determine the ownership of a device from its Stratis sigblock.
+/// Return None if it has no sigblock.
+fn sigblock_ownership(devnode: &Path) -> StratisResult<Option<DevOwnership>> {
+    let mut f = OpenOptions::new().read(true).open(devnode)?;
+    if let Some((pool_uuid, device_uuid)) = StaticHeader::device_identifiers(&mut f)? {
+        return Ok(Some(DevOwnership::Ours(pool_uuid, device_uuid)));
+    }
+    Ok(StaticHeader::destroyed_identifiers(&mut f)?
+        .map(|(pool_uuid, device_uuid, _)| DevOwnership::Destroyed(pool_uuid, device_uuid)))
+}
+
 /// Determine what a block device is used for.
 pub fn identify(devnode: &Path) -> StratisResult<DevOwnership> {
     if let Some(device) = get_udev_block_device(devnode)? {
@@ -86,31 +103,19 @@ pub fn identify(devnode: &Path) -> StratisResult<DevOwnership> {
             // The device is either really empty or we are running on a distribution that hasn't
             // picked up the latest libblkid, lets read down to the device and find out for sure.
             // TODO: At some point in the future we can remove this and just return Unowned.
-            if let Some((pool_uuid, device_uuid)) = StaticHeader::device_identifiers(
-                &mut OpenOptions::new().read(true).open(&devnode)?,
-            )? {
-                Ok(DevOwnership::Ours(pool_uuid, device_uuid))
-            } else {
-                Ok(DevOwnership::Unowned)
-            }
+            Ok(sigblock_ownership(devnode)?.unwrap_or(DevOwnership::Unowned))
         } else if device
             .get("DM_MULTIPATH_DEVICE_PATH")
             .map_or(false, |v| v == "1")
         {
-            Ok(DevOwnership::Theirs(String::from("multipath path")))
+            Ok(DevOwnership::Theirs(String::from(MULTIPATH_SIGNATURE)))
         } else if device.contains_key("ID_FS_TYPE") && device["ID_FS_TYPE"] == "stratis" {
             // Device is ours, but we don't get everything we need from udev db, lets go to disk.
-            if let Some((pool_uuid, device_uuid)) = StaticHeader::device_identifiers(
-                &mut OpenOptions::new().read(true).open(&devnode)?,
-            )? {
-                Ok(DevOwnership::Ours(pool_uuid, device_uuid))
-            } else {
-                // In this case the udev db says it's ours, but our check says otherwise.  We should
-                // trust ourselves.  Should we raise an error here?
-                Ok(DevOwnership::Theirs(String::from(
-                    "Udev db says stratis, disk meta says no",
-                )))
-            }
+            // If the udev db says it's ours, but our check says otherwise, we should
+            // trust ourselves.  Should we raise an error here?
+            Ok(sigblock_ownership(devnode)?.unwrap_or_else(|| {
+                DevOwnership::Theirs(String::from("Udev db says stratis, disk meta says no"))
+            }))
         } else {
             Ok(DevOwnership::Theirs(signature(&device)))
         }
@@ -126,6 +131,20 @@ pub fn identify(devnode: &Path) -> StratisResult<DevOwnership> {
     }
 }
 
+/// Determine whether the device is rotational, from the queue attributes
+/// in sysfs. A partition has no queue of its own, so fall back on the
+/// queue of its parent. Return None if the attribute can not be read.
+pub fn is_rotational(device: Device) -> Option<bool> {
+    let sysfs_path = PathBuf::from(format!("/sys/dev/block/{}", device));
+    [
+        sysfs_path.join("queue/rotational"),
+        sysfs_path.join("../queue/rotational"),
+    ].iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .next()
+        .map(|value| value.trim() == "1")
+}
+
 /// Determine if devnode is a Stratis device. Return the device's Stratis
 /// pool UUID if it belongs to Stratis.
 pub fn is_stratis_device(devnode: &Path) -> StratisResult<Option<(PoolUuid, DevUuid)>> {
@@ -135,6 +154,43 @@ pub fn is_stratis_device(devnode: &Path) -> StratisResult<Option<(PoolUuid, DevU
     }
 }
 
+/// The properties of the device at devnode if it is a Stratis device, as
+/// the Stratis prober of libblkid reports them, under the names that udev
+/// gives them, so that udev rules and installers may identify Stratis
+/// devices as stratisd does. The name of the device's pool is given as
+/// well, if its sigblock records one. The device is only read, never
+/// written.
+/// Return None if the device is not a Stratis device.
+pub fn probe_device(devnode: &Path) -> StratisResult<Option<Vec<(&'static str, String)>>> {
+    let header = match StaticHeader::probe(&mut File::open(devnode)?)? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let dev_uuid = header.dev_uuid().hyphenated().to_string();
+    let mut properties = vec![
+        ("ID_FS_TYPE", "stratis".to_owned()),
+        ("ID_FS_USAGE", "raid".to_owned()),
+        ("ID_FS_UUID", dev_uuid.clone()),
+        ("ID_FS_UUID_ENC", dev_uuid),
+        (
+            "ID_FS_POOL_UUID",
+            header.pool_uuid().hyphenated().to_string(),
+        ),
+        (
+            "ID_FS_BLOCKDEV_SECTORS",
+            (*header.blkdev_size()).to_string(),
+        ),
+        (
+            "ID_FS_BLOCKDEV_INITTIME",
+            header.initialization_time().to_string(),
+        ),
+    ];
+    if let Some(pool_name) = header.pool_name() {
+        properties.push(("ID_FS_POOL_NAME", pool_name.to_owned()));
+    }
+    Ok(Some(properties))
+}
+
 #[cfg(test)]
 mod test {
     use std::path::Path;
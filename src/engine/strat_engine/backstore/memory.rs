@@ -0,0 +1,311 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// A stand-in for the devices of a pool that holds their contents in memory.
+// The metadata path of a pool, from writing the BDAs of a new pool, through
+// saving, finding, and loading its metadata, to wiping its devices when it
+// is destroyed, can be run against it without root privileges, loop
+// devices, or devicemapper.
+
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json;
+
+use devicemapper::{Sectors, IEC};
+
+use stratis::StratisResult;
+
+use super::super::super::types::{ClonedDevice, DevUuid, PoolUuid};
+use super::super::serde_structs::{
+    BackstoreSave, BaseBlockDevSave, BaseDevSave, BlockDevSave, CapSave, DataTierSave,
+    FlexDevsSave, PoolSave, ThinPoolDevSave,
+};
+use super::super::thinpool::DATA_BLOCK_SIZE;
+
+use super::metadata::{MetadataLayout, StaticHeader, BDA, MIN_MDA_SECTORS};
+use super::setup::{find_clones_from, get_metadata_from};
+
+/// The size of each flex device in the metadata recorded for a pool.
+const FLEX_DEV_SIZE: Sectors = Sectors(IEC::Ki);
+
+/// A device whose contents are held in memory.
+struct MemoryDevice {
+    devnode: PathBuf,
+    data: Cursor<Vec<u8>>,
+    bda: Option<BDA>,
+}
+
+/// The devices of a pool, held in memory.
+pub struct MemoryPool {
+    pool_uuid: PoolUuid,
+    devices: Vec<MemoryDevice>,
+    last_update_time: Option<DateTime<Utc>>,
+}
+
+impl MemoryPool {
+    /// Make a new pool from zeroed devices of the given sizes, writing a
+    /// BDA to each.
+    pub fn initialize(sizes: &[Sectors]) -> StratisResult<MemoryPool> {
+        let pool_uuid = PoolUuid::new_v4();
+        let mut devices = Vec::new();
+        for (index, size) in sizes.iter().enumerate() {
+            let mut data = Cursor::new(vec![0u8; *size.bytes() as usize]);
+            let bda = BDA::initialize(
+                &mut data,
+                pool_uuid,
+                DevUuid::new_v4(),
+                MIN_MDA_SECTORS,
+                *size,
+                Utc::now().timestamp() as u64,
+                MetadataLayout::default(),
+            )?;
+            devices.push(MemoryDevice {
+                devnode: PathBuf::from(format!("/dev/memory{}", index)),
+                data,
+                bda: Some(bda),
+            });
+        }
+        Ok(MemoryPool {
+            pool_uuid,
+            devices,
+            last_update_time: None,
+        })
+    }
+
+    /// The metadata of a pool with the given name made up of these
+    /// devices. All of the space on each device beyond its BDA is allocated
+    /// to the cap device, and the flex devices are laid out in it in order.
+    pub fn record(&self, name: &str) -> PoolSave {
+        let bdas = self.devices
+            .iter()
+            .filter_map(|dev| dev.bda.as_ref())
+            .collect::<Vec<_>>();
+        let allocs = bdas.iter()
+            .map(|bda| BaseDevSave {
+                parent: bda.dev_uuid(),
+                start: bda.size(),
+                length: bda.dev_size() - bda.size(),
+            })
+            .collect::<Vec<_>>();
+        let cap_size = allocs.iter().map(|alloc| alloc.length).sum::<Sectors>();
+        PoolSave {
+            name: name.to_owned(),
+            backstore: BackstoreSave {
+                data_tier: DataTierSave {
+                    blockdev: BlockDevSave {
+                        allocs: vec![allocs],
+                        devs: bdas.iter()
+                            .map(|bda| BaseBlockDevSave {
+                                uuid: bda.dev_uuid(),
+                                user_info: None,
+                                hardware_info: None,
+                            })
+                            .collect(),
+                    },
+                    allocation_strategy: None,
+                },
+                cap: CapSave {
+                    allocs: vec![(Sectors(0), cap_size)],
+                },
+                cache_tier: None,
+                raid: None,
+            },
+            flex_devs: FlexDevsSave {
+                meta_dev: vec![(Sectors(0), FLEX_DEV_SIZE)],
+                thin_meta_dev: vec![(FLEX_DEV_SIZE, FLEX_DEV_SIZE)],
+                thin_data_dev: vec![(FLEX_DEV_SIZE * 3u64, cap_size - FLEX_DEV_SIZE * 3u64)],
+                thin_meta_dev_spare: vec![(FLEX_DEV_SIZE * 2u64, FLEX_DEV_SIZE)],
+            },
+            thinpool_dev: ThinPoolDevSave {
+                data_block_size: DATA_BLOCK_SIZE,
+                overcommit_cap: None,
+                default_fs_size: None,
+                usage_thresholds: None,
+                warn_watermark: None,
+                extend_watermark: None,
+                fs_limit: None,
+                snapshot_schedules: None,
+            },
+            verify_writes: None,
+            created_by: None,
+            worker_priority: None,
+            encrypted: None,
+            creation_params: None,
+        }
+    }
+
+    /// Save the metadata to every device that has a BDA. As in the
+    /// BlockDevMgr, the metadata is stamped with a time later than that of
+    /// the previous save, even if the clock has not advanced.
+    pub fn save(&mut self, pool_save: &PoolSave) -> StratisResult<()> {
+        let data = serde_json::to_string(pool_save)?;
+        let current_time = Utc::now();
+        let stamp_time = match self.last_update_time {
+            Some(time) if current_time <= time => time + Duration::nanoseconds(1),
+            _ => current_time,
+        };
+        for dev in &mut self.devices {
+            if let Some(ref mut bda) = dev.bda {
+                bda.save_state(&stamp_time, data.as_bytes(), true, &mut dev.data)?;
+            }
+        }
+        self.last_update_time = Some(stamp_time);
+        Ok(())
+    }
+
+    /// Discard everything known about the devices except their contents,
+    /// and set up their BDAs again from those contents, as on a restart.
+    pub fn reload(&mut self) -> StratisResult<()> {
+        for dev in &mut self.devices {
+            dev.bda = BDA::load(&mut dev.data)?;
+        }
+        self.last_update_time = self.devices
+            .iter()
+            .filter_map(|dev| dev.bda.as_ref().and_then(|bda| bda.last_update_time()))
+            .max()
+            .cloned();
+        Ok(())
+    }
+
+    /// The device nodes of the devices identified as belonging to this
+    /// pool, as by find_all().
+    pub fn find(&mut self) -> StratisResult<Vec<PathBuf>> {
+        let mut found = Vec::new();
+        for dev in &mut self.devices {
+            if let Some((pool_uuid, _)) = StaticHeader::device_identifiers(&mut dev.data)? {
+                if pool_uuid == self.pool_uuid {
+                    found.push(dev.devnode.clone());
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// The most recent metadata on the devices, as by get_metadata().
+    pub fn load(&mut self) -> StratisResult<Option<PoolSave>> {
+        let mut devices = self.devices
+            .iter_mut()
+            .map(|dev| (dev.devnode.as_path(), &mut dev.data))
+            .collect::<Vec<(&Path, _)>>();
+        get_metadata_from(self.pool_uuid, &mut devices)
+    }
+
+    /// Add a copy of the device at index, as made block by block.
+    pub fn clone_device(&mut self, index: usize) -> StratisResult<()> {
+        let mut data = Cursor::new(self.devices[index].data.get_ref().clone());
+        let bda = BDA::load(&mut data)?;
+        let devnode = PathBuf::from(format!("/dev/memory{}", self.devices.len()));
+        self.devices.push(MemoryDevice { devnode, data, bda });
+        Ok(())
+    }
+
+    /// The devices that are clones of others, as by find_clones().
+    pub fn clones(&mut self) -> StratisResult<Vec<ClonedDevice>> {
+        let mut devices = self.devices
+            .iter_mut()
+            .map(|dev| (dev.devnode.as_path(), &mut dev.data))
+            .collect::<Vec<(&Path, _)>>();
+        find_clones_from(self.pool_uuid, &mut devices, &HashSet::new())
+    }
+
+    /// Wipe the BDA from each device, as when the pool is destroyed.
+    pub fn destroy(&mut self) -> StratisResult<()> {
+        for dev in &mut self.devices {
+            BDA::wipe(&mut dev.data)?;
+            dev.bda = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The size of each device, large enough for a BDA and the flex devices.
+    const DEVICE_SIZE: Sectors = Sectors(16 * IEC::Ki);
+
+    #[test]
+    /// Verify that a pool's metadata can be saved, renamed, and found again
+    /// after a restart, and that once the pool is destroyed neither its
+    /// devices nor its metadata are found.
+    fn test_lifecycle() {
+        let mut pool = MemoryPool::initialize(&[DEVICE_SIZE; 3]).unwrap();
+        assert_eq!(pool.find().unwrap().len(), 3);
+        assert_eq!(pool.load().unwrap(), None);
+
+        let pool_save = pool.record("name");
+        pool.save(&pool_save).unwrap();
+        assert_eq!(pool.load().unwrap(), Some(pool_save));
+
+        let pool_save = pool.record("new_name");
+        pool.save(&pool_save).unwrap();
+        pool.reload().unwrap();
+        assert_eq!(pool.find().unwrap().len(), 3);
+        assert_eq!(pool.load().unwrap(), Some(pool_save));
+
+        pool.destroy().unwrap();
+        assert_eq!(pool.find().unwrap(), Vec::<PathBuf>::new());
+        assert_eq!(pool.load().unwrap(), None);
+        pool.reload().unwrap();
+        assert_eq!(pool.load().unwrap(), None);
+    }
+
+    #[test]
+    /// Verify that after a restart the most recently saved metadata is
+    /// loaded, and that metadata saved after the restart supersedes it.
+    fn test_save_after_reload() {
+        let mut pool = MemoryPool::initialize(&[DEVICE_SIZE; 2]).unwrap();
+        for name in &["first", "second", "third"] {
+            let pool_save = pool.record(name);
+            pool.save(&pool_save).unwrap();
+        }
+        pool.reload().unwrap();
+        assert_eq!(pool.load().unwrap().map(|p| p.name), Some("third".into()));
+
+        let pool_save = pool.record("fourth");
+        pool.save(&pool_save).unwrap();
+        assert_eq!(pool.load().unwrap(), Some(pool_save));
+    }
+
+    #[test]
+    /// Verify that a copy of a device is found to be a clone of it. Verify
+    /// that once metadata has been saved to the copy but not to the
+    /// original, the copy is kept instead.
+    fn test_clones() {
+        let mut pool = MemoryPool::initialize(&[DEVICE_SIZE; 2]).unwrap();
+        let pool_save = pool.record("name");
+        pool.save(&pool_save).unwrap();
+        assert_eq!(pool.clones().unwrap(), vec![]);
+
+        pool.clone_device(1).unwrap();
+        let clones = pool.clones().unwrap();
+        assert_eq!(clones.len(), 1);
+        assert_eq!(clones[0].devnode, PathBuf::from("/dev/memory2"));
+        assert_eq!(clones[0].kept, PathBuf::from("/dev/memory1"));
+        assert_eq!(clones[0].pool_uuid, pool.pool_uuid);
+
+        pool.devices[1].bda = None;
+        pool.save(&pool_save).unwrap();
+        pool.reload().unwrap();
+        let clones = pool.clones().unwrap();
+        assert_eq!(clones.len(), 1);
+        assert_eq!(clones[0].devnode, PathBuf::from("/dev/memory1"));
+        assert_eq!(clones[0].kept, PathBuf::from("/dev/memory2"));
+    }
+
+    #[test]
+    /// Verify that metadata of another pool is not loaded.
+    fn test_load_other_pool() {
+        let mut pool = MemoryPool::initialize(&[DEVICE_SIZE]).unwrap();
+        let pool_save = pool.record("name");
+        pool.save(&pool_save).unwrap();
+        pool.pool_uuid = PoolUuid::new_v4();
+        assert_eq!(pool.find().unwrap(), Vec::<PathBuf>::new());
+        assert_eq!(pool.load().unwrap(), None);
+    }
+}
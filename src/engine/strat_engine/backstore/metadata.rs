@@ -9,31 +9,238 @@ use std::str::from_utf8;
 use byteorder::{ByteOrder, LittleEndian};
 use chrono::{DateTime, Utc};
 use crc::crc32;
-use uuid::Uuid;
 
 use devicemapper::{Bytes, Sectors, IEC, SECTOR_SIZE};
 
 use stratis::{ErrorEnum, StratisError, StratisResult};
 
-use super::super::super::types::{DevUuid, PoolUuid};
+use super::super::super::types::{
+    DevUuid, FormatVersions, MetadataStats, PoolUuid, MAX_FORMAT_VERSIONS,
+};
 
 use super::super::device::SyncAll;
 
+#[cfg(feature = "fuzzing")]
+pub use self::mda::fuzz_mda_header;
 pub use self::mda::{validate_mda_size, MIN_MDA_SECTORS};
 
 const _BDA_STATIC_HDR_SIZE: usize = 16 * SECTOR_SIZE;
 const BDA_STATIC_HDR_SIZE: Bytes = Bytes(_BDA_STATIC_HDR_SIZE as u64);
 
 const MDA_RESERVED_SECTORS: Sectors = Sectors(3 * IEC::Mi / (SECTOR_SIZE as u64)); // = 3 MiB
+/// The size to which the reserved area of a BDA may be shrunk.
+const MDA_MIN_RESERVED_SECTORS: Sectors = Sectors(IEC::Mi / (SECTOR_SIZE as u64)); // = 1 MiB
 
 const STRAT_MAGIC: &[u8] = b"!Stra0tis\x86\xff\x02^\x41rh";
 
-const STRAT_SIGBLOCK_VERSION: u8 = 1;
+const STRAT_SIGBLOCK_VERSION: u8 = MAX_FORMAT_VERSIONS.sigblock;
+
+/// Set in the sigblock flags if a copy of the sigblock is kept in the last
+/// 8 sectors of the device, as recorded in the sigblock's blkdev_size field.
+const STRAT_SIGBLOCK_FLAG_TAIL_COPY: u64 = 0x1;
+/// Set in the sigblock flags if the backup copies of the sigblock and of the
+/// MDA regions are placed SPREAD_DISTANCE past the primary copies.
+const STRAT_SIGBLOCK_FLAG_SPREAD: u64 = 0x2;
+/// Set in the sigblock flags if the pool to which the device belonged was
+/// destroyed, but the sigblock was kept. Such a device belongs to no pool.
+const STRAT_SIGBLOCK_FLAG_DESTROYED: u64 = 0x4;
+/// Set in the sigblock flags if the reserved area of the BDA has been
+/// shrunk below MDA_RESERVED_SECTORS. A version of stratisd that does not
+/// know the flag rejects the sigblock, rather than misplacing the BDA.
+const STRAT_SIGBLOCK_FLAG_RESERVE_SHRUNK: u64 = 0x8;
+const STRAT_SIGBLOCK_KNOWN_FLAGS: u64 = STRAT_SIGBLOCK_FLAG_TAIL_COPY | STRAT_SIGBLOCK_FLAG_SPREAD
+    | STRAT_SIGBLOCK_FLAG_DESTROYED | STRAT_SIGBLOCK_FLAG_RESERVE_SHRUNK;
+
+/// The distance between the primary and the backup copy of the sigblock and
+/// of each MDA region in the spread layout. It is at least the size of the
+/// erase blocks of most flash devices, so that the two copies do not share
+/// an erase block, and are not lost together if it fails.
+const SPREAD_DISTANCE: Sectors = Sectors(4 * IEC::Mi / (SECTOR_SIZE as u64)); // = 4 MiB
+
+/// The bytes of the sigblock that hold the name of the pool to which the
+/// device belongs, padded with NULs, so that the pool may be named without
+/// reading its metadata. A sigblock written before the name was kept has
+/// zeroes there, and so records no name.
+const POOL_NAME_START: usize = 144;
+const POOL_NAME_END: usize = 400;
+
+const _BDA_TAIL_HDR_SIZE: usize = 8 * SECTOR_SIZE;
+const BDA_TAIL_HDR_SIZE: Bytes = Bytes(_BDA_TAIL_HDR_SIZE as u64);
+
+/// The longest prefix of the pool name that fits in the sigblock, cut at a
+/// character boundary.
+pub fn truncate_pool_name(name: &str) -> &str {
+    let mut end = POOL_NAME_END - POOL_NAME_START;
+    if name.len() <= end {
+        return name;
+    }
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+/// The offset in bytes of the tail sigblock region on a device of
+/// blkdev_size. The tail region is laid out like the regions at the
+/// beginning of the device, so the sigblock is in its second sector.
+fn tail_region_offset(blkdev_size: Sectors) -> u64 {
+    *(blkdev_size.bytes() - BDA_TAIL_HDR_SIZE)
+}
+
+/// Where the copies of the metadata on a device are placed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MetadataLayout {
+    /// Whether a copy of the sigblock is kept at the end of the device.
+    pub tail_copy: bool,
+    /// Whether the backup copies of the sigblock and of the MDA regions are
+    /// placed far enough from the primary copies that they are not in the
+    /// same erase block of a flash device.
+    pub spread: bool,
+}
+
+impl MetadataLayout {
+    /// The layout recorded in the sigblock flags.
+    fn from_flags(flags: u64) -> MetadataLayout {
+        MetadataLayout {
+            tail_copy: flags & STRAT_SIGBLOCK_FLAG_TAIL_COPY != 0,
+            spread: flags & STRAT_SIGBLOCK_FLAG_SPREAD != 0,
+        }
+    }
+
+    /// The sigblock flags that record this layout.
+    fn flags(&self) -> u64 {
+        let mut flags = 0;
+        if self.tail_copy {
+            flags |= STRAT_SIGBLOCK_FLAG_TAIL_COPY;
+        }
+        if self.spread {
+            flags |= STRAT_SIGBLOCK_FLAG_SPREAD;
+        }
+        flags
+    }
+
+    /// The number of sectors occupied by the tail copy of the sigblock.
+    fn tail_size(&self) -> Sectors {
+        if self.tail_copy {
+            BDA_TAIL_HDR_SIZE.sectors()
+        } else {
+            Sectors(0)
+        }
+    }
+
+    /// The number of sectors that the BDA is extended by to make room for
+    /// the backup copies of the metadata.
+    fn spread_size(&self) -> Sectors {
+        if self.spread {
+            SPREAD_DISTANCE
+        } else {
+            Sectors(0)
+        }
+    }
+
+    /// The offset in bytes of the region holding the second copy of the
+    /// sigblock.
+    fn second_sigblock_offset(&self) -> u64 {
+        if self.spread {
+            *SPREAD_DISTANCE.bytes()
+        } else {
+            8 * SECTOR_SIZE as u64
+        }
+    }
+}
+
+/// The number of sectors that the metadata of a device initialized with an
+/// MDA of mda_size occupies, including the tail copy of the sigblock if
+/// there is one.
+pub fn metadata_footprint(mda_size: Sectors, layout: MetadataLayout) -> Sectors {
+    BDA_STATIC_HDR_SIZE.sectors() + mda_size + MDA_RESERVED_SECTORS + layout.spread_size()
+        + layout.tail_size()
+}
+
+/// In the spread layout the primary MDA regions lie between the static
+/// header and the second copy of the sigblock, so they must fit there.
+fn validate_spread(mda_size: Sectors) -> StratisResult<()> {
+    let primaries = mda_size / 2usize;
+    if BDA_STATIC_HDR_SIZE.sectors() + primaries > SPREAD_DISTANCE {
+        return Err(StratisError::Engine(
+            ErrorEnum::Invalid,
+            format!(
+                "MDA size {} is too large for the spread metadata layout",
+                mda_size
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Verify that the metadata described by a sigblock fits on a device of
+/// blkdev_size, and that the size of the device in bytes is representable,
+/// so that no later arithmetic on these sizes can overflow.
+fn validate_footprint(
+    blkdev_size: Sectors,
+    mda_size: Sectors,
+    reserved_size: Sectors,
+    layout: MetadataLayout,
+) -> StratisResult<()> {
+    if layout.spread {
+        validate_spread(mda_size)?;
+    }
+    let footprint = (*BDA_STATIC_HDR_SIZE.sectors())
+        .checked_add(*mda_size)
+        .and_then(|size| size.checked_add(*reserved_size))
+        .and_then(|size| size.checked_add(*layout.spread_size()))
+        .and_then(|size| size.checked_add(*layout.tail_size()));
+    let device_bytes = (*blkdev_size).checked_mul(SECTOR_SIZE as u64);
+
+    match (footprint, device_bytes) {
+        (Some(footprint), Some(_)) if footprint <= *blkdev_size => Ok(()),
+        _ => Err(StratisError::Engine(
+            ErrorEnum::Invalid,
+            format!(
+                "Sigblock metadata of {} MDA sectors and {} reserved sectors does \
+                 not fit on a device of {} sectors",
+                mda_size, reserved_size, blkdev_size
+            ),
+        )),
+    }
+}
+
+/// Write a single sigblock region at offset. Zeroes the first sector, writes
+/// bda_buf to the second sector, and then zeroes the remaining six sectors.
+/// Read back the second sector, from the device rather than from the page
+/// cache, and verify that it matches bda_buf.
+fn write_sigblock_region<F>(f: &mut F, offset: u64, bda_buf: &[u8]) -> io::Result<()>
+where
+    F: Read + Seek + SyncAll,
+{
+    let zeroed = [0u8; SECTOR_SIZE * 6];
+
+    f.seek(SeekFrom::Start(offset))?;
+    f.write_all(&zeroed[..SECTOR_SIZE])?; // Zero 1 unused sector
+    f.write_all(bda_buf)?;
+    f.write_all(&zeroed)?; // Zero 6 unused sectors
+    f.sync_all()?;
+    f.drop_cache(offset, (SECTOR_SIZE * 8) as u64)?;
+
+    let mut verify_buf = [0u8; SECTOR_SIZE];
+    f.seek(SeekFrom::Start(offset + SECTOR_SIZE as u64))?;
+    f.read_exact(&mut verify_buf)?;
+    if crc32::checksum_castagnoli(&verify_buf) != crc32::checksum_castagnoli(bda_buf) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("sigblock at offset {} failed verification after write", offset),
+        ));
+    }
+    Ok(())
+}
 
 #[derive(Debug)]
 pub struct BDA {
     header: StaticHeader,
     regions: mda::MDARegions,
+    // Whether a copy of the sigblock other than the first could not be
+    // written when the device was initialized.
+    sigblock_degraded: bool,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -46,8 +253,12 @@ enum MetadataLocation {
 impl BDA {
     /// Read the BDA from the device and return 2 SECTORS worth of data, one for each BDA returned
     /// in the order of layout on disk (location 1, location 2).
+    /// The second location is where layout places it.
     /// Only the BDA sectors are read up from disk, zero areas are *not* read.
-    fn read<F>(f: &mut F) -> io::Result<([u8; SECTOR_SIZE], [u8; SECTOR_SIZE])>
+    fn read<F>(
+        f: &mut F,
+        layout: MetadataLayout,
+    ) -> io::Result<([u8; SECTOR_SIZE], [u8; SECTOR_SIZE])>
     where
         F: Read + Seek,
     {
@@ -71,7 +282,11 @@ impl BDA {
         }
 
         let loc_1_read_result = read_sector_at_offset(f, SECTOR_SIZE, &mut buf_loc_1);
-        let loc_2_read_result = read_sector_at_offset(f, 9 * SECTOR_SIZE, &mut buf_loc_2);
+        let loc_2_read_result = read_sector_at_offset(
+            f,
+            layout.second_sigblock_offset() as usize + SECTOR_SIZE,
+            &mut buf_loc_2,
+        );
 
         match (loc_1_read_result, loc_2_read_result) {
             (Err(loc_1_err), Err(_)) => Err(loc_1_err),
@@ -82,65 +297,101 @@ impl BDA {
     // Writes bda_buf according to the value of which.
     // If first location is specified, write zeroes to empty regions in the
     // first 8 sectors. If the second location is specified, writes zeroes to empty
-    // regions in the second 8 sectors.
-    fn write<F>(f: &mut F, bda_buf: &[u8], which: MetadataLocation) -> io::Result<()>
+    // regions in the 8 sectors where layout places the second copy.
+    // Each copy is read back after it is synced and compared with bda_buf.
+    // The second copy is not written until the first has been verified, so
+    // if verification fails, the copy not yet written still holds the
+    // previous sigblock, and setup() will fall back on it.
+    fn write<F>(
+        f: &mut F,
+        bda_buf: &[u8],
+        which: MetadataLocation,
+        layout: MetadataLayout,
+    ) -> io::Result<()>
     where
-        F: Seek + SyncAll,
+        F: Read + Seek + SyncAll,
     {
-        let zeroed = [0u8; _BDA_STATIC_HDR_SIZE];
-        f.seek(SeekFrom::Start(0))?;
-
-        // Write to a single region in the header. Zeroes the first sector,
-        // writes bda_buf to the second sector, and then zeroes the remaining
-        // six sectors.
-        fn write_region<F>(f: &mut F, bda_buf: &[u8], zeroed: &[u8]) -> io::Result<()>
-        where
-            F: Seek + SyncAll,
-        {
-            f.write_all(&zeroed[..SECTOR_SIZE])?; // Zero 1 unused sector
-            f.write_all(bda_buf)?;
-            f.write_all(&zeroed[..SECTOR_SIZE * 6])?; // Zero 6 unused sectors
-            f.sync_all()?;
-            Ok(())
-        };
-
         if which == MetadataLocation::Both || which == MetadataLocation::First {
-            write_region(f, bda_buf, &zeroed)?;
-        } else {
-            f.seek(SeekFrom::Start(8 * SECTOR_SIZE as u64))?;
+            write_sigblock_region(f, 0, bda_buf)?;
         }
 
         if which == MetadataLocation::Both || which == MetadataLocation::Second {
-            write_region(f, bda_buf, &zeroed)?;
+            write_sigblock_region(f, layout.second_sigblock_offset(), bda_buf)?;
         }
         Ok(())
     }
 
+    // Writes bda_buf to the tail copy location of a device of blkdev_size.
+    // The tail copy is laid out exactly like either of the copies at the
+    // beginning of the device, and is verified in the same way.
+    fn write_tail<F>(f: &mut F, bda_buf: &[u8], blkdev_size: Sectors) -> io::Result<()>
+    where
+        F: Read + Seek + SyncAll,
+    {
+        write_sigblock_region(f, tail_region_offset(blkdev_size), bda_buf)
+    }
+
     /// Initialize a blockdev with a Stratis BDA.
+    /// If layout.tail_copy is true, an additional copy of the sigblock is
+    /// written in the last 8 sectors of the device. If layout.spread is true,
+    /// the backup copies of the sigblock and of the MDA regions are placed
+    /// SPREAD_DISTANCE past the primary copies.
+    /// It is an error if the first copy of the sigblock can not be written;
+    /// a failure to write any other copy is only recorded.
     pub fn initialize<F>(
         f: &mut F,
-        pool_uuid: Uuid,
-        dev_uuid: Uuid,
+        pool_uuid: PoolUuid,
+        dev_uuid: DevUuid,
         mda_size: Sectors,
         blkdev_size: Sectors,
         initialization_time: u64,
+        layout: MetadataLayout,
     ) -> StratisResult<BDA>
     where
-        F: Seek + SyncAll,
+        F: Read + Seek + SyncAll,
     {
-        let header = StaticHeader::new(
+        if layout.spread {
+            validate_spread(mda_size)?;
+        }
+
+        let mut header = StaticHeader::new(
             pool_uuid,
             dev_uuid,
             mda_size,
             blkdev_size,
             initialization_time,
+            layout,
         );
 
-        BDA::write(f, &header.sigblock_to_buf(), MetadataLocation::Both)?;
+        let buf = header.stage();
+        BDA::write(f, &buf, MetadataLocation::First, layout)?;
 
-        let regions = mda::MDARegions::initialize(BDA_STATIC_HDR_SIZE, header.mda_size, f)?;
+        let mut sigblock_degraded = false;
+        if layout.spread {
+            // Zero the sectors where the default layout places the second
+            // copy, so that no stale copy is found there.
+            f.seek(SeekFrom::Start(8 * SECTOR_SIZE as u64))?;
+            f.write_all(&[0u8; 8 * SECTOR_SIZE])?;
+        }
+        if let Err(err) = BDA::write(f, &buf, MetadataLocation::Second, layout) {
+            warn!("writing second copy of sigblock failed: {}", err);
+            sigblock_degraded = true;
+        }
+        if layout.tail_copy {
+            if let Err(err) = BDA::write_tail(f, &buf, header.blkdev_size) {
+                warn!("writing tail copy of sigblock failed: {}", err);
+                sigblock_degraded = true;
+            }
+        }
 
-        Ok(BDA { header, regions })
+        let regions =
+            mda::MDARegions::initialize(BDA_STATIC_HDR_SIZE, header.mda_size, layout.spread, f)?;
+
+        Ok(BDA {
+            header,
+            regions,
+            sigblock_degraded,
+        })
     }
 
     /// Load a BDA on initial setup of a device.
@@ -154,19 +405,57 @@ impl BDA {
             None => return Ok(None),
         };
 
-        let regions = mda::MDARegions::load(BDA_STATIC_HDR_SIZE, header.mda_size, f)?;
-
-        Ok(Some(BDA { header, regions }))
+        let regions = mda::MDARegions::load(
+            BDA_STATIC_HDR_SIZE,
+            header.mda_size,
+            header.layout().spread,
+            f,
+        )?;
+
+        Ok(Some(BDA {
+            header,
+            regions,
+            sigblock_degraded: false,
+        }))
     }
 
     /// Zero out Static Header on the blockdev. This causes it to no
     /// longer be seen as a Stratis blockdev.
+    /// If there is a copy of the sigblock at the tail of the device, it is
+    /// zeroed first, so that an interrupted wipe can not leave behind a
+    /// tail copy from which the device would be rediscovered.
     pub fn wipe<F>(f: &mut F) -> StratisResult<()>
     where
-        F: Seek + SyncAll,
+        F: Read + Seek + SyncAll,
     {
         let zeroed = [0u8; _BDA_STATIC_HDR_SIZE];
 
+        if let Some((header, _)) = StaticHeader::probe_tail(f) {
+            f.seek(SeekFrom::Start(tail_region_offset(header.blkdev_size)))?;
+            f.write_all(&zeroed[.._BDA_TAIL_HDR_SIZE])?;
+            f.sync_all()?;
+        }
+
+        // A second copy of the sigblock placed by the spread layout is
+        // zeroed before the first copy, for the same reason. If the first
+        // copy is not usable, the second copy is zeroed if it appears to be
+        // there at all.
+        let spread = MetadataLayout {
+            spread: true,
+            ..Default::default()
+        };
+        if let Ok((buf_loc_1, buf_loc_2)) = BDA::read(f, spread) {
+            let is_spread = match StaticHeader::sigblock_from_buf(&buf_loc_1) {
+                Ok(Some(header)) => header.layout().spread,
+                _ => &buf_loc_2[4..20] == STRAT_MAGIC,
+            };
+            if is_spread {
+                f.seek(SeekFrom::Start(spread.second_sigblock_offset()))?;
+                f.write_all(&zeroed[.._BDA_TAIL_HDR_SIZE])?;
+                f.sync_all()?;
+            }
+        }
+
         // Wiping Static Header should do it
         f.seek(SeekFrom::Start(0))?;
         f.write_all(&zeroed)?;
@@ -174,18 +463,149 @@ impl BDA {
         Ok(())
     }
 
-    /// Save metadata to the disk
+    /// Mark the sigblock as that of a device whose pool was destroyed at
+    /// destroyed_time, in seconds since the epoch, and reset the MDA
+    /// regions, so that no pool metadata is found on the device. The pool
+    /// and device UUIDs are kept in the sigblock, so that the device may be
+    /// identified afterward. The sigblock is marked first, so that if the
+    /// MDA regions can not be reset, the device still belongs to no pool.
+    pub fn mark_destroyed<F>(&mut self, destroyed_time: u64, f: &mut F) -> StratisResult<()>
+    where
+        F: Read + Seek + SyncAll,
+    {
+        let layout = self.header.layout();
+        self.header.flags |= STRAT_SIGBLOCK_FLAG_DESTROYED;
+        self.header.destroyed_time = destroyed_time;
+
+        let buf = self.header.stage();
+        BDA::write(f, &buf, MetadataLocation::Both, layout)?;
+        if layout.tail_copy {
+            BDA::write_tail(f, &buf, self.header.blkdev_size)?;
+        }
+
+        self.regions = mda::MDARegions::initialize(
+            BDA_STATIC_HDR_SIZE,
+            self.header.mda_size,
+            layout.spread,
+            f,
+        )?;
+        Ok(())
+    }
+
+    /// Clear the mark of a destroyed pool from the sigblock, so that the
+    /// device belongs to its pool again. The MDA regions are left as they
+    /// are; metadata must be written to them for the pool to be set up.
+    pub fn unmark_destroyed<F>(&mut self, f: &mut F) -> StratisResult<()>
+    where
+        F: Read + Seek + SyncAll,
+    {
+        let layout = self.header.layout();
+        self.header.flags &= !STRAT_SIGBLOCK_FLAG_DESTROYED;
+        self.header.destroyed_time = 0;
+
+        let buf = self.header.stage();
+        BDA::write(f, &buf, MetadataLocation::Both, layout)?;
+        if layout.tail_copy {
+            BDA::write_tail(f, &buf, self.header.blkdev_size)?;
+        }
+        Ok(())
+    }
+
+    /// Record the name of the device's pool in the sigblock, truncated to
+    /// fit. If the sigblock can not be written, the BDA is left as it was.
+    /// Returns true if the recorded name was changed.
+    pub fn set_pool_name<F>(&mut self, name: &str, f: &mut F) -> StratisResult<bool>
+    where
+        F: Read + Seek + SyncAll,
+    {
+        let name = truncate_pool_name(name);
+        if self.pool_name() == Some(name) {
+            return Ok(false);
+        }
+
+        let layout = self.header.layout();
+        let previous = self.header.pool_name.clone();
+        self.header.pool_name = Some(name.to_owned());
+
+        let blkdev_size = self.header.blkdev_size;
+        let buf = self.header.stage();
+        let result = BDA::write(f, &buf, MetadataLocation::Both, layout).and_then(|_| {
+            if layout.tail_copy {
+                BDA::write_tail(f, &buf, blkdev_size)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            self.header.pool_name = previous;
+            return Err(err.into());
+        }
+        Ok(true)
+    }
+
+    /// The (offset, length) of the sectors at the end of the BDA that
+    /// shrink_reserved() would give up, or None if the reserved area is
+    /// already as small as it may be.
+    pub fn excess_reserved(&self) -> Option<(Sectors, Sectors)> {
+        if self.header.reserved_size <= MDA_MIN_RESERVED_SECTORS {
+            return None;
+        }
+        let excess = self.header.reserved_size - MDA_MIN_RESERVED_SECTORS;
+        Some((self.size() - excess, excess))
+    }
+
+    /// Shrink the reserved area of the BDA to MDA_MIN_RESERVED_SECTORS, and
+    /// record in the sigblock that it has been shrunk. Nothing in the BDA
+    /// lies in the sectors given up, which are at its end, in every layout.
+    /// Return the (offset, length) of the sectors given up, or None if the
+    /// reserved area was already as small as it may be. If the sigblock can
+    /// not be written, the BDA is left as it was.
+    pub fn shrink_reserved<F>(&mut self, f: &mut F) -> StratisResult<Option<(Sectors, Sectors)>>
+    where
+        F: Read + Seek + SyncAll,
+    {
+        let excess = match self.excess_reserved() {
+            Some(excess) => excess,
+            None => return Ok(None),
+        };
+
+        let layout = self.header.layout();
+        let (reserved_size, flags) = (self.header.reserved_size, self.header.flags);
+        self.header.reserved_size = MDA_MIN_RESERVED_SECTORS;
+        self.header.flags |= STRAT_SIGBLOCK_FLAG_RESERVE_SHRUNK;
+
+        let blkdev_size = self.header.blkdev_size;
+        let buf = self.header.stage();
+        let result = BDA::write(f, &buf, MetadataLocation::Both, layout).and_then(|_| {
+            if layout.tail_copy {
+                BDA::write_tail(f, &buf, blkdev_size)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            self.header.reserved_size = reserved_size;
+            self.header.flags = flags;
+            return Err(err.into());
+        }
+        Ok(Some(excess))
+    }
+
+    /// Save metadata to the disk.
+    /// If verify is true, read back each copy of the metadata after it is
+    /// written and check it before proceeding.
     pub fn save_state<F>(
         &mut self,
         time: &DateTime<Utc>,
         metadata: &[u8],
+        verify: bool,
         f: &mut F,
     ) -> StratisResult<()>
     where
-        F: Seek + SyncAll,
+        F: Read + Seek + SyncAll,
     {
         self.regions
-            .save_state(BDA_STATIC_HDR_SIZE, time, metadata, f)
+            .save_state(BDA_STATIC_HDR_SIZE, time, metadata, verify, f)
     }
 
     /// Read latest metadata from the disk
@@ -196,6 +616,14 @@ impl BDA {
         self.regions.load_state(BDA_STATIC_HDR_SIZE, &mut f)
     }
 
+    /// Read the metadata saved before the latest from the disk, if any
+    pub fn load_older_state<F>(&self, mut f: &mut F) -> StratisResult<Option<Vec<u8>>>
+    where
+        F: Read + Seek,
+    {
+        self.regions.load_older_state(BDA_STATIC_HDR_SIZE, &mut f)
+    }
+
     /// The time when the most recent metadata was written to the BDA,
     /// if any.
     pub fn last_update_time(&self) -> Option<&DateTime<Utc>> {
@@ -212,14 +640,38 @@ impl BDA {
         self.header.pool_uuid
     }
 
+    /// The name of the device's pool, as recorded in the sigblock, if any.
+    pub fn pool_name(&self) -> Option<&str> {
+        self.header.pool_name()
+    }
+
     /// The size of the device.
     pub fn dev_size(&self) -> Sectors {
         self.header.blkdev_size
     }
 
     /// The number of sectors the BDA itself occupies.
+    /// This does not include the sectors occupied by the tail copy of the
+    /// sigblock, if there is one.
     pub fn size(&self) -> Sectors {
         BDA_STATIC_HDR_SIZE.sectors() + self.header.mda_size + self.header.reserved_size
+            + self.header.layout().spread_size()
+    }
+
+    /// Where the copies of the metadata on the device are placed.
+    pub fn layout(&self) -> MetadataLayout {
+        self.header.layout()
+    }
+
+    /// The region at the end of the device occupied by the tail copy of the
+    /// sigblock, as an (offset, length) pair. None if there is no tail copy.
+    pub fn tail_region(&self) -> Option<(Sectors, Sectors)> {
+        if self.header.layout().tail_copy {
+            let size = BDA_TAIL_HDR_SIZE.sectors();
+            Some((self.header.blkdev_size - size, size))
+        } else {
+            None
+        }
     }
 
     /// The maximum size of variable length metadata that can be accommodated.
@@ -231,6 +683,36 @@ impl BDA {
     pub fn initialization_time(&self) -> u64 {
         self.header.initialization_time
     }
+
+    /// The number of times a metadata region on the device has been found
+    /// to be corrupt.
+    pub fn metadata_corruptions(&self) -> u64 {
+        self.regions.corruptions()
+    }
+
+    /// Counts of the outcomes of reads and writes of the MDA regions on
+    /// the device.
+    pub fn metadata_stats(&self) -> MetadataStats {
+        self.regions.stats()
+    }
+
+    /// Whether writes to the backup copies of the sigblock or of the MDA
+    /// regions on the device have failed, so that the metadata may only
+    /// be held by the primary copies.
+    pub fn metadata_degraded(&self) -> bool {
+        self.sigblock_degraded || self.regions.degraded()
+    }
+
+    /// The versions of the on-disk format found on the device. A sigblock
+    /// or MDA region header of any version but the current one is rejected
+    /// when it is read, so these are always the current versions.
+    pub fn format_versions(&self) -> FormatVersions {
+        FormatVersions {
+            sigblock: STRAT_SIGBLOCK_VERSION,
+            mda_header: mda::STRAT_REGION_HDR_VERSION,
+            metadata: mda::STRAT_METADATA_VERSION,
+        }
+    }
 }
 
 #[derive(Eq, PartialEq)]
@@ -243,6 +725,14 @@ pub struct StaticHeader {
     flags: u64,
     /// Seconds portion of DateTime<Utc> value.
     initialization_time: u64,
+    /// Advanced each time a new version of the sigblock is staged, so that
+    /// the newer of two otherwise valid copies can be identified.
+    sequence: u64,
+    /// When the pool to which the device belonged was destroyed, in seconds
+    /// since the epoch, if the destroyed flag is set; otherwise 0.
+    destroyed_time: u64,
+    /// The name of the pool, truncated to fit, if one has been recorded.
+    pool_name: Option<String>,
 }
 
 impl StaticHeader {
@@ -252,6 +742,7 @@ impl StaticHeader {
         mda_size: Sectors,
         blkdev_size: Sectors,
         initialization_time: u64,
+        layout: MetadataLayout,
     ) -> StaticHeader {
         StaticHeader {
             blkdev_size,
@@ -259,48 +750,281 @@ impl StaticHeader {
             dev_uuid,
             mda_size,
             reserved_size: MDA_RESERVED_SECTORS,
-            flags: 0,
+            flags: layout.flags(),
             initialization_time,
+            sequence: 0,
+            destroyed_time: 0,
+            pool_name: None,
         }
     }
 
+    /// Advance the sequence number and generate a buf for the new version
+    /// of the sigblock.
+    fn stage(&mut self) -> [u8; SECTOR_SIZE] {
+        self.sequence += 1;
+        self.sigblock_to_buf()
+    }
+
+    /// Where the copies of the metadata on the device are placed.
+    fn layout(&self) -> MetadataLayout {
+        MetadataLayout::from_flags(self.flags)
+    }
+
     /// Try to find a valid StaticHeader on a device.
     /// Return the latest copy that validates as a Stratis BDA, however verify both
     /// copies and if one validates but one does not, re-write the one that is incorrect.  If both
     /// copies are valid, but one is newer than the other, rewrite the older one to match.
-    /// Return None if the static header's magic does not match for *both* copies.
+    /// If the header indicates that there is a copy at the tail of the device,
+    /// verify that copy as well and re-write it if it does not match.
+    /// If neither copy at the beginning of the device is usable, look for a
+    /// copy at the tail of the device, and if one is found, restore both
+    /// copies at the beginning of the device from it.
+    /// Return None if the static header's magic does not match for *both* copies
+    /// and there is no copy at the tail of the device.
     fn setup<F>(f: &mut F) -> StratisResult<Option<StaticHeader>>
     where
         F: Read + Seek + SyncAll,
     {
-        let (buf_loc_1, buf_loc_2) = BDA::read(f)?;
+        match StaticHeader::setup_head(f) {
+            Ok(Some(header)) => {
+                if header.layout().tail_copy {
+                    StaticHeader::repair_tail(f, &header)?;
+                }
+                Ok(Some(header))
+            }
+            head_result => match StaticHeader::probe_tail(f) {
+                Some((header, buf)) => {
+                    warn!(
+                        "No usable sigblock at beginning of device {}, \
+                         restoring from copy at end of device",
+                        header.dev_uuid
+                    );
+                    BDA::write(f, &buf, MetadataLocation::Both, header.layout())?;
+                    Ok(Some(header))
+                }
+                None => head_result,
+            },
+        }
+    }
 
-        match (
-            StaticHeader::sigblock_from_buf(&buf_loc_1),
-            StaticHeader::sigblock_from_buf(&buf_loc_2),
-        ) {
+    /// Look for a valid copy of the sigblock at the end of the device.
+    /// The copy is only accepted if it has the tail copy flag set and its
+    /// recorded device size matches the actual size of the device, since
+    /// otherwise it can not be where the tail copy would have been written.
+    /// Any error encountered is treated as the absence of a tail copy.
+    fn probe_tail<F>(f: &mut F) -> Option<(StaticHeader, [u8; SECTOR_SIZE])>
+    where
+        F: Read + Seek,
+    {
+        let dev_size = f.seek(SeekFrom::End(0)).ok()?;
+        if dev_size < *(BDA_STATIC_HDR_SIZE + BDA_TAIL_HDR_SIZE) {
+            return None;
+        }
+
+        let mut buf = [0u8; SECTOR_SIZE];
+        f.seek(SeekFrom::Start(
+            dev_size - *BDA_TAIL_HDR_SIZE + SECTOR_SIZE as u64,
+        )).ok()?;
+        f.read_exact(&mut buf).ok()?;
+
+        match StaticHeader::sigblock_from_buf(&buf) {
+            Ok(Some(header)) => {
+                if header.layout().tail_copy && *header.blkdev_size.bytes() == dev_size {
+                    Some((header, buf))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Verify the copy of the sigblock at the end of the device against
+    /// header, and re-write it if it does not match.
+    fn repair_tail<F>(f: &mut F, header: &StaticHeader) -> StratisResult<()>
+    where
+        F: Read + Seek + SyncAll,
+    {
+        let mut buf = [0u8; SECTOR_SIZE];
+        let read_result = f
+            .seek(SeekFrom::Start(
+                tail_region_offset(header.blkdev_size) + SECTOR_SIZE as u64,
+            ))
+            .and_then(|_| f.read_exact(&mut buf));
+
+        let matches = read_result.is_ok() && match StaticHeader::sigblock_from_buf(&buf) {
+            Ok(Some(ref tail)) => tail == header,
+            _ => false,
+        };
+
+        if !matches {
+            BDA::write_tail(f, &header.sigblock_to_buf(), header.blkdev_size)?;
+        }
+        Ok(())
+    }
+
+    /// Read the two copies of the sigblock at the beginning of the device.
+    /// The layout is not known until a copy has been read, so the second
+    /// copy is first looked for where the default layout places it. If the
+    /// first copy records the spread layout, or if the first copy is not
+    /// usable and there is no second copy where the default layout places
+    /// it, the second copy is looked for where the spread layout places it.
+    /// If that finds no usable copy either, but a copy read for the default
+    /// layout was damaged rather than blank, the copies read for the default
+    /// layout are returned, so that the damage is reported, and the device
+    /// is not taken to be free.
+    /// Return the layout for which the copies were read, and the copies.
+    fn read_head<F>(
+        f: &mut F,
+    ) -> io::Result<(MetadataLayout, [u8; SECTOR_SIZE], [u8; SECTOR_SIZE])>
+    where
+        F: Read + Seek,
+    {
+        let default_layout = MetadataLayout::default();
+        let (buf_loc_1, buf_loc_2) = BDA::read(f, default_layout)?;
+
+        let loc_1_result = StaticHeader::sigblock_from_buf(&buf_loc_1);
+        let loc_2_result = StaticHeader::sigblock_from_buf(&buf_loc_2);
+        let guessed = match loc_1_result {
+            Ok(Some(ref header)) => {
+                let layout = header.layout();
+                if !layout.spread {
+                    return Ok((layout, buf_loc_1, buf_loc_2));
+                }
+                false
+            }
+            _ => match loc_2_result {
+                Ok(Some(_)) => return Ok((default_layout, buf_loc_1, buf_loc_2)),
+                _ => true,
+            },
+        };
+
+        let layout = MetadataLayout {
+            spread: true,
+            ..Default::default()
+        };
+        let (spread_loc_1, spread_loc_2) = BDA::read(f, layout)?;
+        let damaged = loc_1_result.is_err() || loc_2_result.is_err();
+        if guessed && damaged {
+            match StaticHeader::head_copies(layout, &spread_loc_1, &spread_loc_2) {
+                (Ok(Some(_)), _) | (_, Ok(Some(_))) => (),
+                _ => return Ok((default_layout, buf_loc_1, buf_loc_2)),
+            }
+        }
+        Ok((layout, spread_loc_1, spread_loc_2))
+    }
+
+    /// The sigblocks in the two copies read for layout by read_head().
+    /// A copy read from where the layout places the second copy is only
+    /// accepted if it records that layout.
+    fn head_copies(
+        layout: MetadataLayout,
+        buf_loc_1: &[u8; SECTOR_SIZE],
+        buf_loc_2: &[u8; SECTOR_SIZE],
+    ) -> (
+        StratisResult<Option<StaticHeader>>,
+        StratisResult<Option<StaticHeader>>,
+    ) {
+        let loc_2_result = match StaticHeader::sigblock_from_buf(buf_loc_2) {
+            Ok(Some(ref header)) if header.layout().spread != layout.spread => Ok(None),
+            loc_2_result => loc_2_result,
+        };
+        (StaticHeader::sigblock_from_buf(buf_loc_1), loc_2_result)
+    }
+
+    /// Find a valid StaticHeader on a device, as setup() does, but without
+    /// writing to the device, so that a device may be probed while in use,
+    /// or by a process that must not change it. No copy that is missing or
+    /// out of date is re-written.
+    pub fn probe<F>(f: &mut F) -> StratisResult<Option<StaticHeader>>
+    where
+        F: Read + Seek,
+    {
+        let (layout, buf_loc_1, buf_loc_2) = StaticHeader::read_head(f)?;
+        let (loc_1_result, loc_2_result) =
+            StaticHeader::head_copies(layout, &buf_loc_1, &buf_loc_2);
+        let any_error = loc_1_result.is_err() || loc_2_result.is_err();
+
+        let newest = loc_1_result
+            .ok()
+            .and_then(|header| header)
+            .into_iter()
+            .chain(loc_2_result.ok().and_then(|header| header))
+            .max_by_key(|header| (header.initialization_time, header.sequence));
+        match newest {
+            Some(header) => Ok(Some(header)),
+            None => match StaticHeader::probe_tail(f) {
+                Some((header, _)) => Ok(Some(header)),
+                None if any_error => {
+                    let err_str = "Appeared to be a Stratis device, but no valid sigblock found";
+                    Err(StratisError::Engine(ErrorEnum::Invalid, err_str.into()))
+                }
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// The UUID of the pool to which the device belongs.
+    pub fn pool_uuid(&self) -> PoolUuid {
+        self.pool_uuid
+    }
+
+    /// The name of the pool to which the device belongs, truncated to fit
+    /// in the sigblock, if one has been recorded.
+    pub fn pool_name(&self) -> Option<&str> {
+        self.pool_name.as_ref().map(|name| name.as_str())
+    }
+
+    /// The UUID of the device.
+    pub fn dev_uuid(&self) -> DevUuid {
+        self.dev_uuid
+    }
+
+    /// The size of the device, as recorded when it was initialized.
+    pub fn blkdev_size(&self) -> Sectors {
+        self.blkdev_size
+    }
+
+    /// The time at which the device was initialized, in seconds since the
+    /// epoch.
+    pub fn initialization_time(&self) -> u64 {
+        self.initialization_time
+    }
+
+    /// Find a valid StaticHeader among the two copies at the beginning of
+    /// the device, as described for setup(), and read as described for
+    /// read_head().
+    fn setup_head<F>(f: &mut F) -> StratisResult<Option<StaticHeader>>
+    where
+        F: Read + Seek + SyncAll,
+    {
+        let (layout, buf_loc_1, buf_loc_2) = StaticHeader::read_head(f)?;
+
+        match StaticHeader::head_copies(layout, &buf_loc_1, &buf_loc_2) {
             (Ok(loc_1), Ok(loc_2)) => {
                 match (loc_1, loc_2) {
                     (Some(loc_1), Some(loc_2)) => {
                         if loc_1 == loc_2 {
                             Ok(Some(loc_1))
-                        } else if loc_1.initialization_time > loc_2.initialization_time {
-                            BDA::write(f, &buf_loc_1, MetadataLocation::Second)?;
+                        } else if (loc_1.initialization_time, loc_1.sequence)
+                            > (loc_2.initialization_time, loc_2.sequence)
+                        {
+                            BDA::write(f, &buf_loc_1, MetadataLocation::Second, layout)?;
                             Ok(Some(loc_1))
                         } else {
-                            BDA::write(f, &buf_loc_2, MetadataLocation::First)?;
+                            BDA::write(f, &buf_loc_2, MetadataLocation::First, layout)?;
                             Ok(Some(loc_2))
                         }
                     }
                     (None, None) => Ok(None),
                     (Some(loc_1), None) => {
                         // Copy 1 has valid Stratis BDA, copy 2 has no magic, re-write copy 2
-                        BDA::write(f, &buf_loc_1, MetadataLocation::Second)?;
+                        BDA::write(f, &buf_loc_1, MetadataLocation::Second, layout)?;
                         Ok(Some(loc_1))
                     }
                     (None, Some(loc_2)) => {
                         // Copy 2 has valid Stratis BDA, copy 1 has no magic, re-write copy 1
-                        BDA::write(f, &buf_loc_2, MetadataLocation::First)?;
+                        BDA::write(f, &buf_loc_2, MetadataLocation::First, layout)?;
                         Ok(Some(loc_2))
                     }
                 }
@@ -308,7 +1032,7 @@ impl StaticHeader {
             (Ok(loc_1), Err(loc_2)) => {
                 // Re-write copy 2
                 if loc_1.is_some() {
-                    BDA::write(f, &buf_loc_1, MetadataLocation::Second)?;
+                    BDA::write(f, &buf_loc_1, MetadataLocation::Second, layout)?;
                     Ok(loc_1)
                 } else {
                     // Location 1 doesn't have a signature, but location 2 did, but it got an error,
@@ -320,7 +1044,7 @@ impl StaticHeader {
             (Err(loc_1), Ok(loc_2)) => {
                 // Re-write copy 1
                 if loc_2.is_some() {
-                    BDA::write(f, &buf_loc_2, MetadataLocation::First)?;
+                    BDA::write(f, &buf_loc_2, MetadataLocation::First, layout)?;
                     Ok(loc_2)
                 } else {
                     // Location 2 doesn't have a signature, but location 1 did, but it got an error,
@@ -337,6 +1061,8 @@ impl StaticHeader {
     }
 
     /// Retrieve the device and pool UUIDs from a stratis device.
+    /// A device whose pool was destroyed with its sigblock kept belongs to
+    /// no pool, so none are retrieved from it.
     pub fn device_identifiers<F>(f: &mut F) -> StratisResult<Option<((PoolUuid, DevUuid))>>
     where
         F: Read + Seek + SyncAll,
@@ -346,12 +1072,34 @@ impl StaticHeader {
         // it must also have correct CRC, no weird stuff in fields,
         // etc!
         match StaticHeader::setup(f) {
+            Ok(Some(ref sh)) if sh.destroyed() => Ok(None),
             Ok(Some(sh)) => Ok(Some((sh.pool_uuid, sh.dev_uuid))),
             Ok(None) => Ok(None),
             Err(err) => Err(err),
         }
     }
 
+    /// Retrieve the pool and device UUIDs, and the time the pool was
+    /// destroyed, from a device whose pool was destroyed with its sigblock
+    /// kept. Return None for any other device.
+    pub fn destroyed_identifiers<F>(f: &mut F) -> StratisResult<Option<(PoolUuid, DevUuid, u64)>>
+    where
+        F: Read + Seek + SyncAll,
+    {
+        match StaticHeader::setup(f)? {
+            Some(ref sh) if sh.destroyed() => {
+                Ok(Some((sh.pool_uuid, sh.dev_uuid, sh.destroyed_time)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether the pool to which the device belonged was destroyed, with
+    /// the sigblock kept.
+    fn destroyed(&self) -> bool {
+        self.flags & STRAT_SIGBLOCK_FLAG_DESTROYED != 0
+    }
+
     /// Generate a buf suitable for writing to blockdev
     fn sigblock_to_buf(&self) -> [u8; SECTOR_SIZE] {
         let mut buf = [0u8; SECTOR_SIZE];
@@ -362,7 +1110,14 @@ impl StaticHeader {
         buf[64..96].clone_from_slice(self.dev_uuid.simple().to_string().as_bytes());
         LittleEndian::write_u64(&mut buf[96..104], *self.mda_size);
         LittleEndian::write_u64(&mut buf[104..112], *self.reserved_size);
+        LittleEndian::write_u64(&mut buf[112..120], self.flags);
         LittleEndian::write_u64(&mut buf[120..128], self.initialization_time);
+        LittleEndian::write_u64(&mut buf[128..136], self.sequence);
+        LittleEndian::write_u64(&mut buf[136..144], self.destroyed_time);
+        if let Some(ref pool_name) = self.pool_name {
+            let name = truncate_pool_name(pool_name).as_bytes();
+            buf[POOL_NAME_START..POOL_NAME_START + name.len()].clone_from_slice(name);
+        }
 
         let hdr_crc = crc32::checksum_castagnoli(&buf[4..SECTOR_SIZE]);
         LittleEndian::write_u32(&mut buf[..4], hdr_crc);
@@ -396,25 +1151,85 @@ impl StaticHeader {
             ));
         }
 
-        let pool_uuid = Uuid::parse_str(from_utf8(&buf[32..64])?)?;
-        let dev_uuid = Uuid::parse_str(from_utf8(&buf[64..96])?)?;
+        let pool_uuid = PoolUuid::parse_str(from_utf8(&buf[32..64])?)?;
+        let dev_uuid = DevUuid::parse_str(from_utf8(&buf[64..96])?)?;
 
         let mda_size = Sectors(LittleEndian::read_u64(&buf[96..104]));
 
         mda::validate_mda_size(mda_size)?;
 
+        let flags = LittleEndian::read_u64(&buf[112..120]);
+        if flags & !STRAT_SIGBLOCK_KNOWN_FLAGS != 0 {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                format!("Unknown sigblock flags: {:#x}", flags),
+            ));
+        }
+
+        let reserved_size = Sectors(LittleEndian::read_u64(&buf[104..112]));
+        let min_reserved_size = if flags & STRAT_SIGBLOCK_FLAG_RESERVE_SHRUNK != 0 {
+            MDA_MIN_RESERVED_SECTORS
+        } else {
+            MDA_RESERVED_SECTORS
+        };
+        if reserved_size < min_reserved_size {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                format!(
+                    "Reserved size {} is less than minimum ({})",
+                    reserved_size, min_reserved_size
+                ),
+            ));
+        }
+
+        validate_footprint(
+            blkdev_size,
+            mda_size,
+            reserved_size,
+            MetadataLayout::from_flags(flags),
+        )?;
+
+        // The name is only informational, so a name that is not UTF-8 is
+        // ignored rather than rejected.
+        let name_buf = &buf[POOL_NAME_START..POOL_NAME_END];
+        let name_len = name_buf
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or_else(|| name_buf.len());
+        let pool_name = match from_utf8(&name_buf[..name_len]) {
+            Ok(name) if !name.is_empty() => Some(name.to_owned()),
+            _ => None,
+        };
+
         Ok(Some(StaticHeader {
             pool_uuid,
             dev_uuid,
             blkdev_size,
             mda_size,
-            reserved_size: Sectors(LittleEndian::read_u64(&buf[104..112])),
-            flags: 0,
+            reserved_size,
+            flags,
             initialization_time: LittleEndian::read_u64(&buf[120..128]),
+            sequence: LittleEndian::read_u64(&buf[128..136]),
+            destroyed_time: LittleEndian::read_u64(&buf[136..144]),
+            pool_name,
         }))
     }
 }
 
+/// Parse buf, truncated or zero-padded to a sector, as a sigblock. The
+/// magic and CRC of the sigblock are filled in, so that the fields past them
+/// are reached.
+#[cfg(feature = "fuzzing")]
+pub fn fuzz_sigblock(buf: &[u8]) {
+    let mut sector = [0u8; SECTOR_SIZE];
+    let len = buf.len().min(SECTOR_SIZE);
+    sector[..len].copy_from_slice(&buf[..len]);
+    sector[4..20].copy_from_slice(STRAT_MAGIC);
+    let hdr_crc = crc32::checksum_castagnoli(&sector[4..SECTOR_SIZE]);
+    LittleEndian::write_u32(&mut sector[..4], hdr_crc);
+    let _ = StaticHeader::sigblock_from_buf(&sector);
+}
+
 impl fmt::Debug for StaticHeader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("StaticHeader")
@@ -425,24 +1240,28 @@ impl fmt::Debug for StaticHeader {
             .field("reserved_size", &self.reserved_size)
             .field("flags", &self.flags)
             .field("initialization_time", &self.initialization_time)
+            .field("sequence", &self.sequence)
+            .field("destroyed_time", &self.destroyed_time)
+            .field("pool_name", &self.pool_name)
             .finish()
     }
 }
 
 mod mda {
     use std;
-    use std::cmp::Ordering;
+    use std::cell::Cell;
+    use std::cmp::{min, Ordering};
     use std::io::{Read, Seek, SeekFrom};
 
     use byteorder::{ByteOrder, LittleEndian};
     use chrono::{DateTime, TimeZone, Utc};
-    use crc::crc32;
+    use crc::{crc32, Hasher32};
 
     use devicemapper::{Bytes, Sectors};
 
     use stratis::{ErrorEnum, StratisError, StratisResult};
 
-    use super::SyncAll;
+    use super::{MetadataStats, SyncAll, MAX_FORMAT_VERSIONS, SPREAD_DISTANCE};
 
     const _MDA_REGION_HDR_SIZE: usize = 32;
     const MDA_REGION_HDR_SIZE: Bytes = Bytes(_MDA_REGION_HDR_SIZE as u64);
@@ -452,20 +1271,71 @@ mod mda {
     const NUM_PRIMARY_MDA_REGIONS: usize = NUM_MDA_REGIONS / PER_MDA_REGION_COPIES;
     pub const MIN_MDA_SECTORS: Sectors = Sectors(2032);
 
-    const STRAT_REGION_HDR_VERSION: u8 = 1;
-    const STRAT_METADATA_VERSION: u8 = 1;
+    /// The size of the chunks in which the data of a region is read.
+    const LOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+    /// The number of consecutive failed writes to a backup region after
+    /// which the regions are considered degraded.
+    const BACKUP_WRITE_FAILURE_LIMIT: u64 = 3;
+
+    pub const STRAT_REGION_HDR_VERSION: u8 = MAX_FORMAT_VERSIONS.mda_header;
+    pub const STRAT_METADATA_VERSION: u8 = MAX_FORMAT_VERSIONS.metadata;
 
     #[derive(Debug)]
     pub struct MDARegions {
         // Spec defines 4 regions, but regions 2 & 3 are duplicates of 0 and 1 respectively
         region_size: Sectors,
+        // The distance from each of regions 0 and 1 to its duplicate.
+        backup_distance: Bytes,
         mdas: [Option<MDAHeader>; NUM_PRIMARY_MDA_REGIONS],
+        // The regions, counting duplicates, found to be corrupt. A
+        // quarantined region is not read again until it is next written.
+        quarantined: Cell<[bool; NUM_MDA_REGIONS]>,
+        // The quarantined regions that were quarantined because they were
+        // found to be corrupt, rather than because a write to them failed.
+        corrupt: Cell<[bool; NUM_MDA_REGIONS]>,
+        // The number of times a region has been found to be corrupt.
+        corruptions: Cell<u64>,
+        // The number of consecutive failed writes to a backup region.
+        backup_write_failures: u64,
+        // Counts of the outcomes of reads and writes of the regions.
+        stats: Cell<MetadataStats>,
+    }
+
+    /// Whether an error reading a region indicates that the region is
+    /// corrupt, rather than that it could not be read at all.
+    fn is_corruption(err: &StratisError) -> bool {
+        match *err {
+            StratisError::Engine(ErrorEnum::Invalid, _) => true,
+            _ => false,
+        }
+    }
+
+    /// The distance from each primary region to its duplicate. By default
+    /// the duplicates follow the primary regions; in the spread layout they
+    /// are SPREAD_DISTANCE past them.
+    fn backup_distance(per_region_size: Bytes, spread: bool) -> Bytes {
+        if spread {
+            SPREAD_DISTANCE.bytes()
+        } else {
+            per_region_size * NUM_PRIMARY_MDA_REGIONS
+        }
     }
 
     impl MDARegions {
         /// Calculate the offset from start of device for an MDARegion.
-        fn mda_offset(header_size: Bytes, index: usize, per_region_size: Bytes) -> u64 {
-            *(header_size + per_region_size * index)
+        fn mda_offset(
+            header_size: Bytes,
+            index: usize,
+            per_region_size: Bytes,
+            backup_distance: Bytes,
+        ) -> u64 {
+            let offset = header_size + per_region_size * (index % NUM_PRIMARY_MDA_REGIONS);
+            if index < NUM_PRIMARY_MDA_REGIONS {
+                *offset
+            } else {
+                *(offset + backup_distance)
+            }
         }
 
         /// The maximum size of variable length metadata that this region
@@ -474,11 +1344,88 @@ mod mda {
             self.region_size
         }
 
+        /// The number of times a region has been found to be corrupt.
+        pub fn corruptions(&self) -> u64 {
+            self.corruptions.get()
+        }
+
+        /// Counts of the outcomes of reads and writes of the regions.
+        pub fn stats(&self) -> MetadataStats {
+            self.stats.get()
+        }
+
+        /// Update the counts of outcomes with f.
+        fn count<G>(&self, f: G)
+        where
+            G: FnOnce(&mut MetadataStats),
+        {
+            let mut stats = self.stats.get();
+            f(&mut stats);
+            self.stats.set(stats);
+        }
+
+        /// Quarantine the region specified by index, which has been found
+        /// to be corrupt.
+        fn quarantine(&self, index: usize) {
+            let mut quarantined = self.quarantined.get();
+            if !quarantined[index] {
+                warn!("MDA region {} is corrupt, not reading it again until rewritten", index);
+                quarantined[index] = true;
+                self.quarantined.set(quarantined);
+                let mut corrupt = self.corrupt.get();
+                corrupt[index] = true;
+                self.corrupt.set(corrupt);
+                self.corruptions.set(self.corruptions.get() + 1);
+            }
+        }
+
+        /// Release the region specified by index from quarantine, because
+        /// it has been rewritten. A region quarantined because it was
+        /// corrupt counts as repaired.
+        fn release(&self, index: usize) {
+            let mut corrupt = self.corrupt.get();
+            if corrupt[index] {
+                self.count(|stats| stats.crc_repairs += 1);
+                corrupt[index] = false;
+                self.corrupt.set(corrupt);
+            }
+            let mut quarantined = self.quarantined.get();
+            quarantined[index] = false;
+            self.quarantined.set(quarantined);
+        }
+
+        /// Record that a write to the backup regions specified by indices
+        /// has failed. The regions may hold a partial write, so they are not
+        /// read again until they are successfully rewritten.
+        fn backup_write_failed(&mut self, indices: &[usize], err: &StratisError) {
+            warn!(
+                "writing backup MDA regions {:?} failed, continuing with primary regions: {}",
+                indices, err
+            );
+            let mut quarantined = self.quarantined.get();
+            for index in indices {
+                quarantined[*index] = true;
+            }
+            self.quarantined.set(quarantined);
+            self.backup_write_failures += 1;
+        }
+
+        /// Whether writes to the backup regions have failed repeatedly, so
+        /// that the metadata may only be held by the primary regions.
+        pub fn degraded(&self) -> bool {
+            self.backup_write_failures >= BACKUP_WRITE_FAILURE_LIMIT
+        }
+
         /// Initialize the space allotted to the MDA regions to 0.
         /// Return an MDARegions object with uninitialized MDAHeader objects.
+        /// It is an error if the primary regions can not be initialized;
+        /// a failure to initialize the backup regions is only recorded.
+        /// If spread is true, the duplicate regions are placed as in the
+        /// spread layout.
         pub fn initialize<F>(
             header_size: Bytes,
             size: Sectors,
+            spread: bool,
             f: &mut F,
         ) -> StratisResult<MDARegions>
         where
@@ -488,21 +1435,39 @@ mod mda {
 
             let region_size = size / NUM_MDA_REGIONS;
             let per_region_size = region_size.bytes();
-            for region in 0..NUM_MDA_REGIONS {
-                f.seek(SeekFrom::Start(MDARegions::mda_offset(
-                    header_size,
-                    region,
-                    per_region_size,
-                )))?;
-                f.write_all(&hdr_buf)?;
-            }
+            let backup_distance = backup_distance(per_region_size, spread);
+            let mut init_regions = |indices: std::ops::Range<usize>| -> StratisResult<()> {
+                for region in indices {
+                    f.seek(SeekFrom::Start(MDARegions::mda_offset(
+                        header_size,
+                        region,
+                        per_region_size,
+                        backup_distance,
+                    )))?;
+                    f.write_all(&hdr_buf)?;
+                }
+                f.sync_all()?;
+                Ok(())
+            };
 
-            f.sync_all()?;
+            init_regions(0..NUM_PRIMARY_MDA_REGIONS)?;
+            let backup_result = init_regions(NUM_PRIMARY_MDA_REGIONS..NUM_MDA_REGIONS);
 
-            Ok(MDARegions {
+            let mut regions = MDARegions {
                 region_size,
+                backup_distance,
                 mdas: [None, None],
-            })
+                quarantined: Cell::new([false; NUM_MDA_REGIONS]),
+                corrupt: Cell::new([false; NUM_MDA_REGIONS]),
+                corruptions: Cell::new(0),
+                backup_write_failures: 0,
+                stats: Cell::new(MetadataStats::default()),
+            };
+            if let Err(err) = backup_result {
+                let backups = (NUM_PRIMARY_MDA_REGIONS..NUM_MDA_REGIONS).collect::<Vec<_>>();
+                regions.backup_write_failed(&backups, &err);
+            }
+            Ok(regions)
         }
 
         /// Construct MDARegions from data on the disk.
@@ -510,12 +1475,18 @@ mod mda {
         /// StaticHeader has already been read. Therefore, it
         /// constitutes an error if it is not possible to discover two
         /// well-formed MDAHeaders for this device.
-        pub fn load<F>(header_size: Bytes, size: Sectors, f: &mut F) -> StratisResult<MDARegions>
+        pub fn load<F>(
+            header_size: Bytes,
+            size: Sectors,
+            spread: bool,
+            f: &mut F,
+        ) -> StratisResult<MDARegions>
         where
             F: Read + Seek,
         {
             let region_size = size / NUM_MDA_REGIONS;
             let per_region_size = region_size.bytes();
+            let backup_distance = backup_distance(per_region_size, spread);
 
             // Load a single region at the location specified by index.
             // If it appears that no metadata has been written at the location
@@ -527,22 +1498,46 @@ mod mda {
                     header_size,
                     index,
                     per_region_size,
+                    backup_distance,
                 )))?;
                 f.read_exact(&mut hdr_buf)?;
                 Ok(MDAHeader::from_buf(&hdr_buf, per_region_size)?)
             };
 
+            let regions = MDARegions {
+                region_size,
+                backup_distance,
+                mdas: [None, None],
+                quarantined: Cell::new([false; NUM_MDA_REGIONS]),
+                corrupt: Cell::new([false; NUM_MDA_REGIONS]),
+                corruptions: Cell::new(0),
+                backup_write_failures: 0,
+                stats: Cell::new(MetadataStats::default()),
+            };
+
             // Get an MDAHeader for the given index.
             // If there is a failure reading the first, fall back on the
             // second. If there is a failure reading both, return an error.
-            let mut get_mda = |index: usize| -> StratisResult<Option<MDAHeader>> {
-                load_a_region(index).or_else(|_| load_a_region(index + 2))
+            // A region whose header is corrupt is quarantined.
+            let mdas = {
+                let mut load_or_quarantine = |index: usize| {
+                    load_a_region(index).map_err(|err| {
+                        if is_corruption(&err) {
+                            regions.quarantine(index);
+                        }
+                        err
+                    })
+                };
+                let mut load_or_fall_back = |index: usize| {
+                    load_or_quarantine(index).or_else(|_| {
+                        regions.count(|stats| stats.fallback_loads += 1);
+                        load_or_quarantine(index + NUM_PRIMARY_MDA_REGIONS)
+                    })
+                };
+                [load_or_fall_back(0)?, load_or_fall_back(1)?]
             };
 
-            Ok(MDARegions {
-                region_size,
-                mdas: [get_mda(0)?, get_mda(1)?],
-            })
+            Ok(MDARegions { mdas, ..regions })
         }
 
         /// Write metadata to the older of the metadata regions.
@@ -552,15 +1547,22 @@ mod mda {
         /// error. If the size of the data is greater than the available space,
         /// return an error. If there is an error when writing the data, return
         /// an error.
+        /// If verify is true, read back each region after it is written and
+        /// return an error if it does not match. The backup copy of a region
+        /// is written only after the primary copy has been verified, so on
+        /// failure the previous contents of the backup copy remain intact.
+        /// A failure to write the backup copy is recorded, but is not an
+        /// error, since the metadata is held by the primary copy.
         pub fn save_state<F>(
             &mut self,
             header_size: Bytes,
             time: &DateTime<Utc>,
             data: &[u8],
+            verify: bool,
             f: &mut F,
         ) -> StratisResult<()>
         where
-            F: Seek + SyncAll,
+            F: Read + Seek + SyncAll,
         {
             if self.last_update_time() >= Some(time) {
                 return Err(StratisError::Engine(
@@ -579,28 +1581,74 @@ mod mda {
                 data_crc: crc32::checksum_castagnoli(data),
             };
             let hdr_buf = header.to_buf();
+            let backup_distance = self.backup_distance;
 
-            // Write data to a region specified by index.
-            let mut save_region = |index: usize| -> StratisResult<()> {
-                f.seek(SeekFrom::Start(MDARegions::mda_offset(
-                    header_size,
-                    index,
-                    region_size,
-                )))?;
+            // Write data to a region specified by index.
+            let mut save_region = |index: usize| -> StratisResult<()> {
+                let offset =
+                    MDARegions::mda_offset(header_size, index, region_size, backup_distance);
+                f.seek(SeekFrom::Start(offset))?;
                 f.write_all(&hdr_buf)?;
                 f.write_all(data)?;
                 f.sync_all()?;
 
+                if verify {
+                    // Read back from the device, not from the page cache.
+                    f.drop_cache(offset, (_MDA_REGION_HDR_SIZE + data.len()) as u64)?;
+                    let mut verify_buf = [0u8; _MDA_REGION_HDR_SIZE];
+                    f.seek(SeekFrom::Start(offset))?;
+                    f.read_exact(&mut verify_buf)?;
+                    if verify_buf != hdr_buf {
+                        return Err(StratisError::Engine(
+                            ErrorEnum::Error,
+                            format!("MDA region {} header failed verification after write", index),
+                        ));
+                    }
+                    header.load_region(region_size, f).map_err(|_| {
+                        StratisError::Engine(
+                            ErrorEnum::Error,
+                            format!("MDA region {} data failed verification after write", index),
+                        )
+                    })?;
+                }
+
                 Ok(())
             };
 
-            // TODO: Consider if there is an action that should be taken if
-            // saving to one or the other region fails.
             let older_region = self.older();
-            save_region(older_region)?;
-            save_region(older_region + 2)?;
+            let backup_region = older_region + NUM_PRIMARY_MDA_REGIONS;
+            let mut retried = false;
+            let backup_result = {
+                // Write to a region, trying once more if the write fails.
+                let mut save_or_retry = |index: usize| -> StratisResult<()> {
+                    match save_region(index) {
+                        Ok(()) => Ok(()),
+                        Err(err) => {
+                            warn!("writing MDA region {} failed, trying again: {}", index, err);
+                            retried = true;
+                            save_region(index)
+                        }
+                    }
+                };
+                save_or_retry(older_region)?;
+                save_or_retry(backup_region)
+            };
+            self.release(older_region);
+            match backup_result {
+                Ok(()) => {
+                    self.release(backup_region);
+                    self.backup_write_failures = 0;
+                }
+                Err(err) => self.backup_write_failed(&[backup_region], &err),
+            }
 
             self.mdas[older_region] = Some(header);
+            self.count(|stats| {
+                stats.saves += 1;
+                if retried {
+                    stats.retried_saves += 1;
+                }
+            });
 
             Ok(())
         }
@@ -614,25 +1662,72 @@ mod mda {
             F: Read + Seek,
         {
             let newer_region = self.newer();
-            let mda = match self.mdas[newer_region] {
+            self.load_generation(newer_region, header_size, f)
+        }
+
+        /// Load metadata from the older MDA region, the generation saved
+        /// before that in the newer region.
+        /// In case the older region holds no record of metadata, as when
+        /// metadata has been saved only once, return None.
+        pub fn load_older_state<F>(
+            &self,
+            header_size: Bytes,
+            f: &mut F,
+        ) -> StratisResult<Option<Vec<u8>>>
+        where
+            F: Read + Seek,
+        {
+            let older_region = self.older();
+            self.load_generation(older_region, header_size, f)
+        }
+
+        /// Load metadata from the given primary MDA region, or from its
+        /// backup if it can not be read.
+        fn load_generation<F>(
+            &self,
+            region: usize,
+            header_size: Bytes,
+            f: &mut F,
+        ) -> StratisResult<Option<Vec<u8>>>
+        where
+            F: Read + Seek,
+        {
+            let mda = match self.mdas[region] {
                 None => return Ok(None),
                 Some(ref mda) => mda,
             };
             let region_size = self.region_size.bytes();
 
             // Load the metadata region specified by index.
-            // It is an error if the metadata can not be found.
+            // It is an error if the metadata can not be found, or if the
+            // region is quarantined. A region whose data is found to be
+            // corrupt is quarantined.
             let mut load_region = |index: usize| -> StratisResult<Vec<u8>> {
-                let offset = MDARegions::mda_offset(header_size, index, region_size)
-                    + _MDA_REGION_HDR_SIZE as u64;
+                if self.quarantined.get()[index] {
+                    return Err(StratisError::Engine(
+                        ErrorEnum::Invalid,
+                        format!("MDA region {} is quarantined", index),
+                    ));
+                }
+                let offset =
+                    MDARegions::mda_offset(header_size, index, region_size, self.backup_distance)
+                        + _MDA_REGION_HDR_SIZE as u64;
                 f.seek(SeekFrom::Start(offset))?;
-                mda.load_region(f)
+                mda.load_region(region_size, f).map_err(|err| {
+                    if is_corruption(&err) {
+                        self.quarantine(index);
+                    }
+                    err
+                })
             };
 
             // TODO: Figure out if there is an action to take if the
             // first read returns an error.
-            load_region(newer_region)
-                .or_else(|_| load_region(newer_region + 2))
+            load_region(region)
+                .or_else(|_| {
+                    self.count(|stats| stats.fallback_loads += 1);
+                    load_region(region + NUM_PRIMARY_MDA_REGIONS)
+                })
                 .map(Some)
         }
 
@@ -728,14 +1823,25 @@ mod mda {
                     let used = Bytes(LittleEndian::read_u64(&buf[8..16]));
                     check_mda_region_size(used, region_size)?;
 
-                    // Signed cast is safe, highest order bit of each value
-                    // read is guaranteed to be 0.
-                    assert!(secs <= std::i64::MAX as u64);
-
+                    // The header was written from a valid time, so its
+                    // seconds fit in an i64 and its nanoseconds are in range,
+                    // unless the header was not written by Stratis.
                     let nsecs = LittleEndian::read_u32(&buf[24..28]);
+                    let last_updated = if secs <= std::i64::MAX as u64 {
+                        Utc.timestamp_opt(secs as i64, nsecs).single()
+                    } else {
+                        None
+                    };
+                    let last_updated = last_updated.ok_or_else(|| {
+                        StratisError::Engine(
+                            ErrorEnum::Invalid,
+                            format!("MDA region header time invalid: {}s {}ns", secs, nsecs),
+                        )
+                    })?;
+
                     Ok(Some(MDAHeader {
                         used,
-                        last_updated: Utc.timestamp(secs as i64, nsecs),
+                        last_updated,
                         data_crc: LittleEndian::read_u32(&buf[4..8]),
                     }))
                 }
@@ -764,8 +1870,10 @@ mod mda {
         /// Given a pre-seek()ed File, load the MDA region and return the contents.
         /// Return an error if the data can not be read, since the existence
         /// of the MDAHeader implies that the data must be available.
+        /// The data is read in chunks of bounded size, so that a corrupt
+        /// header can not cause a huge allocation before the CRC is checked.
         // MDAHeader cannot seek because it doesn't know which region it's in
-        fn load_region<F>(&self, f: &mut F) -> StratisResult<Vec<u8>>
+        fn load_region<F>(&self, region_size: Bytes, f: &mut F) -> StratisResult<Vec<u8>>
         where
             F: Read,
         {
@@ -777,12 +1885,22 @@ mod mda {
             // where usize is u64, which is usual. It is not absurd when
             // compiled in an environment where usize is u32.
             #![allow(absurd_extreme_comparisons)]
+            check_mda_region_size(self.used, region_size)?;
             assert!(*self.used <= std::usize::MAX as u64);
-            let mut data_buf = vec![0u8; *self.used as usize];
 
-            f.read_exact(&mut data_buf)?;
+            let mut remaining = *self.used as usize;
+            let mut data_buf = Vec::new();
+            let mut chunk = vec![0u8; min(remaining, LOAD_CHUNK_SIZE)];
+            let mut digest = crc32::Digest::new(crc32::CASTAGNOLI);
+            while remaining > 0 {
+                let len = min(remaining, LOAD_CHUNK_SIZE);
+                f.read_exact(&mut chunk[..len])?;
+                digest.write(&chunk[..len]);
+                data_buf.extend_from_slice(&chunk[..len]);
+                remaining -= len;
+            }
 
-            if self.data_crc != crc32::checksum_castagnoli(&data_buf) {
+            if self.data_crc != digest.sum32() {
                 return Err(StratisError::Engine(
                     ErrorEnum::Invalid,
                     "MDA region data CRC".into(),
@@ -796,7 +1914,10 @@ mod mda {
     /// Check that data size does not exceed region available.
     /// Note that used is the amount used for metadata only.
     fn check_mda_region_size(used: Bytes, available: Bytes) -> StratisResult<()> {
-        if MDA_REGION_HDR_SIZE + used > available {
+        if (*used)
+            .checked_add(*MDA_REGION_HDR_SIZE)
+            .map_or(true, |total| total > *available)
+        {
             let err_msg = format!(
                 "metadata length {} exceeds region available {}",
                 used,
@@ -808,6 +1929,19 @@ mod mda {
         Ok(())
     }
 
+    /// Parse buf as an MDA region header of a region of region_size bytes.
+    /// The CRC of the header is recomputed, so that the fields past it are
+    /// reached.
+    #[cfg(feature = "fuzzing")]
+    pub fn fuzz_mda_header(region_size: u64, buf: &[u8]) {
+        let mut hdr_buf = [0u8; _MDA_REGION_HDR_SIZE];
+        let len = min(buf.len(), _MDA_REGION_HDR_SIZE);
+        hdr_buf[..len].copy_from_slice(&buf[..len]);
+        let hdr_crc = crc32::checksum_castagnoli(&hdr_buf[4..]);
+        LittleEndian::write_u32(&mut hdr_buf[..4], hdr_crc);
+        let _ = MDAHeader::from_buf(&hdr_buf, Bytes(region_size));
+    }
+
     /// Validate MDA size
     pub fn validate_mda_size(size: Sectors) -> StratisResult<()> {
         if size % NUM_MDA_REGIONS != Sectors(0) {
@@ -835,7 +1969,7 @@ mod mda {
 
     #[cfg(test)]
     mod tests {
-        use std::io::Cursor;
+        use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
 
         use chrono::Utc;
         use proptest::{
@@ -868,10 +2002,13 @@ mod mda {
         fn test_reading_mda_regions() {
             let buf_length = *(BDA_STATIC_HDR_SIZE + 4usize * MIN_MDA_SECTORS.bytes()) as usize;
             let mut buf = Cursor::new(vec![0; buf_length]);
-            assert!(MDARegions::load(BDA_STATIC_HDR_SIZE, MIN_MDA_SECTORS, &mut buf).is_err());
+            assert!(
+                MDARegions::load(BDA_STATIC_HDR_SIZE, MIN_MDA_SECTORS, false, &mut buf).is_err()
+            );
 
-            MDARegions::initialize(BDA_STATIC_HDR_SIZE, MIN_MDA_SECTORS, &mut buf).unwrap();
-            let regions = MDARegions::load(BDA_STATIC_HDR_SIZE, MIN_MDA_SECTORS, &mut buf).unwrap();
+            MDARegions::initialize(BDA_STATIC_HDR_SIZE, MIN_MDA_SECTORS, false, &mut buf).unwrap();
+            let regions =
+                MDARegions::load(BDA_STATIC_HDR_SIZE, MIN_MDA_SECTORS, false, &mut buf).unwrap();
             assert!(regions.last_update_time().is_none());
         }
 
@@ -937,6 +2074,204 @@ mod mda {
             let buf = header.to_buf();
             assert!(MDAHeader::from_buf(&buf, MDA_REGION_HDR_SIZE).is_err());
         }
+
+        /// Verify that a header with an out of range time, or with a size
+        /// so large that adding the header size to it overflows, causes an
+        /// error rather than a panic.
+        #[test]
+        fn test_from_buf_out_of_range() {
+            let data = [0u8; 3];
+            let header = MDAHeader {
+                last_updated: Utc::now(),
+                used: Bytes(data.len() as u64),
+                data_crc: crc32::checksum_castagnoli(&data),
+            };
+            let region_size = Bytes(data.len() as u64) + MDA_REGION_HDR_SIZE;
+            let valid_buf = header.to_buf();
+            for &(start, end, value) in &[
+                (16, 24, std::u64::MAX),
+                (24, 28, 2_000_000_000),
+                (8, 16, std::u64::MAX),
+            ] {
+                let mut buf = valid_buf;
+                LittleEndian::write_uint(&mut buf[start..end], value, end - start);
+                let hdr_crc = crc32::checksum_castagnoli(&buf[4..]);
+                LittleEndian::write_u32(&mut buf[..4], hdr_crc);
+                assert!(MDAHeader::from_buf(&buf, region_size).is_err());
+            }
+        }
+
+        /// Verify that a header claiming more data than fits in the region
+        /// is rejected before the data is read.
+        #[test]
+        fn test_load_region_size_error() {
+            let header = MDAHeader {
+                last_updated: Utc::now(),
+                used: Bytes(1 << 40),
+                data_crc: 0,
+            };
+            let mut buf = Cursor::new(vec![]);
+            assert!(
+                header
+                    .load_region(MIN_MDA_SECTORS.bytes(), &mut buf)
+                    .is_err()
+            );
+        }
+
+        /// Verify that a region whose data is corrupt is quarantined and
+        /// counted, and that its copy is read instead. Verify that the
+        /// quarantined region is not read again until it is rewritten.
+        /// Verify that the fallback loads and the repair are counted.
+        #[test]
+        fn test_quarantine_corrupt_region() {
+            let buf_length = *(BDA_STATIC_HDR_SIZE + MIN_MDA_SECTORS.bytes()) as usize;
+            let mut buf = Cursor::new(vec![0; buf_length]);
+            let mut regions =
+                MDARegions::initialize(BDA_STATIC_HDR_SIZE, MIN_MDA_SECTORS, false, &mut buf)
+                    .unwrap();
+
+            let data = b"metadata";
+            regions
+                .save_state(BDA_STATIC_HDR_SIZE, &Utc.timestamp(1, 0), data, false, &mut buf)
+                .unwrap();
+
+            // The first save is to region 0 and its copy, region 2.
+            let offset = MDARegions::mda_offset(
+                BDA_STATIC_HDR_SIZE,
+                0,
+                (MIN_MDA_SECTORS / NUM_MDA_REGIONS).bytes(),
+                backup_distance((MIN_MDA_SECTORS / NUM_MDA_REGIONS).bytes(), false),
+            ) as usize + _MDA_REGION_HDR_SIZE;
+            let byte = buf.get_ref()[offset];
+            buf.get_mut()[offset] = !byte;
+
+            for _ in 0..2 {
+                assert_eq!(
+                    regions.load_state(BDA_STATIC_HDR_SIZE, &mut buf).unwrap(),
+                    Some(data.to_vec())
+                );
+                assert_eq!(regions.corruptions(), 1);
+                assert!(regions.quarantined.get()[0]);
+            }
+            assert_eq!(regions.stats().fallback_loads, 2);
+
+            regions
+                .save_state(BDA_STATIC_HDR_SIZE, &Utc.timestamp(2, 0), data, false, &mut buf)
+                .unwrap();
+            assert!(regions.quarantined.get()[0]);
+            regions
+                .save_state(BDA_STATIC_HDR_SIZE, &Utc.timestamp(3, 0), data, false, &mut buf)
+                .unwrap();
+            assert!(!regions.quarantined.get()[0]);
+            assert_eq!(regions.corruptions(), 1);
+            assert_eq!(
+                regions.stats(),
+                MetadataStats {
+                    saves: 3,
+                    retried_saves: 0,
+                    crc_repairs: 1,
+                    fallback_loads: 2,
+                }
+            );
+        }
+
+        /// A device on which any write that overlaps the failing range of
+        /// offsets, if any, fails.
+        struct FailingDevice {
+            inner: Cursor<Vec<u8>>,
+            failing: Option<(u64, u64)>,
+        }
+
+        impl Read for FailingDevice {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.inner.read(buf)
+            }
+        }
+
+        impl Seek for FailingDevice {
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        impl Write for FailingDevice {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let start = self.inner.position();
+                let end = start + buf.len() as u64;
+                match self.failing {
+                    Some((fail_start, fail_end)) if start < fail_end && fail_start < end => {
+                        Err(io::Error::new(io::ErrorKind::Other, "injected write failure"))
+                    }
+                    _ => self.inner.write(buf),
+                }
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.inner.flush()
+            }
+        }
+
+        impl SyncAll for FailingDevice {
+            fn sync_all(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+
+            fn drop_cache(&mut self, _offset: u64, _length: u64) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        /// Verify that metadata is saved and loaded while writes to the
+        /// backup regions fail, that the regions are degraded once writes
+        /// have failed repeatedly, and that they recover once a write to
+        /// a backup region succeeds.
+        fn test_backup_write_failures() {
+            let buf_length = *(BDA_STATIC_HDR_SIZE + MIN_MDA_SECTORS.bytes());
+            let backup_start = MDARegions::mda_offset(
+                BDA_STATIC_HDR_SIZE,
+                NUM_PRIMARY_MDA_REGIONS,
+                (MIN_MDA_SECTORS / NUM_MDA_REGIONS).bytes(),
+                backup_distance((MIN_MDA_SECTORS / NUM_MDA_REGIONS).bytes(), false),
+            );
+            let mut dev = FailingDevice {
+                inner: Cursor::new(vec![0; buf_length as usize]),
+                failing: Some((backup_start, buf_length)),
+            };
+
+            let mut regions =
+                MDARegions::initialize(BDA_STATIC_HDR_SIZE, MIN_MDA_SECTORS, false, &mut dev)
+                    .unwrap();
+            assert!(!regions.degraded());
+
+            let data = b"metadata";
+            for secs in 1..3 {
+                regions
+                    .save_state(BDA_STATIC_HDR_SIZE, &Utc.timestamp(secs, 0), data, true, &mut dev)
+                    .unwrap();
+                assert_eq!(
+                    regions.load_state(BDA_STATIC_HDR_SIZE, &mut dev).unwrap(),
+                    Some(data.to_vec())
+                );
+                assert_eq!(regions.degraded(), secs == 2);
+            }
+            assert!(regions.quarantined.get()[2]);
+            assert!(regions.quarantined.get()[3]);
+
+            dev.failing = None;
+            regions
+                .save_state(BDA_STATIC_HDR_SIZE, &Utc.timestamp(3, 0), data, true, &mut dev)
+                .unwrap();
+            assert!(!regions.degraded());
+            assert_eq!(regions.corruptions(), 0);
+
+            // Every write to a backup region was tried again, but no
+            // region failing a write is counted as repaired.
+            let stats = regions.stats();
+            assert_eq!(stats.saves, 3);
+            assert_eq!(stats.retried_saves, 2);
+            assert_eq!(stats.crc_repairs, 0);
+        }
     }
 }
 
@@ -944,11 +2279,10 @@ mod mda {
 mod tests {
     use std::io::{Cursor, Write};
 
-    use devicemapper::{Bytes, Sectors, IEC};
+    use devicemapper::Sectors;
     use proptest::{
         collection::{vec, SizeRange}, num, option, prelude::BoxedStrategy, strategy::Strategy,
     };
-    use uuid::Uuid;
 
     use super::*;
 
@@ -968,18 +2302,21 @@ mod tests {
     }
 
     /// Return a static header with random block device and MDA size.
-    /// The block device is less than the minimum, for efficiency in testing.
+    /// The block device is little larger than the metadata it holds, for
+    /// efficiency in testing.
     fn random_static_header(blkdev_size: u64, mda_size_factor: u32) -> StaticHeader {
-        let pool_uuid = Uuid::new_v4();
-        let dev_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
+        let dev_uuid = DevUuid::new_v4();
         let mda_size = MIN_MDA_SECTORS + Sectors((mda_size_factor * 4) as u64);
-        let blkdev_size = (Bytes(IEC::Mi) + Sectors(blkdev_size).bytes()).sectors();
+        let layout = MetadataLayout::default();
+        let blkdev_size = metadata_footprint(mda_size, layout) + Sectors(blkdev_size);
         StaticHeader::new(
             pool_uuid,
             dev_uuid,
             mda_size,
             blkdev_size,
             Utc::now().timestamp() as u64,
+            layout,
         )
     }
 
@@ -1010,6 +2347,7 @@ mod tests {
                 sh.mda_size,
                 sh.blkdev_size,
                 Utc::now().timestamp() as u64,
+                MetadataLayout::default(),
             ).unwrap();
 
             prop_assert!(StaticHeader::device_identifiers(&mut buf)
@@ -1037,6 +2375,7 @@ mod tests {
                 sh.mda_size,
                 sh.blkdev_size,
                 Utc::now().timestamp() as u64,
+                MetadataLayout::default(),
             ).unwrap();
             prop_assert!(bda.last_update_time().is_none());
         }
@@ -1058,6 +2397,7 @@ mod tests {
             sh.mda_size,
             sh.blkdev_size,
             Utc::now().timestamp() as u64,
+            MetadataLayout::default(),
         ).unwrap();
 
         let timestamp0 = Utc::now();
@@ -1065,19 +2405,19 @@ mod tests {
         assert_ne!(timestamp0, timestamp1);
 
         let mut buf = Cursor::new(vec![0; *sh.blkdev_size.bytes() as usize]);
-        bda.save_state(&timestamp1, &data, &mut buf).unwrap();
+        bda.save_state(&timestamp1, &data, false, &mut buf).unwrap();
 
         // Error, because current timestamp is older than written to newer.
-        assert!(bda.save_state(&timestamp0, &data, &mut buf).is_err());
+        assert!(bda.save_state(&timestamp0, &data, false, &mut buf).is_err());
 
         let timestamp2 = Utc::now();
         let timestamp3 = Utc::now();
         assert_ne!(timestamp2, timestamp3);
 
-        bda.save_state(&timestamp3, &data, &mut buf).unwrap();
+        bda.save_state(&timestamp3, &data, false, &mut buf).unwrap();
 
         // Error, because current timestamp is older than written to newer.
-        assert!(bda.save_state(&timestamp2, &data, &mut buf).is_err());
+        assert!(bda.save_state(&timestamp2, &data, false, &mut buf).is_err());
     }
 
     proptest! {
@@ -1088,6 +2428,7 @@ mod tests {
         /// Reload BDA and verify that new BDA has correct update time.
         /// Load state using new BDA and verify correct state.
         /// Save metadata again, and reload one more time, verifying new timestamp.
+        /// Verify that the first state is kept as the older generation.
         fn check_state(
             ref sh in static_header_strategy(),
             ref state in vec(num::u8::ANY, SizeRange::default()),
@@ -1102,9 +2443,10 @@ mod tests {
                 sh.mda_size,
                 sh.blkdev_size,
                 Utc::now().timestamp() as u64,
+                MetadataLayout::default(),
             ).unwrap();
             let current_time = Utc::now();
-            bda.save_state(&current_time, &state, &mut buf).unwrap();
+            bda.save_state(&current_time, &state, false, &mut buf).unwrap();
             let loaded_state = bda.load_state(&mut buf).unwrap();
             prop_assert!(bda.last_update_time().map(|t| t == &current_time).unwrap_or(false));
             prop_assert!(loaded_state.map(|s| &s == state).unwrap_or(false));
@@ -1113,13 +2455,16 @@ mod tests {
             let loaded_state = bda.load_state(&mut buf).unwrap();
             prop_assert!(loaded_state.map(|s| &s == state).unwrap_or(false));
             prop_assert!(bda.last_update_time().map(|t| t == &current_time).unwrap_or(false));
+            prop_assert!(bda.load_older_state(&mut buf).unwrap().is_none());
 
             let current_time = Utc::now();
-            bda.save_state(&current_time, &next_state, &mut buf)
+            bda.save_state(&current_time, &next_state, true, &mut buf)
                 .unwrap();
             let loaded_state = bda.load_state(&mut buf).unwrap();
             prop_assert!(loaded_state.map(|s| &s == next_state).unwrap_or(false));
             prop_assert!(bda.last_update_time().map(|t| t == &current_time).unwrap_or(false));
+            let older_state = bda.load_older_state(&mut buf).unwrap();
+            prop_assert!(older_state.map(|s| &s == state).unwrap_or(false));
 
         }
     }
@@ -1138,6 +2483,103 @@ mod tests {
             prop_assert_eq!(sh1.reserved_size, sh2.reserved_size);
             prop_assert_eq!(sh1.flags, sh2.flags);
             prop_assert_eq!(sh1.initialization_time, sh2.initialization_time);
+            prop_assert_eq!(sh1.sequence, sh2.sequence);
+            prop_assert_eq!(sh1.destroyed_time, sh2.destroyed_time);
+        }
+    }
+
+    /// Overwrite the size fields of a sigblock buffer and recompute its CRC.
+    fn set_sizes(buf: &mut [u8], blkdev_size: u64, mda_size: u64, reserved_size: u64) {
+        LittleEndian::write_u64(&mut buf[20..28], blkdev_size);
+        LittleEndian::write_u64(&mut buf[96..104], mda_size);
+        LittleEndian::write_u64(&mut buf[104..112], reserved_size);
+        let hdr_crc = crc32::checksum_castagnoli(&buf[4..SECTOR_SIZE]);
+        LittleEndian::write_u32(&mut buf[..4], hdr_crc);
+    }
+
+    proptest! {
+        #[test]
+        /// Give a valid sigblock arbitrary size fields and flags, with a
+        /// correct CRC. Verify that parsing it does not panic, and that it
+        /// is accepted exactly when its metadata fits on the device, in which
+        /// case computing the layout of the metadata does not panic either.
+        fn sigblock_sizes_fuzz(
+            ref sh in static_header_strategy(),
+            blkdev_size in num::u64::ANY,
+            mda_size in num::u64::ANY,
+            reserved_size in num::u64::ANY,
+            tail_copy in ::proptest::bool::ANY
+        ) {
+            let mut buf = sh.sigblock_to_buf();
+            LittleEndian::write_u64(&mut buf[112..120], tail_copy as u64);
+            set_sizes(&mut buf, blkdev_size, mda_size, reserved_size);
+
+            let tail_size = if tail_copy { *BDA_TAIL_HDR_SIZE.sectors() } else { 0 };
+            let fits = blkdev_size.checked_mul(SECTOR_SIZE as u64).is_some()
+                && (*BDA_STATIC_HDR_SIZE.sectors())
+                    .checked_add(mda_size)
+                    .and_then(|size| size.checked_add(reserved_size))
+                    .and_then(|size| size.checked_add(tail_size))
+                    .map_or(false, |size| size <= blkdev_size);
+            let valid = fits
+                && mda::validate_mda_size(Sectors(mda_size)).is_ok()
+                && Sectors(reserved_size) >= MDA_RESERVED_SECTORS;
+
+            match StaticHeader::sigblock_from_buf(&buf) {
+                Ok(Some(parsed)) => {
+                    prop_assert!(valid);
+                    let size = BDA_STATIC_HDR_SIZE.sectors() + parsed.mda_size
+                        + parsed.reserved_size;
+                    prop_assert!(size <= parsed.blkdev_size);
+                    if tail_copy {
+                        prop_assert!(tail_region_offset(parsed.blkdev_size)
+                                     >= *size.bytes());
+                    }
+                }
+                Ok(None) => prop_assert!(false),
+                Err(_) => prop_assert!(!valid),
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        /// Verify that parsing an arbitrary sector that carries the Stratis
+        /// magic and a correct CRC never panics.
+        fn sigblock_arbitrary_fuzz(ref contents in vec(num::u8::ANY, SECTOR_SIZE)) {
+            let mut buf = contents.clone();
+            buf[4..20].copy_from_slice(STRAT_MAGIC);
+            buf[28] = STRAT_SIGBLOCK_VERSION;
+            let hdr_crc = crc32::checksum_castagnoli(&buf[4..SECTOR_SIZE]);
+            LittleEndian::write_u32(&mut buf[..4], hdr_crc);
+            let _ = StaticHeader::sigblock_from_buf(&buf);
+        }
+    }
+
+    #[test]
+    /// Verify that sigblocks whose size fields are inconsistent, or whose
+    /// sums overflow, are rejected.
+    fn test_sigblock_sizes_invalid() {
+        let sh = random_static_header(0, 0);
+        let blkdev_size = *sh.blkdev_size;
+        let mda_size = *sh.mda_size;
+        let reserved_size = *sh.reserved_size;
+        let mut buf = sh.sigblock_to_buf();
+        for &(blkdev, mda, reserved) in &[
+            (blkdev_size, mda_size, reserved_size),
+            (mda_size, mda_size, reserved_size),
+            (blkdev_size, mda_size, 0),
+            (blkdev_size, mda_size, u64::max_value()),
+            (u64::max_value(), mda_size, u64::max_value() - 16),
+            (u64::max_value(), mda_size, reserved_size),
+        ] {
+            set_sizes(&mut buf, blkdev, mda, reserved);
+            let result = StaticHeader::sigblock_from_buf(&buf);
+            if (blkdev, mda, reserved) == (blkdev_size, mda_size, reserved_size) {
+                assert!(result.unwrap().is_some());
+            } else {
+                assert!(result.is_err());
+            }
         }
     }
 
@@ -1159,6 +2601,7 @@ mod tests {
                 sh.mda_size,
                 sh.blkdev_size,
                 Utc::now().timestamp() as u64,
+                MetadataLayout::default(),
             ).unwrap();
 
             let reference_buf = buf.clone();
@@ -1219,6 +2662,7 @@ mod tests {
             sh.mda_size,
             sh.blkdev_size,
             ts,
+            MetadataLayout::default(),
         ).unwrap();
 
         let mut buf_newer = Cursor::new(vec![0; buf_size]);
@@ -1229,6 +2673,7 @@ mod tests {
             sh.mda_size,
             sh.blkdev_size,
             ts + 1,
+            MetadataLayout::default(),
         ).unwrap();
 
         // We should always match this reference buffer as it's the newer one.
@@ -1252,4 +2697,350 @@ mod tests {
         }
     }
 
+    #[test]
+    /// Test that if two sigblocks have the same initialization time, the one
+    /// with the higher sequence number is chosen and the other re-written.
+    fn bda_test_rewrite_lower_sequence() {
+        let mut sh = random_static_header(10000, 4);
+        let buf_size = *sh.mda_size.bytes() as usize + _BDA_STATIC_HDR_SIZE;
+        let mut buf = Cursor::new(vec![0; buf_size]);
+
+        let layout = MetadataLayout::default();
+        BDA::write(&mut buf, &sh.stage(), MetadataLocation::Both, layout).unwrap();
+
+        // Simulate an update that was interrupted after the first copy
+        let newer = sh.stage();
+        BDA::write(&mut buf, &newer, MetadataLocation::First, layout).unwrap();
+
+        let setup_result = StaticHeader::setup(&mut buf).unwrap().unwrap();
+        assert_eq!(setup_result.sequence, sh.sequence);
+
+        let (buf_loc_1, buf_loc_2) = BDA::read(&mut buf, layout).unwrap();
+        assert_eq!(&buf_loc_1[..], &newer[..]);
+        assert_eq!(&buf_loc_2[..], &newer[..]);
+    }
+
+    #[test]
+    /// Test that probing finds the newer of two sigblocks, but re-writes
+    /// neither, and that probing a device with no sigblock finds none.
+    fn bda_test_probe_read_only() {
+        let mut sh = random_static_header(10000, 4);
+        let buf_size = *sh.mda_size.bytes() as usize + _BDA_STATIC_HDR_SIZE;
+        let mut buf = Cursor::new(vec![0; buf_size]);
+        assert!(StaticHeader::probe(&mut buf).unwrap().is_none());
+
+        let layout = MetadataLayout::default();
+        BDA::write(&mut buf, &sh.stage(), MetadataLocation::Both, layout).unwrap();
+        BDA::write(&mut buf, &sh.stage(), MetadataLocation::First, layout).unwrap();
+        let reference_buf = buf.clone();
+
+        let probed = StaticHeader::probe(&mut buf).unwrap().unwrap();
+        assert_eq!(probed.sequence, sh.sequence);
+        assert_eq!(probed.pool_uuid(), sh.pool_uuid);
+        assert_eq!(probed.dev_uuid(), sh.dev_uuid);
+        assert_eq!(reference_buf.get_ref(), buf.get_ref());
+    }
+
+    #[test]
+    /// Test that a device with a tail copy of the sigblock is still found
+    /// if the beginning of the device is zeroed, and that both copies at the
+    /// beginning of the device are restored from the tail copy.
+    /// Test that a corrupted tail copy is re-written.
+    /// Test that wiping the device also wipes the tail copy.
+    fn bda_test_tail_copy() {
+        let sh = random_static_header(10000, 4);
+        let mut buf = Cursor::new(vec![0; *sh.blkdev_size.bytes() as usize]);
+        let bda = BDA::initialize(
+            &mut buf,
+            sh.pool_uuid,
+            sh.dev_uuid,
+            sh.mda_size,
+            sh.blkdev_size,
+            Utc::now().timestamp() as u64,
+            MetadataLayout {
+                tail_copy: true,
+                ..Default::default()
+            },
+        ).unwrap();
+        assert_eq!(
+            bda.tail_region(),
+            Some((sh.blkdev_size - Sectors(8), Sectors(8)))
+        );
+
+        let reference_buf = buf.clone();
+
+        buf.seek(SeekFrom::Start(0)).unwrap();
+        buf.write_all(&[0u8; _BDA_STATIC_HDR_SIZE]).unwrap();
+        assert_eq!(
+            StaticHeader::device_identifiers(&mut buf).unwrap(),
+            Some((sh.pool_uuid, sh.dev_uuid))
+        );
+        assert_eq!(reference_buf.get_ref(), buf.get_ref());
+
+        let tail_sigblock = tail_region_offset(sh.blkdev_size) + SECTOR_SIZE as u64;
+        corrupt_byte(&mut buf, tail_sigblock + 40).unwrap();
+        assert!(StaticHeader::setup(&mut buf).unwrap().is_some());
+        assert_eq!(reference_buf.get_ref(), buf.get_ref());
+
+        BDA::wipe(&mut buf).unwrap();
+        assert!(StaticHeader::device_identifiers(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    /// Test that in the spread layout the second copy of the sigblock is
+    /// SPREAD_DISTANCE past the first, that either copy is restored from
+    /// the other, that metadata saved is loaded again after setup, and that
+    /// wiping the device also wipes the second copy.
+    fn bda_test_spread() {
+        let layout = MetadataLayout {
+            spread: true,
+            ..Default::default()
+        };
+        let mda_size = MIN_MDA_SECTORS;
+        let blkdev_size = metadata_footprint(mda_size, layout) + Sectors(8);
+        let mut buf = Cursor::new(vec![0; *blkdev_size.bytes() as usize]);
+        let pool_uuid = PoolUuid::new_v4();
+        let dev_uuid = DevUuid::new_v4();
+        let mut bda = BDA::initialize(
+            &mut buf,
+            pool_uuid,
+            dev_uuid,
+            mda_size,
+            blkdev_size,
+            Utc::now().timestamp() as u64,
+            layout,
+        ).unwrap();
+        assert_eq!(bda.layout(), layout);
+        assert_eq!(
+            bda.size(),
+            BDA_STATIC_HDR_SIZE.sectors() + mda_size + MDA_RESERVED_SECTORS + SPREAD_DISTANCE
+        );
+
+        let data = b"metadata";
+        let current_time = Utc::now();
+        bda.save_state(&current_time, data, true, &mut buf).unwrap();
+
+        let (buf_loc_1, buf_loc_2) = BDA::read(&mut buf, layout).unwrap();
+        assert_eq!(&buf_loc_1[..], &buf_loc_2[..]);
+        assert!(buf.get_ref()[8 * SECTOR_SIZE..16 * SECTOR_SIZE]
+            .iter()
+            .all(|x| *x == 0u8));
+
+        let reference_buf = buf.clone();
+        let second_sigblock = *SPREAD_DISTANCE.bytes() + SECTOR_SIZE as u64;
+        for offset in &[SECTOR_SIZE as u64, second_sigblock] {
+            corrupt_byte(&mut buf, offset + 40).unwrap();
+            let mut bda = BDA::load(&mut buf).unwrap().unwrap();
+            assert_eq!(reference_buf.get_ref(), buf.get_ref());
+            assert_eq!(bda.layout(), layout);
+            assert_eq!(bda.load_state(&mut buf).unwrap(), Some(data.to_vec()));
+        }
+
+        BDA::wipe(&mut buf).unwrap();
+        assert!(StaticHeader::device_identifiers(&mut buf).unwrap().is_none());
+        assert!(buf.get_ref()[second_sigblock as usize..][..SECTOR_SIZE]
+            .iter()
+            .all(|x| *x == 0u8));
+    }
+
+    #[test]
+    /// Test that a device marked destroyed, including its tail copy of the
+    /// sigblock, belongs to no pool and holds no metadata, but still
+    /// identifies its pool and itself, and that wiping it leaves nothing.
+    fn bda_test_mark_destroyed() {
+        let sh = random_static_header(10000, 4);
+        let mut buf = Cursor::new(vec![0; *sh.blkdev_size.bytes() as usize]);
+        let mut bda = BDA::initialize(
+            &mut buf,
+            sh.pool_uuid,
+            sh.dev_uuid,
+            sh.mda_size,
+            sh.blkdev_size,
+            Utc::now().timestamp() as u64,
+            MetadataLayout {
+                tail_copy: true,
+                ..Default::default()
+            },
+        ).unwrap();
+        bda.save_state(&Utc::now(), &[0u8; 3], false, &mut buf)
+            .unwrap();
+        assert_eq!(
+            StaticHeader::destroyed_identifiers(&mut buf).unwrap(),
+            None
+        );
+
+        bda.mark_destroyed(1234, &mut buf).unwrap();
+        assert_eq!(StaticHeader::device_identifiers(&mut buf).unwrap(), None);
+        assert_eq!(
+            StaticHeader::destroyed_identifiers(&mut buf).unwrap(),
+            Some((sh.pool_uuid, sh.dev_uuid, 1234))
+        );
+        let loaded = BDA::load(&mut buf).unwrap().unwrap();
+        assert!(loaded.last_update_time().is_none());
+        assert_eq!(loaded.load_state(&mut buf).unwrap(), None);
+
+        buf.seek(SeekFrom::Start(0)).unwrap();
+        buf.write_all(&[0u8; _BDA_STATIC_HDR_SIZE]).unwrap();
+        assert_eq!(
+            StaticHeader::destroyed_identifiers(&mut buf).unwrap(),
+            Some((sh.pool_uuid, sh.dev_uuid, 1234))
+        );
+
+        BDA::wipe(&mut buf).unwrap();
+        assert_eq!(
+            StaticHeader::destroyed_identifiers(&mut buf).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    /// Verify that a device marked destroyed belongs to its pool again
+    /// once unmarked, and that metadata saved afterward is found.
+    fn bda_test_unmark_destroyed() {
+        let sh = random_static_header(10000, 4);
+        let mut buf = Cursor::new(vec![0; *sh.blkdev_size.bytes() as usize]);
+        let mut bda = BDA::initialize(
+            &mut buf,
+            sh.pool_uuid,
+            sh.dev_uuid,
+            sh.mda_size,
+            sh.blkdev_size,
+            Utc::now().timestamp() as u64,
+            MetadataLayout::default(),
+        ).unwrap();
+        bda.mark_destroyed(1234, &mut buf).unwrap();
+
+        let mut loaded = BDA::load(&mut buf).unwrap().unwrap();
+        loaded.unmark_destroyed(&mut buf).unwrap();
+        assert_eq!(
+            StaticHeader::destroyed_identifiers(&mut buf).unwrap(),
+            None
+        );
+        assert_eq!(
+            StaticHeader::device_identifiers(&mut buf).unwrap(),
+            Some((sh.pool_uuid, sh.dev_uuid))
+        );
+
+        loaded
+            .save_state(&Utc::now(), &[1u8; 3], false, &mut buf)
+            .unwrap();
+        let reloaded = BDA::load(&mut buf).unwrap().unwrap();
+        assert_eq!(reloaded.load_state(&mut buf).unwrap(), Some(vec![1u8; 3]));
+    }
+
+    #[test]
+    /// Verify that shrinking the reserved area gives up the sectors at the
+    /// end of the BDA in either layout, that the shrunk BDA and the metadata
+    /// in it are found again, and that it is shrunk only once. Verify that a
+    /// sigblock with a shrunk reserved area is refused without the flag.
+    fn bda_test_shrink_reserved() {
+        for spread in &[false, true] {
+            let layout = MetadataLayout {
+                tail_copy: true,
+                spread: *spread,
+            };
+            let sh = random_static_header(10000, 4);
+            let mut buf = Cursor::new(vec![0; *sh.blkdev_size.bytes() as usize]);
+            let mut bda = BDA::initialize(
+                &mut buf,
+                sh.pool_uuid,
+                sh.dev_uuid,
+                sh.mda_size,
+                sh.blkdev_size,
+                Utc::now().timestamp() as u64,
+                layout,
+            ).unwrap();
+            bda.save_state(&Utc::now(), &[1u8; 3], false, &mut buf)
+                .unwrap();
+
+            let size = bda.size();
+            let excess = MDA_RESERVED_SECTORS - MDA_MIN_RESERVED_SECTORS;
+            assert_eq!(
+                bda.shrink_reserved(&mut buf).unwrap(),
+                Some((size - excess, excess))
+            );
+            assert_eq!(bda.size(), size - excess);
+            assert_eq!(bda.shrink_reserved(&mut buf).unwrap(), None);
+
+            let loaded = BDA::load(&mut buf).unwrap().unwrap();
+            assert_eq!(loaded.size(), size - excess);
+            assert_eq!(loaded.layout(), layout);
+            assert_eq!(loaded.load_state(&mut buf).unwrap(), Some(vec![1u8; 3]));
+        }
+
+        let mut sh = random_static_header(10000, 4);
+        sh.reserved_size = MDA_MIN_RESERVED_SECTORS;
+        assert!(StaticHeader::sigblock_from_buf(&sh.sigblock_to_buf()).is_err());
+        sh.flags |= STRAT_SIGBLOCK_FLAG_RESERVE_SHRUNK;
+        assert!(
+            StaticHeader::sigblock_from_buf(&sh.sigblock_to_buf())
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    /// Verify that the pool name recorded in the sigblock is kept in every
+    /// copy, that it is only written if it changes, and that a name too
+    /// long for the sigblock is truncated at a character boundary.
+    fn bda_test_pool_name() {
+        let layout = MetadataLayout {
+            tail_copy: true,
+            spread: false,
+        };
+        let sh = random_static_header(10000, 4);
+        let mut buf = Cursor::new(vec![0; *sh.blkdev_size.bytes() as usize]);
+        let mut bda = BDA::initialize(
+            &mut buf,
+            sh.pool_uuid,
+            sh.dev_uuid,
+            sh.mda_size,
+            sh.blkdev_size,
+            Utc::now().timestamp() as u64,
+            layout,
+        ).unwrap();
+        assert_eq!(bda.pool_name(), None);
+
+        assert!(bda.set_pool_name("home", &mut buf).unwrap());
+        assert!(!bda.set_pool_name("home", &mut buf).unwrap());
+        assert_eq!(BDA::load(&mut buf).unwrap().unwrap().pool_name(), Some("home"));
+        assert_eq!(
+            StaticHeader::probe_tail(&mut buf).unwrap().0.pool_name(),
+            Some("home")
+        );
+
+        // Each "\u{e9}" takes two bytes, so the end of the space in the
+        // sigblock falls within one.
+        let long_name = format!("a{}", "\u{e9}".repeat(POOL_NAME_END - POOL_NAME_START));
+        assert!(bda.set_pool_name(&long_name, &mut buf).unwrap());
+        let loaded = BDA::load(&mut buf).unwrap().unwrap();
+        let recorded = loaded.pool_name().unwrap();
+        assert!(long_name.starts_with(recorded));
+        assert_eq!(recorded.len(), POOL_NAME_END - POOL_NAME_START - 1);
+        assert!(!bda.set_pool_name(&long_name, &mut buf).unwrap());
+    }
+
+    #[test]
+    /// Verify that the spread layout is refused if the primary MDA regions
+    /// would reach the second copy of the sigblock.
+    fn test_spread_too_large() {
+        let layout = MetadataLayout {
+            spread: true,
+            ..Default::default()
+        };
+        let mda_size = SPREAD_DISTANCE * 2u64;
+        let blkdev_size = metadata_footprint(mda_size, layout) + Sectors(8);
+        let mut buf = Cursor::new(vec![0; 16 * SECTOR_SIZE]);
+        assert!(
+            BDA::initialize(
+                &mut buf,
+                PoolUuid::new_v4(),
+                DevUuid::new_v4(),
+                mda_size,
+                blkdev_size,
+                Utc::now().timestamp() as u64,
+                layout,
+            ).is_err()
+        );
+    }
 }
@@ -2,6 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+mod allocation;
 #[allow(module_inception)]
 mod backstore;
 mod blockdev;
@@ -11,13 +12,30 @@ mod cleanup;
 mod data_tier;
 pub mod device;
 mod metadata;
+#[cfg(test)]
+mod memory;
+mod raid;
 mod range_alloc;
+mod restore;
 mod setup;
+mod signatures;
 mod util;
+mod writecache;
 
 pub use self::backstore::Backstore;
 pub use self::blockdev::StratBlockDev;
+pub use self::blockdevmgr::{
+    check_device_sizes, set_metadata_time_fallback, set_metadata_write_interval,
+};
 pub use self::device::blkdev_size;
-pub use self::device::is_stratis_device;
-pub use self::metadata::MIN_MDA_SECTORS;
-pub use self::setup::{find_all, get_metadata};
+pub use self::device::{is_stratis_device, probe_device};
+#[cfg(feature = "fuzzing")]
+pub use self::metadata::{fuzz_mda_header, fuzz_sigblock};
+pub use self::metadata::{MetadataLayout, MIN_MDA_SECTORS};
+pub use self::raid::recorded_raid_size;
+pub use self::restore::{reconstruct_blockdevs, restore_metadata, revive_blockdevs};
+pub use self::cleanup::wipe_destroyed_device;
+pub use self::setup::{
+    check_allocations, find_all, find_clones, find_destroyed, get_metadata,
+    get_metadata_generations, MetadataGenerations,
+};
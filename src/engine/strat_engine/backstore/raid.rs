@@ -0,0 +1,553 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// The RAID layer of a pool with redundancy, a DM raid device that lies
+// between the blockdevs of the data tier and the cap device. Each blockdev
+// is a member of the array, and gives it a meta sub-device, which holds the
+// array's superblock and write-intent bitmap, and a data sub-device, all of
+// the same size. The devicemapper crate has no support for the raid target,
+// so the device is managed here with DM ioctls directly.
+
+use devicemapper::{
+    device_exists, DevId, Device, DmDevice, DmFlags, DmNameBuf, DmOptions, LinearDev, Sectors,
+    TargetTypeBuf, IEC,
+};
+
+use stratis::{ErrorEnum, StratisError, StratisResult};
+
+use super::super::super::types::{DevUuid, PoolUuid, Redundancy};
+use super::super::device::wipe_sectors;
+use super::super::dm::get_dm;
+use super::super::names::{format_raid_ids, RaidRole};
+use super::super::serde_structs::{BaseDevSave, RaidMemberSave, RaidSave, Recordable};
+use super::super::targets::{require_target, RAID};
+
+use super::blockdevmgr::{map_to_dm, BlkDevSegment, BlockDevMgr, Segment};
+
+/// The size of the meta sub-device of each member.
+const RAID_META_SIZE: Sectors = Sectors(8 * IEC::Ki); // 4 MiB
+
+/// The size of a chunk of a RAID5 array. The data sub-device of each member
+/// is a whole number of chunks, whatever the level.
+const RAID_CHUNK_SIZE: Sectors = Sectors(128); // 64 KiB
+
+/// The raid target type that implements the redundancy. RAID5 arrays use
+/// the left-symmetric layout, as md does by default.
+fn raid_type(redundancy: Redundancy) -> &'static str {
+    match redundancy {
+        Redundancy::RAID1 => "raid1",
+        Redundancy::RAID5 => "raid5_ls",
+        Redundancy::NONE => panic!("a pool without redundancy has no RAID layer"),
+    }
+}
+
+/// The size of an array with the given redundancy, of the given number of
+/// members, each with a data sub-device of member_size.
+fn array_size(redundancy: Redundancy, members: usize, member_size: Sectors) -> Sectors {
+    match redundancy {
+        Redundancy::RAID5 => member_size * (members - 1),
+        _ => member_size,
+    }
+}
+
+/// The table of a raid device of the given size, made of the meta and data
+/// sub-devices of each member, in order. If rebuild is given, the member
+/// with that index is rebuilt from the others.
+fn raid_table(
+    redundancy: Redundancy,
+    size: Sectors,
+    members: &[(Device, Device)],
+    rebuild: Option<usize>,
+) -> Vec<(Sectors, Sectors, TargetTypeBuf, String)> {
+    let devices = members
+        .iter()
+        .map(|&(meta, data)| format!("{} {}", meta, data))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let params = match rebuild {
+        Some(index) => format!("3 {} rebuild {}", *RAID_CHUNK_SIZE, index),
+        None => format!("1 {}", *RAID_CHUNK_SIZE),
+    };
+    vec![(
+        Sectors(0),
+        size,
+        TargetTypeBuf::new("raid".into()).expect("\"raid\" is a valid target type"),
+        format!(
+            "{} {} {} {}",
+            raid_type(redundancy),
+            params,
+            members.len(),
+            devices
+        ),
+    )]
+}
+
+/// The size of the array recorded in the metadata, or None if the
+/// metadata records no members.
+pub fn recorded_raid_size(raid_save: &RaidSave) -> Option<Sectors> {
+    let redundancy = Redundancy::from_name(&raid_save.level)?;
+    let member_size = raid_save
+        .members
+        .first()?
+        .data
+        .iter()
+        .map(|seg| seg.length)
+        .sum();
+    Some(array_size(redundancy, raid_save.members.len(), member_size))
+}
+
+/// A member of the array: the segments of its meta and data sub-devices,
+/// which lie on the same blockdev, and the sub-devices themselves.
+#[derive(Debug)]
+struct RaidMember {
+    meta_segments: Vec<BlkDevSegment>,
+    data_segments: Vec<BlkDevSegment>,
+    meta: LinearDev,
+    data: LinearDev,
+}
+
+impl RaidMember {
+    /// Make the meta and data sub-devices of the member with the given
+    /// index. If the data sub-device can not be made, the meta sub-device
+    /// is removed again.
+    fn setup(
+        pool_uuid: PoolUuid,
+        index: usize,
+        meta_segments: Vec<BlkDevSegment>,
+        data_segments: Vec<BlkDevSegment>,
+    ) -> StratisResult<RaidMember> {
+        let (meta_name, meta_uuid) = format_raid_ids(pool_uuid, RaidRole::MetaSub(index));
+        let mut meta = LinearDev::setup(
+            get_dm(),
+            &meta_name,
+            Some(&meta_uuid),
+            map_to_dm(&meta_segments),
+        )?;
+        let (data_name, data_uuid) = format_raid_ids(pool_uuid, RaidRole::DataSub(index));
+        let data = match LinearDev::setup(
+            get_dm(),
+            &data_name,
+            Some(&data_uuid),
+            map_to_dm(&data_segments),
+        ) {
+            Ok(data) => data,
+            Err(err) => {
+                meta.teardown(get_dm())?;
+                return Err(err.into());
+            }
+        };
+        Ok(RaidMember {
+            meta_segments,
+            data_segments,
+            meta,
+            data,
+        })
+    }
+
+    /// Load the sub-devices of the member with the given segments, which
+    /// must be of the same sizes as those they replace.
+    fn load(
+        &mut self,
+        meta_segments: Vec<BlkDevSegment>,
+        data_segments: Vec<BlkDevSegment>,
+    ) -> StratisResult<()> {
+        self.meta.set_table(get_dm(), map_to_dm(&meta_segments))?;
+        self.meta.resume(get_dm())?;
+        self.data.set_table(get_dm(), map_to_dm(&data_segments))?;
+        self.data.resume(get_dm())?;
+        self.meta_segments = meta_segments;
+        self.data_segments = data_segments;
+        Ok(())
+    }
+
+    fn teardown(&mut self) -> StratisResult<()> {
+        self.data.teardown(get_dm())?;
+        self.meta.teardown(get_dm())?;
+        Ok(())
+    }
+}
+
+impl Recordable<RaidMemberSave> for RaidMember {
+    fn record(&self) -> RaidMemberSave {
+        RaidMemberSave {
+            meta: self.meta_segments.record(),
+            data: self.data_segments.record(),
+        }
+    }
+}
+
+/// The meta and data sub-devices of each member, in order.
+fn member_devices(members: &[RaidMember]) -> Vec<(Device, Device)> {
+    members
+        .iter()
+        .map(|member| (member.meta.device(), member.data.device()))
+        .collect()
+}
+
+/// The DM raid device of a pool with redundancy, with the sub-devices of
+/// its members.
+#[derive(Debug)]
+pub struct RaidLayer {
+    redundancy: Redundancy,
+    name: DmNameBuf,
+    device: Device,
+    size: Sectors,
+    members: Vec<RaidMember>,
+}
+
+impl RaidLayer {
+    /// Make the raid device on the sub-devices of members. If a device of
+    /// the given name exists already, as it does if stratisd has been
+    /// restarted, it is taken over as it is. If the raid device can not be
+    /// made, the sub-devices are removed again.
+    fn setup_members(
+        pool_uuid: PoolUuid,
+        redundancy: Redundancy,
+        mut members: Vec<RaidMember>,
+    ) -> StratisResult<RaidLayer> {
+        let member_size = members
+            .first()
+            .map_or(Sectors(0), |member| member.data.size());
+        let size = array_size(redundancy, members.len(), member_size);
+        let (name, uuid) = format_raid_ids(pool_uuid, RaidRole::Raid);
+
+        let result = {
+            let id = DevId::Name(&name);
+            if device_exists(get_dm(), &name)? {
+                get_dm().device_info(&id).map(|info| info.device())
+            } else {
+                let table = raid_table(redundancy, size, &member_devices(&members), None);
+                get_dm()
+                    .device_create(&name, Some(&uuid), &DmOptions::new())
+                    .and_then(|_| get_dm().table_load(&id, &table))
+                    .and_then(|_| get_dm().device_suspend(&id, &DmOptions::new()))
+                    .map(|info| info.device())
+                    .or_else(|err| {
+                        if device_exists(get_dm(), &name)? {
+                            get_dm().device_remove(&id, &DmOptions::new())?;
+                        }
+                        Err(err)
+                    })
+            }
+        };
+
+        match result {
+            Ok(device) => Ok(RaidLayer {
+                redundancy,
+                name,
+                device,
+                size,
+                members,
+            }),
+            Err(err) => {
+                for member in &mut members {
+                    member.teardown()?;
+                }
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Make a new array with the given redundancy, with every blockdev of
+    /// block_mgr as a member. Each member gives the array as much of its
+    /// space as the blockdev with the least space available can give. The
+    /// meta sub-device of each member is zeroed, so that the kernel formats
+    /// the array afresh.
+    ///
+    /// WARNING: metadata changing event
+    pub fn initialize(
+        pool_uuid: PoolUuid,
+        redundancy: Redundancy,
+        block_mgr: &mut BlockDevMgr,
+    ) -> StratisResult<RaidLayer> {
+        let least = {
+            let blockdevs = block_mgr.blockdevs();
+            redundancy.check_devices(blockdevs.len())?;
+            blockdevs
+                .iter()
+                .map(|&(_, bd)| bd.available())
+                .min()
+                .expect("redundancy.check_devices() => !blockdevs.is_empty()")
+        };
+        require_target(&RAID)?;
+
+        let member_size = if least > RAID_META_SIZE {
+            ((least - RAID_META_SIZE) / RAID_CHUNK_SIZE) * RAID_CHUNK_SIZE
+        } else {
+            Sectors(0)
+        };
+        if member_size == Sectors(0) {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                format!("a device has too little space to be a member of a {} array", redundancy),
+            ));
+        }
+
+        let allocs = block_mgr
+            .alloc_from_each(&[RAID_META_SIZE, member_size])
+            .expect("every blockdev has at least RAID_META_SIZE + member_size available");
+
+        let mut members: Vec<RaidMember> = Vec::new();
+        for (index, mut segments) in allocs.into_iter().enumerate() {
+            let data_segments = segments.pop().expect("a data segment list was requested");
+            let meta_segments = segments.pop().expect("a meta segment list was requested");
+            let member = RaidMember::setup(pool_uuid, index, meta_segments, data_segments)
+                .and_then(|mut member| {
+                    match wipe_sectors(&member.meta.devnode(), Sectors(0), RAID_META_SIZE) {
+                        Ok(_) => Ok(member),
+                        Err(err) => {
+                            member.teardown()?;
+                            Err(err)
+                        }
+                    }
+                });
+            match member {
+                Ok(member) => members.push(member),
+                Err(err) => {
+                    for member in &mut members {
+                        member.teardown()?;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        RaidLayer::setup_members(pool_uuid, redundancy, members)
+    }
+
+    /// Set up the array recorded in the metadata, on the blockdevs whose
+    /// device numbers uuid_to_devno finds.
+    pub fn setup(
+        pool_uuid: PoolUuid,
+        uuid_to_devno: &Fn(DevUuid) -> Option<Device>,
+        raid_save: &RaidSave,
+    ) -> StratisResult<RaidLayer> {
+        let redundancy = Redundancy::from_name(&raid_save.level).ok_or_else(|| {
+            StratisError::Engine(
+                ErrorEnum::Invalid,
+                format!("unknown RAID level {}", raid_save.level),
+            )
+        })?;
+
+        let mapper = |segs: &[BaseDevSave]| -> StratisResult<Vec<BlkDevSegment>> {
+            segs.iter()
+                .map(|seg| -> StratisResult<BlkDevSegment> {
+                    let device = uuid_to_devno(seg.parent).ok_or_else(|| {
+                        StratisError::Engine(
+                            ErrorEnum::NotFound,
+                            format!("missing device for UUID {:?}", &seg.parent),
+                        )
+                    })?;
+                    Ok(BlkDevSegment::new(
+                        seg.parent,
+                        Segment::new(device, seg.start, seg.length),
+                    ))
+                })
+                .collect()
+        };
+
+        let mut members: Vec<RaidMember> = Vec::new();
+        for (index, member_save) in raid_save.members.iter().enumerate() {
+            let member = mapper(&member_save.meta).and_then(|meta_segments| {
+                let data_segments = mapper(&member_save.data)?;
+                RaidMember::setup(pool_uuid, index, meta_segments, data_segments)
+            });
+            match member {
+                Ok(member) => members.push(member),
+                Err(err) => {
+                    for member in &mut members {
+                        member.teardown()?;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        RaidLayer::setup_members(pool_uuid, redundancy, members)
+    }
+
+    /// The device number of the raid device.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// The size of the raid device, all of which is usable by the layers
+    /// above.
+    pub fn size(&self) -> Sectors {
+        self.size
+    }
+
+    /// The redundancy of the array.
+    pub fn redundancy(&self) -> Redundancy {
+        self.redundancy
+    }
+
+    /// The names of the sub-devices of each member, with the segments
+    /// mapped by each.
+    pub fn member_segments(&self) -> Vec<(DmNameBuf, &[BlkDevSegment])> {
+        self.members
+            .iter()
+            .flat_map(|member| {
+                vec![
+                    (member.meta.name().to_owned(), member.meta_segments.as_slice()),
+                    (member.data.name().to_owned(), member.data_segments.as_slice()),
+                ]
+            })
+            .collect()
+    }
+
+    /// The index of the member on the blockdev, if it is a member.
+    pub fn member_index(&self, uuid: DevUuid) -> Option<usize> {
+        self.members
+            .iter()
+            .position(|member| member.data_segments.iter().any(|seg| seg.uuid == uuid))
+    }
+
+    /// Replace the member on the blockdev old with a member on the blockdev
+    /// new of block_mgr, which the kernel rebuilds from the other members,
+    /// so that nothing is read from old. The sub-devices of the member keep
+    /// their names, and are loaded with space allocated from new while the
+    /// raid device is suspended; the meta sub-device is zeroed, so that the
+    /// kernel formats it afresh. If the raid device can not be loaded, the
+    /// member is restored as it was, though the space allocated from new is
+    /// not given back.
+    ///
+    /// WARNING: metadata changing event
+    pub fn replace_member(
+        &mut self,
+        old: DevUuid,
+        new: DevUuid,
+        block_mgr: &mut BlockDevMgr,
+    ) -> StratisResult<()> {
+        let index = self.member_index(old).ok_or_else(|| {
+            StratisError::Engine(
+                ErrorEnum::NotFound,
+                format!("blockdev {} is not a member of the array", old.simple()),
+            )
+        })?;
+        let member_size = self.members[index].data.size();
+
+        let except = block_mgr
+            .blockdevs()
+            .iter()
+            .map(|&(uuid, _)| uuid)
+            .filter(|&uuid| uuid != new)
+            .collect::<Vec<_>>();
+        let mut segments = block_mgr
+            .alloc_space_except(&[RAID_META_SIZE, member_size], &except)
+            .ok_or_else(|| {
+                StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!(
+                        "blockdev {} has too little space to be a member of the array",
+                        new.simple()
+                    ),
+                )
+            })?;
+        let data_segments = segments.pop().expect("a data segment list was requested");
+        let meta_segments = segments.pop().expect("a meta segment list was requested");
+
+        let name = self.name.clone();
+        let id = DevId::Name(&name);
+        get_dm().device_suspend(&id, &DmOptions::new().set_flags(DmFlags::DM_SUSPEND))?;
+        let previous = (
+            self.members[index].meta_segments.clone(),
+            self.members[index].data_segments.clone(),
+        );
+        if let Err(err) = self.load_rebuild(index, meta_segments, data_segments) {
+            self.members[index].load(previous.0, previous.1)?;
+            get_dm().device_suspend(&id, &DmOptions::new())?;
+            return Err(err);
+        }
+        get_dm().device_suspend(&id, &DmOptions::new())?;
+        Ok(())
+    }
+
+    /// Load the member with the given index with the given segments, and
+    /// load the raid device with a table that rebuilds it.
+    ///
+    /// Precondition: the raid device is suspended.
+    fn load_rebuild(
+        &mut self,
+        index: usize,
+        meta_segments: Vec<BlkDevSegment>,
+        data_segments: Vec<BlkDevSegment>,
+    ) -> StratisResult<()> {
+        self.members[index].load(meta_segments, data_segments)?;
+        wipe_sectors(
+            &self.members[index].meta.devnode(),
+            Sectors(0),
+            RAID_META_SIZE,
+        )?;
+        let table = raid_table(
+            self.redundancy,
+            self.size,
+            &member_devices(&self.members),
+            Some(index),
+        );
+        get_dm().table_load(&DevId::Name(&self.name), &table)?;
+        Ok(())
+    }
+
+    /// Remove the raid device, then the sub-devices of its members.
+    pub fn teardown(&mut self) -> StratisResult<()> {
+        let id = DevId::Name(&self.name);
+        get_dm().device_suspend(&id, &DmOptions::new().set_flags(DmFlags::DM_SUSPEND))?;
+        get_dm().device_remove(&id, &DmOptions::new())?;
+        for member in &mut self.members {
+            member.teardown()?;
+        }
+        Ok(())
+    }
+}
+
+impl Recordable<RaidSave> for RaidLayer {
+    fn record(&self) -> RaidSave {
+        RaidSave {
+            level: self.redundancy.to_string(),
+            members: self.members.iter().map(|member| member.record()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the table lists the meta and data sub-devices of each
+    /// member in order, that a RAID5 array holds all but one member's worth
+    /// of data, and that a member to be rebuilt is named by its index.
+    fn test_raid_table() {
+        let members = vec![
+            (Device { major: 253, minor: 1 }, Device { major: 253, minor: 2 }),
+            (Device { major: 253, minor: 3 }, Device { major: 253, minor: 4 }),
+            (Device { major: 253, minor: 5 }, Device { major: 253, minor: 6 }),
+        ];
+        let member_size = Sectors(1024);
+
+        let size = array_size(Redundancy::RAID1, members.len(), member_size);
+        assert_eq!(size, member_size);
+        assert_eq!(
+            raid_table(Redundancy::RAID1, size, &members[..2], None),
+            vec![(
+                Sectors(0),
+                Sectors(1024),
+                TargetTypeBuf::new("raid".into()).unwrap(),
+                "raid1 1 128 2 253:1 253:2 253:3 253:4".to_owned()
+            )]
+        );
+
+        let size = array_size(Redundancy::RAID5, members.len(), member_size);
+        assert_eq!(size, Sectors(2048));
+        assert_eq!(
+            raid_table(Redundancy::RAID5, size, &members, None)[0].3,
+            "raid5_ls 1 128 3 253:1 253:2 253:3 253:4 253:5 253:6"
+        );
+        assert_eq!(
+            raid_table(Redundancy::RAID5, size, &members, Some(1))[0].3,
+            "raid5_ls 3 128 rebuild 1 3 253:1 253:2 253:3 253:4 253:5 253:6"
+        );
+    }
+}
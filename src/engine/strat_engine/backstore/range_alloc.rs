@@ -127,7 +127,6 @@ impl RangeAllocator {
         Ok(())
     }
 
-    #[allow(dead_code)]
     /// Mark ranges previously marked as used as now unused.
     fn remove_ranges(&mut self, to_free: &[(Sectors, Sectors)]) -> () {
         for &(off, len) in to_free {
@@ -180,6 +179,28 @@ impl RangeAllocator {
         }
     }
 
+    /// Whether the range (off, len) lies wholly within a single range
+    /// marked as used.
+    pub fn in_use(&self, off: Sectors, len: Sectors) -> bool {
+        self.check_for_overflow(off, len).is_ok() && self.used
+            .range((Unbounded, Included(off)))
+            .rev()
+            .next()
+            .map_or(false, |(prev_off, prev_len)| *prev_off + *prev_len >= off + len)
+    }
+
+    /// Mark the range (off, len), previously marked as used, as now unused.
+    /// Return an error, and change nothing, if the range does not lie
+    /// wholly within a single range marked as used.
+    pub fn release(&mut self, off: Sectors, len: Sectors) -> StratisResult<()> {
+        if !self.in_use(off, len) {
+            let err_msg = format!("range ({}, {}) is not wholly in use", off, len);
+            return Err(StratisError::Engine(ErrorEnum::Invalid, err_msg));
+        }
+        self.remove_ranges(&[(off, len)]);
+        Ok(())
+    }
+
     /// Available sectors
     pub fn available(&self) -> Sectors {
         self.limit - self.used()
@@ -409,6 +430,23 @@ mod tests {
         );
     }
 
+    #[test]
+    /// Verify that release() frees a range at the end of a range in use,
+    /// and that it refuses, changing nothing, to free a range that is not
+    /// wholly in use.
+    fn test_allocator_release() {
+        let mut allocator =
+            RangeAllocator::new(Sectors(128), &[(Sectors(0), Sectors(40))]).unwrap();
+
+        assert!(allocator.release(Sectors(30), Sectors(20)).is_err());
+        assert!(allocator.release(Sectors(120), Sectors(20)).is_err());
+        assert_eq!(allocator.used(), Sectors(40));
+
+        allocator.release(Sectors(30), Sectors(10)).unwrap();
+        assert_eq!(allocator.used_ranges(), vec![(Sectors(0), Sectors(30))]);
+        assert!(!allocator.in_use(Sectors(30), Sectors(1)));
+    }
+
     #[test]
     #[should_panic]
     /// Verify that remove_ranges() panics when an element at the
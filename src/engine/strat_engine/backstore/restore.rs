@@ -0,0 +1,257 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to restore the metadata of a pool that can not be set up from the
+// metadata on its devices, or to reconstruct the pool from its metadata on
+// fresh devices.
+
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, Utc};
+
+use devicemapper::{Bytes, Device, Sectors};
+
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult, StratisResultExt};
+
+use super::super::super::types::{DevUuid, PoolUuid};
+
+use super::super::serde_structs::BlockDevSave;
+
+use super::device::{blkdev_size, identify, resolve_devices, DevOwnership};
+use super::metadata::{metadata_footprint, MetadataLayout, BDA, MIN_MDA_SECTORS};
+
+/// Write metadata to the MDAs of the devices in devnodes that belong to the
+/// pool, in place of whatever metadata they hold. The metadata is stamped
+/// with a time later than that of any metadata already on the devices, so
+/// that it is the metadata found when the pool is next set up.
+/// A device whose BDA can not be read, or whose MDA is too small for the
+/// metadata, is skipped.
+/// Returns an error if the metadata is not written to any device.
+#[allow(implicit_hasher)]
+pub fn restore_metadata(
+    pool_uuid: PoolUuid,
+    devnodes: &HashMap<Device, PathBuf>,
+    metadata: &[u8],
+) -> StratisResult<()> {
+    let mut bdas = Vec::new();
+    for devnode in devnodes.values() {
+        let loaded = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(devnode)
+            .with_devnode(devnode)
+            .and_then(|mut f| BDA::load(&mut f).map(|bda| bda.map(|bda| (f, bda))))
+            .with_devnode(devnode);
+        match loaded {
+            Ok(Some((f, bda))) => {
+                if bda.pool_uuid() == pool_uuid {
+                    bdas.push((devnode, f, bda));
+                }
+            }
+            Ok(None) => {}
+            Err(err) => warn!("not restoring metadata to {}, reason: {}", devnode.display(), err),
+        }
+    }
+
+    let now = Utc::now();
+    let time = match bdas.iter()
+        .filter_map(|&(_, _, ref bda)| bda.last_update_time())
+        .max()
+    {
+        Some(latest) if *latest >= now => *latest + Duration::nanoseconds(1),
+        _ => now,
+    };
+
+    let data_size = Bytes(metadata.len() as u64).sectors();
+    let mut restored = false;
+    for (devnode, mut f, mut bda) in bdas {
+        if bda.max_data_size() < data_size {
+            continue;
+        }
+        match bda.save_state(&time, metadata, true, &mut f) {
+            Ok(()) => restored = true,
+            Err(err) => warn!(
+                "unable to restore metadata to {}, reason: {}",
+                devnode.display(),
+                err
+            ),
+        }
+    }
+
+    if restored {
+        Ok(())
+    } else {
+        let err_msg = format!("unable to restore metadata to any device in pool {}", pool_uuid);
+        Err(StratisError::Engine(ErrorEnum::Error, err_msg))
+    }
+}
+
+/// Clear the mark of a destroyed pool from the sigblocks of the devices in
+/// devnodes, which must all have belonged to the pool, so that they belong
+/// to it again. Every device is checked before any is written.
+#[allow(implicit_hasher)]
+pub fn revive_blockdevs(
+    pool_uuid: PoolUuid,
+    devnodes: &HashMap<Device, PathBuf>,
+) -> StratisResult<()> {
+    let mut bdas = Vec::new();
+    for devnode in devnodes.values() {
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(devnode)
+            .with_devnode(devnode)?;
+        match BDA::load(&mut f).with_devnode(devnode)? {
+            Some(ref bda) if bda.pool_uuid() != pool_uuid => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("DeviceInOtherPool")
+                        .param("devnode", devnode.display())
+                        .param("owner", bda.pool_uuid()),
+                ));
+            }
+            Some(bda) => bdas.push((devnode, f, bda)),
+            None => {
+                let err_msg = format!("device {} has no Stratis sigblock", devnode.display());
+                return Err(StratisError::Engine(ErrorEnum::NotFound, err_msg));
+            }
+        }
+    }
+
+    for (devnode, mut f, mut bda) in bdas {
+        bda.unmark_destroyed(&mut f).with_devnode(devnode)?;
+    }
+    Ok(())
+}
+
+/// Write a new BDA to each of paths, one for each blockdev recorded in
+/// blockdev_save, in the order recorded. Each device takes the place of the
+/// corresponding recorded blockdev under a new device UUID, and
+/// blockdev_save is updated to use the new UUIDs. The MDAs of the new BDAs
+/// are empty.
+/// Every device must be unowned and large enough for the segments allocated
+/// on the blockdev it replaces; this is checked before any device is written.
+/// Returns the devices written to.
+pub fn reconstruct_blockdevs(
+    pool_uuid: PoolUuid,
+    blockdev_save: &mut BlockDevSave,
+    paths: &[&Path],
+) -> StratisResult<HashMap<Device, PathBuf>> {
+    if paths.len() != blockdev_save.devs.len() {
+        let err_msg = format!(
+            "{} devices were specified, but the metadata records {} blockdevs",
+            paths.len(),
+            blockdev_save.devs.len()
+        );
+        return Err(StratisError::Engine(ErrorEnum::Invalid, err_msg));
+    }
+
+    let devices = resolve_devices(paths)?;
+    if devices.len() != paths.len() {
+        let err_msg = "the same device was specified more than once";
+        return Err(StratisError::Engine(ErrorEnum::Invalid, err_msg.into()));
+    }
+
+    // The extent of the segments allocated on each recorded blockdev, as
+    // the lowest start and the highest end.
+    let mut extents: HashMap<DevUuid, (Sectors, Sectors)> = HashMap::new();
+    for seg in blockdev_save.allocs.iter().flat_map(|allocs| allocs.iter()) {
+        let extent = extents
+            .entry(seg.parent)
+            .or_insert((seg.start, seg.start + seg.length));
+        extent.0 = min(extent.0, seg.start);
+        extent.1 = max(extent.1, seg.start + seg.length);
+    }
+
+    let bda_size = metadata_footprint(MIN_MDA_SECTORS, MetadataLayout::default());
+    let mut planned = Vec::new();
+    for (path, bd_save) in paths.iter().zip(blockdev_save.devs.iter()) {
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_devnode(path)?;
+        let dev_size = blkdev_size(&f).with_devnode(path)?.sectors();
+
+        match identify(path).with_devnode(path)? {
+            DevOwnership::Unowned => {}
+            DevOwnership::Theirs(signature) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("DeviceHasSignature")
+                        .param("devnode", path.display())
+                        .param("signature", signature),
+                ));
+            }
+            DevOwnership::Ours(owner, _) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("DeviceInOtherPool")
+                        .param("devnode", path.display())
+                        .param("owner", owner),
+                ));
+            }
+            DevOwnership::Destroyed(owner, _) => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("DeviceOfDestroyedPool")
+                        .param("devnode", path.display())
+                        .param("owner", owner),
+                ));
+            }
+        }
+
+        if let Some(&(start, end)) = extents.get(&bd_save.uuid) {
+            if start < bda_size || dev_size < end {
+                let err_msg = format!(
+                    "device {} of {} sectors can not hold segments {} to {} of blockdev {}",
+                    path.display(),
+                    dev_size,
+                    start,
+                    end,
+                    bd_save.uuid
+                );
+                return Err(StratisError::Engine(ErrorEnum::Invalid, err_msg));
+            }
+        }
+
+        let device = *devices
+            .iter()
+            .find(|&(_, p)| p == path)
+            .expect("devices was resolved from paths")
+            .0;
+        planned.push((device, path, dev_size, f, bd_save.uuid));
+    }
+
+    let mut uuids = HashMap::new();
+    let mut devnodes = HashMap::new();
+    for (device, path, dev_size, mut f, old_uuid) in planned {
+        let dev_uuid = DevUuid::new_v4();
+        BDA::initialize(
+            &mut f,
+            pool_uuid,
+            dev_uuid,
+            MIN_MDA_SECTORS,
+            dev_size,
+            Utc::now().timestamp() as u64,
+            MetadataLayout::default(),
+        ).with_devnode(path)?;
+        uuids.insert(old_uuid, dev_uuid);
+        devnodes.insert(device, path.to_path_buf());
+    }
+
+    for bd_save in &mut blockdev_save.devs {
+        bd_save.uuid = uuids[&bd_save.uuid];
+    }
+    for seg in blockdev_save.allocs.iter_mut().flat_map(|allocs| allocs.iter_mut()) {
+        if let Some(uuid) = uuids.get(&seg.parent) {
+            seg.parent = *uuid;
+        }
+    }
+
+    Ok(devnodes)
+}
@@ -7,21 +7,29 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
+use std::io::{Read, Seek};
 use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, TimeZone, Utc};
 use serde_json;
 
 use devicemapper::{devnode_to_devno, Device, Sectors};
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult, StratisResultExt};
 
-use super::super::super::types::{BlockDevTier, DevUuid, PoolUuid};
+use super::super::super::event::{get_engine_listener_list, EngineEvent};
+use super::super::super::types::{
+    BlockDevState, BlockDevTier, ClonedDevice, DevUuid, MaybeDbusPath, PoolUuid,
+    ReclaimableDevice,
+};
 
+use super::super::device::SyncAll;
 use super::super::serde_structs::{BackstoreSave, BaseBlockDevSave, PoolSave};
 
 use super::blockdev::StratBlockDev;
 use super::device::blkdev_size;
 use super::metadata::{StaticHeader, BDA};
+use super::range_alloc::RangeAllocator;
 use super::util::get_stratis_block_devices;
 
 /// Find all Stratis devices.
@@ -34,9 +42,13 @@ pub fn find_all() -> StratisResult<HashMap<PoolUuid, HashMap<Device, PathBuf>>>
         match devnode_to_devno(&devnode)? {
             None => continue,
             Some(devno) => {
-                if let Some((pool_uuid, _)) = StaticHeader::device_identifiers(
-                    &mut OpenOptions::new().read(true).open(&devnode)?,
-                )? {
+                if let Some((pool_uuid, _)) = OpenOptions::new()
+                    .read(true)
+                    .open(&devnode)
+                    .with_devnode(&devnode)
+                    .and_then(|mut f| StaticHeader::device_identifiers(&mut f))
+                    .with_devnode(&devnode)?
+                {
                     pool_map
                         .entry(pool_uuid)
                         .or_insert_with(HashMap::new)
@@ -48,6 +60,102 @@ pub fn find_all() -> StratisResult<HashMap<PoolUuid, HashMap<Device, PathBuf>>>
     Ok(pool_map)
 }
 
+/// Find all devices that kept their Stratis sigblocks when their pool was
+/// destroyed.
+pub fn find_destroyed() -> StratisResult<Vec<ReclaimableDevice>> {
+    let mut devices = Vec::new();
+
+    for devnode in get_stratis_block_devices()? {
+        if let Some((pool_uuid, dev_uuid, destroyed)) = OpenOptions::new()
+            .read(true)
+            .open(&devnode)
+            .with_devnode(&devnode)
+            .and_then(|mut f| StaticHeader::destroyed_identifiers(&mut f))
+            .with_devnode(&devnode)?
+        {
+            devices.push(ReclaimableDevice {
+                devnode,
+                pool_uuid,
+                dev_uuid,
+                destroyed: Utc.timestamp(destroyed as i64, 0),
+            });
+        }
+    }
+    Ok(devices)
+}
+
+/// Find the devices among devnodes that are clones of other devices of the
+/// pool, claiming the same device UUID. Of the devices claiming a UUID, the
+/// one kept is a member of the pool as set up, if any is, and otherwise the
+/// one whose metadata was updated most recently. If the metadata of more
+/// than one was updated at the same time, the one with the lowest device
+/// node is kept, so that the same device is kept every time.
+#[allow(implicit_hasher)]
+pub fn find_clones(
+    pool_uuid: PoolUuid,
+    devnodes: &HashMap<Device, PathBuf>,
+    members: &HashSet<PathBuf>,
+) -> StratisResult<Vec<ClonedDevice>> {
+    let mut devices = Vec::new();
+    for devnode in devnodes.values() {
+        let f = OpenOptions::new()
+            .read(true)
+            .open(devnode)
+            .with_devnode(devnode)?;
+        devices.push((devnode.as_path(), f));
+    }
+    find_clones_from(pool_uuid, &mut devices, members)
+}
+
+/// Find the clones among a set of devices, each paired with its device
+/// node, as find_clones() does.
+#[allow(implicit_hasher)]
+pub fn find_clones_from<F>(
+    pool_uuid: PoolUuid,
+    devices: &mut [(&Path, F)],
+    members: &HashSet<PathBuf>,
+) -> StratisResult<Vec<ClonedDevice>>
+where
+    F: Read + Seek + SyncAll,
+{
+    let mut claims: HashMap<DevUuid, Vec<(&Path, Option<DateTime<Utc>>)>> = HashMap::new();
+    for &mut (devnode, ref mut f) in devices.iter_mut() {
+        if let Some(bda) = BDA::load(f).with_devnode(devnode)? {
+            if bda.pool_uuid() == pool_uuid {
+                claims
+                    .entry(bda.dev_uuid())
+                    .or_insert_with(Vec::new)
+                    .push((devnode, bda.last_update_time().cloned()));
+            }
+        }
+    }
+
+    let mut clones = Vec::new();
+    for (dev_uuid, mut claimants) in claims {
+        if claimants.len() < 2 {
+            continue;
+        }
+
+        // Sort the device to keep first.
+        claimants.sort_by(|&(devnode_a, time_a), &(devnode_b, time_b)| {
+            members
+                .contains(devnode_b)
+                .cmp(&members.contains(devnode_a))
+                .then_with(|| time_b.cmp(&time_a))
+                .then_with(|| devnode_a.cmp(devnode_b))
+        });
+        let kept = claimants[0].0;
+        clones.extend(claimants[1..].iter().map(|&(devnode, _)| ClonedDevice {
+            devnode: devnode.to_owned(),
+            pool_uuid,
+            dev_uuid,
+            kept: kept.to_owned(),
+        }));
+    }
+    clones.sort_by(|a, b| a.devnode.cmp(&b.devnode));
+    Ok(clones)
+}
+
 /// Get the most recent metadata from a set of Devices for a given pool UUID.
 /// Returns None if no metadata found for this pool.
 #[allow(implicit_hasher)]
@@ -55,17 +163,38 @@ pub fn get_metadata(
     pool_uuid: PoolUuid,
     devnodes: &HashMap<Device, PathBuf>,
 ) -> StratisResult<Option<PoolSave>> {
-    // Get pairs of device nodes and matching BDAs
+    let mut devices = Vec::new();
+    for devnode in devnodes.values() {
+        let f = OpenOptions::new()
+            .read(true)
+            .open(devnode)
+            .with_devnode(devnode)?;
+        devices.push((devnode.as_path(), f));
+    }
+    get_metadata_from(pool_uuid, &mut devices)
+}
+
+/// Get the most recent metadata for a given pool UUID from a set of
+/// devices, each paired with its device node. The devices need not be
+/// files, so that the search can be run against devices held in memory.
+/// Returns None if no metadata found for this pool.
+pub fn get_metadata_from<F>(
+    pool_uuid: PoolUuid,
+    devices: &mut [(&Path, F)],
+) -> StratisResult<Option<PoolSave>>
+where
+    F: Read + Seek + SyncAll,
+{
+    // Get pairs of indices of devices and matching BDAs
     // If no BDA, or BDA UUID does not match pool UUID, skip.
     // If there is an error reading the BDA, error. There could have been
     // vital information on that BDA, for example, it may have contained
     // the newest metadata.
     let mut bdas = Vec::new();
-    for devnode in devnodes.values() {
-        let bda = BDA::load(&mut OpenOptions::new().read(true).open(devnode)?)?;
-        if let Some(bda) = bda {
+    for (index, &mut (devnode, ref mut f)) in devices.iter_mut().enumerate() {
+        if let Some(bda) = BDA::load(f).with_devnode(devnode)? {
             if bda.pool_uuid() == pool_uuid {
-                bdas.push((devnode, bda));
+                bdas.push((index, bda));
             }
         }
     }
@@ -83,17 +212,14 @@ pub fn get_metadata(
         }
     };
 
-    // Try to read from all available devnodes that could contain most
+    // Try to read from all available devices that could contain most
     // recent metadata. In the event of errors, continue to try until all are
     // exhausted.
-    for &(devnode, ref bda) in bdas.iter()
+    for &(index, ref bda) in bdas.iter()
         .filter(|&&(_, ref bda)| bda.last_update_time() == Some(most_recent_time))
     {
-        let poolsave = OpenOptions::new()
-            .read(true)
-            .open(devnode)
+        let poolsave = bda.load_state(&mut devices[index].1)
             .ok()
-            .and_then(|mut f| bda.load_state(&mut f).ok())
             .and_then(|opt| opt)
             .and_then(|data| serde_json::from_slice(&data).ok());
 
@@ -109,12 +235,170 @@ pub fn get_metadata(
     Err(StratisError::Engine(ErrorEnum::NotFound, err_str.into()))
 }
 
+/// The segments recorded as allocated on each blockdev, in either tier.
+fn segment_table(backstore_save: &BackstoreSave) -> HashMap<DevUuid, Vec<(Sectors, Sectors)>> {
+    let mut segment_table: HashMap<DevUuid, Vec<(Sectors, Sectors)>> = HashMap::new();
+    for seg in &backstore_save.data_tier.blockdev.allocs[0] {
+        segment_table
+            .entry(seg.parent)
+            .or_insert_with(Vec::default)
+            .push((seg.start, seg.length))
+    }
+
+    if let Some(ref raid) = backstore_save.raid {
+        for seg in raid.members
+            .iter()
+            .flat_map(|member| member.meta.iter().chain(member.data.iter()))
+        {
+            segment_table
+                .entry(seg.parent)
+                .or_insert_with(Vec::default)
+                .push((seg.start, seg.length))
+        }
+    }
+
+    if let Some(ref cache_tier) = backstore_save.cache_tier {
+        for seg in cache_tier.blockdev.allocs.iter().flat_map(|i| i.iter()) {
+            segment_table
+                .entry(seg.parent)
+                .or_insert_with(Vec::default)
+                .push((seg.start, seg.length))
+        }
+    }
+    segment_table
+}
+
+/// The generations of a pool's metadata held by the MDAs of its devices,
+/// with the layout of each device, so that the generations can be checked
+/// against the devices without the devices being touched again.
+#[derive(Debug)]
+pub struct MetadataGenerations {
+    /// The generations, older first, as read from the device most recently
+    /// written. A generation that could not be read is None; the older
+    /// generation is left out if metadata has been saved only once.
+    pub generations: Vec<Option<Vec<u8>>>,
+    /// The size of each device of the pool, and the ranges on it reserved
+    /// for its BDA, which are never allocated.
+    pub devices: HashMap<DevUuid, (Sectors, Vec<(Sectors, Sectors)>)>,
+}
+
+/// Get the generations of the metadata of the pool from a set of Devices.
+/// Returns None if no metadata found for this pool.
+#[allow(implicit_hasher)]
+pub fn get_metadata_generations(
+    pool_uuid: PoolUuid,
+    devnodes: &HashMap<Device, PathBuf>,
+) -> StratisResult<Option<MetadataGenerations>> {
+    let mut devices = Vec::new();
+    for devnode in devnodes.values() {
+        let f = OpenOptions::new()
+            .read(true)
+            .open(devnode)
+            .with_devnode(devnode)?;
+        devices.push((devnode.as_path(), f));
+    }
+    get_metadata_generations_from(pool_uuid, &mut devices)
+}
+
+/// Get the generations of the metadata of the pool from a set of devices,
+/// each paired with its device node, as get_metadata_from() gets the most
+/// recent. The older generation is read from the same device as the newer,
+/// the first of the devices most recently written from which the newer can
+/// be read.
+/// Returns None if no metadata found for this pool.
+pub fn get_metadata_generations_from<F>(
+    pool_uuid: PoolUuid,
+    devices: &mut [(&Path, F)],
+) -> StratisResult<Option<MetadataGenerations>>
+where
+    F: Read + Seek + SyncAll,
+{
+    let mut bdas = Vec::new();
+    for (index, &mut (devnode, ref mut f)) in devices.iter_mut().enumerate() {
+        if let Some(bda) = BDA::load(f).with_devnode(devnode)? {
+            if bda.pool_uuid() == pool_uuid {
+                bdas.push((index, bda));
+            }
+        }
+    }
+
+    let layouts = bdas.iter()
+        .map(|&(_, ref bda)| {
+            let mut reserved = vec![(Sectors(0), bda.size())];
+            reserved.extend(bda.tail_region());
+            (bda.dev_uuid(), (bda.dev_size(), reserved))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let most_recent_time = match bdas.iter()
+        .filter_map(|&(_, ref bda)| bda.last_update_time())
+        .max()
+    {
+        Some(time) => *time,
+        None => return Ok(None),
+    };
+
+    let mut generations = None;
+    for &(index, ref bda) in bdas.iter()
+        .filter(|&&(_, ref bda)| bda.last_update_time() == Some(&most_recent_time))
+    {
+        let f = &mut devices[index].1;
+        if let Ok(Some(newer)) = bda.load_state(f) {
+            generations = Some(match bda.load_older_state(f) {
+                Ok(Some(older)) => vec![Some(older), Some(newer)],
+                Ok(None) => vec![Some(newer)],
+                Err(_) => vec![None, Some(newer)],
+            });
+            break;
+        }
+    }
+
+    Ok(Some(MetadataGenerations {
+        generations: generations.unwrap_or_else(|| vec![None]),
+        devices: layouts,
+    }))
+}
+
+/// Check that the segments recorded as allocated on each blockdev lie within
+/// the blockdev, clear of the ranges reserved on it and of each other, as
+/// they must for the blockdev to be set up. The size and reserved ranges of
+/// each blockdev are given by layouts.
+/// Returns an error if a blockdev on which segments are recorded has no
+/// layout, or if any segments are misplaced.
+#[allow(implicit_hasher)]
+pub fn check_allocations(
+    backstore_save: &BackstoreSave,
+    layouts: &HashMap<DevUuid, (Sectors, Vec<(Sectors, Sectors)>)>,
+) -> StratisResult<()> {
+    for (dev_uuid, segments) in segment_table(backstore_save) {
+        let &(size, ref reserved) = layouts.get(&dev_uuid).ok_or_else(|| {
+            StratisError::Engine(
+                ErrorEnum::NotFound,
+                format!("segments are allocated on unknown blockdev {}", dev_uuid),
+            )
+        })?;
+        let mut ranges = reserved.clone();
+        ranges.extend(segments);
+        RangeAllocator::new(size, &ranges).map_err(|err| {
+            StratisError::Engine(
+                ErrorEnum::Invalid,
+                format!("allocations on blockdev {} are inconsistent: {}", dev_uuid, err),
+            )
+        })?;
+    }
+    Ok(())
+}
+
 /// Get all the blockdevs corresponding to this pool that can be obtained from
 /// the given devices. Sort the blockdevs in the order in which they were
 /// recorded in the metadata.
 /// Returns an error if a BDA can not be read or can not be found on any
 /// blockdev in devnodes.
 /// Returns an error if the blockdevs obtained do not match the metadata.
+/// The exception is a volatile cache tier, whose devices do not survive a
+/// reboot: if its devices are not all found, the pool is set up without a
+/// cache, and the metadata of any that are found is wiped, as their contents
+/// are of no use.
 /// Returns a tuple, of which the first are the data devs, and the second
 /// are the devs that support the cache tier.
 /// Precondition: Every device in devnodes has already been determined to
@@ -146,22 +430,7 @@ pub fn get_blockdevs(
             None => HashMap::new(),
         };
 
-    let mut segment_table: HashMap<DevUuid, Vec<(Sectors, Sectors)>> = HashMap::new();
-    for seg in &backstore_save.data_tier.blockdev.allocs[0] {
-        segment_table
-            .entry(seg.parent)
-            .or_insert_with(Vec::default)
-            .push((seg.start, seg.length))
-    }
-
-    if let Some(ref cache_tier) = backstore_save.cache_tier {
-        for seg in cache_tier.blockdev.allocs.iter().flat_map(|i| i.iter()) {
-            segment_table
-                .entry(seg.parent)
-                .or_insert_with(Vec::default)
-                .push((seg.start, seg.length))
-        }
-    }
+    let segment_table = segment_table(backstore_save);
 
     // Construct a single StratBlockDev. Return the tier to which the
     // blockdev has been found to belong. Returns an error if the block
@@ -178,25 +447,26 @@ pub fn get_blockdevs(
     ) -> StratisResult<(BlockDevTier, StratBlockDev)> {
         // Return an error if apparent size of Stratis block device appears to
         // have decreased since metadata was recorded or if size of block
-        // device could not be obtained.
-        blkdev_size(&OpenOptions::new().read(true).open(devnode)?).and_then(|actual_size| {
-            let actual_size_sectors = actual_size.sectors();
-            let recorded_size = bda.dev_size();
-            if actual_size_sectors < recorded_size {
-                let err_msg = format!(
-                    "Stratis device with device number {}, devnode {}, pool UUID {} and device UUID {} had recorded size ({}), but actual size is less at ({})",
-                    device,
-                    devnode.display(),
-                    bda.pool_uuid(),
-                    bda.dev_uuid(),
-                    recorded_size,
-                    actual_size
-                );
-                Err(StratisError::Engine(ErrorEnum::Error, err_msg))
-            } else {
-                Ok(())
-            }
-        })?;
+        // device could not be obtained. Data recorded beyond the end of the
+        // device could not be read, so the device is marked bad rather than
+        // used.
+        let actual_size = blkdev_size(&OpenOptions::new().read(true).open(devnode)?)?.sectors();
+        let recorded_size = bda.dev_size();
+        if actual_size < recorded_size {
+            get_engine_listener_list().notify(&EngineEvent::BlockdevStateChanged {
+                dbus_path: &MaybeDbusPath(None),
+                pool_uuid: bda.pool_uuid(),
+                uuid: bda.dev_uuid(),
+                state: BlockDevState::Bad,
+            });
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("BlockDevShrunk")
+                    .param("uuid", bda.dev_uuid())
+                    .param("actual", *actual_size)
+                    .param("recorded", *recorded_size),
+            ));
+        }
 
         let dev_uuid = bda.dev_uuid();
 
@@ -212,12 +482,10 @@ pub fn get_blockdevs(
             })
             .ok_or_else(|| {
                 let err_msg = format!(
-                        "Stratis device with device number {}, devnode {}, pool UUID {} and device UUID {} had no record in pool metadata",
-                        device,
-                        devnode.display(),
-                        bda.pool_uuid(),
-                        bda.dev_uuid()
-                    );
+                    "Stratis device with device number {} and device UUID {} had no record in pool metadata",
+                    device,
+                    bda.dev_uuid()
+                );
                 StratisError::Engine(ErrorEnum::NotFound, err_msg)
             })?;
 
@@ -241,13 +509,30 @@ pub fn get_blockdevs(
 
     let (mut datadevs, mut cachedevs): (Vec<StratBlockDev>, Vec<StratBlockDev>) = (vec![], vec![]);
     for (device, devnode) in devnodes {
-        let bda = BDA::load(&mut OpenOptions::new().read(true).open(devnode)?)?.ok_or_else(|| {
-            StratisError::Engine(ErrorEnum::NotFound,
-                                                 format!("Device {} with devnode {} was previously determined to belong to pool with uuid {} but no BDA was found",
-                                                 device,
-                                                 devnode.display(),
-                                                 pool_uuid))
-        })?;
+        let bda = OpenOptions::new()
+            .read(true)
+            .open(devnode)
+            .with_devnode(devnode)
+            .and_then(|mut f| {
+                let bda = BDA::load(&mut f)?;
+                // Read the metadata back, so that any corrupt region is
+                // quarantined and counted before the blockdev is set up.
+                if let Some(ref bda) = bda {
+                    let _ = bda.load_state(&mut f);
+                }
+                Ok(bda)
+            })
+            .and_then(|bda| {
+                bda.ok_or_else(|| {
+                    let err_msg = format!(
+                        "Device {} was previously determined to belong to this pool but no BDA was found",
+                        device
+                    );
+                    StratisError::Engine(ErrorEnum::NotFound, err_msg)
+                })
+            })
+            .with_devnode(devnode)
+            .with_pool(pool_uuid)?;
 
         get_blockdev(
             *device,
@@ -256,7 +541,9 @@ pub fn get_blockdevs(
             &recorded_data_map,
             &recorded_cache_map,
             &segment_table,
-        ).map(|(tier, blockdev)| {
+        ).with_devnode(devnode)
+        .with_pool(pool_uuid)
+        .map(|(tier, blockdev)| {
             match tier {
                 BlockDevTier::Data => &mut datadevs,
                 BlockDevTier::Cache => &mut cachedevs,
@@ -307,6 +594,27 @@ pub fn get_blockdevs(
     }
 
     let datadevs = check_and_sort_devs(datadevs, &recorded_data_map)?;
+
+    // A volatile cache is in writethrough mode, so the origin holds all the
+    // data, and the pool can do without the cache.
+    let volatile = backstore_save
+        .cache_tier
+        .as_ref()
+        .and_then(|cache_tier| cache_tier.volatile)
+        .unwrap_or(false);
+    let found_cache_uuids = cachedevs.iter().map(|dev| dev.uuid()).collect::<HashSet<_>>();
+    let recorded_cache_uuids = recorded_cache_map.keys().cloned().collect::<HashSet<_>>();
+    if volatile && found_cache_uuids != recorded_cache_uuids {
+        warn!(
+            "devices of the volatile cache of pool {} are missing; setting up without a cache",
+            pool_uuid.simple()
+        );
+        for dev in &cachedevs {
+            dev.wipe_metadata()?;
+        }
+        return Ok((datadevs, vec![]));
+    }
+
     let cachedevs = check_and_sort_devs(cachedevs, &recorded_cache_map)?;
 
     Ok((datadevs, cachedevs))
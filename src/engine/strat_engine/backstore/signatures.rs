@@ -0,0 +1,210 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Detection of the signatures of foreign filesystems, RAID members, volume
+// groups, and partition tables on a device. The udev db records these only
+// if libblkid has probed the device, so the device is also read directly,
+// in the manner of wipefs, before any Stratis metadata is written to it.
+
+use std::fmt;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+
+use devicemapper::Bytes;
+
+use stratis::StratisResult;
+
+/// A known signature: its magic bytes and where they are found.
+struct KnownSignature {
+    name: &'static str,
+    offset: u64,
+    magic: &'static [u8],
+}
+
+/// The signatures probed for, each identified by a magic string at a fixed
+/// offset from the start of the device. A format with several possible
+/// locations for its magic is listed once for each location.
+const KNOWN_SIGNATURES: &[KnownSignature] = &[
+    KnownSignature {
+        name: "xfs",
+        offset: 0,
+        magic: b"XFSB",
+    },
+    KnownSignature {
+        name: "ext4",
+        offset: 0x438,
+        magic: &[0x53, 0xef],
+    },
+    KnownSignature {
+        name: "btrfs",
+        offset: 0x1_0040,
+        magic: b"_BHRfS_M",
+    },
+    KnownSignature {
+        name: "swap",
+        offset: 0xff6,
+        magic: b"SWAPSPACE2",
+    },
+    KnownSignature {
+        name: "crypto_LUKS",
+        offset: 0,
+        magic: b"LUKS\xba\xbe",
+    },
+    KnownSignature {
+        name: "LVM2_member",
+        offset: 0x200,
+        magic: b"LABELONE",
+    },
+    KnownSignature {
+        name: "linux_raid_member",
+        offset: 0,
+        magic: &[0xfc, 0x4e, 0x2b, 0xa9],
+    },
+    KnownSignature {
+        name: "linux_raid_member",
+        offset: 0x1000,
+        magic: &[0xfc, 0x4e, 0x2b, 0xa9],
+    },
+    KnownSignature {
+        name: "iso9660",
+        offset: 0x8001,
+        magic: b"CD001",
+    },
+    KnownSignature {
+        name: "gpt",
+        offset: 0x200,
+        magic: b"EFI PART",
+    },
+    KnownSignature {
+        name: "dos",
+        offset: 0x1fe,
+        magic: &[0x55, 0xaa],
+    },
+];
+
+/// A foreign signature found on a device.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ForeignSignature {
+    /// The name of the format, as wipefs would report it.
+    pub name: &'static str,
+    /// The offset of the magic bytes from the start of the device.
+    pub offset: Bytes,
+    /// The number of magic bytes.
+    pub length: Bytes,
+}
+
+impl fmt::Display for ForeignSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {:#x}", self.name, *self.offset)
+    }
+}
+
+/// Read the device, of dev_size, at the location of each known signature.
+/// Return the signatures found, in the order in which they are probed for.
+pub fn probe_signatures<F>(f: &mut F, dev_size: Bytes) -> StratisResult<Vec<ForeignSignature>>
+where
+    F: Read + Seek,
+{
+    let mut found = Vec::new();
+    for known in KNOWN_SIGNATURES {
+        let length = known.magic.len() as u64;
+        if known.offset + length > *dev_size {
+            continue;
+        }
+
+        let mut buf = vec![0u8; known.magic.len()];
+        f.seek(SeekFrom::Start(known.offset))?;
+        match f.read_exact(&mut buf) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => continue,
+            Err(err) => return Err(err.into()),
+        }
+
+        if buf == known.magic {
+            found.push(ForeignSignature {
+                name: known.name,
+                offset: Bytes(known.offset),
+                length: Bytes(length),
+            });
+        }
+    }
+    Ok(found)
+}
+
+/// Erase the signatures from the device by overwriting their magic bytes
+/// with zeros.
+pub fn wipe_signatures<F>(f: &mut F, signatures: &[ForeignSignature]) -> StratisResult<()>
+where
+    F: Write + Seek,
+{
+    for sig in signatures {
+        f.seek(SeekFrom::Start(*sig.offset))?;
+        f.write_all(&vec![0u8; *sig.length as usize])?;
+    }
+    f.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    /// Verify that no signature is found on a zeroed device, and that each
+    /// signature written to a device is found at the offset written.
+    fn test_probe_signatures() {
+        let size = Bytes(0x2_0000);
+        let mut buf = Cursor::new(vec![0u8; *size as usize]);
+        assert_eq!(probe_signatures(&mut buf, size).unwrap(), vec![]);
+
+        buf.seek(SeekFrom::Start(0x1_0040)).unwrap();
+        buf.write_all(b"_BHRfS_M").unwrap();
+        buf.seek(SeekFrom::Start(0x1fe)).unwrap();
+        buf.write_all(&[0x55, 0xaa]).unwrap();
+
+        let found = probe_signatures(&mut buf, size).unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|sig| (sig.name, *sig.offset))
+                .collect::<Vec<_>>(),
+            vec![("btrfs", 0x1_0040), ("dos", 0x1fe)]
+        );
+    }
+
+    #[test]
+    /// Verify that a signature beyond the end of a small device is not
+    /// looked for.
+    fn test_probe_small_device() {
+        let size = Bytes(0x1000);
+        let mut buf = Cursor::new(vec![0u8; *size as usize]);
+        buf.write_all(b"XFSB").unwrap();
+        let found = probe_signatures(&mut buf, size).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "xfs");
+    }
+
+    #[test]
+    /// Verify that wiping the signatures found on a device leaves no
+    /// signature to be found, and writes nothing else.
+    fn test_wipe_signatures() {
+        let size = Bytes(0x2_0000);
+        let mut buf = Cursor::new(vec![0xffu8; *size as usize]);
+        buf.write_all(b"XFSB").unwrap();
+        buf.seek(SeekFrom::Start(0x200)).unwrap();
+        buf.write_all(b"LABELONE").unwrap();
+
+        let found = probe_signatures(&mut buf, size).unwrap();
+        assert_eq!(found.len(), 2);
+
+        wipe_signatures(&mut buf, &found).unwrap();
+        assert_eq!(probe_signatures(&mut buf, size).unwrap(), vec![]);
+
+        let data = buf.into_inner();
+        assert!(data[..4].iter().all(|b| *b == 0));
+        assert!(data[0x200..0x208].iter().all(|b| *b == 0));
+        assert_eq!(data.iter().filter(|b| **b == 0).count(), 12);
+    }
+}
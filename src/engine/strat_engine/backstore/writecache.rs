@@ -0,0 +1,239 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// A DM writecache device, which caches writes to an origin device on a
+// faster cache device, and writes them back to the origin in the
+// background. The devicemapper crate has no support for the writecache
+// target, so the device is managed here with DM ioctls directly.
+
+use devicemapper::{
+    device_exists, DevId, Device, DmDevice, DmFlags, DmName, DmNameBuf, DmOptions, DmUuid,
+    LinearDev, LinearDevTargetParams, Sectors, TargetLine, TargetTypeBuf,
+};
+
+use stratis::{ErrorEnum, StratisError, StratisResult};
+
+use super::super::super::types::CacheUsage;
+use super::super::dm::get_dm;
+
+/// The size of a block of the cache. It is the page size, which
+/// dm-writecache requires of a cache on persistent memory, and which suits
+/// a cache on an SSD as well.
+pub const WRITECACHE_BLOCK_SIZE: Sectors = Sectors(8); // 4 KiB
+
+/// The status of a writecache device.
+#[derive(Debug, Eq, PartialEq)]
+pub struct WriteCacheStatus {
+    /// The number of blocks in the cache.
+    pub total_blocks: u64,
+    /// The number of blocks in the cache that hold no data.
+    pub free_blocks: u64,
+    /// The number of blocks in the cache being written back to the origin.
+    pub writeback_blocks: u64,
+}
+
+impl WriteCacheStatus {
+    /// Parse the status line of a writecache target:
+    /// <error> <#blocks> <#free blocks> <#blocks under writeback> ...
+    /// Return an error if the target has failed.
+    fn parse(status: &str) -> StratisResult<WriteCacheStatus> {
+        let invalid = || {
+            StratisError::Engine(
+                ErrorEnum::Invalid,
+                format!("unexpected writecache status: {}", status),
+            )
+        };
+
+        let mut words = status.split_whitespace();
+        let error = words
+            .next()
+            .and_then(|word| word.parse::<i64>().ok())
+            .ok_or_else(&invalid)?;
+        if error != 0 {
+            return Err(StratisError::Engine(
+                ErrorEnum::Error,
+                format!("writecache device has failed with error {}", error),
+            ));
+        }
+
+        let counts = words
+            .take(3)
+            .map(|word| word.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|counts| if counts.len() == 3 { Some(counts) } else { None })
+            .ok_or_else(&invalid)?;
+
+        Ok(WriteCacheStatus {
+            total_blocks: counts[0],
+            free_blocks: counts[1],
+            writeback_blocks: counts[2],
+        })
+    }
+
+    /// The usage of the cache. Every block that holds data is dirty, as a
+    /// block is released as soon as it has been written back.
+    pub fn usage(&self) -> CacheUsage {
+        let used = WRITECACHE_BLOCK_SIZE * (self.total_blocks - self.free_blocks);
+        CacheUsage {
+            used,
+            dirty: used,
+            total: WRITECACHE_BLOCK_SIZE * self.total_blocks,
+        }
+    }
+}
+
+/// The table of a writecache device, caching writes to origin on cache, a
+/// device on an SSD.
+fn writecache_table(
+    origin: &LinearDev,
+    cache: &LinearDev,
+) -> Vec<(Sectors, Sectors, TargetTypeBuf, String)> {
+    vec![(
+        Sectors(0),
+        origin.size(),
+        TargetTypeBuf::new("writecache".into()).expect("\"writecache\" is a valid target type"),
+        format!(
+            "s {} {} {} 0",
+            origin.device(),
+            cache.device(),
+            *WRITECACHE_BLOCK_SIZE.bytes()
+        ),
+    )]
+}
+
+/// A DM writecache device, with its cache and origin sub-devices.
+#[derive(Debug)]
+pub struct WriteCacheDev {
+    name: DmNameBuf,
+    device: Device,
+    cache: LinearDev,
+    origin: LinearDev,
+}
+
+impl WriteCacheDev {
+    /// Set up a writecache device on the cache and origin sub-devices. If
+    /// a device of the given name exists already, as it does if stratisd
+    /// has been restarted, it is taken over as it is.
+    pub fn setup(
+        name: &DmName,
+        uuid: &DmUuid,
+        cache: LinearDev,
+        origin: LinearDev,
+    ) -> StratisResult<WriteCacheDev> {
+        let id = DevId::Name(name);
+        let device = if device_exists(get_dm(), name)? {
+            get_dm().device_info(&id)?.device()
+        } else {
+            get_dm().device_create(name, Some(uuid), &DmOptions::new())?;
+            let table = writecache_table(&origin, &cache);
+            let loaded = get_dm()
+                .table_load(&id, &table)
+                .and_then(|_| get_dm().device_suspend(&id, &DmOptions::new()));
+            match loaded {
+                Ok(info) => info.device(),
+                Err(err) => {
+                    get_dm().device_remove(&id, &DmOptions::new())?;
+                    return Err(err.into());
+                }
+            }
+        };
+
+        Ok(WriteCacheDev {
+            name: name.to_owned(),
+            device,
+            cache,
+            origin,
+        })
+    }
+
+    /// The name of the writecache device.
+    pub fn name(&self) -> &DmName {
+        &self.name
+    }
+
+    /// The device number of the writecache device.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// The size of the writecache device, which is that of its origin.
+    pub fn size(&self) -> Sectors {
+        self.origin.size()
+    }
+
+    /// Load the origin sub-device with a new table, and the writecache
+    /// device with a table of the origin's new size. The origin is resumed;
+    /// the writecache device must be resumed by the caller.
+    pub fn set_origin_table(
+        &mut self,
+        table: Vec<TargetLine<LinearDevTargetParams>>,
+    ) -> StratisResult<()> {
+        self.origin.set_table(get_dm(), table)?;
+        self.origin.resume(get_dm())?;
+        let table = writecache_table(&self.origin, &self.cache);
+        get_dm().table_load(&DevId::Name(&self.name), &table)?;
+        Ok(())
+    }
+
+    /// The status of the writecache device.
+    pub fn status(&self) -> StratisResult<WriteCacheStatus> {
+        let (_, status) = get_dm().table_status(&DevId::Name(&self.name), &DmOptions::new())?;
+        match status.first() {
+            Some(&(_, _, _, ref params)) => WriteCacheStatus::parse(params),
+            None => Err(StratisError::Engine(
+                ErrorEnum::Error,
+                format!("writecache device {} has no table", &*self.name),
+            )),
+        }
+    }
+
+    /// Remove the writecache device, then its sub-devices. Data that has
+    /// not been written back remains on the cache sub-device, and is written
+    /// back when the writecache device is next set up.
+    pub fn teardown(&mut self) -> StratisResult<()> {
+        let id = DevId::Name(&self.name);
+        get_dm().device_suspend(&id, &DmOptions::new().set_flags(DmFlags::DM_SUSPEND))?;
+        get_dm().device_remove(&id, &DmOptions::new())?;
+        self.cache.teardown(get_dm())?;
+        self.origin.teardown(get_dm())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the status of a writecache device is parsed, whether or
+    /// not the kernel reports the statistics that follow the counts of
+    /// blocks, and that a failed device or a malformed status is an error.
+    fn test_status_parse() {
+        let status = WriteCacheStatus::parse("0 1024 1000 4").unwrap();
+        assert_eq!(
+            status,
+            WriteCacheStatus {
+                total_blocks: 1024,
+                free_blocks: 1000,
+                writeback_blocks: 4,
+            }
+        );
+        assert_eq!(
+            status.usage(),
+            CacheUsage {
+                used: Sectors(24 * 8),
+                dirty: Sectors(24 * 8),
+                total: Sectors(1024 * 8),
+            }
+        );
+
+        assert_eq!(
+            WriteCacheStatus::parse("0 1024 1000 4 10 2 8 0 0 0 0 0 0 0").unwrap(),
+            status
+        );
+        assert!(WriteCacheStatus::parse("-5 1024 1000 4").is_err());
+        assert!(WriteCacheStatus::parse("5 1024 1000 4").is_err());
+        assert!(WriteCacheStatus::parse("0 1024 1000").is_err());
+    }
+}
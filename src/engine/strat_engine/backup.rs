@@ -0,0 +1,193 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Backups of pool metadata, kept in files outside the pool. Whenever
+// metadata is saved to a pool's MDAs, a copy is also written here, so
+// that the metadata of a pool whose MDAs are damaged can be restored.
+
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str;
+
+use chrono::{DateTime, Utc};
+use serde_json;
+
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
+
+use super::super::types::PoolUuid;
+
+/// The directory in which metadata backups are kept, in a subdirectory for
+/// each pool.
+pub const BACKUP_DIR: &str = "/var/lib/stratisd/backups";
+
+/// The number of backups kept for each pool.
+const MAX_BACKUPS: usize = 10;
+
+/// A copy of the metadata of a pool, as saved to its MDAs.
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MetadataBackup {
+    /// Each backup of a pool has a higher generation than the one before.
+    pub generation: u64,
+    /// The time the metadata was saved to the MDAs, in RFC 3339 format.
+    pub time: String,
+    /// The metadata, as serialized to the MDAs.
+    pub metadata: String,
+}
+
+impl MetadataBackup {
+    /// The time the metadata was saved to the MDAs.
+    pub fn time(&self) -> StratisResult<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.time)
+            .map(|time| time.with_timezone(&Utc))
+            .map_err(|_| {
+                StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!("backup time {} is not in RFC 3339 format", self.time),
+                )
+            })
+    }
+}
+
+fn pool_dir(dir: &Path, pool_uuid: PoolUuid) -> PathBuf {
+    dir.join(pool_uuid.simple().to_string())
+}
+
+fn backup_path(dir: &Path, pool_uuid: PoolUuid, generation: u64) -> PathBuf {
+    pool_dir(dir, pool_uuid).join(format!("{}.json", generation))
+}
+
+/// The generations of the backups of the pool kept in dir, oldest first.
+pub fn list_backups(dir: &Path, pool_uuid: PoolUuid) -> StratisResult<Vec<u64>> {
+    let pool_dir = pool_dir(dir, pool_uuid);
+    if !pool_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut generations = Vec::new();
+    for entry in fs::read_dir(&pool_dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "json") {
+            if let Some(generation) = path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            {
+                generations.push(generation);
+            }
+        }
+    }
+    generations.sort();
+    Ok(generations)
+}
+
+/// Back up the metadata of the pool, which was saved to its MDAs at time,
+/// to a new file in dir. Remove the oldest backups, so that no more than
+/// MAX_BACKUPS remain.
+/// Return the generation of the new backup.
+pub fn save_backup(
+    dir: &Path,
+    pool_uuid: PoolUuid,
+    time: &DateTime<Utc>,
+    metadata: &[u8],
+) -> StratisResult<u64> {
+    fs::create_dir_all(pool_dir(dir, pool_uuid))?;
+
+    let generations = list_backups(dir, pool_uuid)?;
+    let generation = generations.last().map_or(0, |last| last + 1);
+    let backup = MetadataBackup {
+        generation,
+        time: time.to_rfc3339(),
+        metadata: str::from_utf8(metadata)?.to_owned(),
+    };
+
+    // Write to a temporary file first, so that a backup is never found
+    // half-written.
+    let path = backup_path(dir, pool_uuid, generation);
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut f = File::create(&tmp_path)?;
+        f.write_all(serde_json::to_string(&backup)?.as_bytes())?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp_path, &path)?;
+
+    for old in generations.iter().rev().skip(MAX_BACKUPS - 1) {
+        fs::remove_file(backup_path(dir, pool_uuid, *old))?;
+    }
+
+    Ok(generation)
+}
+
+/// Load the backup of the pool with the given generation from dir.
+pub fn load_backup(
+    dir: &Path,
+    pool_uuid: PoolUuid,
+    generation: u64,
+) -> StratisResult<MetadataBackup> {
+    let mut f = match File::open(backup_path(dir, pool_uuid, generation)) {
+        Ok(f) => f,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => {
+            return Err(StratisError::Structured(
+                ErrorEnum::NotFound,
+                ErrorPayload::new("BackupNotFound")
+                    .param("pool", pool_uuid)
+                    .param("generation", generation),
+            ));
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    read_backup(&mut f)
+}
+
+/// Read a backup from a file, e.g., one copied out of the backup directory.
+pub fn read_backup<F: Read>(f: &mut F) -> StratisResult<MetadataBackup> {
+    let mut data = String::new();
+    f.read_to_string(&mut data)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use tempfile;
+
+    use super::*;
+
+    #[test]
+    /// Verify that backups are numbered in order, that only the newest
+    /// MAX_BACKUPS are kept, and that a backup loads as it was saved.
+    fn test_backup_rotation() {
+        let dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let pool_uuid = PoolUuid::new_v4();
+
+        assert!(list_backups(dir.path(), pool_uuid).unwrap().is_empty());
+
+        let count = MAX_BACKUPS as u64 + 2;
+        for generation in 0..count {
+            let time = Utc.timestamp(generation as i64 + 1, 0);
+            let metadata = format!("{{\"generation\": {}}}", generation);
+            assert_eq!(
+                save_backup(dir.path(), pool_uuid, &time, metadata.as_bytes()).unwrap(),
+                generation
+            );
+        }
+
+        assert_eq!(
+            list_backups(dir.path(), pool_uuid).unwrap(),
+            (count - MAX_BACKUPS as u64..count).collect::<Vec<u64>>()
+        );
+
+        let backup = load_backup(dir.path(), pool_uuid, count - 1).unwrap();
+        assert_eq!(backup.generation, count - 1);
+        assert_eq!(backup.time().unwrap(), Utc.timestamp(count as i64, 0));
+        assert_eq!(backup.metadata, format!("{{\"generation\": {}}}", count - 1));
+
+        assert!(load_backup(dir.path(), pool_uuid, 0).is_err());
+        assert!(load_backup(dir.path(), PoolUuid::new_v4(), count - 1).is_err());
+    }
+}
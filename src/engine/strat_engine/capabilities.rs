@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Detect which of the optional capabilities are available, by checking
+// that the kernel supports the DM targets each requires. The kernel loads
+// the module for a DM target only when a device is first made with it, so
+// a target that is not loaded is looked for among the kernel's modules.
+
+use std::collections::HashSet;
+use std::fs;
+
+use devicemapper::DM;
+
+use super::super::types::Capability;
+
+/// The DM targets the capability requires.
+fn required_targets(capability: Capability) -> &'static [&'static str] {
+    match capability {
+        Capability::Encryption => &["crypt"],
+        Capability::Integrity => &["integrity"],
+        Capability::WriteCache => &["writecache"],
+        Capability::Raid1 => &["raid"],
+        Capability::JsonRpc => &[],
+    }
+}
+
+/// The names of the modules listed in the contents of a modules.dep or
+/// modules.builtin file, without their directories or extensions.
+fn module_names(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .filter_map(|line| line.split(':').next())
+        .filter_map(|path| path.rsplit('/').next())
+        .filter_map(|file| file.split(".ko").next())
+        .filter(|name| !name.is_empty())
+        .map(|name| name.replace('_', "-"))
+        .collect()
+}
+
+/// The names of the modules the running kernel may load, or has built in.
+pub fn kernel_modules() -> HashSet<String> {
+    let release = match fs::read_to_string("/proc/sys/kernel/osrelease") {
+        Ok(release) => release.trim().to_owned(),
+        Err(_) => return HashSet::new(),
+    };
+    ["modules.dep", "modules.builtin"]
+        .iter()
+        .filter_map(|file| fs::read_to_string(format!("/lib/modules/{}/{}", release, file)).ok())
+        .flat_map(|contents| module_names(&contents))
+        .collect()
+}
+
+/// The capabilities that are implemented by this build, and whose targets
+/// are among the loaded targets or the modules of the kernel.
+fn available(loaded: &HashSet<String>, modules: &HashSet<String>) -> Vec<Capability> {
+    Capability::ALL
+        .iter()
+        .cloned()
+        .filter(|capability| capability.implemented())
+        .filter(|&capability| {
+            required_targets(capability).iter().all(|target| {
+                loaded.contains(*target) || modules.contains(&format!("dm-{}", target))
+            })
+        })
+        .collect()
+}
+
+/// The capabilities available with the running kernel. If the targets the
+/// kernel has loaded can not be listed, only those capabilities that
+/// require no target, or whose targets' modules are found, are available.
+pub fn probe_capabilities(dm: &DM) -> Vec<Capability> {
+    let loaded = dm.list_versions()
+        .map(|versions| {
+            versions
+                .into_iter()
+                .map(|(name, _, _, _)| name)
+                .collect::<HashSet<_>>()
+        })
+        .unwrap_or_else(|err| {
+            warn!("unable to list the DM targets loaded, reason: {}", err);
+            HashSet::new()
+        });
+    available(&loaded, &kernel_modules())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that module names are found in modules.dep and
+    /// modules.builtin, whether or not the modules are compressed.
+    fn test_module_names() {
+        let names = module_names(
+            "kernel/drivers/md/dm-writecache.ko.xz: kernel/drivers/md/dm-bufio.ko.xz\n\
+             kernel/drivers/md/dm_crypt.ko:\n\
+             \n",
+        );
+        assert_eq!(
+            names,
+            ["dm-writecache", "dm-crypt"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    /// Verify that a capability is available only if it is implemented and
+    /// each of its targets is loaded or may be loaded.
+    fn test_available() {
+        let none = HashSet::new();
+        assert_eq!(
+            available(&none, &none),
+            Capability::ALL
+                .iter()
+                .cloned()
+                .filter(|&capability| {
+                    capability.implemented() && required_targets(capability).is_empty()
+                })
+                .collect::<Vec<_>>()
+        );
+
+        let loaded = ["writecache", "crypt", "integrity", "raid"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            available(&loaded, &none),
+            Capability::ALL
+                .iter()
+                .cloned()
+                .filter(|capability| capability.implemented())
+                .collect::<Vec<_>>()
+        );
+
+        let modules = ["dm-writecache".to_string()]
+            .iter()
+            .cloned()
+            .collect::<HashSet<_>>();
+        assert!(available(&none, &modules).contains(&Capability::WriteCache));
+    }
+}
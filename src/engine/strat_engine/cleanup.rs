@@ -7,11 +7,12 @@
 use stratis::{ErrorEnum, StratisError, StratisResult};
 
 use super::super::structures::Table;
+use super::super::types::PoolUuid;
 
 use super::pool::StratPool;
 
 /// Teardown pools.
-pub fn teardown_pools(pools: Table<StratPool>) -> StratisResult<()> {
+pub fn teardown_pools(pools: Table<PoolUuid, StratPool>) -> StratisResult<()> {
     let mut untorndown_pools = Vec::new();
     for (_, uuid, mut pool) in pools {
         pool.teardown()
@@ -15,13 +15,17 @@
 // an explicit error is returned if the executable can not be found.
 
 use std::collections::HashMap;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
 
+use serde_json::{Map, Value};
 use uuid::Uuid;
 
 use stratis::{StratisError, StratisResult};
 
+use super::watchdog::{watch, STUCK_THRESHOLD_SECS};
+
 /// Find the binary with the given name by looking in likely locations.
 /// Return None if no binary was found.
 /// Search an explicit list of directories rather than the user's PATH
@@ -37,21 +41,33 @@ fn find_binary(name: &str) -> Option<PathBuf> {
 // These are the external binaries that stratisd relies on.
 // Any change in this list requires a corresponding change to BINARIES,
 // and vice-versa.
+const CRYPTSETUP: &str = "cryptsetup";
 const MKFS_XFS: &str = "mkfs.xfs";
 const THIN_CHECK: &str = "thin_check";
+const THIN_LS: &str = "thin_ls";
 const THIN_REPAIR: &str = "thin_repair";
 const UDEVADM: &str = "udevadm";
 const XFS_DB: &str = "xfs_db";
+const XFS_FREEZE: &str = "xfs_freeze";
 const XFS_GROWFS: &str = "xfs_growfs";
+const XFS_REPAIR: &str = "xfs_repair";
+
+// Clevis is needed only to bind encrypted pools to Tang servers, and to
+// unlock them with those bindings, and so is not among BINARIES.
+const CLEVIS: &str = "clevis";
 
 lazy_static! {
     static ref BINARIES: HashMap<String, Option<PathBuf>> = [
+        (CRYPTSETUP.to_string(), find_binary(CRYPTSETUP)),
         (MKFS_XFS.to_string(), find_binary(MKFS_XFS)),
         (THIN_CHECK.to_string(), find_binary(THIN_CHECK)),
+        (THIN_LS.to_string(), find_binary(THIN_LS)),
         (THIN_REPAIR.to_string(), find_binary(THIN_REPAIR)),
         (UDEVADM.to_string(), find_binary(UDEVADM)),
         (XFS_DB.to_string(), find_binary(XFS_DB)),
+        (XFS_FREEZE.to_string(), find_binary(XFS_FREEZE)),
         (XFS_GROWFS.to_string(), find_binary(XFS_GROWFS)),
+        (XFS_REPAIR.to_string(), find_binary(XFS_REPAIR)),
     ].iter()
         .cloned()
         .collect();
@@ -94,6 +110,41 @@ fn execute_cmd(cmd: &mut Command) -> StratisResult<()> {
     }
 }
 
+/// Invoke the specified command, writing input to its standard input, so
+/// that a secret need not appear among its arguments. Return its output,
+/// whether or not the command itself succeeds. Return an error if invoking
+/// the command fails.
+fn execute_cmd_with_input(cmd: &mut Command, input: &[u8]) -> StratisResult<Output> {
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let spawned = cmd.spawn();
+
+    let cmd = &*cmd;
+    let to_error = |err: io::Error| {
+        StratisError::Error(format!(
+            "Failed to execute command {:?}, err: {:?}",
+            cmd, err
+        ))
+    };
+    let mut child = spawned.map_err(&to_error)?;
+    {
+        let stdin = child.stdin.as_mut().expect("stdin was piped");
+        stdin.write_all(input).map_err(&to_error)?;
+    }
+    child.wait_with_output().map_err(&to_error)
+}
+
+/// The error for a command that failed, with its output.
+fn command_failed(cmd: &Command, result: &Output) -> StratisError {
+    StratisError::Error(format!(
+        "Command failed: cmd: {:?}, stdout: {} stderr: {}",
+        cmd,
+        String::from_utf8_lossy(&result.stdout),
+        String::from_utf8_lossy(&result.stderr)
+    ))
+}
+
 /// Get an absolute path for the executable with the given name.
 /// Precondition: verify_binaries() has already been invoked.
 fn get_executable(name: &str) -> &Path {
@@ -104,16 +155,31 @@ fn get_executable(name: &str) -> &Path {
         .expect("verify_binaries() was previously called and returned no error")
 }
 
+/// Get an absolute path for the optional executable with the given name.
+/// Return an error if it is not installed.
+fn get_optional_executable(name: &str) -> StratisResult<PathBuf> {
+    find_binary(name).ok_or_else(|| {
+        StratisError::Error(format!(
+            "Unable to find absolute path for \"{}\", which is needed for this operation",
+            name
+        ))
+    })
+}
+
 /// Create a filesystem on devnode.
-pub fn create_fs(devnode: &Path, uuid: Uuid) -> StratisResult<()> {
-    execute_cmd(
-        Command::new(get_executable(MKFS_XFS).as_os_str())
-            .arg("-f")
-            .arg("-q")
-            .arg(&devnode)
-            .arg("-m")
-            .arg(format!("uuid={}", uuid)),
-    )
+/// If discard is false, the blocks of the device are not discarded.
+pub fn create_fs(devnode: &Path, uuid: Uuid, discard: bool) -> StratisResult<()> {
+    let mut command = Command::new(get_executable(MKFS_XFS).as_os_str());
+    command
+        .arg("-f")
+        .arg("-q")
+        .arg(&devnode)
+        .arg("-m")
+        .arg(format!("uuid={}", uuid));
+    if !discard {
+        command.arg("-K");
+    }
+    execute_cmd(&mut command)
 }
 
 /// Use the xfs_growfs command to expand a filesystem mounted at the given
@@ -126,6 +192,17 @@ pub fn xfs_growfs(mount_point: &Path) -> StratisResult<()> {
     )
 }
 
+/// Use the xfs_freeze command to freeze the filesystem mounted at the given
+/// mount point, so that it is consistent on disk and is not written until
+/// it is thawed, or to thaw it if thaw is true.
+pub fn xfs_freeze(mount_point: &Path, thaw: bool) -> StratisResult<()> {
+    execute_cmd(
+        Command::new(get_executable(XFS_FREEZE).as_os_str())
+            .arg(if thaw { "-u" } else { "-f" })
+            .arg(mount_point),
+    )
+}
+
 /// Set a new UUID for filesystem on the devnode.
 pub fn set_uuid(devnode: &Path, uuid: Uuid) -> StratisResult<()> {
     execute_cmd(
@@ -136,11 +213,14 @@ pub fn set_uuid(devnode: &Path, uuid: Uuid) -> StratisResult<()> {
     )
 }
 
-/// Call thin_check on a thinpool
+/// Call thin_check on a thinpool. If the metadata is found consistent, the
+/// needs_check flag, set by the kernel when it finds a problem with the
+/// metadata, is cleared.
 pub fn thin_check(devnode: &Path) -> StratisResult<()> {
     execute_cmd(
         Command::new(get_executable(THIN_CHECK).as_os_str())
             .arg("-q")
+            .arg("--clear-needs-check-flag")
             .arg(devnode),
     )
 }
@@ -156,9 +236,276 @@ pub fn thin_repair(meta_dev: &Path, new_meta_dev: &Path) -> StratisResult<()> {
     )
 }
 
-/// Call udevadm settle
+/// Call thin_ls on the metadata snapshot of a live thinpool, whose
+/// metadata device is meta_dev. The snapshot must have been reserved.
+/// Return the output, a line for each thin device giving its id and the
+/// number of sectors that it alone maps, and that it shares with other
+/// thin devices.
+pub fn thin_ls(meta_dev: &Path) -> StratisResult<String> {
+    let mut command = Command::new(get_executable(THIN_LS).as_os_str());
+    command
+        .arg("--metadata-snap")
+        .arg("--no-headers")
+        .arg("-o")
+        .arg("DEV,EXCLUSIVE_SECTORS,SHARED_SECTORS")
+        .arg(meta_dev);
+
+    let result = command.output().map_err(|err| {
+        StratisError::Error(format!(
+            "Failed to execute command {:?}, err: {:?}",
+            command, err
+        ))
+    })?;
+    if !result.status.success() {
+        return Err(StratisError::Error(format!(
+            "Command failed: cmd: {:?}, stderr: {}",
+            command,
+            String::from_utf8_lossy(&result.stderr)
+        )));
+    }
+    Ok(String::from_utf8_lossy(&result.stdout).into_owned())
+}
+
+/// Format devnode as a LUKS2 device with the given UUID and label, with a
+/// single keyslot that is opened by passphrase. Everything on the device
+/// is lost.
+pub fn luks_format(
+    devnode: &Path,
+    uuid: Uuid,
+    label: &str,
+    passphrase: &str,
+) -> StratisResult<()> {
+    let mut command = Command::new(get_executable(CRYPTSETUP).as_os_str());
+    command
+        .arg("luksFormat")
+        .arg("--type")
+        .arg("luks2")
+        .arg("--batch-mode")
+        .arg("--uuid")
+        .arg(uuid.to_string())
+        .arg("--label")
+        .arg(label)
+        .arg("--key-file")
+        .arg("-")
+        .arg(devnode);
+
+    let result = execute_cmd_with_input(&mut command, passphrase.as_bytes())?;
+    if !result.status.success() {
+        return Err(command_failed(&command, &result));
+    }
+    Ok(())
+}
+
+/// Open the LUKS2 device devnode with passphrase, setting up a dm-crypt
+/// device with the given name.
+/// Return false if no keyslot is opened by the passphrase.
+pub fn luks_open(devnode: &Path, name: &str, passphrase: &str) -> StratisResult<bool> {
+    let mut command = Command::new(get_executable(CRYPTSETUP).as_os_str());
+    command
+        .arg("open")
+        .arg("--type")
+        .arg("luks2")
+        .arg("--key-file")
+        .arg("-")
+        .arg(devnode)
+        .arg(name);
+
+    let result = execute_cmd_with_input(&mut command, passphrase.as_bytes())?;
+    match result.status.code() {
+        Some(0) => Ok(true),
+        // cryptsetup exits with 2 if the passphrase is wrong.
+        Some(2) => Ok(false),
+        _ => Err(command_failed(&command, &result)),
+    }
+}
+
+/// Remove the dm-crypt device with the given name.
+pub fn luks_close(name: &str) -> StratisResult<()> {
+    execute_cmd(
+        Command::new(get_executable(CRYPTSETUP).as_os_str())
+            .arg("close")
+            .arg(name),
+    )
+}
+
+/// Add keyslot, opened by new_passphrase, to the LUKS2 device devnode.
+/// cryptsetup reads each passphrase up to a newline, so neither may hold
+/// one.
+/// Return false if no keyslot is opened by passphrase.
+pub fn luks_add_key(
+    devnode: &Path,
+    passphrase: &str,
+    keyslot: u32,
+    new_passphrase: &str,
+) -> StratisResult<bool> {
+    if passphrase.contains('\n') || new_passphrase.contains('\n') {
+        return Err(StratisError::Error(
+            "a passphrase to add a keyslot with may not contain a newline".into(),
+        ));
+    }
+    let mut command = Command::new(get_executable(CRYPTSETUP).as_os_str());
+    command
+        .arg("luksAddKey")
+        .arg("--batch-mode")
+        .arg("--key-slot")
+        .arg(keyslot.to_string())
+        .arg(devnode);
+
+    let input = format!("{}\n{}\n", passphrase, new_passphrase);
+    let result = execute_cmd_with_input(&mut command, input.as_bytes())?;
+    match result.status.code() {
+        Some(0) => Ok(true),
+        // cryptsetup exits with 2 if the passphrase is wrong.
+        Some(2) => Ok(false),
+        _ => Err(command_failed(&command, &result)),
+    }
+}
+
+/// Wipe keyslot of the LUKS2 device devnode. In batch mode, cryptsetup
+/// asks for no passphrase to do so.
+pub fn luks_kill_slot(devnode: &Path, keyslot: u32) -> StratisResult<()> {
+    execute_cmd(
+        Command::new(get_executable(CRYPTSETUP).as_os_str())
+            .arg("luksKillSlot")
+            .arg("--batch-mode")
+            .arg(devnode)
+            .arg(keyslot.to_string()),
+    )
+}
+
+/// The ids of the entries in a section of the output of cryptsetup
+/// luksDump, such as "Keyslots:" or "Tokens:", where each entry is listed as
+/// "  <id>: <type>", followed by its details.
+fn parse_dump_ids(dump: &str, section: &str) -> Vec<u32> {
+    dump.lines()
+        .skip_while(|line| *line != section)
+        .skip(1)
+        .take_while(|line| line.starts_with(|c: char| c.is_whitespace()))
+        .filter_map(|line| {
+            let mut fields = line.trim().splitn(2, ':');
+            match (fields.next(), fields.next()) {
+                (Some(id), Some(_)) => id.parse::<u32>().ok(),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The ids of the keyslots in use, and the ids of the tokens, in the LUKS2
+/// header of devnode.
+pub fn luks_header_ids(devnode: &Path) -> StratisResult<(Vec<u32>, Vec<u32>)> {
+    let mut command = Command::new(get_executable(CRYPTSETUP).as_os_str());
+    command.arg("luksDump").arg(devnode);
+
+    let result = execute_cmd_with_input(&mut command, &[])?;
+    if !result.status.success() {
+        return Err(command_failed(&command, &result));
+    }
+    let dump = String::from_utf8_lossy(&result.stdout);
+    Ok((
+        parse_dump_ids(&dump, "Keyslots:"),
+        parse_dump_ids(&dump, "Tokens:"),
+    ))
+}
+
+/// The JSON of the token with the given id in the LUKS2 header of devnode.
+/// Precondition: the header has a token with the id.
+pub fn luks_token_export(devnode: &Path, token_id: u32) -> StratisResult<String> {
+    let mut command = Command::new(get_executable(CRYPTSETUP).as_os_str());
+    command
+        .arg("token")
+        .arg("export")
+        .arg("--token-id")
+        .arg(token_id.to_string())
+        .arg(devnode);
+
+    let result = execute_cmd_with_input(&mut command, &[])?;
+    if !result.status.success() {
+        return Err(command_failed(&command, &result));
+    }
+    Ok(String::from_utf8_lossy(&result.stdout).trim().to_owned())
+}
+
+/// Import json as the token with the given id in the LUKS2 header of
+/// devnode.
+/// Precondition: the header has no token with the id.
+pub fn luks_token_import(devnode: &Path, token_id: u32, json: &str) -> StratisResult<()> {
+    let mut command = Command::new(get_executable(CRYPTSETUP).as_os_str());
+    command
+        .arg("token")
+        .arg("import")
+        .arg("--token-id")
+        .arg(token_id.to_string())
+        .arg("--json-file")
+        .arg("-")
+        .arg(devnode);
+
+    let result = execute_cmd_with_input(&mut command, json.as_bytes())?;
+    if !result.status.success() {
+        return Err(command_failed(&command, &result));
+    }
+    Ok(())
+}
+
+/// Remove the token with the given id from the LUKS2 header of devnode.
+pub fn luks_token_remove(devnode: &Path, token_id: u32) -> StratisResult<()> {
+    execute_cmd(
+        Command::new(get_executable(CRYPTSETUP).as_os_str())
+            .arg("token")
+            .arg("remove")
+            .arg("--token-id")
+            .arg(token_id.to_string())
+            .arg(devnode),
+    )
+}
+
+/// Seal secret with Clevis's tang pin, so that it can be unsealed only with
+/// the help of the Tang server at url, whose signing key has the given
+/// thumbprint. Given the thumbprint, Clevis trusts the server without
+/// asking.
+/// Return the JWE that holds the sealed secret, in compact form.
+pub fn clevis_encrypt_tang(url: &str, thumbprint: &str, secret: &str) -> StratisResult<String> {
+    let mut config = Map::new();
+    config.insert("url".to_owned(), Value::String(url.to_owned()));
+    config.insert("thp".to_owned(), Value::String(thumbprint.to_owned()));
+
+    let mut command = Command::new(get_optional_executable(CLEVIS)?.as_os_str());
+    command
+        .arg("encrypt")
+        .arg("tang")
+        .arg(Value::Object(config).to_string());
+
+    let result = execute_cmd_with_input(&mut command, secret.as_bytes())?;
+    if !result.status.success() {
+        return Err(command_failed(&command, &result));
+    }
+    Ok(String::from_utf8_lossy(&result.stdout).trim().to_owned())
+}
+
+/// Unseal the secret sealed by Clevis in jwe, which is in compact form.
+pub fn clevis_decrypt(jwe: &str) -> StratisResult<String> {
+    let mut command = Command::new(get_optional_executable(CLEVIS)?.as_os_str());
+    command.arg("decrypt");
+
+    let result = execute_cmd_with_input(&mut command, jwe.as_bytes())?;
+    if !result.status.success() {
+        return Err(command_failed(&command, &result));
+    }
+    String::from_utf8(result.stdout).map_err(|_| {
+        StratisError::Error("the secret unsealed by Clevis is not a passphrase".into())
+    })
+}
+
+/// Call udevadm settle. Give up, returning an error, if the event queue
+/// has not emptied by the time the wait is considered stuck.
 pub fn udev_settle() -> StratisResult<()> {
-    execute_cmd(Command::new(get_executable(UDEVADM).as_os_str()).arg("settle"))
+    watch(None, "udev event queue", "settle", || {
+        execute_cmd(
+            Command::new(get_executable(UDEVADM).as_os_str())
+                .arg("settle")
+                .arg(format!("--timeout={}", STUCK_THRESHOLD_SECS)),
+        )
+    })
 }
 
 #[cfg(test)]
@@ -166,7 +513,74 @@ pub fn create_ext3_fs(devnode: &Path) -> StratisResult<()> {
     execute_cmd(Command::new("mkfs.ext3").arg(&devnode))
 }
 
+/// Run xfs_repair on the unmounted filesystem on devnode. If repair is
+/// false, the filesystem is only checked, and is not modified.
+/// Return whether the filesystem was found consistent, or was repaired, and
+/// the output of the command. Return an error if the command could not be
+/// run, or if it failed other than by finding the filesystem inconsistent.
+pub fn xfs_repair(devnode: &Path, repair: bool) -> StratisResult<(bool, String)> {
+    let mut command = Command::new(get_executable(XFS_REPAIR).as_os_str());
+    if !repair {
+        command.arg("-n");
+    }
+    command.arg(&devnode);
+
+    let result = command.output().map_err(|err| {
+        StratisError::Error(format!(
+            "Failed to execute command {:?}, err: {:?}",
+            command, err
+        ))
+    })?;
+
+    let output = format!(
+        "{}{}",
+        String::from_utf8_lossy(&result.stdout),
+        String::from_utf8_lossy(&result.stderr)
+    );
+    match result.status.code() {
+        Some(0) => Ok((true, output)),
+        // In no-modify mode, an exit status of 1 means that corruption
+        // was found.
+        Some(1) if !repair => Ok((false, output)),
+        _ => Err(StratisError::Error(format!(
+            "Command failed: cmd: {:?}, output: {}",
+            command, output
+        ))),
+    }
+}
+
 #[cfg(test)]
-pub fn xfs_repair(devnode: &Path) -> StratisResult<()> {
-    execute_cmd(Command::new("xfs_repair").arg("-n").arg(&devnode))
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the ids of the keyslots and the tokens are found in
+    /// their sections of the output of luksDump, and not in the details of
+    /// each entry.
+    fn test_parse_dump_ids() {
+        let dump = "LUKS header information
+Version:       \t2
+
+Data segments:
+  0: crypt
+\toffset: 16777216 [bytes]
+Keyslots:
+  0: luks2
+\tKey:        512 bits
+\tSalt:       f6 2e 3a 9b 0d 4c 5e 61
+\t            0f 3e 8a 11 2c 7d 9e 0b
+\tDigest ID:  0
+  2: luks2
+\tKey:        512 bits
+Tokens:
+  0: clevis
+\tKeyslot:    2
+Digests:
+  0: pbkdf2
+\tSalt:       0a 1b
+";
+        assert_eq!(parse_dump_ids(dump, "Keyslots:"), vec![0, 2]);
+        assert_eq!(parse_dump_ids(dump, "Tokens:"), vec![0]);
+        assert_eq!(parse_dump_ids("", "Tokens:"), Vec::<u32>::new());
+    }
 }
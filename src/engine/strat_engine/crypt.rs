@@ -0,0 +1,877 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to manage the dm-crypt layer beneath the data tier of an encrypted
+// pool. Each data device of an encrypted pool is formatted as a LUKS2
+// device, and the pool's blockdev is the dm-crypt device that is opened on
+// it. The label of the LUKS2 device records the pool to which it belongs,
+// so that a locked pool can be found before any of its Stratis metadata can
+// be read. The operations on the LUKS2 devices themselves are done through
+// the CryptOps trait, so that they can be done in memory where root
+// privileges can not be had. They are done with the cryptsetup and clevis
+// commands, as the other storage tools are driven from cmd.rs, rather than
+// through libcryptsetup, whose bindings stratisd does not depend on. The
+// simulator formats, binds and unbinds its devices through CryptOps too,
+// holding their LUKS2 headers in memory. A pool may also be bound to a Tang
+// server, as clevis luks bind binds a device: a passphrase is sealed with
+// Clevis and kept in a token in the header of each device, and the pool can
+// be unlocked whenever the server can be reached to unseal it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use devicemapper::{Bytes, DmNameBuf, Sectors, IEC};
+use libudev;
+use rand::{OsRng, Rng};
+use serde_json::{self, Map, Value};
+use uuid::Uuid;
+
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
+
+use super::super::types::{PoolBindings, PoolUuid};
+use super::cmd::{
+    clevis_decrypt, clevis_encrypt_tang, luks_add_key, luks_close, luks_format, luks_header_ids,
+    luks_kill_slot, luks_open, luks_token_export, luks_token_import, luks_token_remove,
+};
+use super::device::wipe_sectors;
+use super::names::format_crypt_name;
+
+/// The prefix of the label of every LUKS2 device of an encrypted pool.
+const LABEL_PREFIX: &str = "stratis-";
+
+/// The size of the area at the start of a LUKS2 device that holds its
+/// headers and its keyslots, with cryptsetup's defaults.
+const LUKS2_HEADER_SIZE: Bytes = Bytes(16 * IEC::Mi);
+
+/// The number of keyslots in a LUKS2 header.
+pub const LUKS2_KEYSLOTS: u32 = 32;
+
+/// The number of tokens in a LUKS2 header.
+pub const LUKS2_TOKENS: u32 = 32;
+
+/// The type of the tokens in which Clevis records the keyslots whose
+/// passphrases it seals.
+const CLEVIS_TOKEN_TYPE: &str = "clevis";
+
+/// The Clevis pin that seals a passphrase with the help of a Tang server.
+const TANG_PIN: &str = "tang";
+
+/// The parts of a JWE, in the order in which its compact form joins them.
+const JWE_PARTS: [&str; 5] = ["protected", "encrypted_key", "iv", "ciphertext", "tag"];
+
+/// The number of random bytes in a passphrase that stratisd makes for a
+/// keyslot that Clevis opens.
+const SEALED_PASSPHRASE_BYTES: usize = 32;
+
+/// The operations on LUKS2 devices by which the dm-crypt layer is managed:
+/// making their headers, activating and deactivating them, managing their
+/// keyslots and the tokens in their headers, and sealing and unsealing
+/// passphrases with Clevis.
+pub trait CryptOps {
+    /// Format physical as a LUKS2 device with the given UUID and label,
+    /// with a single keyslot that is opened by passphrase. Everything on
+    /// the device is lost.
+    fn format(
+        &self,
+        physical: &Path,
+        luks_uuid: Uuid,
+        label: &str,
+        passphrase: &str,
+    ) -> StratisResult<()>;
+
+    /// Activate the LUKS2 device at physical with passphrase, as the
+    /// dm-crypt device with the given name.
+    /// Return false if no keyslot is opened by the passphrase.
+    fn activate(&self, physical: &Path, name: &str, passphrase: &str) -> StratisResult<bool>;
+
+    /// Deactivate the dm-crypt device with the given name.
+    fn deactivate(&self, name: &str) -> StratisResult<()>;
+
+    /// Add a keyslot opened by new_passphrase to the LUKS2 device at
+    /// physical, in the first keyslot that is free.
+    /// Return the id of the keyslot, or None if no keyslot is opened by
+    /// passphrase, so that none was added.
+    fn add_keyslot(
+        &self,
+        physical: &Path,
+        passphrase: &str,
+        new_passphrase: &str,
+    ) -> StratisResult<Option<u32>>;
+
+    /// Remove the keyslot with the given id from the LUKS2 device at
+    /// physical.
+    fn remove_keyslot(&self, physical: &Path, keyslot: u32) -> StratisResult<()>;
+
+    /// The ids of the keyslots in use in the header of the LUKS2 device at
+    /// physical.
+    fn keyslots(&self, physical: &Path) -> StratisResult<Vec<u32>>;
+
+    /// The id and the JSON of each token in the header of the LUKS2 device
+    /// at physical.
+    fn tokens(&self, physical: &Path) -> StratisResult<Vec<(u32, String)>>;
+
+    /// Add the token json to the header of the LUKS2 device at physical,
+    /// with the first token id that is free.
+    /// Return the id of the token.
+    fn add_token(&self, physical: &Path, json: &str) -> StratisResult<u32>;
+
+    /// Remove the token with the given id from the header of the LUKS2
+    /// device at physical.
+    fn remove_token(&self, physical: &Path, token_id: u32) -> StratisResult<()>;
+
+    /// Seal secret with Clevis's tang pin, so that it can be unsealed only
+    /// with the help of the Tang server at url, whose signing key has the
+    /// given thumbprint.
+    /// Return the JWE that holds the sealed secret, in compact form.
+    fn seal_tang(&self, url: &str, thumbprint: &str, secret: &str) -> StratisResult<String>;
+
+    /// Unseal the secret sealed by Clevis in jwe, which is in compact form.
+    fn unseal(&self, jwe: &str) -> StratisResult<String>;
+}
+
+/// The first id below limit that is not among used.
+pub fn first_free(used: &[u32], limit: u32, what: &str, physical: &Path) -> StratisResult<u32> {
+    (0..limit).find(|id| !used.contains(id)).ok_or_else(|| {
+        StratisError::Engine(
+            ErrorEnum::NoSpace,
+            format!("no {} of {} is free", what, physical.display()),
+        )
+    })
+}
+
+/// The operations on LUKS2 devices, done with cryptsetup.
+#[derive(Debug)]
+pub struct Cryptsetup;
+
+impl CryptOps for Cryptsetup {
+    fn format(
+        &self,
+        physical: &Path,
+        luks_uuid: Uuid,
+        label: &str,
+        passphrase: &str,
+    ) -> StratisResult<()> {
+        luks_format(physical, luks_uuid, label, passphrase)
+    }
+
+    fn activate(&self, physical: &Path, name: &str, passphrase: &str) -> StratisResult<bool> {
+        luks_open(physical, name, passphrase)
+    }
+
+    fn deactivate(&self, name: &str) -> StratisResult<()> {
+        luks_close(name)
+    }
+
+    fn add_keyslot(
+        &self,
+        physical: &Path,
+        passphrase: &str,
+        new_passphrase: &str,
+    ) -> StratisResult<Option<u32>> {
+        let used = self.keyslots(physical)?;
+        let keyslot = first_free(&used, LUKS2_KEYSLOTS, "keyslot", physical)?;
+        if luks_add_key(physical, passphrase, keyslot, new_passphrase)? {
+            Ok(Some(keyslot))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn remove_keyslot(&self, physical: &Path, keyslot: u32) -> StratisResult<()> {
+        luks_kill_slot(physical, keyslot)
+    }
+
+    fn keyslots(&self, physical: &Path) -> StratisResult<Vec<u32>> {
+        Ok(luks_header_ids(physical)?.0)
+    }
+
+    fn tokens(&self, physical: &Path) -> StratisResult<Vec<(u32, String)>> {
+        let mut tokens = Vec::new();
+        for token_id in luks_header_ids(physical)?.1 {
+            tokens.push((token_id, luks_token_export(physical, token_id)?));
+        }
+        Ok(tokens)
+    }
+
+    fn add_token(&self, physical: &Path, json: &str) -> StratisResult<u32> {
+        let used = luks_header_ids(physical)?.1;
+        let token_id = first_free(&used, LUKS2_TOKENS, "token", physical)?;
+        luks_token_import(physical, token_id, json)?;
+        Ok(token_id)
+    }
+
+    fn remove_token(&self, physical: &Path, token_id: u32) -> StratisResult<()> {
+        luks_token_remove(physical, token_id)
+    }
+
+    fn seal_tang(&self, url: &str, thumbprint: &str, secret: &str) -> StratisResult<String> {
+        clevis_encrypt_tang(url, thumbprint, secret)
+    }
+
+    fn unseal(&self, jwe: &str) -> StratisResult<String> {
+        clevis_decrypt(jwe)
+    }
+}
+
+/// The label that records the pool to which a LUKS2 device belongs.
+fn crypt_label(pool_uuid: PoolUuid) -> String {
+    format!("{}{}", LABEL_PREFIX, pool_uuid.simple())
+}
+
+/// The pool recorded by the label of a LUKS2 device, if it is a label of a
+/// device of an encrypted pool.
+fn pool_of_label(label: &str) -> Option<PoolUuid> {
+    if label.starts_with(LABEL_PREFIX) {
+        PoolUuid::parse_str(&label[LABEL_PREFIX.len()..]).ok()
+    } else {
+        None
+    }
+}
+
+/// The devnode of the dm-crypt device with the given name.
+fn crypt_devnode(name: &DmNameBuf) -> PathBuf {
+    Path::new("/dev/mapper").join(name.to_string())
+}
+
+/// The devnode of the LUKS2 device beneath the dm-crypt device at devnode,
+/// the only device that it holds.
+fn backing_devnode(devnode: &Path) -> StratisResult<PathBuf> {
+    let canonical = fs::canonicalize(devnode)?;
+    let kernel_name = canonical.file_name().ok_or_else(|| {
+        StratisError::Engine(
+            ErrorEnum::Invalid,
+            format!("{} is not a block device", devnode.display()),
+        )
+    })?;
+    let slaves = Path::new("/sys/class/block").join(kernel_name).join("slaves");
+    match fs::read_dir(&slaves)?.next() {
+        Some(entry) => Ok(Path::new("/dev").join(entry?.file_name())),
+        None => Err(StratisError::Engine(
+            ErrorEnum::NotFound,
+            format!("no device found beneath {}", devnode.display()),
+        )),
+    }
+}
+
+/// Open the LUKS2 device at physical, which has the given LUKS UUID and
+/// belongs to the given pool, with passphrase.
+/// Return the devnode of the dm-crypt device, or None if no keyslot is
+/// opened by the passphrase.
+pub fn open_crypt(
+    crypt: &CryptOps,
+    pool_uuid: PoolUuid,
+    physical: &Path,
+    luks_uuid: Uuid,
+    passphrase: &str,
+) -> StratisResult<Option<PathBuf>> {
+    let name = format_crypt_name(pool_uuid, luks_uuid);
+    if crypt.activate(physical, &name.to_string(), passphrase)? {
+        Ok(Some(crypt_devnode(&name)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Format the device at physical as a LUKS2 device of the given pool,
+/// opened by passphrase, and open it.
+/// Return the devnode of the dm-crypt device. If the device could not be
+/// opened, its LUKS2 header is wiped.
+pub fn initialize_crypt(
+    crypt: &CryptOps,
+    pool_uuid: PoolUuid,
+    physical: &Path,
+    passphrase: &str,
+) -> StratisResult<PathBuf> {
+    let luks_uuid = Uuid::new_v4();
+    crypt.format(physical, luks_uuid, &crypt_label(pool_uuid), passphrase)?;
+    match open_crypt(crypt, pool_uuid, physical, luks_uuid, passphrase) {
+        Ok(Some(devnode)) => Ok(devnode),
+        result => {
+            let _ = wipe_sectors(physical, Sectors(0), LUKS2_HEADER_SIZE.sectors());
+            Err(result.err().unwrap_or_else(|| {
+                StratisError::Engine(
+                    ErrorEnum::Error,
+                    format!(
+                        "{} could not be opened with the passphrase just set",
+                        physical.display()
+                    ),
+                )
+            }))
+        }
+    }
+}
+
+/// Close the dm-crypt device at devnode, leaving the LUKS2 device beneath
+/// it as it is.
+pub fn close_crypt(crypt: &CryptOps, devnode: &Path) -> StratisResult<()> {
+    let name = devnode.file_name().ok_or_else(|| {
+        StratisError::Engine(
+            ErrorEnum::Invalid,
+            format!("{} is not a dm-crypt device", devnode.display()),
+        )
+    })?;
+    crypt.deactivate(&name.to_string_lossy())
+}
+
+/// Close the dm-crypt device at devnode and wipe the header of the LUKS2
+/// device beneath it, so that nothing that was written through the
+/// dm-crypt device can be read again.
+pub fn destroy_crypt(crypt: &CryptOps, devnode: &Path) -> StratisResult<()> {
+    let physical = backing_devnode(devnode)?;
+    close_crypt(crypt, devnode)?;
+    wipe_sectors(&physical, Sectors(0), LUKS2_HEADER_SIZE.sectors())
+}
+
+/// Decode s from unpadded base64url, as the parts of a JWE are encoded.
+fn decode_base64url(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// A token in which Clevis keeps the JWE that seals the passphrase of its
+/// keyslots.
+#[derive(Debug, PartialEq)]
+struct ClevisToken {
+    /// The pin that sealed the passphrase, named in the JWE's protected
+    /// header.
+    pin: String,
+    /// The keyslots opened by the passphrase.
+    keyslots: Vec<u32>,
+    /// The JWE, in compact form.
+    jwe: String,
+}
+
+/// The token json, if it is a Clevis token.
+fn clevis_token(json: &str) -> Option<ClevisToken> {
+    let token: Value = serde_json::from_str(json).ok()?;
+    if token.get("type")?.as_str()? != CLEVIS_TOKEN_TYPE {
+        return None;
+    }
+    let keyslots = token
+        .get("keyslots")?
+        .as_array()?
+        .iter()
+        .filter_map(|keyslot| keyslot.as_str().and_then(|k| k.parse::<u32>().ok()))
+        .collect();
+    let jwe = token.get("jwe")?;
+    let jwe = JWE_PARTS
+        .iter()
+        .map(|part| jwe.get(part).and_then(|p| p.as_str()))
+        .collect::<Option<Vec<_>>>()?
+        .join(".");
+    let protected = decode_base64url(jwe.split('.').next()?)?;
+    let header: Value = serde_json::from_slice(&protected).ok()?;
+    let pin = header.get("clevis")?.get("pin")?.as_str()?.to_owned();
+    Some(ClevisToken { pin, keyslots, jwe })
+}
+
+/// The JSON of a Clevis token for keyslot that keeps jwe, which is in
+/// compact form. The token is laid out as clevis luks bind lays out its
+/// own, so that Clevis can unlock the device with it too.
+fn clevis_token_json(keyslot: u32, jwe: &str) -> StratisResult<String> {
+    let parts = jwe.split('.').collect::<Vec<_>>();
+    if parts.len() != JWE_PARTS.len() {
+        return Err(StratisError::Error(
+            "the JWE returned by Clevis is not in compact form".into(),
+        ));
+    }
+    let mut jwe = Map::new();
+    for (name, part) in JWE_PARTS.iter().zip(parts) {
+        jwe.insert((*name).to_owned(), Value::String(part.to_owned()));
+    }
+    let mut token = Map::new();
+    token.insert(
+        "type".to_owned(),
+        Value::String(CLEVIS_TOKEN_TYPE.to_owned()),
+    );
+    token.insert(
+        "keyslots".to_owned(),
+        Value::Array(vec![Value::String(keyslot.to_string())]),
+    );
+    token.insert("jwe".to_owned(), Value::Object(jwe));
+    Ok(Value::Object(token).to_string())
+}
+
+/// The Clevis tokens of the LUKS2 device at physical, with their ids.
+fn clevis_tokens(crypt: &CryptOps, physical: &Path) -> StratisResult<Vec<(u32, ClevisToken)>> {
+    Ok(crypt
+        .tokens(physical)?
+        .iter()
+        .filter_map(|&(token_id, ref json)| clevis_token(json).map(|token| (token_id, token)))
+        .collect())
+}
+
+/// The Clevis pins bound to the LUKS2 device at physical, and the number
+/// of its keyslots that are in use.
+pub fn header_bindings(crypt: &CryptOps, physical: &Path) -> StratisResult<(Vec<String>, u32)> {
+    let pins = clevis_tokens(crypt, physical)?
+        .into_iter()
+        .map(|(_, token)| token.pin)
+        .collect();
+    Ok((pins, crypt.keyslots(physical)?.len() as u32))
+}
+
+/// Open the LUKS2 device at physical, which has the given LUKS UUID and
+/// belongs to the given pool, with a passphrase that Clevis unseals from
+/// one of its tokens.
+/// Return the devnode of the dm-crypt device, or None if no passphrase
+/// could be unsealed that opens the device.
+pub fn open_crypt_clevis(
+    crypt: &CryptOps,
+    pool_uuid: PoolUuid,
+    physical: &Path,
+    luks_uuid: Uuid,
+) -> StratisResult<Option<PathBuf>> {
+    for (_, token) in clevis_tokens(crypt, physical)? {
+        match crypt.unseal(&token.jwe) {
+            Ok(passphrase) => {
+                let opened = open_crypt(crypt, pool_uuid, physical, luks_uuid, &passphrase)?;
+                if opened.is_some() {
+                    return Ok(opened);
+                }
+            }
+            Err(err) => warn!(
+                "unable to unseal a passphrase of {} with the Clevis pin {}, reason: {}",
+                physical.display(),
+                token.pin,
+                err
+            ),
+        }
+    }
+    Ok(None)
+}
+
+/// Bind the LUKS2 device at physical to the Tang server at url, as clevis
+/// luks bind does: add a keyslot opened by a new random passphrase, and
+/// keep the passphrase, sealed with the tang pin, in a Clevis token.
+/// Return false if no keyslot is opened by passphrase.
+fn bind_device_tang(
+    crypt: &CryptOps,
+    physical: &Path,
+    passphrase: &str,
+    url: &str,
+    thumbprint: &str,
+) -> StratisResult<bool> {
+    let mut secret = [0u8; SEALED_PASSPHRASE_BYTES];
+    OsRng::new()?.fill_bytes(&mut secret);
+    let secret = secret
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    let jwe = crypt.seal_tang(url, thumbprint, &secret)?;
+
+    let keyslot = match crypt.add_keyslot(physical, passphrase, &secret)? {
+        Some(keyslot) => keyslot,
+        None => return Ok(false),
+    };
+    if let Err(err) =
+        clevis_token_json(keyslot, &jwe).and_then(|json| crypt.add_token(physical, &json))
+    {
+        let _ = crypt.remove_keyslot(physical, keyslot);
+        return Err(err);
+    }
+    Ok(true)
+}
+
+/// Remove every binding of the LUKS2 device at physical with the given
+/// Clevis pin: its tokens, and the keyslots that they open. Nothing is
+/// removed if it would leave the device with no keyslot.
+/// Return the number of bindings removed.
+fn unbind_device_clevis(crypt: &CryptOps, physical: &Path, pin: &str) -> StratisResult<usize> {
+    let bound = clevis_tokens(crypt, physical)?
+        .into_iter()
+        .filter(|&(_, ref token)| token.pin == pin)
+        .collect::<Vec<_>>();
+    if bound.is_empty() {
+        return Ok(0);
+    }
+    if crypt
+        .keyslots(physical)?
+        .iter()
+        .all(|keyslot| bound.iter().any(|&(_, ref token)| token.keyslots.contains(keyslot)))
+    {
+        return Err(StratisError::Engine(
+            ErrorEnum::Invalid,
+            format!(
+                "removing the {} bindings of {} would leave no keyslot to open it",
+                pin,
+                physical.display()
+            ),
+        ));
+    }
+    for &(token_id, ref token) in &bound {
+        for &keyslot in &token.keyslots {
+            crypt.remove_keyslot(physical, keyslot)?;
+        }
+        crypt.remove_token(physical, token_id)?;
+    }
+    Ok(bound.len())
+}
+
+/// Find the LUKS2 devices of encrypted pools, by the labels that udev
+/// records for them. Return, for each pool, the devnode and the LUKS UUID
+/// of each of its devices, and whether it is open.
+fn find_luks() -> StratisResult<HashMap<PoolUuid, Vec<(PathBuf, Uuid, bool)>>> {
+    let context = libudev::Context::new()?;
+    let mut enumerator = libudev::Enumerator::new(&context)?;
+    enumerator.match_subsystem("block")?;
+    enumerator.match_property("ID_FS_TYPE", "crypto_LUKS")?;
+
+    let mut found = HashMap::new();
+    for dev in enumerator
+        .scan_devices()?
+        .filter(|dev| dev.is_initialized())
+        .filter(|dev| {
+            dev.property_value("DM_MULTIPATH_DEVICE_PATH")
+                .map_or(true, |v| v != "1")
+        }) {
+        let pool_uuid = dev.property_value("ID_FS_LABEL")
+            .and_then(|label| pool_of_label(&label.to_string_lossy()));
+        let luks_uuid = dev.property_value("ID_FS_UUID")
+            .and_then(|uuid| Uuid::parse_str(&uuid.to_string_lossy()).ok());
+        if let (Some(pool_uuid), Some(luks_uuid), Some(devnode)) =
+            (pool_uuid, luks_uuid, dev.devnode())
+        {
+            let open = crypt_devnode(&format_crypt_name(pool_uuid, luks_uuid)).exists();
+            found
+                .entry(pool_uuid)
+                .or_insert_with(Vec::new)
+                .push((devnode.to_owned(), luks_uuid, open));
+        }
+    }
+    Ok(found)
+}
+
+/// Find the LUKS2 devices of encrypted pools that are not open. Return, for
+/// each pool, the devnode and the LUKS UUID of each of its locked devices.
+pub fn find_locked() -> StratisResult<HashMap<PoolUuid, Vec<(PathBuf, Uuid)>>> {
+    Ok(find_luks()?
+        .into_iter()
+        .filter_map(|(pool_uuid, devices)| {
+            let locked = devices
+                .into_iter()
+                .filter(|&(_, _, open)| !open)
+                .map(|(devnode, luks_uuid, _)| (devnode, luks_uuid))
+                .collect::<Vec<_>>();
+            if locked.is_empty() {
+                None
+            } else {
+                Some((pool_uuid, locked))
+            }
+        })
+        .collect())
+}
+
+/// The means by which the pool may be unlocked, as the LUKS2 header of its
+/// first device records them; every device of a pool is bound alike. The
+/// key kept in the keyring, which no header records, is not looked for.
+/// Return None if no LUKS2 device of the pool is found.
+pub fn find_bindings(crypt: &CryptOps, pool_uuid: PoolUuid) -> StratisResult<Option<PoolBindings>> {
+    let devices = match find_luks()?.remove(&pool_uuid) {
+        Some(devices) => devices,
+        None => return Ok(None),
+    };
+    let first = devices
+        .iter()
+        .map(|&(ref devnode, _, _)| devnode)
+        .min()
+        .expect("a pool is found only by its devices");
+    let (clevis_pins, keyslots_used) = header_bindings(crypt, first)?;
+    Ok(Some(PoolBindings {
+        unlocked: devices.iter().all(|&(_, _, open)| open),
+        key_description: None,
+        clevis_pins,
+        keyslots_used,
+        keyslots_total: LUKS2_KEYSLOTS,
+    }))
+}
+
+/// The devnodes of the LUKS2 devices of the pool, locked or not.
+fn pool_devices(pool_uuid: PoolUuid) -> StratisResult<Vec<PathBuf>> {
+    match find_luks()?.remove(&pool_uuid) {
+        Some(devices) => Ok(devices
+            .into_iter()
+            .map(|(devnode, _, _)| devnode)
+            .collect()),
+        None => Err(StratisError::Engine(
+            ErrorEnum::NotFound,
+            format!("no devices of an encrypted pool {} found", pool_uuid),
+        )),
+    }
+}
+
+/// Bind every LUKS2 device of the pool to the Tang server at url, whose
+/// signing key has the given thumbprint, so that the pool can be unlocked
+/// with the server's help.
+/// Return false if the pool is already bound to a Tang server.
+pub fn bind_pool_tang(
+    crypt: &CryptOps,
+    pool_uuid: PoolUuid,
+    passphrase: &str,
+    url: &str,
+    thumbprint: &str,
+) -> StratisResult<bool> {
+    let devices = pool_devices(pool_uuid)?;
+    bind_devices_tang(crypt, pool_uuid, &devices, passphrase, url, thumbprint)
+}
+
+/// Bind the LUKS2 devices of the pool at devices to the Tang server at url,
+/// whose signing key has the given thumbprint. If a device can not be
+/// bound, the devices that were already bound are unbound again.
+/// Return false if the pool is already bound to a Tang server.
+pub fn bind_devices_tang(
+    crypt: &CryptOps,
+    pool_uuid: PoolUuid,
+    devices: &[PathBuf],
+    passphrase: &str,
+    url: &str,
+    thumbprint: &str,
+) -> StratisResult<bool> {
+    for physical in devices {
+        if header_bindings(crypt, physical)?.0.iter().any(|pin| pin == TANG_PIN) {
+            return Ok(false);
+        }
+    }
+
+    let mut result = Ok(true);
+    let mut bound = Vec::new();
+    for physical in devices {
+        match bind_device_tang(crypt, physical, passphrase, url, thumbprint) {
+            Ok(true) => bound.push(physical),
+            Ok(false) => {
+                result = Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("PassphraseIncorrect").param("pool", pool_uuid),
+                ));
+                break;
+            }
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+    if result.is_err() {
+        for physical in bound {
+            let _ = unbind_device_clevis(crypt, physical, TANG_PIN);
+        }
+    }
+    result
+}
+
+/// Remove the Tang bindings of every LUKS2 device of the pool.
+/// Return false if the pool was not bound to a Tang server.
+pub fn unbind_pool_tang(crypt: &CryptOps, pool_uuid: PoolUuid) -> StratisResult<bool> {
+    unbind_devices_tang(crypt, &pool_devices(pool_uuid)?)
+}
+
+/// Remove the Tang bindings of the LUKS2 devices at devices.
+/// Return false if none was bound to a Tang server.
+pub fn unbind_devices_tang(crypt: &CryptOps, devices: &[PathBuf]) -> StratisResult<bool> {
+    let mut removed = 0;
+    for physical in devices {
+        removed += unbind_device_clevis(crypt, physical, TANG_PIN)?;
+    }
+    Ok(removed > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::sim_engine::SimCrypt;
+
+    use super::*;
+
+    /// The protected header of a JWE sealed with the tang pin,
+    /// {"clevis":{"pin":"tang"}}.
+    const TANG_PROTECTED: &str = "eyJjbGV2aXMiOnsicGluIjoidGFuZyJ9fQ";
+
+    #[test]
+    /// Verify that a device is opened just by the passphrases of its
+    /// keyslots, as they are added and removed, and that closing it
+    /// deactivates the dm-crypt device opened on it.
+    fn test_open_close_crypt() {
+        let crypt = SimCrypt::default();
+        let pool_uuid = PoolUuid::new_v4();
+        let physical = Path::new("/dev/memory0");
+        let devnode = initialize_crypt(&crypt, pool_uuid, physical, "secret").unwrap();
+        assert!(devnode.starts_with("/dev/mapper"));
+        close_crypt(&crypt, &devnode).unwrap();
+        assert!(close_crypt(&crypt, &devnode).is_err());
+
+        let luks_uuid = Uuid::new_v4();
+        crypt
+            .format(physical, luks_uuid, &crypt_label(pool_uuid), "secret")
+            .unwrap();
+        assert_eq!(
+            open_crypt(&crypt, pool_uuid, physical, luks_uuid, "wrong").unwrap(),
+            None
+        );
+        assert_eq!(crypt.add_keyslot(physical, "wrong", "other").unwrap(), None);
+        assert_eq!(crypt.add_keyslot(physical, "secret", "other").unwrap(), Some(1));
+        crypt.remove_keyslot(physical, 0).unwrap();
+        assert_eq!(crypt.keyslots(physical).unwrap(), vec![1]);
+        assert_eq!(
+            open_crypt(&crypt, pool_uuid, physical, luks_uuid, "secret").unwrap(),
+            None
+        );
+        assert_eq!(
+            open_crypt(&crypt, pool_uuid, physical, luks_uuid, "other").unwrap(),
+            Some(crypt_devnode(&format_crypt_name(pool_uuid, luks_uuid)))
+        );
+    }
+
+    #[test]
+    /// Verify that each token is added with the first free id, and that the
+    /// id of a token removed is used again.
+    fn test_tokens() {
+        let crypt = SimCrypt::default();
+        let physical = Path::new("/dev/memory0");
+        assert!(crypt.tokens(physical).is_err());
+
+        crypt
+            .format(physical, Uuid::new_v4(), "stratis-", "secret")
+            .unwrap();
+        assert_eq!(crypt.tokens(physical).unwrap(), vec![]);
+        assert_eq!(crypt.add_token(physical, "{}").unwrap(), 0);
+        assert_eq!(crypt.add_token(physical, "{\"keyslots\": []}").unwrap(), 1);
+        crypt.remove_token(physical, 0).unwrap();
+        assert_eq!(
+            crypt.tokens(physical).unwrap(),
+            vec![(1, "{\"keyslots\": []}".to_owned())]
+        );
+        assert_eq!(crypt.add_token(physical, "{}").unwrap(), 0);
+    }
+
+    /// A Clevis token for keyslot, whose JWE names the tang pin in its
+    /// protected header. The JWE seals nothing.
+    fn tang_json(keyslot: u32) -> String {
+        format!(
+            "{{\"type\": \"clevis\", \"keyslots\": [\"{}\"], \"jwe\": {{\
+             \"ciphertext\": \"\", \"encrypted_key\": \"\", \"iv\": \"\", \
+             \"protected\": \"{}\", \"tag\": \"\"}}}}",
+            keyslot, TANG_PROTECTED
+        )
+    }
+
+    #[test]
+    /// Verify that base64url is decoded without padding, and that anything
+    /// else is not decoded.
+    fn test_decode_base64url() {
+        assert_eq!(decode_base64url(""), Some(vec![]));
+        assert_eq!(decode_base64url("Zg"), Some(b"f".to_vec()));
+        assert_eq!(decode_base64url("Zm8"), Some(b"fo".to_vec()));
+        assert_eq!(decode_base64url("Zm9v"), Some(b"foo".to_vec()));
+        assert_eq!(decode_base64url("-_8"), Some(vec![0xfb, 0xff]));
+        assert_eq!(decode_base64url("Zm9v+/=="), None);
+    }
+
+    #[test]
+    /// Verify that the pin and keyslots of a Clevis token are found, and
+    /// that tokens of other types are passed over.
+    fn test_header_bindings() {
+        assert_eq!(
+            clevis_token(&tang_json(1)),
+            Some(ClevisToken {
+                pin: "tang".to_owned(),
+                keyslots: vec![1],
+                jwe: format!("{}....", TANG_PROTECTED),
+            })
+        );
+        assert_eq!(
+            clevis_token("{\"type\": \"systemd-tpm2\", \"keyslots\": [\"1\"]}"),
+            None
+        );
+        assert_eq!(clevis_token("not json"), None);
+
+        let crypt = SimCrypt::default();
+        let physical = Path::new("/dev/memory0");
+        crypt
+            .format(physical, Uuid::new_v4(), "stratis-", "secret")
+            .unwrap();
+        assert_eq!(header_bindings(&crypt, physical).unwrap(), (vec![], 1));
+
+        assert_eq!(crypt.add_keyslot(physical, "secret", "sealed").unwrap(), Some(1));
+        crypt.add_token(physical, &tang_json(1)).unwrap();
+        crypt
+            .add_token(physical, "{\"type\": \"other\", \"keyslots\": []}")
+            .unwrap();
+        assert_eq!(
+            header_bindings(&crypt, physical).unwrap(),
+            (vec!["tang".to_owned()], 2)
+        );
+    }
+
+    #[test]
+    /// Verify that a device bound to a Tang server is opened with the
+    /// passphrase that Clevis unseals, that a device is not bound if the
+    /// passphrase given does not open it or the server can not be reached,
+    /// and that unbinding it removes the keyslot that was added, but never
+    /// the last keyslot.
+    fn test_bind_tang() {
+        let crypt = SimCrypt::default();
+        let pool_uuid = PoolUuid::new_v4();
+        let physical = Path::new("/dev/memory0");
+        let luks_uuid = Uuid::new_v4();
+        crypt
+            .format(physical, luks_uuid, &crypt_label(pool_uuid), "secret")
+            .unwrap();
+        assert_eq!(
+            open_crypt_clevis(&crypt, pool_uuid, physical, luks_uuid).unwrap(),
+            None
+        );
+
+        assert!(!bind_device_tang(&crypt, physical, "wrong", "http://tang", "thp").unwrap());
+        assert!(bind_device_tang(&crypt, physical, "secret", "http://", "thp").is_err());
+        assert_eq!(header_bindings(&crypt, physical).unwrap(), (vec![], 1));
+
+        assert!(bind_device_tang(&crypt, physical, "secret", "http://tang", "thp").unwrap());
+        assert_eq!(
+            header_bindings(&crypt, physical).unwrap(),
+            (vec!["tang".to_owned()], 2)
+        );
+        assert_eq!(
+            open_crypt_clevis(&crypt, pool_uuid, physical, luks_uuid).unwrap(),
+            Some(crypt_devnode(&format_crypt_name(pool_uuid, luks_uuid)))
+        );
+
+        assert_eq!(unbind_device_clevis(&crypt, physical, "tpm2").unwrap(), 0);
+        assert_eq!(unbind_device_clevis(&crypt, physical, TANG_PIN).unwrap(), 1);
+        assert_eq!(header_bindings(&crypt, physical).unwrap(), (vec![], 1));
+        assert_eq!(crypt.keyslots(physical).unwrap(), vec![0]);
+
+        crypt.add_token(physical, &tang_json(0)).unwrap();
+        assert!(unbind_device_clevis(&crypt, physical, TANG_PIN).is_err());
+        assert_eq!(crypt.keyslots(physical).unwrap(), vec![0]);
+    }
+
+    #[test]
+    /// Verify that the pool is recovered from the label of its devices, and
+    /// that no pool is found in other labels.
+    fn test_pool_of_label() {
+        let pool_uuid = PoolUuid::new_v4();
+        assert_eq!(pool_of_label(&crypt_label(pool_uuid)), Some(pool_uuid));
+        assert_eq!(pool_of_label("stratis-"), None);
+        assert_eq!(pool_of_label("home"), None);
+    }
+}
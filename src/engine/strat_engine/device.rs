@@ -4,11 +4,13 @@
 
 // Functions for dealing with devices.
 
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufWriter, Cursor, Seek, SeekFrom, Write};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
 
-use devicemapper::{Sectors, IEC, SECTOR_SIZE};
+use devicemapper::{Device, Sectors, IEC, SECTOR_SIZE};
+use nix::libc;
 
 use stratis::StratisResult;
 
@@ -18,6 +20,11 @@ use stratis::StratisResult;
 /// do not implement sync_all, e.g., the Cursor type.
 pub trait SyncAll: Write {
     fn sync_all(&mut self) -> io::Result<()>;
+
+    /// Drop length bytes at offset from any cache that holds them, so that
+    /// they are next read from the device itself. The bytes must already
+    /// have been synced.
+    fn drop_cache(&mut self, offset: u64, length: u64) -> io::Result<()>;
 }
 
 impl SyncAll for File {
@@ -25,6 +32,23 @@ impl SyncAll for File {
     fn sync_all(&mut self) -> io::Result<()> {
         File::sync_all(self)
     }
+
+    /// Advises the kernel that the range will not be needed, which drops
+    /// its clean pages from the page cache.
+    fn drop_cache(&mut self, offset: u64, length: u64) -> io::Result<()> {
+        let advised = unsafe {
+            libc::posix_fadvise(
+                self.as_raw_fd(),
+                offset as libc::off_t,
+                length as libc::off_t,
+                libc::POSIX_FADV_DONTNEED,
+            )
+        };
+        if advised != 0 {
+            return Err(io::Error::from_raw_os_error(advised));
+        }
+        Ok(())
+    }
 }
 
 impl<T> SyncAll for Cursor<T>
@@ -36,6 +60,11 @@ where
     fn sync_all(&mut self) -> io::Result<()> {
         Ok(())
     }
+
+    /// A no-op. Nothing is cached; the data is the Cursor inner value.
+    fn drop_cache(&mut self, _offset: u64, _length: u64) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 impl<T> SyncAll for BufWriter<T>
@@ -45,6 +74,23 @@ where
     fn sync_all(&mut self) -> io::Result<()> {
         self.get_mut().sync_all()
     }
+
+    fn drop_cache(&mut self, offset: u64, length: u64) -> io::Result<()> {
+        self.get_mut().drop_cache(offset, length)
+    }
+}
+
+impl<'a, T> SyncAll for &'a mut T
+where
+    T: SyncAll + ?Sized,
+{
+    fn sync_all(&mut self) -> io::Result<()> {
+        (**self).sync_all()
+    }
+
+    fn drop_cache(&mut self, offset: u64, length: u64) -> io::Result<()> {
+        (**self).drop_cache(offset, length)
+    }
 }
 
 /// Write buf at offset length times.
@@ -74,3 +120,50 @@ pub fn wipe_sectors<P: AsRef<Path>>(
 ) -> StratisResult<()> {
     write_sectors(path, offset, length, &[0u8; SECTOR_SIZE])
 }
+
+/// Copy length sectors at from_offset in the device at from to to_offset
+/// in the device at to, and sync the device written to.
+/// What is written through the dm devices above the device at from does
+/// not pass through the page cache of its node, so whatever of the range
+/// is cached there may be stale. It is dropped from the cache before the
+/// range is read, so that the range is read from the device itself.
+pub fn copy_sectors<P: AsRef<Path>, Q: AsRef<Path>>(
+    from: P,
+    from_offset: Sectors,
+    to: Q,
+    to_offset: Sectors,
+    length: Sectors,
+) -> StratisResult<()> {
+    let mut reader = File::open(from)?;
+    reader.drop_cache(*from_offset.bytes(), *length.bytes())?;
+    let mut writer = OpenOptions::new().write(true).open(to)?;
+    reader.seek(SeekFrom::Start(*from_offset.bytes()))?;
+    writer.seek(SeekFrom::Start(*to_offset.bytes()))?;
+
+    let mut buf = vec![0u8; IEC::Mi as usize];
+    let mut remaining = *length.bytes();
+    while remaining > 0 {
+        let amount = if remaining < buf.len() as u64 {
+            remaining as usize
+        } else {
+            buf.len()
+        };
+        reader.read_exact(&mut buf[..amount])?;
+        writer.write_all(&buf[..amount])?;
+        remaining -= amount as u64;
+    }
+
+    writer.sync_all()?;
+    Ok(())
+}
+
+/// Set the read-ahead of the device through its queue attribute in sysfs.
+/// The attribute is kept in KiB, so read_ahead is rounded down to a whole
+/// KiB.
+pub fn set_read_ahead(device: Device, read_ahead: Sectors) -> StratisResult<()> {
+    fs::write(
+        format!("/sys/dev/block/{}/queue/read_ahead_kb", device),
+        format!("{}", *read_ahead.bytes() / IEC::Ki),
+    )?;
+    Ok(())
+}
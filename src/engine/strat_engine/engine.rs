@@ -3,97 +3,162 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::clone::Clone;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
 use std::path::{Path, PathBuf};
 
-use devicemapper::{Device, DmNameBuf};
+use chrono::{DateTime, Duration, Utc};
+use serde_json;
+use uuid::Uuid;
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use devicemapper::{devnode_to_devno, Bytes, Device, DmNameBuf, Sectors};
 
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult, StratisResultExt};
+
+use super::super::changes::{record_change, record_pool_change, ChangeKind, ObjectKind};
 use super::super::devlinks;
 use super::super::engine::{Engine, Eventable, Pool};
 use super::super::event::{get_engine_listener_list, EngineEvent};
+use super::super::stats::{count_by, Counter};
 use super::super::structures::Table;
-use super::super::types::{Name, PoolUuid, Redundancy, RenameAction};
+use super::super::types::{
+    CacheType, Capability, ClonedDevice, Creator, MixedSizePolicy, Name, PoolBindings, PoolUuid,
+    ReclaimableDevice, Redundancy, RenameAction, SignaturePolicy,
+    DEFAULT_UNDO_DESTROY_WINDOW_SECS, validate_tang,
+};
 
 use super::backstore::device::is_stratis_device;
-use super::backstore::{find_all, get_metadata};
+use super::backstore::{
+    find_all, find_clones, find_destroyed, get_metadata, reconstruct_blockdevs, restore_metadata,
+    revive_blockdevs, set_metadata_time_fallback, set_metadata_write_interval,
+    wipe_destroyed_device, MetadataLayout,
+};
+use super::backup::{list_backups, load_backup, read_backup, MetadataBackup, BACKUP_DIR};
+use super::capabilities::probe_capabilities;
 #[cfg(test)]
 use super::cleanup::teardown_pools;
 use super::cmd::verify_binaries;
+use super::crypt::{
+    bind_pool_tang, close_crypt, find_bindings, find_locked, open_crypt, open_crypt_clevis,
+    unbind_pool_tang, Cryptsetup,
+};
 use super::dm::{get_dm, get_dm_init};
+use super::journal::{forget_job, forget_pool_jobs, list_jobs, JOURNAL_DIR};
+use super::keys::{kept_key_description, list_keys, read_key, set_key, unset_key};
 use super::names::validate_name;
 use super::pool::{check_metadata, StratPool};
+use super::repair::{repair_dm_names, Repair};
+use super::replay::log_replay;
+use super::serde_structs::PoolSave;
+use super::targets::{cache_target, require_target, CORE_TARGETS};
+use super::watchdog;
 
 const REQUIRED_DM_MINOR_VERSION: u32 = 37;
 
 /// Setup a pool from constituent devices in the context of some already
-/// setup pools. Return an error on anything that prevents the pool
-/// being set up.
+/// setup pools. Return the pool and any repairs made to its devlinks.
+/// Return an error on anything that prevents the pool being set up.
+/// A device that is a clone of another device of the pool is left out of it.
 /// Precondition: every device in devices has already been determined to belong
 /// to the pool with pool_uuid.
 pub fn setup_pool(
     pool_uuid: PoolUuid,
     devices: &HashMap<Device, PathBuf>,
-    pools: &Table<StratPool>,
-) -> StratisResult<(Name, StratPool)> {
-    // FIXME: In this method, various errors are assembled from various
-    // sources and combined into strings, so that they
-    // can be printed as log messages if necessary. Instead, some kind of
-    // error-chaining should be used here and if it is necessary
-    // to log the error, the log code should be able to reduce the error
-    // chain to something that can be sensibly logged.
-    let info_string = || {
-        let dev_paths = devices
-            .values()
-            .map(|p| p.to_str().expect("Unix is utf-8"))
-            .collect::<Vec<&str>>()
-            .join(" ,");
-        format!("(pool UUID: {}, devnodes: {})", pool_uuid, dev_paths)
-    };
-
-    let metadata = get_metadata(pool_uuid, devices)?.ok_or_else(|| {
-        let err_msg = format!("no metadata found for {}", info_string());
-        StratisError::Engine(ErrorEnum::NotFound, err_msg)
-    })?;
+    pools: &Table<PoolUuid, StratPool>,
+) -> StratisResult<(Name, StratPool, Vec<Repair>)> {
+    let clones = find_clones(pool_uuid, devices, &HashSet::new()).with_pool(pool_uuid)?;
+    for clone in &clones {
+        warn!(
+            "device {} claims the same device UUID {} in pool {} as device {}, \
+             leaving it out of the pool as a clone",
+            clone.devnode.display(),
+            clone.dev_uuid.simple(),
+            pool_uuid.simple(),
+            clone.kept.display()
+        );
+    }
+    let devices = &devices
+        .iter()
+        .filter(|&(_, devnode)| clones.iter().all(|clone| clone.devnode != *devnode))
+        .map(|(device, devnode)| (*device, devnode.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let metadata = get_metadata(pool_uuid, devices)
+        .with_pool(pool_uuid)?
+        .ok_or_else(|| {
+            StratisError::Engine(ErrorEnum::NotFound, "no metadata found".into())
+                .with_pool(pool_uuid)
+        })?;
 
     if pools.contains_name(&metadata.name) {
         let err_msg = format!(
-            "pool with name \"{}\" set up; metadata specifies same name for {}",
+            "pool with name \"{}\" set up; metadata specifies same name",
             &metadata.name,
-            info_string()
         );
-        return Err(StratisError::Engine(ErrorEnum::AlreadyExists, err_msg));
+        return Err(StratisError::Engine(ErrorEnum::AlreadyExists, err_msg).with_pool(pool_uuid));
     }
 
-    check_metadata(&metadata)
-        .or_else(|e| {
-            let err_msg = format!(
-                "inconsistent metadata for {}: reason: {:?}",
-                info_string(),
-                e
-            );
-            Err(StratisError::Engine(ErrorEnum::Error, err_msg))
-        })
-        .and_then(|_| {
-            StratPool::setup(pool_uuid, devices, &metadata).or_else(|e| {
-                let err_msg = format!(
-                    "failed to set up pool for {}: reason: {:?}",
-                    info_string(),
-                    e
-                );
-                Err(StratisError::Engine(ErrorEnum::Error, err_msg))
-            })
-        })
-        .and_then(|(pool_name, pool)| {
-            devlinks::setup_pool_devlinks(&pool_name, &pool);
-            Ok((pool_name, pool))
+    // If the metadata can not be set up, a crash while it was being saved
+    // may be to blame; replay its generations to find out.
+    let (pool_name, pool) = check_metadata(&metadata)
+        .and_then(|_| StratPool::setup(pool_uuid, devices, &metadata))
+        .map_err(|err| {
+            log_replay(pool_uuid, devices);
+            err
         })
+        .with_pool(pool_uuid)?;
+    let repairs = devlinks::setup_pool_devlinks(&pool_name, &pool)
+        .into_iter()
+        .map(Repair::Devlink)
+        .collect();
+    Ok((pool_name, pool, repairs))
+}
+
+/// Reconstruct the pool whose metadata is backed up in backup on the fresh
+/// devices specified by paths, one for each data tier blockdev recorded in
+/// the metadata, in the order recorded. The reconstructed pool has a new
+/// pool UUID and new device UUIDs, so that it can not be confused with any
+/// surviving devices of the original pool. The thin pool and the MDV are
+/// made anew, so the pool has no filesystems, and no cache tier is
+/// reconstructed.
+/// Return the pool, its UUID, and any repairs made to its devlinks.
+pub fn reconstruct_pool(
+    backup: &MetadataBackup,
+    paths: &[&Path],
+    pools: &Table<PoolUuid, StratPool>,
+) -> StratisResult<(Name, PoolUuid, StratPool, Vec<Repair>)> {
+    let mut metadata: PoolSave = serde_json::from_str(&backup.metadata)?;
+    check_metadata(&metadata)?;
+
+    if pools.contains_name(&metadata.name) {
+        return Err(StratisError::Structured(
+            ErrorEnum::AlreadyExists,
+            ErrorPayload::new("PoolNameExists").param("name", &metadata.name),
+        ));
+    }
+
+    if metadata.backstore.cache_tier.take().is_some() {
+        warn!(
+            "pool {} has a cache tier, which is not reconstructed",
+            &metadata.name
+        );
+    }
+
+    let pool_uuid = PoolUuid::new_v4();
+    let devices =
+        reconstruct_blockdevs(pool_uuid, &mut metadata.backstore.data_tier.blockdev, paths)?;
+    let (pool_name, pool) =
+        StratPool::reconstruct(pool_uuid, &devices, &metadata).with_pool(pool_uuid)?;
+    let repairs = devlinks::setup_pool_devlinks(&pool_name, &pool)
+        .into_iter()
+        .map(Repair::Devlink)
+        .collect();
+    Ok((pool_name, pool_uuid, pool, repairs))
 }
 
 #[derive(Debug)]
 pub struct StratEngine {
-    pools: Table<StratPool>,
+    pools: Table<PoolUuid, StratPool>,
 
     // Map of stratis devices that have been found but one or more stratis block devices are missing
     // which prevents the associated pools from being setup.
@@ -102,16 +167,33 @@ pub struct StratEngine {
     // Maps name of DM devices we are watching to the most recent event number
     // we've handled for each
     watched_dev_last_event_nrs: HashMap<DmNameBuf, u32>,
+
+    // The layout of the metadata on devices of newly created pools
+    metadata_layout: MetadataLayout,
+
+    // Repairs made to DM device names and devlinks while setting up pools
+    repairs: Vec<Repair>,
+
+    // The optional capabilities available with the running kernel
+    capabilities: Vec<Capability>,
+
+    // How long after a pool was destroyed, in seconds, its destruction may
+    // be undone
+    undo_destroy_window: u64,
 }
 
 impl StratEngine {
     /// Setup a StratEngine.
     /// 1. Verify the existence of Stratis /dev directory.
-    /// 2. Setup all the pools belonging to the engine.
+    /// 2. Rename any Stratis DM devices whose names do not match their UUIDs.
+    /// 3. Setup all the pools belonging to the engine.
     ///    a. Places any devices which belong to a pool, but are not complete
     ///       in the incomplete pools data structure.
+    ///    b. Repairs the devlinks of each pool set up.
+    /// 4. Remove devlinks that belong to no pool.
     ///
-    /// Returns an error if the kernel doesn't support required DM features.
+    /// Returns an error if the kernel doesn't support required DM features,
+    /// or lacks a DM target without which no pool can be set up.
     /// Returns an error if there was an error reading device nodes.
     /// Returns an error if the binaries on which it depends can not be found.
     pub fn initialize() -> StratisResult<StratEngine> {
@@ -126,35 +208,307 @@ impl StratEngine {
             return Err(StratisError::Engine(ErrorEnum::Error, err_msg));
         }
 
+        for target in CORE_TARGETS {
+            require_target(target)?;
+        }
+        for &cache_type in &[CacheType::Cache, CacheType::WriteCache] {
+            if let Err(err) = require_target(cache_target(cache_type)) {
+                warn!("caches of type {} can not be made: {}", cache_type, err);
+            }
+        }
+
+        let capabilities = probe_capabilities(dm);
+        info!(
+            "optional capabilities available: {}",
+            capabilities
+                .iter()
+                .map(|capability| capability.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
         devlinks::setup_dev_path()?;
 
+        watchdog::start_monitor();
+
+        let mut repairs = repair_dm_names().unwrap_or_else(|err| {
+            warn!("unable to repair DM device names, reason: {}", err);
+            Vec::new()
+        });
+
         let pools = find_all()?;
 
         let mut table = Table::default();
         let mut incomplete_pools = HashMap::new();
         for (pool_uuid, devices) in pools {
             match setup_pool(pool_uuid, &devices, &table) {
-                Ok((pool_name, pool)) => {
+                Ok((pool_name, pool, pool_repairs)) => {
                     table.insert(pool_name, pool_uuid, pool);
+                    repairs.extend(pool_repairs);
                 }
                 Err(err) => {
-                    warn!("no pool set up, reason: {:?}", err);
+                    warn!(
+                        "no pool set up from devnodes {:?}, reason: {}",
+                        devices.values().collect::<Vec<_>>(),
+                        err
+                    );
                     incomplete_pools.insert(pool_uuid, devices);
                 }
             }
         }
 
-        let engine = StratEngine {
+        let mut engine = StratEngine {
             pools: table,
             incomplete_pools,
             watched_dev_last_event_nrs: HashMap::new(),
+            metadata_layout: MetadataLayout::default(),
+            repairs,
+            capabilities,
+            undo_destroy_window: DEFAULT_UNDO_DESTROY_WINDOW_SECS,
         };
 
-        devlinks::cleanup_devlinks(engine.pools().iter());
+        engine.unlock_with_bindings();
+        engine.recover_jobs();
+
+        let devlink_repairs = devlinks::cleanup_devlinks(engine.pools().iter());
+        engine
+            .repairs
+            .extend(devlink_repairs.into_iter().map(Repair::Devlink));
+        count_by(Counter::Repairs, engine.repairs.len() as u64);
+        if !engine.repairs.is_empty() {
+            info!(
+                "made {} repairs while setting up pools: {:?}",
+                engine.repairs.len(),
+                engine.repairs
+            );
+        }
 
         Ok(engine)
     }
 
+    /// Resume, or roll back, each job recorded in the journal whose pool is
+    /// set up. The record of a job on a pool that is not set up is kept
+    /// until the pool is. The record of any other job is removed once the
+    /// job has been recovered, or could not be.
+    fn recover_jobs(&mut self) {
+        let dir = Path::new(JOURNAL_DIR);
+        let records = match list_jobs(dir) {
+            Ok(records) => records,
+            Err(err) => {
+                warn!("unable to read the records of jobs, reason: {}", err);
+                return;
+            }
+        };
+        for record in records {
+            let (pool_name, pool) = match self.pools.get_mut_by_uuid(record.pool_uuid) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            info!(
+                "recovering job {:?} on pool {}, cut short with {:?} of its data moved",
+                record.kind, pool_name, record.progress
+            );
+            if let Err(err) = pool.recover_job(record.pool_uuid, &pool_name, &record) {
+                warn!(
+                    "unable to recover job {} on pool {}, reason: {}",
+                    record.id, pool_name, err
+                );
+            }
+            if let Err(err) = forget_job(dir, record.id) {
+                warn!("unable to remove record of job {}, reason: {}", record.id, err);
+            }
+        }
+    }
+
+    /// Unlock the encrypted pool designated by pool_uuid by opening each of
+    /// its locked devices with open, and set up the pool on the dm-crypt
+    /// devices opened. If any device can not be opened, those already
+    /// opened are closed again.
+    /// Returns true if the pool was set up, false if it was already set up
+    /// or could not yet be set up.
+    fn open_locked<F>(&mut self, pool_uuid: PoolUuid, open: F) -> StratisResult<bool>
+    where
+        F: Fn(&Path, Uuid) -> StratisResult<PathBuf>,
+    {
+        if self.pools.contains_uuid(pool_uuid) {
+            return Ok(false);
+        }
+
+        let locked = match find_locked()?.remove(&pool_uuid) {
+            Some(locked) => locked,
+            None => {
+                let err_msg = format!("no locked devices found belonging to pool {}", pool_uuid);
+                return Err(StratisError::Engine(ErrorEnum::NotFound, err_msg));
+            }
+        };
+
+        let mut opened = Vec::new();
+        let mut result = Ok(());
+        for &(ref physical, luks_uuid) in &locked {
+            match open(physical, luks_uuid) {
+                Ok(devnode) => opened.push(devnode),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        if let Err(err) = result {
+            for devnode in &opened {
+                let _ = close_crypt(&Cryptsetup, devnode);
+            }
+            return Err(err);
+        }
+
+        let mut devices = self.incomplete_pools
+            .remove(&pool_uuid)
+            .unwrap_or_else(HashMap::new);
+        for devnode in opened {
+            if let Some(devno) = devnode_to_devno(&devnode)? {
+                devices.insert(Device::from(devno), devnode);
+            }
+        }
+
+        match setup_pool(pool_uuid, &devices, &self.pools) {
+            Ok((pool_name, pool, repairs)) => {
+                record_pool_change(pool_uuid, &pool, ChangeKind::Created);
+                self.pools.insert(pool_name, pool_uuid, pool);
+                self.repairs.extend(repairs);
+                Ok(true)
+            }
+            Err(err) => {
+                warn!(
+                    "no pool set up from devnodes {:?} after unlocking it, reason: {}",
+                    devices.values().collect::<Vec<_>>(),
+                    err
+                );
+                self.incomplete_pools.insert(pool_uuid, devices);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Unlock each locked pool by the means bound to it that need no
+    /// passphrase to be given: first with its key, if one is kept in the
+    /// keyring, and, if that does not unlock it, with its Clevis bindings.
+    fn unlock_with_bindings(&mut self) {
+        let locked = match self.locked_pools() {
+            Ok(locked) => locked,
+            Err(err) => {
+                warn!("unable to find locked pools, reason: {}", err);
+                return;
+            }
+        };
+        for pool_uuid in locked {
+            match read_key(pool_uuid) {
+                Ok(Some(passphrase)) => match self.unlock_pool(pool_uuid, &passphrase) {
+                    Ok(true) => {
+                        info!("unlocked pool {} with its key", pool_uuid);
+                        continue;
+                    }
+                    Ok(false) => continue,
+                    Err(err) => warn!(
+                        "unable to unlock pool {} with its key, reason: {}",
+                        pool_uuid, err
+                    ),
+                },
+                Ok(None) => (),
+                Err(err) => warn!("unable to read the key of pool {}, reason: {}", pool_uuid, err),
+            }
+
+            match find_bindings(&Cryptsetup, pool_uuid) {
+                Ok(Some(ref bindings)) if !bindings.clevis_pins.is_empty() => (),
+                Ok(_) => continue,
+                Err(err) => {
+                    warn!("unable to read the bindings of pool {}, reason: {}", pool_uuid, err);
+                    continue;
+                }
+            }
+            let opened = self.open_locked(pool_uuid, |physical, luks_uuid| {
+                open_crypt_clevis(&Cryptsetup, pool_uuid, physical, luks_uuid)?.ok_or_else(|| {
+                    StratisError::Engine(
+                        ErrorEnum::NotFound,
+                        format!("no Clevis binding of {} opens it", physical.display()),
+                    )
+                })
+            });
+            match opened {
+                Ok(true) => info!("unlocked pool {} with its Clevis bindings", pool_uuid),
+                Ok(false) => (),
+                Err(err) => warn!(
+                    "unable to unlock pool {} with its Clevis bindings, reason: {}",
+                    pool_uuid, err
+                ),
+            }
+        }
+    }
+
+    /// Set whether the devices of pools created from now on keep an
+    /// additional copy of their sigblock at the end of the device.
+    /// Existing pools are unaffected; devices added to an existing pool
+    /// follow that pool's policy.
+    pub fn set_tail_sigblocks(&mut self, enabled: bool) {
+        self.metadata_layout.tail_copy = enabled;
+    }
+
+    /// Set whether the devices of pools created from now on place the
+    /// backup copies of their metadata far enough from the primary copies
+    /// that the two are not in the same erase block of a flash device.
+    /// Existing pools are unaffected; devices added to an existing pool
+    /// follow that pool's layout.
+    pub fn set_spread_metadata(&mut self, enabled: bool) {
+        self.metadata_layout.spread = enabled;
+    }
+
+    /// Set whether pools' metadata is saved even if the clock reads earlier
+    /// than the time of metadata written in an earlier run, stamped just
+    /// after that time. If not, such saves fail until the clock catches
+    /// up. This applies to all pools.
+    pub fn set_metadata_time_fallback(&mut self, enabled: bool) {
+        set_metadata_time_fallback(enabled)
+    }
+
+    /// Set the least number of seconds between background writes of
+    /// metadata, such as those made when a pool's devices are extended, to
+    /// any one blockdev. If zero, background writes are not throttled.
+    /// A write that comes too soon is held back until the pools' next
+    /// housekeeping after the interval has elapsed.
+    /// This applies to all pools.
+    pub fn set_metadata_write_interval(&mut self, seconds: u64) {
+        set_metadata_write_interval(seconds)
+    }
+
+    /// Set how long after a pool was destroyed with its signatures kept,
+    /// in seconds, its destruction may be undone.
+    pub fn set_undo_destroy_window(&mut self, seconds: u64) {
+        self.undo_destroy_window = seconds;
+    }
+
+    /// Reconstruct a pool from the metadata backup in the file at
+    /// backup_path onto fresh devices, as reconstruct_pool() does.
+    /// Returns the UUID of the reconstructed pool.
+    pub fn reconstruct_pool_from_file(
+        &mut self,
+        backup_path: &Path,
+        blockdev_paths: &[&Path],
+    ) -> StratisResult<PoolUuid> {
+        let backup = read_backup(&mut File::open(backup_path)?)?;
+        self.insert_reconstructed(&backup, blockdev_paths)
+    }
+
+    fn insert_reconstructed(
+        &mut self,
+        backup: &MetadataBackup,
+        blockdev_paths: &[&Path],
+    ) -> StratisResult<PoolUuid> {
+        let (pool_name, pool_uuid, pool, repairs) =
+            reconstruct_pool(backup, blockdev_paths, &self.pools)?;
+        record_pool_change(pool_uuid, &pool, ChangeKind::Created);
+        self.pools.insert(pool_name, pool_uuid, pool);
+        self.repairs.extend(repairs);
+        Ok(pool_uuid)
+    }
+
     /// Teardown Stratis, preparatory to a shutdown.
     #[cfg(test)]
     pub fn teardown(self) -> StratisResult<()> {
@@ -168,19 +522,84 @@ impl Engine for StratEngine {
         name: &str,
         blockdev_paths: &[&Path],
         redundancy: Option<u16>,
+        size_policy: Option<u16>,
+        signature_policy: Option<u16>,
+        created_by: Option<Creator>,
+    ) -> StratisResult<PoolUuid> {
+        let redundancy = calculate_redundancy!(redundancy);
+        let size_policy = calculate_size_policy!(size_policy);
+        let signature_policy = calculate_signature_policy!(signature_policy);
+
+        validate_name(name)?;
+
+        if self.pools.contains_name(name) {
+            return Err(StratisError::Structured(
+                ErrorEnum::AlreadyExists,
+                ErrorPayload::new("PoolNameExists").param("name", name),
+            ));
+        }
+
+        let (uuid, pool) = StratPool::initialize(
+            name,
+            blockdev_paths,
+            redundancy,
+            size_policy,
+            self.metadata_layout,
+            signature_policy,
+            created_by,
+        )?;
+
+        let name = Name::new(name.to_owned());
+        devlinks::pool_added(&name);
+        record_pool_change(uuid, &pool, ChangeKind::Created);
+        self.pools.insert(name, uuid, pool);
+        Ok(uuid)
+    }
+
+    fn create_encrypted_pool(
+        &mut self,
+        name: &str,
+        blockdev_paths: &[&Path],
+        redundancy: Option<u16>,
+        size_policy: Option<u16>,
+        signature_policy: Option<u16>,
+        passphrase: &str,
+        created_by: Option<Creator>,
     ) -> StratisResult<PoolUuid> {
         let redundancy = calculate_redundancy!(redundancy);
+        let size_policy = calculate_size_policy!(size_policy);
+        let signature_policy = calculate_signature_policy!(signature_policy);
 
         validate_name(name)?;
 
+        if passphrase.is_empty() {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                "the passphrase must not be empty".into(),
+            ));
+        }
+
         if self.pools.contains_name(name) {
-            return Err(StratisError::Engine(ErrorEnum::AlreadyExists, name.into()));
+            return Err(StratisError::Structured(
+                ErrorEnum::AlreadyExists,
+                ErrorPayload::new("PoolNameExists").param("name", name),
+            ));
         }
 
-        let (uuid, pool) = StratPool::initialize(name, blockdev_paths, redundancy)?;
+        let (uuid, pool) = StratPool::initialize_encrypted(
+            name,
+            blockdev_paths,
+            redundancy,
+            size_policy,
+            self.metadata_layout,
+            signature_policy,
+            passphrase,
+            created_by,
+        )?;
 
         let name = Name::new(name.to_owned());
         devlinks::pool_added(&name);
+        record_pool_change(uuid, &pool, ChangeKind::Created);
         self.pools.insert(name, uuid, pool);
         Ok(uuid)
     }
@@ -241,12 +660,19 @@ impl Engine for StratEngine {
                     .expect("We just retrieved or created a HashMap");
                 devices.insert(device, dev_node);
                 match setup_pool(pool_uuid, &devices, &self.pools) {
-                    Ok((pool_name, pool)) => {
+                    Ok((pool_name, pool, repairs)) => {
+                        record_pool_change(pool_uuid, &pool, ChangeKind::Created);
                         self.pools.insert(pool_name, pool_uuid, pool);
+                        self.repairs.extend(repairs);
+                        self.recover_jobs();
                         Some(pool_uuid)
                     }
                     Err(err) => {
-                        warn!("no pool set up, reason: {:?}", err);
+                        warn!(
+                            "no pool set up from devnodes {:?}, reason: {}",
+                            devices.values().collect::<Vec<_>>(),
+                            err
+                        );
                         self.incomplete_pools.insert(pool_uuid, devices);
                         None
                     }
@@ -258,12 +684,12 @@ impl Engine for StratEngine {
         Ok(pool_uuid)
     }
 
-    fn destroy_pool(&mut self, uuid: PoolUuid) -> StratisResult<bool> {
+    fn destroy_pool(&mut self, uuid: PoolUuid, keep_signatures: bool) -> StratisResult<bool> {
         if let Some((_, pool)) = self.pools.get_by_uuid(uuid) {
             if pool.has_filesystems() {
-                return Err(StratisError::Engine(
+                return Err(StratisError::Structured(
                     ErrorEnum::Busy,
-                    "filesystems remaining on pool".into(),
+                    ErrorPayload::new("PoolHasFilesystems"),
                 ));
             };
         } else {
@@ -274,15 +700,208 @@ impl Engine for StratEngine {
             .remove_by_uuid(uuid)
             .expect("Must succeed since self.pools.get_by_uuid() returned a value");
 
-        if let Err(err) = pool.destroy() {
+        if let Err(err) = pool.destroy(keep_signatures) {
             self.pools.insert(pool_name, uuid, pool);
             Err(err)
         } else {
             devlinks::pool_removed(&pool_name);
+            record_pool_change(uuid, &pool, ChangeKind::Deleted);
+            if let Err(err) = forget_pool_jobs(Path::new(JOURNAL_DIR), uuid) {
+                warn!("unable to remove records of jobs on pool {}, reason: {}", uuid, err);
+            }
             Ok(true)
         }
     }
 
+    fn reclaimable_devices(&self) -> StratisResult<Vec<ReclaimableDevice>> {
+        find_destroyed()
+    }
+
+    fn wipe_reclaimable_device(&mut self, devnode: &Path) -> StratisResult<bool> {
+        wipe_destroyed_device(devnode)
+    }
+
+    fn cloned_devices(&self) -> StratisResult<Vec<ClonedDevice>> {
+        let mut clones = Vec::new();
+        for (pool_uuid, devnodes) in find_all()? {
+            let members = match self.pools.get_by_uuid(pool_uuid) {
+                Some((_, pool)) => pool.blockdevs()
+                    .iter()
+                    .map(|&(_, blockdev)| blockdev.devnode())
+                    .collect(),
+                None => HashSet::new(),
+            };
+            clones.extend(find_clones(pool_uuid, &devnodes, &members).with_pool(pool_uuid)?);
+        }
+        Ok(clones)
+    }
+
+    fn undo_destroy_pool(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool> {
+        if self.pools.contains_uuid(pool_uuid) {
+            let err_msg = format!("pool {} is set up and was not destroyed", pool_uuid);
+            return Err(StratisError::Engine(ErrorEnum::Busy, err_msg));
+        }
+
+        let reclaimable: Vec<ReclaimableDevice> = find_destroyed()?
+            .into_iter()
+            .filter(|dev| dev.pool_uuid == pool_uuid)
+            .collect();
+        let destroyed = match reclaimable.iter().map(|dev| dev.destroyed).min() {
+            Some(destroyed) => destroyed,
+            None => {
+                let err_msg = format!("no devices found belonging to destroyed pool {}", pool_uuid);
+                return Err(StratisError::Engine(ErrorEnum::NotFound, err_msg));
+            }
+        };
+        if Utc::now() - destroyed > Duration::seconds(self.undo_destroy_window as i64) {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("UndoWindowElapsed")
+                    .param("pool", pool_uuid)
+                    .param("destroyed", destroyed.to_rfc3339()),
+            ));
+        }
+
+        let generation = match list_backups(Path::new(BACKUP_DIR), pool_uuid)?
+            .into_iter()
+            .max()
+        {
+            Some(generation) => generation,
+            None => {
+                let err_msg = format!("no metadata backup of pool {}", pool_uuid);
+                return Err(StratisError::Engine(ErrorEnum::NotFound, err_msg));
+            }
+        };
+        let backup = load_backup(Path::new(BACKUP_DIR), pool_uuid, generation)?;
+        let metadata: PoolSave = serde_json::from_str(&backup.metadata)?;
+        check_metadata(&metadata).with_pool(pool_uuid)?;
+
+        // Every blockdev recorded in the metadata must still be marked as
+        // one of the destroyed pool's.
+        let backstore = &metadata.backstore;
+        let mut devices = HashMap::new();
+        for bd_save in backstore.data_tier.blockdev.devs.iter().chain(
+            backstore
+                .cache_tier
+                .iter()
+                .flat_map(|tier| tier.blockdev.devs.iter()),
+        ) {
+            let found = match reclaimable.iter().find(|dev| dev.dev_uuid == bd_save.uuid) {
+                Some(dev) => devnode_to_devno(&dev.devnode)?.map(|devno| (devno, &dev.devnode)),
+                None => None,
+            };
+            match found {
+                Some((devno, devnode)) => {
+                    devices.insert(Device::from(devno), devnode.clone());
+                }
+                None => {
+                    return Err(StratisError::Structured(
+                        ErrorEnum::NotFound,
+                        ErrorPayload::new("DestroyedDeviceMissing")
+                            .param("dev", bd_save.uuid)
+                            .param("pool", pool_uuid),
+                    ));
+                }
+            }
+        }
+
+        restore_metadata(pool_uuid, &devices, backup.metadata.as_bytes())
+            .and_then(|_| revive_blockdevs(pool_uuid, &devices))
+            .with_pool(pool_uuid)?;
+
+        match setup_pool(pool_uuid, &devices, &self.pools) {
+            Ok((pool_name, pool, repairs)) => {
+                record_pool_change(pool_uuid, &pool, ChangeKind::Created);
+                self.pools.insert(pool_name, pool_uuid, pool);
+                self.repairs.extend(repairs);
+                Ok(true)
+            }
+            Err(err) => {
+                warn!(
+                    "no pool set up from devnodes {:?} after undoing its destruction, reason: {}",
+                    devices.values().collect::<Vec<_>>(),
+                    err
+                );
+                self.incomplete_pools.insert(pool_uuid, devices);
+                Ok(false)
+            }
+        }
+    }
+
+    fn unlock_pool(&mut self, pool_uuid: PoolUuid, passphrase: &str) -> StratisResult<bool> {
+        self.open_locked(pool_uuid, |physical, luks_uuid| {
+            open_crypt(&Cryptsetup, pool_uuid, physical, luks_uuid, passphrase)?.ok_or_else(|| {
+                StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("PassphraseIncorrect").param("pool", pool_uuid),
+                )
+            })
+        })
+    }
+
+    fn bind_tang(
+        &mut self,
+        pool_uuid: PoolUuid,
+        passphrase: &str,
+        url: &str,
+        thumbprint: &str,
+    ) -> StratisResult<bool> {
+        validate_tang(url, thumbprint)?;
+        let changed = bind_pool_tang(&Cryptsetup, pool_uuid, passphrase, url, thumbprint)?;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn unbind_tang(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool> {
+        let changed = unbind_pool_tang(&Cryptsetup, pool_uuid)?;
+        if changed {
+            record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn locked_pools(&self) -> StratisResult<Vec<PoolUuid>> {
+        Ok(find_locked()?
+            .into_iter()
+            .map(|(pool_uuid, _)| pool_uuid)
+            .filter(|&pool_uuid| !self.pools.contains_uuid(pool_uuid))
+            .collect())
+    }
+
+    fn set_key(&mut self, pool_uuid: PoolUuid, passphrase: &str) -> StratisResult<bool> {
+        if passphrase.is_empty() {
+            return Err(StratisError::Engine(
+                ErrorEnum::Invalid,
+                "the passphrase must not be empty".into(),
+            ));
+        }
+        set_key(pool_uuid, passphrase)
+    }
+
+    fn unset_key(&mut self, pool_uuid: PoolUuid) -> StratisResult<bool> {
+        unset_key(pool_uuid)
+    }
+
+    fn keys(&self) -> StratisResult<Vec<PoolUuid>> {
+        list_keys()
+    }
+
+    fn key_description(&self, pool_uuid: PoolUuid) -> StratisResult<Option<String>> {
+        kept_key_description(pool_uuid)
+    }
+
+    fn bindings(&self, pool_uuid: PoolUuid) -> StratisResult<Option<PoolBindings>> {
+        match find_bindings(&Cryptsetup, pool_uuid)? {
+            Some(bindings) => Ok(Some(PoolBindings {
+                key_description: kept_key_description(pool_uuid)?,
+                ..bindings
+            })),
+            None => Ok(None),
+        }
+    }
+
     fn rename_pool(&mut self, uuid: PoolUuid, new_name: &str) -> StratisResult<RenameAction> {
         validate_name(new_name)?;
         let old_name = rename_pool_pre!(self; uuid; new_name);
@@ -304,6 +923,7 @@ impl Engine for StratEngine {
 
             self.pools.insert(new_name.clone(), uuid, pool);
             devlinks::pool_renamed(&old_name, &new_name);
+            record_change(ObjectKind::Pool, uuid, ChangeKind::Modified);
             Ok(RenameAction::Renamed)
         }
     }
@@ -320,6 +940,20 @@ impl Engine for StratEngine {
         Ok(()) // we're not the simulator and not configurable, so just say ok
     }
 
+    fn configure_simulator_capacities(
+        &mut self,
+        _device_size: Option<Sectors>,
+        _filesystem_size: Option<Sectors>,
+        _initial_used: Option<Bytes>,
+        _growth: Option<Bytes>,
+    ) -> StratisResult<()> {
+        Ok(())
+    }
+
+    fn advance_simulator_clock(&mut self, _seconds: u64) -> StratisResult<()> {
+        Ok(())
+    }
+
     fn pools(&self) -> Vec<(Name, PoolUuid, &Pool)> {
         self.pools
             .iter()
@@ -334,6 +968,93 @@ impl Engine for StratEngine {
             .collect()
     }
 
+    fn metadata_backups(&self, pool_uuid: PoolUuid) -> StratisResult<Vec<(u64, DateTime<Utc>)>> {
+        let dir = Path::new(BACKUP_DIR);
+        list_backups(dir, pool_uuid)?
+            .into_iter()
+            .map(|generation| {
+                load_backup(dir, pool_uuid, generation)
+                    .and_then(|backup| backup.time())
+                    .map(|time| (generation, time))
+            })
+            .collect()
+    }
+
+    fn restore_metadata_backup(
+        &mut self,
+        pool_uuid: PoolUuid,
+        generation: u64,
+    ) -> StratisResult<bool> {
+        if self.pools.contains_uuid(pool_uuid) {
+            let err_msg = format!("pool {} is set up and can not be restored", pool_uuid);
+            return Err(StratisError::Engine(ErrorEnum::Busy, err_msg));
+        }
+
+        let devices = match self.incomplete_pools.remove(&pool_uuid) {
+            Some(devices) => devices,
+            None => {
+                let err_msg = format!("no devices found belonging to pool {}", pool_uuid);
+                return Err(StratisError::Engine(ErrorEnum::NotFound, err_msg));
+            }
+        };
+
+        // Check the backup before overwriting anything with it.
+        let restored = load_backup(Path::new(BACKUP_DIR), pool_uuid, generation)
+            .and_then(|backup| {
+                let metadata: PoolSave = serde_json::from_str(&backup.metadata)?;
+                check_metadata(&metadata)?;
+                Ok(backup.metadata)
+            })
+            .and_then(|metadata| restore_metadata(pool_uuid, &devices, metadata.as_bytes()));
+        if let Err(err) = restored {
+            self.incomplete_pools.insert(pool_uuid, devices);
+            return Err(err.with_pool(pool_uuid));
+        }
+
+        match setup_pool(pool_uuid, &devices, &self.pools) {
+            Ok((pool_name, pool, repairs)) => {
+                record_pool_change(pool_uuid, &pool, ChangeKind::Created);
+                self.pools.insert(pool_name, pool_uuid, pool);
+                self.repairs.extend(repairs);
+                Ok(true)
+            }
+            Err(err) => {
+                warn!(
+                    "no pool set up from devnodes {:?} after restoring metadata, reason: {}",
+                    devices.values().collect::<Vec<_>>(),
+                    err
+                );
+                self.incomplete_pools.insert(pool_uuid, devices);
+                Ok(false)
+            }
+        }
+    }
+
+    fn reconstruct_pool(
+        &mut self,
+        pool_uuid: PoolUuid,
+        generation: u64,
+        blockdev_paths: &[&Path],
+    ) -> StratisResult<PoolUuid> {
+        // Reconstruction is for a pool that is lost; refuse to make a
+        // second copy of a pool that is running.
+        if self.pools.contains_uuid(pool_uuid) {
+            let err_msg = format!("pool {} is set up and can not be reconstructed", pool_uuid);
+            return Err(StratisError::Engine(ErrorEnum::Busy, err_msg));
+        }
+
+        let backup = load_backup(Path::new(BACKUP_DIR), pool_uuid, generation)?;
+        self.insert_reconstructed(&backup, blockdev_paths)
+    }
+
+    fn capabilities(&self) -> Vec<Capability> {
+        self.capabilities.clone()
+    }
+
+    fn is_simulator(&self) -> bool {
+        false
+    }
+
     fn get_eventable(&self) -> Option<&'static Eventable> {
         Some(get_dm())
     }
@@ -366,10 +1087,15 @@ impl Engine for StratEngine {
 
 #[cfg(test)]
 mod test {
-    use std::fs::remove_dir_all;
+    use std::fs::{remove_dir_all, remove_file};
+    use std::os::unix::fs::symlink;
+
+    use devicemapper::{DevId, DmName};
 
     use engine::engine::DEV_PATH;
 
+    use super::super::super::devlinks::DevlinkRepair;
+    use super::super::names::{format_thin_ids, ThinRole};
     use super::super::tests::{loopbacked, real};
 
     use super::*;
@@ -379,7 +1105,7 @@ mod test {
         let mut engine = StratEngine::initialize().unwrap();
 
         let name1 = "name1";
-        let uuid1 = engine.create_pool(&name1, paths, None).unwrap();
+        let uuid1 = engine.create_pool(&name1, paths, None, None, None, None).unwrap();
 
         let name2 = "name2";
         let action = engine.rename_pool(uuid1, name2).unwrap();
@@ -422,10 +1148,10 @@ mod test {
         let mut engine = StratEngine::initialize().unwrap();
 
         let name1 = "name1";
-        let uuid1 = engine.create_pool(&name1, paths1, None).unwrap();
+        let uuid1 = engine.create_pool(&name1, paths1, None, None, None, None).unwrap();
 
         let name2 = "name2";
-        let uuid2 = engine.create_pool(&name2, paths2, None).unwrap();
+        let uuid2 = engine.create_pool(&name2, paths2, None, None, None, None).unwrap();
 
         assert!(engine.get_pool(uuid1).is_some());
         assert!(engine.get_pool(uuid2).is_some());
@@ -458,4 +1184,62 @@ mod test {
     pub fn real_test_setup() {
         real::test_with_spec(real::DeviceLimits::AtLeast(2, None, None), test_setup);
     }
+
+    /// Verify that a filesystem's DM device left with a name other than
+    /// its UUID, and a devlink pointing to the wrong device, are both
+    /// repaired when the engine is set up again, and that the repairs are
+    /// recorded.
+    fn test_startup_repair(paths: &[&Path]) {
+        let mut engine = StratEngine::initialize().unwrap();
+
+        let pool_name = "pool_name";
+        let uuid = engine.create_pool(pool_name, paths, None, None, None, None).unwrap();
+        let fs_name = "fs_name";
+        let fs_uuid = {
+            let pool = engine.get_mut_pool(uuid).unwrap().1;
+            pool.create_filesystems(uuid, pool_name, &[(fs_name, None, false)], None)
+                .unwrap()[0]
+                .1
+        };
+
+        let (dm_name, _) = format_thin_ids(uuid, ThinRole::Filesystem(fs_uuid));
+        let wrong_name = DmName::new("stratis-test-wrong-name").unwrap();
+        get_dm().device_rename(&dm_name, &DevId::Name(wrong_name)).unwrap();
+
+        let link = devlinks::filesystem_mount_path(pool_name, fs_name);
+        remove_file(&link).unwrap();
+        symlink("/dev/null", &link).unwrap();
+
+        let engine = StratEngine::initialize().unwrap();
+        assert!(engine.get_pool(uuid).is_some());
+        assert!(engine.repairs.iter().any(|repair| match *repair {
+            Repair::DmDeviceRenamed {
+                ref old_name,
+                ref new_name,
+            } => old_name.to_string() == wrong_name.to_string() && *new_name == dm_name,
+            _ => false,
+        }));
+        assert!(engine.repairs.iter().any(|repair| match *repair {
+            Repair::Devlink(DevlinkRepair::Replaced(ref path)) => *path == link,
+            _ => false,
+        }));
+
+        engine.teardown().unwrap();
+    }
+
+    #[test]
+    pub fn loop_test_startup_repair() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_startup_repair,
+        );
+    }
+
+    #[test]
+    pub fn real_test_startup_repair() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(1, None, None),
+            test_startup_repair,
+        );
+    }
 }
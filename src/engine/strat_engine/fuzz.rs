@@ -0,0 +1,18 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Entry points for fuzzing the parsers of metadata read from devices. Each
+// takes arbitrary bytes, parses them, and discards the result; a parser is
+// at fault only if it panics. The fuzz targets in fuzz/ call these.
+
+use serde_json;
+
+use super::serde_structs::PoolSave;
+
+pub use super::backstore::{fuzz_mda_header, fuzz_sigblock};
+
+/// Parse buf as the pool metadata saved in an MDA region.
+pub fn fuzz_pool_metadata(buf: &[u8]) {
+    let _ = serde_json::from_slice::<PoolSave>(buf);
+}
@@ -0,0 +1,241 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Records of the long-running jobs done on pools, kept in files outside the
+// pools while the jobs run. A job cut short by a crash or a restart of
+// stratisd leaves its record behind, from which the job is resumed, or
+// rolled back, once its pool is set up again. A pool's metadata always
+// records where its data lies, so a record need only say what was being
+// done, and how far it had got.
+
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde_json;
+use uuid::Uuid;
+
+use devicemapper::Sectors;
+
+use stratis::StratisResult;
+
+use super::super::types::{DevUuid, PoolUuid};
+
+/// The directory in which the records of running jobs are kept, one file
+/// for each job.
+pub const JOURNAL_DIR: &str = "/var/lib/stratisd/jobs";
+
+/// What a job does.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum JobKind {
+    /// Initialize the devices at paths, and add them to the pool.
+    InitializeDevices { paths: Vec<PathBuf> },
+    /// Move the data off the blockdevs, and remove them from the pool.
+    RemoveBlockDevs { uuids: Vec<DevUuid> },
+    /// Replace the blockdev old by the device at new.
+    ReplaceBlockDev {
+        old: DevUuid,
+        new: PathBuf,
+        signature_policy: Option<u16>,
+    },
+}
+
+/// The record of a job, as kept in the journal.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct JobRecord {
+    pub id: Uuid,
+    pub pool_uuid: PoolUuid,
+    /// The time the job began, in RFC 3339 format.
+    pub started: String,
+    pub kind: JobKind,
+    /// The data moved so far, and the data to move in all, for a job which
+    /// moves data, once it has begun to.
+    pub progress: Option<(Sectors, Sectors)>,
+}
+
+fn record_path(dir: &Path, id: Uuid) -> PathBuf {
+    dir.join(format!("{}.json", id.simple()))
+}
+
+/// Write the record to dir, replacing any earlier record of the job.
+pub fn save_job(dir: &Path, record: &JobRecord) -> StratisResult<()> {
+    fs::create_dir_all(dir)?;
+
+    // Write to a temporary file first, so that a record is never found
+    // half-written.
+    let path = record_path(dir, record.id);
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut f = File::create(&tmp_path)?;
+        f.write_all(serde_json::to_string(record)?.as_bytes())?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// Remove the record of the job from dir, if there is one.
+pub fn forget_job(dir: &Path, id: Uuid) -> StratisResult<()> {
+    match fs::remove_file(record_path(dir, id)) {
+        Ok(_) => Ok(()),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn read_job(path: &Path) -> StratisResult<JobRecord> {
+    let mut data = String::new();
+    File::open(path)?.read_to_string(&mut data)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// The records kept in dir, those of the jobs begun earliest first. A
+/// record that can not be read is left where it is, and warned of.
+pub fn list_jobs(dir: &Path) -> StratisResult<Vec<JobRecord>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut records = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |ext| ext != "json") {
+            continue;
+        }
+        match read_job(&path) {
+            Ok(record) => records.push(record),
+            Err(err) => warn!("unable to read job record {}: {}", path.display(), err),
+        }
+    }
+    records.sort_by(|a, b| a.started.cmp(&b.started));
+    Ok(records)
+}
+
+/// Remove the records of the jobs on the pool from dir, as when the pool
+/// is destroyed.
+pub fn forget_pool_jobs(dir: &Path, pool_uuid: PoolUuid) -> StratisResult<()> {
+    for record in list_jobs(dir)? {
+        if record.pool_uuid == pool_uuid {
+            forget_job(dir, record.id)?;
+        }
+    }
+    Ok(())
+}
+
+/// A job recorded in the journal while it runs. Like a metadata backup, the
+/// record is a convenience: a failure to write it is logged, and the job
+/// goes on, though it can then not be resumed if it is cut short.
+#[derive(Debug)]
+pub struct JournaledJob {
+    dir: PathBuf,
+    record: Option<JobRecord>,
+}
+
+impl JournaledJob {
+    /// Record in dir that the job of kind has begun on the pool.
+    pub fn begin(dir: &Path, pool_uuid: PoolUuid, kind: JobKind) -> JournaledJob {
+        let record = JobRecord {
+            id: Uuid::new_v4(),
+            pool_uuid,
+            started: Utc::now().to_rfc3339(),
+            kind,
+            progress: None,
+        };
+        let record = match save_job(dir, &record) {
+            Ok(_) => Some(record),
+            Err(err) => {
+                warn!(
+                    "unable to record job on pool {}, it can not be resumed if cut short: {}",
+                    pool_uuid, err
+                );
+                None
+            }
+        };
+        JournaledJob {
+            dir: dir.to_owned(),
+            record,
+        }
+    }
+
+    /// Record that moved of the total data to move has been moved.
+    pub fn checkpoint(&mut self, moved: Sectors, total: Sectors) {
+        if let Some(ref mut record) = self.record {
+            record.progress = Some((moved, total));
+            if let Err(err) = save_job(&self.dir, record) {
+                warn!("unable to record progress of job {}: {}", record.id, err);
+            }
+        }
+    }
+
+    /// Remove the record of the job, which is done, whether or not it
+    /// succeeded.
+    pub fn finish(self) {
+        if let Some(record) = self.record {
+            if let Err(err) = forget_job(&self.dir, record.id) {
+                warn!("unable to remove record of job {}: {}", record.id, err);
+            }
+        }
+    }
+}
+
+/// Do the job of kind on the pool, recorded in dir until job_fn returns.
+/// job_fn is given the job, so that it may record its progress.
+pub fn journaled<T, F>(
+    dir: &Path,
+    pool_uuid: PoolUuid,
+    kind: JobKind,
+    job_fn: F,
+) -> StratisResult<T>
+where
+    F: FnOnce(&mut JournaledJob) -> StratisResult<T>,
+{
+    let mut job = JournaledJob::begin(dir, pool_uuid, kind);
+    let result = job_fn(&mut job);
+    job.finish();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile;
+
+    use super::*;
+
+    #[test]
+    /// Verify that a job is recorded while it runs, with its progress, and
+    /// that its record is removed once it is finished.
+    fn test_journaled_job() {
+        let dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let pool_uuid = PoolUuid::new_v4();
+        let kind = JobKind::RemoveBlockDevs {
+            uuids: vec![DevUuid::new_v4()],
+        };
+
+        assert_eq!(list_jobs(&dir.path().join("absent")).unwrap(), vec![]);
+
+        let mut job = JournaledJob::begin(dir.path(), pool_uuid, kind.clone());
+        let records = list_jobs(dir.path()).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].pool_uuid, pool_uuid);
+        assert_eq!(records[0].kind, kind);
+        assert_eq!(records[0].progress, None);
+
+        job.checkpoint(Sectors(8), Sectors(32));
+        assert_eq!(
+            list_jobs(dir.path()).unwrap()[0].progress,
+            Some((Sectors(8), Sectors(32)))
+        );
+
+        File::create(dir.path().join("garbage.json"))
+            .unwrap()
+            .write_all(b"{")
+            .unwrap();
+        job.finish();
+        assert_eq!(list_jobs(dir.path()).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,227 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to keep the passphrases that unlock encrypted pools in the kernel
+// keyring, so that a pool can be unlocked when stratisd sets it up without
+// anyone to type its passphrase. Each passphrase is a key of type "user" in
+// the user keyring of root, which outlives stratisd, and is described by
+// the UUID of the pool that it unlocks.
+
+use std::ffi::CString;
+use std::io;
+
+use byteorder::{ByteOrder, NativeEndian};
+use nix::libc;
+
+use stratis::{ErrorEnum, StratisError, StratisResult};
+
+use super::super::types::PoolUuid;
+
+// From linux/keyctl.h
+const KEY_SPEC_USER_KEYRING: libc::c_long = -4;
+const KEYCTL_DESCRIBE: libc::c_long = 6;
+const KEYCTL_UNLINK: libc::c_long = 9;
+const KEYCTL_SEARCH: libc::c_long = 10;
+const KEYCTL_READ: libc::c_long = 11;
+
+/// The type of every key that holds a passphrase.
+const KEY_TYPE: &str = "user";
+
+/// The prefix of the description of every key that holds a passphrase.
+const DESCRIPTION_PREFIX: &str = "stratis-1-key-";
+
+/// The description of the key that holds the passphrase of the pool.
+fn key_description(pool_uuid: PoolUuid) -> String {
+    format!("{}{}", DESCRIPTION_PREFIX, pool_uuid.simple())
+}
+
+/// The pool whose passphrase is held by the key with the given
+/// description, as keyctl(2) describes a key, if it is such a key.
+fn pool_of_description(description: &str) -> Option<PoolUuid> {
+    // "<type>;<uid>;<gid>;<perm>;<description>"
+    let fields = description.splitn(5, ';').collect::<Vec<_>>();
+    if fields.len() != 5 || fields[0] != KEY_TYPE || !fields[4].starts_with(DESCRIPTION_PREFIX) {
+        return None;
+    }
+    PoolUuid::parse_str(&fields[4][DESCRIPTION_PREFIX.len()..]).ok()
+}
+
+fn c_string(value: &str) -> StratisResult<CString> {
+    CString::new(value).map_err(|_| {
+        StratisError::Engine(
+            ErrorEnum::Invalid,
+            format!("{:?} contains a NUL byte", value),
+        )
+    })
+}
+
+/// Read the payload of the key or keyring with the given serial number.
+fn read_payload(serial: libc::c_long) -> StratisResult<Vec<u8>> {
+    let mut buf: Vec<u8> = Vec::new();
+    loop {
+        let len = unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_READ,
+                serial,
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // The payload may have grown since its size was found.
+        if len as usize <= buf.len() {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+        buf.resize(len as usize, 0);
+    }
+}
+
+/// The serial number of the key that holds the passphrase of the pool, if
+/// there is one.
+fn find_key(pool_uuid: PoolUuid) -> StratisResult<Option<libc::c_long>> {
+    let key_type = c_string(KEY_TYPE)?;
+    let description = c_string(&key_description(pool_uuid))?;
+    let serial = unsafe {
+        libc::syscall(
+            libc::SYS_keyctl,
+            KEYCTL_SEARCH,
+            KEY_SPEC_USER_KEYRING,
+            key_type.as_ptr(),
+            description.as_ptr(),
+            0,
+        )
+    };
+    if serial < 0 {
+        let err = io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::ENOKEY) | Some(libc::EKEYREVOKED) | Some(libc::EKEYEXPIRED) => Ok(None),
+            _ => Err(err.into()),
+        };
+    }
+    Ok(Some(serial))
+}
+
+/// The passphrase of the pool, if the keyring holds one.
+pub fn read_key(pool_uuid: PoolUuid) -> StratisResult<Option<String>> {
+    match find_key(pool_uuid)? {
+        Some(serial) => {
+            let passphrase = String::from_utf8(read_payload(serial)?).map_err(|_| {
+                StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!("the key of pool {} is not a passphrase", pool_uuid),
+                )
+            })?;
+            Ok(Some(passphrase))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The description of the key that holds the passphrase of the pool, if
+/// the keyring holds one.
+pub fn kept_key_description(pool_uuid: PoolUuid) -> StratisResult<Option<String>> {
+    Ok(find_key(pool_uuid)?.map(|_| key_description(pool_uuid)))
+}
+
+/// Set the passphrase of the pool in the keyring, replacing any passphrase
+/// that it held before.
+/// Returns true if the passphrase held was changed.
+pub fn set_key(pool_uuid: PoolUuid, passphrase: &str) -> StratisResult<bool> {
+    if read_key(pool_uuid)?.as_ref().map(|p| p.as_str()) == Some(passphrase) {
+        return Ok(false);
+    }
+
+    let key_type = c_string(KEY_TYPE)?;
+    let description = c_string(&key_description(pool_uuid))?;
+    let serial = unsafe {
+        libc::syscall(
+            libc::SYS_add_key,
+            key_type.as_ptr(),
+            description.as_ptr(),
+            passphrase.as_ptr(),
+            passphrase.len(),
+            KEY_SPEC_USER_KEYRING,
+        )
+    };
+    if serial < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(true)
+}
+
+/// Remove the passphrase of the pool from the keyring.
+/// Returns true if the keyring held a passphrase of the pool.
+pub fn unset_key(pool_uuid: PoolUuid) -> StratisResult<bool> {
+    let serial = match find_key(pool_uuid)? {
+        Some(serial) => serial,
+        None => return Ok(false),
+    };
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_keyctl,
+            KEYCTL_UNLINK,
+            serial,
+            KEY_SPEC_USER_KEYRING,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(true)
+}
+
+/// The pools whose passphrases the keyring holds.
+pub fn list_keys() -> StratisResult<Vec<PoolUuid>> {
+    // The payload of a keyring is the serial number of each of its keys.
+    let payload = read_payload(KEY_SPEC_USER_KEYRING)?;
+    let mut pool_uuids = Vec::new();
+    for chunk in payload.chunks(4) {
+        let serial = libc::c_long::from(NativeEndian::read_i32(chunk));
+        let mut buf = [0u8; 256];
+        let len = unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_DESCRIBE,
+                serial,
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+        };
+        // A key that can not be described is not one of ours, or has just
+        // been removed.
+        if len <= 0 || len as usize > buf.len() {
+            continue;
+        }
+        // The description ends with a NUL byte.
+        let description = String::from_utf8_lossy(&buf[..len as usize - 1]);
+        if let Some(pool_uuid) = pool_of_description(&description) {
+            pool_uuids.push(pool_uuid);
+        }
+    }
+    Ok(pool_uuids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the pool is recovered from the description of its key,
+    /// and that no pool is found in the description of any other key.
+    fn test_pool_of_description() {
+        let pool_uuid = PoolUuid::new_v4();
+        let description = format!("user;0;0;3f010000;{}", key_description(pool_uuid));
+        assert_eq!(pool_of_description(&description), Some(pool_uuid));
+        assert_eq!(
+            pool_of_description(&format!("logon;0;0;3f010000;{}", key_description(pool_uuid))),
+            None
+        );
+        assert_eq!(pool_of_description("user;0;0;3f010000;home"), None);
+        assert_eq!(pool_of_description("user;0;0"), None);
+    }
+}
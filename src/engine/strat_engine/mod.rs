@@ -3,18 +3,36 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 mod backstore;
+mod backup;
+mod capabilities;
 #[cfg(test)]
 mod cleanup;
 mod cmd;
+mod crypt;
 mod device;
 mod dm;
 mod engine;
+mod journal;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+mod keys;
 mod names;
 mod pool;
+mod priority;
+mod repair;
+mod replay;
 mod serde_structs;
+mod targets;
 mod thinpool;
 mod throttle;
+mod verify;
+mod watchdog;
 
+pub use self::backstore::probe_device;
+pub use self::crypt::{
+    bind_devices_tang, first_free, header_bindings, initialize_crypt, unbind_devices_tang,
+    CryptOps, LUKS2_KEYSLOTS, LUKS2_TOKENS,
+};
 pub use self::engine::StratEngine;
 pub use self::throttle::set_write_throttling;
 
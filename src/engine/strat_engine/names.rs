@@ -8,9 +8,10 @@ use std::fmt;
 use std::fmt::Display;
 use std::path::Path;
 
-use devicemapper::{DmNameBuf, DmUuidBuf};
+use devicemapper::{DmName, DmNameBuf, DmUuidBuf};
+use uuid::Uuid;
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
 
 use super::super::super::engine::{FilesystemUuid, PoolUuid};
 
@@ -75,6 +76,9 @@ pub enum CacheRole {
     MetaSub,
     /// The origin sub-device of the DM cache device, holds the actual data.
     OriginSub,
+    /// The cap device, which maps all of the cache device, or of the origin
+    /// sub-device if there is no cache.
+    Cap,
 }
 
 impl Display for CacheRole {
@@ -84,6 +88,29 @@ impl Display for CacheRole {
             CacheRole::CacheSub => write!(f, "cachesub"),
             CacheRole::MetaSub => write!(f, "metasub"),
             CacheRole::OriginSub => write!(f, "originsub"),
+            CacheRole::Cap => write!(f, "cap"),
+        }
+    }
+}
+
+/// The roles taken on by DM devices in the RAID layer of a pool with
+/// redundancy.
+#[derive(Clone, Copy)]
+pub enum RaidRole {
+    /// The DM raid device, made of the sub-devices of each member.
+    Raid,
+    /// The meta sub-device of the member with the given index.
+    MetaSub(usize),
+    /// The data sub-device of the member with the given index.
+    DataSub(usize),
+}
+
+impl Display for RaidRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RaidRole::Raid => write!(f, "raid"),
+            RaidRole::MetaSub(index) => write!(f, "meta-{}", index),
+            RaidRole::DataSub(index) => write!(f, "data-{}", index),
         }
     }
 }
@@ -187,52 +214,107 @@ pub fn format_backstore_ids(pool_uuid: PoolUuid, role: CacheRole) -> (DmNameBuf,
     )
 }
 
+/// Format a name & uuid for dm devices in the RAID layer.
+///
+/// Prerequisite: len(format!("{}", FORMAT_VERSION)
+///             + len("stratis")                         7
+///             + len("private")                         7
+///             + len("raid")                            4
+///             + num_dashes                             5
+///             + len(pool uuid)                         32
+///             + max(len(RaidRole))                     25
+///             < 128 (129 for UUID)
+///
+/// which is equivalent to len(format!("{}", FORMAT_VERSION) < 48 (49 for UUID)
+pub fn format_raid_ids(pool_uuid: PoolUuid, role: RaidRole) -> (DmNameBuf, DmUuidBuf) {
+    let value = format!(
+        "stratis-{}-private-{}-raid-{}",
+        FORMAT_VERSION,
+        pool_uuid.simple().to_string(),
+        role
+    );
+    (
+        DmNameBuf::new(value.clone()).expect("FORMAT_VERSION display_length < 48"),
+        DmUuidBuf::new(value).expect("FORMAT_VERSION display_length < 49"),
+    )
+}
+
+/// Format a name for the dm-crypt device that cryptsetup opens on the LUKS2
+/// device with the given LUKS UUID, a member of the given pool. The device
+/// is not made by the engine with devicemapper, so it has no Stratis UUID.
+///
+/// Prerequisite: len(format!("{}", FORMAT_VERSION)
+///             + len("stratis")                         7
+///             + len("private")                         7
+///             + len("crypt")                           5
+///             + num_dashes                             4
+///             + len(pool uuid)                         32
+///             + len(luks uuid)                         32
+///             < 128
+///
+/// which is equivalent to len(format!("{}", FORMAT_VERSION) < 41
+pub fn format_crypt_name(pool_uuid: PoolUuid, luks_uuid: Uuid) -> DmNameBuf {
+    let value = format!(
+        "stratis-{}-private-{}-crypt-{}",
+        FORMAT_VERSION,
+        pool_uuid.simple().to_string(),
+        luks_uuid.simple().to_string()
+    );
+    DmNameBuf::new(value).expect("FORMAT_VERSION display_length < 41")
+}
+
+/// Get the UUID of the pool that owns the DM device with the given name.
+/// Returns None if the name was not constructed by one of the format
+/// functions above.
+pub fn pool_uuid_from_dm_name(name: &DmName) -> Option<PoolUuid> {
+    let name = name.to_string();
+    let mut parts = name.split('-');
+    if parts.next() != Some("stratis") {
+        return None;
+    }
+    // Skip the format version
+    parts.next();
+    match parts.next() {
+        Some("private") => parts.next(),
+        part => part,
+    }.and_then(|uuid| PoolUuid::parse_str(uuid).ok())
+}
+
+/// The error for a name that violates rule.
+fn invalid_name(name: &str, rule: &str) -> StratisError {
+    StratisError::Structured(
+        ErrorEnum::Invalid,
+        ErrorPayload::new("NameInvalid")
+            .param("name", name)
+            .param("rule", rule),
+    )
+}
+
 /// Validate a path for use as a Pool or Filesystem name.
 pub fn validate_name(name: &str) -> StratisResult<()> {
     let name_path = Path::new(name);
     if name.contains('\u{0}') {
-        return Err(StratisError::Engine(
-            ErrorEnum::Invalid,
-            format!("Name contains NULL characters : {}", name),
-        ));
+        return Err(invalid_name(name, "null-character"));
     }
     if name_path.components().count() != 1 {
-        return Err(StratisError::Engine(
-            ErrorEnum::Invalid,
-            format!("Name is a path with 0 or more than 1 components : {}", name),
-        ));
+        return Err(invalid_name(name, "path-components"));
     }
     if name_path.is_absolute() {
-        return Err(StratisError::Engine(
-            ErrorEnum::Invalid,
-            format!("Name is an absolute path : {}", name),
-        ));
+        return Err(invalid_name(name, "absolute-path"));
     }
     if name == "." || name == ".." {
-        return Err(StratisError::Engine(
-            ErrorEnum::Invalid,
-            format!("Name is . or .. : {}", name),
-        ));
+        return Err(invalid_name(name, "dot"));
     }
     // Linux has a maximum filename length of 255 bytes
     if name.len() > 255 {
-        return Err(StratisError::Engine(
-            ErrorEnum::Invalid,
-            format!("Name has more than 255 characters : {}", name),
-        ));
+        return Err(invalid_name(name, "too-long"));
     }
 
     if name.len() != name.trim().len() {
-        return Err(StratisError::Engine(
-            ErrorEnum::Invalid,
-            format!("Name contains leading or trailing space : {}", name),
-        ));
+        return Err(invalid_name(name, "whitespace"));
     }
     if name.chars().any(|c| c.is_control()) {
-        return Err(StratisError::Engine(
-            ErrorEnum::Invalid,
-            format!("Name contains control characters : {}", name),
-        ));
+        return Err(invalid_name(name, "control-character"));
     }
     Ok(())
 }
@@ -240,7 +322,39 @@ pub fn validate_name(name: &str) -> StratisResult<()> {
 #[cfg(test)]
 mod tests {
 
-    use engine::strat_engine::names::validate_name;
+    use devicemapper::DmName;
+    use uuid::Uuid;
+
+    use engine::{FilesystemUuid, PoolUuid};
+    use engine::strat_engine::names::{
+        format_backstore_ids, format_crypt_name, format_flex_ids, format_raid_ids,
+        format_thin_ids, format_thinpool_ids, pool_uuid_from_dm_name, validate_name, CacheRole,
+        FlexRole, RaidRole, ThinPoolRole, ThinRole,
+    };
+
+    #[test]
+    /// Verify that the pool UUID can be recovered from the name of every
+    /// kind of DM device, and that no UUID is found in other names.
+    pub fn test_pool_uuid_from_dm_name() {
+        let pool_uuid = PoolUuid::new_v4();
+        let names = vec![
+            format_flex_ids(pool_uuid, FlexRole::ThinMeta).0,
+            format_thin_ids(pool_uuid, ThinRole::Filesystem(FilesystemUuid::new_v4())).0,
+            format_thinpool_ids(pool_uuid, ThinPoolRole::Pool).0,
+            format_backstore_ids(pool_uuid, CacheRole::OriginSub).0,
+            format_crypt_name(pool_uuid, Uuid::new_v4()),
+            format_raid_ids(pool_uuid, RaidRole::Raid).0,
+            format_raid_ids(pool_uuid, RaidRole::DataSub(2)).0,
+        ];
+        for name in names {
+            assert_eq!(pool_uuid_from_dm_name(&name), Some(pool_uuid));
+        }
+
+        assert_eq!(
+            pool_uuid_from_dm_name(DmName::new("vg0-lv_root").unwrap()),
+            None
+        );
+    }
 
     #[test]
     pub fn test_validate_name() {
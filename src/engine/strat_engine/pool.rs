@@ -7,22 +7,38 @@ use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
 use std::vec::Vec;
 
+use chrono::{DateTime, Utc};
 use serde_json;
 use uuid::Uuid;
 
 use devicemapper::{Device, DmName, DmNameBuf, Sectors};
 
+use super::super::changes::{record_change, ChangeKind, ObjectKind};
 use super::super::engine::{BlockDev, Filesystem, Pool};
+use super::super::event::{get_engine_listener_list, EngineEvent};
+use super::super::schedule::scheduled_snapshot_name;
+use super::super::stats::{count, Counter};
 use super::super::types::{
-    BlockDevTier, DevUuid, FilesystemUuid, FreeSpaceState, MaybeDbusPath, Name, PoolExtendState,
-    PoolState, PoolUuid, Redundancy, RenameAction,
+    check_overcommit, destroy_order, reclaim_order, refuse_if_protected, validate_fs_limit,
+    validate_reclaim_target, validate_snapshot_retention, validate_usage_thresholds,
+    validate_watermarks, validate_worker_priority, AddDataDevsPlan, AllocationStrategy,
+    BlockDevTier, CacheType, CacheUsage, CreationParams, Creator, DevUuid, DmDeviceInfo,
+    FilesystemUuid, FormatVersions, FreeSpaceState, IoClass, MaybeDbusPath, MixedSizePolicy, Name,
+    PoolExtendState, PoolState, PoolUuid, Redundancy, RenameAction, SignaturePolicy,
+    SnapshotInterval, SnapshotSchedule, Watermarks, WorkerPriority, MAX_FORMAT_VERSIONS,
 };
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
 
-use super::backstore::{Backstore, StratBlockDev, MIN_MDA_SECTORS};
+use super::backstore::{
+    check_device_sizes, recorded_raid_size, Backstore, MetadataLayout, StratBlockDev,
+    MIN_MDA_SECTORS,
+};
+use super::journal::{journaled, JobKind, JobRecord, JournaledJob, JOURNAL_DIR};
 use super::names::validate_name;
+use super::replay::replay_pool;
 use super::serde_structs::{FlexDevsSave, PoolSave, Recordable};
-use super::thinpool::{ThinPool, ThinPoolSizeParams, DATA_BLOCK_SIZE};
+use super::thinpool::{validate_fs_size, ThinPool, ThinPoolSizeParams, DATA_BLOCK_SIZE};
+use super::watchdog;
 
 /// Get the index which indicates the start of unallocated space in the cap
 /// device.
@@ -96,12 +112,16 @@ pub fn check_metadata(metadata: &PoolSave) -> StratisResult<()> {
     // If the amount allocated to the cap device is less than the amount
     // allocated to the flex devices, consider the situation an error.
     // Consider it an error if the amount allocated to the cap device is 0.
-    // If this is the case, then the thin pool can not exist.
+    // If this is the case, then the thin pool can not exist. In a pool with
+    // redundancy, all of the raid device is allocated to the cap device.
     {
-        let total_allocated = metadata.backstore.data_tier.blockdev.allocs[0]
-            .iter()
-            .map(|x| x.length)
-            .sum::<Sectors>();
+        let total_allocated = match metadata.backstore.raid {
+            Some(ref raid) => recorded_raid_size(raid).unwrap_or(Sectors(0)),
+            None => metadata.backstore.data_tier.blockdev.allocs[0]
+                .iter()
+                .map(|x| x.length)
+                .sum::<Sectors>(),
+        };
 
         if total_allocated == Sectors(0) {
             let err_msg = format!(
@@ -128,6 +148,11 @@ pub struct StratPool {
     backstore: Backstore,
     redundancy: Redundancy,
     thin_pool: ThinPool,
+    verify_writes: bool,
+    created_by: Option<Creator>,
+    creation_params: Option<CreationParams>,
+    /// The most recently made plan for adding datadevs, and its token.
+    add_datadevs_plan: Option<(Uuid, AddDataDevsPlan)>,
     dbus_path: MaybeDbusPath,
 }
 
@@ -135,14 +160,99 @@ impl StratPool {
     /// Initialize a Stratis Pool.
     /// 1. Initialize the block devices specified by paths.
     /// 2. Set up thinpool device to back filesystems.
+    /// Every device in the pool is initialized with the given metadata
+    /// layout.
+    /// The size policy is applied before any device is written to.
+    /// The signature policy determines whether devices with foreign
+    /// signatures are initialized.
+    /// The creator, if given, is recorded in the pool's metadata.
     pub fn initialize(
         name: &str,
         paths: &[&Path],
         redundancy: Redundancy,
+        size_policy: MixedSizePolicy,
+        layout: MetadataLayout,
+        signature_policy: SignaturePolicy,
+        created_by: Option<Creator>,
+    ) -> StratisResult<(PoolUuid, StratPool)> {
+        redundancy.check_devices(paths.len())?;
+        check_device_sizes(paths, size_policy)?;
+
+        let pool_uuid = PoolUuid::new_v4();
+
+        let backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            layout,
+            signature_policy,
+        )?;
+
+        StratPool::build(
+            name,
+            pool_uuid,
+            backstore,
+            MIN_MDA_SECTORS,
+            redundancy,
+            created_by,
+        )
+    }
+
+    /// Initialize a Stratis Pool, as initialize() does, whose data devices
+    /// are encrypted with LUKS2. Each device is formatted as a LUKS2 device
+    /// opened by passphrase, and the pool is made on the dm-crypt devices
+    /// opened on them.
+    #[allow(too_many_arguments)]
+    pub fn initialize_encrypted(
+        name: &str,
+        paths: &[&Path],
+        redundancy: Redundancy,
+        size_policy: MixedSizePolicy,
+        layout: MetadataLayout,
+        signature_policy: SignaturePolicy,
+        passphrase: &str,
+        created_by: Option<Creator>,
     ) -> StratisResult<(PoolUuid, StratPool)> {
-        let pool_uuid = Uuid::new_v4();
+        redundancy.check_devices(paths.len())?;
+        check_device_sizes(paths, size_policy)?;
+
+        let pool_uuid = PoolUuid::new_v4();
+
+        let backstore = Backstore::initialize_encrypted(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            layout,
+            signature_policy,
+            passphrase,
+        )?;
+
+        StratPool::build(
+            name,
+            pool_uuid,
+            backstore,
+            MIN_MDA_SECTORS,
+            redundancy,
+            created_by,
+        )
+    }
 
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS)?;
+    /// Set up the RAID layer, if the pool has redundancy, and the thinpool
+    /// device of a new pool on its newly initialized backstore, whose
+    /// devices have MDAs of mda_size, and write the pool's metadata. If
+    /// either can not be made, the backstore is destroyed.
+    fn build(
+        name: &str,
+        pool_uuid: PoolUuid,
+        mut backstore: Backstore,
+        mda_size: Sectors,
+        redundancy: Redundancy,
+        created_by: Option<Creator>,
+    ) -> StratisResult<(PoolUuid, StratPool)> {
+        if let Err(err) = backstore.initialize_raid(pool_uuid, redundancy) {
+            let _ = backstore.destroy(false);
+            return Err(err);
+        }
 
         let thinpool = ThinPool::new(
             pool_uuid,
@@ -154,17 +264,27 @@ impl StratPool {
         let mut thinpool = match thinpool {
             Ok(thinpool) => thinpool,
             Err(err) => {
-                let _ = backstore.destroy();
+                let _ = backstore.destroy(false);
                 return Err(err);
             }
         };
 
         thinpool.check(pool_uuid, &mut backstore)?;
 
+        let creation_params = CreationParams::new(
+            mda_size,
+            DATA_BLOCK_SIZE,
+            redundancy,
+            backstore.encrypted(),
+        );
         let mut pool = StratPool {
             backstore,
             redundancy,
             thin_pool: thinpool,
+            verify_writes: false,
+            created_by,
+            creation_params: Some(creation_params),
+            add_datadevs_plan: None,
             dbus_path: MaybeDbusPath(None),
         };
 
@@ -183,6 +303,10 @@ impl StratPool {
         metadata: &PoolSave,
     ) -> StratisResult<(Name, StratPool)> {
         let mut backstore = Backstore::setup(uuid, &metadata.backstore, devnodes, None)?;
+        if let Some(ref priority) = metadata.worker_priority {
+            backstore.set_worker_priority(WorkerPriority::from(priority));
+        }
+        backstore.set_encrypted(metadata.encrypted.unwrap_or(false));
         let mut thinpool = ThinPool::setup(
             uuid,
             &metadata.thinpool_dev,
@@ -192,10 +316,18 @@ impl StratPool {
 
         let changed = thinpool.check(uuid, &mut backstore)?;
 
+        let redundancy = backstore.redundancy();
         let mut pool = StratPool {
             backstore,
-            redundancy: Redundancy::NONE,
+            redundancy,
             thin_pool: thinpool,
+            verify_writes: metadata.verify_writes.unwrap_or(false),
+            created_by: metadata.created_by.as_ref().map(Creator::from),
+            creation_params: metadata
+                .creation_params
+                .as_ref()
+                .map(CreationParams::from),
+            add_datadevs_plan: None,
             dbus_path: MaybeDbusPath(None),
         };
 
@@ -208,10 +340,90 @@ impl StratPool {
         Ok((Name::new(pool_name.to_owned()), pool))
     }
 
+    /// Reconstruct a StratPool from its metadata on fresh devices, which
+    /// already have BDAs for the blockdevs recorded in the metadata.
+    /// The thin pool and the MDV are made anew, so the pool has no
+    /// filesystems. The metadata is written to the devices' MDAs.
+    /// Precondition: the metadata records no cache tier.
+    /// Precondition: A metadata verification step has already been run.
+    pub fn reconstruct(
+        uuid: PoolUuid,
+        devnodes: &HashMap<Device, PathBuf>,
+        metadata: &PoolSave,
+    ) -> StratisResult<(Name, StratPool)> {
+        let mut backstore = Backstore::setup(uuid, &metadata.backstore, devnodes, None)?;
+        if let Some(ref priority) = metadata.worker_priority {
+            backstore.set_worker_priority(WorkerPriority::from(priority));
+        }
+        let thinpool = ThinPool::reconstruct(
+            uuid,
+            &metadata.thinpool_dev,
+            &metadata.flex_devs,
+            &backstore,
+        );
+
+        let mut thinpool = match thinpool {
+            Ok(thinpool) => thinpool,
+            Err(err) => {
+                let _ = backstore.destroy(false);
+                return Err(err);
+            }
+        };
+
+        thinpool.check(uuid, &mut backstore)?;
+
+        let redundancy = backstore.redundancy();
+        let mut pool = StratPool {
+            backstore,
+            redundancy,
+            thin_pool: thinpool,
+            verify_writes: metadata.verify_writes.unwrap_or(false),
+            created_by: metadata.created_by.as_ref().map(Creator::from),
+            creation_params: metadata
+                .creation_params
+                .as_ref()
+                .map(CreationParams::from),
+            add_datadevs_plan: None,
+            dbus_path: MaybeDbusPath(None),
+        };
+
+        let pool_name = &metadata.name;
+        pool.write_metadata(pool_name)?;
+
+        Ok((Name::new(pool_name.to_owned()), pool))
+    }
+
     /// Write current metadata to pool members.
+    /// If the clock reads earlier than the time of the metadata last
+    /// written, whether or not the metadata is then written, notify the
+    /// engine's listeners of both times.
     pub fn write_metadata(&mut self, name: &str) -> StratisResult<()> {
+        self.save_metadata(name, false)
+    }
+
+    /// Write current metadata to pool members, as write_metadata() does.
+    /// If throttle is true, the write is a background one, made for the
+    /// daemon's own housekeeping, and if it would go to a device written to
+    /// too recently it is held back until the pool's housekeeping is run.
+    /// Only a save that records no allocation or change of layout, such as
+    /// a heartbeat or statistics, may be throttled.
+    fn save_metadata(&mut self, name: &str, throttle: bool) -> StratisResult<()> {
         let data = serde_json::to_string(&self.record(name))?;
-        self.backstore.save_state(data.as_bytes())
+        if let Some((proposed, newest)) = self.backstore.clock_skew() {
+            warn!(
+                "clock reads {}, earlier than metadata of pool {} written at {}",
+                proposed, name, newest
+            );
+            get_engine_listener_list().notify(&EngineEvent::PoolMetadataClockSkew {
+                dbus_path: &self.dbus_path,
+                proposed: &proposed,
+                newest: &newest,
+            });
+        }
+        // The sigblocks record the pool's name, which may be new.
+        self.backstore.set_pool_name(name);
+        self.backstore
+            .save_state(data.as_bytes(), self.verify_writes, throttle)
     }
 
     /// Teardown a pool.
@@ -245,6 +457,8 @@ impl StratPool {
                 .iter()
                 .any(|x| dm_name == &**x)
         );
+        // A save after check() records new or extended segments, so it is
+        // never held back.
         if self.thin_pool.check(pool_uuid, &mut self.backstore)? {
             self.write_metadata(pool_name)?;
         }
@@ -257,12 +471,166 @@ impl StratPool {
             backstore: self.backstore.record(),
             flex_devs: self.thin_pool.record(),
             thinpool_dev: self.thin_pool.record(),
+            verify_writes: if self.verify_writes { Some(true) } else { None },
+            created_by: self.created_by.as_ref().map(|c| c.record()),
+            worker_priority: match self.backstore.worker_priority() {
+                priority if priority == WorkerPriority::default() => None,
+                priority => Some(priority.record()),
+            },
+            encrypted: if self.backstore.encrypted() {
+                Some(true)
+            } else {
+                None
+            },
+            creation_params: self.creation_params.as_ref().map(|p| p.record()),
         }
     }
 
     pub fn get_strat_blockdev(&self, uuid: DevUuid) -> Option<(BlockDevTier, &StratBlockDev)> {
         self.backstore.get_blockdev_by_uuid(uuid)
     }
+
+    /// Resume, or roll back, the job recorded in record, which was cut
+    /// short by a crash or a restart of stratisd. A device whose addition
+    /// to the pool was cut short holds no data, and is wiped. A job which
+    /// moves data is resumed from where it stopped, since the metadata
+    /// records where the data lies.
+    pub fn recover_job(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        record: &JobRecord,
+    ) -> StratisResult<()> {
+        match record.kind {
+            JobKind::InitializeDevices { ref paths } => {
+                for path in paths {
+                    if self.backstore.wipe_stray_device(pool_uuid, path)? {
+                        info!(
+                            "wiped device {}, whose addition to pool {} was cut short",
+                            path.display(),
+                            pool_name
+                        );
+                    }
+                }
+                Ok(())
+            }
+            JobKind::RemoveBlockDevs { ref uuids } => {
+                let remaining = uuids
+                    .iter()
+                    .filter(|&&uuid| self.backstore.get_blockdev_by_uuid(uuid).is_some())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if remaining.is_empty() {
+                    return Ok(());
+                }
+                self.remove_blockdevs(pool_uuid, pool_name, &remaining)
+            }
+            JobKind::ReplaceBlockDev {
+                old,
+                ref new,
+                signature_policy,
+            } => {
+                if self.backstore.get_blockdev_by_uuid(old).is_none() {
+                    return Ok(());
+                }
+                if self.backstore.datadev_at(new)?.is_none() {
+                    self.backstore.wipe_stray_device(pool_uuid, new)?;
+                }
+                self.replace_blockdev(pool_uuid, pool_name, old, new, signature_policy)
+                    .map(|_| ())
+            }
+        }
+    }
+
+    /// Move the data on the blockdevs specified by uuids to the other
+    /// blockdevs of the data tier, or only to the blockdev target if one is
+    /// given, a part at a time. The progress of the move, of total sectors,
+    /// is announced by a PoolRemoveProgress event after each part.
+    fn move_data(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        uuids: &[DevUuid],
+        target: Option<DevUuid>,
+        total: Sectors,
+        job: &mut JournaledJob,
+    ) -> StratisResult<()> {
+        let mut moved = Sectors(0);
+        while let Some(data_move) = self.backstore.begin_data_move(uuids, target)? {
+            // The metadata must record where the data has been moved to
+            // before any of it is written there, so that the data is found
+            // where it was last written if stratisd stops part way.
+            let length = data_move.length;
+            if let Err(err) = self.write_metadata(pool_name) {
+                self.backstore.abort_data_move(data_move)?;
+                return Err(err);
+            }
+            self.backstore.commit_data_move(pool_uuid, data_move)?;
+
+            moved += length;
+            job.checkpoint(moved, total);
+            get_engine_listener_list().notify(&EngineEvent::PoolRemoveProgress {
+                dbus_path: &self.dbus_path,
+                moved,
+                total,
+            });
+        }
+        Ok(())
+    }
+
+    /// Replace the blockdev old by the device at new, the job of
+    /// replace_blockdev().
+    fn replace_datadev(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        old: DevUuid,
+        new: &Path,
+        signature_policy: SignaturePolicy,
+        job: &mut JournaledJob,
+    ) -> StratisResult<DevUuid> {
+        // A replacement that was cut short left new in the pool, and is
+        // resumed from where it stopped.
+        let new_uuid = match self.backstore.datadev_at(new)? {
+            Some(uuid) => uuid,
+            None => {
+                self.backstore.check_replace_datadev(old, None)?;
+                let uuid = self.backstore
+                    .add_replacement_datadev(pool_uuid, new, signature_policy)?;
+                self.write_metadata(pool_name)?;
+                record_change(ObjectKind::BlockDev, uuid, ChangeKind::Created);
+                uuid
+            }
+        };
+        let total = self.backstore.check_replace_datadev(old, Some(new_uuid))?;
+
+        if self.backstore.redundancy() == Redundancy::NONE {
+            self.move_data(pool_uuid, pool_name, &[old], Some(new_uuid), total, job)?;
+        } else if self.backstore.is_raid_member(old) {
+            // The array is loaded with the new member before the metadata
+            // records it, so that if stratisd stops in between, the metadata
+            // names old, which still holds its data, as the member.
+            self.backstore.rebuild_raid_member(pool_uuid, old, new_uuid)?;
+            self.write_metadata(pool_name)?;
+        }
+
+        self.backstore.remove_datadevs(&[old])?;
+        self.write_metadata(pool_name)?;
+        record_change(ObjectKind::BlockDev, old, ChangeKind::Deleted);
+        Ok(new_uuid)
+    }
+
+    /// Return an error if the pool is encrypted. Devices can not yet be
+    /// added to an encrypted pool, since they would be added unencrypted.
+    fn refuse_if_encrypted(&self, pool_name: &str) -> StratisResult<()> {
+        if self.backstore.encrypted() {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("PoolEncrypted").param("name", pool_name),
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Pool for StratPool {
@@ -270,28 +638,64 @@ impl Pool for StratPool {
         &'a mut self,
         pool_uuid: PoolUuid,
         pool_name: &str,
-        specs: &[(&'b str, Option<Sectors>)],
+        specs: &[(&'b str, Option<Sectors>, bool)],
+        created_by: Option<Creator>,
     ) -> StratisResult<Vec<(&'b str, FilesystemUuid)>> {
-        let names: HashMap<_, _> = HashMap::from_iter(specs.iter().map(|&tup| (tup.0, tup.1)));
+        self.thin_pool.refuse_if_needs_check(pool_name)?;
+
+        let names: HashMap<_, _> =
+            HashMap::from_iter(specs.iter().map(|&tup| (tup.0, (tup.1, tup.2))));
         for name in names.keys() {
             validate_name(name)?;
             if self.thin_pool.get_mut_filesystem_by_name(*name).is_some() {
-                return Err(StratisError::Engine(
+                return Err(StratisError::Structured(
                     ErrorEnum::AlreadyExists,
-                    name.to_string(),
+                    ErrorPayload::new("FilesystemNameExists").param("name", name),
                 ));
             }
         }
 
-        // TODO: Roll back on filesystem initialization failure.
-        let mut result = Vec::new();
-        for (name, size) in names {
-            let fs_uuid = self.thin_pool
-                .create_filesystem(pool_uuid, pool_name, name, size)?;
-            result.push((name, fs_uuid));
+        for size in names.values().filter_map(|&(size, _)| size) {
+            validate_fs_size(size)?;
         }
 
-        Ok(result)
+        self.thin_pool.check_fs_limit(names.len())?;
+
+        let total_size: Sectors = names
+            .values()
+            .map(|&(size, _)| self.thin_pool.fs_size(size))
+            .sum();
+        self.thin_pool
+            .check_overcommit(self.backstore.datatier_size(), total_size)?;
+
+        let guaranteed_size: Sectors = names
+            .values()
+            .filter(|&&(_, guaranteed)| guaranteed)
+            .map(|&(size, _)| self.thin_pool.fs_size(size))
+            .sum();
+        if guaranteed_size != Sectors(0) {
+            let reserved = self.thin_pool
+                .reserve_data(pool_uuid, &mut self.backstore, guaranteed_size);
+            // Reserving may have extended the data device, even if it failed.
+            self.write_metadata(pool_name)?;
+            reserved?;
+        }
+
+        let specs = names
+            .into_iter()
+            .map(|(name, (size, guaranteed))| (name, size, guaranteed))
+            .collect::<Vec<_>>();
+        let fs_uuids = self.thin_pool
+            .create_filesystems(pool_uuid, pool_name, &specs, created_by)?;
+
+        Ok(specs
+            .into_iter()
+            .zip(fs_uuids)
+            .map(|((name, _, _), fs_uuid)| {
+                record_change(ObjectKind::Filesystem, fs_uuid, ChangeKind::Created);
+                (name, fs_uuid)
+            })
+            .collect())
     }
 
     fn add_blockdevs(
@@ -300,19 +704,33 @@ impl Pool for StratPool {
         pool_name: &str,
         paths: &[&Path],
         tier: BlockDevTier,
+        signature_policy: Option<u16>,
     ) -> StratisResult<Vec<DevUuid>> {
-        let bdev_info = if tier == BlockDevTier::Cache {
-            // If adding cache devices, must suspend the pool, since the cache
-            // must be augmeneted with the new devices.
-            self.thin_pool.suspend()?;
-            let bdev_info = self.backstore.add_cachedevs(pool_uuid, paths)?;
-            self.thin_pool.set_device(self.backstore.device().expect("Since thin pool exists, space must have been allocated from the backstore, so backstore must have a cap device"))?;
-            self.thin_pool.resume()?;
-            Ok(bdev_info)
-        } else {
+        if tier == BlockDevTier::Cache {
+            let cache_type = self.backstore.cache_type().map(u16::from);
+            let volatile = self.backstore.cache_volatile();
+            return self.add_cachedevs(
+                pool_uuid,
+                pool_name,
+                paths,
+                signature_policy,
+                cache_type,
+                volatile,
+            );
+        }
+
+        self.thin_pool.refuse_if_needs_check(pool_name)?;
+        self.refuse_if_encrypted(pool_name)?;
+        let signature_policy = calculate_signature_policy!(signature_policy);
+
+        let kind = JobKind::InitializeDevices {
+            paths: paths.iter().map(|path| path.to_path_buf()).collect(),
+        };
+        let bdev_info = journaled(Path::new(JOURNAL_DIR), pool_uuid, kind, |_| {
             // If just adding data devices, no need to suspend the pool.
             // No action will be taken on the DM devices.
-            let bdev_info = self.backstore.add_datadevs(pool_uuid, paths)?;
+            let bdev_info = self.backstore
+                .add_datadevs(pool_uuid, paths, signature_policy)?;
 
             // Adding data devices does not change the state of the thin
             // pool at all. However, if the thin pool is in a state
@@ -321,15 +739,171 @@ impl Pool for StratPool {
             // so that it can satisfy the allocation request where
             // previously it could not. Run check() in case that is true.
             self.thin_pool.check(pool_uuid, &mut self.backstore)?;
+            self.write_metadata(pool_name)?;
             Ok(bdev_info)
+        })?;
+        for &uuid in &bdev_info {
+            record_change(ObjectKind::BlockDev, uuid, ChangeKind::Created);
+        }
+        Ok(bdev_info)
+    }
+
+    fn remove_blockdevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        uuids: &[DevUuid],
+    ) -> StratisResult<()> {
+        self.thin_pool.refuse_if_needs_check(pool_name)?;
+        let total = self.backstore.check_remove_datadevs(uuids)?;
+        let kind = JobKind::RemoveBlockDevs {
+            uuids: uuids.to_vec(),
         };
-        self.write_metadata(pool_name)?;
-        bdev_info
+        journaled(Path::new(JOURNAL_DIR), pool_uuid, kind, |job| {
+            self.move_data(pool_uuid, pool_name, uuids, None, total, job)?;
+            self.backstore.remove_datadevs(uuids)?;
+            self.write_metadata(pool_name)
+        })?;
+        for &uuid in uuids {
+            record_change(ObjectKind::BlockDev, uuid, ChangeKind::Deleted);
+        }
+        Ok(())
+    }
+
+    fn replace_blockdev(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        old: DevUuid,
+        new: &Path,
+        signature_policy: Option<u16>,
+    ) -> StratisResult<DevUuid> {
+        self.thin_pool.refuse_if_needs_check(pool_name)?;
+        self.refuse_if_encrypted(pool_name)?;
+        let kind = JobKind::ReplaceBlockDev {
+            old,
+            new: new.to_owned(),
+            signature_policy,
+        };
+        let signature_policy = calculate_signature_policy!(signature_policy);
+        journaled(Path::new(JOURNAL_DIR), pool_uuid, kind, |job| {
+            self.replace_datadev(pool_uuid, pool_name, old, new, signature_policy, job)
+        })
+    }
+
+    fn add_cachedevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        paths: &[&Path],
+        signature_policy: Option<u16>,
+        cache_type: Option<u16>,
+        volatile: bool,
+    ) -> StratisResult<Vec<DevUuid>> {
+        self.thin_pool.refuse_if_needs_check(pool_name)?;
+        self.refuse_if_encrypted(pool_name)?;
+        let signature_policy = calculate_signature_policy!(signature_policy);
+        let cache_type = calculate_cache_type!(cache_type);
+
+        if let Some(existing) = self.backstore.cache_type() {
+            if existing != cache_type {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("CacheTypeMismatch")
+                        .param("name", pool_name)
+                        .param("existing", existing)
+                        .param("requested", cache_type),
+                ));
+            }
+            if self.backstore.cache_volatile() != volatile {
+                return Err(StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!(
+                        "The cache of pool {} is {}volatile",
+                        pool_name,
+                        if volatile { "not " } else { "" }
+                    ),
+                ));
+            }
+        }
+
+        let kind = JobKind::InitializeDevices {
+            paths: paths.iter().map(|path| path.to_path_buf()).collect(),
+        };
+        let bdev_info = journaled(Path::new(JOURNAL_DIR), pool_uuid, kind, |_| {
+            // Must suspend the pool, since the cache must be augmented with
+            // the new devices. The thin pool is built on the backstore's cap
+            // device, which does not change, so its tables need not be
+            // rewritten.
+            self.thin_pool.suspend()?;
+            let bdev_info = self.backstore
+                .add_cachedevs(pool_uuid, paths, signature_policy, cache_type, volatile);
+            self.thin_pool.resume()?;
+            self.write_metadata(pool_name)?;
+            bdev_info
+        })?;
+        for &uuid in &bdev_info {
+            record_change(ObjectKind::BlockDev, uuid, ChangeKind::Created);
+        }
+        Ok(bdev_info)
+    }
+
+    fn cache_type(&self) -> Option<CacheType> {
+        self.backstore.cache_type()
+    }
+
+    fn cache_volatile(&self) -> bool {
+        self.backstore.cache_volatile()
+    }
+
+    fn cache_usage(&self) -> StratisResult<Option<CacheUsage>> {
+        self.backstore.cache_usage()
+    }
+
+    fn plan_add_datadevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        paths: &[&Path],
+    ) -> StratisResult<(Uuid, AddDataDevsPlan)> {
+        let plan = self.backstore.plan_add_datadevs(pool_uuid, paths)?;
+        let token = Uuid::new_v4();
+        self.add_datadevs_plan = Some((token, plan.clone()));
+        Ok((token, plan))
+    }
+
+    fn apply_add_datadevs_plan(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        token: Uuid,
+    ) -> StratisResult<Vec<DevUuid>> {
+        let plan = match self.add_datadevs_plan {
+            Some((plan_token, ref plan)) if plan_token == token => plan.clone(),
+            _ => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NotFound,
+                    ErrorPayload::new("PlanNotFound").param("token", token),
+                ))
+            }
+        };
+
+        let devnodes = plan.new_devnodes();
+        let paths = devnodes.iter().map(|p| p.as_path()).collect::<Vec<_>>();
+        if self.backstore.plan_add_datadevs(pool_uuid, &paths)? != plan {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("PlanOutOfDate").param("token", token),
+            ));
+        }
+
+        let uuids = self.add_blockdevs(pool_uuid, pool_name, &paths, BlockDevTier::Data, None)?;
+        self.add_datadevs_plan = None;
+        Ok(uuids)
     }
 
-    fn destroy(&mut self) -> StratisResult<()> {
+    fn destroy(&mut self, keep_signatures: bool) -> StratisResult<()> {
         self.thin_pool.teardown()?;
-        self.backstore.destroy()?;
+        self.backstore.destroy(keep_signatures)?;
         Ok(())
     }
 
@@ -337,14 +911,80 @@ impl Pool for StratPool {
         &'a mut self,
         pool_name: &str,
         fs_uuids: &[FilesystemUuid],
-    ) -> StratisResult<Vec<FilesystemUuid>> {
-        let mut removed = Vec::new();
-        for &uuid in fs_uuids {
-            self.thin_pool.destroy_filesystem(pool_name, uuid)?;
-            removed.push(uuid);
+    ) -> StratisResult<Vec<(FilesystemUuid, StratisResult<()>)>> {
+        self.thin_pool.refuse_if_needs_check(pool_name)?;
+        refuse_if_protected(fs_uuids.iter().filter_map(|&uuid| {
+            self.thin_pool
+                .get_filesystem_by_uuid(uuid)
+                .map(|(_, fs)| (uuid, fs.protected()))
+        }))?;
+
+        let order = destroy_order(fs_uuids.iter().filter_map(|&uuid| {
+            self.thin_pool
+                .get_filesystem_by_uuid(uuid)
+                .map(|(_, fs)| (uuid, fs.origin()))
+        }));
+
+        let mut results = Vec::new();
+        for uuid in order {
+            let result = self.thin_pool.destroy_filesystem(pool_name, uuid);
+            match result {
+                Ok(_) => record_change(ObjectKind::Filesystem, uuid, ChangeKind::Deleted),
+                Err(ref err) => warn!(
+                    "Could not destroy filesystem {} of pool {}: {}",
+                    uuid, pool_name, err
+                ),
+            }
+            results.push((uuid, result));
+        }
+
+        Ok(results)
+    }
+
+    fn emergency_reclaim(
+        &mut self,
+        pool_name: &str,
+        target: u8,
+    ) -> StratisResult<Vec<(Name, FilesystemUuid)>> {
+        validate_reclaim_target(target)?;
+        self.thin_pool.refuse_if_needs_check(pool_name)?;
+
+        let order = reclaim_order(
+            self.thin_pool
+                .filesystems()
+                .into_iter()
+                .map(|(_, uuid, fs)| (uuid, fs.origin(), fs.protected(), fs.created())),
+        );
+
+        let mut destroyed = Vec::new();
+        for uuid in order {
+            let used = self.thin_pool.data_used_pct()?;
+            if used < target {
+                break;
+            }
+            let name = match self.thin_pool.get_filesystem_by_uuid(uuid) {
+                Some((name, _)) => name,
+                None => continue,
+            };
+            match self.thin_pool.destroy_filesystem(pool_name, uuid) {
+                Ok(_) => {
+                    warn!(
+                        "Reclaimed space: destroyed snapshot {} ({}) of pool {} at {}% usage",
+                        name, uuid, pool_name, used
+                    );
+                    record_change(ObjectKind::Filesystem, uuid, ChangeKind::Deleted);
+                    destroyed.push((name, uuid));
+                }
+                Err(err) => {
+                    warn!(
+                        "Reclaiming space: could not destroy snapshot {} ({}) of pool {}: {}",
+                        name, uuid, pool_name, err
+                    );
+                }
+            }
         }
 
-        Ok(removed)
+        Ok(destroyed)
     }
 
     fn rename_filesystem(
@@ -354,7 +994,50 @@ impl Pool for StratPool {
         new_name: &str,
     ) -> StratisResult<RenameAction> {
         validate_name(new_name)?;
-        self.thin_pool.rename_filesystem(pool_name, uuid, new_name)
+        let action = self.thin_pool.rename_filesystem(pool_name, uuid, new_name)?;
+        if action == RenameAction::Renamed {
+            record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        }
+        Ok(action)
+    }
+
+    fn set_filesystem_read_ahead(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        read_ahead: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        let changed = self.thin_pool.set_filesystem_read_ahead(uuid, read_ahead)?;
+        if changed {
+            record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn set_filesystem_protected(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        protected: bool,
+    ) -> StratisResult<bool> {
+        let changed = self.thin_pool.set_filesystem_protected(uuid, protected)?;
+        if changed {
+            record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
+    }
+
+    fn set_filesystem_size_limit(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        limit: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        let changed = self.thin_pool.set_filesystem_size_limit(uuid, limit)?;
+        if changed {
+            record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        }
+        Ok(changed)
     }
 
     fn snapshot_filesystem(
@@ -363,21 +1046,57 @@ impl Pool for StratPool {
         pool_name: &str,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        freeze: bool,
     ) -> StratisResult<(FilesystemUuid, &mut Filesystem)> {
+        self.thin_pool.refuse_if_needs_check(pool_name)?;
         validate_name(snapshot_name)?;
 
         if self.thin_pool
             .get_filesystem_by_name(snapshot_name)
             .is_some()
         {
-            return Err(StratisError::Engine(
+            return Err(StratisError::Structured(
                 ErrorEnum::AlreadyExists,
-                snapshot_name.to_string(),
+                ErrorPayload::new("FilesystemNameExists").param("name", snapshot_name),
             ));
         }
 
-        self.thin_pool
-            .snapshot_filesystem(pool_uuid, pool_name, origin_uuid, snapshot_name)
+        self.thin_pool.check_fs_limit(1)?;
+        self.thin_pool.refuse_if_meta_critical()?;
+        if let Some((_, origin)) = self.thin_pool.get_filesystem_by_uuid(origin_uuid) {
+            self.thin_pool
+                .check_overcommit(self.backstore.datatier_size(), origin.size())?;
+        }
+
+        let (uuid, snapshot) = self.thin_pool
+            .snapshot_filesystem(pool_uuid, pool_name, origin_uuid, snapshot_name, freeze)?;
+        record_change(ObjectKind::Filesystem, uuid, ChangeKind::Created);
+        Ok((uuid, snapshot))
+    }
+
+    fn check_filesystem(
+        &self,
+        uuid: FilesystemUuid,
+        repair: bool,
+    ) -> StratisResult<(bool, String)> {
+        match self.thin_pool.get_filesystem_by_uuid(uuid) {
+            Some((_, filesystem)) => filesystem.check_consistency(repair),
+            None => Err(StratisError::Structured(
+                ErrorEnum::NotFound,
+                ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+            )),
+        }
+    }
+
+    fn repair(&mut self, pool_uuid: PoolUuid, pool_name: &str) -> StratisResult<bool> {
+        let repaired = self.thin_pool.repair(pool_uuid, &mut self.backstore)?;
+        if repaired {
+            count(Counter::Repairs);
+            // Repairing the thin pool's metadata device may have replaced
+            // it with its spare.
+            self.write_metadata(pool_name)?;
+        }
+        Ok(repaired)
     }
 
     fn total_physical_size(&self) -> Sectors {
@@ -410,6 +1129,10 @@ impl Pool for StratPool {
             .map(|(name, fs)| (name, fs as &mut Filesystem))
     }
 
+    fn filesystem_space(&self) -> StratisResult<Vec<(FilesystemUuid, Sectors, Sectors)>> {
+        self.thin_pool.filesystem_space()
+    }
+
     fn blockdevs(&self) -> Vec<(DevUuid, &BlockDev)> {
         self.backstore
             .blockdevs()
@@ -445,22 +1168,417 @@ impl Pool for StratPool {
     ) -> StratisResult<bool> {
         if self.backstore.set_blockdev_user_info(uuid, user_info)? {
             self.write_metadata(pool_name)?;
+            record_change(ObjectKind::BlockDev, uuid, ChangeKind::Modified);
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
-    fn state(&self) -> PoolState {
-        self.thin_pool.state()
+    fn created_by(&self) -> Option<&Creator> {
+        self.created_by.as_ref()
     }
 
-    fn extend_state(&self) -> PoolExtendState {
-        self.thin_pool.extend_state()
+    fn encrypted(&self) -> bool {
+        self.backstore.encrypted()
     }
 
-    fn free_space_state(&self) -> FreeSpaceState {
-        self.thin_pool.free_space_state()
+    fn creation_params(&self) -> Option<&CreationParams> {
+        self.creation_params.as_ref()
+    }
+
+    fn verify_writes(&self) -> bool {
+        self.verify_writes
+    }
+
+    fn set_verify_writes(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        verify: bool,
+    ) -> StratisResult<bool> {
+        if self.verify_writes == verify {
+            return Ok(false);
+        }
+        self.verify_writes = verify;
+        if let Err(err) = self.write_metadata(pool_name) {
+            self.verify_writes = !verify;
+            return Err(err);
+        }
+        record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn allocation_strategy(&self) -> AllocationStrategy {
+        self.backstore.allocation_strategy()
+    }
+
+    fn set_allocation_strategy(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        strategy: Option<u16>,
+    ) -> StratisResult<bool> {
+        let strategy = calculate_allocation_strategy!(strategy);
+        let previous = self.backstore.allocation_strategy();
+        if previous == strategy {
+            return Ok(false);
+        }
+        self.backstore.set_allocation_strategy(strategy);
+        if let Err(err) = self.write_metadata(pool_name) {
+            self.backstore.set_allocation_strategy(previous);
+            return Err(err);
+        }
+        record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn worker_priority(&self) -> WorkerPriority {
+        self.backstore.worker_priority()
+    }
+
+    fn set_worker_priority(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        niceness: i16,
+        io_class: Option<u16>,
+        io_level: u8,
+    ) -> StratisResult<bool> {
+        validate_worker_priority(niceness, io_level)?;
+        let io_class = calculate_io_class!(io_class);
+        let priority = WorkerPriority {
+            niceness: niceness as i8,
+            io_class,
+            io_level: if io_class == IoClass::Idle { 0 } else { io_level },
+        };
+        let previous = self.backstore.worker_priority();
+        if previous == priority {
+            return Ok(false);
+        }
+        self.backstore.set_worker_priority(priority);
+        if let Err(err) = self.write_metadata(pool_name) {
+            self.backstore.set_worker_priority(previous);
+            return Err(err);
+        }
+        record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn shrink_reserved_space(&mut self) -> StratisResult<Sectors> {
+        self.backstore.shrink_reserved()
+    }
+
+    fn blockdev_removed(&mut self, device: Device) -> Option<(BlockDevTier, DevUuid)> {
+        let removed = self.backstore.mark_missing(device);
+        if removed.is_some() {
+            self.thin_pool.blockdev_removed();
+        }
+        removed
+    }
+
+    fn state(&self) -> PoolState {
+        self.thin_pool.state()
+    }
+
+    fn format_versions(&self) -> FormatVersions {
+        FormatVersions::lowest(
+            self.backstore
+                .blockdevs()
+                .iter()
+                .map(|&(_, bd)| bd.format_versions()),
+        ).unwrap_or(MAX_FORMAT_VERSIONS)
+    }
+
+    fn dm_devices(&self) -> Vec<(String, DmDeviceInfo)> {
+        self.thin_pool.dm_devices()
+    }
+
+    fn extend_state(&self) -> PoolExtendState {
+        self.thin_pool.extend_state()
+    }
+
+    fn last_metadata_update(&self) -> Option<DateTime<Utc>> {
+        self.backstore.last_update_time().cloned()
+    }
+
+    fn metadata_behind_blockdevs(&self) -> Vec<DevUuid> {
+        self.backstore.metadata_behind()
+    }
+
+    fn operation_stuck(&self, pool_uuid: PoolUuid) -> bool {
+        watchdog::is_stuck(pool_uuid)
+    }
+
+    fn free_space_state(&self) -> FreeSpaceState {
+        self.thin_pool.free_space_state()
+    }
+
+    fn days_until_full(&self) -> Option<u64> {
+        self.thin_pool.days_until_full()
+    }
+
+    fn overcommit_percent(&self) -> Option<u64> {
+        self.thin_pool
+            .overcommit_percent(self.backstore.datatier_size())
+    }
+
+    fn thin_meta_usage(&self) -> Option<(Sectors, Sectors)> {
+        self.thin_pool.meta_usage()
+    }
+
+    fn replay_metadata(&self, pool_uuid: PoolUuid) -> StratisResult<Vec<String>> {
+        let devnodes = self.backstore
+            .blockdevs()
+            .iter()
+            .map(|&(_, bd)| (*bd.device(), bd.devnode()))
+            .collect::<HashMap<_, _>>();
+        replay_pool(pool_uuid, &devnodes)
+    }
+
+    fn overcommit_cap(&self) -> Option<u64> {
+        self.thin_pool.overcommit_cap()
+    }
+
+    fn set_overcommit_cap(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        cap: Option<u64>,
+    ) -> StratisResult<bool> {
+        let old_cap = self.thin_pool.overcommit_cap();
+        if old_cap == cap {
+            return Ok(false);
+        }
+        self.thin_pool.set_overcommit_cap(cap);
+        if let Err(err) = self.write_metadata(pool_name) {
+            self.thin_pool.set_overcommit_cap(old_cap);
+            return Err(err);
+        }
+        record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn usage_thresholds(&self) -> Vec<u8> {
+        self.thin_pool.usage_thresholds().to_vec()
+    }
+
+    fn set_usage_thresholds(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        thresholds: &[u8],
+    ) -> StratisResult<bool> {
+        validate_usage_thresholds(thresholds)?;
+        let old_thresholds = self.thin_pool.usage_thresholds().to_vec();
+        if !self.thin_pool.set_usage_thresholds(thresholds) {
+            return Ok(false);
+        }
+        if let Err(err) = self.write_metadata(pool_name) {
+            self.thin_pool.set_usage_thresholds(&old_thresholds);
+            return Err(err);
+        }
+        record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn watermarks(&self) -> Watermarks {
+        self.thin_pool.watermarks()
+    }
+
+    fn set_watermarks(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        watermarks: Watermarks,
+    ) -> StratisResult<bool> {
+        validate_watermarks(watermarks)?;
+        let old_watermarks = self.thin_pool.watermarks();
+        if !self.thin_pool.set_watermarks(watermarks) {
+            return Ok(false);
+        }
+        if let Err(err) = self.write_metadata(pool_name) {
+            self.thin_pool.set_watermarks(old_watermarks);
+            return Err(err);
+        }
+        record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        // Check now, so that the low water mark of the data device is set
+        // from the new extend watermark.
+        if self.thin_pool.check(pool_uuid, &mut self.backstore)? {
+            self.write_metadata(pool_name)?;
+        }
+        Ok(true)
+    }
+
+    fn default_filesystem_size(&self) -> Sectors {
+        self.thin_pool.fs_size(None)
+    }
+
+    fn set_default_filesystem_size(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        size: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        let old_size = self.thin_pool.default_fs_size();
+        if old_size == size {
+            return Ok(false);
+        }
+        if let Some(size) = size {
+            validate_fs_size(size)?;
+            // Refuse a default that would exceed the overcommit cap even in
+            // a pool with no filesystems.
+            check_overcommit(
+                Sectors(0),
+                size,
+                self.backstore.datatier_size(),
+                self.thin_pool.overcommit_cap(),
+            )?;
+        }
+        self.thin_pool.set_default_fs_size(size);
+        if let Err(err) = self.write_metadata(pool_name) {
+            self.thin_pool.set_default_fs_size(old_size);
+            return Err(err);
+        }
+        record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn fs_limit(&self) -> u64 {
+        self.thin_pool.fs_limit()
+    }
+
+    fn set_fs_limit(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        limit: u64,
+    ) -> StratisResult<bool> {
+        let old_limit = self.thin_pool.fs_limit();
+        if old_limit == limit {
+            return Ok(false);
+        }
+        validate_fs_limit(self.thin_pool.filesystems().len(), limit)?;
+        self.thin_pool.set_fs_limit(limit);
+        if let Err(err) = self.write_metadata(pool_name) {
+            self.thin_pool.set_fs_limit(old_limit);
+            return Err(err);
+        }
+        record_change(ObjectKind::Pool, pool_uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn snapshot_schedule(&self, uuid: FilesystemUuid) -> Option<SnapshotSchedule> {
+        self.thin_pool.snapshot_schedules().get(uuid)
+    }
+
+    fn set_snapshot_schedule(
+        &mut self,
+        pool_name: &str,
+        uuid: FilesystemUuid,
+        schedule: Option<(u16, u32)>,
+    ) -> StratisResult<bool> {
+        let schedule = match schedule {
+            Some((interval, retain)) => {
+                validate_snapshot_retention(retain)?;
+                Some(SnapshotSchedule {
+                    interval: calculate_snapshot_interval!(interval),
+                    retain,
+                })
+            }
+            None => None,
+        };
+        if self.thin_pool.get_filesystem_by_uuid(uuid).is_none() {
+            return Err(StratisError::Structured(
+                ErrorEnum::NotFound,
+                ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+            ));
+        }
+
+        let old_schedule = self.thin_pool.snapshot_schedules().get(uuid);
+        if !self.thin_pool.snapshot_schedules_mut().set(uuid, schedule) {
+            return Ok(false);
+        }
+        if let Err(err) = self.write_metadata(pool_name) {
+            // A schedule that was removed is restored without the record
+            // of the snapshots that it took, which are then kept.
+            self.thin_pool
+                .snapshot_schedules_mut()
+                .set(uuid, old_schedule);
+            return Err(err);
+        }
+        record_change(ObjectKind::Filesystem, uuid, ChangeKind::Modified);
+        Ok(true)
+    }
+
+    fn run_snapshot_schedules(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        now: DateTime<Utc>,
+    ) -> StratisResult<(Vec<FilesystemUuid>, Vec<FilesystemUuid>)> {
+        let forgotten = self.thin_pool.forget_destroyed_schedules();
+        let due = self.thin_pool.snapshot_schedules().due(now);
+        if due.is_empty() {
+            if forgotten {
+                self.write_metadata(pool_name)?;
+            }
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let mut taken = Vec::new();
+        let mut destroyed = Vec::new();
+        for (origin, interval) in due {
+            let snapshot_name = match self.thin_pool.get_filesystem_by_uuid(origin) {
+                Some((name, _)) => scheduled_snapshot_name(&name, interval, now),
+                None => continue,
+            };
+            let snapshot =
+                match self.snapshot_filesystem(pool_uuid, pool_name, origin, &snapshot_name, true) {
+                    Ok((uuid, _)) => Some(uuid),
+                    Err(err) => {
+                        warn!(
+                            "Could not take scheduled snapshot {} of pool {}: {}",
+                            snapshot_name, pool_name, err
+                        );
+                        None
+                    }
+                };
+            taken.extend(snapshot);
+
+            let excess = self.thin_pool
+                .snapshot_schedules_mut()
+                .record_run(origin, now, snapshot);
+            for uuid in excess {
+                match self.destroy_filesystems(pool_name, &[uuid]) {
+                    Ok(results) => destroyed.extend(
+                        results
+                            .into_iter()
+                            .filter(|&(_, ref result)| result.is_ok())
+                            .map(|(uuid, _)| uuid),
+                    ),
+                    Err(err) => warn!(
+                        "Could not destroy scheduled snapshot {} of pool {}: {}",
+                        uuid, pool_name, err
+                    ),
+                }
+            }
+        }
+
+        self.thin_pool.forget_destroyed_schedules();
+        self.write_metadata(pool_name)?;
+        Ok((taken, destroyed))
+    }
+
+    fn run_housekeeping(&mut self, pool_uuid: PoolUuid, pool_name: &str) -> StratisResult<()> {
+        // The kernel raises an event when the meta device passes its low
+        // water mark, but the mark that stratisd extends at may differ from
+        // it, so the thin pool is checked here as well as on events.
+        if self.thin_pool.check(pool_uuid, &mut self.backstore)? {
+            self.write_metadata(pool_name)?;
+        }
+        self.backstore.save_deferred()?;
+        Ok(())
     }
 
     fn set_dbus_path(&mut self, path: MaybeDbusPath) -> () {
@@ -484,10 +1602,11 @@ mod tests {
     use devicemapper::{Bytes, IEC, SECTOR_SIZE};
 
     use super::super::super::devlinks;
-    use super::super::super::types::Redundancy;
+    use super::super::super::types::{MixedSizePolicy, Redundancy};
 
     use super::super::backstore::{find_all, get_metadata};
     use super::super::cmd;
+    use super::super::tests::fault::{self, Fault, FaultDev};
     use super::super::tests::{loopbacked, real};
 
     use super::*;
@@ -509,13 +1628,29 @@ mod tests {
         let (paths1, paths2) = paths.split_at(paths.len() / 2);
 
         let name1 = "name1";
-        let (uuid1, mut pool1) = StratPool::initialize(&name1, paths1, Redundancy::NONE).unwrap();
+        let (uuid1, mut pool1) = StratPool::initialize(
+            &name1,
+            paths1,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            None,
+        ).unwrap();
         invariant(&pool1, &name1);
 
         let metadata1 = pool1.record(name1);
 
         let name2 = "name2";
-        let (uuid2, mut pool2) = StratPool::initialize(&name2, paths2, Redundancy::NONE).unwrap();
+        let (uuid2, mut pool2) = StratPool::initialize(
+            &name2,
+            paths2,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            None,
+        ).unwrap();
         invariant(&pool2, &name2);
 
         let metadata2 = pool2.record(name2);
@@ -564,7 +1699,17 @@ mod tests {
     /// space required.
     fn test_empty_pool(paths: &[&Path]) -> () {
         assert_eq!(paths.len(), 0);
-        assert!(StratPool::initialize("stratis_test_pool", paths, Redundancy::NONE).is_err());
+        assert!(
+            StratPool::initialize(
+                "stratis_test_pool",
+                paths,
+                Redundancy::NONE,
+                MixedSizePolicy::Warn,
+                MetadataLayout::default(),
+                SignaturePolicy::Refuse,
+                None
+            ).is_err()
+        );
     }
 
     #[test]
@@ -588,14 +1733,23 @@ mod tests {
 
         let name = "stratis-test-pool";
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let (uuid, mut pool) = StratPool::initialize(&name, paths2, Redundancy::NONE).unwrap();
+        let (uuid, mut pool) = StratPool::initialize(
+            &name,
+            paths2,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            None,
+        ).unwrap();
         devlinks::pool_added(&name);
         invariant(&pool, &name);
 
         let metadata1 = pool.record(name);
         assert!(metadata1.backstore.cache_tier.is_none());
 
-        let (_, fs_uuid) = pool.create_filesystems(uuid, &name, &[("stratis-filesystem", None)])
+        let (_, fs_uuid) = pool
+            .create_filesystems(uuid, &name, &[("stratis-filesystem", None, false)], None)
             .unwrap()
             .pop()
             .unwrap();
@@ -625,7 +1779,7 @@ mod tests {
                 .unwrap();
         }
 
-        pool.add_blockdevs(uuid, &name, paths1, BlockDevTier::Cache)
+        pool.add_blockdevs(uuid, &name, paths1, BlockDevTier::Cache, None)
             .unwrap();
         invariant(&pool, &name);
 
@@ -704,12 +1858,21 @@ mod tests {
 
         let name = "stratis-test-pool";
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let (pool_uuid, mut pool) = StratPool::initialize(&name, paths1, Redundancy::NONE).unwrap();
+        let (pool_uuid, mut pool) = StratPool::initialize(
+            &name,
+            paths1,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            None,
+        ).unwrap();
         devlinks::pool_added(&name);
         invariant(&pool, &name);
 
         let fs_name = "stratis_test_filesystem";
-        let (_, fs_uuid) = pool.create_filesystems(pool_uuid, &name, &[(&fs_name, None)])
+        let (_, fs_uuid) = pool
+            .create_filesystems(pool_uuid, &name, &[(&fs_name, None, false)], None)
             .unwrap()
             .pop()
             .expect("just created one");
@@ -745,7 +1908,7 @@ mod tests {
                 }
             }
 
-            pool.add_blockdevs(pool_uuid, &name, paths2, BlockDevTier::Data)
+            pool.add_blockdevs(pool_uuid, &name, paths2, BlockDevTier::Data, None)
                 .unwrap();
             assert!(match pool.thin_pool.extend_state() {
                 PoolExtendState::Good => true,
@@ -778,4 +1941,408 @@ mod tests {
             test_add_datadevs,
         );
     }
+
+    /// Verify that recovering the addition of a device that is in the pool
+    /// leaves it in the pool, and that recovering its removal removes it.
+    fn test_recover_jobs(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (paths1, paths2) = paths.split_at(1);
+
+        let name = "stratis-test-pool";
+        devlinks::cleanup_devlinks(Vec::new().into_iter());
+        let (pool_uuid, mut pool) = StratPool::initialize(
+            &name,
+            paths1,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            None,
+        ).unwrap();
+        devlinks::pool_added(&name);
+
+        let uuids = pool.add_blockdevs(pool_uuid, &name, paths2, BlockDevTier::Data, None)
+            .unwrap();
+        let record = |kind| JobRecord {
+            id: Uuid::new_v4(),
+            pool_uuid,
+            started: Utc::now().to_rfc3339(),
+            kind,
+            progress: None,
+        };
+
+        let paths = paths2.iter().map(|path| path.to_path_buf()).collect();
+        pool.recover_job(
+            pool_uuid,
+            &name,
+            &record(JobKind::InitializeDevices { paths }),
+        ).unwrap();
+        for &uuid in &uuids {
+            assert!(pool.get_strat_blockdev(uuid).is_some());
+        }
+        invariant(&pool, &name);
+
+        let kind = JobKind::RemoveBlockDevs {
+            uuids: uuids.clone(),
+        };
+        pool.recover_job(pool_uuid, &name, &record(kind)).unwrap();
+        for &uuid in &uuids {
+            assert!(pool.get_strat_blockdev(uuid).is_none());
+        }
+        invariant(&pool, &name);
+    }
+
+    #[test]
+    pub fn loop_test_recover_jobs() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(2, 3, None),
+            test_recover_jobs,
+        );
+    }
+
+    #[test]
+    pub fn real_test_recover_jobs() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(2, None, None),
+            test_recover_jobs,
+        );
+    }
+
+    /// Verify that the overcommit cap prevents creating a filesystem that
+    /// would take the pool beyond it, that it is saved in the pool's
+    /// metadata, and that the filesystem can be created once it is removed.
+    fn test_overcommit_cap(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) = StratPool::initialize(
+            &name,
+            paths,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            None,
+        ).unwrap();
+        invariant(&pool, &name);
+
+        assert_eq!(pool.overcommit_percent(), Some(0));
+        assert!(pool.set_overcommit_cap(uuid, name, Some(100)).unwrap());
+        assert!(!pool.set_overcommit_cap(uuid, name, Some(100)).unwrap());
+
+        cmd::udev_settle().unwrap();
+        let pools = find_all().unwrap();
+        let pool_save = get_metadata(uuid, pools.get(&uuid).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(pool_save.thinpool_dev.overcommit_cap, Some(100));
+
+        let size = 2u64 * pool.total_physical_size();
+        assert!(
+            pool.create_filesystems(uuid, name, &[("fs", Some(size), false)], None)
+                .is_err()
+        );
+        assert!(pool.filesystems().is_empty());
+
+        assert!(pool.set_overcommit_cap(uuid, name, None).unwrap());
+        pool.create_filesystems(uuid, name, &[("fs", Some(size), false)], None)
+            .unwrap();
+        assert!(pool.overcommit_percent().unwrap() >= 200);
+    }
+
+    #[test]
+    pub fn loop_test_overcommit_cap() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_overcommit_cap,
+        );
+    }
+
+    #[test]
+    pub fn real_test_overcommit_cap() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(1, None, None),
+            test_overcommit_cap,
+        );
+    }
+
+    /// Verify that an encrypted pool is made on dm-crypt devices, that its
+    /// metadata records that it is encrypted, that no devices can be added
+    /// to it, and that destroying it closes the dm-crypt devices.
+    fn test_encrypted_pool(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) = StratPool::initialize_encrypted(
+            &name,
+            paths,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            "passphrase",
+            None,
+        ).unwrap();
+        invariant(&pool, &name);
+        assert!(pool.encrypted());
+
+        let devnodes = pool.blockdevs()
+            .iter()
+            .map(|&(_, bd)| bd.devnode())
+            .collect::<Vec<_>>();
+        assert!(devnodes.iter().all(|d| d.starts_with("/dev/mapper")));
+
+        cmd::udev_settle().unwrap();
+        let pools = find_all().unwrap();
+        let pool_save = get_metadata(uuid, pools.get(&uuid).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(pool_save.encrypted, Some(true));
+        assert!(
+            pool_save
+                .creation_params
+                .map_or(false, |params| params.encrypted)
+        );
+
+        assert!(
+            pool.add_blockdevs(uuid, name, paths, BlockDevTier::Data, None)
+                .is_err()
+        );
+
+        pool.destroy(false).unwrap();
+        assert!(devnodes.iter().all(|d| !d.exists()));
+    }
+
+    #[test]
+    pub fn loop_test_encrypted_pool() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_encrypted_pool,
+        );
+    }
+
+    #[test]
+    pub fn real_test_encrypted_pool() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(1, None, None),
+            test_encrypted_pool,
+        );
+    }
+
+    /// Verify that each write of a pool's metadata advances the time of its
+    /// last update, and that when the pool has few enough blockdevs that
+    /// the metadata is written to all of them, none is behind.
+    fn test_last_metadata_update(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) = StratPool::initialize(
+            &name,
+            paths,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            None,
+        ).unwrap();
+        invariant(&pool, &name);
+
+        let first = pool.last_metadata_update().unwrap();
+        assert_eq!(pool.metadata_behind_blockdevs(), vec![]);
+
+        assert!(pool.set_overcommit_cap(uuid, name, Some(100)).unwrap());
+        assert!(pool.last_metadata_update().unwrap() > first);
+        assert_eq!(pool.metadata_behind_blockdevs(), vec![]);
+    }
+
+    #[test]
+    pub fn loop_test_last_metadata_update() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_last_metadata_update,
+        );
+    }
+
+    #[test]
+    pub fn real_test_last_metadata_update() {
+        real::test_with_spec(
+            real::DeviceLimits::Range(1, 10, None, None),
+            test_last_metadata_update,
+        );
+    }
+
+    /// Make a pool on the fault devices.
+    fn initialize_on_faults(name: &str, devs: &[FaultDev]) -> (PoolUuid, StratPool) {
+        let devnodes = devs.iter().map(|dev| dev.devnode()).collect::<Vec<_>>();
+        let paths = devnodes.iter().map(|p| p.as_path()).collect::<Vec<_>>();
+        let (uuid, pool) = StratPool::initialize(
+            name,
+            &paths,
+            Redundancy::NONE,
+            MixedSizePolicy::Warn,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+            None,
+        ).unwrap();
+        invariant(&pool, name);
+        (uuid, pool)
+    }
+
+    /// Tear down the pool, stop injecting faults, and power cycle the fault
+    /// devices. Then set the pool up again from the fault devices, as
+    /// stratisd would on starting.
+    fn restart_on_faults(
+        uuid: PoolUuid,
+        mut pool: StratPool,
+        devs: &mut [FaultDev],
+    ) -> (Name, StratPool) {
+        pool.teardown().unwrap();
+        for dev in devs.iter_mut() {
+            dev.set_fault(Fault::Nothing);
+            dev.power_cycle();
+        }
+
+        let devnodes = fault::devnodes(devs);
+        let metadata = get_metadata(uuid, &devnodes).unwrap().unwrap();
+        let (name, pool) = StratPool::setup(uuid, &devnodes, &metadata).unwrap();
+        invariant(&pool, &name);
+        (name, pool)
+    }
+
+    /// Verify that if the writes to one blockdev are lost while the
+    /// metadata is saved, as if the power failed before they reached the
+    /// disk, the pool is set up again with the metadata saved to the other
+    /// blockdevs.
+    fn test_dropped_metadata_save(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        fault::test_with_faults(paths, |devs| {
+            let name = "stratis-test-pool";
+            let (uuid, mut pool) = initialize_on_faults(name, devs);
+
+            devs[0].set_fault(Fault::DropWrites);
+            assert!(pool.set_overcommit_cap(uuid, name, Some(100)).unwrap());
+
+            let (_, pool) = restart_on_faults(uuid, pool, devs);
+            assert_eq!(pool.overcommit_cap(), Some(100));
+        });
+    }
+
+    #[test]
+    pub fn loop_test_dropped_metadata_save() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(2, 3, None),
+            test_dropped_metadata_save,
+        );
+    }
+
+    #[test]
+    pub fn real_test_dropped_metadata_save() {
+        real::test_with_spec(
+            real::DeviceLimits::Range(2, 3, None, None),
+            test_dropped_metadata_save,
+        );
+    }
+
+    /// Verify that if the writes to one blockdev fail while the metadata is
+    /// saved, the save succeeds, the blockdev is reported as behind, and
+    /// the pool is set up again with the metadata saved to the other
+    /// blockdevs.
+    fn test_failed_metadata_save(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        fault::test_with_faults(paths, |devs| {
+            let name = "stratis-test-pool";
+            let (uuid, mut pool) = initialize_on_faults(name, devs);
+
+            devs[0].set_fault(Fault::ErrorWrites);
+            assert!(pool.set_overcommit_cap(uuid, name, Some(100)).unwrap());
+            assert_eq!(pool.metadata_behind_blockdevs().len(), 1);
+
+            let (_, pool) = restart_on_faults(uuid, pool, devs);
+            assert_eq!(pool.overcommit_cap(), Some(100));
+        });
+    }
+
+    #[test]
+    pub fn loop_test_failed_metadata_save() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(2, 3, None),
+            test_failed_metadata_save,
+        );
+    }
+
+    #[test]
+    pub fn real_test_failed_metadata_save() {
+        real::test_with_spec(
+            real::DeviceLimits::Range(2, 3, None, None),
+            test_failed_metadata_save,
+        );
+    }
+
+    /// Verify that if every write is lost while a filesystem is made, the
+    /// thin pool and the MDV are found as they were before, and the pool is
+    /// set up again without the filesystem.
+    fn test_dropped_thin_writes(paths: &[&Path]) {
+        fault::test_with_faults(paths, |devs| {
+            let name = "stratis-test-pool";
+            let (uuid, mut pool) = initialize_on_faults(name, devs);
+
+            for dev in devs.iter_mut() {
+                dev.set_fault(Fault::DropWrites);
+            }
+            pool.create_filesystems(uuid, name, &[("stratis-filesystem", None, false)], None)
+                .unwrap();
+            assert!(pool.has_filesystems());
+
+            let (_, pool) = restart_on_faults(uuid, pool, devs);
+            assert!(!pool.has_filesystems());
+        });
+    }
+
+    #[test]
+    pub fn loop_test_dropped_thin_writes() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_dropped_thin_writes,
+        );
+    }
+
+    #[test]
+    pub fn real_test_dropped_thin_writes() {
+        real::test_with_spec(
+            real::DeviceLimits::Range(1, 3, None, None),
+            test_dropped_thin_writes,
+        );
+    }
+
+    /// Verify that if the first copy of the sigblock of a blockdev can not
+    /// be read, as if the power failed while it was written, the pool is
+    /// set up again from the second copy, which restores the first.
+    fn test_bad_sigblock_sector(paths: &[&Path]) {
+        fault::test_with_faults(paths, |devs| {
+            let name = "stratis-test-pool";
+            let (uuid, mut pool) = initialize_on_faults(name, devs);
+            pool.teardown().unwrap();
+
+            devs[0].set_fault(Fault::BadSectors(vec![Sectors(1)]));
+            devs[0].power_cycle();
+
+            let devnodes = fault::devnodes(devs);
+            let metadata = get_metadata(uuid, &devnodes).unwrap().unwrap();
+            let (name, pool) = StratPool::setup(uuid, &devnodes, &metadata).unwrap();
+            invariant(&pool, &name);
+        });
+    }
+
+    #[test]
+    pub fn loop_test_bad_sigblock_sector() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_bad_sigblock_sector,
+        );
+    }
+
+    #[test]
+    pub fn real_test_bad_sigblock_sector() {
+        real::test_with_spec(
+            real::DeviceLimits::Range(1, 3, None, None),
+            test_bad_sigblock_sector,
+        );
+    }
 }
@@ -0,0 +1,65 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to set the CPU and I/O scheduling priority of the threads that do a
+// pool's background work, so that the housekeeping of one pool does not
+// compete with the I/O of a pool that is sensitive to latency.
+
+use std::io;
+
+use nix::libc;
+
+use stratis::StratisResult;
+
+use super::super::types::{IoClass, WorkerPriority};
+
+// From linux/ioprio.h
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+const IOPRIO_CLASS_BE: libc::c_int = 2;
+const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+/// The value that ioprio_set(2) takes for the I/O class and level.
+fn ioprio(priority: &WorkerPriority) -> libc::c_int {
+    match priority.io_class {
+        IoClass::BestEffort => {
+            IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT | libc::c_int::from(priority.io_level)
+        }
+        IoClass::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+    }
+}
+
+/// Set the niceness and I/O priority of the calling thread. Linux keeps
+/// both for each thread, so no other thread of stratisd is affected.
+pub fn set_thread_priority(priority: &WorkerPriority) -> StratisResult<()> {
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    let niceness = libc::c_int::from(priority.niceness);
+    let which = libc::PRIO_PROCESS as libc::__priority_which_t;
+    if unsafe { libc::setpriority(which, tid, niceness) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    // A "who" of 0 is the calling thread.
+    let value = ioprio(priority);
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, value) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the class and level are packed as linux/ioprio.h
+    /// packs them, and that the idle class has no level.
+    fn test_ioprio() {
+        let mut priority = WorkerPriority::default();
+        assert_eq!(ioprio(&priority), 0x4004);
+
+        priority.io_class = IoClass::Idle;
+        assert_eq!(ioprio(&priority), 0x6000);
+    }
+}
@@ -0,0 +1,76 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Repairs made while setting up pools, to bring DM device names and the
+// links under /stratis back in line with the pools' metadata. These are
+// mostly needed after an unclean shutdown.
+
+use std::collections::HashSet;
+
+use devicemapper::{DevId, DmNameBuf};
+
+use stratis::StratisResult;
+
+use super::super::devlinks::DevlinkRepair;
+
+use super::dm::get_dm;
+
+/// A repair made while setting up pools.
+#[derive(Debug)]
+pub enum Repair {
+    /// A Stratis DM device had a name other than its UUID, and was renamed.
+    DmDeviceRenamed {
+        old_name: DmNameBuf,
+        new_name: DmNameBuf,
+    },
+    /// A link or directory under /stratis was created, replaced or removed.
+    Devlink(DevlinkRepair),
+}
+
+/// Rename every Stratis DM device whose name is not its UUID. Stratis gives
+/// its devices the same name and UUID, so a mismatch is left only by an
+/// interrupted rename or by a device set up outside stratisd. Setting up a
+/// pool looks devices up by name, so this must be done first.
+/// A device that can not be renamed is logged and left alone.
+pub fn repair_dm_names() -> StratisResult<Vec<Repair>> {
+    let dm = get_dm();
+    let names: HashSet<DmNameBuf> = dm.list_devices()?
+        .into_iter()
+        .map(|(name, _, _)| name)
+        .collect();
+
+    let mut repairs = Vec::new();
+    for old_name in &names {
+        let uuid = match dm.device_info(&DevId::Name(old_name))?.uuid() {
+            Some(uuid) => uuid.to_string(),
+            None => continue,
+        };
+        if !uuid.starts_with("stratis-") || uuid == old_name.to_string() {
+            continue;
+        }
+
+        let new_name = DmNameBuf::new(uuid)?;
+        if names.contains(&new_name) {
+            warn!(
+                "DM device {} should be named {}, but that name is taken",
+                &**old_name, &*new_name
+            );
+            continue;
+        }
+
+        if let Err(err) = dm.device_rename(old_name, &DevId::Name(&new_name)) {
+            warn!(
+                "unable to rename DM device {} to {}, reason {:?}",
+                &**old_name, &*new_name, err
+            );
+            continue;
+        }
+        info!("renamed DM device {} to {}", &**old_name, &*new_name);
+        repairs.push(Repair::DmDeviceRenamed {
+            old_name: old_name.clone(),
+            new_name,
+        });
+    }
+    Ok(repairs)
+}
@@ -0,0 +1,222 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Replay the generations of a pool's metadata that its MDAs hold, older
+// first, through the checks that setting up the pool makes of them. The
+// generations are checked against the layouts recorded in the devices' BDAs,
+// in memory, so nothing is set up and the devices are not written. A crash
+// while metadata is being saved may leave a newer generation that does not
+// follow from the older, which this is meant to find.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use devicemapper::{Device, Sectors};
+use serde_json;
+
+use stratis::StratisResult;
+
+use super::super::types::PoolUuid;
+use super::backstore::{check_allocations, get_metadata_generations, MetadataGenerations};
+use super::pool::check_metadata;
+use super::serde_structs::PoolSave;
+
+/// The name of the generation at index, of count generations.
+fn generation_name(index: usize, count: usize) -> &'static str {
+    if index + 1 == count {
+        "newer generation"
+    } else {
+        "older generation"
+    }
+}
+
+/// Whether every segment in older lies within some segment in newer. The
+/// engine never gives up space, though it may merge adjacent segments.
+fn segments_kept(older: &[(Sectors, Sectors)], newer: &[(Sectors, Sectors)]) -> bool {
+    older.iter().all(|&(start, length)| {
+        newer
+            .iter()
+            .any(|&(n_start, n_length)| n_start <= start && start + length <= n_start + n_length)
+    })
+}
+
+/// Describe what is missing from the metadata that the checks made when
+/// setting up a pool take to be there, if anything.
+fn missing_parts(pool_save: &PoolSave) -> Option<&'static str> {
+    let flex_devs = &pool_save.flex_devs;
+    if pool_save.backstore.cap.allocs.is_empty()
+        || pool_save.backstore.data_tier.blockdev.allocs.is_empty()
+    {
+        Some("no allocations from the backstore are recorded")
+    } else if flex_devs.meta_dev.is_empty()
+        || flex_devs.thin_meta_dev.is_empty()
+        || flex_devs.thin_data_dev.is_empty()
+        || flex_devs.thin_meta_dev_spare.is_empty()
+    {
+        Some("a flex device has no segments")
+    } else {
+        None
+    }
+}
+
+/// Describe each way in which newer does not follow from older, as it
+/// would if it had been saved by the engine after a change to the pool.
+fn compare_generations(older: &PoolSave, newer: &PoolSave) -> Vec<String> {
+    let mut anomalies = Vec::new();
+
+    let (older_next, newer_next) = (
+        older.backstore.cap.allocs[0].1,
+        newer.backstore.cap.allocs[0].1,
+    );
+    if newer_next < older_next {
+        anomalies.push(format!(
+            "space allocated from the cap device shrank from {} to {}",
+            older_next, newer_next
+        ));
+    }
+
+    let segments = |pool_save: &PoolSave| {
+        let mut segments = HashMap::new();
+        for seg in &pool_save.backstore.data_tier.blockdev.allocs[0] {
+            segments
+                .entry(seg.parent)
+                .or_insert_with(Vec::new)
+                .push((seg.start, seg.length));
+        }
+        segments
+    };
+    let newer_segments = segments(newer);
+    for (dev_uuid, older_segs) in segments(older) {
+        match newer_segments.get(&dev_uuid) {
+            Some(newer_segs) if segments_kept(&older_segs, newer_segs) => {}
+            Some(_) => anomalies.push(format!(
+                "space allocated on blockdev {} in the older generation is not in the newer",
+                dev_uuid
+            )),
+            None => anomalies.push(format!(
+                "blockdev {} holds data in the older generation, but none in the newer",
+                dev_uuid
+            )),
+        }
+    }
+
+    for &(name, older_segs, newer_segs) in &[
+        ("MDV", &older.flex_devs.meta_dev, &newer.flex_devs.meta_dev),
+        (
+            "thin data device",
+            &older.flex_devs.thin_data_dev,
+            &newer.flex_devs.thin_data_dev,
+        ),
+    ] {
+        if !segments_kept(older_segs, newer_segs) {
+            anomalies.push(format!(
+                "space of the {} in the older generation is not in the newer",
+                name
+            ));
+        }
+    }
+
+    anomalies
+}
+
+/// Replay the generations of a pool's metadata, older first. Return a
+/// description of each anomaly found: a generation that can not be read or
+/// parsed, that setting up the pool would refuse, or that does not follow
+/// from the generation before it.
+pub fn replay_generations(generations: &MetadataGenerations) -> Vec<String> {
+    let count = generations.generations.len();
+    let mut anomalies = Vec::new();
+    let mut previous: Option<PoolSave> = None;
+    for (index, data) in generations.generations.iter().enumerate() {
+        let name = generation_name(index, count);
+        let pool_save = match *data {
+            Some(ref data) => match serde_json::from_slice::<PoolSave>(data) {
+                Ok(pool_save) => pool_save,
+                Err(err) => {
+                    anomalies.push(format!("{} can not be parsed: {}", name, err));
+                    previous = None;
+                    continue;
+                }
+            },
+            None => {
+                anomalies.push(format!("{} can not be read", name));
+                previous = None;
+                continue;
+            }
+        };
+
+        if let Some(missing) = missing_parts(&pool_save) {
+            anomalies.push(format!("{} is incomplete: {}", name, missing));
+            previous = None;
+            continue;
+        }
+        if let Err(err) = check_metadata(&pool_save) {
+            anomalies.push(format!("{} is inconsistent: {}", name, err));
+        }
+        if let Err(err) = check_allocations(&pool_save.backstore, &generations.devices) {
+            anomalies.push(format!("{} can not be set up: {}", name, err));
+        }
+        if let Some(ref older) = previous {
+            anomalies.extend(compare_generations(older, &pool_save));
+        }
+        previous = Some(pool_save);
+    }
+    anomalies
+}
+
+/// Replay the generations of the metadata of the pool held on devnodes.
+/// Return a description of each anomaly found, as replay_generations()
+/// does.
+#[allow(implicit_hasher)]
+pub fn replay_pool(
+    pool_uuid: PoolUuid,
+    devnodes: &HashMap<Device, PathBuf>,
+) -> StratisResult<Vec<String>> {
+    match get_metadata_generations(pool_uuid, devnodes)? {
+        Some(generations) => Ok(replay_generations(&generations)),
+        None => Ok(vec!["no metadata found".to_owned()]),
+    }
+}
+
+/// Replay the generations of the metadata of a pool that could not be set
+/// up, and log what is found, to help tell a crash while metadata was
+/// being saved from other causes.
+#[allow(implicit_hasher)]
+pub fn log_replay(pool_uuid: PoolUuid, devnodes: &HashMap<Device, PathBuf>) {
+    match replay_pool(pool_uuid, devnodes) {
+        Ok(ref anomalies) if anomalies.is_empty() => info!(
+            "metadata generations of pool {} replay without anomalies",
+            pool_uuid.simple()
+        ),
+        Ok(anomalies) => for anomaly in anomalies {
+            warn!(
+                "replaying metadata of pool {}: {}",
+                pool_uuid.simple(),
+                anomaly
+            );
+        },
+        Err(err) => warn!(
+            "unable to replay metadata of pool {}: {}",
+            pool_uuid.simple(),
+            err
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that a segment is kept if it lies within a segment of the
+    /// newer generation, however the segments have been merged.
+    fn test_segments_kept() {
+        let older = [(Sectors(0), Sectors(8)), (Sectors(8), Sectors(8))];
+        assert!(segments_kept(&older, &[(Sectors(0), Sectors(32))]));
+        assert!(segments_kept(&older, &older));
+        assert!(segments_kept(&[], &older));
+        assert!(!segments_kept(&older, &[(Sectors(0), Sectors(12))]));
+        assert!(!segments_kept(&older, &[]));
+    }
+}
@@ -12,11 +12,16 @@
 // can convert to or from them when saving our current state, or
 // restoring state from saved metadata.
 
+use chrono::{TimeZone, Utc};
 use serde::Serialize;
 
 use devicemapper::{Sectors, ThinDevId};
 
-use super::super::types::{DevUuid, FilesystemUuid};
+use super::super::schedule::{ScheduleState, SnapshotSchedules};
+use super::super::types::{
+    CreationParams, Creator, DevUuid, FilesystemUuid, IoClass, Redundancy, SnapshotInterval,
+    SnapshotSchedule, WorkerPriority,
+};
 
 /// Implements saving struct data to a serializable form. The form should be
 /// sufficient, in conjunction with the environment, to reconstruct the
@@ -25,6 +30,131 @@ pub trait Recordable<T: Serialize> {
     fn record(&self) -> T;
 }
 
+impl Recordable<CreatorSave> for Creator {
+    fn record(&self) -> CreatorSave {
+        CreatorSave {
+            uid: self.uid,
+            process: self.process.clone(),
+        }
+    }
+}
+
+impl<'a> From<&'a CreatorSave> for Creator {
+    fn from(save: &'a CreatorSave) -> Creator {
+        Creator {
+            uid: save.uid,
+            process: save.process.clone(),
+        }
+    }
+}
+
+impl Recordable<CreationParamsSave> for CreationParams {
+    fn record(&self) -> CreationParamsSave {
+        CreationParamsSave {
+            mda_size: self.mda_size,
+            chunk_size: self.chunk_size,
+            redundancy: self.redundancy.to_string(),
+            encrypted: self.encrypted,
+            daemon_version: self.daemon_version.clone(),
+        }
+    }
+}
+
+impl<'a> From<&'a CreationParamsSave> for CreationParams {
+    fn from(save: &'a CreationParamsSave) -> CreationParams {
+        let redundancy = Redundancy::from_name(&save.redundancy).unwrap_or_else(|| {
+            warn!(
+                "unknown redundancy {} among creation parameters; taking it as {}",
+                save.redundancy,
+                Redundancy::NONE
+            );
+            Redundancy::NONE
+        });
+        CreationParams {
+            mda_size: save.mda_size,
+            chunk_size: save.chunk_size,
+            redundancy,
+            encrypted: save.encrypted,
+            daemon_version: save.daemon_version.clone(),
+        }
+    }
+}
+
+impl Recordable<WorkerPrioritySave> for WorkerPriority {
+    fn record(&self) -> WorkerPrioritySave {
+        WorkerPrioritySave {
+            niceness: self.niceness,
+            io_class: self.io_class.to_string(),
+            io_level: self.io_level,
+        }
+    }
+}
+
+impl<'a> From<&'a WorkerPrioritySave> for WorkerPriority {
+    fn from(save: &'a WorkerPrioritySave) -> WorkerPriority {
+        let io_class = IoClass::from_name(&save.io_class).unwrap_or_else(|| {
+            warn!(
+                "unknown I/O class {}; running workers in class {}",
+                save.io_class,
+                IoClass::BestEffort
+            );
+            IoClass::BestEffort
+        });
+        WorkerPriority {
+            niceness: save.niceness,
+            io_class,
+            io_level: save.io_level,
+        }
+    }
+}
+
+impl Recordable<Vec<SnapshotScheduleSave>> for SnapshotSchedules {
+    fn record(&self) -> Vec<SnapshotScheduleSave> {
+        let mut saves = self.states()
+            .iter()
+            .map(|(&uuid, state)| SnapshotScheduleSave {
+                filesystem: uuid,
+                interval: state.schedule.interval.to_string(),
+                retain: state.schedule.retain,
+                last_run: state.last_run.map(|t| t.timestamp() as u64),
+                snapshots: state.snapshots.clone(),
+            })
+            .collect::<Vec<_>>();
+        saves.sort_by_key(|save| save.filesystem);
+        saves
+    }
+}
+
+impl<'a> From<&'a [SnapshotScheduleSave]> for SnapshotSchedules {
+    fn from(saves: &'a [SnapshotScheduleSave]) -> SnapshotSchedules {
+        SnapshotSchedules::new(
+            saves
+                .iter()
+                .filter_map(|save| match SnapshotInterval::from_name(&save.interval) {
+                    Some(interval) => Some((
+                        save.filesystem,
+                        ScheduleState {
+                            schedule: SnapshotSchedule {
+                                interval,
+                                retain: save.retain,
+                            },
+                            last_run: save.last_run.map(|t| Utc.timestamp(t as i64, 0)),
+                            snapshots: save.snapshots.clone(),
+                        },
+                    )),
+                    None => {
+                        warn!(
+                            "unknown snapshot interval {}; dropping the schedule of filesystem {}",
+                            save.interval, save.filesystem
+                        );
+                        None
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
 // ALL structs that represent variable length metadata in pre-order
 // depth-first traversal order. Note that when organized by types rather than
 // values the structure is a DAG not a tree. This just means that there are
@@ -35,6 +165,39 @@ pub struct PoolSave {
     pub backstore: BackstoreSave,
     pub flex_devs: FlexDevsSave,
     pub thinpool_dev: ThinPoolDevSave,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_writes: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<CreatorSave>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_priority: Option<WorkerPrioritySave>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creation_params: Option<CreationParamsSave>,
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CreatorSave {
+    pub uid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CreationParamsSave {
+    pub mda_size: Sectors,
+    pub chunk_size: Sectors,
+    pub redundancy: String,
+    pub encrypted: bool,
+    pub daemon_version: String,
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WorkerPrioritySave {
+    pub niceness: i8,
+    pub io_class: String,
+    pub io_level: u8,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -43,11 +206,15 @@ pub struct BackstoreSave {
     pub cap: CapSave,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cache_tier: Option<CacheTierSave>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raid: Option<RaidSave>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct DataTierSave {
     pub blockdev: BlockDevSave,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocation_strategy: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -80,6 +247,24 @@ pub struct CapSave {
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CacheTierSave {
     pub blockdev: BlockDevSave,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volatile: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RaidSave {
+    pub level: String,
+    pub members: Vec<RaidMemberSave>,
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RaidMemberSave {
+    pub meta: Vec<BaseDevSave>,
+    pub data: Vec<BaseDevSave>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -93,6 +278,30 @@ pub struct FlexDevsSave {
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ThinPoolDevSave {
     pub data_block_size: Sectors,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overcommit_cap: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_fs_size: Option<Sectors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_thresholds: Option<Vec<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warn_watermark: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extend_watermark: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fs_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_schedules: Option<Vec<SnapshotScheduleSave>>,
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SnapshotScheduleSave {
+    pub filesystem: FilesystemUuid,
+    pub interval: String,
+    pub retain: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<u64>, // Unix timestamp
+    pub snapshots: Vec<FilesystemUuid>,
 }
 
 // Struct representing filesystem metadata. This metadata is not held in the
@@ -105,4 +314,16 @@ pub struct FilesystemSave {
     pub thin_id: ThinDevId,
     pub size: Sectors,
     pub created: u64, // Unix timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guaranteed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<CreatorSave>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_ahead: Option<Sectors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_limit: Option<Sectors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<FilesystemUuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protected: Option<bool>,
 }
@@ -0,0 +1,161 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Check that the kernel provides the DM targets stratisd uses, at versions
+// recent enough, before they are used. The versions the kernel reports are
+// cached, so that each check does not cost an ioctl. The kernel reports the
+// version of a target only once its module is loaded, which it is when a
+// device is first made with the target; until then, a target whose module
+// the kernel may load passes the check, and its version is checked once it
+// has been loaded.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
+
+use super::super::types::CacheType;
+use super::capabilities::kernel_modules;
+use super::dm::get_dm;
+
+/// A DM target on which stratisd depends, and the least version of the
+/// target with which stratisd works.
+#[derive(Debug)]
+pub struct RequiredTarget {
+    /// The name of the target, as in a DM table.
+    name: &'static str,
+    /// The name of the kernel module that provides the target.
+    module: &'static str,
+    /// The least version required.
+    version: (u32, u32, u32),
+}
+
+pub const LINEAR: RequiredTarget = RequiredTarget {
+    name: "linear",
+    module: "dm-mod",
+    version: (1, 1, 0),
+};
+
+pub const THIN_POOL: RequiredTarget = RequiredTarget {
+    name: "thin-pool",
+    module: "dm-thin-pool",
+    version: (1, 9, 0),
+};
+
+pub const THIN: RequiredTarget = RequiredTarget {
+    name: "thin",
+    module: "dm-thin-pool",
+    version: (1, 9, 0),
+};
+
+pub const CACHE: RequiredTarget = RequiredTarget {
+    name: "cache",
+    module: "dm-cache",
+    version: (1, 6, 0),
+};
+
+pub const WRITECACHE: RequiredTarget = RequiredTarget {
+    name: "writecache",
+    module: "dm-writecache",
+    version: (1, 0, 0),
+};
+
+pub const RAID: RequiredTarget = RequiredTarget {
+    name: "raid",
+    module: "dm-raid",
+    version: (1, 9, 0),
+};
+
+/// The targets without which no pool can be set up.
+pub const CORE_TARGETS: &[&RequiredTarget] = &[&LINEAR, &THIN_POOL, &THIN];
+
+/// The target with which a cache of the given type is made.
+pub fn cache_target(cache_type: CacheType) -> &'static RequiredTarget {
+    match cache_type {
+        CacheType::Cache => &CACHE,
+        CacheType::WriteCache => &WRITECACHE,
+    }
+}
+
+lazy_static! {
+    static ref TARGET_VERSIONS: Mutex<HashMap<String, (u32, u32, u32)>> =
+        Mutex::new(HashMap::new());
+}
+
+fn format_version(version: (u32, u32, u32)) -> String {
+    format!("{}.{}.{}", version.0, version.1, version.2)
+}
+
+/// Check a target against the version the kernel reports for it, if any.
+/// If the kernel reports none, the target passes only if its module is
+/// among those the kernel may load or has built in.
+fn check_target<F>(
+    target: &RequiredTarget,
+    found: Option<(u32, u32, u32)>,
+    module_available: F,
+) -> StratisResult<()>
+where
+    F: FnOnce() -> bool,
+{
+    match found {
+        Some(found) if found >= target.version => Ok(()),
+        Some(found) => Err(StratisError::Structured(
+            ErrorEnum::Error,
+            ErrorPayload::new("DmTargetTooOld")
+                .param("target", target.name)
+                .param("required", format_version(target.version))
+                .param("found", format_version(found)),
+        )),
+        None if module_available() => Ok(()),
+        None => Err(StratisError::Structured(
+            ErrorEnum::Error,
+            ErrorPayload::new("DmTargetMissing")
+                .param("target", target.name)
+                .param("required", format_version(target.version)),
+        )),
+    }
+}
+
+/// Return an error if the kernel does not provide the target at the
+/// version required. Once the kernel has reported a version recent enough,
+/// the target is not checked again.
+pub fn require_target(target: &RequiredTarget) -> StratisResult<()> {
+    let mut versions = TARGET_VERSIONS.lock().expect("no thread panics holding the lock");
+    let cached = versions.get(target.name).cloned();
+    if cached.map_or(false, |version| version >= target.version) {
+        return Ok(());
+    }
+    for (name, major, minor, patch) in get_dm().list_versions()? {
+        versions.insert(name, (major, minor, patch));
+    }
+    let found = versions.get(target.name).cloned();
+    check_target(target, found, || kernel_modules().contains(target.module))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that a target passes if its version is at least that
+    /// required, or if it is not loaded but its module may be, and that
+    /// otherwise the error names the target and the version required.
+    fn test_check_target() {
+        assert!(check_target(&THIN_POOL, Some((1, 9, 0)), || false).is_ok());
+        assert!(check_target(&THIN_POOL, Some((2, 0, 0)), || false).is_ok());
+        assert!(check_target(&THIN_POOL, None, || true).is_ok());
+
+        let err = check_target(&THIN_POOL, Some((1, 8, 9)), || true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "kernel too old: needs dm-thin-pool >= 1.9.0, but has 1.8.9"
+        );
+
+        let err = check_target(&THIN_POOL, None, || false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "kernel does not provide dm-thin-pool, needs dm-thin-pool >= 1.9.0"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Fault devices, which stand between a pool and the devices a test is
+// given, and through which the test can make writes go missing or fail,
+// or sectors go bad, as they might when the power fails. Writes are made to
+// go missing or fail with dm-flakey, and sectors to go bad with dm-dust.
+// The devicemapper crate supports neither target, so the devices are
+// managed here with DM ioctls directly.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::panic;
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use devicemapper::{
+    devnode_to_devno, DevId, Device, DmFlags, DmNameBuf, DmOptions, Sectors, TargetTypeBuf,
+};
+
+use super::super::backstore::blkdev_size;
+use super::super::cmd;
+use super::super::dm::get_dm;
+
+use super::util::clean_up;
+
+/// A fault that a fault device injects.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// Pass all I/O through unchanged.
+    Nothing,
+    /// Report writes as done, but do not do them.
+    DropWrites,
+    /// Fail all writes.
+    ErrorWrites,
+    /// Fail reads of the given sectors. A write to one of the sectors
+    /// succeeds, and the sector is good from then on.
+    BadSectors(Vec<Sectors>),
+}
+
+/// A DM device that passes I/O through to the device under it, except as
+/// a fault directs.
+#[derive(Debug)]
+pub struct FaultDev {
+    name: DmNameBuf,
+    origin: Device,
+    size: Sectors,
+    device: Device,
+    fault: Fault,
+}
+
+impl FaultDev {
+    /// Make a fault device on the device at path, which injects no fault.
+    pub fn new(path: &Path) -> FaultDev {
+        let origin = Device::from(devnode_to_devno(path).unwrap().unwrap());
+        let size = blkdev_size(&File::open(path).unwrap()).unwrap().sectors();
+        let name = DmNameBuf::new(format!("stratis_test_fault_{}", Uuid::new_v4().simple()))
+            .expect("valid format");
+        let mut dev = FaultDev {
+            name,
+            origin,
+            size,
+            device: origin,
+            fault: Fault::Nothing,
+        };
+        dev.create();
+        dev
+    }
+
+    /// The device node of the fault device.
+    pub fn devnode(&self) -> PathBuf {
+        PathBuf::from(format!("/dev/dm-{}", self.device.minor))
+    }
+
+    /// The table of the fault device, for the fault it injects.
+    fn table(&self) -> Vec<(Sectors, Sectors, TargetTypeBuf, String)> {
+        let (target_type, params) = match self.fault {
+            Fault::Nothing => ("linear", format!("{} 0", self.origin)),
+            Fault::DropWrites => ("flakey", format!("{} 0 0 1 1 drop_writes", self.origin)),
+            Fault::ErrorWrites => ("flakey", format!("{} 0 0 1 1 error_writes", self.origin)),
+            Fault::BadSectors(_) => ("dust", format!("{} 0 512", self.origin)),
+        };
+        vec![(
+            Sectors(0),
+            self.size,
+            TargetTypeBuf::new(target_type.to_owned()).expect("valid target type"),
+            params,
+        )]
+    }
+
+    /// Mark the bad sectors, if any, and have dm-dust fail reads of them.
+    fn mark_bad_sectors(&self) {
+        if let Fault::BadSectors(ref sectors) = self.fault {
+            let id = DevId::Name(&self.name);
+            for sector in sectors {
+                get_dm()
+                    .target_msg(&id, None, &format!("addbadblock {}", **sector))
+                    .unwrap();
+            }
+            get_dm().target_msg(&id, None, "enable").unwrap();
+        }
+    }
+
+    /// Make the DM device, with a table for the fault.
+    fn create(&mut self) {
+        let id = DevId::Name(&self.name);
+        get_dm()
+            .device_create(&self.name, None, &DmOptions::new())
+            .unwrap();
+        get_dm()
+            .table_load(&id, &self.table())
+            .unwrap();
+        self.device = get_dm()
+            .device_suspend(&id, &DmOptions::new())
+            .unwrap()
+            .device();
+        self.mark_bad_sectors();
+    }
+
+    /// Inject the given fault from now on. The device may be in use.
+    pub fn set_fault(&mut self, fault: Fault) {
+        self.fault = fault;
+        let id = DevId::Name(&self.name);
+        get_dm()
+            .table_load(&id, &self.table())
+            .unwrap();
+        get_dm()
+            .device_suspend(&id, &DmOptions::new().set_flags(DmFlags::DM_SUSPEND))
+            .unwrap();
+        get_dm().device_suspend(&id, &DmOptions::new()).unwrap();
+        self.mark_bad_sectors();
+    }
+
+    /// Remove the DM device and make it again, with the same fault, so
+    /// that nothing read from or written to it before is cached, as after
+    /// the power has failed. Sectors marked bad are bad again.
+    /// Precondition: the device is not in use.
+    pub fn power_cycle(&mut self) {
+        get_dm()
+            .device_remove(&DevId::Name(&self.name), &DmOptions::new())
+            .unwrap();
+        self.create();
+    }
+
+    /// Remove the DM device.
+    fn teardown(self) {
+        get_dm()
+            .device_remove(&DevId::Name(&self.name), &DmOptions::new())
+            .unwrap();
+    }
+}
+
+/// The device nodes of the fault devices, as find_all() would give them
+/// for a pool made up of those devices.
+pub fn devnodes(devs: &[FaultDev]) -> HashMap<Device, PathBuf> {
+    devs.iter().map(|dev| (dev.device, dev.devnode())).collect()
+}
+
+/// Run the test on fault devices, one on each of the devices given, which
+/// inject no fault until the test directs them to. Afterwards, remove any
+/// Stratis DM devices left, and then the fault devices, whether the test
+/// succeeded or not.
+pub fn test_with_faults<F>(paths: &[&Path], test: F) -> ()
+where
+    F: Fn(&mut [FaultDev]) -> (),
+{
+    let mut devs = paths.iter().map(|path| FaultDev::new(path)).collect::<Vec<_>>();
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| test(&mut devs)));
+    let tear_down = clean_up();
+    cmd::udev_settle().unwrap();
+    for dev in devs {
+        dev.teardown();
+    }
+
+    if let Err(err) = result {
+        panic::resume_unwind(err);
+    }
+    tear_down.unwrap();
+}
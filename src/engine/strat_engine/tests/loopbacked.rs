@@ -4,6 +4,7 @@
 
 extern crate loopdev;
 
+use std::env;
 use std::fs::OpenOptions;
 use std::os::unix::io::AsRawFd;
 use std::panic;
@@ -16,6 +17,8 @@ use devicemapper::{Bytes, Sectors, IEC};
 
 use self::loopdev::{LoopControl, LoopDevice};
 
+use super::super::verify::VERIFY_MAPPINGS_VAR;
+
 use super::logger::init_logger;
 use super::util::clean_up;
 
@@ -97,6 +100,7 @@ where
     let counts = get_device_counts(limits);
 
     init_logger();
+    env::set_var(VERIFY_MAPPINGS_VAR, "1");
 
     for (count, size) in counts {
         let tmpdir = tempfile::Builder::new()
@@ -5,6 +5,7 @@
 extern crate env_logger;
 extern crate log;
 
+pub mod fault;
 mod logger;
 pub mod loopbacked;
 pub mod real;
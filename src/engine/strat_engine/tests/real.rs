@@ -4,6 +4,7 @@
 
 extern crate either;
 
+use std::env;
 use std::fs::OpenOptions;
 use std::path::{Path, PathBuf};
 use std::{cmp, panic};
@@ -20,6 +21,7 @@ use devicemapper::{
 use super::super::backstore::blkdev_size;
 use super::super::device::wipe_sectors;
 use super::super::dm::get_dm;
+use super::super::verify::VERIFY_MAPPINGS_VAR;
 
 use super::logger::init_logger;
 use super::util::clean_up;
@@ -221,6 +223,7 @@ where
     assert!(!runs.is_empty());
 
     init_logger();
+    env::set_var(VERIFY_MAPPINGS_VAR, "1");
 
     for run_paths in runs {
         clean_up().unwrap();
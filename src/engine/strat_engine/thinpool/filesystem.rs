@@ -3,8 +3,8 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use chrono::{DateTime, TimeZone, Utc};
-use uuid::Uuid;
 
+use std::cmp::min;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -20,18 +20,22 @@ use nix::mount::{mount, umount, MsFlags};
 use nix::sys::statvfs::statvfs;
 use tempfile;
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
 
 use super::super::super::engine::Filesystem;
-use super::super::super::types::{FilesystemUuid, MaybeDbusPath, Name, PoolUuid};
+use super::super::super::types::{
+    validate_fs_size_limit, Creator, DmDeviceInfo, FilesystemUuid, MaybeDbusPath, Name, PoolUuid,
+};
 
-use super::super::cmd::{create_fs, set_uuid, udev_settle, xfs_growfs};
+use super::super::cmd::{create_fs, set_uuid, udev_settle, xfs_freeze, xfs_growfs, xfs_repair};
+use super::super::device::{set_read_ahead, wipe_sectors};
 use super::super::dm::get_dm;
 use super::super::names::{format_thin_ids, ThinRole};
-use super::super::serde_structs::FilesystemSave;
+use super::super::serde_structs::{FilesystemSave, Recordable};
+use super::super::watchdog::{resume_dm, suspend_dm, watch_dm};
 use super::thinpool::{DATA_BLOCK_SIZE, DATA_LOWATER};
 
-const DEFAULT_THIN_DEV_SIZE: Sectors = Sectors(2 * IEC::Gi); // 1 TiB
+pub const DEFAULT_THIN_DEV_SIZE: Sectors = Sectors(2 * IEC::Gi); // 1 TiB
 
 const TEMP_MNT_POINT_PREFIX: &str = "stratis_mp_";
 
@@ -39,10 +43,31 @@ const TEMP_MNT_POINT_PREFIX: &str = "stratis_mp_";
 /// expansion check is triggered by crossing the data low water mark for the thin pool.
 pub const FILESYSTEM_LOWATER: Sectors = Sectors(4 * (DATA_LOWATER.0 * DATA_BLOCK_SIZE.0));
 
+/// Return an error if a filesystem of the given size would be too small.
+/// A filesystem no larger than its low water mark would be extended as soon
+/// as it is checked.
+pub fn validate_fs_size(size: Sectors) -> StratisResult<()> {
+    if size <= FILESYSTEM_LOWATER {
+        return Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("FilesystemSizeTooSmall")
+                .param("size", *size)
+                .param("minimum", *FILESYSTEM_LOWATER + 1),
+        ));
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct StratFilesystem {
     thin_dev: ThinDev,
     created: DateTime<Utc>,
+    guaranteed: bool,
+    created_by: Option<Creator>,
+    read_ahead: Option<Sectors>,
+    size_limit: Option<Sectors>,
+    origin: Option<FilesystemUuid>,
+    protected: bool,
     dbus_path: MaybeDbusPath,
 }
 
@@ -66,24 +91,40 @@ pub fn fs_settle() -> () {
 
 impl StratFilesystem {
     /// Create a StratFilesystem on top of the given ThinDev.
+    /// If guaranteed is true, every block of the ThinDev is written, so that
+    /// all of its space is allocated from the thin pool. The thin pool must
+    /// have room for all of it without needing to be extended.
+    /// The creator, if given, is recorded in the filesystem's metadata.
     pub fn initialize(
         pool_uuid: PoolUuid,
         thinpool_dev: &ThinPoolDev,
-        size: Option<Sectors>,
+        size: Sectors,
         id: ThinDevId,
+        guaranteed: bool,
+        created_by: Option<Creator>,
     ) -> StratisResult<(FilesystemUuid, StratFilesystem)> {
-        let fs_uuid = Uuid::new_v4();
+        let fs_uuid = FilesystemUuid::new_v4();
         let (dm_name, dm_uuid) = format_thin_ids(pool_uuid, ThinRole::Filesystem(fs_uuid));
         let mut thin_dev = ThinDev::new(
             get_dm(),
             &dm_name,
             Some(&dm_uuid),
-            size.unwrap_or(DEFAULT_THIN_DEV_SIZE),
+            size,
             thinpool_dev,
             id,
         )?;
 
-        if let Err(err) = create_fs(&thin_dev.devnode(), fs_uuid) {
+        // Writing every block allocates it from the thin pool. mkfs must
+        // then not discard the device, or the blocks would be returned.
+        let devnode = thin_dev.devnode();
+        let result = if guaranteed {
+            wipe_sectors(&devnode, Sectors(0), size)
+                .and_then(|_| create_fs(&devnode, *fs_uuid, false))
+        } else {
+            create_fs(&devnode, *fs_uuid, true)
+        };
+
+        if let Err(err) = result {
             fs_settle();
             if let Err(err2) = thin_dev.destroy(get_dm(), thinpool_dev) {
                 error!(
@@ -103,6 +144,12 @@ impl StratFilesystem {
             StratFilesystem {
                 thin_dev,
                 created: Utc::now(),
+                guaranteed,
+                created_by,
+                read_ahead: None,
+                size_limit: None,
+                origin: None,
+                protected: false,
                 dbus_path: MaybeDbusPath(None),
             },
         ))
@@ -123,11 +170,97 @@ impl StratFilesystem {
             &thinpool_dev,
             fssave.thin_id,
         )?;
-        Ok(StratFilesystem {
+        let filesystem = StratFilesystem {
             thin_dev,
             created: Utc.timestamp(fssave.created as i64, 0),
+            guaranteed: fssave.guaranteed.unwrap_or(false),
+            created_by: fssave.created_by.as_ref().map(Creator::from),
+            read_ahead: fssave.read_ahead,
+            size_limit: fssave.size_limit,
+            origin: fssave.origin,
+            protected: fssave.protected.unwrap_or(false),
             dbus_path: MaybeDbusPath(None),
-        })
+        };
+        filesystem.apply_read_ahead();
+        Ok(filesystem)
+    }
+
+    /// Set the read-ahead recorded for the filesystem's device, if any, on
+    /// the device. A failure is logged, but otherwise ignored, as the
+    /// filesystem is usable regardless.
+    fn apply_read_ahead(&self) {
+        if let Some(read_ahead) = self.read_ahead {
+            if let Err(err) = set_read_ahead(self.thin_dev.device(), read_ahead) {
+                warn!(
+                    "failed to set read-ahead of {} to {}: {}",
+                    self.thin_dev.devnode().display(),
+                    read_ahead,
+                    err
+                );
+            }
+        }
+    }
+
+    /// Set the read-ahead of the filesystem's device, and record it so that
+    /// it is set whenever the device is activated. If read_ahead is None,
+    /// the device keeps its current read-ahead, but none is recorded.
+    pub fn set_read_ahead(&mut self, read_ahead: Option<Sectors>) -> StratisResult<()> {
+        if let Some(read_ahead) = read_ahead {
+            set_read_ahead(self.thin_dev.device(), read_ahead)?;
+        }
+        self.read_ahead = read_ahead;
+        Ok(())
+    }
+
+    /// Protect the filesystem from being destroyed, or unprotect it.
+    pub fn set_protected(&mut self, protected: bool) {
+        self.protected = protected;
+    }
+
+    /// Set the size limit of the filesystem. If the limit is above the size
+    /// of the filesystem's device, the device is extended to the limit and
+    /// the filesystem is grown to fill it. The device can not be shrunk, so
+    /// a limit below its size is refused.
+    pub fn set_size_limit(&mut self, limit: Option<Sectors>) -> StratisResult<()> {
+        if let Some(limit) = limit {
+            validate_fs_size_limit(self.thin_dev.size(), limit)?;
+            if limit > self.thin_dev.size() {
+                self.extend(limit)?;
+            }
+        }
+        self.size_limit = limit;
+        Ok(())
+    }
+
+    /// Extend the filesystem's device to new_size, and grow the filesystem
+    /// to fill it. XFS can only be grown while it is mounted, so a
+    /// filesystem that is not mounted is mounted at a temporary mount point
+    /// while it is grown.
+    fn extend(&mut self, new_size: Sectors) -> StratisResult<()> {
+        let mut table = self.thin_dev.table().table.clone();
+        table.length = new_size;
+        let name = self.thin_dev.name().to_owned();
+        // set_table() suspends the device, which may wait on I/O; it is
+        // suspended first, so that set_table()'s suspend does nothing.
+        suspend_dm(&name, false)?;
+        watch_dm(&name, "reload", || self.thin_dev.set_table(get_dm(), table))?;
+
+        if let Some(mount_point) = self.mount_points()?.first() {
+            return xfs_growfs(mount_point);
+        }
+        let tmp_dir = tempfile::Builder::new()
+            .prefix(TEMP_MNT_POINT_PREFIX)
+            .tempdir()?;
+        mount(
+            Some(&self.thin_dev.devnode()),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None::<&str>,
+        )?;
+        let result = xfs_growfs(tmp_dir.path());
+        umount(tmp_dir.path())?;
+        result
     }
 
     /// Create a snapshot of the filesystem. Return the resulting filesystem/ThinDev
@@ -136,6 +269,9 @@ impl StratFilesystem {
     /// Mounting a filesystem with a duplicate UUID would require special handling,
     /// so snapshot_fs_uuid is used to update the new snapshot filesystem so it has
     /// a unique UUID.
+    /// If freeze is true and the filesystem is mounted, it is frozen while
+    /// the snapshot is taken and thawed at once after, so that the snapshot
+    /// is clean, not just crash-consistent.
     #[allow(too_many_arguments)]
     pub fn snapshot(
         &self,
@@ -146,14 +282,37 @@ impl StratFilesystem {
         snapshot_fs_name: &Name,
         snapshot_fs_uuid: FilesystemUuid,
         snapshot_thin_id: ThinDevId,
+        origin_uuid: FilesystemUuid,
+        freeze: bool,
     ) -> StratisResult<StratFilesystem> {
-        match self.thin_dev.snapshot(
+        let frozen = if freeze {
+            self.mount_points()?.into_iter().next()
+        } else {
+            None
+        };
+        if let Some(ref mount_point) = frozen {
+            xfs_freeze(mount_point, false)?;
+        }
+        let snapshot = self.thin_dev.snapshot(
             get_dm(),
             snapshot_dm_name,
             snapshot_dm_uuid,
             thin_pool,
             snapshot_thin_id,
-        ) {
+        );
+        // The snapshot is already taken, so a failure to thaw does not undo
+        // it, but the origin can not be written until it is thawed by hand.
+        if let Some(ref mount_point) = frozen {
+            if let Err(err) = xfs_freeze(mount_point, true) {
+                error!(
+                    "Could not thaw filesystem {} mounted at {}, reason: {}",
+                    snapshot_fs_name,
+                    mount_point.display(),
+                    err
+                );
+            }
+        }
+        match snapshot {
             Ok(thin_dev) => {
                 // If the source is mounted, XFS puts a dummy record in the
                 // log to enforce replay of the snapshot to deal with any
@@ -180,12 +339,23 @@ impl StratFilesystem {
                     umount(tmp_dir.path())?;
                 }
 
-                set_uuid(&thin_dev.devnode(), snapshot_fs_uuid)?;
-                Ok(StratFilesystem {
+                set_uuid(&thin_dev.devnode(), *snapshot_fs_uuid)?;
+                // A snapshot shares its blocks with its origin, so no space
+                // is guaranteed to it. It is likely read as its origin is,
+                // so it takes the origin's read-ahead.
+                let snapshot = StratFilesystem {
                     thin_dev,
                     created: Utc::now(),
+                    guaranteed: false,
+                    created_by: None,
+                    read_ahead: self.read_ahead,
+                    size_limit: self.size_limit,
+                    origin: Some(origin_uuid),
+                    protected: false,
                     dbus_path: MaybeDbusPath(None),
-                })
+                };
+                snapshot.apply_read_ahead();
+                Ok(snapshot)
             }
             Err(e) => Err(StratisError::Engine(
                 ErrorEnum::Error,
@@ -198,18 +368,37 @@ impl StratFilesystem {
     }
 
     /// check if filesystem is getting full and needs to be extended
+    /// A guaranteed filesystem is never extended, since the extension
+    /// would not be guaranteed. A filesystem is never extended beyond its
+    /// size limit.
     /// TODO: deal with the thindev in a Fail state.
     pub fn check(&mut self) -> StratisResult<FilesystemStatus> {
         match self.thin_dev.status(get_dm())? {
             ThinStatus::Working(_) => {
+                if self.guaranteed {
+                    return Ok(FilesystemStatus::Good);
+                }
                 if let Some(mount_point) = self.mount_points()?.first() {
                     let (fs_total_bytes, fs_total_used_bytes) = fs_usage(&mount_point)?;
                     let free_bytes = fs_total_bytes - fs_total_used_bytes;
-                    if free_bytes.sectors() < FILESYSTEM_LOWATER {
+                    let size = self.thin_dev.size();
+                    let mut new_size = size + self.extend_size(size);
+                    if let Some(limit) = self.size_limit {
+                        new_size = min(new_size, limit);
+                    }
+                    if free_bytes.sectors() < FILESYSTEM_LOWATER && new_size > size {
                         let mut table = self.thin_dev.table().table.clone();
-                        table.length =
-                            self.thin_dev.size() + self.extend_size(self.thin_dev.size());
-                        if self.thin_dev.set_table(get_dm(), table).is_err() {
+                        table.length = new_size;
+                        let name = self.thin_dev.name().to_owned();
+                        if suspend_dm(&name, false)
+                            .and_then(|_| {
+                                watch_dm(&name, "reload", || {
+                                    self.thin_dev.set_table(get_dm(), table)
+                                })?;
+                                Ok(())
+                            })
+                            .is_err()
+                        {
                             return Ok(FilesystemStatus::ThinDevExtendFailed);
                         }
                         if xfs_growfs(&mount_point).is_err() {
@@ -225,6 +414,27 @@ impl StratFilesystem {
         Ok(FilesystemStatus::Good)
     }
 
+    /// Return an error if the filesystem is mounted.
+    pub fn refuse_if_mounted(&self) -> StratisResult<()> {
+        if let Some(mount_point) = self.mount_points()?.first() {
+            return Err(StratisError::Structured(
+                ErrorEnum::Busy,
+                ErrorPayload::new("FilesystemMounted")
+                    .param("mount_point", mount_point.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the consistency of the filesystem with xfs_repair, repairing
+    /// it if repair is true. Return whether the filesystem was found
+    /// consistent, or was repaired, and the output of xfs_repair.
+    /// Return an error if the filesystem is mounted.
+    pub fn check_consistency(&self, repair: bool) -> StratisResult<(bool, String)> {
+        self.refuse_if_mounted()?;
+        xfs_repair(&self.thin_dev.devnode(), repair)
+    }
+
     /// Return an extend size for the thindev under the filesystem
     /// TODO: returning the current size will double the space provisioned to
     /// the thin device.  We should determine if this is a reasonable value.
@@ -234,16 +444,28 @@ impl StratFilesystem {
 
     /// Tear down the filesystem.
     pub fn teardown(&mut self) -> StratisResult<()> {
-        self.thin_dev.teardown(get_dm())?;
+        let name = self.thin_dev.name().to_owned();
+        watch_dm(&name, "teardown", || self.thin_dev.teardown(get_dm()))?;
         Ok(())
     }
 
     /// Destroy the filesystem.
     pub fn destroy(&mut self, thin_pool: &ThinPoolDev) -> StratisResult<()> {
-        self.thin_dev.destroy(get_dm(), thin_pool)?;
+        let name = self.thin_dev.name().to_owned();
+        watch_dm(&name, "destroy", || self.thin_dev.destroy(get_dm(), thin_pool))?;
         Ok(())
     }
 
+    /// The size of the filesystem's thin device.
+    pub fn size(&self) -> Sectors {
+        self.thin_dev.size()
+    }
+
+    /// The id of the filesystem's thin device within the thin pool.
+    pub fn thin_id(&self) -> ThinDevId {
+        self.thin_dev.id()
+    }
+
     pub fn record(&self, name: &Name, uuid: FilesystemUuid) -> FilesystemSave {
         FilesystemSave {
             name: name.to_owned(),
@@ -251,22 +473,77 @@ impl StratFilesystem {
             thin_id: self.thin_dev.id(),
             size: self.thin_dev.size(),
             created: self.created.timestamp() as u64,
+            guaranteed: if self.guaranteed { Some(true) } else { None },
+            created_by: self.created_by.as_ref().map(|c| c.record()),
+            read_ahead: self.read_ahead,
+            size_limit: self.size_limit,
+            origin: self.origin,
+            protected: if self.protected { Some(true) } else { None },
         }
     }
 
     #[allow(dead_code)]
     pub fn suspend(&mut self, flush: bool) -> StratisResult<()> {
-        self.thin_dev.suspend(get_dm(), flush)?;
+        let name = self.thin_dev.name().to_owned();
+        suspend_dm(&name, flush)?;
         Ok(())
     }
 
     #[allow(dead_code)]
     pub fn resume(&mut self) -> StratisResult<()> {
-        self.thin_dev.resume(get_dm())?;
+        let name = self.thin_dev.name().to_owned();
+        resume_dm(&name)?;
         Ok(())
     }
+}
+
+impl Filesystem for StratFilesystem {
+    fn devnode(&self) -> PathBuf {
+        self.thin_dev.devnode()
+    }
+
+    fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    fn used(&self) -> StratisResult<Bytes> {
+        match self.thin_dev.status(get_dm())? {
+            ThinStatus::Working(wk_status) => Ok(wk_status.nr_mapped_sectors.bytes()),
+            ThinStatus::Fail => {
+                let error_msg = format!("ThinDev {} is in a failed state", self.thin_dev.device());
+                Err(StratisError::Engine(ErrorEnum::Error, error_msg))
+            }
+        }
+    }
+
+    fn guaranteed(&self) -> bool {
+        self.guaranteed
+    }
+
+    fn created_by(&self) -> Option<&Creator> {
+        self.created_by.as_ref()
+    }
+
+    fn read_ahead(&self) -> Option<Sectors> {
+        self.read_ahead
+    }
+
+    fn size_limit(&self) -> Option<Sectors> {
+        self.size_limit
+    }
+
+    fn origin(&self) -> Option<FilesystemUuid> {
+        self.origin
+    }
+
+    fn protected(&self) -> bool {
+        self.protected
+    }
+
+    fn dm_device(&self) -> Option<DmDeviceInfo> {
+        Some(DmDeviceInfo::new(self.thin_dev.name(), self.thin_dev.uuid()))
+    }
 
-    /// Find places where this filesystem is mounted.
     fn mount_points(&self) -> StratisResult<Vec<PathBuf>> {
         // Use major:minor values to find mounts for this filesystem
         let major = u64::from(self.thin_dev.device().major);
@@ -293,26 +570,6 @@ impl StratFilesystem {
 
         Ok(ret_vec)
     }
-}
-
-impl Filesystem for StratFilesystem {
-    fn devnode(&self) -> PathBuf {
-        self.thin_dev.devnode()
-    }
-
-    fn created(&self) -> DateTime<Utc> {
-        self.created
-    }
-
-    fn used(&self) -> StratisResult<Bytes> {
-        match self.thin_dev.status(get_dm())? {
-            ThinStatus::Working(wk_status) => Ok(wk_status.nr_mapped_sectors.bytes()),
-            ThinStatus::Fail => {
-                let error_msg = format!("ThinDev {} is in a failed state", self.thin_dev.device());
-                Err(StratisError::Engine(ErrorEnum::Error, error_msg))
-            }
-        }
-    }
 
     fn set_dbus_path(&mut self, path: MaybeDbusPath) -> () {
         self.dbus_path = path
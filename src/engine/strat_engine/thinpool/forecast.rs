@@ -0,0 +1,98 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Forecast when a pool will run out of space from the growth of its used
+// space over time. The samples are kept only in the daemon's memory, so a
+// forecast is available only some time after the daemon has started.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Duration, Utc};
+
+use devicemapper::DataBlocks;
+
+/// The shortest interval between two recorded samples. Samples taken more
+/// often are discarded, so that a burst of checks does not displace the
+/// history.
+const SAMPLE_INTERVAL_SECS: i64 = 5 * 60;
+
+/// The number of samples kept, about one day's worth at the shortest
+/// interval.
+const MAX_SAMPLES: usize = 288;
+
+/// The shortest span of samples from which a growth rate is computed.
+const MIN_SPAN_SECS: i64 = 60 * 60;
+
+const SECS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// A history of the used space of a pool.
+#[derive(Debug, Default)]
+pub struct UsageHistory {
+    samples: VecDeque<(DateTime<Utc>, DataBlocks)>,
+}
+
+impl UsageHistory {
+    /// Record the used space at time now.
+    pub fn record(&mut self, now: DateTime<Utc>, used: DataBlocks) {
+        if let Some(&(last, _)) = self.samples.back() {
+            if now < last + Duration::seconds(SAMPLE_INTERVAL_SECS) {
+                return;
+            }
+        }
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, used));
+    }
+
+    /// The growth of the used space in blocks per day, over the span of the
+    /// recorded samples. None if the samples span too short a time.
+    pub fn growth_rate(&self) -> Option<f64> {
+        let (&(first, first_used), &(last, last_used)) =
+            match (self.samples.front(), self.samples.back()) {
+                (Some(first), Some(last)) => (first, last),
+                _ => return None,
+            };
+        let span = last.signed_duration_since(first).num_seconds();
+        if span < MIN_SPAN_SECS {
+            return None;
+        }
+        Some((*last_used as f64 - *first_used as f64) * SECS_PER_DAY / span as f64)
+    }
+
+    /// The number of days until the available space is used up, if the used
+    /// space continues to grow at the current rate. None if the used space
+    /// is not growing, or if there are not yet enough samples.
+    pub fn days_until_full(&self, available: DataBlocks) -> Option<u64> {
+        match self.growth_rate() {
+            Some(rate) if rate > 0.0 => Some((*available as f64 / rate) as u64),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that samples closer together than the sample interval are
+    /// discarded, that no forecast is made from too short a span, and that
+    /// the forecast follows the growth rate.
+    fn test_days_until_full() {
+        let start = Utc::now();
+        let mut history = UsageHistory::default();
+
+        history.record(start, DataBlocks(100));
+        history.record(start + Duration::seconds(1), DataBlocks(10_000));
+        assert_eq!(history.samples.len(), 1);
+        assert_eq!(history.days_until_full(DataBlocks(1000)), None);
+
+        history.record(start + Duration::hours(12), DataBlocks(150));
+        assert_eq!(history.days_until_full(DataBlocks(1000)), Some(10));
+
+        history.record(start + Duration::hours(24), DataBlocks(50));
+        assert_eq!(history.days_until_full(DataBlocks(1000)), None);
+    }
+}
@@ -14,16 +14,17 @@ use nix;
 use nix::mount::{mount, umount, MsFlags};
 use serde_json;
 
-use devicemapper::{DmDevice, LinearDev, LinearDevTargetParams, TargetLine};
+use devicemapper::{DmDevice, LinearDev};
 
 use stratis::StratisResult;
 
 use super::super::super::engine::DEV_PATH;
-use super::super::super::types::{FilesystemUuid, Name, PoolUuid};
+use super::super::super::types::{DmDeviceInfo, FilesystemUuid, Name, PoolUuid};
 
 use super::super::cmd::create_fs;
 use super::super::dm::get_dm;
 use super::super::serde_structs::FilesystemSave;
+use super::super::watchdog::{resume_dm, suspend_dm, watch_dm};
 
 use super::filesystem::StratFilesystem;
 
@@ -90,7 +91,7 @@ impl<'a> Drop for MountedMDV<'a> {
 impl MetadataVol {
     /// Initialize a new Metadata Volume.
     pub fn initialize(pool_uuid: PoolUuid, dev: LinearDev) -> StratisResult<MetadataVol> {
-        create_fs(&dev.devnode(), pool_uuid)?;
+        create_fs(&dev.devnode(), *pool_uuid, true)?;
         MetadataVol::setup(pool_uuid, dev)
     }
 
@@ -117,6 +118,11 @@ impl MetadataVol {
         Ok(mdv)
     }
 
+    /// The devicemapper device of the MDV.
+    pub fn dm_device(&self) -> DmDeviceInfo {
+        DmDeviceInfo::new(self.dev.name(), self.dev.uuid())
+    }
+
     /// Save info on a new filesystem to persistent storage, or update
     /// the existing info on a filesystem.
     // Write to a temp file and then rename to actual filename, to
@@ -128,15 +134,55 @@ impl MetadataVol {
         uuid: FilesystemUuid,
         fs: &StratFilesystem,
     ) -> StratisResult<()> {
-        let data = serde_json::to_string(&fs.record(name, uuid))?;
-        let path = self.mount_pt
+        let _mount = MountedMDV::mount(self)?;
+        self.write_fs(name, uuid, fs)
+    }
+
+    /// Save info on several new filesystems to persistent storage, with the
+    /// MDV mounted only once. If the info on any of them can not be saved,
+    /// the info already saved on the others is removed.
+    pub fn save_fses(
+        &self,
+        filesystems: &[(&Name, FilesystemUuid, &StratFilesystem)],
+    ) -> StratisResult<()> {
+        let _mount = MountedMDV::mount(self)?;
+
+        for (saved, &(name, uuid, fs)) in filesystems.iter().enumerate() {
+            if let Err(err) = self.write_fs(name, uuid, fs) {
+                for &(_, uuid, _) in &filesystems[..saved] {
+                    if let Err(err2) = remove_file(self.fs_path(uuid)) {
+                        error!(
+                            "When handling failed save_fses(), removing the info on {} failed: {}",
+                            uuid, err2
+                        )
+                    }
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The path of the file that holds the info on a filesystem.
+    fn fs_path(&self, uuid: FilesystemUuid) -> PathBuf {
+        self.mount_pt
             .join(FILESYSTEM_DIR)
             .join(uuid.simple().to_string())
-            .with_extension("json");
+            .with_extension("json")
+    }
 
-        let temp_path = path.clone().with_extension("temp");
+    /// Write the info on a filesystem to the mounted MDV.
+    fn write_fs(
+        &self,
+        name: &Name,
+        uuid: FilesystemUuid,
+        fs: &StratFilesystem,
+    ) -> StratisResult<()> {
+        let data = serde_json::to_string(&fs.record(name, uuid))?;
+        let path = self.fs_path(uuid);
 
-        let _mount = MountedMDV::mount(self)?;
+        let temp_path = path.clone().with_extension("temp");
 
         // Braces to ensure f is closed before renaming
         {
@@ -157,10 +203,7 @@ impl MetadataVol {
 
     /// Remove info on a filesystem from persistent storage.
     pub fn rm_fs(&self, fs_uuid: FilesystemUuid) -> StratisResult<()> {
-        let fs_path = self.mount_pt
-            .join(FILESYSTEM_DIR)
-            .join(fs_uuid.simple().to_string())
-            .with_extension("json");
+        let fs_path = self.fs_path(fs_uuid);
 
         let _mount = MountedMDV::mount(self)?;
 
@@ -198,34 +241,23 @@ impl MetadataVol {
 
     /// Tear down a Metadata Volume.
     pub fn teardown(&mut self) -> StratisResult<()> {
-        self.dev.teardown(get_dm())?;
+        let name = self.dev.name().to_owned();
+        watch_dm(&name, "teardown", || self.dev.teardown(get_dm()))?;
 
         Ok(())
     }
 
     /// Suspend the metadata volume DM devices
     pub fn suspend(&mut self) -> StratisResult<()> {
-        self.dev.suspend(get_dm(), true)?;
+        let name = self.dev.name().to_owned();
+        suspend_dm(&name, true)?;
         Ok(())
     }
 
     /// Resume the metadata volume DM devices
     pub fn resume(&mut self) -> StratisResult<()> {
-        self.dev.resume(get_dm())?;
-        Ok(())
-    }
-
-    /// Get a reference to the backing device
-    pub fn device(&self) -> &LinearDev {
-        &self.dev
-    }
-
-    /// Set the table of the backing device
-    pub fn set_table(
-        &mut self,
-        table: Vec<TargetLine<LinearDevTargetParams>>,
-    ) -> StratisResult<()> {
-        self.dev.set_table(get_dm(), table)?;
+        let name = self.dev.name().to_owned();
+        resume_dm(&name)?;
         Ok(())
     }
 }
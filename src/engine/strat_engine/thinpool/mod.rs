@@ -3,9 +3,12 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 mod filesystem;
+mod forecast;
 mod mdv;
 mod thinids;
 #[allow(module_inception)]
 mod thinpool;
+mod thresholds;
 
+pub use self::filesystem::validate_fs_size;
 pub use self::thinpool::{ThinPool, ThinPoolSizeParams, DATA_BLOCK_SIZE};
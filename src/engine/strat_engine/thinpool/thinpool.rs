@@ -7,29 +7,32 @@
 use std;
 use std::borrow::BorrowMut;
 use std::cmp::{max, min};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use uuid::Uuid;
+use chrono::Utc;
 
 use devicemapper::{
-    device_exists, DataBlocks, Device, DmDevice, DmName, DmNameBuf, FlakeyTargetParams, LinearDev,
+    device_exists, DataBlocks, DevId, Device, DmDevice, DmName, DmNameBuf, LinearDev,
     LinearDevTargetParams, LinearTargetParams, MetaBlocks, Sectors, TargetLine, ThinDevId,
     ThinPoolDev, ThinPoolStatus, ThinPoolStatusSummary, IEC,
 };
 
-use stratis::{ErrorEnum, StratisError, StratisResult};
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult};
 
 use super::super::super::devlinks;
 use super::super::super::engine::Filesystem;
 use super::super::super::event::{get_engine_listener_list, EngineEvent};
+use super::super::super::schedule::SnapshotSchedules;
 use super::super::super::structures::Table;
 use super::super::super::types::{
-    FilesystemUuid, FreeSpaceState, MaybeDbusPath, Name, PoolExtendState, PoolState, PoolUuid,
-    RenameAction,
+    check_fs_limit, check_overcommit, overcommit_percent, Creator, DmDeviceInfo, FilesystemUuid,
+    FreeSpaceState, MaybeDbusPath, Name, PoolExtendState, PoolState, PoolUuid, RenameAction,
+    Watermarks, DEFAULT_FS_LIMIT,
 };
 
 use super::super::backstore::Backstore;
-use super::super::cmd::{thin_check, thin_repair};
+use super::super::cmd::{thin_check, thin_ls, thin_repair};
 use super::super::device::wipe_sectors;
 use super::super::dm::get_dm;
 use super::super::names::{
@@ -37,15 +40,25 @@ use super::super::names::{
 };
 use super::super::serde_structs::{FlexDevsSave, Recordable, ThinPoolDevSave};
 use super::super::set_write_throttling;
+use super::super::targets::{require_target, THIN, THIN_POOL};
+use super::super::verify::verify_mapping;
+use super::super::watchdog::{resume_dm, suspend_dm, watch_dm};
 
-use super::filesystem::{fs_settle, FilesystemStatus, StratFilesystem};
+use super::filesystem::{fs_settle, FilesystemStatus, StratFilesystem, DEFAULT_THIN_DEV_SIZE};
+use super::forecast::UsageHistory;
 use super::mdv::MetadataVol;
 use super::thinids::ThinDevIdPool;
+use super::thresholds::{UsageThresholds, WatermarkState};
 
 pub const DATA_BLOCK_SIZE: Sectors = Sectors(2 * IEC::Ki);
 pub const DATA_LOWATER: DataBlocks = DataBlocks(2048); // 2 GiB
 const DATA_EXPAND_SIZE: Sectors = Sectors(16 * IEC::Mi); // 8 GiB
 const META_LOWATER_FALLBACK: MetaBlocks = MetaBlocks(1024);
+/// The percentage of the meta device that is kept free, by extending the
+/// device once less than that is.
+const META_LOWATER_PCT: u64 = 25;
+/// The largest meta device of which the kernel makes any use.
+const MAX_META_SIZE: MetaBlocks = MetaBlocks(255 * 16 * IEC::Ki); // ~16 GiB
 
 const INITIAL_META_SIZE: MetaBlocks = MetaBlocks(4 * IEC::Ki);
 const INITIAL_DATA_SIZE: DataBlocks = DataBlocks(768);
@@ -99,6 +112,43 @@ fn current_dirty_mem() -> StratisResult<Sectors> {
     ))
 }
 
+/// Return a value from 0 to 100 that is the percentage that "used" makes up
+/// in "total".
+fn used_pct(used: u64, total: u64) -> u8 {
+    assert!(total >= used);
+    let mut val = (used * 100) / total;
+    if (used * 100) % total != 0 {
+        val += 1; // round up
+    }
+    assert!(val <= 100);
+    val as u8
+}
+
+/// Parse the output of thin_ls into the number of sectors that each thin
+/// device alone maps, and that it shares with other thin devices.
+fn parse_thin_ls(output: &str) -> StratisResult<HashMap<ThinDevId, (Sectors, Sectors)>> {
+    let mut space = HashMap::new();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let values = match line.split_whitespace()
+            .map(|value| value.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(ref values) if values.len() == 3 => values.clone(),
+            _ => {
+                return Err(StratisError::Engine(
+                    ErrorEnum::Invalid,
+                    format!("unexpected line in output of thin_ls: {}", line),
+                ));
+            }
+        };
+        space.insert(
+            ThinDevId::new_u64(values[0])?,
+            (Sectors(values[1]), Sectors(values[2])),
+        );
+    }
+    Ok(space)
+}
+
 /// Transform a list of segments belonging to a single device into a
 /// list of target lines for a linear device.
 fn segs_to_table(
@@ -121,6 +171,23 @@ fn segs_to_table(
     table
 }
 
+/// If verification of mappings is turned on, check the table of the flex
+/// device with the given role against the segments of dev recorded as
+/// allocated to it.
+fn verify_flex_mapping(
+    pool_uuid: PoolUuid,
+    role: FlexRole,
+    dev: Device,
+    segments: &[(Sectors, Sectors)],
+) -> StratisResult<()> {
+    let (dm_name, _) = format_flex_ids(pool_uuid, role);
+    let records = segments
+        .iter()
+        .map(|&(start, length)| (dev, start, length))
+        .collect::<Vec<_>>();
+    verify_mapping(&dm_name, &records)
+}
+
 /// Append the second list of segments to the first, or if the last
 /// segment of the first argument is adjacent to the first segment of the
 /// second argument, merge those two together.
@@ -212,6 +279,49 @@ fn calc_lowater(
     }
 }
 
+/// The meta low water of a meta device of meta_dev_size: the kernel's, if it
+/// is known, unless META_LOWATER_PCT of the device is more. For a large
+/// device, the kernel's low water is reached too late for the device to be
+/// extended before the pool goes read only.
+fn calc_meta_lowater(meta_dev_size: MetaBlocks, kernel_lowater: Option<MetaBlocks>) -> MetaBlocks {
+    max(
+        kernel_lowater.unwrap_or(META_LOWATER_FALLBACK),
+        MetaBlocks(*meta_dev_size * META_LOWATER_PCT / 100),
+    )
+}
+
+/// The amount by which to extend a meta device of total, of which used is
+/// in use, or None if more than lowater of it is free. The device is
+/// extended by a quarter of its size, but by no less than INITIAL_META_SIZE,
+/// and never beyond MAX_META_SIZE.
+fn meta_extension_request(
+    total: MetaBlocks,
+    used: MetaBlocks,
+    lowater: MetaBlocks,
+) -> Option<MetaBlocks> {
+    if total - used > lowater || total >= MAX_META_SIZE {
+        return None;
+    }
+    Some(min(
+        max(INITIAL_META_SIZE, MetaBlocks(*total / 4)),
+        MAX_META_SIZE - total,
+    ))
+}
+
+/// The low water mark of a data device of data_dev_size, lowater, unless
+/// the extend watermark, if any, calls for the device to be extended while
+/// more of it is free.
+fn watermark_lowater(
+    data_dev_size: DataBlocks,
+    lowater: DataBlocks,
+    extend: Option<u8>,
+) -> DataBlocks {
+    match extend {
+        Some(percent) => max(lowater, data_dev_size - (data_dev_size * percent) / 100u8),
+        None => lowater,
+    }
+}
+
 pub struct ThinPoolSizeParams {
     meta_size: MetaBlocks,
     data_size: DataBlocks,
@@ -248,21 +358,38 @@ impl Default for ThinPoolSizeParams {
 /// metadata associated with it.
 #[derive(Debug)]
 pub struct ThinPool {
+    /// The UUID of the pool to which the thin pool belongs, by which it is
+    /// identified in the events it notifies.
+    pool_uuid: PoolUuid,
     thin_pool: ThinPoolDev,
     meta_segments: Vec<(Sectors, Sectors)>,
     meta_spare_segments: Vec<(Sectors, Sectors)>,
     data_segments: Vec<(Sectors, Sectors)>,
     mdv_segments: Vec<(Sectors, Sectors)>,
     id_gen: ThinDevIdPool,
-    filesystems: Table<StratFilesystem>,
+    filesystems: Table<FilesystemUuid, StratFilesystem>,
     mdv: MetadataVol,
     /// The single DM device that the backstore presents as its upper-most
     /// layer. All DM components obtain their storage from this layer.
-    /// The device will change if the backstore adds or removes a cache.
+    /// This is the backstore's cap device, which does not change if the
+    /// backstore adds or removes a cache.
     backstore_device: Device,
     pool_state: PoolState,
     pool_extend_state: PoolExtendState,
     free_space_state: FreeSpaceState,
+    usage_history: UsageHistory,
+    days_until_full: Option<u64>,
+    usage_thresholds: UsageThresholds,
+    watermarks: WatermarkState,
+    /// The overcommit percentage beyond which no filesystem or snapshot
+    /// may be created, if any.
+    overcommit_cap: Option<u64>,
+    /// The size of a filesystem created without an explicit size, if it
+    /// differs from DEFAULT_THIN_DEV_SIZE.
+    default_fs_size: Option<Sectors>,
+    /// The most filesystems and snapshots that the pool may hold.
+    fs_limit: u64,
+    snapshot_schedules: SnapshotSchedules,
     dbus_path: MaybeDbusPath,
 }
 
@@ -274,6 +401,9 @@ impl ThinPool {
         data_block_size: Sectors,
         backstore: &mut Backstore,
     ) -> StratisResult<ThinPool> {
+        require_target(&THIN_POOL)?;
+        require_target(&THIN)?;
+
         let mut segments_list = match backstore.alloc(
             pool_uuid,
             &[
@@ -295,8 +425,63 @@ impl ThinPool {
         let spare_segments = segments_list.pop().expect("len(segments_list) == 2");
         let meta_segments = segments_list.pop().expect("len(segments_list) == 1");
 
+        ThinPool::make(
+            pool_uuid,
+            data_block_size,
+            vec![meta_segments],
+            vec![spare_segments],
+            vec![data_segments],
+            vec![mdv_segments],
+            backstore,
+        )
+    }
+
+    /// Reconstruct a thin pool on the segments recorded in flex_devs, which
+    /// have been allocated from a backstore of fresh devices. The thin pool
+    /// and the MDV are made anew, so the thin pool has no filesystems.
+    pub fn reconstruct(
+        pool_uuid: PoolUuid,
+        thin_pool_save: &ThinPoolDevSave,
+        flex_devs: &FlexDevsSave,
+        backstore: &Backstore,
+    ) -> StratisResult<ThinPool> {
+        let mut thin_pool = ThinPool::make(
+            pool_uuid,
+            thin_pool_save.data_block_size,
+            flex_devs.thin_meta_dev.to_vec(),
+            flex_devs.thin_meta_dev_spare.to_vec(),
+            flex_devs.thin_data_dev.to_vec(),
+            flex_devs.meta_dev.to_vec(),
+            backstore,
+        )?;
+        thin_pool.overcommit_cap = thin_pool_save.overcommit_cap;
+        if let Some(ref thresholds) = thin_pool_save.usage_thresholds {
+            thin_pool.usage_thresholds = UsageThresholds::new(thresholds);
+        }
+        thin_pool.watermarks = WatermarkState::new(Watermarks {
+            warn: thin_pool_save.warn_watermark,
+            extend: thin_pool_save.extend_watermark,
+        });
+        thin_pool.default_fs_size = thin_pool_save.default_fs_size;
+        // Metadata that records no limit may come from a pool created before
+        // pools had one, which may already hold more than DEFAULT_FS_LIMIT.
+        thin_pool.fs_limit = thin_pool_save.fs_limit.unwrap_or(u64::max_value());
+        Ok(thin_pool)
+    }
+
+    /// Make a thin pool, with a fresh meta device and MDV, on segments
+    /// already allocated from the backstore.
+    fn make(
+        pool_uuid: PoolUuid,
+        data_block_size: Sectors,
+        meta_segments: Vec<(Sectors, Sectors)>,
+        spare_segments: Vec<(Sectors, Sectors)>,
+        data_segments: Vec<(Sectors, Sectors)>,
+        mdv_segments: Vec<(Sectors, Sectors)>,
+        backstore: &Backstore,
+    ) -> StratisResult<ThinPool> {
         let backstore_device = backstore.device().expect(
-            "Space has just been allocated from the backstore, so it must have a cap device",
+            "Space has been allocated from the backstore, so it must have a cap device",
         );
 
         // When constructing a thin-pool, Stratis reserves the first N
@@ -312,7 +497,7 @@ impl ThinPool {
             get_dm(),
             &dm_name,
             Some(&dm_uuid),
-            segs_to_table(backstore_device, &[meta_segments]),
+            segs_to_table(backstore_device, &meta_segments),
         )?;
 
         // Wipe the first 4 KiB, i.e. 8 sectors as recommended in kernel DM
@@ -329,7 +514,7 @@ impl ThinPool {
             get_dm(),
             &dm_name,
             Some(&dm_uuid),
-            segs_to_table(backstore_device, &[data_segments]),
+            segs_to_table(backstore_device, &data_segments),
         )?;
 
         let (dm_name, dm_uuid) = format_flex_ids(pool_uuid, FlexRole::MetadataVolume);
@@ -337,7 +522,7 @@ impl ThinPool {
             get_dm(),
             &dm_name,
             Some(&dm_uuid),
-            segs_to_table(backstore_device, &[mdv_segments]),
+            segs_to_table(backstore_device, &mdv_segments),
         )?;
         let mdv = MetadataVol::initialize(pool_uuid, mdv_dev)?;
 
@@ -358,12 +543,22 @@ impl ThinPool {
             ),
         )?;
 
+        verify_flex_mapping(pool_uuid, FlexRole::ThinMeta, backstore_device, &meta_segments)?;
+        verify_flex_mapping(pool_uuid, FlexRole::ThinData, backstore_device, &data_segments)?;
+        verify_flex_mapping(
+            pool_uuid,
+            FlexRole::MetadataVolume,
+            backstore_device,
+            &mdv_segments,
+        )?;
+
         Ok(ThinPool {
+            pool_uuid,
             thin_pool: thinpool_dev,
-            meta_segments: vec![meta_segments],
-            meta_spare_segments: vec![spare_segments],
-            data_segments: vec![data_segments],
-            mdv_segments: vec![mdv_segments],
+            meta_segments,
+            meta_spare_segments: spare_segments,
+            data_segments,
+            mdv_segments,
             id_gen: ThinDevIdPool::new_from_ids(&[]),
             filesystems: Table::default(),
             mdv,
@@ -371,6 +566,14 @@ impl ThinPool {
             pool_state: PoolState::Initializing,
             pool_extend_state: PoolExtendState::Initializing,
             free_space_state,
+            usage_history: UsageHistory::default(),
+            days_until_full: None,
+            usage_thresholds: UsageThresholds::default(),
+            watermarks: WatermarkState::default(),
+            overcommit_cap: None,
+            default_fs_size: None,
+            fs_limit: DEFAULT_FS_LIMIT,
+            snapshot_schedules: SnapshotSchedules::default(),
             dbus_path: MaybeDbusPath(None),
         })
     }
@@ -388,6 +591,9 @@ impl ThinPool {
         flex_devs: &FlexDevsSave,
         backstore: &Backstore,
     ) -> StratisResult<ThinPool> {
+        require_target(&THIN_POOL)?;
+        require_target(&THIN)?;
+
         let mdv_segments = flex_devs.meta_dev.to_vec();
         let meta_segments = flex_devs.thin_meta_dev.to_vec();
         let data_segments = flex_devs.thin_data_dev.to_vec();
@@ -466,7 +672,18 @@ impl ThinPool {
         }
 
         let thin_ids: Vec<ThinDevId> = filesystem_metadatas.iter().map(|x| x.thin_id).collect();
+
+        verify_flex_mapping(pool_uuid, FlexRole::ThinMeta, backstore_device, &meta_segments)?;
+        verify_flex_mapping(pool_uuid, FlexRole::ThinData, backstore_device, &data_segments)?;
+        verify_flex_mapping(
+            pool_uuid,
+            FlexRole::MetadataVolume,
+            backstore_device,
+            &mdv_segments,
+        )?;
+
         Ok(ThinPool {
+            pool_uuid,
             thin_pool: thinpool_dev,
             meta_segments,
             meta_spare_segments: spare_segments,
@@ -479,6 +696,23 @@ impl ThinPool {
             pool_state: PoolState::Initializing,
             pool_extend_state: PoolExtendState::Initializing,
             free_space_state,
+            usage_history: UsageHistory::default(),
+            days_until_full: None,
+            usage_thresholds: thin_pool_save
+                .usage_thresholds
+                .as_ref()
+                .map_or_else(UsageThresholds::default, |t| UsageThresholds::new(t)),
+            watermarks: WatermarkState::new(Watermarks {
+                warn: thin_pool_save.warn_watermark,
+                extend: thin_pool_save.extend_watermark,
+            }),
+            overcommit_cap: thin_pool_save.overcommit_cap,
+            default_fs_size: thin_pool_save.default_fs_size,
+            fs_limit: thin_pool_save.fs_limit.unwrap_or(u64::max_value()),
+            snapshot_schedules: thin_pool_save
+                .snapshot_schedules
+                .as_ref()
+                .map_or_else(SnapshotSchedules::default, |s| SnapshotSchedules::from(&s[..])),
             dbus_path: MaybeDbusPath(None),
         })
     }
@@ -487,10 +721,10 @@ impl ThinPool {
     /// Returns a bool communicating if a configuration change requiring a
     /// metadata save has been made.
     pub fn check(&mut self, pool_uuid: PoolUuid, backstore: &mut Backstore) -> StratisResult<bool> {
-        // Calculate amount to request for data- or meta- device.
+        // Calculate amount to request for data device.
         // Return None if device does not need to be expanded.
-        // Returned request, if it exists, is always INITIAL_META_SIZE
-        // for meta device, 1 Gi for data device.
+        // Returned request, if it exists, is the larger of the amount of
+        // dirty memory and DATA_EXPAND_SIZE.
         // Since one event can have many potential causes (meta extension,
         // data extension OR space state check), check remaining against
         // low_water to see if our condition was even the cause of the event.
@@ -498,17 +732,12 @@ impl ThinPool {
             total: Sectors,
             used: Sectors,
             low_water: Sectors,
-            data: bool,
         ) -> Option<Sectors> {
             let remaining = total - used;
             if remaining <= low_water {
-                Some(if data {
-                    match current_dirty_mem() {
-                        Ok(dirty_mem_size) => max(dirty_mem_size, DATA_EXPAND_SIZE),
-                        Err(_) => DATA_EXPAND_SIZE,
-                    }
-                } else {
-                    INITIAL_META_SIZE.sectors()
+                Some(match current_dirty_mem() {
+                    Ok(dirty_mem_size) => max(dirty_mem_size, DATA_EXPAND_SIZE),
+                    Err(_) => DATA_EXPAND_SIZE,
                 })
             } else {
                 None
@@ -528,6 +757,19 @@ impl ThinPool {
                 let mut meta_extend_failed = false;
                 let mut data_extend_failed = false;
                 match status.summary {
+                    // The kernel sets the needs_check flag when it finds a
+                    // problem with the metadata. The pool then stays read
+                    // only until the metadata has been checked.
+                    _ if status.needs_check => {
+                        error!("Thinpool metadata needs check! -> NeedsCheck");
+                        self.set_state(PoolState::NeedsCheck);
+                    }
+                    // The kernel does not notice that a device under the
+                    // pool is gone until I/O to it fails.
+                    _ if backstore.has_missing() => {
+                        error!("Thinpool blockdev missing! -> Failed");
+                        self.set_state(PoolState::Failed);
+                    }
                     ThinPoolStatusSummary::Good => {
                         self.set_state(PoolState::Running);
                     }
@@ -547,24 +789,44 @@ impl ThinPool {
 
                 // Kernel 4.19+ includes the kernel-set meta lowater value in
                 // thinpool status. For older kernels, use a default value.
-                let meta_lowater = status
-                    .meta_low_water
-                    .map(MetaBlocks)
-                    .unwrap_or(META_LOWATER_FALLBACK);
-                if let Some(request) = calculate_extension_request(
-                    usage.total_meta.sectors(),
-                    usage.used_meta.sectors(),
-                    meta_lowater.sectors(),
-                    false,
-                ) {
-                    match self.extend_thin_meta_device(pool_uuid, backstore, request) {
+                let meta_lowater = calc_meta_lowater(
+                    usage.total_meta,
+                    status.meta_low_water.map(MetaBlocks),
+                );
+                if let Some(request) =
+                    meta_extension_request(usage.total_meta, usage.used_meta, meta_lowater)
+                {
+                    match self.extend_thin_meta_device(pool_uuid, backstore, request.sectors()) {
                         Ok(extend_size) => {
                             info!("Extended thin meta device by {}", extend_size);
                             should_save = true;
+                            self.notify_meta_extended(
+                                true,
+                                usage.total_meta.sectors() + extend_size,
+                                "",
+                            );
+                            // A pool which ran out of meta space leaves read
+                            // only mode once the meta device is extended,
+                            // unless its metadata needs checking.
+                            if self.pool_state == PoolState::ReadOnly {
+                                if let ThinPoolStatus::Working(ref status) =
+                                    self.thin_pool.status(get_dm())?
+                                {
+                                    if status.summary == ThinPoolStatusSummary::Good {
+                                        info!("Thinpool meta extended -> Running");
+                                        self.set_state(PoolState::Running);
+                                    }
+                                }
+                            }
                         }
                         Err(err) => {
                             meta_extend_failed = true;
                             error!("Thinpool meta extend failed! -> reason {:?}", err);
+                            self.notify_meta_extended(
+                                false,
+                                usage.total_meta.sectors(),
+                                &err.to_string(),
+                            );
                         }
                     }
                 }
@@ -574,7 +836,6 @@ impl ThinPool {
                         datablocks_to_sectors(usage.total_data),
                         datablocks_to_sectors(usage.used_data),
                         datablocks_to_sectors(self.thin_pool.table().table.params.low_water_mark),
-                        true,
                     ) {
                         None => DataBlocks(0),
                         Some(request) => {
@@ -603,12 +864,30 @@ impl ThinPool {
 
                 let current_total = usage.total_data + extend_size;
 
+                let data_used_pct = used_pct(*usage.used_data, *current_total);
+                if let Some(rising) = self.watermarks.update_extend(data_used_pct) {
+                    get_engine_listener_list().notify(&EngineEvent::PoolWatermarkCrossed {
+                        dbus_path: self.get_dbus_path(),
+                        watermark: "extend",
+                        percent: self.watermarks
+                            .watermarks()
+                            .extend
+                            .expect("only a watermark that is set is crossed"),
+                        rising,
+                        used: datablocks_to_sectors(usage.used_data),
+                        total: datablocks_to_sectors(current_total),
+                    });
+                }
+
                 // Update pool space state
-                self.free_space_check(
-                    usage.used_data,
-                    current_total + sectors_to_datablocks(backstore.available_in_backstore())
-                        - usage.used_data,
-                )?;
+                let available = current_total
+                    + sectors_to_datablocks(backstore.available_in_backstore())
+                    - usage.used_data;
+                self.free_space_check(usage.used_data, available)?;
+
+                self.usage_history.record(Utc::now(), usage.used_data);
+                let days_until_full = self.usage_history.days_until_full(available);
+                self.set_days_until_full(days_until_full);
 
                 // Trigger next event depending on pool space state
                 let lowater = calc_lowater(
@@ -616,6 +895,8 @@ impl ThinPool {
                     sectors_to_datablocks(backstore.available_in_backstore()),
                     self.free_space_state,
                 );
+                let lowater =
+                    watermark_lowater(current_total, lowater, self.watermarks.watermarks().extend);
 
                 self.thin_pool.set_low_water_mark(get_dm(), lowater)?;
                 self.resume()?;
@@ -648,6 +929,7 @@ impl ThinPool {
             self.pool_state = new_state;
             get_engine_listener_list().notify(&EngineEvent::PoolStateChanged {
                 dbus_path: self.get_dbus_path(),
+                pool_uuid: self.pool_uuid,
                 state: new_state,
             });
         }
@@ -666,6 +948,7 @@ impl ThinPool {
             self.pool_extend_state = new_state;
             get_engine_listener_list().notify(&EngineEvent::PoolExtendStateChanged {
                 dbus_path: self.get_dbus_path(),
+                pool_uuid: self.pool_uuid,
                 state: new_state,
             });
         }
@@ -676,11 +959,33 @@ impl ThinPool {
             self.free_space_state = new_state;
             get_engine_listener_list().notify(&EngineEvent::PoolSpaceStateChanged {
                 dbus_path: self.get_dbus_path(),
+                pool_uuid: self.pool_uuid,
                 state: new_state,
             });
         }
     }
 
+    fn set_days_until_full(&mut self, days_until_full: Option<u64>) {
+        if self.days_until_full != days_until_full {
+            self.days_until_full = days_until_full;
+            get_engine_listener_list().notify(&EngineEvent::PoolDaysUntilFullChanged {
+                dbus_path: self.get_dbus_path(),
+                days_until_full,
+            });
+        }
+    }
+
+    /// Announce that the metadata device was extended automatically to
+    /// total, or that it could not be, for reason.
+    fn notify_meta_extended(&self, succeeded: bool, total: Sectors, reason: &str) {
+        get_engine_listener_list().notify(&EngineEvent::PoolThinMetaExtended {
+            dbus_path: self.get_dbus_path(),
+            succeeded,
+            total,
+            reason,
+        });
+    }
+
     /// Possibly transition to a new FreeSpaceState based on usage, and invoke
     /// policies (throttling, suspension) accordingly.
     fn free_space_check(
@@ -688,21 +993,33 @@ impl ThinPool {
         used: DataBlocks,
         available: DataBlocks,
     ) -> StratisResult<FreeSpaceState> {
-        // Return a value from 0 to 100 that is the percentage that "used"
-        // makes up in "total".
-        fn used_pct(used: u64, total: u64) -> u8 {
-            assert!(total >= used);
-            let mut val = (used * 100) / total;
-            if (used * 100) % total != 0 {
-                val += 1; // round up
-            }
-            assert!(val <= 100);
-            val as u8
-        }
-
         let overall_used_pct = used_pct(*used, *used + *available);
         info!("Data tier percent used: {}", overall_used_pct);
 
+        for (threshold, rising) in self.usage_thresholds.update(overall_used_pct) {
+            get_engine_listener_list().notify(&EngineEvent::PoolUsageThresholdCrossed {
+                dbus_path: self.get_dbus_path(),
+                threshold,
+                rising,
+                used: datablocks_to_sectors(used),
+                total: datablocks_to_sectors(used + available),
+            });
+        }
+
+        if let Some(rising) = self.watermarks.update_warn(overall_used_pct) {
+            get_engine_listener_list().notify(&EngineEvent::PoolWatermarkCrossed {
+                dbus_path: self.get_dbus_path(),
+                watermark: "warn",
+                percent: self.watermarks
+                    .watermarks()
+                    .warn
+                    .expect("only a watermark that is set is crossed"),
+                rising,
+                used: datablocks_to_sectors(used),
+                total: datablocks_to_sectors(used + available),
+            });
+        }
+
         let new_state = if overall_used_pct < SPACE_WARN_PCT {
             FreeSpaceState::Good
         } else if overall_used_pct < SPACE_CRIT_PCT {
@@ -766,7 +1083,8 @@ impl ThinPool {
         for (_, _, ref mut fs) in &mut self.filesystems {
             fs.teardown()?;
         }
-        self.thin_pool.teardown(get_dm())?;
+        let name = self.thin_pool.name().to_owned();
+        watch_dm(&name, "teardown", || self.thin_pool.teardown(get_dm()))?;
 
         // ..but MDV has no DM dependencies with the above
         self.mdv.teardown()?;
@@ -798,6 +1116,8 @@ impl ThinPool {
     }
 
     /// Extend thinpool's meta dev. See extend_thin_sub_device for more info.
+    /// The spare is extended by as much, if there is space for it, so that
+    /// the metadata can still be repaired onto it.
     fn extend_thin_meta_device(
         &mut self,
         pool_uuid: PoolUuid,
@@ -809,7 +1129,7 @@ impl ThinPool {
             pool_uuid, extend_size,
         );
 
-        ThinPool::extend_thin_sub_device(
+        let extended = ThinPool::extend_thin_sub_device(
             pool_uuid,
             &mut self.thin_pool,
             backstore,
@@ -817,7 +1137,30 @@ impl ThinPool {
             MetaBlocks(1).sectors(),
             &mut self.meta_segments,
             false,
-        )
+        )?;
+
+        match backstore.request(pool_uuid, extended, MetaBlocks(1).sectors()) {
+            Ok(Some(region)) => {
+                if region.1 < extended {
+                    warn!(
+                        "thinpool meta spare of pool {} could only be extended by {} of {}",
+                        pool_uuid, region.1, extended
+                    );
+                }
+                self.meta_spare_segments = coalesce_segs(&self.meta_spare_segments, &[region]);
+            }
+            Ok(None) => warn!(
+                "no space to extend thinpool meta spare of pool {}, the meta device may \
+                 not be repairable",
+                pool_uuid
+            ),
+            Err(err) => warn!(
+                "unable to extend thinpool meta spare of pool {}: {}",
+                pool_uuid, err
+            ),
+        }
+
+        Ok(extended)
     }
 
     /// Extend the thinpool's data or meta devices. The result is the value
@@ -841,13 +1184,22 @@ impl ThinPool {
                 .device()
                 .expect("If request succeeded, backstore must have cap device.");
             let mut segments = coalesce_segs(existing_segs, &[region]);
-            if data {
-                thinpooldev.set_data_table(get_dm(), segs_to_table(device, &segments))?;
-            } else {
-                thinpooldev.set_meta_table(get_dm(), segs_to_table(device, &segments))?;
-            }
+            let name = thinpooldev.name().to_owned();
+            watch_dm(&name, "reload", || {
+                if data {
+                    thinpooldev.set_data_table(get_dm(), segs_to_table(device, &segments))
+                } else {
+                    thinpooldev.set_meta_table(get_dm(), segs_to_table(device, &segments))
+                }
+            })?;
 
-            thinpooldev.resume(get_dm())?;
+            resume_dm(&name)?;
+            let role = if data {
+                FlexRole::ThinData
+            } else {
+                FlexRole::ThinMeta
+            };
+            verify_flex_mapping(pool_uuid, role, device, &segments)?;
             existing_segs.clear();
             existing_segs.append(&mut segments);
 
@@ -884,6 +1236,21 @@ impl ThinPool {
         Ok(data_dev_used + spare_total + meta_dev_total + mdv_total)
     }
 
+    /// The percentage of the thin pool's data device that is in use,
+    /// rounded up.
+    pub fn data_used_pct(&self) -> StratisResult<u8> {
+        match self.thin_pool.status(get_dm())? {
+            ThinPoolStatus::Working(ref status) => Ok(used_pct(
+                *status.usage.used_data,
+                *status.usage.total_data,
+            )),
+            _ => {
+                let err_msg = "thin pool failed, could not obtain usage";
+                Err(StratisError::Engine(ErrorEnum::Invalid, err_msg.into()))
+            }
+        }
+    }
+
     pub fn get_filesystem_by_uuid(&self, uuid: FilesystemUuid) -> Option<(Name, &StratFilesystem)> {
         self.filesystems.get_by_uuid(uuid)
     }
@@ -924,44 +1291,165 @@ impl ThinPool {
             .collect()
     }
 
+    /// Make sure that the thin pool's data device has at least size
+    /// sectors unallocated, extending it from the backstore if necessary.
+    /// Space is kept beyond size so that allocating the reserved space
+    /// does not bring the data device below its low water mark.
+    /// Returns an error if the backstore can not supply the space.
+    pub fn reserve_data(
+        &mut self,
+        pool_uuid: PoolUuid,
+        backstore: &mut Backstore,
+        size: Sectors,
+    ) -> StratisResult<()> {
+        let (total, used) = match self.thin_pool.status(get_dm())? {
+            ThinPoolStatus::Working(ref status) => (
+                datablocks_to_sectors(status.usage.total_data),
+                datablocks_to_sectors(status.usage.used_data),
+            ),
+            ThinPoolStatus::Fail => {
+                let err_msg = "thin pool failed, could not obtain usage";
+                return Err(StratisError::Engine(ErrorEnum::Invalid, err_msg.into()));
+            }
+        };
+
+        let lowater = datablocks_to_sectors(DATA_LOWATER);
+        let required = size + lowater;
+        let free = total - used;
+        if free >= required {
+            return Ok(());
+        }
+
+        let extended = self.extend_thin_data_device(pool_uuid, backstore, required - free)?;
+        let available = free + extended;
+        if available < required {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("SpaceUnavailable")
+                    .param("requested", *size)
+                    .param("available", (*available).saturating_sub(*lowater)),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The default filesystem size set for this pool, if any.
+    pub fn default_fs_size(&self) -> Option<Sectors> {
+        self.default_fs_size
+    }
+
+    /// Set the default filesystem size, or go back to DEFAULT_THIN_DEV_SIZE
+    /// if size is None. The caller is responsible for saving the pool's
+    /// metadata afterwards.
+    pub fn set_default_fs_size(&mut self, size: Option<Sectors>) {
+        self.default_fs_size = size;
+    }
+
+    /// The size of a new filesystem, given the size requested for it.
+    pub fn fs_size(&self, size: Option<Sectors>) -> Sectors {
+        size.or(self.default_fs_size).unwrap_or(DEFAULT_THIN_DEV_SIZE)
+    }
+
     /// Create a filesystem within the thin pool. Given name must not
-    /// already be in use.
+    /// already be in use. If size is None, the pool's default filesystem
+    /// size is used.
+    /// If guaranteed is true, all the space of the filesystem is allocated
+    /// from the thin pool; space for it must have been reserved.
+    /// The creator, if given, is recorded in the filesystem's metadata.
+    #[cfg(test)]
     pub fn create_filesystem(
         &mut self,
         pool_uuid: PoolUuid,
         pool_name: &str,
         name: &str,
         size: Option<Sectors>,
+        guaranteed: bool,
+        created_by: Option<Creator>,
     ) -> StratisResult<FilesystemUuid> {
-        let (fs_uuid, mut new_filesystem) =
-            StratFilesystem::initialize(pool_uuid, &self.thin_pool, size, self.id_gen.new_id()?)?;
-        let name = Name::new(name.to_owned());
-        if let Err(err) = self.mdv.save_fs(&name, fs_uuid, &new_filesystem) {
-            fs_settle();
-            if let Err(err2) = new_filesystem.destroy(&self.thin_pool) {
-                error!(
-                    "When handling failed save_fs(), fs.destroy() failed: {}",
-                    err2
+        self.create_filesystems(pool_uuid, pool_name, &[(name, size, guaranteed)], created_by)
+            .map(|fs_uuids| fs_uuids[0])
+    }
+
+    /// Create a filesystem for each of specs, a name, a size, and whether
+    /// its space is guaranteed, and save the info on all of them to the MDV
+    /// at once. Either all the filesystems are created or none are.
+    /// Returns the UUIDs of the filesystems in the order of specs.
+    pub fn create_filesystems(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        specs: &[(&str, Option<Sectors>, bool)],
+        created_by: Option<Creator>,
+    ) -> StratisResult<Vec<FilesystemUuid>> {
+        let mut created = Vec::new();
+        let mut failure = None;
+        for &(name, size, guaranteed) in specs {
+            let size = self.fs_size(size);
+            let initialized = self.id_gen.new_id().and_then(|thin_id| {
+                StratFilesystem::initialize(
+                    pool_uuid,
+                    &self.thin_pool,
+                    size,
+                    thin_id,
+                    guaranteed,
+                    created_by.clone(),
                 )
+            });
+            match initialized {
+                Ok((fs_uuid, filesystem)) => {
+                    created.push((Name::new(name.to_owned()), fs_uuid, filesystem))
+                }
+                Err(err) => {
+                    failure = Some(err);
+                    break;
+                }
+            }
+        }
+
+        if failure.is_none() {
+            let records = created
+                .iter()
+                .map(|&(ref name, fs_uuid, ref filesystem)| (name, fs_uuid, filesystem))
+                .collect::<Vec<_>>();
+            failure = self.mdv.save_fses(&records).err();
+        }
+
+        if let Some(err) = failure {
+            fs_settle();
+            for (_, _, mut filesystem) in created {
+                if let Err(err2) = filesystem.destroy(&self.thin_pool) {
+                    error!(
+                        "When handling failed create_filesystems(), fs.destroy() failed: {}",
+                        err2
+                    )
+                }
             }
             return Err(err);
         }
-        devlinks::filesystem_added(pool_name, &name, &new_filesystem.devnode());
-        self.filesystems.insert(name, fs_uuid, new_filesystem);
 
-        Ok(fs_uuid)
+        let mut fs_uuids = Vec::new();
+        for (name, fs_uuid, filesystem) in created {
+            devlinks::filesystem_added(pool_name, &name, &filesystem.devnode());
+            self.filesystems.insert(name, fs_uuid, filesystem);
+            fs_uuids.push(fs_uuid);
+        }
+
+        Ok(fs_uuids)
     }
 
     /// Create a filesystem snapshot of the origin.  Given origin_uuid
     /// must exist.  Returns the Uuid of the new filesystem.
+    /// If freeze is true, a mounted origin is frozen while the snapshot is
+    /// taken.
     pub fn snapshot_filesystem(
         &mut self,
         pool_uuid: PoolUuid,
         pool_name: &str,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        freeze: bool,
     ) -> StratisResult<(FilesystemUuid, &mut Filesystem)> {
-        let snapshot_fs_uuid = Uuid::new_v4();
+        let snapshot_fs_uuid = FilesystemUuid::new_v4();
         let (snapshot_dm_name, snapshot_dm_uuid) =
             format_thin_ids(pool_uuid, ThinRole::Filesystem(snapshot_fs_uuid));
         let snapshot_id = self.id_gen.new_id()?;
@@ -974,11 +1462,13 @@ impl ThinPool {
                 &fs_name,
                 snapshot_fs_uuid,
                 snapshot_id,
+                origin_uuid,
+                freeze,
             )?,
             None => {
-                return Err(StratisError::Engine(
+                return Err(StratisError::Structured(
                     ErrorEnum::Error,
-                    "snapshot_filesystem failed, filesystem not found".into(),
+                    ErrorPayload::new("FilesystemNotFound").param("uuid", origin_uuid),
                 ));
             }
         };
@@ -1027,10 +1517,119 @@ impl ThinPool {
         }
     }
 
+    /// The DM devices of the thin pool, its metadata and data devices, and
+    /// the MDV, each with the role by which it is named.
+    pub fn dm_devices(&self) -> Vec<(String, DmDeviceInfo)> {
+        let meta_dev = self.thin_pool.meta_dev();
+        let data_dev = self.thin_pool.data_dev();
+        vec![
+            (
+                ThinPoolRole::Pool.to_string(),
+                DmDeviceInfo::new(self.thin_pool.name(), self.thin_pool.uuid()),
+            ),
+            (
+                FlexRole::ThinMeta.to_string(),
+                DmDeviceInfo::new(meta_dev.name(), meta_dev.uuid()),
+            ),
+            (
+                FlexRole::ThinData.to_string(),
+                DmDeviceInfo::new(data_dev.name(), data_dev.uuid()),
+            ),
+            (FlexRole::MetadataVolume.to_string(), self.mdv.dm_device()),
+        ]
+    }
+
     pub fn state(&self) -> PoolState {
         self.pool_state
     }
 
+    /// Fail the pool, as a blockdev under it has been removed from the
+    /// system.
+    pub fn blockdev_removed(&mut self) {
+        error!("Thinpool blockdev removed! -> Failed");
+        self.set_state(PoolState::Failed);
+    }
+
+    /// The size of the thin pool's metadata device and the space used on
+    /// it, as the kernel reports them now. None if the thin pool has
+    /// failed, or its status can not be read.
+    pub fn meta_usage(&self) -> Option<(Sectors, Sectors)> {
+        match self.thin_pool.status(get_dm()) {
+            Ok(ThinPoolStatus::Working(ref status)) => Some((
+                status.usage.total_meta.sectors(),
+                status.usage.used_meta.sectors(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Return an error if so little of the thin pool's metadata device is
+    /// free that a snapshot, which adds to the metadata all the mappings of
+    /// its origin, could fill it. A full metadata device makes the thin pool
+    /// read only.
+    pub fn refuse_if_meta_critical(&self) -> StratisResult<()> {
+        if let Some((total, used)) = self.meta_usage() {
+            let percent = used_pct(*used, *total);
+            if percent >= SPACE_CRIT_PCT {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NoSpace,
+                    ErrorPayload::new("ThinMetaSpaceLow").param("percent", percent),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return an error if the thin pool's metadata is flagged as needing a
+    /// check, as it is unsafe to change the metadata until it is repaired.
+    pub fn refuse_if_needs_check(&self, pool_name: &str) -> StratisResult<()> {
+        if self.pool_state == PoolState::NeedsCheck {
+            return Err(StratisError::Structured(
+                ErrorEnum::Busy,
+                ErrorPayload::new("PoolNeedsCheck").param("name", pool_name),
+            ));
+        }
+        Ok(())
+    }
+
+    /// If the thin pool's metadata is flagged as needing a check, tear down
+    /// the filesystems and the thin pool and set them up again. On setup,
+    /// the metadata is checked, which clears the flag, and is repaired if
+    /// it is inconsistent.
+    /// Returns true if the thin pool was set up again, otherwise false.
+    /// Returns an error if any filesystem is mounted.
+    pub fn repair(
+        &mut self,
+        pool_uuid: PoolUuid,
+        backstore: &mut Backstore,
+    ) -> StratisResult<bool> {
+        if self.pool_state != PoolState::NeedsCheck {
+            return Ok(false);
+        }
+        for (_, _, fs) in &self.filesystems {
+            fs.refuse_if_mounted()?;
+        }
+
+        let thin_pool_save: ThinPoolDevSave = self.record();
+        let flex_devs: FlexDevsSave = self.record();
+        let mut dbus_paths = self.filesystems
+            .iter()
+            .map(|(_, uuid, fs)| (*uuid, fs.get_dbus_path().clone()))
+            .collect::<HashMap<_, _>>();
+
+        self.teardown()?;
+        let mut thin_pool = ThinPool::setup(pool_uuid, &thin_pool_save, &flex_devs, backstore)?;
+        thin_pool.set_dbus_path(self.dbus_path.clone());
+        for (_, uuid, fs) in &mut thin_pool.filesystems {
+            if let Some(path) = dbus_paths.remove(uuid) {
+                fs.set_dbus_path(path);
+            }
+        }
+        thin_pool.check(pool_uuid, backstore)?;
+        *self = thin_pool;
+        Ok(true)
+    }
+
     pub fn extend_state(&self) -> PoolExtendState {
         self.pool_extend_state
     }
@@ -1039,6 +1638,110 @@ impl ThinPool {
         self.free_space_state
     }
 
+    /// The forecast number of days until the pool is full, if the pool's
+    /// used space is growing.
+    pub fn days_until_full(&self) -> Option<u64> {
+        self.days_until_full
+    }
+
+    /// The sum of the sizes of the thin devices of all the filesystems.
+    pub fn virtual_size(&self) -> Sectors {
+        self.filesystems.iter().map(|(_, _, fs)| fs.size()).sum()
+    }
+
+    /// The virtual size as a percentage of data_tier_size.
+    pub fn overcommit_percent(&self, data_tier_size: Sectors) -> Option<u64> {
+        overcommit_percent(self.virtual_size(), data_tier_size)
+    }
+
+    pub fn overcommit_cap(&self) -> Option<u64> {
+        self.overcommit_cap
+    }
+
+    /// Set the overcommit cap. The caller is responsible for saving the
+    /// pool's metadata afterwards.
+    pub fn set_overcommit_cap(&mut self, cap: Option<u64>) {
+        self.overcommit_cap = cap;
+    }
+
+    /// The usage thresholds, in ascending order.
+    pub fn usage_thresholds(&self) -> &[u8] {
+        self.usage_thresholds.thresholds()
+    }
+
+    /// Replace the usage thresholds, unless they are the same as the
+    /// current ones. Usage is considered to be below all of the new
+    /// thresholds until it is next checked. The caller is responsible for
+    /// saving the pool's metadata afterwards.
+    /// Returns true if the thresholds were changed, otherwise false.
+    pub fn set_usage_thresholds(&mut self, thresholds: &[u8]) -> bool {
+        let new_thresholds = UsageThresholds::new(thresholds);
+        if new_thresholds.thresholds() == self.usage_thresholds.thresholds() {
+            return false;
+        }
+        self.usage_thresholds = new_thresholds;
+        true
+    }
+
+    pub fn watermarks(&self) -> Watermarks {
+        self.watermarks.watermarks()
+    }
+
+    /// Replace the watermarks, unless they are the same as the current ones.
+    /// Usage is considered to be below both until it is next checked, when
+    /// the extend watermark also takes effect. The caller is responsible for
+    /// saving the pool's metadata afterwards.
+    /// Returns true if the watermarks were changed, otherwise false.
+    pub fn set_watermarks(&mut self, watermarks: Watermarks) -> bool {
+        if watermarks == self.watermarks.watermarks() {
+            return false;
+        }
+        self.watermarks = WatermarkState::new(watermarks);
+        true
+    }
+
+    /// Return an error if creating filesystems or snapshots with a total
+    /// size of additional sectors would take the overcommit percentage
+    /// beyond the cap.
+    pub fn check_overcommit(&self, data_tier_size: Sectors, additional: Sectors) -> StratisResult<()> {
+        check_overcommit(self.virtual_size(), additional, data_tier_size, self.overcommit_cap)
+    }
+
+    pub fn fs_limit(&self) -> u64 {
+        self.fs_limit
+    }
+
+    /// Set the most filesystems and snapshots that the pool may hold. The
+    /// caller is responsible for saving the pool's metadata afterwards.
+    pub fn set_fs_limit(&mut self, limit: u64) {
+        self.fs_limit = limit;
+    }
+
+    /// Return an error if creating additional filesystems or snapshots
+    /// would take the number of filesystems beyond the limit.
+    pub fn check_fs_limit(&self, additional: usize) -> StratisResult<()> {
+        check_fs_limit(self.filesystems.len(), additional, self.fs_limit)
+    }
+
+    pub fn snapshot_schedules(&self) -> &SnapshotSchedules {
+        &self.snapshot_schedules
+    }
+
+    /// The snapshot schedules of the filesystems, to be changed. The caller
+    /// is responsible for saving the pool's metadata afterwards.
+    pub fn snapshot_schedules_mut(&mut self) -> &mut SnapshotSchedules {
+        &mut self.snapshot_schedules
+    }
+
+    /// Forget the snapshot schedules of filesystems that have been
+    /// destroyed, and the destroyed snapshots that schedules took.
+    /// Returns true if anything was forgotten.
+    pub fn forget_destroyed_schedules(&mut self) -> bool {
+        let filesystems = &self.filesystems;
+        self.snapshot_schedules
+            .forget_missing(|uuid| filesystems.get_by_uuid(uuid).is_some())
+    }
+
     /// Rename a filesystem within the thin pool.
     pub fn rename_filesystem(
         &mut self,
@@ -1069,6 +1772,126 @@ impl ThinPool {
         }
     }
 
+    /// Set the read-ahead of the filesystem with the given UUID, and save it
+    /// in the filesystem's metadata. If the metadata can not be saved, the
+    /// previous read-ahead is restored.
+    /// Returns true if the read-ahead was changed, otherwise false.
+    pub fn set_filesystem_read_ahead(
+        &mut self,
+        uuid: FilesystemUuid,
+        read_ahead: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        let (name, filesystem) = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some(found) => found,
+            None => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NotFound,
+                    ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+                ));
+            }
+        };
+
+        let previous = filesystem.read_ahead();
+        if previous == read_ahead {
+            return Ok(false);
+        }
+        filesystem.set_read_ahead(read_ahead)?;
+        if let Err(err) = self.mdv.save_fs(&name, uuid, filesystem) {
+            if let Err(err2) = filesystem.set_read_ahead(previous) {
+                warn!("failed to restore read-ahead of filesystem {}: {}", uuid, err2);
+            }
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// Protect the filesystem with the given UUID from being destroyed, or
+    /// unprotect it. Returns true if the protection was changed.
+    pub fn set_filesystem_protected(
+        &mut self,
+        uuid: FilesystemUuid,
+        protected: bool,
+    ) -> StratisResult<bool> {
+        let (name, filesystem) = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some(found) => found,
+            None => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NotFound,
+                    ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+                ));
+            }
+        };
+
+        if filesystem.protected() == protected {
+            return Ok(false);
+        }
+        filesystem.set_protected(protected);
+        if let Err(err) = self.mdv.save_fs(&name, uuid, filesystem) {
+            filesystem.set_protected(!protected);
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// Set the size limit of the filesystem with the given UUID, extending
+    /// it to the limit, and save it in the filesystem's metadata. The
+    /// extension can not be undone, so if the metadata can not be saved,
+    /// the limit remains in effect until the metadata is next saved.
+    /// Returns true if the limit was changed, otherwise false.
+    pub fn set_filesystem_size_limit(
+        &mut self,
+        uuid: FilesystemUuid,
+        limit: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        let (name, filesystem) = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some(found) => found,
+            None => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::NotFound,
+                    ErrorPayload::new("FilesystemNotFound").param("uuid", uuid),
+                ));
+            }
+        };
+
+        if filesystem.size_limit() == limit {
+            return Ok(false);
+        }
+        filesystem.set_size_limit(limit)?;
+        self.mdv.save_fs(&name, uuid, filesystem)?;
+        Ok(true)
+    }
+
+    /// The space mapped by each filesystem's thin device: that which it
+    /// alone maps, which destroying the filesystem would free, and that
+    /// which it shares with other thin devices, as a snapshot shares blocks
+    /// with its origin, and with the snapshots of itself. The space is
+    /// found by reading a snapshot of the thin pool's metadata, so that the
+    /// metadata can be read while the pool is in use.
+    pub fn filesystem_space(&self) -> StratisResult<Vec<(FilesystemUuid, Sectors, Sectors)>> {
+        let id = DevId::Name(self.thin_pool.name());
+        get_dm().target_msg(&id, None, "reserve_metadata_snap")?;
+        let output = thin_ls(&self.thin_pool.meta_dev().devnode());
+        if let Err(err) = get_dm().target_msg(&id, None, "release_metadata_snap") {
+            warn!(
+                "failed to release metadata snapshot of thin pool {}: {}",
+                self.thin_pool.name(),
+                err
+            );
+        }
+        let space = parse_thin_ls(&output?)?;
+
+        Ok(self.filesystems
+            .iter()
+            .map(|(_, &uuid, filesystem)| {
+                let (exclusive, shared) = space
+                    .get(&filesystem.thin_id())
+                    .cloned()
+                    .unwrap_or((Sectors(0), Sectors(0)));
+                (uuid, exclusive, shared)
+            })
+            .collect())
+    }
+
     /// The names of DM devices belonging to this pool that may generate events
     pub fn get_eventing_dev_names(&self, pool_uuid: PoolUuid) -> Vec<DmNameBuf> {
         vec![
@@ -1082,7 +1905,8 @@ impl ThinPool {
     /// Suspend the thinpool
     pub fn suspend(&mut self) -> StratisResult<()> {
         // thindevs automatically suspended when thinpool is suspended
-        self.thin_pool.suspend(get_dm(), true)?;
+        let name = self.thin_pool.name().to_owned();
+        suspend_dm(&name, true)?;
         self.mdv.suspend()?;
         Ok(())
     }
@@ -1091,73 +1915,11 @@ impl ThinPool {
     pub fn resume(&mut self) -> StratisResult<()> {
         self.mdv.resume()?;
         // thindevs automatically resumed here
-        self.thin_pool.resume(get_dm())?;
+        let name = self.thin_pool.name().to_owned();
+        resume_dm(&name)?;
         Ok(())
     }
 
-    /// Set the device on all DM devices
-    pub fn set_device(&mut self, backstore_device: Device) -> StratisResult<bool> {
-        if backstore_device == self.backstore_device {
-            return Ok(false);
-        }
-
-        let xform_target_line =
-            |line: &TargetLine<LinearDevTargetParams>| -> TargetLine<LinearDevTargetParams> {
-                let new_params = match line.params {
-                    LinearDevTargetParams::Linear(ref params) => LinearDevTargetParams::Linear(
-                        LinearTargetParams::new(backstore_device, params.start_offset),
-                    ),
-                    LinearDevTargetParams::Flakey(ref params) => {
-                        let feature_args = params.feature_args.iter().cloned().collect::<Vec<_>>();
-                        LinearDevTargetParams::Flakey(FlakeyTargetParams::new(
-                            backstore_device,
-                            params.start_offset,
-                            params.up_interval,
-                            params.down_interval,
-                            feature_args,
-                        ))
-                    }
-                };
-
-                TargetLine::new(line.start, line.length, new_params)
-            };
-
-        let meta_table = self.thin_pool
-            .meta_dev()
-            .table()
-            .table
-            .clone()
-            .iter()
-            .map(&xform_target_line)
-            .collect::<Vec<_>>();
-
-        let data_table = self.thin_pool
-            .data_dev()
-            .table()
-            .table
-            .clone()
-            .iter()
-            .map(&xform_target_line)
-            .collect::<Vec<_>>();
-
-        let mdv_table = self.mdv
-            .device()
-            .table()
-            .table
-            .clone()
-            .iter()
-            .map(&xform_target_line)
-            .collect::<Vec<_>>();
-
-        self.thin_pool.set_meta_table(get_dm(), meta_table)?;
-        self.thin_pool.set_data_table(get_dm(), data_table)?;
-        self.mdv.set_table(mdv_table)?;
-
-        self.backstore_device = backstore_device;
-
-        Ok(true)
-    }
-
     pub fn set_dbus_path(&mut self, path: MaybeDbusPath) -> () {
         self.dbus_path = path
     }
@@ -1182,6 +1944,25 @@ impl Recordable<ThinPoolDevSave> for ThinPool {
     fn record(&self) -> ThinPoolDevSave {
         ThinPoolDevSave {
             data_block_size: self.thin_pool.data_block_size(),
+            overcommit_cap: self.overcommit_cap,
+            default_fs_size: self.default_fs_size,
+            usage_thresholds: if self.usage_thresholds.is_default() {
+                None
+            } else {
+                Some(self.usage_thresholds.thresholds().to_vec())
+            },
+            warn_watermark: self.watermarks.watermarks().warn,
+            extend_watermark: self.watermarks.watermarks().extend,
+            fs_limit: if self.fs_limit == u64::max_value() {
+                None
+            } else {
+                Some(self.fs_limit)
+            },
+            snapshot_schedules: if self.snapshot_schedules.states().is_empty() {
+                None
+            } else {
+                Some(self.snapshot_schedules.record())
+            },
         }
     }
 }
@@ -1256,11 +2037,12 @@ mod tests {
 
     use nix::mount::{mount, umount, MsFlags};
     use tempfile;
-    use uuid::Uuid;
 
     use devicemapper::{Bytes, SECTOR_SIZE};
 
-    use super::super::super::backstore::MIN_MDA_SECTORS;
+    use super::super::super::super::types::{CacheType, SignaturePolicy};
+
+    use super::super::super::backstore::{MetadataLayout, MIN_MDA_SECTORS};
     use super::super::super::cmd;
     use super::super::super::device::SyncAll;
     use super::super::super::tests::{loopbacked, real};
@@ -1271,13 +2053,105 @@ mod tests {
 
     const BYTES_PER_WRITE: usize = 2 * IEC::Ki as usize * SECTOR_SIZE as usize;
 
+    #[test]
+    /// Verify that the space of each thin device is parsed from the output
+    /// of thin_ls, and that output of an unexpected form is an error.
+    fn test_parse_thin_ls() {
+        let space = parse_thin_ls("0 2048 1024\n1 0 1024\n\n").unwrap();
+        assert_eq!(space.len(), 2);
+        assert_eq!(
+            space.get(&ThinDevId::new_u64(0).unwrap()),
+            Some(&(Sectors(2048), Sectors(1024)))
+        );
+        assert_eq!(
+            space.get(&ThinDevId::new_u64(1).unwrap()),
+            Some(&(Sectors(0), Sectors(1024)))
+        );
+
+        assert!(parse_thin_ls("0 2048").is_err());
+        assert!(parse_thin_ls("0 2048 lots").is_err());
+    }
+
+    #[test]
+    /// Verify that the extend watermark raises the low water mark only when
+    /// it would have the data device extended sooner.
+    fn test_watermark_lowater() {
+        let size = DataBlocks(10000);
+        assert_eq!(watermark_lowater(size, DATA_LOWATER, None), DATA_LOWATER);
+        assert_eq!(
+            watermark_lowater(size, DATA_LOWATER, Some(70)),
+            DataBlocks(3000)
+        );
+        assert_eq!(
+            watermark_lowater(size, DATA_LOWATER, Some(90)),
+            DATA_LOWATER
+        );
+    }
+
+    #[test]
+    /// Verify that the meta low water is the kernel's for a small meta
+    /// device, but grows with a large one.
+    fn test_calc_meta_lowater() {
+        assert_eq!(
+            calc_meta_lowater(INITIAL_META_SIZE, None),
+            META_LOWATER_FALLBACK
+        );
+        assert_eq!(
+            calc_meta_lowater(INITIAL_META_SIZE, Some(MetaBlocks(4))),
+            MetaBlocks(1024)
+        );
+        assert_eq!(
+            calc_meta_lowater(MetaBlocks(16), Some(MetaBlocks(4))),
+            MetaBlocks(4)
+        );
+        assert_eq!(
+            calc_meta_lowater(MetaBlocks(64 * IEC::Ki), Some(MetaBlocks(1024))),
+            MetaBlocks(16 * IEC::Ki)
+        );
+    }
+
+    #[test]
+    /// Verify that a meta device that is exhausted, or nearly so, is
+    /// extended in proportion to its size, but never beyond the largest
+    /// size the kernel makes use of.
+    fn test_meta_extension_request() {
+        let lowater = MetaBlocks(1024);
+        assert_eq!(
+            meta_extension_request(INITIAL_META_SIZE, MetaBlocks(16), lowater),
+            None
+        );
+        assert_eq!(
+            meta_extension_request(INITIAL_META_SIZE, INITIAL_META_SIZE, lowater),
+            Some(INITIAL_META_SIZE)
+        );
+        assert_eq!(
+            meta_extension_request(MetaBlocks(64 * IEC::Ki), MetaBlocks(64 * IEC::Ki), lowater),
+            Some(MetaBlocks(16 * IEC::Ki))
+        );
+        let almost_max = MAX_META_SIZE - MetaBlocks(8);
+        assert_eq!(
+            meta_extension_request(almost_max, almost_max, lowater),
+            Some(MetaBlocks(8))
+        );
+        assert_eq!(
+            meta_extension_request(MAX_META_SIZE, MAX_META_SIZE, lowater),
+            None
+        );
+    }
+
     /// Verify that a full pool extends properly when additional space is added.
     fn test_full_pool(paths: &[&Path]) {
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::setup_dev_path().unwrap();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
         let (first_path, remaining_paths) = paths.split_at(1);
-        let mut backstore = Backstore::initialize(pool_uuid, &first_path, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            &first_path,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1287,7 +2161,8 @@ mod tests {
 
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
-        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None)
+        let fs_uuid = pool
+            .create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None, false, None)
             .unwrap();
         let write_buf = &[8u8; BYTES_PER_WRITE];
         let source_tmp_dir = tempfile::Builder::new()
@@ -1345,7 +2220,7 @@ mod tests {
         cmd::udev_settle().unwrap();
 
         // Add block devices to the pool and run check() to extend
-        backstore.add_datadevs(pool_uuid, &remaining_paths).unwrap();
+        backstore.add_datadevs(pool_uuid, &remaining_paths, SignaturePolicy::Refuse).unwrap();
         pool.check(pool_uuid, &mut backstore).unwrap();
         // Verify the pool is back in a Good state
         match pool.thin_pool.status(get_dm()).unwrap() {
@@ -1380,11 +2255,18 @@ mod tests {
         );
     }
 
-    /// Verify a snapshot has the same files and same contents as the origin.
+    /// Verify a snapshot has the same files and same contents as the origin,
+    /// and that an origin frozen for a snapshot is thawed after.
     fn test_filesystem_snapshot(paths: &[&Path]) {
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1394,7 +2276,8 @@ mod tests {
 
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
-        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None)
+        let fs_uuid = pool
+            .create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None, false, None)
             .unwrap();
 
         let write_buf = &[8u8; SECTOR_SIZE];
@@ -1440,8 +2323,20 @@ mod tests {
             datablocks_to_sectors(INITIAL_DATA_SIZE),
         ).unwrap();
 
+        // Freezing the mounted origin for a snapshot leaves it thawed, so
+        // it can still be written.
+        pool.snapshot_filesystem(pool_uuid, pool_name, fs_uuid, "test_snapshot_frozen", true)
+            .unwrap();
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(source_tmp_dir.path().join("stratis_test_thawed.txt"))
+            .unwrap();
+        f.write_all(write_buf).unwrap();
+        f.sync_all().unwrap();
+
         let (_, snapshot_filesystem) =
-            pool.snapshot_filesystem(pool_uuid, pool_name, fs_uuid, "test_snapshot")
+            pool.snapshot_filesystem(pool_uuid, pool_name, fs_uuid, "test_snapshot", false)
                 .unwrap();
         let mut read_buf = [0u8; SECTOR_SIZE];
         let snapshot_tmp_dir = tempfile::Builder::new()
@@ -1489,9 +2384,15 @@ mod tests {
         let name1 = "name1";
         let name2 = "name2";
 
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1501,7 +2402,7 @@ mod tests {
 
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
-        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, &name1, None)
+        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, &name1, None, false, None)
             .unwrap();
 
         let action = pool.rename_filesystem(pool_name, fs_uuid, name2).unwrap();
@@ -1535,9 +2436,15 @@ mod tests {
     /// down does not fail. Clutter the original pool with a filesystem with
     /// some data on it.
     fn test_pool_setup(paths: &[&Path]) {
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1547,7 +2454,7 @@ mod tests {
 
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
-        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, "fsname", None)
+        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, "fsname", None, false, None)
             .unwrap();
 
         let tmp_dir = tempfile::Builder::new()
@@ -1594,9 +2501,15 @@ mod tests {
     /// from the thinpool, by attempting to reinstantiate it using the
     /// same thin id and verifying that it fails.
     fn test_thindev_destroy(paths: &[&Path]) -> () {
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1606,7 +2519,7 @@ mod tests {
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
         let fs_name = "stratis_test_filesystem";
-        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, &fs_name, None)
+        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, &fs_name, None, false, None)
             .unwrap();
         pool.destroy_filesystem(pool_name, fs_uuid).unwrap();
         let flexdevs: FlexDevsSave = pool.record();
@@ -1641,11 +2554,19 @@ mod tests {
     /// Verify that the meta device backing a ThinPool is expanded when meta
     /// utilization exceeds the kernel-set meta lowater mark, by creating a
     /// ThinPool with a meta device of such a small size that we've determined
-    /// it will definitely be smaller than the meta lowater value.
+    /// it will definitely be smaller than the meta lowater value. Verify also
+    /// that the spare is expanded with it, and that a meta device with
+    /// plenty free is left as it is.
     fn test_meta_expand(paths: &[&Path]) -> () {
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         let small_meta_size = MetaBlocks(16);
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         // Create a ThinPool with a very small meta device.
         let mut thin_pool = ThinPool::new(
             pool_uuid,
@@ -1665,14 +2586,38 @@ mod tests {
             }
             ThinPoolStatus::Fail => panic!("thin_pool.status() failed"),
         }
+        let spare_size = |thin_pool: &ThinPool| -> Sectors {
+            thin_pool.meta_spare_segments.iter().map(|s| s.1).sum()
+        };
+        let old_spare_size = spare_size(&thin_pool);
+
         // The meta device is smaller than meta lowater, so it should be expanded
         // in the thin_pool.check() call.
-        thin_pool.check(pool_uuid, &mut backstore).unwrap();
-        match thin_pool.thin_pool.status(get_dm()).unwrap() {
+        assert!(thin_pool.check(pool_uuid, &mut backstore).unwrap());
+        let total_meta = match thin_pool.thin_pool.status(get_dm()).unwrap() {
             ThinPoolStatus::Working(ref status) => {
                 let usage = &status.usage;
                 // validate that the meta has been expanded.
                 assert!(usage.total_meta > small_meta_size);
+                assert_eq!(status.summary, ThinPoolStatusSummary::Good);
+                usage.total_meta
+            }
+            ThinPoolStatus::Fail => panic!("thin_pool.status() failed"),
+        };
+        // The spare is expanded with the meta device, so that the meta
+        // device can still be repaired onto it.
+        assert_eq!(
+            spare_size(&thin_pool) - old_spare_size,
+            (total_meta - small_meta_size).sectors()
+        );
+        assert_eq!(thin_pool.state(), PoolState::Running);
+
+        // Now that plenty of the meta device is free, it is not expanded
+        // again.
+        thin_pool.check(pool_uuid, &mut backstore).unwrap();
+        match thin_pool.thin_pool.status(get_dm()).unwrap() {
+            ThinPoolStatus::Working(ref status) => {
+                assert_eq!(status.usage.total_meta, total_meta);
             }
             ThinPoolStatus::Fail => panic!("thin_pool.status() failed"),
         }
@@ -1706,9 +2651,15 @@ mod tests {
     /// size of the data device.
     /// 6. Run xfs_repair to verify success.
     fn test_thinpool_expand(paths: &[&Path]) -> () {
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1718,7 +2669,7 @@ mod tests {
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
         let fs_name = "stratis_test_filesystem";
-        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, fs_name, None)
+        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, fs_name, None, false, None)
             .unwrap();
 
         let fs_devnode = pool.get_filesystem_by_uuid(fs_uuid).unwrap().1.devnode();
@@ -1837,7 +2788,7 @@ mod tests {
             }
         }
         umount(tmp_dir.path()).unwrap();
-        assert!(cmd::xfs_repair(&fs_devnode).is_ok());
+        assert!(cmd::xfs_repair(&fs_devnode, false).unwrap().0);
     }
 
     #[test]
@@ -1863,9 +2814,15 @@ mod tests {
     /// expanded by calling filesystem.check() then looking at the total space
     /// compared to the original size.
     fn test_xfs_expand(paths: &[&Path]) -> () {
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1880,7 +2837,8 @@ mod tests {
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
         let fs_name = "stratis_test_filesystem";
-        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, fs_name, Some(fs_size))
+        let fs_uuid = pool
+            .create_filesystem(pool_uuid, pool_name, fs_name, Some(fs_size), false, None)
             .unwrap();
 
         // Braces to ensure f is closed before destroy and the borrow of
@@ -1932,13 +2890,136 @@ mod tests {
         real::test_with_spec(real::DeviceLimits::AtLeast(1, None, None), test_xfs_expand);
     }
 
+    /// Verify that all the space of a guaranteed filesystem is allocated
+    /// from the thin pool when the filesystem is created, and that the
+    /// guarantee is recorded in the filesystem's metadata.
+    fn test_guaranteed_filesystem(paths: &[&Path]) -> () {
+        let pool_uuid = PoolUuid::new_v4();
+        devlinks::cleanup_devlinks(Vec::new().into_iter());
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::default(),
+            DATA_BLOCK_SIZE,
+            &mut backstore,
+        ).unwrap();
+
+        let fs_size = Bytes(64 * IEC::Mi).sectors();
+        pool.reserve_data(pool_uuid, &mut backstore, fs_size).unwrap();
+        let used = pool.total_physical_used().unwrap();
+
+        let pool_name = "stratis_test_pool";
+        devlinks::pool_added(&pool_name);
+        let fs_name = "stratis_test_filesystem";
+        let fs_uuid = pool
+            .create_filesystem(pool_uuid, pool_name, fs_name, Some(fs_size), true, None)
+            .unwrap();
+        assert!(pool.total_physical_used().unwrap() >= used + fs_size);
+
+        let (name, filesystem) = pool.get_filesystem_by_uuid(fs_uuid).unwrap();
+        assert!(filesystem.guaranteed());
+        assert_eq!(filesystem.record(&name, fs_uuid).guaranteed, Some(true));
+    }
+
+    #[test]
+    pub fn loop_test_guaranteed_filesystem() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_guaranteed_filesystem,
+        );
+    }
+
+    #[test]
+    pub fn real_test_guaranteed_filesystem() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(1, None, None),
+            test_guaranteed_filesystem,
+        );
+    }
+
+    /// Verify that a newly created filesystem is found consistent, and that
+    /// it can not be checked while it is mounted.
+    fn test_check_filesystem(paths: &[&Path]) {
+        let pool_uuid = PoolUuid::new_v4();
+        devlinks::cleanup_devlinks(Vec::new().into_iter());
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::default(),
+            DATA_BLOCK_SIZE,
+            &mut backstore,
+        ).unwrap();
+
+        let pool_name = "stratis_test_pool";
+        devlinks::pool_added(&pool_name);
+        let fs_uuid = pool
+            .create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None, false, None)
+            .unwrap();
+
+        let filesystem = pool.get_filesystem_by_uuid(fs_uuid).unwrap().1;
+        assert!(filesystem.check_consistency(false).unwrap().0);
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        mount(
+            Some(&filesystem.devnode()),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        ).unwrap();
+        assert!(match filesystem.check_consistency(false) {
+            Err(ref err) => err.code() == ErrorEnum::Busy,
+            _ => false,
+        });
+        umount(tmp_dir.path()).unwrap();
+
+        assert!(filesystem.check_consistency(true).unwrap().0);
+    }
+
+    #[test]
+    pub fn loop_test_check_filesystem() {
+        loopbacked::test_with_spec(
+            loopbacked::DeviceLimits::Range(1, 3, None),
+            test_check_filesystem,
+        );
+    }
+
+    #[test]
+    pub fn real_test_check_filesystem() {
+        real::test_with_spec(
+            real::DeviceLimits::AtLeast(1, None, None),
+            test_check_filesystem,
+        );
+    }
+
     /// Just suspend and resume the device and make sure it doesn't crash.
     /// Suspend twice in succession and then resume twice in succession
     /// to check idempotency.
     fn test_suspend_resume(paths: &[&Path]) {
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let mut backstore = Backstore::initialize(pool_uuid, paths, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1948,7 +3029,7 @@ mod tests {
 
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
-        pool.create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None)
+        pool.create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None, false, None)
             .unwrap();
 
         pool.suspend().unwrap();
@@ -1975,16 +3056,22 @@ mod tests {
 
     /// Set up thinpool and backstore. Set up filesystem and write to it.
     /// Add cachedev to backstore, causing cache to be built.
-    /// Update device on self. Read written bits from filesystem
-    /// presented on cache device.
-    fn test_set_device(paths: &[&Path]) {
+    /// Verify that the backstore's device is unchanged. Read written bits
+    /// from filesystem presented on cache device.
+    fn test_add_cachedevs(paths: &[&Path]) {
         assert!(paths.len() > 1);
 
         let (paths1, paths2) = paths.split_at(paths.len() / 2);
 
-        let pool_uuid = Uuid::new_v4();
+        let pool_uuid = PoolUuid::new_v4();
         devlinks::cleanup_devlinks(Vec::new().into_iter());
-        let mut backstore = Backstore::initialize(pool_uuid, paths2, MIN_MDA_SECTORS).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_uuid,
+            paths2,
+            MIN_MDA_SECTORS,
+            MetadataLayout::default(),
+            SignaturePolicy::Refuse,
+        ).unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::default(),
@@ -1994,7 +3081,8 @@ mod tests {
 
         let pool_name = "stratis_test_pool";
         devlinks::pool_added(&pool_name);
-        let fs_uuid = pool.create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None)
+        let fs_uuid = pool
+            .create_filesystem(pool_uuid, pool_name, "stratis_test_filesystem", None, false, None)
             .unwrap();
 
         let tmp_dir = tempfile::Builder::new()
@@ -2034,12 +3122,13 @@ mod tests {
         let old_device = backstore
             .device()
             .expect("Space already allocated from backstore, backstore must have device");
-        backstore.add_cachedevs(pool_uuid, paths1).unwrap();
+        backstore
+            .add_cachedevs(pool_uuid, paths1, SignaturePolicy::Refuse, CacheType::Cache, false)
+            .unwrap();
         let new_device = backstore
             .device()
             .expect("Space already allocated from backstore, backstore must have device");
-        assert_ne!(old_device, new_device);
-        pool.set_device(new_device).unwrap();
+        assert_eq!(old_device, new_device);
         pool.resume().unwrap();
 
         let mut buf = [0u8; 10];
@@ -2065,12 +3154,12 @@ mod tests {
     }
 
     #[test]
-    pub fn loop_test_set_device() {
-        loopbacked::test_with_spec(loopbacked::DeviceLimits::Range(2, 3, None), test_set_device);
+    pub fn loop_test_add_cachedevs() {
+        loopbacked::test_with_spec(loopbacked::DeviceLimits::Range(2, 3, None), test_add_cachedevs);
     }
 
     #[test]
-    pub fn real_test_set_device() {
-        real::test_with_spec(real::DeviceLimits::AtLeast(2, None, None), test_set_device);
+    pub fn real_test_add_cachedevs() {
+        real::test_with_spec(real::DeviceLimits::AtLeast(2, None, None), test_add_cachedevs);
     }
 }
@@ -0,0 +1,164 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Track the crossing of thresholds by the percentage of a pool's data space
+// that is in use, and of the pool's watermarks. A threshold crossed upward is
+// only crossed downward again once usage has fallen some margin below it, so
+// that usage which hovers about a threshold does not produce a stream of
+// crossings. A watermark is crossed as a threshold is.
+
+use super::super::super::types::{Watermarks, DEFAULT_USAGE_THRESHOLDS};
+
+/// The number of percentage points by which usage must fall below a
+/// threshold for the threshold to be crossed downward.
+const HYSTERESIS_PCT: u8 = 5;
+
+/// A set of usage thresholds, and how many of them usage is above.
+#[derive(Debug)]
+pub struct UsageThresholds {
+    /// The thresholds, in ascending order and without duplicates.
+    thresholds: Vec<u8>,
+    /// The number of thresholds, counted from the lowest, that usage is
+    /// considered to be above.
+    level: usize,
+}
+
+impl Default for UsageThresholds {
+    fn default() -> UsageThresholds {
+        UsageThresholds::new(DEFAULT_USAGE_THRESHOLDS)
+    }
+}
+
+impl UsageThresholds {
+    /// Make a new set of thresholds, none of which has yet been crossed.
+    pub fn new(thresholds: &[u8]) -> UsageThresholds {
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort();
+        thresholds.dedup();
+        UsageThresholds {
+            thresholds,
+            level: 0,
+        }
+    }
+
+    /// The thresholds, in ascending order.
+    pub fn thresholds(&self) -> &[u8] {
+        &self.thresholds
+    }
+
+    /// Whether these are the default thresholds.
+    pub fn is_default(&self) -> bool {
+        self.thresholds == DEFAULT_USAGE_THRESHOLDS
+    }
+
+    /// Update the set with the current usage as a percentage. Return the
+    /// thresholds crossed, each paired with true if it was crossed upward,
+    /// in the order in which they were crossed.
+    pub fn update(&mut self, used_pct: u8) -> Vec<(u8, bool)> {
+        let mut crossed = Vec::new();
+        while self.level < self.thresholds.len() && used_pct >= self.thresholds[self.level] {
+            crossed.push((self.thresholds[self.level], true));
+            self.level += 1;
+        }
+        while self.level > 0
+            && used_pct.saturating_add(HYSTERESIS_PCT) <= self.thresholds[self.level - 1]
+        {
+            self.level -= 1;
+            crossed.push((self.thresholds[self.level], false));
+        }
+        crossed
+    }
+}
+
+/// The watermarks of a pool, and whether usage is above each.
+#[derive(Debug)]
+pub struct WatermarkState {
+    watermarks: Watermarks,
+    warn: UsageThresholds,
+    extend: UsageThresholds,
+}
+
+impl Default for WatermarkState {
+    fn default() -> WatermarkState {
+        WatermarkState::new(Watermarks::default())
+    }
+}
+
+impl WatermarkState {
+    /// Track the watermarks, neither of which has yet been crossed.
+    pub fn new(watermarks: Watermarks) -> WatermarkState {
+        let thresholds =
+            |percent: Option<u8>| UsageThresholds::new(&percent.into_iter().collect::<Vec<_>>());
+        WatermarkState {
+            watermarks,
+            warn: thresholds(watermarks.warn),
+            extend: thresholds(watermarks.extend),
+        }
+    }
+
+    pub fn watermarks(&self) -> Watermarks {
+        self.watermarks
+    }
+
+    /// Update with the percentage of the pool's data space in use. Return
+    /// whether the warn watermark was crossed upward, if it was crossed.
+    pub fn update_warn(&mut self, used_pct: u8) -> Option<bool> {
+        self.warn.update(used_pct).pop().map(|(_, rising)| rising)
+    }
+
+    /// Update with the percentage of the thin pool's data device in use.
+    /// Return whether the extend watermark was crossed upward, if it was
+    /// crossed.
+    pub fn update_extend(&mut self, used_pct: u8) -> Option<bool> {
+        self.extend.update(used_pct).pop().map(|(_, rising)| rising)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that thresholds are crossed upward as soon as usage reaches
+    /// them, but downward only once usage has fallen by the hysteresis
+    /// margin below them.
+    fn test_hysteresis() {
+        let mut thresholds = UsageThresholds::default();
+        assert_eq!(thresholds.update(50), vec![]);
+        assert_eq!(thresholds.update(70), vec![(70, true)]);
+        assert_eq!(thresholds.update(69), vec![]);
+        assert_eq!(thresholds.update(71), vec![]);
+        assert_eq!(thresholds.update(96), vec![(85, true), (95, true)]);
+        assert_eq!(thresholds.update(91), vec![]);
+        assert_eq!(thresholds.update(90), vec![(95, false)]);
+        assert_eq!(thresholds.update(10), vec![(85, false), (70, false)]);
+    }
+
+    #[test]
+    /// Verify that thresholds are ordered and deduplicated.
+    fn test_new_thresholds() {
+        assert_eq!(UsageThresholds::new(&[90, 50, 90]).thresholds(), &[50, 90]);
+        assert!(UsageThresholds::default().is_default());
+    }
+
+    #[test]
+    /// Verify that each watermark is crossed independently, with the
+    /// margin of a threshold, and that a watermark not set is never
+    /// crossed.
+    fn test_watermarks() {
+        let mut state = WatermarkState::new(Watermarks {
+            warn: Some(80),
+            extend: None,
+        });
+        assert_eq!(state.update_warn(79), None);
+        assert_eq!(state.update_warn(80), Some(true));
+        assert_eq!(state.update_warn(76), None);
+        assert_eq!(state.update_warn(75), Some(false));
+        assert_eq!(state.update_extend(100), None);
+
+        let mut state = WatermarkState::default();
+        assert_eq!(state.watermarks(), Watermarks::default());
+        assert_eq!(state.update_warn(100), None);
+    }
+}
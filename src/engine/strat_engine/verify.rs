@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// A debugging aid, which reads back the tables of linear DM devices once
+// they are loaded, and checks each line against the segments recorded as
+// allocated to the device. A mistake in the mapping would otherwise go
+// unnoticed until data written through the device turned up in the wrong
+// place. It is off unless turned on by an environment variable, and is
+// always on in the tests that use real or loopbacked devices.
+
+use std::env;
+
+use devicemapper::{DevId, Device, DmFlags, DmName, DmOptions, Sectors, TargetTypeBuf};
+
+use stratis::{ErrorEnum, StratisError, StratisResult};
+
+use super::dm::get_dm;
+
+/// The environment variable which, if set to anything but "" or "0", turns
+/// verification of mappings on.
+pub const VERIFY_MAPPINGS_VAR: &str = "STRATIS_VERIFY_MAPPINGS";
+
+/// Whether verification of mappings is turned on.
+fn verify_mappings_enabled() -> bool {
+    env::var_os(VERIFY_MAPPINGS_VAR).map_or(false, |value| !value.is_empty() && value != "0")
+}
+
+/// Compare the table of a linear device, as the kernel reports it, with the
+/// segments recorded as allocated to it, each given as the device on which
+/// it lies, its start on that device, and its length. Each segment must be
+/// mapped by one line, in order. Return the first difference found.
+fn compare_mapping(
+    table: &[(Sectors, Sectors, TargetTypeBuf, String)],
+    segments: &[(Device, Sectors, Sectors)],
+) -> Result<(), String> {
+    if table.len() != segments.len() {
+        return Err(format!(
+            "{} lines in the table, but {} segments recorded",
+            table.len(),
+            segments.len()
+        ));
+    }
+
+    let mut logical_start = Sectors(0);
+    for (index, (line, &(device, start, length))) in table.iter().zip(segments).enumerate() {
+        let &(line_start, line_length, ref target_type, ref params) = line;
+        let expected_params = format!("{} {}", device, *start);
+        if line_start != logical_start
+            || line_length != length
+            || target_type.to_string() != "linear"
+            || *params != expected_params
+        {
+            return Err(format!(
+                "line {} is \"{} {} {} {}\", but the segment recorded is \"{} {} linear {}\"",
+                index,
+                *line_start,
+                *line_length,
+                &**target_type,
+                params,
+                *logical_start,
+                *length,
+                expected_params
+            ));
+        }
+        logical_start = logical_start + length;
+    }
+    Ok(())
+}
+
+/// If verification of mappings is turned on, check the table of the linear
+/// device with the given name against the segments recorded as allocated
+/// to it, as compare_mapping() does. Return an error on any difference.
+pub fn verify_mapping(
+    name: &DmName,
+    segments: &[(Device, Sectors, Sectors)],
+) -> StratisResult<()> {
+    if !verify_mappings_enabled() {
+        return Ok(());
+    }
+
+    let (_, table) = get_dm().table_status(
+        &DevId::Name(name),
+        &DmOptions::new().set_flags(DmFlags::DM_STATUS_TABLE),
+    )?;
+    compare_mapping(&table, segments).map_err(|reason| {
+        error!("mapping of DM device {} is wrong: {}", name, reason);
+        StratisError::Engine(
+            ErrorEnum::Error,
+            format!(
+                "mapping of DM device {} does not match the segments recorded: {}",
+                name, reason
+            ),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that a table matches the segments it was made from, and that
+    /// a line that differs in any way, or a line too few or too many, is
+    /// found.
+    fn test_compare_mapping() {
+        let device = Device { major: 8, minor: 1 };
+        let segments = [
+            (device, Sectors(2048), Sectors(100)),
+            (device, Sectors(4096), Sectors(50)),
+        ];
+        let line = |start: u64, length: u64, target_type: &str, params: &str| {
+            (
+                Sectors(start),
+                Sectors(length),
+                TargetTypeBuf::new(target_type.to_owned()).unwrap(),
+                params.to_owned(),
+            )
+        };
+        let table = vec![
+            line(0, 100, "linear", "8:1 2048"),
+            line(100, 50, "linear", "8:1 4096"),
+        ];
+        assert!(compare_mapping(&table, &segments).is_ok());
+
+        let wrong = [
+            vec![line(0, 100, "linear", "8:1 2048")],
+            vec![line(0, 100, "linear", "8:1 2048"), line(100, 50, "linear", "8:1 4097")],
+            vec![line(0, 100, "linear", "8:1 2048"), line(101, 50, "linear", "8:1 4096")],
+            vec![line(0, 100, "linear", "8:1 2048"), line(100, 49, "linear", "8:1 4096")],
+            vec![line(0, 100, "linear", "8:1 2048"), line(100, 50, "linear", "8:2 4096")],
+            vec![line(0, 100, "linear", "8:1 2048"), line(100, 50, "zero", "8:1 4096")],
+        ];
+        for table in &wrong {
+            assert!(compare_mapping(table, &segments).is_err());
+        }
+    }
+}
@@ -0,0 +1,386 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Watch for devicemapper operations and udev waits which take too long.
+// A udev wait gives up once it is considered stuck, but a stuck ioctl can
+// not be interrupted. Suspends and resumes wait on the I/O in flight to a
+// device, so an operation on a device that has stopped completing I/O is
+// stuck in one of them. They are done on a worker thread, and the thread
+// that handles D-Bus requests waits for one no longer than the threshold:
+// it then gives up with an error, leaving the worker blocked in the ioctl,
+// and goes on handling requests, so that clients can see that the pool's
+// operation is stuck. Other operations, which do not wait on I/O, are done
+// on the calling thread and only monitored. A separate thread logs the
+// device and the operation of each operation that exceeds the threshold,
+// and marks the pool that owns the device.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard, Once, ONCE_INIT};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use devicemapper::{DevId, DmFlags, DmName, DmOptions, DmResult};
+
+use stratis::{ErrorEnum, StratisError, StratisResult};
+
+use super::super::types::PoolUuid;
+
+use super::dm::get_dm;
+use super::names::pool_uuid_from_dm_name;
+
+/// The number of seconds after which an operation is considered stuck.
+pub const STUCK_THRESHOLD_SECS: u64 = 30;
+
+/// The interval at which the monitor thread checks on operations in
+/// progress.
+const CHECK_INTERVAL_MS: u64 = 1000;
+
+/// An operation in progress.
+struct Operation {
+    pool_uuid: Option<PoolUuid>,
+    device: String,
+    operation: &'static str,
+    started: Instant,
+    stuck: bool,
+}
+
+#[derive(Default)]
+struct Watchdog {
+    next_id: u64,
+    operations: HashMap<u64, Operation>,
+    /// Pools on which an operation has exceeded the threshold since the
+    /// last operation on the pool that completed within it.
+    stuck_pools: HashSet<PoolUuid>,
+}
+
+lazy_static! {
+    static ref WATCHDOG: Mutex<Watchdog> = Mutex::new(Watchdog::default());
+}
+
+static MONITOR: Once = ONCE_INIT;
+
+fn lock() -> MutexGuard<'static, Watchdog> {
+    WATCHDOG
+        .lock()
+        .expect("no code panics while holding the watchdog lock")
+}
+
+fn threshold() -> Duration {
+    Duration::from_secs(STUCK_THRESHOLD_SECS)
+}
+
+/// Start the thread that checks on operations in progress.
+/// Only the first invocation has any effect.
+pub fn start_monitor() {
+    MONITOR.call_once(|| {
+        let monitor = thread::Builder::new()
+            .name("stratis-watchdog".into())
+            .spawn(|| loop {
+                thread::sleep(Duration::from_millis(CHECK_INTERVAL_MS));
+                check(Instant::now());
+            });
+        if let Err(err) = monitor {
+            error!("Failed to start watchdog thread: {}", err);
+        }
+    });
+}
+
+/// Whether an operation on a device belonging to the pool has exceeded the
+/// threshold, and no operation on the pool has completed within the
+/// threshold since.
+pub fn is_stuck(pool_uuid: PoolUuid) -> bool {
+    lock().stuck_pools.contains(&pool_uuid)
+}
+
+/// Perform the operation f on the device, monitoring it while it runs.
+pub fn watch<T, F>(pool_uuid: Option<PoolUuid>, device: &str, operation: &'static str, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let id = register(pool_uuid, device, operation, Instant::now());
+    let result = f();
+    complete(id, Instant::now());
+    result
+}
+
+/// Perform the operation f on the DM device with the given name,
+/// monitoring it while it runs.
+pub fn watch_dm<T, F>(name: &DmName, operation: &'static str, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    watch(pool_uuid_from_dm_name(name), &name.to_string(), operation, f)
+}
+
+/// Suspend the DM device with the given name, flushing it first if flush is
+/// true, on a worker thread. Return an error if the suspend has not
+/// completed within the threshold.
+pub fn suspend_dm(name: &DmName, flush: bool) -> StratisResult<()> {
+    let flags = if flush {
+        DmFlags::DM_SUSPEND
+    } else {
+        DmFlags::DM_SUSPEND | DmFlags::DM_NOFLUSH
+    };
+    let owned = name.to_owned();
+    run_dm(name, "suspend", move || {
+        get_dm().device_suspend(&DevId::Name(&owned), DmOptions::new().set_flags(flags))?;
+        Ok(())
+    })
+}
+
+/// Resume the DM device with the given name on a worker thread. Return an
+/// error if the resume has not completed within the threshold.
+pub fn resume_dm(name: &DmName) -> StratisResult<()> {
+    let owned = name.to_owned();
+    run_dm(name, "resume", move || {
+        get_dm().device_suspend(&DevId::Name(&owned), &DmOptions::new())?;
+        Ok(())
+    })
+}
+
+/// Perform the operation f on the DM device with the given name on a
+/// worker thread, monitoring it while it runs.
+fn run_dm<F>(name: &DmName, operation: &'static str, f: F) -> StratisResult<()>
+where
+    F: FnOnce() -> DmResult<()> + Send + 'static,
+{
+    run(
+        pool_uuid_from_dm_name(name),
+        &name.to_string(),
+        operation,
+        threshold(),
+        f,
+    )?.map_err(|err| err.into())
+}
+
+/// Perform the operation f on the device on a worker thread, monitoring it
+/// while it runs, and wait for its result for no longer than timeout. If
+/// the operation has not completed by then, mark it stuck and return an
+/// error; the worker thread completes it whenever it returns.
+fn run<T, F>(
+    pool_uuid: Option<PoolUuid>,
+    device: &str,
+    operation: &'static str,
+    timeout: Duration,
+    f: F,
+) -> StratisResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let id = register(pool_uuid, device, operation, Instant::now());
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::Builder::new()
+        .name("stratis-dm-op".into())
+        .spawn(move || {
+            let result = f();
+            complete(id, Instant::now());
+            // The receiver is gone if the caller has given up waiting.
+            let _ = sender.send(result);
+        });
+    if let Err(err) = worker {
+        lock().operations.remove(&id);
+        return Err(err.into());
+    }
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Ok(result),
+        Err(RecvTimeoutError::Timeout) => {
+            if give_up(id, timeout) {
+                Err(StratisError::Engine(
+                    ErrorEnum::Busy,
+                    format!(
+                        "Operation \"{}\" on device {} has not completed after {} seconds",
+                        operation,
+                        device,
+                        timeout.as_secs()
+                    ),
+                ))
+            } else {
+                // The operation completed just as the wait timed out.
+                receiver
+                    .recv()
+                    .map_err(|_| StratisError::Error("dm operation thread panicked".into()))
+            }
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(StratisError::Error("dm operation thread panicked".into()))
+        }
+    }
+}
+
+fn register(
+    pool_uuid: Option<PoolUuid>,
+    device: &str,
+    operation: &'static str,
+    started: Instant,
+) -> u64 {
+    let mut watchdog = lock();
+    let id = watchdog.next_id;
+    watchdog.next_id = watchdog.next_id.wrapping_add(1);
+    watchdog.operations.insert(
+        id,
+        Operation {
+            pool_uuid,
+            device: device.to_owned(),
+            operation,
+            started,
+            stuck: false,
+        },
+    );
+    id
+}
+
+fn complete(id: u64, now: Instant) {
+    let mut watchdog = lock();
+    let op = match watchdog.operations.remove(&id) {
+        Some(op) => op,
+        None => return,
+    };
+
+    let elapsed = now.duration_since(op.started);
+    if op.stuck || elapsed >= threshold() {
+        warn!(
+            "Operation \"{}\" on device {} completed after {} seconds",
+            op.operation,
+            op.device,
+            elapsed.as_secs()
+        );
+        if let Some(pool_uuid) = op.pool_uuid {
+            watchdog.stuck_pools.insert(pool_uuid);
+        }
+    } else if let Some(pool_uuid) = op.pool_uuid {
+        if !watchdog
+            .operations
+            .values()
+            .any(|other| other.stuck && other.pool_uuid == Some(pool_uuid))
+        {
+            watchdog.stuck_pools.remove(&pool_uuid);
+        }
+    }
+}
+
+/// Mark stuck, log, and mark the pool of, the operation with the given id,
+/// which has not completed after timeout. Return false if the operation
+/// has completed.
+fn give_up(id: u64, timeout: Duration) -> bool {
+    let mut watchdog = lock();
+    let Watchdog {
+        ref mut operations,
+        ref mut stuck_pools,
+        ..
+    } = *watchdog;
+    let op = match operations.get_mut(&id) {
+        Some(op) => op,
+        None => return false,
+    };
+    if !op.stuck {
+        op.stuck = true;
+        error!(
+            "Operation \"{}\" on device {} has not completed after {} seconds",
+            op.operation,
+            op.device,
+            timeout.as_secs()
+        );
+    }
+    if let Some(pool_uuid) = op.pool_uuid {
+        stuck_pools.insert(pool_uuid);
+    }
+    true
+}
+
+/// Log, and mark the pool of, every operation that has newly exceeded the
+/// threshold at time now.
+fn check(now: Instant) {
+    let mut watchdog = lock();
+    let Watchdog {
+        ref mut operations,
+        ref mut stuck_pools,
+        ..
+    } = *watchdog;
+    for op in operations.values_mut().filter(|op| !op.stuck) {
+        if op.started + threshold() <= now {
+            op.stuck = true;
+            error!(
+                "Operation \"{}\" on device {} has not completed after {} seconds",
+                op.operation, op.device, STUCK_THRESHOLD_SECS
+            );
+            if let Some(pool_uuid) = op.pool_uuid {
+                stuck_pools.insert(pool_uuid);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    /// Verify that a pool is marked stuck when an operation on it exceeds
+    /// the threshold, and that the mark remains until an operation on the
+    /// pool completes within the threshold.
+    fn test_stuck_operation() {
+        let pool_uuid = PoolUuid::new_v4();
+        let start = Instant::now();
+        let late = start + threshold() + Duration::from_secs(1);
+
+        let id = register(Some(pool_uuid), "dev", "suspend", start);
+        check(start);
+        assert!(!is_stuck(pool_uuid));
+
+        check(late);
+        assert!(is_stuck(pool_uuid));
+
+        complete(id, late);
+        assert!(is_stuck(pool_uuid));
+
+        let id = register(Some(pool_uuid), "dev", "resume", late);
+        complete(id, late);
+        assert!(!is_stuck(pool_uuid));
+
+        let id = register(Some(pool_uuid), "dev", "resume", start);
+        complete(id, late);
+        assert!(is_stuck(pool_uuid));
+    }
+    #[test]
+    /// Verify that the caller is given back control, with an error, while
+    /// an operation run on a worker thread is stuck, and that meanwhile
+    /// the pool is reported stuck. Once the operation completes late, the
+    /// pool remains reported stuck until an operation on it completes
+    /// promptly.
+    fn test_stuck_operation_released() {
+        let pool_uuid = PoolUuid::new_v4();
+        let (unblock, blocked) = mpsc::channel::<()>();
+
+        let result = run(
+            Some(pool_uuid),
+            "dev",
+            "suspend",
+            Duration::from_millis(100),
+            move || blocked.recv().unwrap(),
+        );
+        assert!(match result {
+            Err(ref err) => err.code() == ErrorEnum::Busy,
+            _ => false,
+        });
+        assert!(is_stuck(pool_uuid));
+
+        unblock.send(()).unwrap();
+        while lock()
+            .operations
+            .values()
+            .any(|op| op.pool_uuid == Some(pool_uuid))
+        {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(is_stuck(pool_uuid));
+
+        run(Some(pool_uuid), "dev", "resume", threshold(), || ()).unwrap();
+        assert!(!is_stuck(pool_uuid));
+    }
+}
@@ -4,19 +4,25 @@
 
 use std::collections::{hash_map, HashMap};
 use std::fmt;
+use std::hash::Hash;
 use std::iter::IntoIterator;
+use std::ops::Deref;
 
 use uuid::Uuid;
 
 use engine::Name;
 
-/// Map UUID and name to T items.
-pub struct Table<T> {
-    name_to_uuid: HashMap<Name, Uuid>,
-    items: HashMap<Uuid, (Name, T)>,
+/// Map UUID and name to T items. U is the type of the UUIDs of the items.
+pub struct Table<U, T> {
+    name_to_uuid: HashMap<Name, U>,
+    items: HashMap<U, (Name, T)>,
 }
 
-impl<T: fmt::Debug> fmt::Debug for Table<T> {
+impl<U, T> fmt::Debug for Table<U, T>
+where
+    U: Copy + Eq + Hash + Deref<Target = Uuid>,
+    T: fmt::Debug,
+{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_map()
             .entries(
@@ -28,8 +34,8 @@ impl<T: fmt::Debug> fmt::Debug for Table<T> {
     }
 }
 
-impl<T> Default for Table<T> {
-    fn default() -> Table<T> {
+impl<U: Eq + Hash, T> Default for Table<U, T> {
+    fn default() -> Table<U, T> {
         Table {
             name_to_uuid: HashMap::default(),
             items: HashMap::default(),
@@ -37,15 +43,15 @@ impl<T> Default for Table<T> {
     }
 }
 
-pub struct Iter<'a, T: 'a> {
-    items: hash_map::Iter<'a, Uuid, (Name, T)>,
+pub struct Iter<'a, U: 'a, T: 'a> {
+    items: hash_map::Iter<'a, U, (Name, T)>,
 }
 
-impl<'a, T> Iterator for Iter<'a, T> {
-    type Item = (&'a Name, &'a Uuid, &'a T);
+impl<'a, U, T> Iterator for Iter<'a, U, T> {
+    type Item = (&'a Name, &'a U, &'a T);
 
     #[inline]
-    fn next(&mut self) -> Option<(&'a Name, &'a Uuid, &'a T)> {
+    fn next(&mut self) -> Option<(&'a Name, &'a U, &'a T)> {
         self.items
             .next()
             .map(|(uuid, &(ref name, ref item))| (&*name, uuid, item))
@@ -57,15 +63,15 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
-pub struct IterMut<'a, T: 'a> {
-    items: hash_map::IterMut<'a, Uuid, (Name, T)>,
+pub struct IterMut<'a, U: 'a, T: 'a> {
+    items: hash_map::IterMut<'a, U, (Name, T)>,
 }
 
-impl<'a, T> Iterator for IterMut<'a, T> {
-    type Item = (&'a Name, &'a Uuid, &'a mut T);
+impl<'a, U, T> Iterator for IterMut<'a, U, T> {
+    type Item = (&'a Name, &'a U, &'a mut T);
 
     #[inline]
-    fn next(&mut self) -> Option<(&'a Name, &'a Uuid, &'a mut T)> {
+    fn next(&mut self) -> Option<(&'a Name, &'a U, &'a mut T)> {
         self.items
             .next()
             .map(|(uuid, &mut (ref name, ref mut item))| (&*name, uuid, item))
@@ -77,15 +83,15 @@ impl<'a, T> Iterator for IterMut<'a, T> {
     }
 }
 
-pub struct IntoIter<T> {
-    items: hash_map::IntoIter<Uuid, (Name, T)>,
+pub struct IntoIter<U, T> {
+    items: hash_map::IntoIter<U, (Name, T)>,
 }
 
-impl<T> Iterator for IntoIter<T> {
-    type Item = (Name, Uuid, T);
+impl<U, T> Iterator for IntoIter<U, T> {
+    type Item = (Name, U, T);
 
     #[inline]
-    fn next(&mut self) -> Option<(Name, Uuid, T)> {
+    fn next(&mut self) -> Option<(Name, U, T)> {
         self.items
             .next()
             .map(|(uuid, (name, item))| (name, uuid, item))
@@ -97,29 +103,29 @@ impl<T> Iterator for IntoIter<T> {
     }
 }
 
-impl<T> IntoIterator for Table<T> {
-    type Item = (Name, Uuid, T);
-    type IntoIter = IntoIter<T>;
+impl<U: Copy + Eq + Hash, T> IntoIterator for Table<U, T> {
+    type Item = (Name, U, T);
+    type IntoIter = IntoIter<U, T>;
 
-    fn into_iter(self) -> IntoIter<T> {
+    fn into_iter(self) -> IntoIter<U, T> {
         self.into_iter()
     }
 }
 
-impl<'a, T> IntoIterator for &'a Table<T> {
-    type Item = (&'a Name, &'a Uuid, &'a T);
-    type IntoIter = Iter<'a, T>;
+impl<'a, U: Copy + Eq + Hash, T> IntoIterator for &'a Table<U, T> {
+    type Item = (&'a Name, &'a U, &'a T);
+    type IntoIter = Iter<'a, U, T>;
 
-    fn into_iter(self) -> Iter<'a, T> {
+    fn into_iter(self) -> Iter<'a, U, T> {
         self.iter()
     }
 }
 
-impl<'a, T> IntoIterator for &'a mut Table<T> {
-    type Item = (&'a Name, &'a Uuid, &'a mut T);
-    type IntoIter = IterMut<'a, T>;
+impl<'a, U: Copy + Eq + Hash, T> IntoIterator for &'a mut Table<U, T> {
+    type Item = (&'a Name, &'a U, &'a mut T);
+    type IntoIter = IterMut<'a, U, T>;
 
-    fn into_iter(self) -> IterMut<'a, T> {
+    fn into_iter(self) -> IterMut<'a, U, T> {
         self.iter_mut()
     }
 }
@@ -127,7 +133,7 @@ impl<'a, T> IntoIterator for &'a mut Table<T> {
 /// All operations are O(1), although Name lookups are slightly disadvantaged
 /// vs. Uuid lookups. In order to rename a T item, it must be removed,
 /// renamed, and reinserted under the new name.
-impl<T> Table<T> {
+impl<U: Copy + Eq + Hash, T> Table<U, T> {
     pub fn is_empty(&self) -> bool {
         self.items.is_empty()
     }
@@ -136,19 +142,19 @@ impl<T> Table<T> {
         self.items.len()
     }
 
-    pub fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<U, T> {
         Iter {
             items: self.items.iter(),
         }
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<T> {
+    pub fn iter_mut(&mut self) -> IterMut<U, T> {
         IterMut {
             items: self.items.iter_mut(),
         }
     }
 
-    pub fn into_iter(self) -> IntoIter<T> {
+    pub fn into_iter(self) -> IntoIter<U, T> {
         IntoIter {
             items: self.items.into_iter(),
         }
@@ -160,26 +166,26 @@ impl<T> Table<T> {
     }
 
     /// Returns true if map has an item corresponding to this uuid, else false.
-    pub fn contains_uuid(&self, uuid: Uuid) -> bool {
+    pub fn contains_uuid(&self, uuid: U) -> bool {
         self.items.contains_key(&uuid)
     }
 
     /// Get item by name.
-    pub fn get_by_name(&self, name: &str) -> Option<(Uuid, &T)> {
+    pub fn get_by_name(&self, name: &str) -> Option<(U, &T)> {
         self.name_to_uuid
             .get(&*name)
             .and_then(|uuid| self.items.get(uuid).map(|&(_, ref item)| (*uuid, item)))
     }
 
     /// Get item by uuid.
-    pub fn get_by_uuid(&self, uuid: Uuid) -> Option<(Name, &T)> {
+    pub fn get_by_uuid(&self, uuid: U) -> Option<(Name, &T)> {
         self.items
             .get(&uuid)
             .map(|&(ref name, ref item)| (name.clone(), item))
     }
 
     /// Get mutable item by name.
-    pub fn get_mut_by_name(&mut self, name: &str) -> Option<(Uuid, &mut T)> {
+    pub fn get_mut_by_name(&mut self, name: &str) -> Option<(U, &mut T)> {
         let uuid = match self.name_to_uuid.get(name) {
             Some(uuid) => uuid,
             None => return None,
@@ -190,21 +196,21 @@ impl<T> Table<T> {
     }
 
     /// Get mutable item by uuid.
-    pub fn get_mut_by_uuid(&mut self, uuid: Uuid) -> Option<(Name, &mut T)> {
+    pub fn get_mut_by_uuid(&mut self, uuid: U) -> Option<(Name, &mut T)> {
         self.items
             .get_mut(&uuid)
             .map(|&mut (ref name, ref mut item)| (name.clone(), item))
     }
 
     /// Removes the item corresponding to name if there is one.
-    pub fn remove_by_name(&mut self, name: &str) -> Option<(Uuid, T)> {
+    pub fn remove_by_name(&mut self, name: &str) -> Option<(U, T)> {
         self.name_to_uuid
             .remove(name)
             .and_then(|uuid| self.items.remove(&uuid).map(|(_, item)| (uuid, item)))
     }
 
     /// Removes the item corresponding to the uuid if there is one.
-    pub fn remove_by_uuid(&mut self, uuid: Uuid) -> Option<(Name, T)> {
+    pub fn remove_by_uuid(&mut self, uuid: U) -> Option<(Name, T)> {
         self.items.remove(&uuid).and_then(|(name, item)| {
             self.name_to_uuid.remove(&name);
             Some((name, item))
@@ -215,7 +221,7 @@ impl<T> Table<T> {
     /// Possibly returns the items displaced.
     /// If two items are displaced, the one displaced by matching name is
     /// returned first.
-    pub fn insert(&mut self, name: Name, uuid: Uuid, item: T) -> Option<Vec<(Name, Uuid, T)>> {
+    pub fn insert(&mut self, name: Name, uuid: U, item: T) -> Option<Vec<(Name, U, T)>> {
         let old_uuid = self.name_to_uuid.insert(name.clone(), uuid);
         let old_pair = self.items.insert(uuid, (name.clone(), item));
         match (old_uuid, old_pair) {
@@ -234,7 +240,7 @@ impl<T> Table<T> {
                     .remove(&old_uuid)
                     .expect("invariant requires existence");
                 assert_eq!(other_name, name);
-                assert_eq!(other_uuid, uuid);
+                assert!(other_uuid == uuid);
                 vec![(name, old_uuid, other_item), (old_name, uuid, old_item)]
             }),
             // entry with same name but different uuid ejected
@@ -250,7 +256,7 @@ impl<T> Table<T> {
                 let other_uuid = self.name_to_uuid
                     .remove(&old_name)
                     .expect("invariant requires existence");
-                assert_eq!(other_uuid, uuid);
+                assert!(other_uuid == uuid);
                 Some(vec![(old_name, uuid, old_item)])
             }
             // nothing ejected
@@ -278,7 +284,7 @@ mod tests {
 
     // A global invariant checker for the table.
     // Verifies proper relationship between internal data structures.
-    fn table_invariant<T>(table: &Table<T>) -> () {
+    fn table_invariant<T>(table: &Table<Uuid, T>) -> () {
         for (uuid, &(ref name, _)) in &table.items {
             assert_eq!(*uuid, *table.name_to_uuid.get(name).unwrap())
         }
@@ -307,7 +313,7 @@ mod tests {
     /// Verify that the table is now empty and that removing by name yields
     /// no result.
     fn remove_existing_item() {
-        let mut t: Table<TestThing> = Table::default();
+        let mut t: Table<Uuid, TestThing> = Table::default();
         table_invariant(&t);
 
         let uuid = Uuid::new_v4();
@@ -341,7 +347,7 @@ mod tests {
     /// This is good, because then you can't have a thing that is both in
     /// the table and not in the table.
     fn insert_same_keys() {
-        let mut t: Table<TestThing> = Table::default();
+        let mut t: Table<Uuid, TestThing> = Table::default();
         table_invariant(&t);
 
         let uuid = Uuid::new_v4();
@@ -382,7 +388,7 @@ mod tests {
     /// Insert a thing and then insert another thing with the same name.
     /// The previously inserted thing should be returned.
     fn insert_same_name() {
-        let mut t: Table<TestThing> = Table::default();
+        let mut t: Table<Uuid, TestThing> = Table::default();
         table_invariant(&t);
 
         let uuid = Uuid::new_v4();
@@ -426,7 +432,7 @@ mod tests {
     /// Insert a thing and then insert another thing with the same uuid.
     /// The previously inserted thing should be returned.
     fn insert_same_uuid() {
-        let mut t: Table<TestThing> = Table::default();
+        let mut t: Table<Uuid, TestThing> = Table::default();
         table_invariant(&t);
 
         let uuid = Uuid::new_v4();
@@ -470,7 +476,7 @@ mod tests {
     /// Insert two things, then insert a thing that matches one name and one
     /// uuid of each. Both existing things should be returned.
     fn insert_same_uuid_and_same_name() {
-        let mut t: Table<TestThing> = Table::default();
+        let mut t: Table<Uuid, TestThing> = Table::default();
         table_invariant(&t);
 
         let uuid1 = Uuid::new_v4();
@@ -3,17 +3,88 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::borrow::Borrow;
+use std::cmp::{max, min};
+use std::collections::HashSet;
 use std::fmt;
+use std::fs;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+use chrono::{DateTime, Timelike, Utc};
 #[cfg(feature = "dbus_enabled")]
 use dbus;
-use uuid::Uuid;
+use devicemapper::{DmName, DmUuid, Sectors};
+use uuid::{ParseError, Uuid};
 
-pub type DevUuid = Uuid;
-pub type FilesystemUuid = Uuid;
-pub type PoolUuid = Uuid;
+use stratis::{ErrorEnum, ErrorPayload, StratisError, StratisResult, VERSION};
+
+/// Define a type for the UUIDs of one kind of object, distinct from the
+/// types for the UUIDs of other kinds, so that the UUID of one kind of
+/// object can not be passed where that of another is expected. The type
+/// dereferences to the Uuid it wraps, and is saved as that Uuid would be,
+/// but a bare Uuid can not be made into one; a UUID of the type is only
+/// generated, parsed, or read from saved metadata.
+macro_rules! uuid {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+        pub struct $name(Uuid);
+
+        impl $name {
+            pub fn new_v4() -> $name {
+                $name(Uuid::new_v4())
+            }
+
+            pub fn nil() -> $name {
+                $name(Uuid::nil())
+            }
+
+            pub fn parse_str(input: &str) -> Result<$name, ParseError> {
+                Uuid::parse_str(input).map($name)
+            }
+        }
+
+        impl Deref for $name {
+            type Target = Uuid;
+
+            fn deref(&self) -> &Uuid {
+                &self.0
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(&self.0, f)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl From<$name> for Uuid {
+            fn from(uuid: $name) -> Uuid {
+                uuid.0
+            }
+        }
+    };
+}
+
+uuid!(
+    /// The UUID of a blockdev.
+    DevUuid
+);
+uuid!(
+    /// The UUID of a filesystem.
+    FilesystemUuid
+);
+uuid!(
+    /// The UUID of a pool.
+    PoolUuid
+);
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum RenameAction {
@@ -26,7 +97,9 @@ pub enum RenameAction {
 /// The 4 modes map to Running, OutOfDataSpace, ReadOnly and Failed - in degrading
 /// order.  Stratis adds 2 additional modes - Initializing and Stopping.  The Stratis
 /// specific modes are used to represent the state when Stratis is either constructing
-/// the pool components or tearing them down.
+/// the pool components or tearing them down.  NeedsCheck represents a pool whose
+/// metadata the kernel has flagged as needing a check, whatever its mode; the pool
+/// must be repaired before its metadata may be changed.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum PoolState {
     Initializing,   // Startup in progress
@@ -36,8 +109,9 @@ pub enum PoolState {
     // same state. PM_OUT_OF_META_SPACE may switch back to PM_WRITE when
     // the meta data device is expanded.  PM_READ_ONLY requires user
     // intervention to switch back to PM_WRITE mode.
-    Failed,   // All I/O fails
-    Stopping, // Teardown in progress
+    Failed,     // All I/O fails
+    Stopping,   // Teardown in progress
+    NeedsCheck, // Metadata flagged as needing a check, repair required
 }
 
 impl PoolState {
@@ -49,6 +123,35 @@ impl PoolState {
             PoolState::OutOfDataSpace => 4,
             PoolState::Failed => 5,
             PoolState::Stopping => 6,
+            PoolState::NeedsCheck => 7,
+        }
+    }
+
+    /// The state with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<PoolState> {
+        match name {
+            "initializing" => Some(PoolState::Initializing),
+            "running" => Some(PoolState::Running),
+            "read-only" => Some(PoolState::ReadOnly),
+            "out-of-data-space" => Some(PoolState::OutOfDataSpace),
+            "failed" => Some(PoolState::Failed),
+            "stopping" => Some(PoolState::Stopping),
+            "needs-check" => Some(PoolState::NeedsCheck),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PoolState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PoolState::Initializing => write!(f, "initializing"),
+            PoolState::Running => write!(f, "running"),
+            PoolState::ReadOnly => write!(f, "read-only"),
+            PoolState::OutOfDataSpace => write!(f, "out-of-data-space"),
+            PoolState::Failed => write!(f, "failed"),
+            PoolState::Stopping => write!(f, "stopping"),
+            PoolState::NeedsCheck => write!(f, "needs-check"),
         }
     }
 }
@@ -72,6 +175,30 @@ impl PoolExtendState {
             PoolExtendState::MetaAndDataFailed => 5,
         }
     }
+
+    /// The state with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<PoolExtendState> {
+        match name {
+            "initializing" => Some(PoolExtendState::Initializing),
+            "good" => Some(PoolExtendState::Good),
+            "data-failed" => Some(PoolExtendState::DataFailed),
+            "meta-failed" => Some(PoolExtendState::MetaFailed),
+            "meta-and-data-failed" => Some(PoolExtendState::MetaAndDataFailed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PoolExtendState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PoolExtendState::Initializing => write!(f, "initializing"),
+            PoolExtendState::Good => write!(f, "good"),
+            PoolExtendState::DataFailed => write!(f, "data-failed"),
+            PoolExtendState::MetaFailed => write!(f, "meta-failed"),
+            PoolExtendState::MetaAndDataFailed => write!(f, "meta-and-data-failed"),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -89,6 +216,26 @@ impl FreeSpaceState {
             FreeSpaceState::Crit => 3,
         }
     }
+
+    /// The state with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<FreeSpaceState> {
+        match name {
+            "good" => Some(FreeSpaceState::Good),
+            "warn" => Some(FreeSpaceState::Warn),
+            "crit" => Some(FreeSpaceState::Crit),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FreeSpaceState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FreeSpaceState::Good => write!(f, "good"),
+            FreeSpaceState::Warn => write!(f, "warn"),
+            FreeSpaceState::Crit => write!(f, "crit"),
+        }
+    }
 }
 
 /// See Design Doc section 10.2.1 for more details.
@@ -111,6 +258,30 @@ impl BlockDevState {
             BlockDevState::InUse => 4,
         }
     }
+
+    /// The state with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<BlockDevState> {
+        match name {
+            "missing" => Some(BlockDevState::Missing),
+            "bad" => Some(BlockDevState::Bad),
+            "spare" => Some(BlockDevState::Spare),
+            "not-in-use" => Some(BlockDevState::NotInUse),
+            "in-use" => Some(BlockDevState::InUse),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BlockDevState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlockDevState::Missing => write!(f, "missing"),
+            BlockDevState::Bad => write!(f, "bad"),
+            BlockDevState::Spare => write!(f, "spare"),
+            BlockDevState::NotInUse => write!(f, "not-in-use"),
+            BlockDevState::InUse => write!(f, "in-use"),
+        }
+    }
 }
 
 /// A struct that may contain a dbus::Path, or may not, and most certainly
@@ -130,13 +301,915 @@ pub enum BlockDevTier {
     Cache,
 }
 
+impl BlockDevTier {
+    pub fn to_dbus_value(self) -> u16 {
+        match self {
+            BlockDevTier::Data => 0,
+            BlockDevTier::Cache => 1,
+        }
+    }
+
+    /// The tier with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<BlockDevTier> {
+        match name {
+            "data" => Some(BlockDevTier::Data),
+            "cache" => Some(BlockDevTier::Cache),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BlockDevTier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlockDevTier::Data => write!(f, "data"),
+            BlockDevTier::Cache => write!(f, "cache"),
+        }
+    }
+}
+
+/// The versions of the parts of the on-disk format: the signature block,
+/// the header of each metadata region, and the pool metadata that the
+/// regions hold.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FormatVersions {
+    pub sigblock: u8,
+    pub mda_header: u8,
+    pub metadata: u8,
+}
+
+/// The highest versions of the on-disk format that this daemon supports.
+pub const MAX_FORMAT_VERSIONS: FormatVersions = FormatVersions {
+    sigblock: 1,
+    mda_header: 1,
+    metadata: 1,
+};
+
+impl FormatVersions {
+    /// The lowest of each version in versions, or None if versions is
+    /// empty. A pool is only as upgraded as its least upgraded device.
+    pub fn lowest<I>(versions: I) -> Option<FormatVersions>
+    where
+        I: IntoIterator<Item = FormatVersions>,
+    {
+        versions.into_iter().fold(None, |acc, v| {
+            Some(match acc {
+                None => v,
+                Some(acc) => FormatVersions {
+                    sigblock: min(acc.sigblock, v.sigblock),
+                    mda_header: min(acc.mda_header, v.mda_header),
+                    metadata: min(acc.metadata, v.metadata),
+                },
+            })
+        })
+    }
+}
+
+/// A device in the data tier of a pool, as it would be if a plan were
+/// applied.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlannedBlockDev {
+    pub devnode: PathBuf,
+    /// The total size of the device.
+    pub size: Sectors,
+    /// The space on the device that is available for data.
+    pub usable: Sectors,
+    /// Whether the device is rotational, if that can be determined.
+    pub rotational: Option<bool>,
+    /// Whether the device would be added by the plan.
+    pub new: bool,
+}
+
+/// The predicted result of adding devices to the data tier of a pool.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AddDataDevsPlan {
+    /// The space available for data in the data tier after the addition.
+    pub usable_capacity: Sectors,
+    /// All the devices in the data tier after the addition.
+    pub layout: Vec<PlannedBlockDev>,
+    /// Conditions which do not prevent the addition, but of which the user
+    /// should be aware.
+    pub warnings: Vec<String>,
+}
+
+impl AddDataDevsPlan {
+    /// Make a plan from the layout of the data tier after the addition.
+    /// The layout is ordered by devnode, so that plans for the same
+    /// devices compare equal.
+    pub fn new(mut layout: Vec<PlannedBlockDev>) -> AddDataDevsPlan {
+        layout.sort_by(|a, b| a.devnode.cmp(&b.devnode));
+
+        let mut warnings = Vec::new();
+
+        if let Some((smallest, largest)) = mixed_sizes(layout.iter().map(|dev| dev.size)) {
+            warnings.push(format!(
+                "devices are of mixed sizes, from {} to {} sectors",
+                *smallest, *largest
+            ));
+        }
+
+        let rotational = layout
+            .iter()
+            .filter_map(|dev| dev.rotational)
+            .collect::<Vec<_>>();
+        if rotational.contains(&true) && rotational.contains(&false) {
+            warnings.push("rotational and non-rotational devices are mixed".into());
+        }
+
+        AddDataDevsPlan {
+            usable_capacity: layout.iter().map(|dev| dev.usable).sum(),
+            layout,
+            warnings,
+        }
+    }
+
+    /// The devnodes of the devices that the plan adds.
+    pub fn new_devnodes(&self) -> Vec<PathBuf> {
+        self.layout
+            .iter()
+            .filter(|dev| dev.new)
+            .map(|dev| dev.devnode.clone())
+            .collect()
+    }
+}
+
+/// A device that kept its Stratis signature when its pool was destroyed.
+/// It belongs to no pool, and can not be added to one until it is wiped.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReclaimableDevice {
+    pub devnode: PathBuf,
+    pub pool_uuid: PoolUuid,
+    pub dev_uuid: DevUuid,
+    /// When the pool was destroyed.
+    pub destroyed: DateTime<Utc>,
+}
+
+/// A device that claims the same device UUID in the same pool as another
+/// device, as a copy made block by block of a member of the pool does. Of
+/// the devices claiming the UUID, only the one kept is set up in the pool;
+/// the clone is never read from or written to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClonedDevice {
+    pub devnode: PathBuf,
+    pub pool_uuid: PoolUuid,
+    pub dev_uuid: DevUuid,
+    /// The device node of the device kept in the clone's stead.
+    pub kept: PathBuf,
+}
+
+/// A filesystem that would be destroyed along with its pool.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlannedFilesystem {
+    pub uuid: FilesystemUuid,
+    pub name: String,
+    /// The places where the filesystem is now mounted.
+    pub mount_points: Vec<PathBuf>,
+}
+
+/// Everything that destroying a pool would affect. The pool is not
+/// destroyed while it has any filesystems, so a plan that lists
+/// filesystems is one that can not yet be carried out.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DestroyPoolPlan {
+    pub filesystems: Vec<PlannedFilesystem>,
+    /// The devnodes of the blockdevs whose Stratis metadata would be wiped.
+    pub blockdevs: Vec<PathBuf>,
+    /// The links under /stratis that would be removed.
+    pub devlinks: Vec<PathBuf>,
+}
+
+/// If the sizes differ by more than the small differences between devices
+/// of nominally the same size, return the smallest and the largest size.
+pub fn mixed_sizes<I>(sizes: I) -> Option<(Sectors, Sectors)>
+where
+    I: IntoIterator<Item = Sectors>,
+{
+    let mut sizes = sizes.into_iter();
+    let first = sizes.next()?;
+    let (smallest, largest) = sizes.fold((first, first), |(smallest, largest), size| {
+        (min(smallest, size), max(largest, size))
+    });
+    if *largest - *smallest > *largest / 10 {
+        Some((smallest, largest))
+    } else {
+        None
+    }
+}
+
+/// The virtual size as a whole percentage of the physical size, rounded
+/// down. None if the physical size is 0. The percentage is computed in 128
+/// bits, so that multiplying a very large size by 100 does not overflow.
+pub fn overcommit_percent(virtual_size: Sectors, physical_size: Sectors) -> Option<u64> {
+    if physical_size == Sectors(0) {
+        None
+    } else {
+        let percent = u128::from(*virtual_size) * 100 / u128::from(*physical_size);
+        Some(if percent > u128::from(u64::max_value()) {
+            u64::max_value()
+        } else {
+            percent as u64
+        })
+    }
+}
+
+/// Return an error if the cap is set and adding additional sectors to the
+/// virtual size would raise the overcommit percentage above it. The sizes
+/// are compared exactly, not by the rounded percentage, so that a virtual
+/// size even a sector beyond the cap is refused.
+pub fn check_overcommit(
+    virtual_size: Sectors,
+    additional: Sectors,
+    physical_size: Sectors,
+    cap: Option<u64>,
+) -> StratisResult<()> {
+    let cap = match cap {
+        Some(cap) => cap,
+        None => return Ok(()),
+    };
+    let virtual_size = virtual_size + additional;
+    let exceeded = physical_size == Sectors(0)
+        || u128::from(*virtual_size) * 100 > u128::from(cap) * u128::from(*physical_size);
+    if exceeded {
+        Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("OvercommitCapExceeded")
+                .param(
+                    "percent",
+                    overcommit_percent(virtual_size, physical_size)
+                        .map_or_else(|| "unbounded".to_owned(), |p| p.to_string()),
+                )
+                .param("cap", cap),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The most filesystems and snapshots that a newly created pool may hold,
+/// unless another limit is set for it. Each one adds to the pool's metadata,
+/// which must fit in the MDA, and to the metadata of its thin pool. A pool
+/// whose saved metadata records no limit has none.
+pub const DEFAULT_FS_LIMIT: u64 = 100;
+
+/// Return an error if adding additional filesystems to the count that a
+/// pool holds would take it beyond the limit.
+pub fn check_fs_limit(count: usize, additional: usize, limit: u64) -> StratisResult<()> {
+    let requested = count as u64 + additional as u64;
+    if requested <= limit {
+        Ok(())
+    } else {
+        Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("FilesystemLimitExceeded")
+                .param("requested", requested)
+                .param("limit", limit),
+        ))
+    }
+}
+
+/// Return an error if the limit is below the count of filesystems that a
+/// pool holds.
+pub fn validate_fs_limit(count: usize, limit: u64) -> StratisResult<()> {
+    if count as u64 > limit {
+        Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("FilesystemLimitTooLow")
+                .param("limit", limit)
+                .param("count", count),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Return an error if the size limit is below the size of a filesystem's
+/// device. The filesystem can not be shrunk to fit within it.
+pub fn validate_fs_size_limit(size: Sectors, limit: Sectors) -> StratisResult<()> {
+    if limit < size {
+        Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("FilesystemSizeLimitTooLow")
+                .param("limit", *limit)
+                .param("size", *size),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Return count names for new filesystems made from prefix, "<prefix>-001"
+/// and so on, in order, skipping each name that is taken.
+pub fn prefixed_names(
+    prefix: &str,
+    count: usize,
+    taken: &HashSet<String>,
+) -> StratisResult<Vec<String>> {
+    if prefix.is_empty() || count == 0 {
+        return Err(StratisError::Engine(
+            ErrorEnum::Invalid,
+            "a prefix and a count of at least 1 are required".into(),
+        ));
+    }
+    Ok((1u64..)
+        .map(|number| format!("{}-{:03}", prefix, number))
+        .filter(|name| !taken.contains(name))
+        .take(count)
+        .collect())
+}
+
+/// How long after a pool was destroyed with its signatures kept its
+/// destruction may be undone, unless otherwise configured.
+pub const DEFAULT_UNDO_DESTROY_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// The usage thresholds of a pool for which none have been set.
+pub const DEFAULT_USAGE_THRESHOLDS: &[u8] = &[70, 85, 95];
+
+/// Return an error if any of the usage thresholds is not a percentage from
+/// 1 to 100.
+pub fn validate_usage_thresholds(thresholds: &[u8]) -> StratisResult<()> {
+    match thresholds.iter().find(|&&t| t == 0 || t > 100) {
+        Some(threshold) => Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("UsageThresholdInvalid").param("threshold", threshold),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The watermarks of a pool, each a percentage of space in use, or None if
+/// it is not set.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Watermarks {
+    /// The percentage of the pool's data space in use at which a warning
+    /// is given.
+    pub warn: Option<u8>,
+    /// The percentage of the thin pool's data device in use at which the
+    /// device is extended, rather than only once little of it is free.
+    pub extend: Option<u8>,
+}
+
+/// Return an error if either watermark is not a percentage from 1 to 99.
+pub fn validate_watermarks(watermarks: Watermarks) -> StratisResult<()> {
+    for &(name, percent) in &[("warn", watermarks.warn), ("extend", watermarks.extend)] {
+        match percent {
+            Some(percent) if percent == 0 || percent > 99 => {
+                return Err(StratisError::Structured(
+                    ErrorEnum::Invalid,
+                    ErrorPayload::new("WatermarkInvalid")
+                        .param("watermark", name)
+                        .param("percent", percent),
+                ))
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Return an error unless the data usage below which emergency reclamation
+/// stops is a percentage from 0 to 100.
+pub fn validate_reclaim_target(target: u8) -> StratisResult<()> {
+    if target > 100 {
+        return Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("ReclaimTargetInvalid").param("target", target),
+        ));
+    }
+    Ok(())
+}
+
+/// The order in which emergency reclamation destroys the filesystems of a
+/// pool, each given by its UUID, its origin, whether it is protected, and
+/// its creation time. Only snapshots that are not protected are destroyed,
+/// the oldest first.
+pub fn reclaim_order<I>(filesystems: I) -> Vec<FilesystemUuid>
+where
+    I: IntoIterator<Item = (FilesystemUuid, Option<FilesystemUuid>, bool, DateTime<Utc>)>,
+{
+    let mut snapshots = filesystems
+        .into_iter()
+        .filter(|&(_, origin, protected, _)| origin.is_some() && !protected)
+        .map(|(uuid, _, _, created)| (created, uuid))
+        .collect::<Vec<_>>();
+    snapshots.sort_by_key(|&(created, _)| created);
+    snapshots.into_iter().map(|(_, uuid)| uuid).collect()
+}
+
+/// The order in which to destroy the filesystems given, each by its UUID and
+/// its origin, so that every snapshot is destroyed before the filesystem it
+/// was made from, whether directly or through other snapshots given. The
+/// filesystems otherwise keep the order in which they were given; a
+/// filesystem given more than once is destroyed once.
+pub fn destroy_order<I>(filesystems: I) -> Vec<FilesystemUuid>
+where
+    I: IntoIterator<Item = (FilesystemUuid, Option<FilesystemUuid>)>,
+{
+    fn visit(
+        uuid: FilesystemUuid,
+        filesystems: &[(FilesystemUuid, Option<FilesystemUuid>)],
+        visited: &mut HashSet<FilesystemUuid>,
+        order: &mut Vec<FilesystemUuid>,
+    ) {
+        if !visited.insert(uuid) {
+            return;
+        }
+        for &(snapshot, origin) in filesystems {
+            if origin == Some(uuid) {
+                visit(snapshot, filesystems, visited, order);
+            }
+        }
+        order.push(uuid);
+    }
+
+    let filesystems = filesystems.into_iter().collect::<Vec<_>>();
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+    for &(uuid, _) in &filesystems {
+        visit(uuid, &filesystems, &mut visited, &mut order);
+    }
+    order
+}
+
+/// Return an error if any of the filesystems is protected.
+pub fn refuse_if_protected<I>(filesystems: I) -> StratisResult<()>
+where
+    I: IntoIterator<Item = (FilesystemUuid, bool)>,
+{
+    match filesystems.into_iter().find(|&(_, protected)| protected) {
+        Some((uuid, _)) => Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("FilesystemProtected").param("uuid", uuid),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Criteria for selecting filesystems from all pools. A filesystem is
+/// selected if it meets every criterion that is set.
+#[derive(Debug, Default)]
+pub struct FilesystemFilter {
+    /// The UUID of the pool the filesystem belongs to.
+    pub pool_uuid: Option<PoolUuid>,
+    /// A glob that the name of the filesystem matches, see glob_match().
+    pub name_glob: Option<String>,
+    /// A time at or after which the filesystem was created.
+    pub created_since: Option<DateTime<Utc>>,
+}
+
+impl FilesystemFilter {
+    /// Whether the filesystem named name, of the pool with pool_uuid,
+    /// created at created, is selected.
+    pub fn matches(&self, pool_uuid: PoolUuid, name: &str, created: DateTime<Utc>) -> bool {
+        self.pool_uuid.map_or(true, |uuid| uuid == pool_uuid)
+            && self.name_glob
+                .as_ref()
+                .map_or(true, |glob| glob_match(glob, name))
+            && self.created_since.map_or(true, |since| created >= since)
+    }
+}
+
+/// Whether name matches the glob pattern, in which '*' matches any sequence
+/// of characters, '?' matches any single character, and every other
+/// character matches itself.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // The position in the pattern just after the most recent '*', and the
+    // position in the name from which that '*' is next tried to match.
+    let mut retry = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(&'*') => {
+                p += 1;
+                retry = Some((p, n + 1));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match retry {
+                Some((retry_p, retry_n)) => {
+                    p = retry_p;
+                    n = retry_n;
+                    retry = Some((retry_p, retry_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// How the engine treats the devices of a new pool if they are of very
+/// different sizes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MixedSizePolicy {
+    /// Create the pool without comment.
+    Accept,
+    /// Create the pool, but log a warning.
+    Warn,
+    /// Refuse to create the pool.
+    Reject,
+}
+
+/// Get the u16 value of this MixedSizePolicy constructor.
+impl From<MixedSizePolicy> for u16 {
+    fn from(p: MixedSizePolicy) -> u16 {
+        p as u16
+    }
+}
+
+/// How the engine treats a device that is to be initialized for a pool if
+/// the device has a foreign signature, e.g., that of a filesystem.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignaturePolicy {
+    /// Refuse to initialize the device.
+    Refuse,
+    /// Initialize the device regardless, but log a warning.
+    Ignore,
+    /// Erase the foreign signatures found on the device, then initialize it.
+    Wipe,
+}
+
+/// Get the u16 value of this SignaturePolicy constructor.
+impl From<SignaturePolicy> for u16 {
+    fn from(p: SignaturePolicy) -> u16 {
+        p as u16
+    }
+}
+
+/// The DM target with which the cache of a pool is made.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheType {
+    /// dm-cache, which caches reads and writes.
+    Cache,
+    /// dm-writecache, which caches only writes, and suits write-heavy
+    /// workloads better.
+    WriteCache,
+}
+
+impl CacheType {
+    /// The cache type with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<CacheType> {
+        match name {
+            "cache" => Some(CacheType::Cache),
+            "writecache" => Some(CacheType::WriteCache),
+            _ => None,
+        }
+    }
+}
+
+/// Get the u16 value of this CacheType constructor.
+impl From<CacheType> for u16 {
+    fn from(t: CacheType) -> u16 {
+        t as u16
+    }
+}
+
+impl fmt::Display for CacheType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CacheType::Cache => write!(f, "cache"),
+            CacheType::WriteCache => write!(f, "writecache"),
+        }
+    }
+}
+
+/// How the space on the devices of a pool's data tier is allocated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AllocationStrategy {
+    /// Take space from each device in turn, moving on to the next only
+    /// when the device is full.
+    FillFirst,
+    /// Begin each allocation on the device after the one on which the
+    /// previous allocation began, spreading allocations across devices.
+    RoundRobin,
+    /// Fill devices that are not rotational before those that are.
+    SsdFirst,
+}
+
+impl AllocationStrategy {
+    /// The strategy with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<AllocationStrategy> {
+        match name {
+            "fill-first" => Some(AllocationStrategy::FillFirst),
+            "round-robin" => Some(AllocationStrategy::RoundRobin),
+            "ssd-first" => Some(AllocationStrategy::SsdFirst),
+            _ => None,
+        }
+    }
+}
+
+/// Get the u16 value of this AllocationStrategy constructor.
+impl From<AllocationStrategy> for u16 {
+    fn from(s: AllocationStrategy) -> u16 {
+        s as u16
+    }
+}
+
+impl fmt::Display for AllocationStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AllocationStrategy::FillFirst => write!(f, "fill-first"),
+            AllocationStrategy::RoundRobin => write!(f, "round-robin"),
+            AllocationStrategy::SsdFirst => write!(f, "ssd-first"),
+        }
+    }
+}
+
+/// Counts of the outcomes of reads and writes of the metadata regions on a
+/// blockdev since the blockdev was set up.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MetadataStats {
+    /// Saves of metadata that succeeded.
+    pub saves: u64,
+    /// Saves of metadata that succeeded only when a failed write of a
+    /// region was tried again.
+    pub retried_saves: u64,
+    /// Regions found to fail their checksums that were since rewritten.
+    pub crc_repairs: u64,
+    /// Loads of metadata that fell back on the copy of a region, because
+    /// the region itself could not be read.
+    pub fallback_loads: u64,
+}
+
+/// The I/O scheduling class of the threads doing a pool's background work.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IoClass {
+    /// Served in turn with other I/O, at the level of priority given.
+    BestEffort,
+    /// Served only when no other I/O to the device is waiting.
+    Idle,
+}
+
+impl IoClass {
+    /// The class with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<IoClass> {
+        match name {
+            "best-effort" => Some(IoClass::BestEffort),
+            "idle" => Some(IoClass::Idle),
+            _ => None,
+        }
+    }
+}
+
+/// Get the u16 value of this IoClass constructor.
+impl From<IoClass> for u16 {
+    fn from(c: IoClass) -> u16 {
+        c as u16
+    }
+}
+
+impl fmt::Display for IoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IoClass::BestEffort => write!(f, "best-effort"),
+            IoClass::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+/// The CPU and I/O scheduling priority of the threads doing a pool's
+/// background work, such as writing metadata that no client asked for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WorkerPriority {
+    /// The niceness of the threads, from -20, the least nice, to 19.
+    pub niceness: i8,
+    /// The I/O scheduling class of the threads.
+    pub io_class: IoClass,
+    /// The level of priority within the best-effort class, from 0, the
+    /// highest, to 7. The idle class has no levels.
+    pub io_level: u8,
+}
+
+impl Default for WorkerPriority {
+    /// The priority at which the kernel runs a thread for which none has
+    /// been set.
+    fn default() -> WorkerPriority {
+        WorkerPriority {
+            niceness: 0,
+            io_class: IoClass::BestEffort,
+            io_level: 4,
+        }
+    }
+}
+
+/// Return an error unless the niceness is from -20 to 19 and the I/O
+/// priority level from 0 to 7.
+pub fn validate_worker_priority(niceness: i16, io_level: u8) -> StratisResult<()> {
+    if niceness < -20 || niceness > 19 {
+        return Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("NicenessInvalid").param("niceness", niceness),
+        ));
+    }
+    if io_level > 7 {
+        return Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("IoLevelInvalid").param("level", io_level),
+        ));
+    }
+    Ok(())
+}
+
+/// How often the snapshot schedule of a filesystem takes a snapshot of it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SnapshotInterval {
+    /// Once in each hour, by the clock.
+    Hourly,
+    /// Once in each day, beginning at midnight UTC.
+    Daily,
+}
+
+impl SnapshotInterval {
+    /// The interval with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<SnapshotInterval> {
+        match name {
+            "hourly" => Some(SnapshotInterval::Hourly),
+            "daily" => Some(SnapshotInterval::Daily),
+            _ => None,
+        }
+    }
+
+    /// The beginning of the hour or day in which now falls.
+    pub fn period_start(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            SnapshotInterval::Hourly => now.date().and_hms(now.hour(), 0, 0),
+            SnapshotInterval::Daily => now.date().and_hms(0, 0, 0),
+        }
+    }
+}
+
+/// Get the u16 value of this SnapshotInterval constructor.
+impl From<SnapshotInterval> for u16 {
+    fn from(i: SnapshotInterval) -> u16 {
+        i as u16
+    }
+}
+
+impl fmt::Display for SnapshotInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotInterval::Hourly => write!(f, "hourly"),
+            SnapshotInterval::Daily => write!(f, "daily"),
+        }
+    }
+}
+
+/// The snapshot schedule of a filesystem: how often a snapshot of it is
+/// taken, and how many of the snapshots so taken are kept.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SnapshotSchedule {
+    pub interval: SnapshotInterval,
+    /// The number of the snapshots taken by the schedule that are kept;
+    /// the oldest are destroyed once there are more.
+    pub retain: u32,
+}
+
+/// Return an error unless a snapshot schedule keeps at least one of the
+/// snapshots that it takes.
+pub fn validate_snapshot_retention(retain: u32) -> StratisResult<()> {
+    if retain == 0 {
+        return Err(StratisError::Structured(
+            ErrorEnum::Invalid,
+            ErrorPayload::new("SnapshotRetentionInvalid").param("retain", retain),
+        ));
+    }
+    Ok(())
+}
+
+/// An optional capability, which may or may not be available, depending on
+/// the build of stratisd and the kernel on which it runs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Capability {
+    /// Pools encrypted with dm-crypt.
+    Encryption,
+    /// Checksums of data, kept with dm-integrity.
+    Integrity,
+    /// Caches made with dm-writecache.
+    WriteCache,
+    /// Mirrored data, kept with dm-raid.
+    Raid1,
+    /// The JSON-RPC interface.
+    JsonRpc,
+}
+
+impl Capability {
+    /// Every capability, in the order in which they are reported.
+    pub const ALL: &'static [Capability] = &[
+        Capability::Encryption,
+        Capability::Integrity,
+        Capability::WriteCache,
+        Capability::Raid1,
+        Capability::JsonRpc,
+    ];
+
+    /// Whether this build of stratisd implements the capability. A
+    /// capability that is implemented may still be unavailable if the
+    /// kernel does not support the DM targets it requires.
+    pub fn implemented(self) -> bool {
+        match self {
+            Capability::WriteCache | Capability::Raid1 => true,
+            Capability::Encryption
+            | Capability::Integrity
+            | Capability::JsonRpc => false,
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Capability::Encryption => write!(f, "encryption"),
+            Capability::Integrity => write!(f, "dm-integrity"),
+            Capability::WriteCache => write!(f, "writecache"),
+            Capability::Raid1 => write!(f, "raid1"),
+            Capability::JsonRpc => write!(f, "json-rpc"),
+        }
+    }
+}
+
+/// The usage of the cache of a pool.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CacheUsage {
+    /// The space in the cache that holds data.
+    pub used: Sectors,
+    /// The space in the cache that holds data not yet written to the
+    /// origin.
+    pub dirty: Sectors,
+    /// The space in the cache available for data.
+    pub total: Sectors,
+}
+
+/// The directory in which devicemapper devices are found by name.
+const DM_DEVNODE_DIR: &str = "/dev/mapper";
+
+/// A devicemapper device that makes up part of a pool or filesystem, as
+/// dmsetup identifies it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DmDeviceInfo {
+    pub name: String,
+    pub uuid: Option<String>,
+    /// The device's node in /dev/mapper.
+    pub devnode: PathBuf,
+}
+
+impl DmDeviceInfo {
+    pub fn new(name: &DmName, uuid: Option<&DmUuid>) -> DmDeviceInfo {
+        DmDeviceInfo {
+            name: name.to_string(),
+            uuid: uuid.map(|uuid| uuid.to_string()),
+            devnode: Path::new(DM_DEVNODE_DIR).join(name.to_string()),
+        }
+    }
+}
+
 /// Redundancy classifications which the engine allows for pools.
 macro_attr! {
-    #[derive(Debug, Eq, PartialEq)]
+    #[derive(Debug, Clone, Copy, Eq, PartialEq)]
     #[allow(non_camel_case_types)]
     /// Redundancy specification for a pool.
     pub enum Redundancy {
         NONE,
+        /// Every data device holds a copy of all the data.
+        RAID1,
+        /// Data is striped across the data devices, with a share of parity
+        /// on each, so that the data on any one device can be rebuilt.
+        RAID5,
+    }
+}
+
+impl Redundancy {
+    /// The redundancy with the given name, as given by Display.
+    pub fn from_name(name: &str) -> Option<Redundancy> {
+        match name {
+            "none" => Some(Redundancy::NONE),
+            "raid1" => Some(Redundancy::RAID1),
+            "raid5" => Some(Redundancy::RAID5),
+            _ => None,
+        }
+    }
+
+    /// Return an error if a pool with this redundancy can not be made on
+    /// count data devices. A pool without redundancy makes no demand of
+    /// its own; whether it has any devices at all is checked elsewhere.
+    pub fn check_devices(self, count: usize) -> StratisResult<()> {
+        let required = match self {
+            Redundancy::NONE => 0,
+            Redundancy::RAID1 => 2,
+            Redundancy::RAID5 => 3,
+        };
+        if count < required {
+            return Err(StratisError::Structured(
+                ErrorEnum::Invalid,
+                ErrorPayload::new("RedundancyTooFewDevices")
+                    .param("redundancy", self)
+                    .param("required", required)
+                    .param("count", count),
+            ));
+        }
+        Ok(())
     }
 }
 
@@ -147,6 +1220,101 @@ impl From<Redundancy> for u16 {
     }
 }
 
+impl fmt::Display for Redundancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Redundancy::NONE => write!(f, "none"),
+            Redundancy::RAID1 => write!(f, "raid1"),
+            Redundancy::RAID5 => write!(f, "raid5"),
+        }
+    }
+}
+
+/// The identity of the process that requested the creation of a pool or a
+/// filesystem, as reported by the D-Bus.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Creator {
+    /// The UID of the process.
+    pub uid: u32,
+    /// The name of the process, if it could be found.
+    pub process: Option<String>,
+}
+
+impl Creator {
+    /// The creator that is the process with PID pid, run by uid, named as
+    /// procfs names it, if it can still be found there.
+    pub fn of_process(uid: u32, pid: u32) -> Creator {
+        let process = fs::read_to_string(format!("/proc/{}/comm", pid))
+            .ok()
+            .map(|comm| comm.trim().to_owned());
+        Creator { uid, process }
+    }
+}
+
+/// The parameters with which a pool was created. They are recorded when the
+/// pool is created and never change afterward, so that how a pool was built
+/// can be found out long after.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CreationParams {
+    /// The size of the MDA of each of the pool's devices.
+    pub mda_size: Sectors,
+    /// The size of the data blocks of the pool's thin pool device.
+    pub chunk_size: Sectors,
+    pub redundancy: Redundancy,
+    pub encrypted: bool,
+    /// The version of stratisd that created the pool.
+    pub daemon_version: String,
+}
+
+impl CreationParams {
+    /// The parameters of a pool created now, by this version of stratisd.
+    pub fn new(
+        mda_size: Sectors,
+        chunk_size: Sectors,
+        redundancy: Redundancy,
+        encrypted: bool,
+    ) -> CreationParams {
+        CreationParams {
+            mda_size,
+            chunk_size,
+            redundancy,
+            encrypted,
+            daemon_version: VERSION.to_owned(),
+        }
+    }
+}
+
+/// The means by which an encrypted pool may be unlocked, and whether it
+/// is.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolBindings {
+    /// Whether every LUKS2 device of the pool is open.
+    pub unlocked: bool,
+    /// The description of the key in the kernel keyring that unlocks the
+    /// pool, if one is kept.
+    pub key_description: Option<String>,
+    /// The Clevis pin, such as "tang", of each keyslot whose passphrase is
+    /// sealed by Clevis.
+    pub clevis_pins: Vec<String>,
+    /// The number of keyslots in use in the LUKS2 header of each device.
+    pub keyslots_used: u32,
+    /// The number of keyslots that the LUKS2 header of each device has.
+    pub keyslots_total: u32,
+}
+
+/// Return an error if the url or the thumbprint of the signing key of a
+/// Tang server, to which a pool is to be bound, is empty.
+pub fn validate_tang(url: &str, thumbprint: &str) -> StratisResult<()> {
+    if url.is_empty() || thumbprint.is_empty() {
+        Err(StratisError::Engine(
+            ErrorEnum::Invalid,
+            "a Tang server is bound by its URL and the thumbprint of its signing key".into(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Name(Rc<String>);
 
@@ -185,3 +1353,379 @@ impl fmt::Display for Name {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use serde_json;
+
+    use super::*;
+
+    fn planned(size: u64, rotational: Option<bool>) -> PlannedBlockDev {
+        PlannedBlockDev {
+            devnode: PathBuf::from("/dev/null"),
+            size: Sectors(size),
+            usable: Sectors(size / 2),
+            rotational,
+            new: true,
+        }
+    }
+
+    #[test]
+    /// Verify that the capacity of a plan is the sum of the usable space
+    /// of its devices, and that mixed sizes and mixed rotational devices
+    /// are warned about.
+    fn test_add_datadevs_plan() {
+        let plan = AddDataDevsPlan::new(vec![planned(1000, Some(true)), planned(950, None)]);
+        assert_eq!(plan.usable_capacity, Sectors(975));
+        assert!(plan.warnings.is_empty());
+
+        let plan = AddDataDevsPlan::new(vec![planned(1000, None), planned(500, None)]);
+        assert_eq!(plan.warnings.len(), 1);
+
+        let plan = AddDataDevsPlan::new(vec![
+            planned(1000, Some(true)),
+            planned(1000, Some(false)),
+        ]);
+        assert_eq!(plan.warnings.len(), 1);
+    }
+
+    #[test]
+    /// Verify that the limit may be reached but not passed, and that a
+    /// limit below the count is refused.
+    fn test_fs_limit() {
+        assert!(check_fs_limit(1, 1, 2).is_ok());
+        assert!(check_fs_limit(1, 2, 2).is_err());
+        assert!(validate_fs_limit(2, 2).is_ok());
+        assert!(validate_fs_limit(3, 2).is_err());
+    }
+
+    #[test]
+    /// Verify that a size limit may not be below the size of a filesystem.
+    fn test_fs_size_limit() {
+        assert!(validate_fs_size_limit(Sectors(2048), Sectors(4096)).is_ok());
+        assert!(validate_fs_size_limit(Sectors(2048), Sectors(2048)).is_ok());
+        assert!(validate_fs_size_limit(Sectors(2048), Sectors(1024)).is_err());
+    }
+
+    #[test]
+    /// Verify that each redundancy requires enough devices to survive the
+    /// loss of one.
+    fn test_redundancy_devices() {
+        assert!(Redundancy::NONE.check_devices(0).is_ok());
+        assert!(Redundancy::NONE.check_devices(1).is_ok());
+        assert!(Redundancy::RAID1.check_devices(1).is_err());
+        assert!(Redundancy::RAID1.check_devices(2).is_ok());
+        assert!(Redundancy::RAID5.check_devices(2).is_err());
+        assert!(Redundancy::RAID5.check_devices(3).is_ok());
+    }
+
+    #[test]
+    /// Verify that names are numbered from 1 with at least three digits,
+    /// that taken names are skipped, and that no names are made without
+    /// a prefix or a count.
+    fn test_prefixed_names() {
+        let taken = ["fs-002", "fs-004", "other-001"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            prefixed_names("fs", 3, &taken).unwrap(),
+            vec!["fs-001", "fs-003", "fs-005"]
+        );
+        assert_eq!(prefixed_names("vm", 1000, &taken).unwrap()[999], "vm-1000");
+        assert!(prefixed_names("", 1, &taken).is_err());
+        assert!(prefixed_names("fs", 0, &taken).is_err());
+    }
+
+    #[test]
+    /// Verify that sizes are mixed only if they differ by more than a
+    /// tenth of the largest size.
+    fn test_mixed_sizes() {
+        assert_eq!(mixed_sizes(vec![]), None);
+        assert_eq!(mixed_sizes(vec![Sectors(1000)]), None);
+        assert_eq!(mixed_sizes(vec![Sectors(1000), Sectors(900)]), None);
+        assert_eq!(
+            mixed_sizes(vec![Sectors(1000), Sectors(2000), Sectors(1500)]),
+            Some((Sectors(1000), Sectors(2000)))
+        );
+    }
+
+    #[test]
+    /// Verify that the overcommit cap is enforced on the percentage after
+    /// the addition, and that no cap admits any addition.
+    fn test_check_overcommit() {
+        assert_eq!(overcommit_percent(Sectors(300), Sectors(200)), Some(150));
+        assert_eq!(overcommit_percent(Sectors(300), Sectors(0)), None);
+
+        assert!(check_overcommit(Sectors(100), Sectors(100), Sectors(200), Some(100)).is_ok());
+        assert!(check_overcommit(Sectors(100), Sectors(101), Sectors(200), Some(100)).is_err());
+        assert_eq!(overcommit_percent(Sectors(201), Sectors(200)), Some(100));
+        assert_eq!(
+            overcommit_percent(Sectors(u64::max_value()), Sectors(1)),
+            Some(u64::max_value())
+        );
+        assert!(check_overcommit(Sectors(0), Sectors(1), Sectors(0), Some(100)).is_err());
+        assert!(check_overcommit(Sectors(0), Sectors(1), Sectors(0), None).is_ok());
+    }
+
+    #[test]
+    /// Verify that the lowest format versions are taken field by field.
+    fn test_lowest_format_versions() {
+        assert_eq!(FormatVersions::lowest(vec![]), None);
+
+        let a = FormatVersions {
+            sigblock: 1,
+            mda_header: 2,
+            metadata: 3,
+        };
+        let b = FormatVersions {
+            sigblock: 2,
+            mda_header: 1,
+            metadata: 3,
+        };
+        assert_eq!(
+            FormatVersions::lowest(vec![a, b]),
+            Some(FormatVersions {
+                sigblock: 1,
+                mda_header: 1,
+                metadata: 3,
+            })
+        );
+    }
+
+    #[test]
+    /// Verify that '*' and '?' match as in a shell glob, and that every
+    /// other character matches only itself.
+    fn test_glob_match() {
+        assert!(glob_match("", ""));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("home", "home"));
+        assert!(!glob_match("home", "homes"));
+        assert!(glob_match("h?me", "home"));
+        assert!(!glob_match("h?me", "hme"));
+        assert!(glob_match("*-backup", "home-backup"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+        assert!(glob_match("**.?", "fs.1"));
+        assert!(!glob_match("*a", "b"));
+    }
+
+    #[test]
+    /// Verify that a filesystem is selected only if it meets every
+    /// criterion that is set.
+    fn test_filesystem_filter() {
+        let pool_uuid = PoolUuid::new_v4();
+        let created = Utc::now();
+        assert!(FilesystemFilter::default().matches(pool_uuid, "fs", created));
+
+        let filter = FilesystemFilter {
+            pool_uuid: Some(pool_uuid),
+            name_glob: Some("f*".into()),
+            created_since: Some(created),
+        };
+        assert!(filter.matches(pool_uuid, "fs", created));
+        assert!(!filter.matches(PoolUuid::new_v4(), "fs", created));
+        assert!(!filter.matches(pool_uuid, "other", created));
+        assert!(!filter.matches(pool_uuid, "fs", created - Duration::seconds(1)));
+    }
+
+    #[test]
+    /// Verify that only snapshots that are not protected are reclaimed, the
+    /// oldest first, and that the target must be a percentage.
+    fn test_reclaim_order() {
+        let (origin, old, new) = (
+            FilesystemUuid::new_v4(),
+            FilesystemUuid::new_v4(),
+            FilesystemUuid::new_v4(),
+        );
+        let pinned = FilesystemUuid::new_v4();
+        let created = Utc::now();
+        let order = reclaim_order(vec![
+            (new, Some(old), false, created),
+            (origin, None, false, created - Duration::hours(2)),
+            (pinned, Some(origin), true, created - Duration::hours(3)),
+            (old, Some(origin), false, created - Duration::hours(1)),
+        ]);
+        assert_eq!(order, vec![old, new]);
+
+        assert!(validate_reclaim_target(0).is_ok());
+        assert!(validate_reclaim_target(100).is_ok());
+        assert!(validate_reclaim_target(101).is_err());
+    }
+
+    #[test]
+    /// Verify that snapshots are ordered before the filesystems they were
+    /// made from, even through a snapshot of a snapshot, and that otherwise
+    /// the given order is kept.
+    fn test_destroy_order() {
+        let (origin, snap, snap_of_snap, other) = (
+            FilesystemUuid::new_v4(),
+            FilesystemUuid::new_v4(),
+            FilesystemUuid::new_v4(),
+            FilesystemUuid::new_v4(),
+        );
+        let order = destroy_order(vec![
+            (origin, None),
+            (other, None),
+            (snap_of_snap, Some(snap)),
+            (snap, Some(origin)),
+            (origin, None),
+        ]);
+        assert_eq!(order, vec![snap_of_snap, snap, origin, other]);
+
+        let order = destroy_order(vec![(snap_of_snap, Some(snap)), (origin, None)]);
+        assert_eq!(order, vec![snap_of_snap, origin]);
+    }
+
+    #[test]
+    /// Verify that a devicemapper device is found in /dev/mapper by its
+    /// name, and that its uuid is kept only if it has one.
+    fn test_dm_device_info() {
+        let name = DmName::new("stratis-1-pool").unwrap();
+        let uuid = DmUuid::new("stratis-1-uuid").unwrap();
+
+        let info = DmDeviceInfo::new(name, Some(uuid));
+        assert_eq!(info.name, "stratis-1-pool");
+        assert_eq!(info.uuid, Some("stratis-1-uuid".to_string()));
+        assert_eq!(info.devnode, PathBuf::from("/dev/mapper/stratis-1-pool"));
+
+        assert_eq!(DmDeviceInfo::new(name, None).uuid, None);
+    }
+
+    #[test]
+    /// Verify that every allocation strategy is found by the name by which
+    /// it is recorded, and that no other name is that of a strategy.
+    fn test_allocation_strategy_names() {
+        for strategy in &[
+            AllocationStrategy::FillFirst,
+            AllocationStrategy::RoundRobin,
+            AllocationStrategy::SsdFirst,
+        ] {
+            assert_eq!(
+                AllocationStrategy::from_name(&strategy.to_string()),
+                Some(*strategy)
+            );
+        }
+        assert_eq!(AllocationStrategy::from_name("fastest"), None);
+    }
+
+    #[test]
+    /// Verify that every I/O class is found by the name by which it is
+    /// recorded, and that worker priorities out of range are refused.
+    fn test_worker_priority() {
+        for io_class in &[IoClass::BestEffort, IoClass::Idle] {
+            assert_eq!(IoClass::from_name(&io_class.to_string()), Some(*io_class));
+        }
+        assert_eq!(IoClass::from_name("realtime"), None);
+
+        assert!(validate_worker_priority(-20, 0).is_ok());
+        assert!(validate_worker_priority(19, 7).is_ok());
+        assert!(validate_worker_priority(-21, 4).is_err());
+        assert!(validate_worker_priority(20, 4).is_err());
+        assert!(validate_worker_priority(0, 8).is_err());
+    }
+
+    #[test]
+    /// Verify that every snapshot interval is found by the name by which it
+    /// is recorded, and that each period begins on the hour or at midnight.
+    fn test_snapshot_interval() {
+        for interval in &[SnapshotInterval::Hourly, SnapshotInterval::Daily] {
+            assert_eq!(
+                SnapshotInterval::from_name(&interval.to_string()),
+                Some(*interval)
+            );
+        }
+        assert_eq!(SnapshotInterval::from_name("weekly"), None);
+
+        let now = Utc.ymd(2019, 2, 14).and_hms(13, 27, 5);
+        assert_eq!(
+            SnapshotInterval::Hourly.period_start(now),
+            Utc.ymd(2019, 2, 14).and_hms(13, 0, 0)
+        );
+        assert_eq!(
+            SnapshotInterval::Daily.period_start(now),
+            Utc.ymd(2019, 2, 14).and_hms(0, 0, 0)
+        );
+
+        assert!(validate_snapshot_retention(1).is_ok());
+        assert!(validate_snapshot_retention(0).is_err());
+    }
+
+    #[test]
+    /// Verify that every state, tier, cache type, and redundancy is found by
+    /// the name by which it is reported, and that a number or a name in
+    /// another case is not the name of a state.
+    fn test_state_names() {
+        let pool_states = [
+            PoolState::Initializing,
+            PoolState::Running,
+            PoolState::ReadOnly,
+            PoolState::OutOfDataSpace,
+            PoolState::Failed,
+            PoolState::Stopping,
+            PoolState::NeedsCheck,
+        ];
+        for state in &pool_states {
+            assert_eq!(PoolState::from_name(&state.to_string()), Some(*state));
+        }
+        let extend_states = [
+            PoolExtendState::Initializing,
+            PoolExtendState::Good,
+            PoolExtendState::DataFailed,
+            PoolExtendState::MetaFailed,
+            PoolExtendState::MetaAndDataFailed,
+        ];
+        for state in &extend_states {
+            assert_eq!(PoolExtendState::from_name(&state.to_string()), Some(*state));
+        }
+        let space_states = [FreeSpaceState::Good, FreeSpaceState::Warn, FreeSpaceState::Crit];
+        for state in &space_states {
+            assert_eq!(FreeSpaceState::from_name(&state.to_string()), Some(*state));
+        }
+        let blockdev_states = [
+            BlockDevState::Missing,
+            BlockDevState::Bad,
+            BlockDevState::Spare,
+            BlockDevState::NotInUse,
+            BlockDevState::InUse,
+        ];
+        for state in &blockdev_states {
+            assert_eq!(BlockDevState::from_name(&state.to_string()), Some(*state));
+        }
+        for tier in &[BlockDevTier::Data, BlockDevTier::Cache] {
+            assert_eq!(BlockDevTier::from_name(&tier.to_string()), Some(*tier));
+        }
+        for cache_type in &[CacheType::Cache, CacheType::WriteCache] {
+            assert_eq!(CacheType::from_name(&cache_type.to_string()), Some(*cache_type));
+        }
+        for redundancy in &[Redundancy::NONE, Redundancy::RAID1, Redundancy::RAID5] {
+            assert_eq!(Redundancy::from_name(&redundancy.to_string()), Some(*redundancy));
+        }
+
+        assert_eq!(PoolState::from_name("Running"), None);
+        assert_eq!(BlockDevState::from_name("2"), None);
+    }
+
+    #[test]
+    /// Verify that a pool UUID is saved, parsed, and printed as the UUID it
+    /// wraps, so that metadata written before the UUID types were distinct
+    /// is still read.
+    fn test_uuid_type_saved_as_uuid() {
+        let pool_uuid = PoolUuid::new_v4();
+        let uuid = *pool_uuid;
+        assert_eq!(
+            serde_json::to_string(&pool_uuid).unwrap(),
+            serde_json::to_string(&uuid).unwrap()
+        );
+        let json = serde_json::to_string(&uuid).unwrap();
+        assert_eq!(serde_json::from_str::<PoolUuid>(&json).unwrap(), pool_uuid);
+        assert_eq!(
+            PoolUuid::parse_str(&uuid.simple().to_string()).unwrap(),
+            pool_uuid
+        );
+        assert_eq!(pool_uuid.to_string(), uuid.to_string());
+        assert_eq!(Uuid::from(pool_uuid), uuid);
+    }
+}
@@ -0,0 +1,349 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Decode the requests made to the JSON-RPC server, dispatch them to the
+// engine's commands, and encode the replies, as JSON-RPC 2.0 describes. Every
+// error returned by the engine has the one code ENGINE_ERROR; what went wrong
+// is given in the error's data, by the identifier and parameters of the
+// error's payload, which the D-Bus also gives.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use devicemapper::{Sectors, SECTOR_SIZE};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use super::super::engine::{commands, Creator, Engine, FilesystemUuid, PoolUuid};
+use super::super::stratis::StratisError;
+
+/// The version of JSON-RPC that every request must name.
+const JSONRPC_VERSION: &str = "2.0";
+
+// The codes of the errors that JSON-RPC 2.0 defines.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The code of every error returned by the engine.
+const ENGINE_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    id: Value,
+}
+
+/// What went wrong, for an error returned by the engine.
+#[derive(Debug, Serialize)]
+struct EngineErrorData {
+    /// The identifier of the error's code, such as "NotFound".
+    kind: &'static str,
+    /// The identifier of the error's payload, such as "PoolNotFound".
+    id: &'static str,
+    params: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<EngineErrorData>,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorObject>,
+    id: Value,
+}
+
+impl Response {
+    fn result(id: Value, result: Value) -> Response {
+        Response {
+            jsonrpc: JSONRPC_VERSION,
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: String) -> Response {
+        Response {
+            jsonrpc: JSONRPC_VERSION,
+            result: None,
+            error: Some(ErrorObject {
+                code,
+                message,
+                data: None,
+            }),
+            id,
+        }
+    }
+
+    fn engine_error(id: Value, err: &StratisError) -> Response {
+        let payload = err.payload();
+        Response {
+            jsonrpc: JSONRPC_VERSION,
+            result: None,
+            error: Some(ErrorObject {
+                code: ENGINE_ERROR,
+                message: err.to_string(),
+                data: Some(EngineErrorData {
+                    kind: err.code().identifier(),
+                    id: payload.id,
+                    params: payload.params,
+                }),
+            }),
+            id,
+        }
+    }
+}
+
+/// Why a method could not be done.
+enum Failure {
+    /// Its params are not those that it takes.
+    Params(serde_json::Error),
+    /// The engine refused it.
+    Engine(StratisError),
+}
+
+impl From<StratisError> for Failure {
+    fn from(err: StratisError) -> Failure {
+        Failure::Engine(err)
+    }
+}
+
+fn decode<T: DeserializeOwned>(params: Value) -> Result<T, Failure> {
+    serde_json::from_value(params).map_err(Failure::Params)
+}
+
+fn encode<T: Serialize>(result: T) -> Result<Value, Failure> {
+    serde_json::to_value(result).map_err(|err| Failure::Engine(err.into()))
+}
+
+/// A size in bytes, rounded up to a whole sector.
+fn bytes_to_sectors(bytes: u64) -> Sectors {
+    let sector_size = SECTOR_SIZE as u64;
+    Sectors(bytes / sector_size + if bytes % sector_size == 0 { 0 } else { 1 })
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatePoolParams {
+    name: String,
+    devices: Vec<PathBuf>,
+    redundancy: Option<u16>,
+    size_policy: Option<u16>,
+    signature_policy: Option<u16>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreatedPool {
+    uuid: PoolUuid,
+}
+
+fn create_pool(
+    engine: &mut Engine,
+    params: Value,
+    creator: Option<Creator>,
+) -> Result<Value, Failure> {
+    let params: CreatePoolParams = decode(params)?;
+    let devices = params
+        .devices
+        .iter()
+        .map(|devnode| devnode.as_path())
+        .collect::<Vec<&Path>>();
+    let pool_uuid = commands::create_pool(
+        engine,
+        &params.name,
+        &devices,
+        params.redundancy,
+        params.size_policy,
+        params.signature_policy,
+        creator,
+    )?;
+    encode(CreatedPool { uuid: pool_uuid })
+}
+
+#[derive(Debug, Deserialize)]
+struct FilesystemSpec {
+    name: String,
+    /// The size of the filesystem in bytes, rounded up to a whole sector;
+    /// the default size if absent.
+    size: Option<u64>,
+    #[serde(default)]
+    guaranteed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateFilesystemsParams {
+    pool: PoolUuid,
+    filesystems: Vec<FilesystemSpec>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreatedFilesystem<'a> {
+    name: &'a str,
+    uuid: FilesystemUuid,
+}
+
+fn create_filesystems(
+    engine: &mut Engine,
+    params: Value,
+    creator: Option<Creator>,
+) -> Result<Value, Failure> {
+    let params: CreateFilesystemsParams = decode(params)?;
+    let specs = params
+        .filesystems
+        .iter()
+        .map(|spec| {
+            (
+                spec.name.as_str(),
+                spec.size.map(bytes_to_sectors),
+                spec.guaranteed,
+            )
+        })
+        .collect::<Vec<(&str, Option<Sectors>, bool)>>();
+    let created = commands::create_filesystems(engine, params.pool, &specs, creator)?;
+    encode(
+        created
+            .into_iter()
+            .map(|(name, uuid)| CreatedFilesystem { name, uuid })
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn dispatch(engine: &mut Engine, request: Request, creator: Option<Creator>) -> Response {
+    let Request {
+        method, params, id, ..
+    } = request;
+    let result = match method.as_str() {
+        "CreatePool" => create_pool(engine, params, creator),
+        "CreateFilesystems" => create_filesystems(engine, params, creator),
+        "Report" => encode(commands::report(engine)),
+        _ => {
+            return Response::error(id, METHOD_NOT_FOUND, format!("no method named {}", method))
+        }
+    };
+    match result {
+        Ok(result) => Response::result(id, result),
+        Err(Failure::Params(err)) => Response::error(id, INVALID_PARAMS, err.to_string()),
+        Err(Failure::Engine(err)) => Response::engine_error(id, &err),
+    }
+}
+
+/// Handle request, a JSON-RPC request made by creator, and return the
+/// reply to it. Every request is replied to, even one without an id.
+pub fn handle_request(engine: &mut Engine, request: &str, creator: Option<Creator>) -> String {
+    let response = match serde_json::from_str::<Value>(request) {
+        Err(err) => Response::error(Value::Null, PARSE_ERROR, err.to_string()),
+        Ok(value) => match serde_json::from_value::<Request>(value) {
+            Err(err) => Response::error(Value::Null, INVALID_REQUEST, err.to_string()),
+            Ok(ref request) if request.jsonrpc != JSONRPC_VERSION => Response::error(
+                request.id.clone(),
+                INVALID_REQUEST,
+                format!("JSON-RPC version {} is not supported", request.jsonrpc),
+            ),
+            Ok(request) => dispatch(engine, request, creator),
+        },
+    };
+    serde_json::to_string(&response).expect("every response can be serialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::engine::SimEngine;
+
+    use super::*;
+
+    /// Make the request, and return the reply parsed.
+    fn call(engine: &mut Engine, request: &str) -> Value {
+        serde_json::from_str(&handle_request(engine, request, None)).unwrap()
+    }
+
+    #[test]
+    /// Verify that a pool and a filesystem created through requests are
+    /// reported, and that the filesystem is given the size requested.
+    fn test_create_and_report() {
+        let mut engine = SimEngine::default();
+        let reply = call(
+            &mut engine,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "CreatePool",
+                "params": {"name": "pool", "devices": ["/dev/one", "/dev/two"]}}"#,
+        );
+        assert_eq!(reply["id"], 1);
+        let pool_uuid = reply["result"]["uuid"].as_str().unwrap().to_owned();
+
+        let reply = call(
+            &mut engine,
+            &format!(
+                r#"{{"jsonrpc": "2.0", "id": 2, "method": "CreateFilesystems",
+                     "params": {{"pool": "{}", "filesystems": [{{"name": "fs"}}]}}}}"#,
+                pool_uuid
+            ),
+        );
+        assert_eq!(reply["result"][0]["name"], "fs");
+
+        let reply = call(&mut engine, r#"{"jsonrpc": "2.0", "id": 3, "method": "Report"}"#);
+        let pools = reply["result"].as_array().unwrap();
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0]["uuid"], Value::String(pool_uuid));
+        assert_eq!(pools[0]["filesystems"][0]["name"], "fs");
+
+        assert_eq!(bytes_to_sectors(1), Sectors(1));
+        assert_eq!(bytes_to_sectors(SECTOR_SIZE as u64), Sectors(1));
+    }
+
+    #[test]
+    /// Verify that each kind of bad request is given the error that
+    /// JSON-RPC 2.0 defines for it, and that an error returned by the
+    /// engine is described by its payload.
+    fn test_errors() {
+        let mut engine = SimEngine::default();
+        let code = |reply: &Value| reply["error"]["code"].as_i64().unwrap();
+
+        assert_eq!(code(&call(&mut engine, "{")), PARSE_ERROR);
+        assert_eq!(
+            code(&call(&mut engine, r#"{"jsonrpc": "2.0", "id": 1}"#)),
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            code(&call(&mut engine, r#"{"jsonrpc": "1.0", "id": 1, "method": "Report"}"#)),
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            code(&call(&mut engine, r#"{"jsonrpc": "2.0", "id": 1, "method": "Nothing"}"#)),
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            code(&call(
+                &mut engine,
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "CreatePool", "params": {}}"#
+            )),
+            INVALID_PARAMS
+        );
+
+        let reply = call(
+            &mut engine,
+            &format!(
+                r#"{{"jsonrpc": "2.0", "id": 1, "method": "CreateFilesystems",
+                     "params": {{"pool": "{}", "filesystems": []}}}}"#,
+                PoolUuid::new_v4()
+            ),
+        );
+        assert_eq!(code(&reply), ENGINE_ERROR);
+        assert_eq!(reply["error"]["data"]["kind"], "NotFound");
+        assert_eq!(reply["error"]["data"]["id"], "PoolNotFound");
+    }
+}
@@ -0,0 +1,166 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// The JSON-RPC server listens on a Unix socket that only root may connect
+// to, and a client that the kernel does not give as root is turned away
+// before its request is read. A client sends a single request, a line of
+// JSON, and is sent a single reply, a line of JSON, after which its
+// connection is closed. The clients are answered one at a time from
+// stratisd's main loop, so that a request is handled as a D-Bus method
+// call is, and a client that is slow to send its request is given up on
+// rather than allowed to hold up the daemon.
+
+use std::fs::{self, Permissions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::mem;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use nix::libc;
+
+use super::super::engine::{Creator, Engine};
+use super::super::stratis::StratisResult;
+
+use super::methods::handle_request;
+
+/// The path of the socket, unless another is chosen.
+pub const DEFAULT_SOCKET_PATH: &str = "/run/stratisd.sock";
+
+/// How long a client is waited on to send its request, or to take its
+/// reply, before it is given up on.
+const CLIENT_TIMEOUT_SECS: u64 = 5;
+
+/// The most bytes that a request may be. A longer request is cut short,
+/// and so can not be parsed.
+const MAX_REQUEST_BYTES: u64 = 1 << 20;
+
+/// A JSON-RPC server, listening on its socket.
+#[derive(Debug)]
+pub struct JsonRpcServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl JsonRpcServer {
+    /// Listen on a socket at path, which only root may connect to. A socket
+    /// left at path by an earlier stratisd is replaced. The socket is
+    /// created with its permissions already restricted, so that there is no
+    /// moment at which another user may connect to it.
+    pub fn bind(path: &Path) -> StratisResult<JsonRpcServer> {
+        if let Err(err) = fs::remove_file(path) {
+            if err.kind() != ErrorKind::NotFound {
+                return Err(err.into());
+            }
+        }
+        let old_umask = unsafe { libc::umask(0o177) };
+        let bound = UnixListener::bind(path);
+        unsafe { libc::umask(old_umask) };
+        let listener = bound?;
+        fs::set_permissions(path, Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+        Ok(JsonRpcServer {
+            listener,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Answer every client waiting to connect.
+    /// Returns true if any request was handled, since it may have changed
+    /// the engine's state.
+    pub fn handle_clients(&self, engine: &mut Engine) -> bool {
+        let mut handled = false;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    handled = true;
+                    if let Err(err) = serve(engine, &stream) {
+                        warn!("unable to answer JSON-RPC client: {}", err);
+                    }
+                }
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => return handled,
+                Err(err) => {
+                    warn!("unable to accept JSON-RPC client: {}", err);
+                    return handled;
+                }
+            }
+        }
+    }
+}
+
+impl AsRawFd for JsonRpcServer {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+impl Drop for JsonRpcServer {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!(
+                "unable to remove JSON-RPC socket {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// The identity of the process at the other end of stream, as the kernel
+/// gives it, if it can be found.
+fn peer_creator(stream: &UnixStream) -> Option<Creator> {
+    let mut cred: libc::ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result < 0 {
+        return None;
+    }
+    Some(Creator::of_process(cred.uid, cred.pid as u32))
+}
+
+/// Read the client's request from stream, and write the reply to it.
+/// A client that is not root, or that can not be identified, is sent
+/// nothing.
+fn serve(engine: &mut Engine, stream: &UnixStream) -> io::Result<()> {
+    let creator = match peer_creator(stream) {
+        Some(creator) => {
+            if creator.uid != 0 {
+                return Err(io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("client with UID {} is not root", creator.uid),
+                ));
+            }
+            creator
+        }
+        None => {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "unable to identify client",
+            ))
+        }
+    };
+
+    let timeout = Some(Duration::from_secs(CLIENT_TIMEOUT_SECS));
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+
+    let mut request = String::new();
+    BufReader::new(stream.take(MAX_REQUEST_BYTES)).read_line(&mut request)?;
+
+    let mut reply = handle_request(engine, &request, Some(creator));
+    reply.push('\n');
+    let mut stream = stream;
+    stream.write_all(reply.as_bytes())
+}
@@ -40,6 +40,8 @@ pub mod engine;
 #[cfg(feature = "dbus_enabled")]
 pub mod dbus_api;
 
+pub mod jsonrpc;
+
 pub mod stratis;
 
 #[macro_use]
@@ -16,6 +16,10 @@ use log::{self, Level, Log, Metadata, MetadataBuilder, Record};
 const LOCK_EXPECT_MSG: &str =
     "No code in this module can panic; therefore the mutex can not be poisoned.";
 
+/// The target of audit records. These are never buffered, since an audit
+/// record must be output when the operation it records is performed.
+pub const AUDIT_TARGET: &str = "stratisd::audit";
+
 #[derive(Debug, Clone)]
 /// A structure that allows interaction with the installed buff_log.
 pub struct Handle<L: Log> {
@@ -118,7 +122,7 @@ impl<L: Log> Log for Logger<L> {
     }
     fn log(&self, record: &Record) {
         let shared = self.0.lock().expect(LOCK_EXPECT_MSG);
-        if shared.pass_through {
+        if shared.pass_through || record.target() == AUDIT_TARGET {
             shared.log.log(record)
         } else {
             let now = Utc::now();
@@ -226,5 +230,8 @@ mod tests {
         assert_eq!(handle.buffered_count(), 6);
         handle.dump();
         assert_eq!(handle.buffered_count(), 0);
+
+        info!(target: AUDIT_TARGET, "audit 1");
+        assert_eq!(handle.buffered_count(), 0);
     }
 }
@@ -2,7 +2,9 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::path::{Path, PathBuf};
 use std::str;
 use std::{fmt, io};
 
@@ -15,18 +17,156 @@ use uuid;
 
 use devicemapper;
 
+use engine::PoolUuid;
+
 pub type StratisResult<T> = Result<T, StratisError>;
 
-#[derive(Debug, Clone)]
+/// The machine-readable classification of a StratisError.
+/// Every StratisError, whatever its origin, maps to exactly one of these,
+/// see StratisError::code(). The APIs translate these, rather than the
+/// StratisError variants, into their own error codes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum ErrorEnum {
     Error,
 
     AlreadyExists,
     Busy,
     Invalid,
+    NoSpace,
     NotFound,
 }
 
+impl ErrorEnum {
+    /// A stable identifier for the code.
+    pub fn identifier(self) -> &'static str {
+        match self {
+            ErrorEnum::Error => "Error",
+            ErrorEnum::AlreadyExists => "AlreadyExists",
+            ErrorEnum::Busy => "Busy",
+            ErrorEnum::Invalid => "Invalid",
+            ErrorEnum::NoSpace => "NoSpace",
+            ErrorEnum::NotFound => "NotFound",
+        }
+    }
+}
+
+/// Prose templates for structured errors, keyed by identifier. Each
+/// occurrence of "{key}" in a template is replaced by the value of the
+/// parameter key.
+const ERROR_TEMPLATES: &[(&str, &str)] = &[
+    ("BackupNotFound", "no metadata backup of pool {pool} with generation {generation}"),
+    ("BlockDevMissing", "blockdev {uuid} is missing, so the data on it can not be moved"),
+    ("BlockDevShrunk", "blockdev {uuid} is {actual} sectors, less than the {recorded} recorded"),
+    ("BlockDevNotData", "blockdev {uuid} is not in the data tier, so it can not be removed"),
+    ("DeviceHasForeignSignatures", "device {devnode} has foreign signatures: {signatures}"),
+    ("DeviceHasSignature", "device {devnode} has an existing signature {signature}"),
+    ("DeviceInOtherPool", "device {devnode} already belongs to Stratis pool {owner}"),
+    ("DeviceOfDestroyedPool", "device {devnode} of destroyed pool {owner} must be wiped first"),
+    ("DeviceSizesMixed", "devices are of mixed sizes, from {smallest} to {largest} sectors"),
+    ("DeviceTooSmall", "device {devnode} too small, minimum {minimum} bytes"),
+    ("DeviceUnknownToPool", "device {devnode} with pool UUID is unknown to pool"),
+    ("FilesystemLimitExceeded", "{requested} filesystems requested, above the limit of {limit}"),
+    ("FilesystemLimitTooLow", "limit {limit} is below the {count} filesystems the pool holds"),
+    ("FilesystemMounted", "filesystem is mounted at {mount_point}"),
+    ("FilesystemNameExists", "a filesystem named {name} already exists"),
+    ("FilesystemNotFound", "no filesystem with UUID {uuid} found"),
+    ("FilesystemSizeLimitTooLow", "size limit {limit} sectors is below the size {size} sectors"),
+    ("FilesystemSizeTooSmall", "filesystem size {size} sectors too small, minimum {minimum}"),
+    ("MetadataClockSkew", "clock reads {proposed}, earlier than metadata written at {newest}"),
+    ("NameInvalid", "name {name} is invalid: {rule}"),
+    ("PoolHasFilesystems", "filesystems remaining on pool"),
+    ("PlanNotFound", "no plan with token {token} found"),
+    ("PassphraseIncorrect", "the passphrase does not unlock pool {pool}"),
+    ("PlanOutOfDate", "the plan with token {token} no longer describes the result of applying it"),
+    ("PoolEncrypted", "devices can not be added to pool {name}, which is encrypted"),
+    ("PoolNameExists", "a pool named {name} already exists"),
+    ("PoolNotFound", "no pool with UUID {uuid} found"),
+    ("PoolNeedsCheck", "the metadata of pool {name} needs a check; repair the pool first"),
+    ("SpaceUnavailable", "{requested} sectors requested, only {available} sectors available"),
+    ("RedundancyUnsupported", "code {code} does not correspond to any redundancy"),
+    ("RedundancyTooFewDevices", "{redundancy} needs at least {required} devices, not {count}"),
+    ("RaidDataDevsUnsupported", "the data devices of a pool with {redundancy} can not change"),
+    ("RemoveAllDataDevs", "the data tier must keep at least one blockdev"),
+    ("SizePolicyUnsupported", "code {code} does not correspond to any mixed size policy"),
+    ("SignaturePolicyUnsupported", "code {code} does not correspond to any signature policy"),
+    ("CacheTypeUnsupported", "code {code} does not correspond to any cache type"),
+    ("AllocationStrategyUnsupported", "code {code} does not correspond to any allocation strategy"),
+    ("IoClassUnsupported", "code {code} does not correspond to any I/O class"),
+    ("NicenessInvalid", "niceness {niceness} is not from -20 to 19"),
+    ("IoLevelInvalid", "I/O priority level {level} is not from 0 to 7"),
+    ("SnapshotIntervalUnsupported", "code {code} does not correspond to any snapshot interval"),
+    ("SnapshotRetentionInvalid", "a snapshot schedule must keep at least 1 snapshot, not {retain}"),
+    ("DmTargetMissing", "kernel does not provide dm-{target}, needs dm-{target} >= {required}"),
+    ("DmTargetTooOld", "kernel too old: needs dm-{target} >= {required}, but has {found}"),
+    ("CacheTypeMismatch", "the cache of pool {name} is of type {existing}, not {requested}"),
+    ("OvercommitCapExceeded", "overcommit would reach {percent}%, above the cap of {cap}%"),
+    ("ThinMetaSpaceLow", "thin pool metadata is {percent}% used, too full for a snapshot"),
+    ("UndoWindowElapsed", "pool {pool} was destroyed at {destroyed}, too long ago to undo"),
+    ("DestroyedDeviceMissing", "blockdev {dev} of destroyed pool {pool} was wiped or is missing"),
+    ("UsageThresholdInvalid", "usage threshold {threshold} is not a percentage from 1 to 100"),
+    ("WatermarkInvalid", "{watermark} watermark {percent} is not a percentage from 1 to 99"),
+];
+
+/// A description of an error by a stable identifier and named parameters,
+/// from which clients can construct their own messages. The prose for an
+/// error is rendered from its payload only when it is reported.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ErrorPayload {
+    pub id: &'static str,
+    pub params: BTreeMap<String, String>,
+}
+
+impl ErrorPayload {
+    pub fn new(id: &'static str) -> ErrorPayload {
+        ErrorPayload {
+            id,
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Add a parameter to the payload.
+    pub fn param<T: ToString>(mut self, key: &str, value: T) -> ErrorPayload {
+        self.params.insert(key.to_owned(), value.to_string());
+        self
+    }
+
+    /// Render the payload as prose. If there is no template for the
+    /// identifier, fall back on the "message" parameter, if any.
+    pub fn render(&self) -> String {
+        match ERROR_TEMPLATES.iter().find(|&&(id, _)| id == self.id) {
+            Some(&(_, template)) => self.params
+                .iter()
+                .fold(template.to_owned(), |acc, (key, value)| {
+                    acc.replace(&format!("{{{}}}", key), value)
+                }),
+            None => self.params
+                .get("message")
+                .cloned()
+                .unwrap_or_else(|| self.id.to_owned()),
+        }
+    }
+}
+
+/// Information about the objects an error pertains to.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ErrorContext {
+    pub pool_uuid: Option<PoolUuid>,
+    pub devnode: Option<PathBuf>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.pool_uuid, &self.devnode) {
+            (Some(uuid), &Some(ref devnode)) => {
+                write!(f, "pool {}, device {}", uuid.simple(), devnode.display())
+            }
+            (Some(uuid), &None) => write!(f, "pool {}", uuid.simple()),
+            (None, &Some(ref devnode)) => write!(f, "device {}", devnode.display()),
+            (None, &None) => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum StratisError {
     Error(String),
@@ -41,6 +181,143 @@ pub enum StratisError {
     #[cfg(feature = "dbus_enabled")]
     Dbus(dbus::Error),
     Udev(libudev::Error),
+
+    /// An error described by a structured payload rather than by prose.
+    Structured(ErrorEnum, ErrorPayload),
+
+    /// An error, annotated with the objects it pertains to. The annotated
+    /// error is never itself a Context.
+    Context(ErrorContext, Box<StratisError>),
+}
+
+impl StratisError {
+    /// The machine-readable code for this error.
+    pub fn code(&self) -> ErrorEnum {
+        match *self {
+            StratisError::Error(_) => ErrorEnum::Error,
+            StratisError::Engine(ref e, _) => *e,
+            StratisError::Io(ref err) => match err.kind() {
+                io::ErrorKind::NotFound => ErrorEnum::NotFound,
+                io::ErrorKind::AlreadyExists => ErrorEnum::AlreadyExists,
+                io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => ErrorEnum::Invalid,
+                _ => ErrorEnum::Error,
+            },
+            StratisError::Nix(nix::Error::Sys(errno)) => match errno {
+                nix::errno::Errno::ENOENT | nix::errno::Errno::ENODEV => ErrorEnum::NotFound,
+                nix::errno::Errno::EEXIST => ErrorEnum::AlreadyExists,
+                nix::errno::Errno::EBUSY => ErrorEnum::Busy,
+                nix::errno::Errno::EINVAL => ErrorEnum::Invalid,
+                nix::errno::Errno::ENOSPC => ErrorEnum::NoSpace,
+                _ => ErrorEnum::Error,
+            },
+            StratisError::Nix(_) => ErrorEnum::Error,
+            StratisError::Uuid(_) | StratisError::Utf8(_) | StratisError::Serde(_) => {
+                ErrorEnum::Invalid
+            }
+            StratisError::DM(_) => ErrorEnum::Error,
+
+            #[cfg(feature = "dbus_enabled")]
+            StratisError::Dbus(_) => ErrorEnum::Error,
+            StratisError::Udev(_) => ErrorEnum::Error,
+            StratisError::Structured(code, _) => code,
+            StratisError::Context(_, ref err) => err.code(),
+        }
+    }
+
+    /// The structured description of this error. Errors not constructed
+    /// from a payload are identified by their code, and their message
+    /// is passed as the "message" parameter. Any context is included
+    /// in the parameters.
+    pub fn payload(&self) -> ErrorPayload {
+        match *self {
+            StratisError::Structured(_, ref payload) => payload.clone(),
+            StratisError::Context(ref context, ref err) => {
+                let mut payload = err.payload();
+                if let Some(pool_uuid) = context.pool_uuid {
+                    payload = payload.param("pool_uuid", pool_uuid.simple());
+                }
+                if let Some(ref devnode) = context.devnode {
+                    payload = payload.param("devnode", devnode.display());
+                }
+                payload
+            }
+            StratisError::Engine(code, ref msg) => {
+                ErrorPayload::new(code.identifier()).param("message", msg)
+            }
+            ref err => ErrorPayload::new(err.code().identifier()).param("message", err),
+        }
+    }
+
+    /// The objects this error pertains to, if known.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match *self {
+            StratisError::Context(ref context, _) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error stripped of any context.
+    pub fn root(&self) -> &StratisError {
+        match *self {
+            StratisError::Context(_, ref err) => &**err,
+            ref err => err,
+        }
+    }
+
+    /// Annotate the error with the UUID of the pool it pertains to.
+    /// An existing pool UUID annotation is not overwritten, since it was
+    /// made closer to the origin of the error.
+    pub fn with_pool(self, pool_uuid: PoolUuid) -> StratisError {
+        self.with_context(|context| {
+            if context.pool_uuid.is_none() {
+                context.pool_uuid = Some(pool_uuid);
+            }
+        })
+    }
+
+    /// Annotate the error with the device node it pertains to.
+    /// An existing device node annotation is not overwritten.
+    pub fn with_devnode(self, devnode: &Path) -> StratisError {
+        self.with_context(|context| {
+            if context.devnode.is_none() {
+                context.devnode = Some(devnode.to_owned());
+            }
+        })
+    }
+
+    fn with_context<F>(self, update: F) -> StratisError
+    where
+        F: FnOnce(&mut ErrorContext),
+    {
+        let (mut context, err) = match self {
+            StratisError::Context(context, err) => (context, err),
+            err => (ErrorContext::default(), Box::new(err)),
+        };
+        update(&mut context);
+        StratisError::Context(context, err)
+    }
+}
+
+/// Extension methods for annotating the error in a StratisResult.
+pub trait StratisResultExt<T> {
+    /// Annotate an error with the UUID of the pool it pertains to.
+    fn with_pool(self, pool_uuid: PoolUuid) -> StratisResult<T>;
+
+    /// Annotate an error with the device node it pertains to.
+    fn with_devnode(self, devnode: &Path) -> StratisResult<T>;
+}
+
+impl<T, E> StratisResultExt<T> for Result<T, E>
+where
+    E: Into<StratisError>,
+{
+    fn with_pool(self, pool_uuid: PoolUuid) -> StratisResult<T> {
+        self.map_err(|err| err.into().with_pool(pool_uuid))
+    }
+
+    fn with_devnode(self, devnode: &Path) -> StratisResult<T> {
+        self.map_err(|err| err.into().with_devnode(devnode))
+    }
 }
 
 impl fmt::Display for StratisError {
@@ -60,6 +337,8 @@ impl fmt::Display for StratisError {
                 write!(f, "Dbus error: {}", err.message().unwrap_or("Unknown"))
             }
             StratisError::Udev(ref err) => write!(f, "Udev error: {}", err),
+            StratisError::Structured(_, ref payload) => write!(f, "{}", payload.render()),
+            StratisError::Context(ref context, ref err) => write!(f, "{}: {}", context, err),
         }
     }
 }
@@ -79,12 +358,16 @@ impl Error for StratisError {
             #[cfg(feature = "dbus_enabled")]
             StratisError::Dbus(ref err) => err.message().unwrap_or("D-Bus Error"),
             StratisError::Udev(ref err) => Error::description(err),
+            StratisError::Structured(_, ref payload) => payload.id,
+            StratisError::Context(_, ref err) => err.description(),
         }
     }
 
     fn cause(&self) -> Option<&Error> {
         match *self {
-            StratisError::Error(_) | StratisError::Engine(_, _) => None,
+            StratisError::Error(_)
+            | StratisError::Engine(_, _)
+            | StratisError::Structured(_, _) => None,
             StratisError::Io(ref err) => Some(err),
             StratisError::Nix(ref err) => Some(err),
             StratisError::Uuid(ref err) => Some(err),
@@ -95,6 +378,7 @@ impl Error for StratisError {
             #[cfg(feature = "dbus_enabled")]
             StratisError::Dbus(ref err) => Some(err),
             StratisError::Udev(ref err) => Some(err),
+            StratisError::Context(_, ref err) => Some(&**err),
         }
     }
 }
@@ -147,3 +431,57 @@ impl From<libudev::Error> for StratisError {
         StratisError::Udev(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    /// Verify that annotating an error preserves its code, that repeated
+    /// annotation does not nest, and that the innermost annotation wins.
+    fn test_context() {
+        let pool_uuid = PoolUuid::new_v4();
+        let result: StratisResult<()> = Err(io::Error::new(io::ErrorKind::NotFound, "gone"))
+            .with_devnode(Path::new("/dev/a"))
+            .with_pool(pool_uuid)
+            .with_devnode(Path::new("/dev/b"));
+        let err = result.unwrap_err();
+
+        assert_eq!(err.code(), ErrorEnum::NotFound);
+        assert_eq!(
+            err.context(),
+            Some(&ErrorContext {
+                pool_uuid: Some(pool_uuid),
+                devnode: Some(Path::new("/dev/a").to_owned()),
+            })
+        );
+        match *err.root() {
+            StratisError::Io(_) => {}
+            ref err => panic!("unexpected root error {:?}", err),
+        }
+    }
+
+    #[test]
+    /// Verify that a structured error is rendered from its template, and
+    /// that its context is included in its payload.
+    fn test_payload() {
+        let pool_uuid = PoolUuid::new_v4();
+        let err = StratisError::Structured(
+            ErrorEnum::AlreadyExists,
+            ErrorPayload::new("FilesystemNameExists").param("name", "fs"),
+        ).with_pool(pool_uuid);
+
+        assert_eq!(err.root().to_string(), "a filesystem named fs already exists");
+
+        let payload = err.payload();
+        assert_eq!(payload.id, "FilesystemNameExists");
+        assert_eq!(payload.params.get("name").map(|s| &**s), Some("fs"));
+        assert_eq!(
+            payload.params.get("pool_uuid"),
+            Some(&pool_uuid.simple().to_string())
+        );
+    }
+}
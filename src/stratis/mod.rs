@@ -2,8 +2,10 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-pub use self::errors::{ErrorEnum, StratisError, StratisResult};
-pub use self::stratis::VERSION;
+pub use self::errors::{
+    ErrorContext, ErrorEnum, ErrorPayload, StratisError, StratisResult, StratisResultExt,
+};
+pub use self::stratis::{build_features, VERSION};
 
 pub mod buff_log;
 mod errors;
@@ -3,3 +3,15 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The optional features with which stratisd was built.
+pub fn build_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "dbus_enabled") {
+        features.push("dbus_enabled");
+    }
+    if cfg!(feature = "fuzzing") {
+        features.push("fuzzing");
+    }
+    features
+}
@@ -21,6 +21,7 @@ from ._implementation import Filesystem
 from ._implementation import Manager
 from ._implementation import ObjectManager
 from ._implementation import Pool
+from ._implementation import Query
 from ._implementation import blockdevs
 from ._implementation import pools
 from ._implementation import filesystems
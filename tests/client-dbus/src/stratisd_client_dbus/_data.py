@@ -27,69 +27,493 @@ SPECS = {
     "org.storage.stratis1.Manager":
     """
 <interface name="org.storage.stratis1.Manager">
+<method name="AdvanceSimulatorClock">
+<arg name="seconds" type="t" direction="in"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="BindTang">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="passphrase" type="s" direction="in"/>
+<arg name="url" type="s" direction="in"/>
+<arg name="thumbprint" type="s" direction="in"/>
+<arg name="bound" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
 <method name="ConfigureSimulator">
 <arg name="denominator" type="u" direction="in"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ConfigureSimulatorCapacities">
+<arg name="device_size" type="(bt)" direction="in"/>
+<arg name="filesystem_size" type="(bt)" direction="in"/>
+<arg name="initial_used" type="(bt)" direction="in"/>
+<arg name="growth" type="(bt)" direction="in"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="CreateEncryptedPool">
+<arg name="name" type="s" direction="in"/>
+<arg name="redundancy" type="(bq)" direction="in"/>
+<arg name="size_policy" type="(bq)" direction="in"/>
+<arg name="signature_policy" type="(bq)" direction="in"/>
+<arg name="devices" type="as" direction="in"/>
+<arg name="passphrase" type="s" direction="in"/>
+<arg name="result" type="(oao)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <method name="CreatePool">
 <arg name="name" type="s" direction="in"/>
 <arg name="redundancy" type="(bq)" direction="in"/>
+<arg name="size_policy" type="(bq)" direction="in"/>
+<arg name="signature_policy" type="(bq)" direction="in"/>
 <arg name="devices" type="as" direction="in"/>
 <arg name="result" type="(oao)" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="CreatePoolWithFilesystems">
+<arg name="name" type="s" direction="in"/>
+<arg name="redundancy" type="(bq)" direction="in"/>
+<arg name="size_policy" type="(bq)" direction="in"/>
+<arg name="signature_policy" type="(bq)" direction="in"/>
+<arg name="devices" type="as" direction="in"/>
+<arg name="filesystems" type="a(s(bt)b)" direction="in"/>
+<arg name="result" type="(oaoao)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <method name="DestroyPool">
 <arg name="pool" type="o" direction="in"/>
+<arg name="keep_signatures" type="b" direction="in"/>
+<arg name="action" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="GetJob">
+<arg name="job" type="t" direction="in"/>
+<arg name="result" type="(bqs)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ReconstructPool">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="generation" type="t" direction="in"/>
+<arg name="devices" type="as" direction="in"/>
+<arg name="result" type="(oao)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="RepairPool">
+<arg name="pool" type="o" direction="in"/>
+<arg name="repaired" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="RestoreMetadataBackup">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="generation" type="t" direction="in"/>
+<arg name="result" type="(bo)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetKey">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="passphrase" type="s" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="UnbindTang">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="unbound" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="UndoDestroyPool">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="result" type="(bo)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="UnlockPool">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="passphrase" type="s" direction="in"/>
+<arg name="result" type="(bo)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="UnsetKey">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="unset" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="WipeReclaimableDevice">
+<arg name="devnode" type="s" direction="in"/>
 <arg name="action" type="b" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
+<signal name="DeviceAdded">
+<arg name="blockdev" type="o"/>
+<arg name="uuid" type="s"/>
+</signal>
+<signal name="DeviceFailed">
+<arg name="blockdev" type="o"/>
+<arg name="uuid" type="s"/>
+</signal>
+<signal name="DeviceRemoved">
+<arg name="blockdev" type="o"/>
+<arg name="uuid" type="s"/>
+</signal>
+<signal name="FilesystemCreated">
+<arg name="filesystem" type="o"/>
+<arg name="uuid" type="s"/>
+</signal>
+<signal name="FilesystemDestroyed">
+<arg name="filesystem" type="o"/>
+<arg name="uuid" type="s"/>
+</signal>
+<signal name="PoolCreated">
+<arg name="pool" type="o"/>
+<arg name="uuid" type="s"/>
+</signal>
+<signal name="PoolDestroyed">
+<arg name="pool" type="o"/>
+<arg name="uuid" type="s"/>
+</signal>
+<signal name="SetupProgress">
+<arg name="name" type="s"/>
+<arg name="step" type="u"/>
+<arg name="total" type="u"/>
+<arg name="stage" type="s"/>
+</signal>
+<property name="EngineCapabilities" type="as" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="MaxFormatVersions" type="(qqq)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="Statistics" type="a{st}" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
 <property name="Version" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
 </property>
 </interface>
+""",
+    "org.storage.stratis1.Query":
+    """
+<interface name="org.storage.stratis1.Query">
+<method name="DaemonInfo">
+<arg name="info" type="(sassttttt)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="GetChanges">
+<arg name="since" type="(bs)" direction="in"/>
+<arg name="result" type="(sba(ssos))" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListBindings">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="bindings" type="(b(bs)as(uu))" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListClonedDevices">
+<arg name="devices" type="a(ssss)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListFilesystemSpace">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="space" type="a(oss)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListFilesystems">
+<arg name="pool_uuid" type="(bs)" direction="in"/>
+<arg name="name" type="(bs)" direction="in"/>
+<arg name="created_since" type="(bs)" direction="in"/>
+<arg name="filesystems" type="a(ssssso)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListFilesystemsPage">
+<arg name="pool_uuid" type="(bs)" direction="in"/>
+<arg name="name" type="(bs)" direction="in"/>
+<arg name="created_since" type="(bs)" direction="in"/>
+<arg name="cursor" type="(bs)" direction="in"/>
+<arg name="limit" type="u" direction="in"/>
+<arg name="result" type="(a(ssssso)(bs))" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListKeys">
+<arg name="pool_uuids" type="as" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListLockedPools">
+<arg name="pool_uuids" type="as" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListMetadataBackups">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="backups" type="a(ts)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ListReclaimableDevices">
+<arg name="devices" type="a(ssss)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="PlanDestroyPool">
+<arg name="pool_uuid" type="s" direction="in"/>
+<arg name="plan" type="(a(ssas)asas)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+</interface>
 """,
     "org.storage.stratis1.pool":
     """
 <interface name="org.storage.stratis1.pool">
 <method name="AddCacheDevs">
 <arg name="devices" type="as" direction="in"/>
+<arg name="signature_policy" type="(bq)" direction="in"/>
+<arg name="cache_type" type="(bq)" direction="in"/>
+<arg name="volatile" type="b" direction="in"/>
 <arg name="results" type="ao" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <method name="AddDataDevs">
 <arg name="devices" type="as" direction="in"/>
+<arg name="signature_policy" type="(bq)" direction="in"/>
 <arg name="results" type="ao" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ApplyAddDataDevsPlan">
+<arg name="plan" type="s" direction="in"/>
+<arg name="results" type="ao" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="CheckFilesystem">
+<arg name="filesystem" type="o" direction="in"/>
+<arg name="repair" type="b" direction="in"/>
+<arg name="result" type="(bs)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <method name="CreateFilesystems">
 <arg name="specs" type="as" direction="in"/>
+<arg name="size" type="(bt)" direction="in"/>
+<arg name="guaranteed" type="b" direction="in"/>
 <arg name="filesystems" type="a(os)" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="CreatePrefixedFilesystems">
+<arg name="prefix" type="s" direction="in"/>
+<arg name="count" type="q" direction="in"/>
+<arg name="size" type="(bt)" direction="in"/>
+<arg name="guaranteed" type="b" direction="in"/>
+<arg name="filesystems" type="a(os)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <method name="DestroyFilesystems">
 <arg name="filesystems" type="ao" direction="in"/>
-<arg name="results" type="as" direction="out"/>
+<arg name="results" type="a(sbs)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="EmergencyReclaim">
+<arg name="target" type="y" direction="in"/>
+<arg name="destroyed" type="ao" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="GetReclaimEstimates">
+<arg name="estimates" type="a(os)" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="PlanAddDataDevs">
+<arg name="devices" type="as" direction="in"/>
+<arg name="plan" type="(ssa(sss(bb)b)as)" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="RemoveBlockDevs">
+<arg name="blockdevs" type="ao" direction="in"/>
+<arg name="removed" type="ao" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ReplaceBlockDev">
+<arg name="blockdev" type="o" direction="in"/>
+<arg name="device" type="s" direction="in"/>
+<arg name="signature_policy" type="(bq)" direction="in"/>
+<arg name="replacement" type="o" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ReplayMetadata">
+<arg name="anomalies" type="as" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetAllocationStrategy">
+<arg name="strategy" type="(bq)" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetDefaultFilesystemSize">
+<arg name="size" type="(bt)" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetFilesystemLimit">
+<arg name="filesystem" type="o" direction="in"/>
+<arg name="limit" type="(bt)" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetFsLimit">
+<arg name="limit" type="t" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <method name="SetName">
 <arg name="name" type="s" direction="in"/>
 <arg name="action" type="b" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetOvercommitCap">
+<arg name="cap" type="(bt)" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetSnapshotSchedule">
+<arg name="filesystem" type="o" direction="in"/>
+<arg name="schedule" type="(b(qu))" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetUsageThresholds">
+<arg name="thresholds" type="ay" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetVerifyWrites">
+<arg name="verify" type="b" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetWatermarks">
+<arg name="warn" type="(by)" direction="in"/>
+<arg name="extend" type="(by)" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetWorkerPriority">
+<arg name="niceness" type="n" direction="in"/>
+<arg name="io_class" type="(bq)" direction="in"/>
+<arg name="io_level" type="y" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="ShrinkReservedSpace">
+<arg name="reclaimed" type="s" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <method name="SnapshotFilesystem">
 <arg name="origin" type="o" direction="in"/>
 <arg name="snapshot_name" type="s" direction="in"/>
+<arg name="freeze" type="b" direction="in"/>
 <arg name="result" type="o" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <property name="Name" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
@@ -106,12 +530,141 @@ SPECS = {
 <property name="State" type="q" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
 </property>
+<property name="StateName" type="s" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
+</property>
 <property name="ExtendState" type="q" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
 </property>
+<property name="ExtendStateName" type="s" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
+</property>
 <property name="SpaceState" type="q" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
 </property>
+<property name="SpaceStateName" type="s" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
+</property>
+<property name="DaysUntilFull" type="(bt)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
+</property>
+<property name="OvercommitPercent" type="(bt)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="OvercommitCap" type="(bt)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="UsageThresholds" type="ay" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="WarnWatermark" type="(by)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="ExtendWatermark" type="(by)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="DefaultFilesystemSize" type="s" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="FsLimit" type="t" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="FilesystemCount" type="t" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="ThinMetaTotal" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="ThinMetaUsed" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="CreatedBy" type="(b(us))" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="Encrypted" type="b" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="KeyDescription" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="CreationMdaSize" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="CreationChunkSize" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="CreationRedundancy" type="(bq)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="CreationEncrypted" type="(bb)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="CreationVersion" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="VerifyWrites" type="b" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="OperationStuck" type="b" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="LastMetadataUpdate" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="MetadataBehindDevs" type="ao" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="FormatVersions" type="(qqq)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="AllocationStrategy" type="q" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="CacheType" type="(bq)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="CacheVolatile" type="b" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="CacheUsage" type="(b(sss))" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="DmDevices" type="a(ssss)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="WorkerPriority" type="(nqy)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<signal name="MetadataClockSkew">
+<arg name="proposed" type="s"/>
+<arg name="newest" type="s"/>
+</signal>
+<signal name="PoolStateChanged">
+<arg name="state" type="q"/>
+<arg name="state_name" type="s"/>
+</signal>
+<signal name="RemoveBlockDevsProgress">
+<arg name="moved" type="s"/>
+<arg name="total" type="s"/>
+</signal>
+<signal name="ThinMetaExtended">
+<arg name="succeeded" type="b"/>
+<arg name="total" type="s"/>
+<arg name="reason" type="s"/>
+</signal>
+<signal name="UsageThresholdCrossed">
+<arg name="threshold" type="y"/>
+<arg name="rising" type="b"/>
+<arg name="used" type="s"/>
+<arg name="total" type="s"/>
+</signal>
+<signal name="WatermarkCrossed">
+<arg name="watermark" type="s"/>
+<arg name="percent" type="y"/>
+<arg name="rising" type="b"/>
+<arg name="used" type="s"/>
+<arg name="total" type="s"/>
+</signal>
 </interface>
 """,
     "org.storage.stratis1.filesystem":
@@ -122,19 +675,52 @@ SPECS = {
 <arg name="action" type="b" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetProtected">
+<arg name="protected" type="b" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
+</method>
+<method name="SetReadAhead">
+<arg name="read_ahead" type="(bt)" direction="in"/>
+<arg name="changed" type="b" direction="out"/>
+<arg name="return_code" type="q" direction="out"/>
+<arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <property name="Created" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
 </property>
+<property name="CreatedBy" type="(b(us))" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
 <property name="Devnode" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
 </property>
+<property name="DmDevice" type="(b(sss))" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
+<property name="Guaranteed" type="b" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
 <property name="Name" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
 </property>
 <property name="Pool" type="o" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
 </property>
+<property name="Protected" type="b" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
+</property>
+<property name="ReadAhead" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
+</property>
+<property name="SizeLimit" type="(bs)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
+</property>
 <property name="Used" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
 </property>
@@ -151,28 +737,53 @@ SPECS = {
 <arg name="changed" type="b" direction="out"/>
 <arg name="return_code" type="q" direction="out"/>
 <arg name="return_string" type="s" direction="out"/>
+<arg name="return_details" type="(sa{ss})" direction="out"/>
 </method>
 <property name="Devnode" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
 </property>
+<property name="FormatVersions" type="(qqq)" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+</property>
 <property name="HardwareInfo" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
 </property>
 <property name="InitializationTime" type="t" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
 </property>
+<property name="MetadataCorruptions" type="t" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="MetadataDegraded" type="b" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="MetadataStatistics" type="a{st}" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
+<property name="MetadataWriteThrottled" type="b" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
 <property name="Pool" type="o" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
 </property>
 <property name="State" type="q" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
 </property>
+<property name="StateName" type="s" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="true"/>
+</property>
 <property name="Tier" type="q" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
 </property>
+<property name="TierName" type="s" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
 <property name="TotalPhysicalSize" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
 </property>
+<property name="UsableSize" type="s" access="read">
+<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+</property>
 <property name="UserInfo" type="s" access="read">
 <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
 </property>
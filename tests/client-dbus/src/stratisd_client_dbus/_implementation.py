@@ -44,5 +44,8 @@ ObjectManager = make_class("ObjectManager",
 Manager = make_class("Manager",
                      ET.fromstring(SPECS['org.storage.stratis1.Manager']),
                      TIME_OUT)
+Query = make_class("Query",
+                   ET.fromstring(SPECS['org.storage.stratis1.Query']),
+                   TIME_OUT)
 Filesystem = make_class("Filesystem", _FILESYSTEM_SPEC, TIME_OUT)
 Pool = make_class("Pool", _POOL_SPEC, TIME_OUT)
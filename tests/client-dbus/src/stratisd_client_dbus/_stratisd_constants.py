@@ -29,3 +29,4 @@ class StratisdErrors(IntEnum):
     BUSY = 3
     INTERNAL_ERROR = 4
     NOT_FOUND = 5
+    NO_SPACE = 6
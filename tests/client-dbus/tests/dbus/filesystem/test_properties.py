@@ -15,6 +15,7 @@
 Test accessing properties of a filesystem.
 """
 
+import os
 import unittest
 
 from stratisd_client_dbus import Filesystem
@@ -45,15 +46,21 @@ class SetNameTestCase(unittest.TestCase):
         self._service = Service()
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
-        ((self._pool_object_path, _), _, _) = Manager.Methods.CreatePool(
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': _DEVICE_STRATEGY.example()
             })
         self._pool_object = get_object(self._pool_object_path)
-        (created, _, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': [self._fs_name]})
+        (created, _, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [self._fs_name],
+                'size': (False, 0),
+                'guaranteed': False
+            })
         self._filesystem_object_path = created[0][0]
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
 
@@ -88,6 +95,24 @@ class SetNameTestCase(unittest.TestCase):
 
         self.assertEqual(used, "12345678")
 
+        guaranteed = Filesystem.Properties.Guaranteed.Get(filesystem)
+
+        self.assertFalse(guaranteed)
+
+        (recorded, (uid, _)) = Filesystem.Properties.CreatedBy.Get(filesystem)
+
+        self.assertTrue(recorded)
+        self.assertEqual(uid, os.getuid())
+
         devnode = Filesystem.Properties.Devnode.Get(filesystem)
 
         self.assertEqual(devnode, "/stratis/deadpool/fs")
+
+        # The simulator makes no devicemapper devices
+        (exists, _) = Filesystem.Properties.DmDevice.Get(filesystem)
+
+        self.assertFalse(exists)
+
+        dm_devices = Pool.Properties.DmDevices.Get(self._pool_object)
+
+        self.assertEqual(dm_devices, [])
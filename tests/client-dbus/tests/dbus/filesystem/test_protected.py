@@ -0,0 +1,103 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test protecting a filesystem from being destroyed.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Filesystem
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class SetProtectedTestCase(unittest.TestCase):
+    """
+    Set up a pool with one filesystem.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool = get_object(pool_object_path)
+        (created, _, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool, {
+                'specs': ['fs'],
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        self._filesystem_path = created[0][0]
+        self._filesystem = get_object(self._filesystem_path)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testProtect(self):
+        """
+        A filesystem is not protected at first. Once protected, it can not
+        be destroyed until it is unprotected.
+        """
+        self.assertFalse(
+            Filesystem.Properties.Protected.Get(self._filesystem))
+
+        (changed, rc, _, _) = Filesystem.Methods.SetProtected(
+            self._filesystem, {'protected': True})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertTrue(Filesystem.Properties.Protected.Get(self._filesystem))
+
+        (changed, rc, _, _) = Filesystem.Methods.SetProtected(
+            self._filesystem, {'protected': True})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(changed)
+
+        (_, rc, _, _) = Pool.Methods.DestroyFilesystems(
+            self._pool, {'filesystems': [self._filesystem_path]})
+        self.assertNotEqual(rc, StratisdErrors.OK)
+
+        (changed, rc, _, _) = Filesystem.Methods.SetProtected(
+            self._filesystem, {'protected': False})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+
+        (destroyed, rc, _, _) = Pool.Methods.DestroyFilesystems(
+            self._pool, {'filesystems': [self._filesystem_path]})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(len(destroyed), 1)
@@ -0,0 +1,98 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test setting the read-ahead of a filesystem.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Filesystem
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class SetReadAheadTestCase(unittest.TestCase):
+    """
+    Set up a pool with one filesystem.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        (created, _, _, _) = Pool.Methods.CreateFilesystems(
+            get_object(pool_object_path), {
+                'specs': ['fs'],
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        self._filesystem = get_object(created[0][0])
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testSetReadAhead(self):
+        """
+        No read-ahead is set at first. Once set, it is reported, and setting
+        it again changes nothing. It can then be unset.
+        """
+        self.assertEqual(
+            Filesystem.Properties.ReadAhead.Get(self._filesystem),
+            (False, ''))
+
+        (changed, rc, _, _) = Filesystem.Methods.SetReadAhead(
+            self._filesystem, {'read_ahead': (True, 4194304)})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(
+            Filesystem.Properties.ReadAhead.Get(self._filesystem),
+            (True, '4194304'))
+
+        (changed, rc, _, _) = Filesystem.Methods.SetReadAhead(
+            self._filesystem, {'read_ahead': (True, 4194304)})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(changed)
+
+        (changed, rc, _, _) = Filesystem.Methods.SetReadAhead(
+            self._filesystem, {'read_ahead': (False, 0)})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(
+            Filesystem.Properties.ReadAhead.Get(self._filesystem),
+            (False, ''))
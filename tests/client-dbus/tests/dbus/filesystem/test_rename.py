@@ -48,15 +48,21 @@ class SetNameTestCase(unittest.TestCase):
         self._service = Service()
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
-        ((self._pool_object_path, _), _, _) = Manager.Methods.CreatePool(
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': _DEVICE_STRATEGY.example()
             })
         self._pool_object = get_object(self._pool_object_path)
-        (created, _, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': [self._fs_name]})
+        (created, _, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [self._fs_name],
+                'size': (False, 0),
+                'guaranteed': False
+            })
         self._filesystem_object_path = created[0][0]
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
 
@@ -71,7 +77,7 @@ class SetNameTestCase(unittest.TestCase):
         Test rename to same name.
         """
         filesystem = get_object(self._filesystem_object_path)
-        (result, rc, _) = Filesystem.Methods.SetName(filesystem,
+        (result, rc, _, _) = Filesystem.Methods.SetName(filesystem,
                                                      {'name': self._fs_name})
 
         self.assertEqual(rc, StratisdErrors.OK)
@@ -82,7 +88,7 @@ class SetNameTestCase(unittest.TestCase):
         Test rename to new name.
         """
         filesystem = get_object(self._filesystem_object_path)
-        (result, rc, _) = Filesystem.Methods.SetName(filesystem,
+        (result, rc, _, _) = Filesystem.Methods.SetName(filesystem,
                                                      {'name': "new"})
 
         self.assertEqual(rc, StratisdErrors.OK)
@@ -0,0 +1,90 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test ListMetadataBackups, ReconstructPool and RestoreMetadataBackup.
+"""
+
+import unittest
+import uuid
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+
+class BackupTestCase(unittest.TestCase):
+    """
+    Test metadata backups in the simulator, which keeps none.
+    """
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testListNoBackups(self):
+        """
+        An unknown pool has no backups.
+        """
+        (backups, rc, _, _) = Query.Methods.ListMetadataBackups(
+            self._proxy, {'pool_uuid': uuid.uuid4().hex})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(backups, [])
+
+    def testRestoreNoBackup(self):
+        """
+        Restoring a backup that does not exist fails.
+        """
+        ((restored, _), rc, _, _) = Manager.Methods.RestoreMetadataBackup(
+            self._proxy, {
+                'pool_uuid': uuid.uuid4().hex,
+                'generation': 0
+            })
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
+        self.assertFalse(restored)
+
+    def testReconstructNoBackup(self):
+        """
+        Reconstructing from a backup that does not exist fails.
+        """
+        (_, rc, _, _) = Manager.Methods.ReconstructPool(
+            self._proxy, {
+                'pool_uuid': uuid.uuid4().hex,
+                'generation': 0,
+                'devices': []
+            })
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
+
+    def testInvalidUuid(self):
+        """
+        A pool UUID that is not a UUID is not found.
+        """
+        (_, rc, _, _) = Query.Methods.ListMetadataBackups(
+            self._proxy, {'pool_uuid': 'bogus'})
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
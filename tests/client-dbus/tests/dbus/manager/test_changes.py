@@ -0,0 +1,128 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test GetChanges.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+
+class GetChangesTestCase(unittest.TestCase):
+    """
+    Test getting the changes made since a token.
+    """
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _changes(self, since=None):
+        """
+        Get the changes since a token.
+
+        :param since: the token, or None
+        :returns: the new token, whether the changes are complete, changes
+        :rtype: str * bool * list of (str * str * str * str)
+        """
+        ((token, complete, changes), rc, _, _) = Query.Methods.GetChanges(
+            self._proxy, {'since': (since is not None, since or '')})
+        self.assertEqual(rc, StratisdErrors.OK)
+        return (token, bool(complete), [tuple(str(x) for x in change)
+                                        for change in changes])
+
+    def testNoToken(self):
+        """
+        Without a token the changes are not complete, so the client must
+        enumerate all objects.
+        """
+        (_, complete, changes) = self._changes()
+        self.assertFalse(complete)
+        self.assertEqual(changes, [])
+
+    def testInvalidToken(self):
+        """
+        A token that was not issued by the daemon is treated as unknown.
+        """
+        for since in ['bogus', '%s.1' % ('0' * 32)]:
+            (_, complete, changes) = self._changes(since)
+            self.assertFalse(complete)
+            self.assertEqual(changes, [])
+
+    def testCreateAndDestroy(self):
+        """
+        Creating and destroying a pool and a filesystem are reported as
+        changes to exactly those objects.
+        """
+        (token, _, _) = self._changes()
+
+        ((poolpath, devpaths), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': 'deadpool',
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': ['/dev/one', '/dev/two']
+            })
+        (token, complete, changes) = self._changes(token)
+        self.assertTrue(complete)
+        self.assertEqual(
+            sorted((kind, path, action)
+                   for (kind, _, path, action) in changes),
+            sorted([('pool', str(poolpath), 'created')] +
+                   [('blockdev', str(p), 'created') for p in devpaths]))
+
+        pool = get_object(poolpath)
+        (fs_list, _, _, _) = Pool.Methods.CreateFilesystems(
+            pool, {
+                'specs': ['fs'],
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        fs_path = str(fs_list[0][0])
+        (token, complete, changes) = self._changes(token)
+        self.assertTrue(complete)
+        self.assertEqual([(kind, path, action)
+                          for (kind, _, path, action) in changes],
+                         [('filesystem', fs_path, 'created')])
+
+        Pool.Methods.DestroyFilesystems(pool, {'filesystems': [fs_path]})
+        Manager.Methods.DestroyPool(self._proxy, {
+            'pool': poolpath,
+            'keep_signatures': False
+        })
+        (_, complete, changes) = self._changes(token)
+        self.assertTrue(complete)
+        self.assertEqual([(kind, action) for (kind, _, _, action) in changes],
+                         [('filesystem', 'deleted'), ('pool', 'deleted'),
+                          ('blockdev', 'deleted'), ('blockdev', 'deleted')])
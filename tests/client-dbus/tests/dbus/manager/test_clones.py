@@ -0,0 +1,71 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test ListClonedDevices.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+
+class ListClonedDevicesTestCase(unittest.TestCase):
+    """
+    Test listing devices left out of their pools as clones.
+    """
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testNoClones(self):
+        """
+        The simulator never finds a clone, with or without a pool.
+        """
+        (devices, rc, _, _) = Query.Methods.ListClonedDevices(
+            self._proxy, {})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(devices, [])
+
+        (_, rc, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': 'deadpool',
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': ['/dev/one', '/dev/two']
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+
+        (devices, rc, _, _) = Query.Methods.ListClonedDevices(
+            self._proxy, {})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(devices, [])
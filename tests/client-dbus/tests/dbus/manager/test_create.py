@@ -60,10 +60,12 @@ class Create2TestCase(unittest.TestCase):
         If rc is OK, then pool must exist.
         """
         devs = _DEVICE_STRATEGY.example()
-        ((poolpath, devnodes), rc, _) = Manager.Methods.CreatePool(
+        ((poolpath, devnodes), rc, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': devs
             })
 
@@ -96,9 +98,68 @@ class Create2TestCase(unittest.TestCase):
         Creation should always fail if RAID value is wrong.
         """
         devs = _DEVICE_STRATEGY.example()
-        (_, rc, _) = Manager.Methods.CreatePool(self._proxy, {
+        (_, rc, _, _) = Manager.Methods.CreatePool(self._proxy, {
             'name': self._POOLNAME,
-            'redundancy': (True, 1),
+            'redundancy': (True, 3),
+            'size_policy': (False, 0),
+            'signature_policy': (False, 0),
+            'devices': devs
+        })
+        self.assertEqual(rc, StratisdErrors.ERROR)
+
+    def testCreateRAID(self):
+        """
+        A pool with redundancy is created only if it has enough devices to
+        survive the loss of one.
+        """
+        for (redundancy, required) in [(1, 2), (2, 3)]:
+            devs = ['/dev/raid%d' % index for index in range(required)]
+            (_, rc, _, _) = Manager.Methods.CreatePool(
+                self._proxy, {
+                    'name': self._POOLNAME,
+                    'redundancy': (True, redundancy),
+                    'size_policy': (False, 0),
+                    'signature_policy': (False, 0),
+                    'devices': devs[1:]
+                })
+            self.assertEqual(rc, StratisdErrors.ERROR)
+
+            ((pool_object_path, _), rc, _, _) = Manager.Methods.CreatePool(
+                self._proxy, {
+                    'name': self._POOLNAME,
+                    'redundancy': (True, redundancy),
+                    'size_policy': (False, 0),
+                    'signature_policy': (False, 0),
+                    'devices': devs
+                })
+            self.assertEqual(rc, StratisdErrors.OK)
+            Manager.Methods.DestroyPool(self._proxy,
+                                        {'pool': pool_object_path})
+
+    def testCreateBadSizePolicy(self):
+        """
+        Creation should always fail if the size policy value is wrong.
+        """
+        devs = _DEVICE_STRATEGY.example()
+        (_, rc, _, _) = Manager.Methods.CreatePool(self._proxy, {
+            'name': self._POOLNAME,
+            'redundancy': (True, 0),
+            'size_policy': (True, 3),
+            'signature_policy': (False, 0),
+            'devices': devs
+        })
+        self.assertEqual(rc, StratisdErrors.ERROR)
+
+    def testCreateBadSignaturePolicy(self):
+        """
+        Creation should always fail if the signature policy value is wrong.
+        """
+        devs = _DEVICE_STRATEGY.example()
+        (_, rc, _, _) = Manager.Methods.CreatePool(self._proxy, {
+            'name': self._POOLNAME,
+            'redundancy': (True, 0),
+            'size_policy': (False, 0),
+            'signature_policy': (True, 3),
             'devices': devs
         })
         self.assertEqual(rc, StratisdErrors.ERROR)
@@ -121,6 +182,8 @@ class Create3TestCase(unittest.TestCase):
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': _DEVICE_STRATEGY.example()
             })
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
@@ -138,10 +201,12 @@ class Create3TestCase(unittest.TestCase):
         pools1 = pools().search(
             ObjectManager.Methods.GetManagedObjects(self._proxy, {}))
 
-        (_, rc, _) = Manager.Methods.CreatePool(
+        (_, rc, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': _DEVICE_STRATEGY.example()
             })
         expected_rc = StratisdErrors.ALREADY_EXISTS
@@ -0,0 +1,106 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test 'CreatePoolWithFilesystems'.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import ObjectManager
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import filesystems
+from stratisd_client_dbus import get_object
+from stratisd_client_dbus import pools
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(1)
+
+
+class CreateWithFilesystemsTestCase(unittest.TestCase):
+    """
+    Test creating a pool together with its filesystems.
+    """
+    _POOLNAME = 'installpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        self._devs = _DEVICE_STRATEGY.example()
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _create(self, names):
+        """
+        Create the pool with filesystems of the given names.
+        """
+        return Manager.Methods.CreatePoolWithFilesystems(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': self._devs,
+                'filesystems': [(name, (False, 0), False) for name in names]
+            })
+
+    def testCreate(self):
+        """
+        The pool and all its filesystems are created, and the filesystems'
+        object paths are returned in the order of their names.
+        """
+        ((poolpath, _, fspaths), rc, _, _) = self._create(['root', 'home'])
+        self.assertEqual(rc, StratisdErrors.OK)
+
+        managed_objects = \
+            ObjectManager.Methods.GetManagedObjects(self._proxy, {})
+        (pool, _) = next(
+            pools(props={
+                'Name': self._POOLNAME
+            }).search(managed_objects))
+        self.assertEqual(pool, poolpath)
+        names = [
+            next(
+                filesystems(props={
+                    'Name': name
+                }).search(managed_objects))[0] for name in ['root', 'home']
+        ]
+        self.assertEqual(names, fspaths)
+
+    def testFailureCleansUp(self):
+        """
+        If a filesystem can not be created, no pool is left behind, and the
+        setup can be tried again.
+        """
+        (_, rc, _, _) = self._create(['root', 'root'])
+        self.assertEqual(rc, StratisdErrors.ALREADY_EXISTS)
+
+        managed_objects = \
+            ObjectManager.Methods.GetManagedObjects(self._proxy, {})
+        self.assertEqual(list(pools().search(managed_objects)), [])
+
+        (_, rc, _, _) = self._create(['root'])
+        self.assertEqual(rc, StratisdErrors.OK)
@@ -0,0 +1,82 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test 'DaemonInfo'.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+
+class DaemonInfoTestCase(unittest.TestCase):
+    """
+    Test the information about the daemon.
+    """
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testEmpty(self):
+        """
+        The simulator is reported, with no pools.
+        """
+        (info, rc, _, _) = Query.Methods.DaemonInfo(self._proxy, {})
+        self.assertEqual(rc, StratisdErrors.OK)
+
+        (version, features, engine, _, _, pools, filesystems,
+         blockdevs) = info
+        self.assertEqual(version, Manager.Properties.Version.Get(self._proxy))
+        self.assertIn('dbus_enabled', features)
+        self.assertEqual(engine, 'sim')
+        self.assertEqual((pools, filesystems, blockdevs), (0, 0, 0))
+
+    def testCounts(self):
+        """
+        The pools, filesystems, and blockdevs are counted.
+        """
+        (_, rc, _, _) = Manager.Methods.CreatePoolWithFilesystems(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': ['/dev/one', '/dev/two'],
+                'filesystems': [(name, (False, 0), False)
+                                for name in ('one', 'two')]
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+
+        (info, rc, _, _) = Query.Methods.DaemonInfo(self._proxy, {})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(info[5:], (1, 2, 2))
@@ -21,6 +21,7 @@ from stratisd_client_dbus import Manager
 from stratisd_client_dbus import ObjectManager
 from stratisd_client_dbus import Pool
 from stratisd_client_dbus import MOBlockDev
+from stratisd_client_dbus import Query
 from stratisd_client_dbus import StratisdErrors
 from stratisd_client_dbus import blockdevs
 from stratisd_client_dbus import get_object
@@ -74,7 +75,10 @@ class Destroy1TestCase(unittest.TestCase):
         """
         Success should occur on a bogus object path.
         """
-        (_, rc, _) = Manager.Methods.DestroyPool(self._proxy, {'pool': "/"})
+        (_, rc, _, _) = Manager.Methods.DestroyPool(self._proxy, {
+            'pool': "/",
+            'keep_signatures': False
+        })
         self.assertEqual(rc, StratisdErrors.OK)
 
 
@@ -97,6 +101,8 @@ class Destroy2TestCase(unittest.TestCase):
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': self._devices
             })
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
@@ -122,8 +128,11 @@ class Destroy2TestCase(unittest.TestCase):
             frozenset(MOBlockDev(b).Devnode() for (_, b) in blockdevs1),
             frozenset(d for d in self._devices))
 
-        (result, rc, _) = \
-                Manager.Methods.DestroyPool(self._proxy, {'pool': pool1})
+        (result, rc, _, _) = \
+                Manager.Methods.DestroyPool(self._proxy, {
+                    'pool': pool1,
+                    'keep_signatures': False
+                })
 
         managed_objects = \
            ObjectManager.Methods.GetManagedObjects(self._proxy, {})
@@ -156,14 +165,20 @@ class Destroy3TestCase(unittest.TestCase):
         self._service.setUp()
 
         self._proxy = get_object(TOP_OBJECT)
-        ((poolpath, _), _, _) = Manager.Methods.CreatePool(
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': _DEVICE_STRATEGY.example()
             })
         Pool.Methods.CreateFilesystems(
-            get_object(poolpath), {'specs': [self._VOLNAME]})
+            get_object(poolpath), {
+                'specs': [self._VOLNAME],
+                'size': (False, 0),
+                'guaranteed': False
+            })
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
 
     def tearDown(self):
@@ -183,8 +198,11 @@ class Destroy3TestCase(unittest.TestCase):
                 'Name': self._POOLNAME
             }).search(managed_objects))
 
-        (result, rc, _) = \
-           Manager.Methods.DestroyPool(self._proxy, {'pool': pool})
+        (result, rc, _, _) = \
+           Manager.Methods.DestroyPool(self._proxy, {
+               'pool': pool,
+               'keep_signatures': False
+           })
         self.assertEqual(rc, StratisdErrors.BUSY)
         self.assertEqual(result, False)
 
@@ -213,6 +231,8 @@ class Destroy4TestCase(unittest.TestCase):
         Manager.Methods.CreatePool(self._proxy, {
             'name': self._POOLNAME,
             'redundancy': (True, 0),
+            'size_policy': (False, 0),
+            'signature_policy': (False, 0),
             'devices': []
         })
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
@@ -235,8 +255,11 @@ class Destroy4TestCase(unittest.TestCase):
                 'Name': self._POOLNAME
             }).search(managed_objects))
 
-        (result, rc, _) = \
-           Manager.Methods.DestroyPool(self._proxy, {'pool': pool})
+        (result, rc, _, _) = \
+           Manager.Methods.DestroyPool(self._proxy, {
+               'pool': pool,
+               'keep_signatures': False
+           })
 
         self.assertEqual(rc, StratisdErrors.OK)
         self.assertEqual(result, True)
@@ -249,3 +272,125 @@ class Destroy4TestCase(unittest.TestCase):
                     'Name': self._POOLNAME
                 }).search(managed_objects),
                 None))
+
+
+class DestroyKeepSignaturesTestCase(unittest.TestCase):
+    """
+    Test 'destroy' keeping the signatures of the pool's devices.
+    """
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        self._devices = list(set(_device_list(1).example()))
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': self._devices
+            })
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testExecution(self):
+        """
+        The devices of the destroyed pool are reclaimable until each is
+        wiped.
+        """
+        pool_uuid = Pool.Properties.Uuid.Get(
+            get_object(self._pool_object_path))
+        (result, rc, _, _) = Manager.Methods.DestroyPool(
+            self._proxy, {
+                'pool': self._pool_object_path,
+                'keep_signatures': True
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(result)
+
+        (devices, rc, _, _) = Query.Methods.ListReclaimableDevices(
+            self._proxy, {})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(
+            sorted(devnode for (devnode, _, _, _) in devices),
+            sorted(self._devices))
+        self.assertTrue(all(uuid == pool_uuid for (_, uuid, _, _) in devices))
+
+        for devnode in self._devices:
+            (result, rc, _, _) = Manager.Methods.WipeReclaimableDevice(
+                self._proxy, {'devnode': devnode})
+            self.assertEqual(rc, StratisdErrors.OK)
+            self.assertTrue(result)
+
+        (devices, _, _, _) = Query.Methods.ListReclaimableDevices(
+            self._proxy, {})
+        self.assertEqual(devices, [])
+
+        (result, rc, _, _) = Manager.Methods.WipeReclaimableDevice(
+            self._proxy, {'devnode': self._devices[0]})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(result)
+
+    def testUndo(self):
+        """
+        Undoing the destruction sets the pool up again, and its devices are
+        no longer reclaimable.
+        """
+        pool_uuid = Pool.Properties.Uuid.Get(
+            get_object(self._pool_object_path))
+        Manager.Methods.DestroyPool(self._proxy, {
+            'pool': self._pool_object_path,
+            'keep_signatures': True
+        })
+
+        ((is_some, pool_object_path), rc,
+         _, _) = Manager.Methods.UndoDestroyPool(self._proxy,
+                                                 {'pool_uuid': pool_uuid})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(is_some)
+        self.assertEqual(
+            Pool.Properties.Uuid.Get(get_object(pool_object_path)),
+            pool_uuid)
+
+        (devices, _, _, _) = Query.Methods.ListReclaimableDevices(
+            self._proxy, {})
+        self.assertEqual(devices, [])
+
+    def testUndoAfterWipe(self):
+        """
+        The destruction can not be undone once a device of the pool has been
+        wiped.
+        """
+        pool_uuid = Pool.Properties.Uuid.Get(
+            get_object(self._pool_object_path))
+        Manager.Methods.DestroyPool(self._proxy, {
+            'pool': self._pool_object_path,
+            'keep_signatures': True
+        })
+        Manager.Methods.WipeReclaimableDevice(self._proxy,
+                                              {'devnode': self._devices[0]})
+
+        ((is_some, _), rc, _,
+         _) = Manager.Methods.UndoDestroyPool(self._proxy,
+                                              {'pool_uuid': pool_uuid})
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
+        self.assertFalse(is_some)
+
+        managed_objects = \
+           ObjectManager.Methods.GetManagedObjects(self._proxy, {})
+        pool = next(
+            pools(props={
+                'Name': self._POOLNAME
+            }).search(managed_objects),
+            None)
+        self.assertIsNone(pool)
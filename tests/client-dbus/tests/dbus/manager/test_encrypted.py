@@ -0,0 +1,193 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test 'CreateEncryptedPool', 'UnlockPool', 'ListLockedPools',
+'ListBindings', 'BindTang', and 'UnbindTang'.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(1)
+
+
+class EncryptedPoolTestCase(unittest.TestCase):
+    """
+    Test the creation and unlocking of an encrypted pool.
+    """
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _create(self, passphrase):
+        """
+        Create an encrypted pool with the given passphrase.
+
+        :returns: the result of the call
+        """
+        return Manager.Methods.CreateEncryptedPool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example(),
+                'passphrase': passphrase
+            })
+
+    def testCreate(self):
+        """
+        An encrypted pool is created, and devices can not be added to it.
+        """
+        ((pool_object_path, _), rc, _, _) = self._create('secret')
+        self.assertEqual(rc, StratisdErrors.OK)
+
+        pool_object = get_object(pool_object_path)
+        self.assertTrue(Pool.Properties.Encrypted.Get(pool_object))
+
+        (_, rc, _, _) = Pool.Methods.AddDataDevs(
+            pool_object, {
+                'devices': ['/dev/one'],
+                'signature_policy': (False, 0)
+            })
+        self.assertEqual(rc, StratisdErrors.ERROR)
+
+    def testEmptyPassphrase(self):
+        """
+        An empty passphrase is refused.
+        """
+        (_, rc, _, _) = self._create('')
+        self.assertEqual(rc, StratisdErrors.ERROR)
+
+    def testUnlock(self):
+        """
+        A pool that is set up is not locked, and unlocking it does nothing.
+        """
+        ((pool_object_path, _), rc, _, _) = self._create('secret')
+        self.assertEqual(rc, StratisdErrors.OK)
+        pool_uuid = Pool.Properties.Uuid.Get(get_object(pool_object_path))
+
+        (pool_uuids, rc, _, _) = Query.Methods.ListLockedPools(
+            self._proxy, {})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(pool_uuids, [])
+
+        ((unlocked, _), rc, _, _) = Manager.Methods.UnlockPool(
+            self._proxy, {
+                'pool_uuid': pool_uuid,
+                'passphrase': 'secret'
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(unlocked)
+
+    def testBindings(self):
+        """
+        An encrypted pool is unlocked, and shows the key kept for it, both in
+        its bindings and in its KeyDescription property.
+        """
+        ((pool_object_path, _), rc, _, _) = self._create('secret')
+        self.assertEqual(rc, StratisdErrors.OK)
+        pool_object = get_object(pool_object_path)
+        pool_uuid = Pool.Properties.Uuid.Get(pool_object)
+
+        ((unlocked, key, pins, (used, total)), rc, _,
+         _) = Query.Methods.ListBindings(self._proxy,
+                                         {'pool_uuid': pool_uuid})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(unlocked)
+        self.assertEqual(key, (False, ''))
+        self.assertEqual(pins, [])
+        self.assertTrue(0 < used <= total)
+
+        (_, rc, _, _) = Manager.Methods.SetKey(self._proxy, {
+            'pool_uuid': pool_uuid,
+            'passphrase': 'secret'
+        })
+        self.assertEqual(rc, StratisdErrors.OK)
+        ((_, key, _, _), rc, _, _) = Query.Methods.ListBindings(
+            self._proxy, {'pool_uuid': pool_uuid})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(key[0])
+        self.assertEqual(Pool.Properties.KeyDescription.Get(pool_object), key)
+
+        (_, rc, _, _) = Query.Methods.ListBindings(
+            self._proxy, {'pool_uuid': '0123456789abcdef0123456789abcdef'})
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
+
+    def testBindTang(self):
+        """
+        An encrypted pool is bound to a Tang server just once, and shows the
+        binding until it is unbound.
+        """
+        ((pool_object_path, _), rc, _, _) = self._create('secret')
+        self.assertEqual(rc, StratisdErrors.OK)
+        pool_uuid = Pool.Properties.Uuid.Get(get_object(pool_object_path))
+
+        def bind(url):
+            """
+            Bind the pool to the Tang server at url.
+            """
+            return Manager.Methods.BindTang(
+                self._proxy, {
+                    'pool_uuid': pool_uuid,
+                    'passphrase': 'secret',
+                    'url': url,
+                    'thumbprint': 'thumbprint'
+                })
+
+        (_, rc, _, _) = bind('')
+        self.assertEqual(rc, StratisdErrors.ERROR)
+
+        (bound, rc, _, _) = bind('http://tang')
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(bound)
+        (bound, rc, _, _) = bind('http://tang')
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(bound)
+
+        ((_, _, pins, _), rc, _, _) = Query.Methods.ListBindings(
+            self._proxy, {'pool_uuid': pool_uuid})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(pins, ['tang'])
+
+        (unbound, rc, _, _) = Manager.Methods.UnbindTang(
+            self._proxy, {'pool_uuid': pool_uuid})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(unbound)
+        ((_, _, pins, _), rc, _, _) = Query.Methods.ListBindings(
+            self._proxy, {'pool_uuid': pool_uuid})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(pins, [])
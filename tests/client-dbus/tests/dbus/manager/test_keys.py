@@ -0,0 +1,111 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test 'SetKey', 'UnsetKey', and 'ListKeys'.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+
+class KeysTestCase(unittest.TestCase):
+    """
+    Test the keys kept to unlock encrypted pools.
+    """
+    _POOL_UUID = '0123456789abcdef0123456789abcdef'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _set(self, passphrase):
+        """
+        Set the key of the pool.
+
+        :returns: the result of the call
+        """
+        return Manager.Methods.SetKey(self._proxy, {
+            'pool_uuid': self._POOL_UUID,
+            'passphrase': passphrase
+        })
+
+    def _list(self):
+        """
+        List the pools for which a key is kept.
+        """
+        (pool_uuids, rc, _, _) = Query.Methods.ListKeys(self._proxy, {})
+        self.assertEqual(rc, StratisdErrors.OK)
+        return pool_uuids
+
+    def testSetAndUnset(self):
+        """
+        A key is set, changed, and unset, and listed only while it is set.
+        """
+        self.assertEqual(self._list(), [])
+
+        (changed, rc, _, _) = self._set('secret')
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(self._list(), [self._POOL_UUID])
+
+        (changed, rc, _, _) = self._set('secret')
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(changed)
+
+        (changed, rc, _, _) = self._set('other')
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+
+        for expected in (True, False):
+            (unset, rc, _, _) = Manager.Methods.UnsetKey(
+                self._proxy, {'pool_uuid': self._POOL_UUID})
+            self.assertEqual(rc, StratisdErrors.OK)
+            self.assertEqual(unset, expected)
+        self.assertEqual(self._list(), [])
+
+    def testEmptyPassphrase(self):
+        """
+        An empty passphrase is refused.
+        """
+        (_, rc, _, _) = self._set('')
+        self.assertEqual(rc, StratisdErrors.ERROR)
+        self.assertEqual(self._list(), [])
+
+    def testBadUuid(self):
+        """
+        A key can not be set for a pool UUID that is not valid.
+        """
+        (_, rc, _, _) = Manager.Methods.SetKey(self._proxy, {
+            'pool_uuid': 'not a uuid',
+            'passphrase': 'secret'
+        })
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
@@ -0,0 +1,187 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test ListFilesystems and ListFilesystemsPage.
+"""
+
+import unittest
+import uuid
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+
+class ListFilesystemsTestCase(unittest.TestCase):
+    """
+    Test listing the filesystems of two pools.
+    """
+
+    _FSNAMES = ['home', 'home-backup', 'var']
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator, and make two pools,
+        only the first of which has filesystems.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        for (name, device) in [('first', '/dev/one'), ('second', '/dev/two')]:
+            ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
+                self._proxy, {
+                    'name': name,
+                    'redundancy': (True, 0),
+                    'size_policy': (False, 0),
+                    'signature_policy': (False, 0),
+                    'devices': [device]
+                })
+            if name == 'first':
+                Pool.Methods.CreateFilesystems(
+                    get_object(poolpath), {
+                        'specs': self._FSNAMES,
+                        'size': (False, 0),
+                        'guaranteed': False
+                    })
+        Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _list(self, pool_uuid=None, name=None, created_since=None):
+        """
+        List the filesystems selected by the given criteria.
+        """
+        (filesystems, rc, _, _) = Query.Methods.ListFilesystems(
+            self._proxy, {
+                'pool_uuid': (pool_uuid is not None, pool_uuid or ''),
+                'name': (name is not None, name or ''),
+                'created_since': (created_since is not None, created_since
+                                  or '')
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+        return filesystems
+
+    def testListAll(self):
+        """
+        With no criteria, every filesystem is listed, with its pool.
+        """
+        filesystems = self._list()
+        self.assertEqual(
+            sorted(name for (_, _, _, name, _, _) in filesystems),
+            self._FSNAMES)
+        self.assertEqual(
+            set(pool_name for (_, pool_name, _, _, _, _) in filesystems),
+            set(['first']))
+
+    def testListByPool(self):
+        """
+        Only the filesystems of the given pool are listed.
+        """
+        pool_uuid = self._list()[0][0]
+        self.assertEqual(len(self._list(pool_uuid=pool_uuid)), 3)
+        self.assertEqual(self._list(pool_uuid=uuid.uuid4().hex), [])
+
+    def testListByName(self):
+        """
+        Only the filesystems whose names match the glob are listed.
+        """
+        filesystems = self._list(name='home*')
+        self.assertEqual(
+            sorted(name for (_, _, _, name, _, _) in filesystems),
+            ['home', 'home-backup'])
+        self.assertEqual(self._list(name='?ar'), self._list(name='var'))
+
+    def testListCreatedSince(self):
+        """
+        Only filesystems created at or after the given time are listed.
+        """
+        self.assertEqual(len(self._list(created_since='2000-01-01T00:00:00Z')),
+                         3)
+        self.assertEqual(self._list(created_since='9999-01-01T00:00:00Z'), [])
+
+    def testInvalidCriteria(self):
+        """
+        An invalid pool UUID or time is an error.
+        """
+        (_, rc, _, _) = Query.Methods.ListFilesystems(
+            self._proxy, {
+                'pool_uuid': (True, 'not a uuid'),
+                'name': (False, ''),
+                'created_since': (False, '')
+            })
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
+
+        (_, rc, _, _) = Query.Methods.ListFilesystems(
+            self._proxy, {
+                'pool_uuid': (False, ''),
+                'name': (False, ''),
+                'created_since': (True, 'yesterday')
+            })
+        self.assertEqual(rc, StratisdErrors.ERROR)
+
+    def _page(self, cursor, limit):
+        """
+        List a page of all filesystems.
+        """
+        ((filesystems, next_cursor), rc, _, _) = \
+            Query.Methods.ListFilesystemsPage(
+                self._proxy, {
+                    'pool_uuid': (False, ''),
+                    'name': (False, ''),
+                    'created_since': (False, ''),
+                    'cursor': cursor,
+                    'limit': limit
+                })
+        self.assertEqual(rc, StratisdErrors.OK)
+        return (filesystems, next_cursor)
+
+    def testListPages(self):
+        """
+        Paging through the filesystems lists each once, in a stable order.
+        """
+        listed = []
+        cursor = (False, '')
+        while True:
+            (filesystems, cursor) = self._page(cursor, 2)
+            self.assertLessEqual(len(filesystems), 2)
+            listed.extend(filesystems)
+            if not cursor[0]:
+                break
+        self.assertEqual(
+            sorted(name for (_, _, _, name, _, _) in listed), self._FSNAMES)
+        self.assertEqual(listed, self._page((False, ''), 3)[0])
+
+    def testInvalidCursor(self):
+        """
+        An invalid cursor is an error.
+        """
+        (_, rc, _, _) = Query.Methods.ListFilesystemsPage(
+            self._proxy, {
+                'pool_uuid': (False, ''),
+                'name': (False, ''),
+                'created_since': (False, ''),
+                'cursor': (True, 'not a cursor'),
+                'limit': 2
+            })
+        self.assertEqual(rc, StratisdErrors.ERROR)
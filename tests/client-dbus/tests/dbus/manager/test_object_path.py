@@ -20,10 +20,16 @@ import dbus
 
 from dbus_python_client_gen import DPClientInvocationError
 
+from stratisd_client_dbus import Filesystem
 from stratisd_client_dbus import Manager
+from stratisd_client_dbus import MOBlockDev
 from stratisd_client_dbus import ObjectManager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import blockdevs
 from stratisd_client_dbus import get_object
 
+from stratisd_client_dbus._constants import TOP_OBJECT
+
 from .._misc import _device_list
 from .._misc import Service
 
@@ -75,3 +81,58 @@ class GetObjectTestCase(unittest.TestCase):
         """
         with self.assertRaises(ValueError):
             get_object('abc')
+
+
+class ObjectPathTestCase(unittest.TestCase):
+    """
+    Test that object paths are derived from UUIDs.
+    """
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testPathsFromUuids(self):
+        """
+        The path of each pool, filesystem, and blockdev is made from the
+        kind of object and its UUID.
+        """
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': 'pool',
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        pool = get_object(poolpath)
+        self.assertEqual(poolpath, '%s/pool/%s' %
+                         (TOP_OBJECT, Pool.Properties.Uuid.Get(pool)))
+
+        (result, _, _, _) = Pool.Methods.CreateFilesystems(
+            pool, {
+                'specs': ['fs'],
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        for (fspath, _) in result:
+            self.assertEqual(fspath, '%s/filesystem/%s' %
+                             (TOP_OBJECT,
+                              Filesystem.Properties.Uuid.Get(
+                                  get_object(fspath))))
+
+        managed_objects = ObjectManager.Methods.GetManagedObjects(
+            self._proxy, {})
+        for (devpath, info) in blockdevs().search(managed_objects):
+            self.assertEqual(devpath, '%s/blockdev/%s' %
+                             (TOP_OBJECT, MOBlockDev(info).Uuid()))
@@ -0,0 +1,92 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test PlanDestroyPool.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(1)
+
+
+class PlanDestroyPoolTestCase(unittest.TestCase):
+    """
+    Set up a pool with some devices and a filesystem.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        self._devices = _DEVICE_STRATEGY.example()
+        ((pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': self._devices
+            })
+        self._pool = get_object(pool_object_path)
+        Pool.Methods.CreateFilesystems(self._pool, {
+            'specs': ['fs'],
+            'size': (False, 0),
+            'guaranteed': False
+        })
+
+    def tearDown(self):
+        """
+        Stop the stratisd daemon and simulator.
+        """
+        self._service.tearDown()
+
+    def testPlan(self):
+        """
+        The plan lists the filesystem, every device, and the links to the
+        filesystem and the pool, and the pool is left in place.
+        """
+        pool_uuid = Pool.Properties.Uuid.Get(self._pool)
+        ((filesystems, devices, devlinks), rc,
+         _, _) = Query.Methods.PlanDestroyPool(self._proxy,
+                                               {'pool_uuid': pool_uuid})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual([name for (_, name, _) in filesystems], ['fs'])
+        self.assertEqual(sorted(devices), sorted(set(self._devices)))
+        self.assertEqual(devlinks,
+                         ['/stratis/deadpool/fs', '/stratis/deadpool'])
+        self.assertEqual(Pool.Properties.Uuid.Get(self._pool), pool_uuid)
+
+    def testUnknownPool(self):
+        """
+        Planning the destruction of a pool that does not exist fails.
+        """
+        (_, rc, _, _) = Query.Methods.PlanDestroyPool(
+            self._proxy, {'pool_uuid': 'not a uuid'})
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
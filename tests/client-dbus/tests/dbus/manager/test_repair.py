@@ -0,0 +1,72 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test RepairPool.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+
+class RepairPoolTestCase(unittest.TestCase):
+    """
+    Test repairing a pool.
+    """
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testBogusObjectPath(self):
+        """
+        Repairing a pool that does not exist fails.
+        """
+        (repaired, rc, _, _) = Manager.Methods.RepairPool(
+            self._proxy, {'pool': "/"})
+        self.assertEqual(rc, StratisdErrors.NOTFOUND)
+        self.assertFalse(repaired)
+
+    def testNoRepairNeeded(self):
+        """
+        A simulated pool never needs a check, so it is not repaired.
+        """
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': 'deadpool',
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': ['/dev/one', '/dev/two']
+            })
+        (repaired, rc, _, _) = Manager.Methods.RepairPool(
+            self._proxy, {'pool': poolpath})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(repaired)
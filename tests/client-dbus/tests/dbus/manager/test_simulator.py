@@ -0,0 +1,122 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test 'ConfigureSimulatorCapacities' and 'AdvanceSimulatorClock'.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Filesystem
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+_GIB = 2**30
+_MIB = 2**20
+
+
+class SimulatorCapacitiesTestCase(unittest.TestCase):
+    """
+    Test the capacities that the simulator models.
+    """
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _create_pool(self):
+        """
+        Create a pool of two devices with one filesystem.
+
+        :returns: the pool object and the filesystem object
+        """
+        ((pool_object_path, _), rc, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': 'deadpool',
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': ['/dev/one', '/dev/two']
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+        pool_object = get_object(pool_object_path)
+        (created, rc, _, _) = Pool.Methods.CreateFilesystems(
+            pool_object, {
+                'specs': ['fs'],
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+        return (pool_object, get_object(created[0][0]))
+
+    def testCapacities(self):
+        """
+        The pool is as big as its devices, and the space its filesystem
+        uses grows with the simulated clock until the filesystem is full.
+        """
+        (rc, _, _) = Manager.Methods.ConfigureSimulatorCapacities(
+            self._proxy, {
+                'device_size': (True, 4 * _GIB),
+                'filesystem_size': (True, _GIB),
+                'initial_used': (True, 0),
+                'growth': (True, _MIB)
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+
+        (pool_object, fs_object) = self._create_pool()
+        self.assertEqual(
+            Pool.Properties.TotalPhysicalSize.Get(pool_object),
+            str(8 * _GIB))
+        self.assertEqual(Filesystem.Properties.Used.Get(fs_object), '0')
+
+        (rc, _, _) = Manager.Methods.AdvanceSimulatorClock(
+            self._proxy, {'seconds': 512})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(
+            Filesystem.Properties.Used.Get(fs_object), str(512 * _MIB))
+        self.assertEqual(
+            Pool.Properties.TotalPhysicalUsed.Get(pool_object),
+            str(512 * _MIB))
+
+        Manager.Methods.AdvanceSimulatorClock(self._proxy, {'seconds': 1024})
+        self.assertEqual(
+            Filesystem.Properties.Used.Get(fs_object), str(_GIB))
+
+    def testZeroSize(self):
+        """
+        A device or filesystem size of 0 is refused.
+        """
+        (rc, _, _) = Manager.Methods.ConfigureSimulatorCapacities(
+            self._proxy, {
+                'device_size': (True, 0),
+                'filesystem_size': (False, 0),
+                'initial_used': (False, 0),
+                'growth': (False, 0)
+            })
+        self.assertNotEqual(rc, StratisdErrors.OK)
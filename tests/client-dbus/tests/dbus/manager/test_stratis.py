@@ -21,6 +21,7 @@ from dbus_python_client_gen import DPClientInvalidArgError
 
 from stratisd_client_dbus import Manager
 from stratisd_client_dbus import ObjectManager
+from stratisd_client_dbus import StratisdErrors
 from stratisd_client_dbus import get_object
 
 from stratisd_client_dbus._constants import TOP_OBJECT
@@ -58,6 +59,39 @@ class StratisTestCase(unittest.TestCase):
         (major, _, _) = version.split(".")
         self.assertEqual(major, "1")
 
+    def testMaxFormatVersions(self):
+        """
+        Every supported format version should be at least 1.
+        """
+        versions = Manager.Properties.MaxFormatVersions.Get(
+            get_object(TOP_OBJECT))
+        self.assertEqual(len(versions), 3)
+        self.assertTrue(all(version >= 1 for version in versions))
+
+    def testEngineCapabilities(self):
+        """
+        The simulator reports every capability this build implements.
+        """
+        capabilities = Manager.Properties.EngineCapabilities.Get(
+            get_object(TOP_OBJECT))
+        self.assertEqual(list(capabilities), ['writecache', 'raid1'])
+
+    def testStatistics(self):
+        """
+        Every counter is reported, and an error returned to a client is
+        counted.
+        """
+        proxy = get_object(TOP_OBJECT)
+        before = Manager.Properties.Statistics.Get(proxy)
+        self.assertEqual(
+            sorted(before.keys()),
+            ['errors', 'jobs_completed', 'metadata_saves', 'repairs'])
+
+        (_, rc, _, _) = Manager.Methods.GetJob(proxy, {'job': 0})
+        self.assertEqual(rc, StratisdErrors.NOTFOUND)
+        after = Manager.Properties.Statistics.Get(proxy)
+        self.assertEqual(after['errors'], before['errors'] + 1)
+
 
 class StratisTestCase2(unittest.TestCase):
     """
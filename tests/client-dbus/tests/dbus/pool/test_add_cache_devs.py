@@ -47,10 +47,12 @@ class AddCacheDevsTestCase1(unittest.TestCase):
         self._service = Service()
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
-        ((poolpath, _), _, _) = Manager.Methods.CreatePool(
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': []
             })
         self._pool_object = get_object(poolpath)
@@ -73,8 +75,13 @@ class AddCacheDevsTestCase1(unittest.TestCase):
                 'Name': self._POOLNAME
             }).search(managed_objects))
 
-        (result, rc, _) = Pool.Methods.AddCacheDevs(self._pool_object,
-                                                    {'devices': []})
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': [],
+                'signature_policy': (False, 0),
+                'cache_type': (False, 0),
+                'volatile': False
+            })
 
         self.assertEqual(len(result), 0)
         self.assertEqual(rc, StratisdErrors.OK)
@@ -98,8 +105,13 @@ class AddCacheDevsTestCase1(unittest.TestCase):
                 'Name': self._POOLNAME
             }).search(managed_objects))
 
-        (result, rc, _) = Pool.Methods.AddCacheDevs(
-            self._pool_object, {'devices': _DEVICE_STRATEGY.example()})
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': _DEVICE_STRATEGY.example(),
+                'signature_policy': (False, 0),
+                'cache_type': (False, 0),
+                'volatile': False
+            })
 
         num_devices_added = len(result)
         managed_objects = \
@@ -149,10 +161,12 @@ class AddCacheDevsTestCase2(unittest.TestCase):
         self._service = Service()
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
-        ((poolpath, devpaths), _, _) = Manager.Methods.CreatePool(
+        ((poolpath, devpaths), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': _DEVICE_STRATEGY.example()
             })
         self._pool_object = get_object(poolpath)
@@ -188,8 +202,13 @@ class AddCacheDevsTestCase2(unittest.TestCase):
         }).search(managed_objects)
         self.assertEqual(list(blockdevs2), [])
 
-        (result, rc, _) = Pool.Methods.AddCacheDevs(self._pool_object,
-                                                    {'devices': []})
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': [],
+                'signature_policy': (False, 0),
+                'cache_type': (False, 0),
+                'volatile': False
+            })
 
         self.assertEqual(len(result), 0)
         self.assertEqual(rc, StratisdErrors.OK)
@@ -217,8 +236,13 @@ class AddCacheDevsTestCase2(unittest.TestCase):
         }).search(managed_objects)
         self.assertEqual(self._devpaths, frozenset(
             op for (op, _) in blockdevs1))
-        (result, rc, _) = Pool.Methods.AddCacheDevs(
-            self._pool_object, {'devices': _DEVICE_STRATEGY.example()})
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': _DEVICE_STRATEGY.example(),
+                'signature_policy': (False, 0),
+                'cache_type': (False, 0),
+                'volatile': False
+            })
 
         num_devices_added = len(result)
         managed_objects = \
@@ -255,3 +279,121 @@ class AddCacheDevsTestCase2(unittest.TestCase):
         }).search(managed_objects)
         self.assertEqual(
             frozenset(op for (op, _) in blockdevs5), self._devpaths)
+
+
+class AddCacheDevsTestCase3(unittest.TestCase):
+    """
+    Test the cache type with which a pool's cache is made.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': []
+            })
+        self._pool_object = get_object(poolpath)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testWriteCache(self):
+        """
+        A pool without a cache has no cache type. Once a writecache is made,
+        it can not be extended, nor can cache devices of another cache type
+        be added.
+        """
+        self.assertEqual(
+            Pool.Properties.CacheType.Get(self._pool_object), (False, 0))
+        self.assertFalse(
+            Pool.Properties.CacheUsage.Get(self._pool_object)[0])
+
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': ['/dev/one'],
+                'signature_policy': (False, 0),
+                'cache_type': (True, 1),
+                'volatile': False
+            })
+        self.assertEqual(len(result), 1)
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(
+            Pool.Properties.CacheType.Get(self._pool_object), (True, 1))
+        self.assertTrue(Pool.Properties.CacheUsage.Get(self._pool_object)[0])
+
+        for cache_type in [(True, 0), (True, 1), (False, 0)]:
+            (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+                self._pool_object, {
+                    'devices': ['/dev/two'],
+                    'signature_policy': (False, 0),
+                    'cache_type': cache_type,
+                    'volatile': False
+                })
+            self.assertEqual(len(result), 0)
+            self.assertEqual(rc, StratisdErrors.ERROR)
+
+    def testUnsupportedCacheType(self):
+        """
+        A cache type code that corresponds to no cache type is an error.
+        """
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': ['/dev/one'],
+                'signature_policy': (False, 0),
+                'cache_type': (True, 2),
+                'volatile': False
+            })
+        self.assertEqual(len(result), 0)
+        self.assertNotEqual(rc, StratisdErrors.OK)
+
+    def testVolatileCache(self):
+        """
+        A volatile cache is flagged as such, and can not be made with the
+        writecache target, nor extended with devices that are not volatile.
+        """
+        self.assertFalse(Pool.Properties.CacheVolatile.Get(self._pool_object))
+
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': ['/dev/one'],
+                'signature_policy': (False, 0),
+                'cache_type': (True, 1),
+                'volatile': True
+            })
+        self.assertEqual(len(result), 0)
+        self.assertEqual(rc, StratisdErrors.ERROR)
+
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': ['/dev/one'],
+                'signature_policy': (False, 0),
+                'cache_type': (False, 0),
+                'volatile': True
+            })
+        self.assertEqual(len(result), 1)
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(Pool.Properties.CacheVolatile.Get(self._pool_object))
+
+        (result, rc, _, _) = Pool.Methods.AddCacheDevs(
+            self._pool_object, {
+                'devices': ['/dev/two'],
+                'signature_policy': (False, 0),
+                'cache_type': (False, 0),
+                'volatile': False
+            })
+        self.assertEqual(len(result), 0)
+        self.assertEqual(rc, StratisdErrors.ERROR)
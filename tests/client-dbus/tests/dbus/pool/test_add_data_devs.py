@@ -47,10 +47,12 @@ class AddDataDevsTestCase(unittest.TestCase):
         self._service = Service()
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
-        ((poolpath, _), _, _) = Manager.Methods.CreatePool(
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': []
             })
         self._pool_object = get_object(poolpath)
@@ -76,8 +78,11 @@ class AddDataDevsTestCase(unittest.TestCase):
         blockdevs1 = blockdevs(props={'Pool': pool}).search(managed_objects)
         self.assertEqual(list(blockdevs1), [])
 
-        (result, rc, _) = Pool.Methods.AddDataDevs(self._pool_object,
-                                                   {'devices': []})
+        (result, rc, _, _) = Pool.Methods.AddDataDevs(
+            self._pool_object, {
+                'devices': [],
+                'signature_policy': (False, 0)
+            })
 
         self.assertEqual(result, [])
         self.assertEqual(rc, StratisdErrors.OK)
@@ -105,8 +110,11 @@ class AddDataDevsTestCase(unittest.TestCase):
         blockdevs1 = blockdevs(props={'Pool': pool}).search(managed_objects)
         self.assertEqual(list(blockdevs1), [])
 
-        (result, rc, _) = Pool.Methods.AddDataDevs(
-            self._pool_object, {'devices': _DEVICE_STRATEGY.example()})
+        (result, rc, _, _) = Pool.Methods.AddDataDevs(
+            self._pool_object, {
+                'devices': _DEVICE_STRATEGY.example(),
+                'signature_policy': (False, 0)
+            })
 
         num_devices_added = len(result)
         managed_objects = \
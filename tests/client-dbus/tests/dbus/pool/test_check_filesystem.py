@@ -0,0 +1,94 @@
+# Copyright 2018 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test checking a filesystem.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class CheckFilesystemTestCase(unittest.TestCase):
+    """
+    Test with a pool with one filesystem.
+    """
+
+    _POOLNAME = 'deadpool'
+    _VOLNAME = 'some_fs'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool_object = get_object(poolpath)
+        (fs_objects, _, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [self._VOLNAME],
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        self._fs_object_path = fs_objects[0][0]
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testCheck(self):
+        """
+        Checking the filesystem finds it consistent.
+        """
+        ((clean, _), rc, _, _) = Pool.Methods.CheckFilesystem(
+            self._pool_object, {
+                'filesystem': self._fs_object_path,
+                'repair': False
+            })
+
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(clean)
+
+    def testCheckUnknown(self):
+        """
+        Checking an object that is not a filesystem fails.
+        """
+        (_, rc, _, _) = Pool.Methods.CheckFilesystem(
+            self._pool_object, {
+                'filesystem': '/org/storage/stratis1/9999',
+                'repair': False
+            })
+
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
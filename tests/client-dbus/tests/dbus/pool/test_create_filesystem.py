@@ -47,10 +47,12 @@ class CreateFSTestCase(unittest.TestCase):
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
         self._devs = _DEVICE_STRATEGY.example()
-        ((poolpath, _), _, _) = Manager.Methods.CreatePool(
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': self._devs
             })
         self._pool_object = get_object(poolpath)
@@ -68,8 +70,12 @@ class CreateFSTestCase(unittest.TestCase):
         list should always succeed, and it should not increase the
         number of volumes.
         """
-        (result, rc, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': []})
+        (result, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [],
+                'size': (False, 0),
+                'guaranteed': False
+            })
 
         self.assertEqual(len(result), 0)
         self.assertEqual(rc, StratisdErrors.OK)
@@ -78,6 +84,62 @@ class CreateFSTestCase(unittest.TestCase):
             ObjectManager.Methods.GetManagedObjects(self._proxy, {}))
         self.assertEqual(len([x for x in result]), 0)
 
+    def testCreateBeyondCap(self):
+        """
+        Test calling with a size that would take the pool beyond its
+        overcommit cap. The filesystem should not be created.
+        """
+        Pool.Methods.SetOvercommitCap(self._pool_object, {'cap': (True, 100)})
+
+        (result, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': ['name'],
+                'size': (True, 2**62),
+                'guaranteed': False
+            })
+
+        self.assertNotEqual(rc, StratisdErrors.OK)
+        self.assertEqual(len(result), 0)
+
+        result = filesystems().search(
+            ObjectManager.Methods.GetManagedObjects(self._proxy, {}))
+        self.assertEqual(len([x for x in result]), 0)
+
+    def testSetDefaultSize(self):
+        """
+        Test setting the pool's default filesystem size and restoring the
+        built-in default.
+        """
+        original = Pool.Properties.DefaultFilesystemSize.Get(self._pool_object)
+
+        (changed, rc, _, _) = Pool.Methods.SetDefaultFilesystemSize(
+            self._pool_object, {'size': (True, 2**21)})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(
+            Pool.Properties.DefaultFilesystemSize.Get(self._pool_object),
+            str(2**21))
+
+        (changed, rc, _, _) = Pool.Methods.SetDefaultFilesystemSize(
+            self._pool_object, {'size': (False, 0)})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(
+            Pool.Properties.DefaultFilesystemSize.Get(self._pool_object),
+            original)
+
+    def testDefaultSizeInBytes(self):
+        """
+        Test that a size is given in bytes and rounded up to a whole sector.
+        """
+        (changed, rc, _, _) = Pool.Methods.SetDefaultFilesystemSize(
+            self._pool_object, {'size': (True, 1000)})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(
+            Pool.Properties.DefaultFilesystemSize.Get(self._pool_object),
+            str(1024))
+
     @unittest.skip("skip until creating multiple filesystems is supported")
     def testDuplicateSpecs(self):
         """
@@ -85,8 +147,12 @@ class CreateFSTestCase(unittest.TestCase):
         """
         new_name = "name"
 
-        (result, rc, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': [new_name, new_name]})
+        (result, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [new_name, new_name],
+                'size': (False, 0),
+                'guaranteed': False
+            })
 
         self.assertEqual(rc, StratisdErrors.OK)
         self.assertEqual(len(result), 1)
@@ -115,15 +181,20 @@ class CreateFSTestCase1(unittest.TestCase):
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
         self._devs = _DEVICE_STRATEGY.example()
-        ((poolpath, _), _, _) = Manager.Methods.CreatePool(
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': self._devs
             })
         self._pool_object = get_object(poolpath)
-        Pool.Methods.CreateFilesystems(self._pool_object,
-                                       {'specs': [self._VOLNAME]})
+        Pool.Methods.CreateFilesystems(self._pool_object, {
+            'specs': [self._VOLNAME],
+            'size': (False, 0),
+            'guaranteed': False
+        })
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
 
     def tearDown(self):
@@ -138,8 +209,12 @@ class CreateFSTestCase1(unittest.TestCase):
         a volume with the given name, the creation of the new volume should
         fail, and no additional volume should be created.
         """
-        (result, rc, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': [self._VOLNAME]})
+        (result, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [self._VOLNAME],
+                'size': (False, 0),
+                'guaranteed': False
+            })
 
         self.assertEqual(rc, StratisdErrors.ALREADY_EXISTS)
         self.assertEqual(len(result), 0)
@@ -155,8 +230,12 @@ class CreateFSTestCase1(unittest.TestCase):
         """
         new_name = "newname"
 
-        (result, rc, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': [new_name]})
+        (result, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [new_name],
+                'size': (False, 0),
+                'guaranteed': False
+            })
 
         self.assertEqual(rc, StratisdErrors.OK)
         self.assertEqual(len(result), 1)
@@ -175,8 +254,12 @@ class CreateFSTestCase1(unittest.TestCase):
         a volume with the given name, the creation of the new volumes should
         fail, and no additional volume should be created.
         """
-        (result, rc, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': [self._VOLNAME, "newname"]})
+        (result, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [self._VOLNAME, "newname"],
+                'size': (False, 0),
+                'guaranteed': False
+            })
 
         self.assertEqual(rc, StratisdErrors.ALREADY_EXISTS)
         self.assertEqual(len(result), 0)
@@ -191,8 +274,12 @@ class CreateFSTestCase1(unittest.TestCase):
         volume names are not supported due to possible d-bus timeouts.  When
         multiple volume support is added back - this test should be removed.
         """
-        (result, rc, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': ["a", "b"]})
+        (result, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': ["a", "b"],
+                'size': (False, 0),
+                'guaranteed': False
+            })
 
         self.assertEqual(rc, StratisdErrors.ERROR)
         self.assertEqual(len(result), 0)
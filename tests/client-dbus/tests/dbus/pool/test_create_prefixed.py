@@ -0,0 +1,126 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test creating numbered filesystems from a prefix.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import ObjectManager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import filesystems
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class CreatePrefixedTestCase(unittest.TestCase):
+    """
+    Set up a pool with one filesystem, named as if made from a prefix.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool_object = get_object(poolpath)
+        Pool.Methods.CreateFilesystems(self._pool_object, {
+            'specs': ['fs-002'],
+            'size': (False, 0),
+            'guaranteed': False
+        })
+        Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _count(self):
+        """
+        The number of filesystems.
+        """
+        result = filesystems().search(
+            ObjectManager.Methods.GetManagedObjects(self._proxy, {}))
+        return len([x for x in result])
+
+    def testCreate(self):
+        """
+        The filesystems are numbered in order, skipping the name in use.
+        """
+        (result, rc, _, _) = Pool.Methods.CreatePrefixedFilesystems(
+            self._pool_object, {
+                'prefix': 'fs',
+                'count': 3,
+                'size': (False, 0),
+                'guaranteed': False
+            })
+
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual([name for (_, name) in result],
+                         ['fs-001', 'fs-003', 'fs-004'])
+        self.assertEqual(self._count(), 4)
+
+    def testBeyondLimit(self):
+        """
+        No filesystem is created if not all of them can be.
+        """
+        Pool.Methods.SetFsLimit(self._pool_object, {'limit': 2})
+
+        (result, rc, _, _) = Pool.Methods.CreatePrefixedFilesystems(
+            self._pool_object, {
+                'prefix': 'fs',
+                'count': 2,
+                'size': (False, 0),
+                'guaranteed': False
+            })
+
+        self.assertNotEqual(rc, StratisdErrors.OK)
+        self.assertEqual(len(result), 0)
+        self.assertEqual(self._count(), 1)
+
+    def testNoCount(self):
+        """
+        A count of 0 is refused.
+        """
+        (_, rc, _, _) = Pool.Methods.CreatePrefixedFilesystems(
+            self._pool_object, {
+                'prefix': 'fs',
+                'count': 0,
+                'size': (False, 0),
+                'guaranteed': False
+            })
+
+        self.assertEqual(rc, StratisdErrors.ERROR)
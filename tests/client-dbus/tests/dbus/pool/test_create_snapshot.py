@@ -49,17 +49,23 @@ class CreateSnapshotTestCase(unittest.TestCase):
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
         self._devs = _DEVICE_STRATEGY.example()
-        ((poolpath, _), _, _) = Manager.Methods.CreatePool(
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': self._devs
             })
         self._pool_object = get_object(poolpath)
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
 
-        (fs_objects, rc, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': [self._VOLNAME]})
+        (fs_objects, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [self._VOLNAME],
+                'size': (False, 0),
+                'guaranteed': False
+            })
 
         self.assertEqual(rc, StratisdErrors.OK)
 
@@ -79,10 +85,31 @@ class CreateSnapshotTestCase(unittest.TestCase):
         Test creating a snapshot and ensure that it works.
         """
 
-        (ss_object_path, rc, _) = Pool.Methods.SnapshotFilesystem(
+        (ss_object_path, rc, _, _) = Pool.Methods.SnapshotFilesystem(
+            self._pool_object, {
+                'origin': self._fs_object_path,
+                'snapshot_name': self._SNAPSHOTNAME,
+                'freeze': False
+            })
+
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertNotEqual(ss_object_path, "/")
+
+        result = filesystems().search(
+            ObjectManager.Methods.GetManagedObjects(self._proxy, {}))
+        self.assertEqual(len([x for x in result]), 2)
+
+    def testCreateFrozen(self):
+        """
+        Test creating a snapshot, freezing the origin. The origin is not
+        mounted, so there is nothing to freeze, but the snapshot is made.
+        """
+
+        (ss_object_path, rc, _, _) = Pool.Methods.SnapshotFilesystem(
             self._pool_object, {
                 'origin': self._fs_object_path,
-                'snapshot_name': self._SNAPSHOTNAME
+                'snapshot_name': self._SNAPSHOTNAME,
+                'freeze': True
             })
 
         self.assertEqual(rc, StratisdErrors.OK)
@@ -97,19 +124,21 @@ class CreateSnapshotTestCase(unittest.TestCase):
         Test creating a snapshot with duplicate name.
         """
 
-        (ss_object_path, rc, _) = Pool.Methods.SnapshotFilesystem(
+        (ss_object_path, rc, _, _) = Pool.Methods.SnapshotFilesystem(
             self._pool_object, {
                 'origin': self._fs_object_path,
-                'snapshot_name': self._SNAPSHOTNAME
+                'snapshot_name': self._SNAPSHOTNAME,
+                'freeze': False
             })
 
         self.assertEqual(rc, StratisdErrors.OK)
         self.assertNotEqual(ss_object_path, "/")
 
-        (ss_object_path_dupe_name, rc, _) = Pool.Methods.SnapshotFilesystem(
+        (ss_object_path_dupe_name, rc, _, _) = Pool.Methods.SnapshotFilesystem(
             self._pool_object, {
                 'origin': self._fs_object_path,
-                'snapshot_name': self._SNAPSHOTNAME
+                'snapshot_name': self._SNAPSHOTNAME,
+                'freeze': False
             })
 
         self.assertEqual(rc, StratisdErrors.ALREADY_EXISTS)
@@ -0,0 +1,86 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test the properties that report the parameters with which a pool was
+created.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class CreationParamsTestCase(unittest.TestCase):
+    """
+    Set up a pool.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool_object = get_object(self._pool_object_path)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testCreationParams(self):
+        """
+        The parameters of the pool are those with which it was created, by
+        this version of stratisd.
+        """
+        self.assertEqual(
+            Pool.Properties.CreationRedundancy.Get(self._pool_object),
+            (True, 0))
+        self.assertEqual(
+            Pool.Properties.CreationEncrypted.Get(self._pool_object),
+            (True, False))
+        self.assertEqual(
+            Pool.Properties.CreationVersion.Get(self._pool_object),
+            (True, Manager.Properties.Version.Get(self._proxy)))
+
+        (known, mda_size) = Pool.Properties.CreationMdaSize.Get(
+            self._pool_object)
+        self.assertTrue(known)
+        self.assertGreater(int(mda_size), 0)
+
+        (known, chunk_size) = Pool.Properties.CreationChunkSize.Get(
+            self._pool_object)
+        self.assertTrue(known)
+        self.assertGreater(int(chunk_size), 0)
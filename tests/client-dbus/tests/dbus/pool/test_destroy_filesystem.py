@@ -17,6 +17,7 @@ Test destroying a filesystem in a pool.
 
 import unittest
 
+from stratisd_client_dbus import Filesystem
 from stratisd_client_dbus import Manager
 from stratisd_client_dbus import ObjectManager
 from stratisd_client_dbus import Pool
@@ -47,10 +48,12 @@ class DestroyFSTestCase(unittest.TestCase):
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
         self._devs = _DEVICE_STRATEGY.example()
-        ((poolpath, _), _, _) = Manager.Methods.CreatePool(
+        ((poolpath, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': self._devs
             })
         self._pool_object = get_object(poolpath)
@@ -68,7 +71,7 @@ class DestroyFSTestCase(unittest.TestCase):
         list should always succeed, and it should not decrease the
         number of volumes.
         """
-        (result, rc, _) = Pool.Methods.DestroyFilesystems(
+        (result, rc, _, _) = Pool.Methods.DestroyFilesystems(
             self._pool_object, {'filesystems': []})
 
         self.assertEqual(len(result), 0)
@@ -83,7 +86,7 @@ class DestroyFSTestCase(unittest.TestCase):
         Test calling with a non-existant object path. This should succeed,
         because at the end the filesystem is not there.
         """
-        (result, rc, _) = Pool.Methods.DestroyFilesystems(
+        (result, rc, _, _) = Pool.Methods.DestroyFilesystems(
             self._pool_object, {'filesystems': ['/']})
         self.assertEqual(rc, StratisdErrors.OK)
         self.assertEqual(len(result), 0)
@@ -109,15 +112,21 @@ class DestroyFSTestCase1(unittest.TestCase):
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
         self._devs = _DEVICE_STRATEGY.example()
-        ((self._poolpath, _), _, _) = Manager.Methods.CreatePool(
+        ((self._poolpath, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': self._devs
             })
         self._pool_object = get_object(self._poolpath)
-        (self._filesystems, _, _) = Pool.Methods.CreateFilesystems(
-            self._pool_object, {'specs': [(self._VOLNAME, '', None)]})
+        (self._filesystems, _, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': [(self._VOLNAME, '', None)],
+                'size': (False, 0),
+                'guaranteed': False
+            })
         Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
 
     def tearDown(self):
@@ -132,7 +141,7 @@ class DestroyFSTestCase1(unittest.TestCase):
         should always succeed.
         """
         fs_object_path = self._filesystems[0][0]
-        (result, rc, _) = Pool.Methods.DestroyFilesystems(
+        (result, rc, _, _) = Pool.Methods.DestroyFilesystems(
             self._pool_object, {'filesystems': [fs_object_path]})
 
         self.assertEqual(len(result), 1)
@@ -149,7 +158,7 @@ class DestroyFSTestCase1(unittest.TestCase):
         returned.
         """
         fs_object_path = self._filesystems[0][0]
-        (result, rc, _) = Pool.Methods.DestroyFilesystems(
+        (result, rc, _, _) = Pool.Methods.DestroyFilesystems(
             self._pool_object, {'filesystems': [fs_object_path, "/"]})
 
         self.assertEqual(len(result), 1)
@@ -158,3 +167,32 @@ class DestroyFSTestCase1(unittest.TestCase):
         result = filesystems().search(
             ObjectManager.Methods.GetManagedObjects(self._proxy, {}))
         self.assertEqual(len([x for x in result]), 0)
+
+    def testDestroyOriginAndSnapshot(self):
+        """
+        Test calling with an origin before its snapshot. The snapshot
+        should be destroyed first, and both should be destroyed.
+        """
+        fs_object_path = self._filesystems[0][0]
+        (ss_object_path, _, _, _) = Pool.Methods.SnapshotFilesystem(
+            self._pool_object, {
+                'origin': fs_object_path,
+                'snapshot_name': 'snap',
+                'freeze': False
+            })
+        uuids = [
+            Filesystem.Properties.Uuid.Get(get_object(op))
+            for op in (ss_object_path, fs_object_path)
+        ]
+
+        (result, rc, _, _) = Pool.Methods.DestroyFilesystems(
+            self._pool_object,
+            {'filesystems': [fs_object_path, ss_object_path]})
+
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual([uuid for (uuid, _, _) in result], uuids)
+        self.assertTrue(all(destroyed for (_, destroyed, _) in result))
+
+        result = filesystems().search(
+            ObjectManager.Methods.GetManagedObjects(self._proxy, {}))
+        self.assertEqual(len([x for x in result]), 0)
@@ -0,0 +1,137 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test listing the space taken by the filesystems of a pool, the space that
+destroying each would reclaim, and reclaiming space in an emergency.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import Query
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class ListFilesystemSpaceTestCase(unittest.TestCase):
+    """
+    Set up a pool with a filesystem, a snapshot of it, and a snapshot of
+    the snapshot.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool = get_object(pool_object_path)
+        (created, _, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool, {
+                'specs': ['fs'],
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        self._paths = [created[0][0]]
+        for name in ['snap', 'snap_snap']:
+            (path, rc, _, _) = Pool.Methods.SnapshotFilesystem(
+                self._pool, {
+                    'origin': self._paths[-1],
+                    'snapshot_name': name,
+                    'freeze': False
+                })
+            self.assertEqual(rc, StratisdErrors.OK)
+            self._paths.append(path)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testListFilesystemSpace(self):
+        """
+        Every filesystem, including a snapshot of a snapshot, is listed,
+        with its exclusive and shared space in bytes.
+        """
+        (space, rc, _, _) = Query.Methods.ListFilesystemSpace(
+            self._proxy,
+            {'pool_uuid': Pool.Properties.Uuid.Get(self._pool)})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(
+            sorted(path for (path, _, _) in space), sorted(self._paths))
+        for (_, exclusive, shared) in space:
+            self.assertGreaterEqual(int(exclusive), 0)
+            self.assertGreaterEqual(int(shared), 0)
+
+    def testUnknownPool(self):
+        """
+        Listing the space of a pool that does not exist fails.
+        """
+        (space, rc, _, _) = Query.Methods.ListFilesystemSpace(
+            self._proxy, {'pool_uuid': 'not a uuid'})
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
+        self.assertEqual(space, [])
+
+    def testGetReclaimEstimates(self):
+        """
+        Every filesystem is listed, with those whose destruction would
+        reclaim the most space first.
+        """
+        (estimates, rc, _, _) = Pool.Methods.GetReclaimEstimates(
+            self._pool, {})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(
+            sorted(path for (path, _) in estimates), sorted(self._paths))
+        sizes = [int(size) for (_, size) in estimates]
+        self.assertEqual(sizes, sorted(sizes, reverse=True))
+
+    def testEmergencyReclaim(self):
+        """
+        By default, the simulator's filesystems use only a sliver of their
+        pool, so only a target of 0 destroys anything. Then every snapshot,
+        but not the filesystem, is destroyed.
+        """
+        (destroyed, rc, _, _) = Pool.Methods.EmergencyReclaim(
+            self._pool, {'target': 50})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(destroyed, [])
+
+        (destroyed, rc, _, _) = Pool.Methods.EmergencyReclaim(
+            self._pool, {'target': 0})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(sorted(destroyed), sorted(self._paths[1:]))
+
+        (destroyed, rc, _, _) = Pool.Methods.EmergencyReclaim(
+            self._pool, {'target': 101})
+        self.assertNotEqual(rc, StratisdErrors.OK)
@@ -0,0 +1,103 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test setting the limit on the number of filesystems of a pool.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class SetFsLimitTestCase(unittest.TestCase):
+    """
+    Set up a pool with the default limit.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool_object = get_object(self._pool_object_path)
+        Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _create(self, specs):
+        """
+        Create filesystems with the given names.
+
+        :returns: the return code of the call
+        """
+        (_, rc, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': specs,
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        return rc
+
+    def testLimit(self):
+        """
+        Filesystems are created up to the limit and no further, and the
+        limit can not be set below the number of filesystems.
+        """
+        self.assertEqual(Pool.Properties.FsLimit.Get(self._pool_object), 100)
+        self.assertEqual(
+            Pool.Properties.FilesystemCount.Get(self._pool_object), 0)
+
+        (changed, rc, _, _) = Pool.Methods.SetFsLimit(self._pool_object,
+                                                      {'limit': 2})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(Pool.Properties.FsLimit.Get(self._pool_object), 2)
+
+        self.assertEqual(self._create(['one', 'two', 'three']),
+                         StratisdErrors.ERROR)
+        self.assertEqual(self._create(['one', 'two']), StratisdErrors.OK)
+        self.assertEqual(
+            Pool.Properties.FilesystemCount.Get(self._pool_object), 2)
+        self.assertEqual(self._create(['three']), StratisdErrors.ERROR)
+
+        (_, rc, _, _) = Pool.Methods.SetFsLimit(self._pool_object,
+                                                {'limit': 1})
+        self.assertEqual(rc, StratisdErrors.ERROR)
+        self.assertEqual(Pool.Properties.FsLimit.Get(self._pool_object), 2)
@@ -0,0 +1,99 @@
+# Copyright 2018 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test planning the addition of blockdevs to a pool.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import ObjectManager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import blockdevs
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+_DEVICES = ['/dev/one', '/dev/two']
+
+
+class PlanAddDataDevsTestCase(unittest.TestCase):
+    """
+    Test planning, and applying a plan, to add devices to a pool which is
+    initially empty.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((self._poolpath, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': []
+            })
+        self._pool_object = get_object(self._poolpath)
+        Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testPlanDoesNotAdd(self):
+        """
+        Making a plan should describe the new devices, but add none of them.
+        """
+        ((_, _, layout, _), rc, _, _) = Pool.Methods.PlanAddDataDevs(
+            self._pool_object, {'devices': _DEVICES})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(
+            sorted(devnode for (devnode, _, _, _, _) in layout), _DEVICES)
+
+        managed_objects = \
+           ObjectManager.Methods.GetManagedObjects(self._proxy, {})
+        blockdevs1 = blockdevs(props={
+            'Pool': self._poolpath
+        }).search(managed_objects)
+        self.assertEqual(list(blockdevs1), [])
+
+    def testApplyPlan(self):
+        """
+        Applying a plan should add the planned devices, and a plan should
+        only be applied once.
+        """
+        ((token, _, _, _), _, _, _) = Pool.Methods.PlanAddDataDevs(
+            self._pool_object, {'devices': _DEVICES})
+
+        (result, rc, _, _) = Pool.Methods.ApplyAddDataDevsPlan(
+            self._pool_object, {'plan': token})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(len(result), len(_DEVICES))
+
+        (result, rc, _, _) = Pool.Methods.ApplyAddDataDevsPlan(
+            self._pool_object, {'plan': token})
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
+        self.assertEqual(result, [])
@@ -0,0 +1,107 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test removing blockdevs from a pool.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import ObjectManager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import blockdevs
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import Service
+
+_DEVICES = ['/dev/one', '/dev/two', '/dev/three']
+
+
+class RemoveBlockDevsTestCase(unittest.TestCase):
+    """
+    Set up a pool with several data devices.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((self._pool_object_path, self._devpaths), _, _,
+         _) = Manager.Methods.CreatePool(
+             self._proxy, {
+                 'name': self._POOLNAME,
+                 'redundancy': (True, 0),
+                 'size_policy': (False, 0),
+                 'signature_policy': (False, 0),
+                 'devices': _DEVICES
+             })
+        self._pool_object = get_object(self._pool_object_path)
+        Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _blockdev_count(self):
+        """
+        The number of blockdevs of the pool.
+        """
+        managed_objects = \
+            ObjectManager.Methods.GetManagedObjects(self._proxy, {})
+        return len(
+            list(
+                blockdevs(props={
+                    'Pool': self._pool_object_path
+                }).search(managed_objects)))
+
+    def testRemoveOne(self):
+        """
+        Removing a blockdev removes its object, and leaves the others.
+        """
+        (removed, rc, _, _) = Pool.Methods.RemoveBlockDevs(
+            self._pool_object, {'blockdevs': self._devpaths[:1]})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertEqual(removed, self._devpaths[:1])
+        self.assertEqual(self._blockdev_count(), len(_DEVICES) - 1)
+
+    def testRemoveAll(self):
+        """
+        Removing every blockdev of the data tier fails, and removes none.
+        """
+        (removed, rc, _, _) = Pool.Methods.RemoveBlockDevs(
+            self._pool_object, {'blockdevs': self._devpaths})
+        self.assertEqual(rc, StratisdErrors.ERROR)
+        self.assertEqual(removed, [])
+        self.assertEqual(self._blockdev_count(), len(_DEVICES))
+
+    def testRemoveUnknown(self):
+        """
+        Removing an object that is not a blockdev of the pool fails, and
+        removes none.
+        """
+        (removed, rc, _, _) = Pool.Methods.RemoveBlockDevs(
+            self._pool_object, {'blockdevs': [self._pool_object_path]})
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
+        self.assertEqual(removed, [])
+        self.assertEqual(self._blockdev_count(), len(_DEVICES))
@@ -46,10 +46,12 @@ class SetNameTestCase(unittest.TestCase):
         self._service = Service()
         self._service.setUp()
         self._proxy = get_object(TOP_OBJECT)
-        ((self._pool_object_path, _), _, _) = Manager.Methods.CreatePool(
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
             self._proxy, {
                 'name': self._POOLNAME,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': _DEVICE_STRATEGY.example()
             })
         self._pool_object = get_object(self._pool_object_path)
@@ -65,7 +67,7 @@ class SetNameTestCase(unittest.TestCase):
         """
         Test rename to same name.
         """
-        (result, rc, _) = Pool.Methods.SetName(self._pool_object,
+        (result, rc, _, _) = Pool.Methods.SetName(self._pool_object,
                                                {'name': self._POOLNAME})
 
         self.assertEqual(rc, StratisdErrors.OK)
@@ -88,7 +90,7 @@ class SetNameTestCase(unittest.TestCase):
         """
         new_name = "new"
 
-        (result, rc, _) = Pool.Methods.SetName(self._pool_object,
+        (result, rc, _, _) = Pool.Methods.SetName(self._pool_object,
                                                {'name': new_name})
 
         self.assertTrue(result)
@@ -0,0 +1,119 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test setting the snapshot schedule of a filesystem.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class SetSnapshotScheduleTestCase(unittest.TestCase):
+    """
+    Set up a pool with one filesystem.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool_object = get_object(pool_object_path)
+        Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
+        (fs_objects, _, _, _) = Pool.Methods.CreateFilesystems(
+            self._pool_object, {
+                'specs': ['home'],
+                'size': (False, 0),
+                'guaranteed': False
+            })
+        self._fs_object_path = fs_objects[0][0]
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def _set(self, filesystem, schedule):
+        """
+        Set the snapshot schedule of the filesystem.
+
+        :returns: whether the schedule was changed, and the return code
+        """
+        (changed, rc, _, _) = Pool.Methods.SetSnapshotSchedule(
+            self._pool_object, {
+                'filesystem': filesystem,
+                'schedule': schedule
+            })
+        return (changed, rc)
+
+    def testSetSchedule(self):
+        """
+        A schedule is changed only if it differs, and can be removed.
+        """
+        self.assertEqual(
+            self._set(self._fs_object_path, (True, (0, 24))),
+            (True, StratisdErrors.OK))
+        self.assertEqual(
+            self._set(self._fs_object_path, (True, (0, 24))),
+            (False, StratisdErrors.OK))
+        self.assertEqual(
+            self._set(self._fs_object_path, (True, (1, 7))),
+            (True, StratisdErrors.OK))
+        self.assertEqual(
+            self._set(self._fs_object_path, (False, (0, 0))),
+            (True, StratisdErrors.OK))
+        self.assertEqual(
+            self._set(self._fs_object_path, (False, (0, 0))),
+            (False, StratisdErrors.OK))
+
+    def testInvalidSchedule(self):
+        """
+        A schedule with an unknown interval or that keeps no snapshots is
+        refused.
+        """
+        (_, rc) = self._set(self._fs_object_path, (True, (2, 24)))
+        self.assertEqual(rc, StratisdErrors.ERROR)
+        (_, rc) = self._set(self._fs_object_path, (True, (0, 0)))
+        self.assertEqual(rc, StratisdErrors.ERROR)
+
+    def testUnknownFilesystem(self):
+        """
+        A schedule for an object that is not a filesystem is refused.
+        """
+        (_, rc) = self._set('/this/is/not/an/object/path', (True, (0, 24)))
+        self.assertEqual(rc, StratisdErrors.NOT_FOUND)
@@ -0,0 +1,123 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test the properties that name the states of pools and blockdevs.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import MOBlockDev
+from stratisd_client_dbus import ObjectManager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import blockdevs
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(1)
+
+_POOL_STATES = {
+    1: 'initializing',
+    2: 'running',
+    3: 'read-only',
+    4: 'out-of-data-space',
+    5: 'failed',
+    6: 'stopping',
+    7: 'needs-check',
+}
+
+_EXTEND_STATES = {
+    1: 'initializing',
+    2: 'good',
+    3: 'data-failed',
+    4: 'meta-failed',
+    5: 'meta-and-data-failed',
+}
+
+_SPACE_STATES = {1: 'good', 2: 'warn', 3: 'crit'}
+
+_BLOCKDEV_STATES = {
+    0: 'missing',
+    1: 'bad',
+    2: 'spare',
+    3: 'not-in-use',
+    4: 'in-use',
+}
+
+_TIERS = {0: 'data', 1: 'cache'}
+
+
+class StateNamesTestCase(unittest.TestCase):
+    """
+    Test that each state property is named by its paired name property.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testPoolStateNames(self):
+        """
+        Each state of the pool is named as its number says.
+        """
+        pool = get_object(self._pool_object_path)
+        self.assertEqual(
+            Pool.Properties.StateName.Get(pool),
+            _POOL_STATES[Pool.Properties.State.Get(pool)])
+        self.assertEqual(
+            Pool.Properties.ExtendStateName.Get(pool),
+            _EXTEND_STATES[Pool.Properties.ExtendState.Get(pool)])
+        self.assertEqual(
+            Pool.Properties.SpaceStateName.Get(pool),
+            _SPACE_STATES[Pool.Properties.SpaceState.Get(pool)])
+
+    def testBlockdevStateNames(self):
+        """
+        The state and tier of each blockdev are named as their numbers say.
+        """
+        managed_objects = ObjectManager.Methods.GetManagedObjects(
+            self._proxy, {})
+        found = list(blockdevs(props={
+            'Pool': self._pool_object_path
+        }).search(managed_objects))
+        self.assertNotEqual(found, [])
+        for (_, info) in found:
+            blockdev = MOBlockDev(info)
+            self.assertEqual(blockdev.StateName(),
+                             _BLOCKDEV_STATES[blockdev.State()])
+            self.assertEqual(blockdev.TierName(), _TIERS[blockdev.Tier()])
@@ -63,10 +63,12 @@ class UdevAdd(unittest.TestCase):
         :param devices:  Devices to use for pool
         :return: Dbus proxy object representing pool.
         """
-        ((pool_object_path, _), _, _) = Manager.Methods.CreatePool(
+        ((pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
             get_object(TOP_OBJECT), {
                 'name': name,
                 'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
                 'devices': devices
             })
         return get_object(pool_object_path)
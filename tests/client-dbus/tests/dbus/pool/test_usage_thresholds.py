@@ -0,0 +1,96 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test setting the usage thresholds of a pool.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class SetUsageThresholdsTestCase(unittest.TestCase):
+    """
+    Set up a pool with the default usage thresholds.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool_object = get_object(self._pool_object_path)
+        Manager.Methods.ConfigureSimulator(self._proxy, {'denominator': 8})
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testSet(self):
+        """
+        Setting the thresholds changes them only the first time, and they
+        are reported in ascending order.
+        """
+        self.assertEqual(
+            list(Pool.Properties.UsageThresholds.Get(self._pool_object)),
+            [70, 85, 95])
+
+        (changed, rc, _, _) = Pool.Methods.SetUsageThresholds(
+            self._pool_object, {'thresholds': [90, 50]})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(
+            list(Pool.Properties.UsageThresholds.Get(self._pool_object)),
+            [50, 90])
+
+        (changed, rc, _, _) = Pool.Methods.SetUsageThresholds(
+            self._pool_object, {'thresholds': [50, 90]})
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(changed)
+
+    def testSetInvalid(self):
+        """
+        A threshold that is not a percentage is refused.
+        """
+        (changed, rc, _, _) = Pool.Methods.SetUsageThresholds(
+            self._pool_object, {'thresholds': [50, 150]})
+        self.assertNotEqual(rc, StratisdErrors.OK)
+        self.assertFalse(changed)
+        self.assertEqual(
+            list(Pool.Properties.UsageThresholds.Get(self._pool_object)),
+            [70, 85, 95])
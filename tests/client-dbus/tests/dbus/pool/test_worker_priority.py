@@ -0,0 +1,119 @@
+# Copyright 2016 Red Hat, Inc.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+"""
+Test setting the priority of the workers doing a pool's background work.
+"""
+
+import unittest
+
+from stratisd_client_dbus import Manager
+from stratisd_client_dbus import Pool
+from stratisd_client_dbus import StratisdErrors
+from stratisd_client_dbus import get_object
+
+from stratisd_client_dbus._constants import TOP_OBJECT
+
+from .._misc import _device_list
+from .._misc import Service
+
+_DEVICE_STRATEGY = _device_list(0)
+
+
+class SetWorkerPriorityTestCase(unittest.TestCase):
+    """
+    Set up a pool with the default worker priority.
+    """
+
+    _POOLNAME = 'deadpool'
+
+    def setUp(self):
+        """
+        Start the stratisd daemon with the simulator.
+        """
+        self._service = Service()
+        self._service.setUp()
+        self._proxy = get_object(TOP_OBJECT)
+        ((self._pool_object_path, _), _, _, _) = Manager.Methods.CreatePool(
+            self._proxy, {
+                'name': self._POOLNAME,
+                'redundancy': (True, 0),
+                'size_policy': (False, 0),
+                'signature_policy': (False, 0),
+                'devices': _DEVICE_STRATEGY.example()
+            })
+        self._pool_object = get_object(self._pool_object_path)
+
+    def tearDown(self):
+        """
+        Stop the stratisd simulator and daemon.
+        """
+        self._service.tearDown()
+
+    def testSetAndReset(self):
+        """
+        Setting the priority changes it only the first time, the idle class
+        has no level, and the default priority can be set again.
+        """
+        self.assertEqual(
+            Pool.Properties.WorkerPriority.Get(self._pool_object), (0, 0, 4))
+
+        (changed, rc, _, _) = Pool.Methods.SetWorkerPriority(
+            self._pool_object, {
+                'niceness': 19,
+                'io_class': (True, 1),
+                'io_level': 7
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(
+            Pool.Properties.WorkerPriority.Get(self._pool_object), (19, 1, 0))
+
+        (changed, rc, _, _) = Pool.Methods.SetWorkerPriority(
+            self._pool_object, {
+                'niceness': 19,
+                'io_class': (True, 1),
+                'io_level': 0
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertFalse(changed)
+
+        (changed, rc, _, _) = Pool.Methods.SetWorkerPriority(
+            self._pool_object, {
+                'niceness': 0,
+                'io_class': (False, 0),
+                'io_level': 4
+            })
+        self.assertEqual(rc, StratisdErrors.OK)
+        self.assertTrue(changed)
+        self.assertEqual(
+            Pool.Properties.WorkerPriority.Get(self._pool_object), (0, 0, 4))
+
+    def testOutOfRange(self):
+        """
+        A niceness, class, or level out of range is an error, and leaves the
+        priority as it was.
+        """
+        for (niceness, io_class, io_level) in [(-21, (False, 0), 4),
+                                               (0, (True, 2), 4),
+                                               (0, (False, 0), 8)]:
+            (changed, rc, _, _) = Pool.Methods.SetWorkerPriority(
+                self._pool_object, {
+                    'niceness': niceness,
+                    'io_class': io_class,
+                    'io_level': io_level
+                })
+            self.assertNotEqual(rc, StratisdErrors.OK)
+            self.assertFalse(changed)
+        self.assertEqual(
+            Pool.Properties.WorkerPriority.Get(self._pool_object), (0, 0, 4))